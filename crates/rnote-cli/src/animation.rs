@@ -0,0 +1,76 @@
+// Imports
+use crate::{cli, validators};
+use anyhow::Context;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use rnote_engine::engine::{EngineSnapshot, ExportOptions};
+use rnote_engine::Engine;
+use std::path::Path;
+
+/// Exports an animated Gif that draws the strokes of the rnote file one after another, in the order they were created.
+///
+/// The last frame is shown for `hold_frames` additional frames before the animation loops.
+pub(crate) async fn run_export_animation(
+    rnote_file: &Path,
+    output_file: &Path,
+    fps: u32,
+    hold_frames: u32,
+    no_background: bool,
+    bitmap_scalefactor: f64,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::file_has_ext(output_file, "gif")?;
+    if !bitmap_scalefactor.is_finite() || bitmap_scalefactor <= 0.0 {
+        return Err(anyhow::anyhow!("The bitmap scale-factor must be positive."));
+    }
+    cli::confirm_overwrite(output_file, "Export")?;
+
+    let mut engine = Engine::default();
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let engine_snapshot = EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?;
+    let _ = engine.load_snapshot(engine_snapshot);
+
+    let progressbar = cli::new_progressbar(format!(
+        "Exporting \"{}\" to: \"{}\"",
+        rnote_file.display(),
+        output_file.display()
+    ));
+    let frame_delay = Delay::from_numer_denom_ms(1000, fps);
+    let mut gif_bytes = Vec::new();
+    let frames_amount = {
+        let mut encoder = GifEncoder::new(&mut gif_bytes);
+        encoder.set_repeat(Repeat::Infinite)?;
+        // Every frame is encoded when the next one arrives, because the last frame gets a longer delay
+        let mut pending_frame = None;
+        let frames_amount = engine.export_playback_frames(
+            !no_background,
+            !no_background,
+            bitmap_scalefactor,
+            &ExportOptions::default(),
+            |frame| {
+                if let Some(pending_frame) = pending_frame.replace(frame.into_imgbuf()?) {
+                    encoder.encode_frame(Frame::from_parts(pending_frame, 0, 0, frame_delay))?;
+                }
+                Ok(())
+            },
+        )?;
+        if let Some(last_frame) = pending_frame {
+            let hold_delay = Delay::from_numer_denom_ms(1000 * (hold_frames + 1), fps);
+            encoder.encode_frame(Frame::from_parts(last_frame, 0, 0, hold_delay))?;
+        }
+        frames_amount
+    };
+    cli::create_overwrite_file_w_bytes(output_file, &gif_bytes, None)
+        .await
+        .with_context(|| format!("Writing \"{}\" failed.", output_file.display()))?;
+
+    let finish_msg = format!(
+        "Exported {frames_amount} frame(s) to \"{}\".",
+        output_file.display()
+    );
+    if progressbar.is_hidden() {
+        println!("{finish_msg}");
+    }
+    progressbar.finish_with_message(finish_msg);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+// Imports
+use crate::{cli, validators};
+use rnote_compose::ext::AabbExt;
+use rnote_compose::shapes::Shapeable;
+use rnote_engine::engine::EngineSnapshot;
+use std::path::{Path, PathBuf};
+
+pub(crate) async fn run_check(rnote_files: &[PathBuf], check_bounds: bool) -> anyhow::Result<()> {
+    if rnote_files.is_empty() {
+        return Err(anyhow::anyhow!(
+            "There must be at least one rnote file specified for checking."
+        ));
+    }
+    let mut failed_files = 0;
+
+    for rnote_file in rnote_files.iter() {
+        match check_file(rnote_file, check_bounds).await {
+            Ok(()) => println!("PASS \"{}\"", rnote_file.display()),
+            Err(e) => {
+                failed_files += 1;
+                println!("FAIL \"{}\": {e:#}", rnote_file.display());
+            }
+        }
+    }
+
+    println!(
+        "{} passed, {failed_files} failed.",
+        rnote_files.len() - failed_files
+    );
+    if failed_files > 0 {
+        return Err(anyhow::anyhow!(
+            "{failed_files} of {} file(s) failed the check.",
+            rnote_files.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks if the file loads, without loading it into an engine, rendering or exporting it.
+async fn check_file(rnote_file: &Path, check_bounds: bool) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let snapshot = EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?;
+    if check_bounds {
+        check_strokes_bounds(&snapshot)?;
+    }
+    Ok(())
+}
+
+fn check_strokes_bounds(snapshot: &EngineSnapshot) -> anyhow::Result<()> {
+    for (i, stroke) in snapshot.stroke_components.values().enumerate() {
+        let bounds = stroke.bounds();
+        if !bounds
+            .mins
+            .iter()
+            .chain(bounds.maxs.iter())
+            .all(|v| v.is_finite())
+        {
+            return Err(anyhow::anyhow!(
+                "Stroke {i} has non-finite bounds `{bounds:?}`."
+            ));
+        }
+        bounds
+            .assert_valid()
+            .map_err(|e| e.context(format!("Stroke {i} has invalid bounds.")))?;
+    }
+    Ok(())
+}
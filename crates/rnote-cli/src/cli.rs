@@ -1,17 +1,29 @@
 // Imports
-use crate::{export, import, test};
+use crate::{
+    animation, check, diff, export, extract_images, import, inspect, migrate, new, profile, relink,
+    reorder, repair, self_test, stickers, test, transform,
+};
 use anyhow::Context;
-use clap::Parser;
-use rnote_compose::SplitOrder;
+use clap::{CommandFactory, FromArgMatches};
+use rnote_compose::{Color, SplitOrder};
+use rnote_engine::document::format::PredefinedFormat;
 use rnote_engine::engine::export::{
-    DocExportFormat, DocPagesExportFormat, DocPagesExportPrefs, SelectionExportFormat,
-    SelectionExportPrefs,
+    BackgroundImageFit, DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs,
+    ExportCenterContent, ExportFlip, ExportHeaderFooter, ExportNup, ExportOrientation,
+    ExportPageBadge, ExportPageBadgeCorner, ExportPrefs, ExportRotation, OnStrokeError,
+    SelectionExportFormat, SelectionExportPrefs, StrokeSmoothing, SvgOrigin, SvgUnits,
+};
+use rnote_engine::engine::import::{
+    PdfImportFormFields, PdfImportLayout, PdfImportPadTo, PdfImportPrefs, XoppImportPrefs,
 };
-use rnote_engine::engine::import::XoppImportPrefs;
+use rnote_engine::render;
+use rnote_engine::strokes::textstroke::TextStyle;
 use rnote_engine::SelectionCollision;
 use smol::fs::File;
 use smol::io::{AsyncReadExt, AsyncWriteExt};
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 ///    rnote-cli{n}{n}
@@ -21,40 +33,337 @@ use std::time::Duration;
 #[derive(clap::Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None, arg_required_else_help = true)]
 pub(crate) struct Cli {
+    /// Answer all confirmation prompts with yes, e.g. for overwriting existing files.{n}
+    /// Without it, prompts fail when the terminal is not interactive.
+    #[arg(short = 'y', long = "yes", visible_alias = "assume-yes", action = clap::ArgAction::SetTrue, global = true)]
+    pub(crate) assume_yes: bool,
+    /// Disable all colors and text styling in the messages and progress bars, e.g. for logs.{n}
+    /// Also disabled when the "NO_COLOR" environment variable is set.
+    #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+    pub(crate) no_color: bool,
+    /// Print additional details about the processed files.
+    #[arg(short = 'v', long, action = clap::ArgAction::SetTrue, global = true)]
+    pub(crate) verbose: bool,
     #[command(subcommand)]
     pub(crate) command: Command,
 }
 
 #[derive(clap::Subcommand, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum Command {
     /// Tests if the specified files can be opened and are valid rnote files.
     Test {
         /// The rnote files.
         rnote_files: Vec<PathBuf>,
     },
+    /// Checks if the specified rnote files load, without rendering or exporting them.{n}
+    /// Prints a pass or fail line for every file and a final tally. Exits with an error if any file fails.
+    Check {
+        /// The rnote files.
+        rnote_files: Vec<PathBuf>,
+        /// Additionally check that the bounds of all strokes are valid.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        bounds: bool,
+    },
+    /// Renders a small built-in document, exports it to every supported format and checks that the outputs{n}
+    /// are valid. Prints a pass or fail line for every format and exits with an error if any format fails.{n}
+    /// Needs no input files, e.g. to confirm that the installation works.
+    SelfTest,
+    /// Compares the strokes of two rnote files and reports the added, removed and modified strokes.{n}
+    /// Strokes are identified by their key and content, and listed with a hash of their content and their bounds.
+    Diff {
+        /// The old rnote file.
+        old_rnote_file: PathBuf,
+        /// The new rnote file.
+        new_rnote_file: PathBuf,
+        /// Render a visual diff into this Png file.{n}
+        /// Added strokes are green, removed strokes red, modified strokes orange and unchanged strokes gray.
+        #[arg(long, value_name = "FILE")]
+        image: Option<PathBuf>,
+    },
+    /// Inspects the specified rnote files and reports their format version and stroke count.{n}
+    /// Reports where loading fails for files that can't be opened.
+    Inspect {
+        /// The rnote files.
+        rnote_files: Vec<PathBuf>,
+        /// Report detailed diagnostics: the compressed and decompressed sizes{n}
+        /// and the full error where loading fails, including the failing field or stroke index.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        debug: bool,
+        /// List the ids and types of the strokes in the order they are rendered, e.g. for "export --stroke-ids".
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        strokes: bool,
+    },
     /// Imports the specified input file and saves it as a rnote save file.{n}
-    /// Currently only `.xopp` files can be imported.
+    /// Currently only `.xopp`, `.pdf`, `.epub`, `.goodnotes`, `.wmf`, `.emf`, `.csv`, plain text and Markdown files can be imported.{n}
+    /// Pdf pages are imported progressively as bitmap images.{n}
+    /// The text of Epub files is laid out on pages that are imported like Pdf pages.{n}
+    /// Of GoodNotes archives the page images and the ink stored as Svg are imported, other entries are skipped.{n}
+    /// Multiple Pdf files can be imported at once, their pages are appended one after another.
     Import {
         /// The rnote save file.
         rnote_file: PathBuf,
-        /// The import input file.
-        #[arg(short = 'i', long)]
-        input_file: PathBuf,
+        /// The import input file. Can be repeated to merge multiple Pdf files into one document.
+        #[arg(short = 'i', long = "input-file", required = true)]
+        input_files: Vec<PathBuf>,
         /// When importing a .xopp file, the import dpi can be specified.
         #[arg(long, default_value_t = XoppImportPrefs::default().dpi)]
         xopp_dpi: f64,
+        /// Force the format of the input file(s) instead of recognizing it.{n}
+        /// Without it, the format is recognized from the file content and then from the file extension.
+        #[arg(long, value_name = "FORMAT", conflicts_with_all = ["import_csv", "import_text"])]
+        input_format: Option<InputFormat>,
+        /// Pad the imported Pdf pages with white margins, so that all pages have the same size.{n}
+        /// "widest" pads to the width of the widest and the height of the tallest page,{n}
+        /// "format" pads to the aspect ratio of the document format. The page content is centered.
+        #[arg(long, default_value_t = Default::default())]
+        pdf_pad_to: PdfImportPadTo,
+        /// How the imported Pdf pages are laid out.{n}
+        /// "vertical" places them one below the other and adjusts the document format to the pages,{n}
+        /// "grid" keeps the document format and tiles the pages next to each other onto its pages,{n}
+        /// shrinking them so that at least two fit next to each other.
+        #[arg(long, value_name = "LAYOUT", default_value_t = Default::default())]
+        pdf_layout: PdfImportLayout,
+        /// Whether the form fields of imported Pdf files are rendered with the values they are filled in with,{n}
+        /// or cleared to import the empty form.
+        #[arg(long, value_name = "FIELDS", default_value_t = Default::default())]
+        pdf_form_fields: PdfImportFormFields,
+        /// Lift near-white pixels with all color channels at or above this value (0 - 255) to pure white,{n}
+        /// removing faint bleed-through from the back of scanned pages and scanner noise.{n}
+        /// Only supported when importing Pdf files or bitmap images. Pdf pages are then imported as bitmaps.
+        #[arg(long, value_name = "VALUE")]
+        whiten_threshold: Option<u8>,
+        /// Remove faint bleed-through and scanner noise, a shorthand for "--whiten-threshold"{n}
+        /// with a threshold that keeps light strokes.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "whiten_threshold")]
+        despeckle: bool,
+        /// Straighten slightly rotated scanned pages, by detecting the angle of the text lines or ruling{n}
+        /// on every page and rotating it back by up to 5 degrees. The angles are printed with "--verbose".{n}
+        /// Only supported when importing Pdf files or bitmap images. Pdf pages are then imported as bitmaps.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        deskew: bool,
+        /// Make the background of imported bitmap images transparent, so that they composite over the background{n}
+        /// of the note, e.g. the paper of photographed handwriting. "white" removes near-white pixels,{n}
+        /// "sampled" removes pixels near the color sampled from the image border. Only supported for bitmap images.
+        #[arg(long, value_name = "COLOR")]
+        remove_background: Option<render::BackgroundRemoval>,
+        /// How much (0 - 255) every color channel of a pixel may differ from the background color to be removed.
+        #[arg(long, value_name = "VALUE", default_value_t = render::BackgroundRemoval::THRESHOLD_DEFAULT, requires = "remove_background")]
+        remove_background_threshold: u8,
+        /// Import the decodable part of truncated Png images, e.g. partially downloaded files,{n}
+        /// with the missing rows left transparent. Without it truncated images fail to import.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        allow_partial_images: bool,
+        /// Invert the colors of imported bitmap images, keeping their transparency,{n}
+        /// e.g. to turn photographed chalkboards into dark writing on a light background.{n}
+        /// Applied before "--whiten-threshold" and "--remove-background". Only supported for bitmap images.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        invert_colors: bool,
+        /// Import the input file as Csv data with numeric x,y pairs in the first two columns{n}
+        /// and draw it as a line chart onto the first page.{n}
+        /// Files with the `.csv` extension are always imported this way.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        import_csv: bool,
+        /// Draw the x and y axes when importing Csv data.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        csv_axes: bool,
+        /// Import the input file as UTF-8 plain text, wrapped to the page width.{n}
+        /// Text that doesn't fit on a page continues on additional pages.{n}
+        /// Files with the `.txt` extension are always imported this way.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "import_csv")]
+        import_text: bool,
+        /// Import the input file as Markdown, laid out like plain text with the formatting rendered:{n}
+        /// headings are enlarged and bold, emphasis is italic, strong emphasis bold and lists are indented.{n}
+        /// Tables and images are imported as plain text with a warning.{n}
+        /// Files with the `.md` extension are always imported this way.
+        #[cfg(feature = "markdown")]
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["input_format", "import_csv", "import_text"])]
+        import_markdown: bool,
+        /// The font family of imported plain text and Markdown.
+        #[arg(long, default_value_t = String::from(TextStyle::FONT_FAMILY_DEFAULT))]
+        font: String,
+        /// The font size of imported plain text and Markdown. Headings are scaled relative to it.
+        #[arg(long, default_value_t = TextStyle::FONT_SIZE_DEFAULT, value_parser = parse_font_size)]
+        font_size: f64,
+        /// Skip Pdf pages that are identical to an already imported page, e.g. repeated blank pages,{n}
+        /// and report how many were removed. The pages are compared by their pixels, so they are imported{n}
+        /// as bitmap images. Only supported when importing Pdf files.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        dedupe_pages: bool,
+        /// Insert a table of contents as the first page(s), listing the imported Pdf files{n}
+        /// with the document pages they start on. The imported pages are moved below it.{n}
+        /// Only supported when importing Pdf files.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        generate_toc: bool,
+        /// Run the import in memory and report the amount of pages and strokes and the document dimensions{n}
+        /// that it would produce, without writing the rnote file.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        validate_only: bool,
+        /// Before importing, check that every Png and Jpeg input file can be decoded by reading its header,{n}
+        /// and report all files that are corrupt. The import is not started when any of them is.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        validate_images: bool,
+        /// Set the permissions of the created rnote file to this octal mode, e.g. "640".{n}
+        /// Only supported on Unix.
+        #[arg(long, value_name = "MODE", value_parser = parse_file_mode)]
+        chmod: Option<u32>,
+    },
+    /// Creates a rnote file with blank pages of the given format and background, e.g. to scaffold a template.
+    New {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The amount of pages.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        pages: u32,
+        /// The paper size of the pages: "a2", "a3", "a4", "a5", "a6", "letter" or "legal".
+        #[arg(long, value_name = "SIZE", default_value = "a4", value_parser = parse_paper_size)]
+        format: PredefinedFormat,
+        /// Lay out the pages in landscape instead of portrait orientation.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        landscape: bool,
+        /// The background color of the pages, e.g. "#fafafa".
+        #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+        background: Option<Color>,
+        /// Create the pages without the background pattern, only with the background color.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_pattern: bool,
+    },
+    /// Migrates a rnote file saved with an older version to the newest file format{n}
+    /// and reports the applied migration steps.
+    Migrate {
+        /// The rnote file to migrate.
+        rnote_file: PathBuf,
+        /// The migrated rnote file. Can be the same as the input file to migrate it in place.
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
+    },
+    /// Rewrites the references to external files of the images embedded in a rnote file{n}
+    /// and reports the images that are not self-contained.
+    Relink {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The rewritten rnote file. Can be the same as the input file to rewrite it in place.
+        #[arg(short = 'o', long, required_unless_present = "check")]
+        output_file: Option<PathBuf>,
+        /// Replace the beginning of references starting with OLD by NEW, e.g. "/home/old/images=../images".{n}
+        /// Can be repeated, the first matching mapping is applied.
+        #[arg(long = "map", value_name = "OLD=NEW", value_parser = parse_relink_map)]
+        maps: Vec<(String, String)>,
+        /// Embed the referenced local image files as inline data after rewriting the references,{n}
+        /// making the rnote file self-contained. Relative references are resolved against the directory of the rnote file.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        inline: bool,
+        /// Only report the images that are not self-contained, without writing a file.{n}
+        /// Fails when there are any.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["output_file", "maps", "inline"])]
+        check: bool,
+    },
+    /// Rotates and scales the images or all strokes of a rnote file, e.g. a stack of imported photos{n}
+    /// that all need the same rotation. At least one transformation needs to be specified.
+    Transform {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The transformed rnote file. Can be the same as the input file to transform it in place.
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
+        /// Rotate every bitmap image clockwise by this angle in degrees.
+        #[arg(
+            long,
+            value_name = "DEGREES",
+            allow_hyphen_values = true,
+            conflicts_with = "rotate_all"
+        )]
+        rotate_images: Option<f64>,
+        /// Rotate every stroke clockwise by this angle in degrees, including the images.
+        #[arg(long, value_name = "DEGREES", allow_hyphen_values = true)]
+        rotate_all: Option<f64>,
+        /// Scale every bitmap image by this factor. Applied after the rotation.
+        #[arg(long, value_name = "FACTOR", value_parser = parse_scale_factor)]
+        scale_images: Option<f64>,
+        /// The point every stroke is rotated and scaled about. "center" is the center of its bounds,{n}
+        /// "origin" the top-left corner of the document.
+        #[arg(long, value_enum, default_value_t = Default::default())]
+        about: TransformAbout,
+    },
+    /// Moves the bitmap images of a rnote file behind or in front of the other strokes on their layer,{n}
+    /// e.g. when imported images cover other imported images. The layers are kept, so images stay{n}
+    /// in front of Pdf pages and behind the ink. The order within the images and within the other strokes is preserved.
+    Reorder {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The reordered rnote file. Can be the same as the input file to reorder it in place.
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
+        /// Move the bitmap images behind the other strokes on their layer.
+        #[arg(long, action = clap::ArgAction::SetTrue, required_unless_present = "images_to_front", conflicts_with = "images_to_front")]
+        images_to_back: bool,
+        /// Move the bitmap images in front of the other strokes on their layer.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        images_to_front: bool,
+    },
+    /// Converts the bitmap images of a rnote file to the memory format that can be drawn directly,{n}
+    /// repairing older files whose images fail to render or export, and reports the converted images.
+    Repair {
+        /// The rnote file to repair.
+        rnote_file: PathBuf,
+        /// The repaired rnote file. Can be the same as the input file to repair it in place.
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
     },
     /// Exports the Rnote file(s) and saves it/them in the desired format.{n}
     /// See sub-commands for usage.
     Export {
         #[command(subcommand)]
         export_command: ExportCommand,
-        /// The rnote save file.
+        /// The rnote save file(s).{n}
+        /// Directories are expanded to the rnote files they contain, other files in them are ignored.
         #[arg(global = true)]
         rnote_files: Vec<PathBuf>,
+        /// Also expand to the rnote files in the subdirectories of the given directories.
+        #[arg(short = 'r', long, action = clap::ArgAction::SetTrue, global = true)]
+        recursive: bool,
+        /// The order in which the rnote files are exported.{n}
+        /// Applies to all files after the directories are expanded, so it replaces both the order of the given paths{n}
+        /// and the file name order within directories. Files that sort equal keep their order.{n}
+        /// "--exclude" only removes files from the sorted order, "--continue-from" and "--state-file" resume in it.
+        #[arg(long, default_value = "given", global = true)]
+        sort: FileSort,
+        /// Skip the rnote files matching this glob pattern, e.g. "*.bak.rnote". Can be repeated.{n}
+        /// Patterns containing a '/' are matched against the full path, others against the file name.{n}
+        /// Supports "*", "**", "?" and character classes like "[a-z]".
+        #[arg(long = "exclude", value_name = "PATTERN", global = true)]
+        exclude_patterns: Vec<String>,
+        /// A Json or Toml file that overrides the export preferences for the rnote files matching patterns,{n}
+        /// e.g. to export some of them without background. Patterns are matched like the ones of "--exclude".{n}
+        /// Precedence: the prefs map (later entries over earlier ones), then the command line flags,{n}
+        /// then the export preferences, then the built-in defaults. Unknown keys are rejected.
+        #[arg(long, value_name = "FILE", global = true)]
+        prefs_map: Option<PathBuf>,
         /// The action that will be performed if the to be exported file(s) already exist(s).
         #[arg(long, default_value = "ask", global = true)]
         on_conflict: OnConflict,
+        /// Resume an interrupted export of multiple files, skipping the rnote files up to and including this one.{n}
+        /// Takes precedence over the file recorded in "--state-file".
+        #[arg(long, value_name = "FILE", global = true)]
+        continue_from: Option<PathBuf>,
+        /// Record the last exported rnote file and the files that failed in this state file while exporting multiple files.{n}
+        /// When the state file exists, the export retries the failed files and resumes after the recorded file.{n}
+        /// It is removed again once all files are exported.
+        #[arg(long, value_name = "FILE", global = true)]
+        state_file: Option<PathBuf>,
+        /// How the progress of the export is reported.
+        #[arg(long, value_enum, default_value_t = Default::default(), global = true)]
+        progress: ProgressStyle,
+        /// Emit newline-delimited Json events on stdout instead of the human readable output,{n}
+        /// for integrating the export into a supervising process.{n}
+        /// Every event has an "event" field with one of "start", "progress", "done" or "error".
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "progress", global = true)]
+        events: bool,
+        /// The weight of the latest drawing rate in the estimated remaining time that the progress bars{n}
+        /// and the progress events show while exporting to Pdf, between 0 (exclusive) and 1.{n}
+        /// Lower values smooth the estimate more when the render times of the pages vary a lot, 1 disables the smoothing.
+        #[arg(long, value_name = "FACTOR", default_value_t = export::EtaEstimator::SMOOTHING_DEFAULT, value_parser = parse_eta_smoothing, global = true)]
+        eta_smoothing: f64,
         /// Export without background.
         #[arg(short = 'b', long, action = clap::ArgAction::SetTrue, global = true)]
         no_background: bool,
@@ -64,10 +373,237 @@ pub(crate) enum Command {
         /// Optimize the background and stroke colors for printing.
         #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
         optimize_printing: bool,
+        /// Strip identifying metadata like the creation date or the producing software from the exported files.{n}
+        /// Affects Pdf and Png exports, the other formats don't contain any.
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        strip_metadata: bool,
+        /// Render at this factor higher resolution and downsample afterwards, for smoother edges in bitmap exports{n}
+        /// and crisper strokes that are converted to bitmap images in Pdf exports.{n}
+        /// Memory usage while rendering grows quadratically with the factor.
+        #[arg(long, default_value_t = 1.0, value_parser = parse_supersampling, global = true)]
+        supersampling: f64,
+        /// Render strokes at least this wide in pixels of the exported output, so that hairlines stay visible{n}
+        /// at low resolutions. Thicker strokes are not affected. Not supported when exporting to Xopp.
+        #[arg(long, value_name = "PX", default_value_t = 0.0, value_parser = |s: &str| parse_non_negative(s, "Minimum line width"), global = true)]
+        min_line_width: f64,
+        /// Additionally write a companion at twice the resolution with the suffix "@2x", e.g. "page.png" and{n}
+        /// "page@2x.png". Both are rendered in one pass and are consistent. Only supported for bitmap image formats.
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        retina: bool,
+        /// The renderer that rasterizes bitmap exports. "tiny-skia" rasterizes with resvg instead of librsvg,{n}
+        /// the Svg of the content is still generated through cairo. Only supported for bitmap image formats.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        render_backend: render::RenderBackend,
+        /// Tag every stroke in exported Svgs with Css classes derived from its type and color,{n}
+        /// e.g. "rnote-stroke rnote-brushstroke rnote-color-1c71d8". Only supported when exporting to Svg.
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        svg_classes: bool,
+        /// Inline the Css stylesheet in this file into exported Svgs. Only supported when exporting to Svg.
+        #[arg(long, value_name = "FILE", global = true)]
+        svg_style: Option<PathBuf>,
+        /// Anchor the coordinate system of exported Svgs at the top-left corner of the page or of the bounding box{n}
+        /// of the strokes. The viewBox is shifted accordingly, the strokes keep their position on the page.{n}
+        /// Only supported when exporting to Svg.
+        #[arg(long, value_name = "ORIGIN", default_value_t = Default::default(), global = true)]
+        svg_origin: SvgOrigin,
+        /// Add a comment with the rnote version and the time of the export to the top of exported Svgs.{n}
+        /// Off by default, so that repeated exports are identical. Only supported when exporting to Svg.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "strip_metadata", global = true)]
+        svg_comment: bool,
+        /// The unit of the width and height of exported Svgs, converted with the dpi of the document format.{n}
+        /// Without it they are unitless. The viewBox stays unitless. Only supported when exporting to Svg.
+        #[arg(long, value_name = "UNIT", global = true)]
+        svg_units: Option<SvgUnits>,
+        /// Smooth the paths of the strokes before rendering, e.g. to get cleaner curves for jagged strokes{n}
+        /// imported from low-resolution sources. "catmull-rom" fits a spline through the points of strokes{n}
+        /// that consist only of lines. The rnote file is not modified.
+        #[arg(long, value_name = "SMOOTHING", default_value_t = Default::default(), global = true)]
+        smooth_strokes: StrokeSmoothing,
+        /// Arrange this many rows and columns of pages on every exported page, e.g. "1x2" for 2-up or "2x2"{n}
+        /// for 4-up printing. The pages are placed left-to-right, top-to-bottom. Only supported when exporting{n}
+        /// the document to Pdf or doc pages to bitmap image formats.
+        #[arg(long, value_name = "RxC", value_parser = parse_nup, global = true)]
+        nup: Option<(u32, u32)>,
+        /// The gap between the pages arranged with "--nup", in millimeters.
+        #[arg(long, value_name = "MM", default_value_t = ExportNup::GUTTER_DEFAULT, value_parser = |s: &str| parse_non_negative(s, "Gutter"), requires = "nup", global = true)]
+        nup_gutter: f64,
+        /// Draw a border around every page arranged with "--nup".
+        #[arg(long, action = clap::ArgAction::SetTrue, requires = "nup", global = true)]
+        nup_borders: bool,
+        /// Additionally write a Json file listing every exported stroke with its type, style and bounds{n}
+        /// in the coordinates of the exported image. For doc pages it holds a list with an entry for every page.{n}
+        /// Only supported when exporting doc pages or a selection of a single rnote file.
+        #[arg(long, value_name = "PATH", global = true)]
+        metadata_json: Option<PathBuf>,
+        /// Render all strokes with this color instead of their own, e.g. "#000000" to export everything in black.{n}
+        /// The transparency of the strokes is kept. Images are not affected.
+        #[arg(long, value_parser = parse_color, global = true)]
+        ink_color: Option<Color>,
+        /// The resolution in dots per inch of bitmap exports and of the strokes that are converted to bitmap images{n}
+        /// in Pdf exports. Takes precedence over the per-format resolutions configured in the export preferences,{n}
+        /// which in turn take precedence over the built-in defaults (e.g. "--bitmap-scalefactor").{n}
+        /// Output devices with non-square pixels can be targeted with a horizontal and vertical resolution, e.g. "200x100".{n}
+        /// This is only supported when exporting doc pages or a selection to bitmap images.
+        #[arg(long, value_name = "DPI", value_parser = parse_export_dpi, global = true)]
+        export_dpi: Option<(f64, f64)>,
+        /// Mirror the exported output horizontally ("h"), vertically ("v") or both ("hv").{n}
+        /// Not supported when exporting to Xopp.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        flip: ExportFlip,
+        /// Rotate the exported output clockwise by this many degrees.{n}
+        /// The width and height of pages are swapped for 90 and 270. Not supported when exporting to Xopp.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        rotate: ExportRotation,
+        /// Draw this image beneath the background pattern and the strokes of every exported page.{n}
+        /// Not supported when exporting to Xopp.
+        #[arg(long, value_name = "PATH", global = true)]
+        background_image: Option<PathBuf>,
+        /// How the background image is fitted into the page.{n}
+        /// "cover" fills the entire page, "contain" fits the whole image inside the page,{n}
+        /// "tile" repeats the image in its original size.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        background_image_fit: BackgroundImageFit,
+        /// Only export strokes that were created at or after this time.{n}
+        /// Accepts a date ("2024-05-01", local midnight), a date and time in RFC 3339 format{n}
+        /// ("2024-05-01T14:30:00+02:00") or a unix timestamp in seconds.{n}
+        /// Strokes of documents saved before creation times were recorded are never exported.
+        #[arg(long, value_name = "TIMESTAMP", value_parser = parse_since, global = true)]
+        since: Option<i64>,
+        /// Only export the strokes with these ids, e.g. "4294967297,4294967299". The ids are listed by{n}
+        /// "inspect --strokes" and in the "--metadata-json" output, they are kept when the document is saved.{n}
+        /// When exporting a selection with "all", the export is cropped to the combined bounds of the strokes.{n}
+        /// Fails if any of the ids doesn't exist.
+        #[arg(long, value_name = "IDS", value_delimiter = ',', global = true)]
+        stroke_ids: Option<Vec<u64>>,
+        /// What happens when a stroke fails to render: "fail" aborts the export, "skip" leaves the stroke out{n}
+        /// and "placeholder" draws a small marker where it would have been. Failing strokes are logged with their index.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        on_stroke_error: OnStrokeError,
+        /// Render at most this many pages and strokes in parallel. Defaults to the amount of cpu cores.
+        #[arg(long, value_name = "TASKS", value_parser = clap::value_parser!(u16).range(1..), global = true)]
+        concurrency_limit: Option<u16>,
+        /// Limit the parallel rendering, so that the estimated memory of the render tasks stays below this amount of megabytes.{n}
+        /// The memory of a render task is roughly estimated from the size of the largest rnote file.{n}
+        /// Only the rendering is limited, the loaded document and the exported pages are held in memory in addition.{n}
+        /// At least one task is always run.
+        #[arg(
+            long,
+            value_name = "MB",
+            conflicts_with = "concurrency_limit",
+            global = true
+        )]
+        max_render_memory: Option<u64>,
+        /// Draw this text at the top of every exported page.{n}
+        /// The placeholders "{page}", "{pages}", "{filename}" and "{date}" are replaced for every page.{n}
+        /// Not supported when exporting to Xopp or when exporting a selection.
+        #[arg(long, value_name = "TEXT", global = true)]
+        header: Option<String>,
+        /// Draw this text at the bottom of every exported page, e.g. "Page {page} of {pages}".{n}
+        /// Supports the same placeholders as "--header".
+        #[arg(long, value_name = "TEXT", global = true)]
+        footer: Option<String>,
+        /// The font size of the header and footer.
+        #[arg(long, default_value_t = ExportHeaderFooter::FONT_SIZE_DEFAULT, value_parser = parse_font_size, global = true)]
+        header_footer_font_size: f64,
+        /// The distance of the header and footer to the top and bottom page edge.
+        #[arg(long, default_value_t = ExportHeaderFooter::MARGIN_DEFAULT, global = true)]
+        header_footer_margin: f64,
+        /// Draw a circular badge with the page number into this corner of every exported page.{n}
+        /// Not supported when exporting to Xopp or when exporting a selection.
+        #[arg(long, value_name = "CORNER", global = true)]
+        page_badge: Option<ExportPageBadgeCorner>,
+        /// The diameter of the page badge. It is scaled together with the page content.
+        #[arg(long, default_value_t = ExportPageBadge::SIZE_DEFAULT, value_parser = parse_page_badge_size, requires = "page_badge", global = true)]
+        page_badge_size: f64,
+        /// The color of the page badge, e.g. "#3584e4".
+        #[arg(long, value_parser = parse_color, requires = "page_badge", global = true)]
+        page_badge_color: Option<Color>,
+        /// Export the background and the strokes into two separate outputs.{n}
+        /// The file names get the suffixes " - background" and " - strokes".{n}
+        /// Only supported for Svg and bitmap image formats.
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        separate_background: bool,
+        /// Generate a legend with a swatch and a label for every distinct ink color and pen of the exported strokes,{n}
+        /// e.g. to explain the color coding of annotated diagrams.
+        #[arg(long, value_name = "MODE", global = true)]
+        legend: Option<ExportLegendMode>,
+        /// Skip the rnote files without content instead of exporting them, e.g. placeholder files.{n}
+        /// Only the strokes that would be exported are counted, see "--since" and "--stroke-ids".
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        skip_empty: bool,
+        /// The amount of strokes a file needs to have to not be skipped by "--skip-empty".
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = parse_min_strokes, requires = "skip_empty", global = true)]
+        min_strokes: usize,
+        /// Don't export trailing pages that are almost blank, e.g. when the content slightly overflows onto the last page.{n}
+        /// A page is almost blank when the bounds of its strokes cover less than "--blank-threshold" of it.{n}
+        /// At least one page is always exported. Not supported when exporting a selection.
+        #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
+        trim_blank_pages: bool,
+        /// The percentage (0 - 100) of the page area the strokes need to cover for it to not be trimmed{n}
+        /// by "--trim-blank-pages".
+        #[arg(long, value_name = "PCT", default_value_t = 1.0, value_parser = parse_blank_threshold, requires = "trim_blank_pages", global = true)]
+        blank_threshold: f64,
+        /// Set the permissions of the created file(s) to this octal mode, e.g. "640".{n}
+        /// Only supported on Unix.
+        #[arg(long, value_name = "MODE", value_parser = parse_file_mode, global = true)]
+        chmod: Option<u32>,
         /// Inspect the result after the export is finished.{n}
         /// Opens output folder when using "doc-pages" sub-command.
         #[arg(long, action = clap::ArgAction::SetTrue, global = true)]
         open: bool,
+        /// Record the time spent loading, rendering every page, encoding and writing to this file in the{n}
+        /// Chrome trace format, which can be viewed in chrome://tracing or Perfetto or turned into a flamegraph.{n}
+        /// Without it, no timings are recorded. An existing file is handled according to "--on-conflict".
+        #[arg(long, value_name = "FILE", global = true)]
+        profile: Option<PathBuf>,
+    },
+    /// Exports every stroke of the document as its own image, cropped to the stroke with a transparent background.{n}
+    /// The files are named after the rnote file, the position of the stroke and its type.
+    ExportStickers {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The directory the stickers get exported to.
+        #[arg(short = 'o', long)]
+        output_dir: PathBuf,
+        /// The export format. Jpeg is not supported because it has no transparency.
+        #[arg(short = 'f', long)]
+        export_format: SelectionExportFormat,
+        /// The padding around every stroke.
+        #[arg(long, default_value_t = 4.0)]
+        padding: f64,
+        /// The bitmap scale-factor in relation to the actual size on the document.
+        #[arg(long, default_value_t = SelectionExportPrefs::default().bitmap_scalefactor)]
+        bitmap_scalefactor: f64,
+    },
+    /// Exports an animated Gif that plays back the note, drawing one more stroke in every frame{n}
+    /// in the order the strokes were created.
+    ExportAnimation {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The animated Gif file.
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
+        /// The frames per second of the playback (1 - 100).
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=100))]
+        fps: u32,
+        /// Show the finished note for this many additional frames before the animation loops.
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        hold_frames: u32,
+        /// Export without the background and the background pattern.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_background: bool,
+        /// The bitmap scale-factor in relation to the actual size on the document.
+        #[arg(long, default_value_t = 1.0)]
+        bitmap_scalefactor: f64,
+    },
+    /// Extracts the images embedded in the document as Png files, in their original resolution.{n}
+    /// A "manifest.json" with the position of every image on the document is written next to them.
+    ExtractImages {
+        /// The rnote file.
+        rnote_file: PathBuf,
+        /// The directory the images get extracted to.
+        #[arg(short = 'o', long)]
+        output_dir: PathBuf,
     },
 }
 
@@ -90,6 +626,80 @@ pub(crate) enum OnConflict {
     AlwaysSuffix,
 }
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportLegendMode {
+    /// Append the legend as last page. Only supported when exporting the document as Pdf.
+    Append,
+    /// Write the legend as Svg next to the export, with the suffix " - legend".
+    Sidecar,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum FileSort {
+    #[default]
+    /// The order of the given paths, the files of a directory sorted by their file name.
+    Given,
+    /// Sorted by the full path.
+    Path,
+    /// Sorted by the file name, regardless of the directory.
+    Name,
+    /// Sorted by the modification time, oldest first.
+    Modified,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ProgressStyle {
+    #[default]
+    /// A progress bar for every exported file.
+    Bars,
+    /// A single line with the amount of exported and failed files and the written bytes.
+    Aggregate,
+    /// No progress output.
+    None,
+    /// Newline-delimited Json events, set through "--events".
+    #[value(skip)]
+    Events,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InputFormat {
+    /// Xournal++ file.
+    Xopp,
+    /// Pdf document.
+    Pdf,
+    /// Epub publication, its text is imported as bitmap pages.
+    #[cfg(feature = "epub")]
+    Epub,
+    /// GoodNotes archive, its page images and Svg ink are imported as pages.
+    #[cfg(feature = "goodnotes")]
+    Goodnotes,
+    /// Png image.
+    Png,
+    /// Jpeg image.
+    Jpeg,
+    /// Svg image.
+    Svg,
+    /// Wmf or Emf vector metafile, converted to a vector image.
+    #[cfg(feature = "metafile")]
+    Metafile,
+    /// Csv data, drawn as line chart.
+    Csv,
+    /// UTF-8 plain text.
+    Txt,
+    /// Markdown text, imported as styled text.
+    #[cfg(feature = "markdown")]
+    Markdown,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum TransformAbout {
+    #[default]
+    /// The center of the bounds of every stroke.
+    Center,
+    /// The top-left corner of the document.
+    Origin,
+}
+
 #[derive(clap::Subcommand, Debug, Clone)]
 pub(crate) enum ExportCommand {
     /// Export the entire document.{n}
@@ -104,13 +714,89 @@ pub(crate) enum ExportCommand {
         /// pages.
         #[arg(long, default_value_t = Default::default())]
         page_order: SplitOrder,
+        /// Force the orientation of the exported pages, rotating the content when necessary.{n}
+        /// With "--paper-size", selects the orientation of the paper instead. Only has an effect when exporting to Pdf.
+        #[arg(long, default_value_t = Default::default())]
+        orientation: ExportOrientation,
+        /// Export the pages at this physical paper size instead of the document format size, with the pages{n}
+        /// centered on the paper. One of "a2", "a3", "a4", "a5", "a6", "letter" or "legal".{n}
+        /// Only supported when exporting to Pdf.
+        #[arg(long, value_name = "SIZE", value_parser = parse_paper_size)]
+        paper_size: Option<PredefinedFormat>,
+        /// Scale the content of every page to fit the paper inside the margins.
+        #[arg(long, action = clap::ArgAction::SetTrue, requires = "paper_size")]
+        fit_to_page: bool,
+        /// The margins in millimeters on every side of the paper that the content is fitted into.
+        #[arg(long, value_name = "MM", default_value_t = DocExportPrefs::PAPER_MARGINS_DEFAULT, value_parser = |s: &str| parse_non_negative(s, "Margins"), requires = "paper_size")]
+        margins: f64,
+        /// Export the content as a single page that is sized to the bounds of the content extended by "--margin",{n}
+        /// instead of the pages of the document format. For Pdf the page box is set to this size.{n}
+        /// Falls back to the document format when there is no content. Not supported when exporting to Xopp.
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["paper_size", "split_every"])]
+        page_size_from_content: bool,
+        /// The margin around the content when using "--page-size-from-content".
+        #[arg(long, default_value_t = DocExportPrefs::CONTENT_MARGIN_DEFAULT, value_parser = |s: &str| parse_non_negative(s, "Margin"), requires = "page_size_from_content")]
+        margin: f64,
+        /// Center the content of every page within the page box, horizontally and vertically ("both", the default{n}
+        /// when no value is given), only "horizontal" or only "vertical". The document itself is not modified.{n}
+        /// With "--fit-to-page" the content is centered on the paper anyway. Not supported when exporting to Xopp.
+        #[arg(long, value_name = "AXES", num_args = 0..=1, default_missing_value = "both")]
+        center_content: Option<ExportCenterContent>,
+        /// Fill the Pdf pages with this color and composite (semi-)transparent strokes against it,{n}
+        /// so that they look the same in every Pdf viewer. Expects a hex color like "#ffffff".{n}
+        /// Only has an effect when exporting to Pdf.
+        #[arg(long, value_parser = parse_color)]
+        flatten_transparency_to_pdf_background: Option<Color>,
+        /// Draw crop marks at the corners of the trim box for print production.{n}
+        /// The pages are extended by room for the marks. Only has an effect when exporting to Pdf.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        crop_marks: bool,
+        /// Extend the pages by this bleed in millimeters on every side.{n}
+        /// The background and strokes that reach over the page edges extend into the bleed.{n}
+        /// Only has an effect when exporting to Pdf.
+        #[arg(long, value_name = "MM", default_value_t = 0.0, value_parser = |s: &str| parse_non_negative(s, "Bleed"))]
+        bleed: f64,
+        /// The page labels that Pdf viewers display instead of the page numbers. Only supported when exporting to Pdf.{n}
+        /// Expects a comma separated list of labels and ranges, e.g. "i-iii,1-10" or "cover,i,ii,1,2".{n}
+        /// Ranges can be either decimal or roman numerals. Pages without a label keep the default numbering.{n}
+        /// The labels belong to the pages of the document, skipped empty pages don't shift them.
+        #[arg(long, value_parser = parse_page_labels)]
+        page_labels: Option<PageLabels>,
+        /// Split the exported Pdf into separate files of this many pages each, e.g. to chunk long documents into{n}
+        /// chapters. The files are named with the page range they hold, e.g. "notes-pages-1-10.pdf".{n}
+        /// Only supported when exporting to Pdf.
+        #[arg(long, value_name = "N", value_parser = parse_split_every)]
+        split_every: Option<usize>,
+        /// Recognize the text on the pages and embed it as invisible text layer behind the content,{n}
+        /// making the text of scanned documents searchable and selectable. Pages without text are left unchanged.{n}
+        /// Only supported when exporting to Pdf. Requires building with the "ocr" feature and installed Tesseract
+        /// language data.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        ocr: bool,
+        /// The Tesseract language(s) of the recognized text, e.g. "eng" or "deu+eng".
+        #[arg(long, value_name = "LANG", default_value = "eng", requires = "ocr")]
+        ocr_lang: String,
     },
     /// Export each page of the document(s) individually.{n}
-    /// Both "--output-dir" and "--output-format" need to be set.
+    /// "--output-format" needs to be set, together with either "--output-dir" to write the pages into a directory{n}
+    /// or "--zip" to pack them into an archive.
     DocPages {
         /// The directory the pages get exported to.
-        #[arg(short = 'o', long)]
-        output_dir: PathBuf,
+        #[arg(
+            short = 'o',
+            long,
+            required_unless_present_any = ["zip", "stdout"],
+            conflicts_with = "zip"
+        )]
+        output_dir: Option<PathBuf>,
+        /// Pack the exported pages into this Zip archive instead of writing them into a directory.{n}
+        /// The archive also contains an "index.json" manifest listing the pages.{n}
+        /// Only a single rnote file can be exported this way.
+        #[arg(long, value_name = "FILE")]
+        zip: Option<PathBuf>,
+        /// The folder inside the Zip archive that the pages and the manifest are placed in.
+        #[arg(long, value_name = "FOLDER", requires = "zip")]
+        zip_folder: Option<String>,
         /// The file name stem when naming the to be exported pages files.
         #[arg(short = 's', long)]
         output_file_stem: Option<String>,
@@ -127,6 +813,44 @@ pub(crate) enum ExportCommand {
         /// The quality of the generated image(s) when Jpeg is used as export format.
         #[arg(long, default_value_t = DocPagesExportPrefs::default().jpeg_quality)]
         jpeg_quality: u8,
+        /// The chroma subsampling of the generated image(s) when Jpeg is used as export format.{n}
+        /// "444" keeps colored edges sharp at the cost of larger files, "420" is supported by every decoder.
+        #[arg(long, default_value_t = Default::default())]
+        jpeg_subsampling: render::JpegSubsampling,
+        /// Quantize Png images to an indexed palette with at most this many colors (2 - 256).{n}
+        /// Produces considerably smaller files for hand-written notes with few colors.
+        #[arg(long, value_name = "N", value_parser = parse_png_palette)]
+        png_palette: Option<u16>,
+        /// Dither the colors when quantizing to the palette, so that smooth gradients don't turn into bands.
+        #[arg(long, default_value_t = Default::default(), requires = "png_palette")]
+        dither: render::Dither,
+        /// Apply a gamma correction to the rendered images, mapping every color channel c to c^(1/GAMMA).{n}
+        /// An advanced option for matching the colors to a specific display or print pipeline,{n}
+        /// values above 1.0 brighten the midtones, below 1.0 darken them. 1.0 keeps the colors unchanged.
+        #[arg(long, value_name = "GAMMA", default_value_t = 1.0, value_parser = parse_gamma)]
+        gamma: f64,
+        /// Force the orientation of the exported pages, rotating the content when necessary.
+        #[arg(long, default_value_t = Default::default())]
+        orientation: ExportOrientation,
+        /// Center the content of every page within the page box, horizontally and vertically ("both", the default{n}
+        /// when no value is given), only "horizontal" or only "vertical". The document itself is not modified.
+        #[arg(long, value_name = "AXES", num_args = 0..=1, default_missing_value = "both")]
+        center_content: Option<ExportCenterContent>,
+        /// Only render the pages in parallel when the document has more pages than this.{n}
+        /// Smaller documents are rendered one page after another, which avoids the overhead of distributing them.
+        #[arg(long, value_name = "N", default_value_t = DocPagesExportPrefs::PARALLEL_PAGES_THRESHOLD_DEFAULT)]
+        parallel_pages_threshold: usize,
+        /// Write exactly the Png of the page selected with "--page" to stdout, without any other output,{n}
+        /// e.g. for piping into clipboard tools like "wl-copy" or "pbcopy".{n}
+        /// Only a single rnote file can be exported this way and only Png is supported.
+        #[arg(long, action = clap::ArgAction::SetTrue, requires = "page", conflicts_with_all = ["output_dir", "zip"])]
+        stdout: bool,
+        /// The page that is written to stdout, starting at 1.
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), requires = "stdout")]
+        page: Option<u32>,
+        /// Fail instead of writing to stdout when the Png is larger than this many bytes.
+        #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..), requires = "stdout")]
+        max_stdout_bytes: Option<u64>,
     },
     /// Export a selection in a document.{n}
     /// When using "--output-file", only a single input file can be specified.{n}
@@ -148,6 +872,22 @@ pub(crate) enum ExportCommand {
         /// The quality of the generated image(s) when Jpeg is used as export format.
         #[arg(long, default_value_t = SelectionExportPrefs::default().jpeg_quality, global = true)]
         jpeg_quality: u8,
+        /// The chroma subsampling of the generated image(s) when Jpeg is used as export format.{n}
+        /// "444" keeps colored edges sharp at the cost of larger files, "420" is supported by every decoder.
+        #[arg(long, default_value_t = Default::default(), global = true)]
+        jpeg_subsampling: render::JpegSubsampling,
+        /// Quantize Png images to an indexed palette with at most this many colors (2 - 256).{n}
+        /// Produces considerably smaller files for hand-written notes with few colors.
+        #[arg(long, value_name = "N", value_parser = parse_png_palette, global = true)]
+        png_palette: Option<u16>,
+        /// Dither the colors when quantizing to the palette, so that smooth gradients don't turn into bands.
+        #[arg(long, default_value_t = Default::default(), requires = "png_palette", global = true)]
+        dither: render::Dither,
+        /// Apply a gamma correction to the rendered images, mapping every color channel c to c^(1/GAMMA).{n}
+        /// An advanced option for matching the colors to a specific display or print pipeline,{n}
+        /// values above 1.0 brighten the midtones, below 1.0 darken them. 1.0 keeps the colors unchanged.
+        #[arg(long, value_name = "GAMMA", default_value_t = 1.0, value_parser = parse_gamma, global = true)]
+        gamma: f64,
         /// The margin around the to be exported content.
         #[arg(long, default_value_t = SelectionExportPrefs::default().margin, global = true)]
         margin: f64,
@@ -203,7 +943,21 @@ impl std::fmt::Display for OnConflict {
 }
 
 pub(crate) async fn run() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    // Checked before parsing, so that the help and the parse errors are printed without colors as well
+    let no_color = no_color_requested();
+    let color_choice = if no_color {
+        clap::ColorChoice::Never
+    } else {
+        clap::ColorChoice::Auto
+    };
+    let cli = Cli::from_arg_matches(&Cli::command().color(color_choice).get_matches())?;
+    if no_color || cli.no_color {
+        // Progress bars and prompts are styled through the console crate
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    ASSUME_YES.store(cli.assume_yes, Ordering::Relaxed);
+    VERBOSE.store(cli.verbose, Ordering::Relaxed);
 
     match cli.command {
         Command::Test { rnote_files } => {
@@ -211,42 +965,898 @@ pub(crate) async fn run() -> anyhow::Result<()> {
             test::run_test(&rnote_files).await?;
             println!("Tests finished successfully!");
         }
+        Command::Check {
+            rnote_files,
+            bounds,
+        } => {
+            check::run_check(&rnote_files, bounds).await?;
+        }
+        Command::SelfTest => {
+            self_test::run_self_test().await?;
+        }
+        Command::Diff {
+            old_rnote_file,
+            new_rnote_file,
+            image,
+        } => {
+            diff::run_diff(&old_rnote_file, &new_rnote_file, image).await?;
+        }
+        Command::Inspect {
+            rnote_files,
+            debug,
+            strokes,
+        } => {
+            inspect::run_inspect(&rnote_files, debug, strokes).await?;
+        }
         Command::Import {
             rnote_file,
-            input_file,
+            input_files,
             xopp_dpi,
+            input_format,
+            pdf_pad_to,
+            pdf_layout,
+            pdf_form_fields,
+            whiten_threshold,
+            despeckle,
+            deskew,
+            remove_background,
+            remove_background_threshold,
+            allow_partial_images,
+            invert_colors,
+            import_csv,
+            csv_axes,
+            import_text,
+            #[cfg(feature = "markdown")]
+            import_markdown,
+            font,
+            font_size,
+            dedupe_pages,
+            generate_toc,
+            validate_only,
+            validate_images,
+            chmod,
         } => {
+            warn_file_mode_unsupported(chmod);
+            let mut text_style = TextStyle::default();
+            text_style.font_family = font;
+            text_style.font_size = font_size;
+            let input_format = input_format
+                .or(import_csv.then_some(InputFormat::Csv))
+                .or(import_text.then_some(InputFormat::Txt));
+            #[cfg(feature = "markdown")]
+            let input_format = input_format.or(import_markdown.then_some(InputFormat::Markdown));
             println!("Importing..");
-            import::run_import(&rnote_file, &input_file, xopp_dpi).await?;
+            import::run_import(
+                &rnote_file,
+                &input_files,
+                xopp_dpi,
+                pdf_pad_to,
+                pdf_layout,
+                pdf_form_fields,
+                whiten_threshold.or(despeckle.then_some(PdfImportPrefs::WHITEN_THRESHOLD_DEFAULT)),
+                deskew,
+                remove_background.map(|removal| (removal, remove_background_threshold)),
+                allow_partial_images,
+                invert_colors,
+                input_format,
+                csv_axes,
+                text_style,
+                dedupe_pages,
+                generate_toc,
+                validate_only,
+                validate_images,
+                chmod,
+            )
+            .await?;
             println!("Import finished!");
         }
+        Command::New {
+            rnote_file,
+            pages,
+            format,
+            landscape,
+            background,
+            no_pattern,
+        } => {
+            new::run_new(
+                &rnote_file,
+                pages,
+                format,
+                landscape,
+                background,
+                no_pattern,
+            )
+            .await?;
+        }
+        Command::Migrate {
+            rnote_file,
+            output_file,
+        } => {
+            println!("Migrating..");
+            migrate::run_migrate(&rnote_file, &output_file).await?;
+            println!("Migration finished!");
+        }
+        Command::Relink {
+            rnote_file,
+            output_file,
+            maps,
+            inline,
+            check,
+        } => {
+            relink::run_relink(&rnote_file, output_file.as_deref(), &maps, inline, check).await?;
+        }
+        Command::Transform {
+            rnote_file,
+            output_file,
+            rotate_images,
+            rotate_all,
+            scale_images,
+            about,
+        } => {
+            transform::run_transform(
+                &rnote_file,
+                &output_file,
+                rotate_images,
+                rotate_all,
+                scale_images,
+                about,
+            )
+            .await?;
+        }
+        Command::Reorder {
+            rnote_file,
+            output_file,
+            images_to_back: _,
+            images_to_front,
+        } => {
+            reorder::run_reorder(&rnote_file, &output_file, images_to_front).await?;
+        }
+        Command::Repair {
+            rnote_file,
+            output_file,
+        } => {
+            repair::run_repair(&rnote_file, &output_file).await?;
+        }
         Command::Export {
             rnote_files,
+            recursive,
+            sort,
+            exclude_patterns,
+            prefs_map,
             no_background,
             no_pattern,
             optimize_printing,
+            strip_metadata,
+            supersampling,
+            min_line_width,
+            retina,
+            render_backend,
+            svg_classes,
+            svg_style,
+            svg_origin,
+            svg_comment,
+            svg_units,
+            smooth_strokes,
+            nup,
+            nup_gutter,
+            nup_borders,
+            metadata_json,
+            ink_color,
+            export_dpi,
+            flip,
+            rotate,
+            background_image,
+            background_image_fit,
+            since,
+            stroke_ids,
+            on_stroke_error,
+            concurrency_limit,
+            max_render_memory,
+            header,
+            footer,
+            header_footer_font_size,
+            header_footer_margin,
+            page_badge,
+            page_badge_size,
+            page_badge_color,
+            separate_background,
+            legend,
+            skip_empty,
+            min_strokes,
+            trim_blank_pages,
+            blank_threshold,
             on_conflict,
+            continue_from,
+            state_file,
+            progress,
+            events,
+            eta_smoothing,
+            chmod,
             open,
+            profile,
             export_command,
         } => {
-            println!("Exporting..");
-            export::run_export(
+            let progress = if events {
+                ProgressStyle::Events
+            } else {
+                progress
+            };
+            // The events or the exported page are the only output on stdout
+            let human_output = progress != ProgressStyle::Events
+                && !matches!(export_command, ExportCommand::DocPages { stdout: true, .. });
+            if human_output {
+                warn_file_mode_unsupported(chmod);
+            }
+            let mut rnote_files = expand_rnote_dirs(rnote_files, recursive)?;
+            sort_files(&mut rnote_files, sort)?;
+            let (rnote_files, excluded) = exclude_files(rnote_files, &exclude_patterns);
+            if human_output {
+                if !exclude_patterns.is_empty() {
+                    println!("Excluded {excluded} file(s).");
+                }
+                println!("Exporting..");
+            }
+            let nup = nup.map(|(rows, columns)| ExportNup {
+                rows,
+                columns,
+                gutter: nup_gutter,
+                borders: nup_borders,
+            });
+            let page_badge = page_badge.map(|corner| ExportPageBadge {
+                corner,
+                size: page_badge_size,
+                color: page_badge_color.unwrap_or(ExportPageBadge::COLOR_DEFAULT),
+            });
+            // The conflict with an existing profile is resolved before the export, not after it finished
+            let profile = profile
+                .map(|profile| {
+                    export::file_conflict_prompt_action(&profile, on_conflict, &mut None)
+                        .map(|new_profile| new_profile.unwrap_or(profile))
+                })
+                .transpose()?;
+            let profiler = profile
+                .as_ref()
+                .map(|_| profile::Profiler::install())
+                .transpose()?;
+            let res = export::run_export(
                 rnote_files,
+                prefs_map,
                 no_background,
                 no_pattern,
                 optimize_printing,
+                strip_metadata,
+                supersampling,
+                min_line_width,
+                retina,
+                render_backend,
+                svg_classes,
+                svg_style,
+                svg_origin,
+                svg_comment,
+                svg_units,
+                smooth_strokes,
+                nup,
+                metadata_json,
+                ink_color,
+                export_dpi,
+                flip,
+                rotate,
+                background_image,
+                background_image_fit,
+                since,
+                stroke_ids,
+                on_stroke_error,
+                concurrency_limit,
+                max_render_memory,
+                header,
+                footer,
+                header_footer_font_size,
+                header_footer_margin,
+                page_badge,
+                separate_background,
+                legend,
+                skip_empty.then_some(min_strokes),
+                trim_blank_pages.then_some(blank_threshold / 100.0),
                 on_conflict,
+                continue_from,
+                state_file,
+                progress,
+                eta_smoothing,
+                chmod,
                 open,
                 export_command,
             )
+            .await;
+            // Also write the profile when the export failed, the recorded phases help finding out why
+            if let (Some(profiler), Some(profile)) = (profiler, profile) {
+                profiler.write(&profile).await?;
+                if human_output {
+                    println!("Wrote the profile to \"{}\".", profile.display());
+                }
+            }
+            res?;
+            if human_output {
+                println!("Export finished!");
+            }
+        }
+        Command::ExportStickers {
+            rnote_file,
+            output_dir,
+            export_format,
+            padding,
+            bitmap_scalefactor,
+        } => {
+            stickers::run_export_stickers(
+                &rnote_file,
+                &output_dir,
+                export_format,
+                padding,
+                bitmap_scalefactor,
+            )
+            .await?;
+        }
+        Command::ExportAnimation {
+            rnote_file,
+            output_file,
+            fps,
+            hold_frames,
+            no_background,
+            bitmap_scalefactor,
+        } => {
+            animation::run_export_animation(
+                &rnote_file,
+                &output_file,
+                fps,
+                hold_frames,
+                no_background,
+                bitmap_scalefactor,
+            )
             .await?;
-            println!("Export finished!");
+        }
+        Command::ExtractImages {
+            rnote_file,
+            output_dir,
+        } => {
+            extract_images::run_extract_images(&rnote_file, &output_dir).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a color from a hex string in the form "#rrggbb" or "#rrggbbaa". The leading '#' is optional.
+pub(crate) fn parse_color(s: &str) -> anyhow::Result<Color> {
+    let hex = s.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .with_context(|| format!("Color \"{s}\" is not a valid hex color."))?;
+    match hex.len() {
+        6 => Ok(Color::from((value << 8) | 0xff)),
+        8 => Ok(Color::from(value)),
+        _ => Err(anyhow::anyhow!(
+            "Color \"{s}\" must be in the form \"#rrggbb\" or \"#rrggbbaa\"."
+        )),
+    }
+}
+
+/// Parses an octal file mode like "640", "0640" or "0o640".
+pub(crate) fn parse_file_mode(s: &str) -> anyhow::Result<u32> {
+    let octal = s.trim().trim_start_matches("0o");
+    let mode = u32::from_str_radix(octal, 8)
+        .with_context(|| format!("File mode \"{s}\" is not a valid octal number."))?;
+    if mode > 0o7777 {
+        return Err(anyhow::anyhow!(
+            "File mode \"{s}\" is out of range, it must be at most 7777."
+        ));
+    }
+    Ok(mode)
+}
+
+pub(crate) fn parse_supersampling(s: &str) -> anyhow::Result<f64> {
+    let supersampling = s
+        .parse::<f64>()
+        .with_context(|| format!("Supersampling factor \"{s}\" is not a number."))?;
+    if !(1.0..=ExportPrefs::SUPERSAMPLING_MAX).contains(&supersampling) {
+        return Err(anyhow::anyhow!(
+            "Supersampling factor must be between 1.0 and {}.",
+            ExportPrefs::SUPERSAMPLING_MAX
+        ));
+    }
+    Ok(supersampling)
+}
+
+pub(crate) fn parse_font_size(s: &str) -> anyhow::Result<f64> {
+    let font_size = s
+        .parse::<f64>()
+        .with_context(|| format!("Font size \"{s}\" is not a number."))?;
+    if !(TextStyle::FONT_SIZE_MIN..=TextStyle::FONT_SIZE_MAX).contains(&font_size) {
+        return Err(anyhow::anyhow!(
+            "Font size must be between {} and {}.",
+            TextStyle::FONT_SIZE_MIN,
+            TextStyle::FONT_SIZE_MAX
+        ));
+    }
+    Ok(font_size)
+}
+
+/// Parses a number that must not be negative, naming the value with the label in the errors.
+pub(crate) fn parse_non_negative(s: &str, label: &str) -> anyhow::Result<f64> {
+    let value = s
+        .parse::<f64>()
+        .with_context(|| format!("{label} \"{s}\" is not a number."))?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(anyhow::anyhow!("{label} must not be negative."));
+    }
+    Ok(value)
+}
+
+pub(crate) fn parse_eta_smoothing(s: &str) -> anyhow::Result<f64> {
+    let smoothing = s
+        .parse::<f64>()
+        .with_context(|| format!("Smoothing factor \"{s}\" is not a number."))?;
+    if !(smoothing > 0.0 && smoothing <= 1.0) {
+        return Err(anyhow::anyhow!(
+            "Smoothing factor must be greater than 0 and at most 1."
+        ));
+    }
+    Ok(smoothing)
+}
+
+pub(crate) fn parse_nup(s: &str) -> anyhow::Result<(u32, u32)> {
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n >= 1)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "N-up layout \"{s}\" must be in the form \"RxC\" with at least one row and column, e.g. \"2x2\"."
+                )
+            })
+    };
+    match s.to_lowercase().split_once('x') {
+        Some((rows, columns)) => Ok((parse(rows)?, parse(columns)?)),
+        None => Err(anyhow::anyhow!(
+            "N-up layout \"{s}\" must be in the form \"RxC\", e.g. \"2x2\"."
+        )),
+    }
+}
+
+pub(crate) fn parse_page_badge_size(s: &str) -> anyhow::Result<f64> {
+    let size = s
+        .parse::<f64>()
+        .with_context(|| format!("Page badge size \"{s}\" is not a number."))?;
+    if !size.is_finite() || size <= 0.0 {
+        return Err(anyhow::anyhow!("Page badge size must be positive."));
+    }
+    Ok(size)
+}
+
+pub(crate) fn parse_paper_size(s: &str) -> anyhow::Result<PredefinedFormat> {
+    match s.trim().to_lowercase().as_str() {
+        "a2" => Ok(PredefinedFormat::A2),
+        "a3" => Ok(PredefinedFormat::A3),
+        "a4" => Ok(PredefinedFormat::A4),
+        "a5" => Ok(PredefinedFormat::A5),
+        "a6" => Ok(PredefinedFormat::A6),
+        "letter" | "us-letter" => Ok(PredefinedFormat::UsLetter),
+        "legal" | "us-legal" => Ok(PredefinedFormat::UsLegal),
+        _ => Err(anyhow::anyhow!(
+            "Paper size \"{s}\" is not supported, expected one of \"a2\", \"a3\", \"a4\", \"a5\", \"a6\", \"letter\" or \"legal\"."
+        )),
+    }
+}
+
+pub(crate) fn parse_scale_factor(s: &str) -> anyhow::Result<f64> {
+    let scale_factor = s
+        .parse::<f64>()
+        .with_context(|| format!("Scale factor \"{s}\" is not a number."))?;
+    if !scale_factor.is_finite() || scale_factor <= 0.0 {
+        return Err(anyhow::anyhow!("Scale factor must be positive."));
+    }
+    Ok(scale_factor)
+}
+
+pub(crate) fn parse_gamma(s: &str) -> anyhow::Result<f64> {
+    let gamma = s
+        .parse::<f64>()
+        .with_context(|| format!("Gamma \"{s}\" is not a number."))?;
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return Err(anyhow::anyhow!("Gamma must be positive."));
+    }
+    Ok(gamma)
+}
+
+pub(crate) fn parse_relink_map(s: &str) -> anyhow::Result<(String, String)> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.is_empty() => Ok((old.to_string(), new.to_string())),
+        _ => Err(anyhow::anyhow!(
+            "Mapping \"{s}\" must be in the form \"OLD=NEW\" with a non-empty OLD."
+        )),
+    }
+}
+
+pub(crate) fn parse_min_strokes(s: &str) -> anyhow::Result<usize> {
+    let min_strokes = s
+        .parse::<usize>()
+        .with_context(|| format!("Minimum amount of strokes \"{s}\" is not a whole number."))?;
+    if min_strokes == 0 {
+        return Err(anyhow::anyhow!(
+            "Minimum amount of strokes must be at least 1."
+        ));
+    }
+    Ok(min_strokes)
+}
+
+pub(crate) fn parse_blank_threshold(s: &str) -> anyhow::Result<f64> {
+    let blank_threshold = s
+        .parse::<f64>()
+        .with_context(|| format!("Blank threshold \"{s}\" is not a number."))?;
+    if !(0.0..=100.0).contains(&blank_threshold) {
+        return Err(anyhow::anyhow!(
+            "Blank threshold must be a percentage between 0 and 100."
+        ));
+    }
+    Ok(blank_threshold)
+}
+
+pub(crate) fn parse_split_every(s: &str) -> anyhow::Result<usize> {
+    let split_every = s
+        .parse::<usize>()
+        .with_context(|| format!("Amount of pages \"{s}\" is not a whole number."))?;
+    if split_every == 0 {
+        return Err(anyhow::anyhow!("Amount of pages must be at least 1."));
+    }
+    Ok(split_every)
+}
+
+pub(crate) fn parse_dpi(s: &str) -> anyhow::Result<f64> {
+    let dpi = s
+        .parse::<f64>()
+        .with_context(|| format!("Dpi \"{s}\" is not a number."))?;
+    if !ExportPrefs::DPI_RANGE.contains(&dpi) {
+        return Err(anyhow::anyhow!(
+            "Dpi must be between {} and {}.",
+            ExportPrefs::DPI_RANGE.start(),
+            ExportPrefs::DPI_RANGE.end()
+        ));
+    }
+    Ok(dpi)
+}
+
+/// Parses a resolution, either a single value for square pixels or the horizontal and vertical resolution
+/// separated by "x", e.g. "200x100".
+pub(crate) fn parse_export_dpi(s: &str) -> anyhow::Result<(f64, f64)> {
+    match s.split_once(['x', 'X']) {
+        Some((dpi_x, dpi_y)) => Ok((parse_dpi(dpi_x.trim())?, parse_dpi(dpi_y.trim())?)),
+        None => {
+            let dpi = parse_dpi(s)?;
+            Ok((dpi, dpi))
         }
     }
+}
+
+pub(crate) fn parse_png_palette(s: &str) -> anyhow::Result<u16> {
+    let colors = s
+        .parse::<u16>()
+        .with_context(|| format!("Png palette size \"{s}\" is not a number."))?;
+    if !render::Image::PALETTE_COLORS_RANGE.contains(&colors) {
+        return Err(anyhow::anyhow!(
+            "Png palette size must be between {} and {}.",
+            render::Image::PALETTE_COLORS_RANGE.start(),
+            render::Image::PALETTE_COLORS_RANGE.end()
+        ));
+    }
+    Ok(colors)
+}
+
+/// Parses a time into a unix timestamp in seconds.
+pub(crate) fn parse_since(s: &str) -> anyhow::Result<i64> {
+    if let Ok(timestamp) = s.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    if let Ok(date_time) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(date_time.timestamp());
+    }
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| {
+        format!("Time \"{s}\" is neither a date, a RFC 3339 date and time nor a unix timestamp.")
+    })?;
+    date.and_time(chrono::NaiveTime::MIN)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|date_time| date_time.timestamp())
+        .ok_or_else(|| anyhow::anyhow!("Date \"{s}\" does not exist in the local timezone."))
+}
+
+/// Page labels, one for each page in order.
+#[derive(Debug, Clone)]
+pub(crate) struct PageLabels(pub(crate) Vec<String>);
+
+/// Parses page labels from a comma separated list of labels and ranges.
+///
+/// A range is either decimal ("1-10") or consists of roman numerals ("i-iv" or "I-IV").
+/// Everything else is taken as a literal label.
+pub(crate) fn parse_page_labels(s: &str) -> anyhow::Result<PageLabels> {
+    /// Avoids allocating huge amounts of labels for mistyped ranges
+    const RANGE_LEN_MAX: usize = 100_000;
+    let mut labels = Vec::new();
+
+    for item in s.split(',').map(|item| item.trim()) {
+        if item.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Page labels \"{s}\" contain an empty label."
+            ));
+        }
+        let Some((start, end)) = item.split_once('-') else {
+            labels.push(item.to_string());
+            continue;
+        };
+        let range = if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            Some((start, end, None))
+        } else if let (Some(start), Some(end)) = (roman_to_usize(start), roman_to_usize(end)) {
+            let uppercase = start_is_uppercase(item);
+            Some((start, end, Some(uppercase)))
+        } else {
+            None
+        };
+        match range {
+            Some((start, end, _)) if end < start || end - start >= RANGE_LEN_MAX => {
+                return Err(anyhow::anyhow!(
+                    "Page label range \"{item}\" is invalid or too large."
+                ));
+            }
+            Some((start, end, None)) => labels.extend((start..=end).map(|n| n.to_string())),
+            Some((start, end, Some(uppercase))) => labels.extend((start..=end).map(|n| {
+                let roman = usize_to_roman(n);
+                if uppercase {
+                    roman.to_uppercase()
+                } else {
+                    roman
+                }
+            })),
+            // Not a range, e.g. "appendix-a"
+            None => labels.push(item.to_string()),
+        }
+    }
+
+    Ok(PageLabels(labels))
+}
 
+fn start_is_uppercase(s: &str) -> bool {
+    s.chars()
+        .next()
+        .map(|c| c.is_ascii_uppercase())
+        .unwrap_or(false)
+}
+
+/// Converts a roman numeral to its value. Returns None if the string isn't a valid (non-zero) roman numeral.
+fn roman_to_usize(s: &str) -> Option<usize> {
+    let value = |c: char| match c.to_ascii_lowercase() {
+        'i' => Some(1),
+        'v' => Some(5),
+        'x' => Some(10),
+        'l' => Some(50),
+        'c' => Some(100),
+        'd' => Some(500),
+        'm' => Some(1000),
+        _ => None,
+    };
+    let values = s.chars().map(value).collect::<Option<Vec<usize>>>()?;
+    let mut total = 0;
+    for (i, v) in values.iter().enumerate() {
+        match values.get(i + 1) {
+            Some(next) if next > v => total -= *v as isize,
+            _ => total += *v as isize,
+        }
+    }
+    let total = usize::try_from(total).ok().filter(|t| *t > 0)?;
+    // Reject non-canonical numerals like "iiiii"
+    (usize_to_roman(total) == s.to_ascii_lowercase()).then_some(total)
+}
+
+/// Converts a value to a lowercase roman numeral.
+fn usize_to_roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
+
+/// Replaces the directories in the given paths with the rnote files they contain, sorted by their file name.
+///
+/// Files without the `.rnote` extension inside directories are ignored.
+/// When `recursive` is set, subdirectories are expanded as well. Symlinked directories are not followed.
+pub(crate) fn expand_rnote_dirs(
+    paths: Vec<PathBuf>,
+    recursive: bool,
+) -> anyhow::Result<Vec<PathBuf>> {
+    fn collect_dir(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+        let mut entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory \"{}\".", dir.display()))?
+            .collect::<Result<Vec<std::fs::DirEntry>, _>>()
+            .with_context(|| {
+                format!("Failed to read entries of directory \"{}\".", dir.display())
+            })?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                if recursive {
+                    collect_dir(&path, recursive, files)?;
+                }
+            } else if path.is_file() && path.extension().is_some_and(|ext| ext == "rnote") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        if path.is_dir() {
+            collect_dir(&path, recursive, &mut files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Sorts the expanded rnote files. The sort is stable, so files that sort equal keep their given order.
+pub(crate) fn sort_files(files: &mut [PathBuf], sort: FileSort) -> anyhow::Result<()> {
+    match sort {
+        FileSort::Given => {}
+        FileSort::Path => files.sort(),
+        FileSort::Name => files.sort_by(|a, b| a.file_name().cmp(&b.file_name())),
+        FileSort::Modified => {
+            let mut modified = Vec::with_capacity(files.len());
+            for file in files.iter() {
+                let time = std::fs::metadata(file)
+                    .and_then(|metadata| metadata.modified())
+                    .with_context(|| {
+                        format!(
+                            "Reading the modification time of \"{}\" failed.",
+                            file.display()
+                        )
+                    })?;
+                modified.push((time, file.clone()));
+            }
+            modified.sort_by_key(|(time, _)| *time);
+            for (file, (_, sorted)) in files.iter_mut().zip(modified) {
+                *file = sorted;
+            }
+        }
+    }
     Ok(())
 }
 
+/// Removes the files that match any of the glob patterns.
+///
+/// Patterns containing a '/' are matched against the full path, others only against the file name.
+/// Returns the remaining files and the number of excluded files.
+pub(crate) fn exclude_files(files: Vec<PathBuf>, patterns: &[String]) -> (Vec<PathBuf>, usize) {
+    let amount = files.len();
+    let files = files
+        .into_iter()
+        .filter(|file| {
+            !patterns
+                .iter()
+                .any(|pattern| file_matches_pattern(file, pattern))
+        })
+        .collect::<Vec<PathBuf>>();
+    let excluded = amount - files.len();
+    (files, excluded)
+}
+
+/// Patterns containing a '/' are matched against the full path, others against the file name.
+pub(crate) fn file_matches_pattern(file: &Path, pattern: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern, &file.to_string_lossy())
+    } else {
+        file.file_name()
+            .is_some_and(|name| glob_match(pattern, &name.to_string_lossy()))
+    }
+}
+
+/// Matches the text against a glob pattern.
+///
+/// Supports `*` (any characters except '/'), `**` (any characters), `?` (a single character except '/')
+/// and character classes like `[abc]`, `[a-z]` or `[!abc]`.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some('*') if p.get(1) == Some(&'*') => (0..=t.len()).any(|i| matches(&p[2..], &t[i..])),
+            Some('*') => (0..=t.len())
+                .take_while(|&i| i == 0 || t[i - 1] != '/')
+                .any(|i| matches(&p[1..], &t[i..])),
+            Some('?') => t.first().is_some_and(|c| *c != '/') && matches(&p[1..], &t[1..]),
+            Some('[') => {
+                let negated = p.get(1) == Some(&'!');
+                let start = if negated { 2 } else { 1 };
+                // The first character of the class can be a ']'
+                let Some(end) = p
+                    .iter()
+                    .skip(start + 1)
+                    .position(|c| *c == ']')
+                    .map(|i| i + start + 1)
+                else {
+                    // Unclosed classes are matched literally
+                    return t.first() == Some(&'[') && matches(&p[1..], &t[1..]);
+                };
+                let Some(&c) = t.first() else {
+                    return false;
+                };
+                let class = &p[start..end];
+                let mut in_class = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == '-' {
+                        in_class |= (class[i]..=class[i + 2]).contains(&c);
+                        i += 3;
+                    } else {
+                        in_class |= class[i] == c;
+                        i += 1;
+                    }
+                }
+                c != '/' && in_class != negated && matches(&p[end + 1..], &t[1..])
+            }
+            Some(c) => t.first() == Some(c) && matches(&p[1..], &t[1..]),
+        }
+    }
+
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    matches(&pattern, &text)
+}
+
+/// If styled output is disabled through "--no-color" or the "NO_COLOR" environment variable.
+///
+/// Following the convention of <https://no-color.org>, "NO_COLOR" is only respected when it is not empty.
+fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || std::env::args_os()
+            .take_while(|arg| arg != "--")
+            .any(|arg| arg == "--no-color")
+}
+
+/// The name of the output file with the zero-indexed number `i` of `amount` numbered files,
+/// formatted as "{output_file_stem} - {label} {number}{suffix}".
+///
+/// The user facing number is one-indexed and padded with leading zeros, so that the files sort in order.
+pub(crate) fn numbered_file_name(
+    output_file_stem: &str,
+    label: &str,
+    i: usize,
+    amount: usize,
+    suffix: &str,
+) -> String {
+    let leading_zeros = amount.to_string().len();
+    format!(
+        "{output_file_stem} - {label} {number}{suffix}",
+        number = format_args!("{:0fill$}", i + 1, fill = leading_zeros)
+    )
+}
+
 pub(crate) fn new_progressbar(message: String) -> indicatif::ProgressBar {
     let pb = indicatif::ProgressBar::new_spinner().with_message(message);
     pb.set_draw_target(indicatif::ProgressDrawTarget::stdout());
@@ -261,16 +1871,116 @@ pub(crate) async fn read_bytes_from_file(file_path: impl AsRef<Path>) -> anyhow:
     Ok(bytes)
 }
 
+/// If all confirmation prompts are answered with yes, set through "--yes".
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// If confirmation prompts should be answered with yes without asking.
+pub(crate) fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// If additional details are printed, set through "--verbose".
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// If additional details about the processed files should be printed.
+pub(crate) fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Asks the user to confirm, defaulting to no.
+///
+/// Returns true without asking when "--yes" is supplied.
+/// Fails when the terminal is not interactive, instead of waiting for an answer that never comes.
+pub(crate) fn confirm(prompt: impl Into<String>) -> anyhow::Result<bool> {
+    confirm_if_interactive(
+        prompt.into(),
+        io::stdin().is_terminal() && io::stdout().is_terminal(),
+    )
+}
+
+fn confirm_if_interactive(prompt: String, interactive: bool) -> anyhow::Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    if !interactive {
+        return Err(anyhow::anyhow!(
+            "{prompt} Confirmation is required, but the terminal is not interactive. Option \"--yes\" needs to be supplied."
+        ));
+    }
+    dialoguer::Confirm::new()
+        .with_prompt(&prompt)
+        .default(false)
+        .interact()
+        .with_context(|| format!("Failed to show confirmation prompt \"{prompt}\"."))
+}
+
+/// Asks the user to confirm overwriting the output file when it already exists, see [confirm].
+///
+/// The action is the name of the aborted operation in the error, e.g. "Import".
+pub(crate) fn confirm_overwrite(output_file: &Path, action: &str) -> anyhow::Result<()> {
+    if output_file.exists()
+        && !confirm(format!(
+            "File \"{}\" already exists, overwrite it?",
+            output_file.display()
+        ))?
+    {
+        return Err(anyhow::anyhow!(
+            "{action} aborted, \"{}\" already exists.",
+            output_file.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Creates or overwrites the file with the bytes.
+///
+/// When a file mode is given, the permissions of the file are set to it afterwards (only on Unix).
 pub(crate) async fn create_overwrite_file_w_bytes(
     output_file: impl AsRef<Path>,
     bytes: &[u8],
+    file_mode: Option<u32>,
 ) -> anyhow::Result<()> {
-    let mut fh = File::create(output_file).await?;
+    let _write_span = tracing::info_span!(
+        "write",
+        file = %output_file.as_ref().display(),
+        bytes = bytes.len()
+    );
+    let mut fh = File::create(&output_file).await?;
     fh.write_all(bytes).await?;
     fh.sync_all().await?;
+    if let Some(file_mode) = file_mode {
+        set_file_mode(output_file.as_ref(), file_mode).await?;
+    }
     Ok(())
 }
 
+#[cfg(unix)]
+async fn set_file_mode(file: &Path, mode: u32) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    smol::fs::set_permissions(file, std::fs::Permissions::from_mode(mode))
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to set the permissions of \"{}\" to {mode:o}.",
+                file.display()
+            )
+        })
+}
+
+#[cfg(not(unix))]
+async fn set_file_mode(_file: &Path, _mode: u32) -> anyhow::Result<()> {
+    // Unsupported, the user is warned once by `warn_file_mode_unsupported()`
+    Ok(())
+}
+
+/// Warns that setting the file mode is not supported on this platform, if one was given.
+fn warn_file_mode_unsupported(file_mode: Option<u32>) {
+    if cfg!(not(unix)) && file_mode.is_some() {
+        println!("Warning: \"--chmod\" is only supported on Unix and will be ignored.");
+    }
+}
+
 pub(crate) fn open_file_default_app(file_path: impl AsRef<Path>) -> anyhow::Result<()> {
     open::that_detached(file_path.as_ref()).with_context(|| {
         format!(
@@ -280,3 +1990,144 @@ pub(crate) fn open_file_default_app(file_path: impl AsRef<Path>) -> anyhow::Resu
     })?;
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        confirm_if_interactive, confirm_overwrite, exclude_files, glob_match, numbered_file_name,
+        parse_non_negative, sort_files, FileSort,
+    };
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    /// Creates a new empty directory for the files of a test.
+    pub(crate) fn temp_test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rnote-cli-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn numbered_file_names_are_one_indexed_and_padded() {
+        assert_eq!(
+            numbered_file_name("doc", "page", 0, 9, ".png"),
+            "doc - page 1.png"
+        );
+        assert_eq!(
+            numbered_file_name("doc", "page", 8, 10, ".png"),
+            "doc - page 09.png"
+        );
+        assert_eq!(
+            numbered_file_name("doc", "sticker", 41, 120, " textstroke.svg"),
+            "doc - sticker 042 textstroke.svg"
+        );
+    }
+
+    #[test]
+    fn parse_non_negative_rejects_negative_and_non_finite_numbers() {
+        assert_eq!(parse_non_negative("0", "Bleed").unwrap(), 0.0);
+        assert_eq!(parse_non_negative("2.5", "Bleed").unwrap(), 2.5);
+        for s in ["-1", "NaN", "inf", "abc"] {
+            assert!(parse_non_negative(s, "Bleed").is_err(), "{s} accepted");
+        }
+        assert_eq!(
+            parse_non_negative("-1", "Gutter").unwrap_err().to_string(),
+            "Gutter must not be negative."
+        );
+    }
+
+    #[test]
+    fn sort_files_orders_expanded_files() {
+        let files = ["b/2.rnote", "a/3.rnote", "c/1.rnote", "a/1.rnote"]
+            .map(PathBuf::from)
+            .to_vec();
+
+        let mut given = files.clone();
+        sort_files(&mut given, FileSort::Given).unwrap();
+        assert_eq!(given, files);
+        let mut by_path = files.clone();
+        sort_files(&mut by_path, FileSort::Path).unwrap();
+        assert_eq!(
+            by_path,
+            ["a/1.rnote", "a/3.rnote", "b/2.rnote", "c/1.rnote"].map(PathBuf::from)
+        );
+        // Equal file names keep their given order
+        let mut by_name = files;
+        sort_files(&mut by_name, FileSort::Name).unwrap();
+        assert_eq!(
+            by_name,
+            ["c/1.rnote", "a/1.rnote", "b/2.rnote", "a/3.rnote"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn sort_files_by_modification_time_then_exclude() {
+        let dir = temp_test_dir("sort-modified");
+        let now = SystemTime::now();
+        let mut files = Vec::new();
+        for (name, age) in [("new.rnote", 0), ("old.rnote", 20), ("old.bak.rnote", 10)] {
+            let path = dir.join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+            files.push(path);
+        }
+        sort_files(&mut files, FileSort::Modified).unwrap();
+        let (files, excluded) = exclude_files(files, &[String::from("*.bak.rnote")]);
+        assert_eq!(excluded, 1);
+        assert_eq!(files, vec![dir.join("old.rnote"), dir.join("new.rnote")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exclude_files_by_name_and_path() {
+        let files = [
+            "notes/a.rnote",
+            "notes/a.bak.rnote",
+            "notes/archive/b.rnote",
+            "c.rnote",
+        ]
+        .map(PathBuf::from)
+        .to_vec();
+
+        // Patterns without a '/' match the file name only
+        let (remaining, excluded) = exclude_files(files.clone(), &[String::from("*.bak.rnote")]);
+        assert_eq!(excluded, 1);
+        assert!(!remaining.contains(&PathBuf::from("notes/a.bak.rnote")));
+        let (_, excluded) = exclude_files(files.clone(), &[String::from("notes*")]);
+        assert_eq!(excluded, 0);
+
+        // Patterns with a '/' match the full path
+        let (remaining, excluded) = exclude_files(
+            files.clone(),
+            &[String::from("notes/*"), String::from("c.rnote")],
+        );
+        assert_eq!(excluded, 3);
+        assert_eq!(remaining, vec![PathBuf::from("notes/archive/b.rnote")]);
+        let (remaining, excluded) = exclude_files(files, &[String::from("**/archive/*")]);
+        assert_eq!(excluded, 1);
+        assert_eq!(remaining.len(), 3);
+    }
+
+    #[test]
+    fn glob_match_wildcards_and_classes() {
+        assert!(glob_match("*.rnote", "a.rnote"));
+        assert!(!glob_match("*.rnote", "dir/a.rnote"));
+        assert!(glob_match("**.rnote", "dir/a.rnote"));
+        assert!(glob_match("page?.rnote", "page1.rnote"));
+        assert!(!glob_match("page?.rnote", "page10.rnote"));
+        assert!(glob_match("page[0-4].rnote", "page3.rnote"));
+        assert!(!glob_match("page[!0-4].rnote", "page3.rnote"));
+    }
+
+    #[test]
+    fn overwrite_prompt_fails_fast_when_not_interactive() {
+        let err = confirm_if_interactive(String::from("Overwrite?"), false).unwrap_err();
+        assert!(format!("{err:#}").contains("\"--yes\""), "{err:#}");
+
+        let dir = temp_test_dir("confirm-overwrite");
+        // No prompt when the file does not exist
+        confirm_overwrite(&dir.join("new.rnote"), "Import").unwrap();
+    }
+}
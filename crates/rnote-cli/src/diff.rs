@@ -0,0 +1,283 @@
+// Imports
+use crate::{cli, validators};
+use p2d::bounding_volume::BoundingVolume;
+use rnote_compose::shapes::Shapeable;
+use rnote_compose::Color;
+use rnote_engine::engine::{EngineSnapshot, StrokeContent};
+use rnote_engine::store::StrokeKey;
+use rnote_engine::strokes::Stroke;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Strokes that exist only in the new file.
+const ADDED_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.6,
+    b: 0.0,
+    a: 1.0,
+};
+/// Strokes that exist only in the old file.
+const REMOVED_COLOR: Color = Color {
+    r: 0.85,
+    g: 0.0,
+    b: 0.0,
+    a: 1.0,
+};
+/// Strokes that exist in both files, but have changed. Drawn in their new state.
+const MODIFIED_COLOR: Color = Color {
+    r: 0.95,
+    g: 0.55,
+    b: 0.0,
+    a: 1.0,
+};
+/// Strokes that are unchanged.
+const UNCHANGED_COLOR: Color = Color {
+    r: 0.75,
+    g: 0.75,
+    b: 0.75,
+    a: 1.0,
+};
+/// The margin around the content of the visual diff image.
+const DIFF_IMAGE_MARGIN: f64 = 12.0;
+
+pub(crate) async fn run_diff(
+    old_rnote_file: &Path,
+    new_rnote_file: &Path,
+    diff_image: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let old = load_snapshot(old_rnote_file).await?;
+    let new = load_snapshot(new_rnote_file).await?;
+    let diff = StrokesDiff::compare(&old, &new);
+
+    for (key, stroke) in diff.removed.iter() {
+        println!("- {}", stroke_line(*key, stroke));
+    }
+    for (key, stroke) in diff.added.iter() {
+        println!("+ {}", stroke_line(*key, stroke));
+    }
+    for (key, stroke) in diff.modified.iter() {
+        println!("~ {}", stroke_line(*key, stroke));
+    }
+    println!(
+        "{} added, {} removed, {} modified, {} unchanged.",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len(),
+        diff.unchanged.len()
+    );
+
+    if let Some(diff_image) = diff_image {
+        validators::file_has_ext(&diff_image, "png")?;
+        match diff.gen_png_bytes()? {
+            Some(bytes) => {
+                cli::create_overwrite_file_w_bytes(&diff_image, &bytes, None).await?;
+                println!("Visual diff written to \"{}\".", diff_image.display());
+            }
+            None => println!("Both files have no strokes, no visual diff was written."),
+        }
+    }
+    Ok(())
+}
+
+async fn load_snapshot(rnote_file: &Path) -> anyhow::Result<EngineSnapshot> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    EngineSnapshot::load_from_rnote_bytes(rnote_bytes)
+        .await
+        .map_err(|e| e.context(format!("Loading \"{}\" failed.", rnote_file.display())))
+}
+
+fn stroke_line(key: StrokeKey, stroke: &Stroke) -> String {
+    let bounds = stroke.bounds();
+    format!(
+        "{key:?} {:016x} bounds: [{:.1}, {:.1}] - [{:.1}, {:.1}]",
+        stroke_hash(stroke),
+        bounds.mins[0],
+        bounds.mins[1],
+        bounds.maxs[0],
+        bounds.maxs[1]
+    )
+}
+
+/// Hash of the serialized stroke, equal for strokes with identical content.
+fn stroke_hash(stroke: &Stroke) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(stroke)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The strokes of two documents, matched by their key and content.
+///
+/// Strokes with the same key but different content are modified.
+/// Strokes whose key is only present in one document are matched by their content,
+/// so strokes that only got a new key are unchanged.
+#[derive(Debug, Default)]
+struct StrokesDiff {
+    added: Vec<(StrokeKey, Arc<Stroke>)>,
+    removed: Vec<(StrokeKey, Arc<Stroke>)>,
+    modified: Vec<(StrokeKey, Arc<Stroke>)>,
+    unchanged: Vec<(StrokeKey, Arc<Stroke>)>,
+}
+
+impl StrokesDiff {
+    fn compare(old: &EngineSnapshot, new: &EngineSnapshot) -> Self {
+        let mut diff = Self::default();
+        // Strokes with keys that are only in the old document, by their hash
+        let mut removed_candidates: HashMap<u64, Vec<(StrokeKey, Arc<Stroke>)>> = HashMap::new();
+        let mut added_candidates = Vec::new();
+
+        for (key, old_stroke) in old.stroke_components.iter() {
+            if !new.stroke_components.contains_key(key) {
+                removed_candidates
+                    .entry(stroke_hash(old_stroke))
+                    .or_default()
+                    .push((key, Arc::clone(old_stroke)));
+            }
+        }
+        for (key, new_stroke) in new.stroke_components.iter() {
+            match old.stroke_components.get(key) {
+                Some(old_stroke) => {
+                    if stroke_hash(old_stroke) == stroke_hash(new_stroke) {
+                        diff.unchanged.push((key, Arc::clone(new_stroke)));
+                    } else {
+                        diff.modified.push((key, Arc::clone(new_stroke)));
+                    }
+                }
+                None => added_candidates.push((key, Arc::clone(new_stroke))),
+            }
+        }
+        for (key, new_stroke) in added_candidates {
+            match removed_candidates
+                .get_mut(&stroke_hash(&new_stroke))
+                .and_then(|candidates| candidates.pop())
+            {
+                Some(_) => diff.unchanged.push((key, new_stroke)),
+                None => diff.added.push((key, new_stroke)),
+            }
+        }
+        diff.removed = removed_candidates.into_values().flatten().collect();
+        diff.removed.sort_unstable_by_key(|(key, _)| *key);
+        diff
+    }
+
+    /// Renders all strokes, colored by their change, on a white background.
+    ///
+    /// Returns `None` when there are no strokes.
+    fn gen_png_bytes(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        let colored = |strokes: &[(StrokeKey, Arc<Stroke>)], color: Color| {
+            StrokeContent::default()
+                .with_strokes(strokes.iter().map(|(_, s)| Arc::clone(s)).collect())
+                .with_ink_color(color)
+                .strokes
+        };
+        let strokes = [
+            colored(&self.unchanged, UNCHANGED_COLOR),
+            colored(&self.removed, REMOVED_COLOR),
+            colored(&self.modified, MODIFIED_COLOR),
+            colored(&self.added, ADDED_COLOR),
+        ]
+        .concat();
+        let Some(bounds) = strokes
+            .iter()
+            .map(|stroke| stroke.bounds())
+            .reduce(|acc, bounds| acc.merged(&bounds))
+        else {
+            return Ok(None);
+        };
+        let background = rnote_engine::document::Background {
+            color: Color::WHITE,
+            ..Default::default()
+        };
+        let content = StrokeContent::default()
+            .with_strokes(strokes)
+            .with_bounds(Some(bounds.loosened(DIFF_IMAGE_MARGIN)))
+            .with_background(Some(background));
+        let Some(svg) = content.gen_svg(true, false, false, 0.0)? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            svg.gen_image(1.0)?
+                .into_encoded_bytes(image::ImageFormat::Png, None)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StrokesDiff;
+    use rnote_compose::shapes::{Rectangle, Shape};
+    use rnote_compose::Style;
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::store::StrokeKey;
+    use rnote_engine::strokes::{ShapeStroke, Stroke};
+    use std::sync::Arc;
+
+    fn rect_stroke(x: f64) -> Arc<Stroke> {
+        Arc::new(Stroke::ShapeStroke(ShapeStroke::new(
+            Shape::Rectangle(Rectangle::from_corners(
+                na::vector![x, 0.0],
+                na::vector![x + 50.0, 50.0],
+            )),
+            Style::default(),
+        )))
+    }
+
+    fn keys(strokes: &[(StrokeKey, Arc<Stroke>)]) -> Vec<StrokeKey> {
+        let mut keys = strokes.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn compare_matches_strokes_by_key_and_content() {
+        let mut old = EngineSnapshot::default();
+        let strokes = Arc::make_mut(&mut old.stroke_components);
+        let unchanged = strokes.insert(rect_stroke(0.0));
+        let modified = strokes.insert(rect_stroke(100.0));
+        let removed = strokes.insert(rect_stroke(200.0));
+        let rekeyed = strokes.insert(rect_stroke(300.0));
+
+        let mut new = old.clone();
+        let strokes = Arc::make_mut(&mut new.stroke_components);
+        strokes[modified] = rect_stroke(150.0);
+        strokes.remove(removed);
+        // Strokes that only got a new key are unchanged
+        strokes.remove(rekeyed);
+        let rekeyed_new = strokes.insert(rect_stroke(300.0));
+        let added = strokes.insert(rect_stroke(400.0));
+
+        let diff = StrokesDiff::compare(&old, &new);
+        assert_eq!(keys(&diff.added), vec![added]);
+        assert_eq!(keys(&diff.removed), vec![removed]);
+        assert_eq!(keys(&diff.modified), vec![modified]);
+        let mut expected_unchanged = vec![unchanged, rekeyed_new];
+        expected_unchanged.sort_unstable();
+        assert_eq!(keys(&diff.unchanged), expected_unchanged);
+    }
+
+    #[test]
+    fn visual_diff_colors_the_changes() {
+        let empty = EngineSnapshot::default();
+        assert!(StrokesDiff::compare(&empty, &empty)
+            .gen_png_bytes()
+            .unwrap()
+            .is_none());
+
+        let mut new = EngineSnapshot::default();
+        Arc::make_mut(&mut new.stroke_components).insert(rect_stroke(0.0));
+        let png = StrokesDiff::compare(&empty, &new)
+            .gen_png_bytes()
+            .unwrap()
+            .unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        // The added stroke is drawn in green on the white background
+        assert!(image
+            .pixels()
+            .any(|pixel| pixel[1] > 100 && pixel[0] < 60 && pixel[2] < 60));
+        assert!(!image.pixels().any(|pixel| pixel[0] > 150 && pixel[1] < 60));
+    }
+}
@@ -1,25 +1,74 @@
 // Imports
-use crate::cli::{self, OnConflict};
+use crate::cli::{self, ExportLegendMode, OnConflict, ProgressStyle};
+use crate::prefs_map::PrefsMap;
 use crate::validators;
 use anyhow::Context;
 use p2d::bounding_volume::Aabb;
-use rnote_compose::SplitOrder;
+use rnote_compose::{Color, SplitOrder};
+use rnote_engine::document::format::PredefinedFormat;
 use rnote_engine::engine::export::{
-    DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs,
-    SelectionExportFormat, SelectionExportPrefs,
+    BackgroundImageFit, DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs,
+    ExportCenterContent, ExportFlip, ExportNup, ExportOrientation, ExportProgressCallback,
+    ExportRotation, OnStrokeError, RetinaExport, SelectionExportFormat, SelectionExportPrefs,
+    StrokeSmoothing, SvgOrigin, SvgUnits,
 };
-use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::engine::{
+    EngineSnapshot, ExportBackgroundImage, ExportHeaderFooter, ExportOptions, ExportPageBadge,
+};
+use rnote_engine::render;
 use rnote_engine::{Engine, SelectionCollision};
-use std::io::{self, IsTerminal};
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn run_export(
     rnote_files: Vec<PathBuf>,
+    prefs_map: Option<PathBuf>,
     no_background: bool,
     no_pattern: bool,
     optimize_printing: bool,
+    strip_metadata: bool,
+    supersampling: f64,
+    min_line_width: f64,
+    retina: bool,
+    render_backend: render::RenderBackend,
+    svg_classes: bool,
+    svg_style: Option<PathBuf>,
+    svg_origin: SvgOrigin,
+    svg_comment: bool,
+    svg_units: Option<SvgUnits>,
+    smooth_strokes: StrokeSmoothing,
+    nup: Option<ExportNup>,
+    metadata_json: Option<PathBuf>,
+    ink_color: Option<Color>,
+    export_dpi: Option<(f64, f64)>,
+    flip: ExportFlip,
+    rotation: ExportRotation,
+    background_image: Option<PathBuf>,
+    background_image_fit: BackgroundImageFit,
+    since: Option<i64>,
+    stroke_ids: Option<Vec<u64>>,
+    on_stroke_error: OnStrokeError,
+    concurrency_limit: Option<u16>,
+    max_render_memory: Option<u64>,
+    header: Option<String>,
+    footer: Option<String>,
+    header_footer_font_size: f64,
+    header_footer_margin: f64,
+    page_badge: Option<ExportPageBadge>,
+    separate_background: bool,
+    legend: Option<ExportLegendMode>,
+    min_strokes: Option<usize>,
+    trim_blank_pages: Option<f64>,
     on_conflict: OnConflict,
+    continue_from: Option<PathBuf>,
+    state_file: Option<PathBuf>,
+    progress: ProgressStyle,
+    eta_smoothing: f64,
+    file_mode: Option<u32>,
     open: bool,
     export_command: cli::ExportCommand,
 ) -> anyhow::Result<()> {
@@ -29,22 +78,86 @@ pub(crate) async fn run_export(
         ));
     }
 
+    let concurrency_limit = match max_render_memory {
+        Some(max_render_memory) => Some(concurrency_for_memory(&rnote_files, max_render_memory)?),
+        None => concurrency_limit.map(usize::from),
+    };
+    if let Some(concurrency_limit) = concurrency_limit {
+        limit_render_concurrency(concurrency_limit)?;
+    }
+
     let mut engine = Engine::default();
+    let mut export_options = ExportOptions::default();
     let mut on_conflict_overwrite = None;
     let output_file = match &export_command {
         cli::ExportCommand::Doc { file_args, .. } => file_args.output_file.as_ref(),
         cli::ExportCommand::Selection { file_args, .. } => file_args.output_file.as_ref(),
         cli::ExportCommand::DocPages {
-            output_file_stem, ..
+            output_file_stem,
+            zip,
+            ..
         } => {
             if rnote_files.len() > 1 && output_file_stem.is_some() {
                 return Err(anyhow::anyhow!(
                     "The option \"--file-stem\" cannot be used when exporting multiple rnote files."
                 ));
             }
+            if zip.is_some() {
+                if rnote_files.len() > 1 {
+                    return Err(anyhow::anyhow!(
+                        "The option \"--zip\" cannot be used when exporting multiple rnote files."
+                    ));
+                }
+                if separate_background {
+                    return Err(anyhow::anyhow!(
+                        "The options \"--zip\" and \"--separate-background\" cannot be used together."
+                    ));
+                }
+            }
             None
         }
     };
+    if output_file.is_some() && (continue_from.is_some() || state_file.is_some()) {
+        return Err(anyhow::anyhow!(
+            "The options \"--continue-from\" and \"--state-file\" are only supported when exporting multiple files with \"--output-format\"."
+        ));
+    }
+    let loaded_state = match &state_file {
+        Some(state_file) => BatchState::load(state_file).await?,
+        None => None,
+    };
+    let batch_state = match (continue_from, loaded_state) {
+        (Some(continue_from), state) => Some(BatchState {
+            last_exported: Some(continue_from),
+            failed: state.map(|state| state.failed).unwrap_or_default(),
+        }),
+        (None, state) => state,
+    };
+    let (rnote_files, mut batch_state) = match batch_state {
+        Some(mut batch_state) => {
+            let remaining = batch_state.remaining_files(rnote_files)?;
+            if progress != ProgressStyle::Events {
+                if let Some(last_exported) = &batch_state.last_exported {
+                    println!(
+                        "Continuing after \"{}\", {} file(s) remaining, retrying {} failed file(s).",
+                        last_exported.display(),
+                        remaining.len(),
+                        batch_state.failed.len()
+                    );
+                }
+            }
+            if remaining.is_empty() {
+                if let Some(state_file) = &state_file {
+                    BatchState::remove(state_file).await?;
+                }
+                return Ok(());
+            }
+            (remaining, batch_state)
+        }
+        None => (rnote_files, BatchState::default()),
+    };
+    // The failed files of the previous export are retried before the remaining files
+    let retried_files = batch_state.failed.clone();
 
     apply_export_prefs(
         &mut engine,
@@ -53,8 +166,437 @@ pub(crate) async fn run_export(
         no_background,
         no_pattern,
         optimize_printing,
+        strip_metadata,
+        supersampling,
+        min_line_width,
+        svg_classes,
+        svg_origin,
+        svg_comment,
+        svg_units,
+        smooth_strokes,
+        nup,
+        render_backend,
+        ink_color,
+        export_dpi,
+        flip,
+        rotation,
     )?;
 
+    if separate_background
+        && matches!(export_command, cli::ExportCommand::Doc { .. })
+        && !matches!(
+            engine.export_prefs.doc_export_prefs.export_format,
+            DocExportFormat::Svg
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "The option \"--separate-background\" is only supported when exporting to Svg or bitmap image formats."
+        ));
+    }
+    if matches!(
+        export_command,
+        cli::ExportCommand::Doc {
+            page_labels: Some(_),
+            ..
+        }
+    ) && !matches!(
+        engine.export_prefs.doc_export_prefs.export_format,
+        DocExportFormat::Pdf
+    ) {
+        return Err(anyhow::anyhow!(
+            "The option \"--page-labels\" is only supported when exporting to Pdf."
+        ));
+    }
+    if matches!(
+        export_command,
+        cli::ExportCommand::Doc {
+            split_every: Some(_),
+            ..
+        }
+    ) {
+        if !matches!(
+            engine.export_prefs.doc_export_prefs.export_format,
+            DocExportFormat::Pdf
+        ) {
+            return Err(anyhow::anyhow!(
+                "The option \"--split-every\" is only supported when exporting to Pdf."
+            ));
+        }
+        if open {
+            return Err(anyhow::anyhow!(
+                "The options \"--split-every\" and \"--open\" cannot be used together."
+            ));
+        }
+    }
+    if matches!(
+        export_command,
+        cli::ExportCommand::Doc {
+            paper_size: Some(_),
+            ..
+        }
+    ) && !matches!(
+        engine.export_prefs.doc_export_prefs.export_format,
+        DocExportFormat::Pdf
+    ) {
+        return Err(anyhow::anyhow!(
+            "The option \"--paper-size\" is only supported when exporting to Pdf."
+        ));
+    }
+    if matches!(
+        export_command,
+        cli::ExportCommand::Doc {
+            page_size_from_content: true,
+            ..
+        }
+    ) && matches!(
+        engine.export_prefs.doc_export_prefs.export_format,
+        DocExportFormat::Xopp
+    ) {
+        return Err(anyhow::anyhow!(
+            "The option \"--page-size-from-content\" is not supported when exporting to Xopp."
+        ));
+    }
+    if matches!(
+        export_command,
+        cli::ExportCommand::Doc {
+            center_content: Some(_),
+            ..
+        }
+    ) && matches!(
+        engine.export_prefs.doc_export_prefs.export_format,
+        DocExportFormat::Xopp
+    ) {
+        return Err(anyhow::anyhow!(
+            "The option \"--center-content\" is not supported when exporting to Xopp."
+        ));
+    }
+    if let cli::ExportCommand::Doc {
+        ocr: true,
+        ocr_lang,
+        ..
+    } = &export_command
+    {
+        if !matches!(
+            engine.export_prefs.doc_export_prefs.export_format,
+            DocExportFormat::Pdf
+        ) {
+            return Err(anyhow::anyhow!(
+                "The option \"--ocr\" is only supported when exporting to Pdf."
+            ));
+        }
+        #[cfg(feature = "ocr")]
+        {
+            export_options.ocr = Some(rnote_engine::engine::ExportOcr {
+                lang: ocr_lang.clone(),
+            });
+        }
+        #[cfg(not(feature = "ocr"))]
+        {
+            let _ = ocr_lang;
+            return Err(anyhow::anyhow!(
+                "The option \"--ocr\" requires rnote-cli to be built with the \"ocr\" feature."
+            ));
+        }
+    }
+    if min_line_width > 0.0
+        && matches!(export_command, cli::ExportCommand::Doc { .. })
+        && matches!(
+            engine.export_prefs.doc_export_prefs.export_format,
+            DocExportFormat::Xopp
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "The option \"--min-line-width\" is not supported when exporting to Xopp."
+        ));
+    }
+    let bitmap_export = match &export_command {
+        cli::ExportCommand::Doc { .. } => false,
+        cli::ExportCommand::DocPages { .. } => !matches!(
+            engine.export_prefs.doc_pages_export_prefs.export_format,
+            DocPagesExportFormat::Svg
+        ),
+        cli::ExportCommand::Selection { .. } => !matches!(
+            engine.export_prefs.selection_export_prefs.export_format,
+            SelectionExportFormat::Svg
+        ),
+    };
+    if export_dpi.is_some_and(|(dpi_x, dpi_y)| dpi_x != dpi_y) {
+        if !bitmap_export {
+            return Err(anyhow::anyhow!(
+                "Differing horizontal and vertical resolutions for \"--export-dpi\" are only supported when exporting doc pages or a selection to bitmap images."
+            ));
+        }
+        if nup.is_some() || metadata_json.is_some() {
+            return Err(anyhow::anyhow!(
+                "Differing horizontal and vertical resolutions for \"--export-dpi\" cannot be used together with \"--nup\" or \"--metadata-json\"."
+            ));
+        }
+    }
+    if retina {
+        if matches!(
+            export_command,
+            cli::ExportCommand::DocPages { zip: Some(_), .. }
+        ) {
+            return Err(anyhow::anyhow!(
+                "The options \"--retina\" and \"--zip\" cannot be used together."
+            ));
+        }
+        if !bitmap_export {
+            return Err(anyhow::anyhow!(
+                "The option \"--retina\" is only supported when exporting to bitmap image formats."
+            ));
+        }
+    }
+    if render_backend != render::RenderBackend::Cairo && !bitmap_export {
+        return Err(anyhow::anyhow!(
+            "The option \"--render-backend\" is only supported when exporting to bitmap image formats."
+        ));
+    }
+    if nup.is_some() {
+        let nup_export = match &export_command {
+            cli::ExportCommand::Doc { .. } => matches!(
+                engine.export_prefs.doc_export_prefs.export_format,
+                DocExportFormat::Pdf
+            ),
+            cli::ExportCommand::DocPages { .. } => bitmap_export,
+            cli::ExportCommand::Selection { .. } => false,
+        };
+        if !nup_export {
+            return Err(anyhow::anyhow!(
+                "The option \"--nup\" is only supported when exporting the document to Pdf or doc pages to bitmap image formats."
+            ));
+        }
+        if retina || metadata_json.is_some() {
+            return Err(anyhow::anyhow!(
+                "The option \"--nup\" cannot be used together with \"--retina\" or \"--metadata-json\"."
+            ));
+        }
+        if matches!(
+            export_command,
+            cli::ExportCommand::Doc {
+                page_labels: Some(_),
+                ..
+            } | cli::ExportCommand::DocPages { stdout: true, .. }
+        ) {
+            return Err(anyhow::anyhow!(
+                "The option \"--nup\" cannot be used together with \"--page-labels\" or \"--stdout\"."
+            ));
+        }
+    }
+    if svg_classes
+        || svg_style.is_some()
+        || svg_origin != SvgOrigin::Page
+        || svg_comment
+        || svg_units.is_some()
+    {
+        let svg_export = match &export_command {
+            cli::ExportCommand::Doc { .. } => matches!(
+                engine.export_prefs.doc_export_prefs.export_format,
+                DocExportFormat::Svg
+            ),
+            cli::ExportCommand::DocPages { .. } => matches!(
+                engine.export_prefs.doc_pages_export_prefs.export_format,
+                DocPagesExportFormat::Svg
+            ),
+            cli::ExportCommand::Selection { .. } => matches!(
+                engine.export_prefs.selection_export_prefs.export_format,
+                SelectionExportFormat::Svg
+            ),
+        };
+        if !svg_export {
+            return Err(anyhow::anyhow!(
+                "The options \"--svg-classes\", \"--svg-style\", \"--svg-origin\", \"--svg-comment\" and \"--svg-units\" are only supported when exporting to Svg."
+            ));
+        }
+    }
+    match legend {
+        Some(ExportLegendMode::Append) => {
+            let pdf_export = matches!(export_command, cli::ExportCommand::Doc { .. })
+                && matches!(
+                    engine.export_prefs.doc_export_prefs.export_format,
+                    DocExportFormat::Pdf
+                );
+            if !pdf_export {
+                return Err(anyhow::anyhow!(
+                    "The option \"--legend append\" is only supported when exporting the document as Pdf."
+                ));
+            }
+            export_options.legend_page = true;
+        }
+        Some(ExportLegendMode::Sidecar) => {
+            if matches!(
+                export_command,
+                cli::ExportCommand::DocPages { zip: Some(_), .. }
+            ) {
+                return Err(anyhow::anyhow!(
+                    "The options \"--legend sidecar\" and \"--zip\" cannot be used together."
+                ));
+            }
+        }
+        None => {}
+    }
+    if metadata_json.is_some() {
+        if matches!(export_command, cli::ExportCommand::Doc { .. }) {
+            return Err(anyhow::anyhow!(
+                "The option \"--metadata-json\" is only supported when exporting doc pages or a selection."
+            ));
+        }
+        if rnote_files.len() > 1 {
+            return Err(anyhow::anyhow!(
+                "The option \"--metadata-json\" cannot be used when exporting multiple rnote files."
+            ));
+        }
+    }
+    if let Some(svg_style) = svg_style {
+        let bytes = cli::read_bytes_from_file(&svg_style)
+            .await
+            .with_context(|| format!("Reading stylesheet \"{}\" failed.", svg_style.display()))?;
+        export_options.svg_style = Some(String::from_utf8(bytes).with_context(|| {
+            format!("Stylesheet \"{}\" is not valid Utf-8.", svg_style.display())
+        })?);
+    }
+    if let Some(background_image) = background_image {
+        if matches!(export_command, cli::ExportCommand::Doc { .. })
+            && matches!(
+                engine.export_prefs.doc_export_prefs.export_format,
+                DocExportFormat::Xopp
+            )
+        {
+            return Err(anyhow::anyhow!(
+                "The option \"--background-image\" is not supported when exporting to Xopp."
+            ));
+        }
+        let bytes = cli::read_bytes_from_file(&background_image)
+            .await
+            .with_context(|| {
+                format!(
+                    "Reading background image \"{}\" failed.",
+                    background_image.display()
+                )
+            })?;
+        export_options.background_image = Some(
+            ExportBackgroundImage::from_image_bytes(&bytes, background_image_fit).with_context(
+                || {
+                    format!(
+                        "Loading background image \"{}\" failed.",
+                        background_image.display()
+                    )
+                },
+            )?,
+        );
+    }
+    export_options.strokes_since = since;
+    export_options.stroke_ids = stroke_ids;
+    export_options.on_stroke_error = on_stroke_error;
+    if header.is_some() || footer.is_some() {
+        match &export_command {
+            cli::ExportCommand::Doc { .. }
+                if matches!(
+                    engine.export_prefs.doc_export_prefs.export_format,
+                    DocExportFormat::Xopp
+                ) =>
+            {
+                return Err(anyhow::anyhow!(
+                    "The options \"--header\" and \"--footer\" are not supported when exporting to Xopp."
+                ));
+            }
+            cli::ExportCommand::Selection { .. } => {
+                return Err(anyhow::anyhow!(
+                    "The options \"--header\" and \"--footer\" are not supported when exporting a selection."
+                ));
+            }
+            _ => {}
+        }
+        if !header_footer_margin.is_finite() || header_footer_margin < 0.0 {
+            return Err(anyhow::anyhow!(
+                "The header and footer margin must not be negative."
+            ));
+        }
+        export_options.header_footer = Some(ExportHeaderFooter {
+            header,
+            footer,
+            font_size: header_footer_font_size,
+            margin: header_footer_margin,
+            ..Default::default()
+        });
+    }
+    if page_badge.is_some() {
+        match &export_command {
+            cli::ExportCommand::Doc { .. }
+                if matches!(
+                    engine.export_prefs.doc_export_prefs.export_format,
+                    DocExportFormat::Xopp
+                ) =>
+            {
+                return Err(anyhow::anyhow!(
+                    "The option \"--page-badge\" is not supported when exporting to Xopp."
+                ));
+            }
+            cli::ExportCommand::Selection { .. } => {
+                return Err(anyhow::anyhow!(
+                    "The option \"--page-badge\" is not supported when exporting a selection."
+                ));
+            }
+            _ => {}
+        }
+        export_options.page_badge = page_badge;
+    }
+    if trim_blank_pages.is_some() {
+        if let cli::ExportCommand::Selection { .. } = &export_command {
+            return Err(anyhow::anyhow!(
+                "The option \"--trim-blank-pages\" is not supported when exporting a selection."
+            ));
+        }
+        export_options.trim_blank_pages = trim_blank_pages;
+    }
+    let prefs_map = match prefs_map {
+        Some(prefs_map) => Some(PrefsMap::load_from_file(&prefs_map, &export_command).await?),
+        None => None,
+    };
+    if let cli::ExportCommand::DocPages {
+        stdout: true,
+        page: Some(page),
+        max_stdout_bytes,
+        ..
+    } = &export_command
+    {
+        let [rnote_file] = rnote_files.as_slice() else {
+            return Err(anyhow::anyhow!(
+                "The option \"--stdout\" cannot be used when exporting multiple rnote files."
+            ));
+        };
+        if engine.export_prefs.doc_pages_export_prefs.export_format != DocPagesExportFormat::Png {
+            return Err(anyhow::anyhow!(
+                "The option \"--stdout\" is only supported when exporting to Png."
+            ));
+        }
+        if retina || separate_background || open || metadata_json.is_some() || legend.is_some() {
+            return Err(anyhow::anyhow!(
+                "The option \"--stdout\" cannot be used together with \"--retina\", \"--separate-background\", \"--open\", \"--metadata-json\" or \"--legend\"."
+            ));
+        }
+        if let Some(prefs_map) = &prefs_map {
+            engine.export_prefs = prefs_map.override_export_prefs(
+                engine.export_prefs,
+                &export_command,
+                rnote_file,
+            )?;
+        }
+        return export_doc_page_to_stdout(
+            &mut engine,
+            &export_options,
+            rnote_file,
+            *page as usize,
+            *max_stdout_bytes,
+        )
+        .await;
+    }
+    let export_layers = if separate_background {
+        vec![ExportLayer::Background, ExportLayer::Strokes]
+    } else {
+        vec![ExportLayer::All]
+    };
+
     match output_file {
         Some(output_file) => {
             let Some(rnote_file) = rnote_files.first() else {
@@ -76,40 +618,80 @@ pub(crate) async fn run_export(
 
             let rnote_file_disp = rnote_file.display().to_string();
             let output_file_disp = output_file.display().to_string();
-            let progressbar = cli::new_progressbar(format!(
-                "Exporting \"{rnote_file_disp}\" to: \"{output_file_disp}\"."
-            ));
+            let export_progress = ExportProgress::new(progress, 1);
+            let file_progress = export_progress.start_file(
+                rnote_file,
+                &output_file,
+                format!("Exporting \"{rnote_file_disp}\" to: \"{output_file_disp}\"."),
+            );
 
-            if let Err(e) = export_to_file(
+            if let Some(prefs_map) = &prefs_map {
+                engine.export_prefs = prefs_map.override_export_prefs(
+                    engine.export_prefs,
+                    &export_command,
+                    rnote_file,
+                )?;
+            }
+            match export_to_file(
                 &mut engine,
+                &export_options,
                 rnote_file,
                 output_file,
                 &export_command,
+                &export_layers,
                 on_conflict,
                 &mut on_conflict_overwrite,
+                file_mode,
+                &file_progress.bytes_written,
                 open,
+                progress,
+                file_progress.progressbar.as_ref(),
+                eta_smoothing,
+                retina,
+                metadata_json.as_deref(),
+                legend == Some(ExportLegendMode::Sidecar),
+                min_strokes,
             )
             .await
             {
-                let abandon_msg = format!(
-                    "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" failed, Err {e:?}"
-                );
-                if progressbar.is_hidden() {
-                    println!("{abandon_msg}")
+                Err(e) => {
+                    export_progress.file_failed(
+                        rnote_file,
+                        Some(file_progress),
+                        &e,
+                        format!(
+                            "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" failed, Err {e:?}"
+                        ),
+                    );
+                    export_progress.finish();
+                    return Err(e);
                 }
-                progressbar.abandon_with_message(abandon_msg);
-                return Err(e);
-            } else {
-                let finish_msg =
-                    format!("Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" succeeded.");
-                if progressbar.is_hidden() {
-                    println!("{finish_msg}")
+                Ok(ExportOutcome::SkippedEmpty { strokes }) => {
+                    export_progress.file_skipped(
+                        file_progress,
+                        format!("Skipped \"{rnote_file_disp}\", it has only {strokes} stroke(s)."),
+                    );
+                }
+                Ok(ExportOutcome::Exported) => {
+                    export_progress.file_succeeded(
+                        file_progress,
+                        format!(
+                            "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" succeeded."
+                        ),
+                    );
                 }
-                progressbar.finish_with_message(finish_msg);
             }
+            export_progress.finish();
         }
         None => {
             let exporting_doc_pages = matches!(export_command, cli::ExportCommand::DocPages { .. });
+            // The pages are exported into the output directory or the Zip archive
+            let doc_pages_output = match &export_command {
+                cli::ExportCommand::DocPages {
+                    output_dir, zip, ..
+                } => zip.as_ref().or(output_dir.as_ref()),
+                _ => None,
+            };
             let output_ext = file_ext_from_export_command(&mut engine, &export_command);
             let output_files = rnote_files
                 .iter()
@@ -120,8 +702,12 @@ pub(crate) async fn run_export(
                 })
                 .collect::<Vec<PathBuf>>();
 
+            let export_progress = ExportProgress::new(progress, rnote_files.len());
             for (rnote_file, output_file) in rnote_files.iter().zip(output_files.iter()) {
-                validators::file_has_ext(rnote_file, "rnote")?;
+                if let Err(e) = validators::file_has_ext(rnote_file, "rnote") {
+                    export_progress.finish();
+                    return Err(e);
+                }
                 let output_file = match get_output_file_path(
                     output_file,
                     on_conflict,
@@ -130,7 +716,22 @@ pub(crate) async fn run_export(
                 ) {
                     Ok(file) => file,
                     Err(e) => {
-                        println!("Failed to generate output file path, Err: {e:?}");
+                        export_progress.file_failed(
+                            rnote_file,
+                            None,
+                            &e,
+                            format!("Failed to generate output file path, Err: {e:?}"),
+                        );
+                        let retried = retried_files
+                            .iter()
+                            .any(|file| is_same_file(file, rnote_file));
+                        batch_state.record(rnote_file, false, retried);
+                        if let Some(state_file) = &state_file {
+                            if let Err(e) = batch_state.save(state_file).await {
+                                export_progress.finish();
+                                return Err(e);
+                            }
+                        }
                         continue;
                     }
                 };
@@ -140,49 +741,267 @@ pub(crate) async fn run_export(
                     true => format!("Exporting \"{rnote_file_disp}\"."),
                     false => format!("Exporting \"{rnote_file_disp}\" to: \"{output_file_disp}\"."),
                 };
-                let progressbar = cli::new_progressbar(progressbar_msg);
+                let file_progress = export_progress.start_file(
+                    rnote_file,
+                    doc_pages_output.unwrap_or(&output_file),
+                    progressbar_msg,
+                );
 
-                if let Err(e) = export_to_file(
+                // The overrides of the prefs map only apply to this file
+                let export_prefs = engine.export_prefs;
+                if let Some(prefs_map) = &prefs_map {
+                    match prefs_map.override_export_prefs(export_prefs, &export_command, rnote_file)
+                    {
+                        Ok(overridden) => engine.export_prefs = overridden,
+                        Err(e) => {
+                            export_progress.file_failed(
+                                rnote_file,
+                                Some(file_progress),
+                                &e,
+                                format!("Export \"{rnote_file_disp}\" failed, Err {e:?}"),
+                            );
+                            export_progress.finish();
+                            return Err(e);
+                        }
+                    }
+                }
+                let export_result = export_to_file(
                     &mut engine,
+                    &export_options,
                     &rnote_file,
                     output_file,
                     &export_command,
+                    &export_layers,
                     on_conflict,
                     &mut on_conflict_overwrite,
+                    file_mode,
+                    &file_progress.bytes_written,
                     open,
+                    progress,
+                    file_progress.progressbar.as_ref(),
+                    eta_smoothing,
+                    retina,
+                    metadata_json.as_deref(),
+                    legend == Some(ExportLegendMode::Sidecar),
+                    min_strokes,
                 )
-                .await
-                {
-                    let abandon_msg = match exporting_doc_pages {
-                        true => format!("Export \"{rnote_file_disp}\" failed, Err {e:?}"),
-                        false => format!(
-                        "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" failed, Err {e:?}"
-                    ),
-                    };
-                    if progressbar.is_hidden() {
-                        println!("{abandon_msg}")
-                    }
-                    progressbar.abandon_with_message(abandon_msg);
-                    return Err(e);
-                } else {
-                    let finish_msg = match exporting_doc_pages {
-                        false => format!(
-                            "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" succeeded."
+                .await;
+                engine.export_prefs = export_prefs;
+                match export_result {
+                    Err(e) => {
+                        let abandon_msg = match exporting_doc_pages {
+                            true => format!("Export \"{rnote_file_disp}\" failed, Err {e:?}"),
+                            false => format!(
+                            "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" failed, Err {e:?}"
                         ),
-                        true => format!("Export \"{rnote_file_disp}\" succeeded."),
-                    };
-                    if progressbar.is_hidden() {
-                        println!("{finish_msg}")
+                        };
+                        export_progress.file_failed(
+                            rnote_file,
+                            Some(file_progress),
+                            &e,
+                            abandon_msg,
+                        );
+                        export_progress.finish();
+                        return Err(e);
+                    }
+                    Ok(ExportOutcome::SkippedEmpty { strokes }) => {
+                        export_progress.file_skipped(
+                            file_progress,
+                            format!(
+                                "Skipped \"{rnote_file_disp}\", it has only {strokes} stroke(s)."
+                            ),
+                        );
+                    }
+                    Ok(ExportOutcome::Exported) => {
+                        let finish_msg = match exporting_doc_pages {
+                            false => format!(
+                                "Export \"{rnote_file_disp}\" to: \"{output_file_disp}\" succeeded."
+                            ),
+                            true => format!("Export \"{rnote_file_disp}\" succeeded."),
+                        };
+                        export_progress.file_succeeded(file_progress, finish_msg);
+                    }
+                }
+                let retried = retried_files
+                    .iter()
+                    .any(|file| is_same_file(file, rnote_file));
+                batch_state.record(rnote_file, true, retried);
+                if let Some(state_file) = &state_file {
+                    if let Err(e) = batch_state.save(state_file).await {
+                        export_progress.finish();
+                        return Err(e);
                     }
-                    progressbar.finish_with_message(finish_msg);
                 }
             }
+            export_progress.finish();
+            if let (Some(state_file), true) = (&state_file, batch_state.failed.is_empty()) {
+                BatchState::remove(state_file).await?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// The progress of an export of multiple files, persisted in the state file so that an interrupted export can be
+/// resumed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct BatchState {
+    /// The rnote file that was exported last, made absolute when possible.
+    last_exported: Option<PathBuf>,
+    /// The rnote files that failed to export and are retried when resuming, made absolute when possible.
+    #[serde(default)]
+    failed: Vec<PathBuf>,
+}
+
+impl BatchState {
+    /// Loads the state, returns `None` when the state file doesn't exist.
+    async fn load(state_file: &Path) -> anyhow::Result<Option<Self>> {
+        if !state_file.exists() {
+            return Ok(None);
+        }
+        let bytes = smol::fs::read(state_file).await?;
+        let state = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Reading state file \"{}\" failed.", state_file.display()))?;
+        Ok(Some(state))
+    }
+
+    /// Records that the rnote file was exported or failed.
+    ///
+    /// Retried files were already passed once, so they don't move the file that was exported last.
+    fn record(&mut self, rnote_file: &Path, succeeded: bool, retried: bool) {
+        let rnote_file = rnote_file
+            .canonicalize()
+            .unwrap_or_else(|_| rnote_file.to_path_buf());
+        self.failed.retain(|file| file != &rnote_file);
+        if !succeeded {
+            self.failed.push(rnote_file.clone());
+        }
+        if !retried {
+            self.last_exported = Some(rnote_file);
+        }
+    }
+
+    /// Saves the state atomically, by writing a temporary file next to the state file and renaming it.
+    async fn save(&self, state_file: &Path) -> anyhow::Result<()> {
+        let mut tmp_file_name = state_file
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_default();
+        tmp_file_name.push(".tmp");
+        let tmp_file = state_file.with_file_name(tmp_file_name);
+        smol::fs::write(&tmp_file, serde_json::to_vec_pretty(self)?)
+            .await
+            .with_context(|| format!("Writing state file \"{}\" failed.", tmp_file.display()))?;
+        smol::fs::rename(&tmp_file, state_file)
+            .await
+            .with_context(|| format!("Writing state file \"{}\" failed.", state_file.display()))
+    }
+
+    /// The rnote files that are left to export, the failed files and the files after the file that was exported last,
+    /// in their original order.
+    ///
+    /// Failed files that are no longer exported are forgotten.
+    fn remaining_files(&mut self, rnote_files: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
+        let last_i = match &self.last_exported {
+            Some(last_exported) => {
+                let Some(last_i) = rnote_files
+                    .iter()
+                    .position(|file| is_same_file(file, last_exported))
+                else {
+                    return Err(anyhow::anyhow!(
+                        "The file \"{}\" to continue from is not one of the exported rnote files.",
+                        last_exported.display()
+                    ));
+                };
+                Some(last_i)
+            }
+            None => None,
+        };
+        self.failed
+            .retain(|failed| rnote_files.iter().any(|file| is_same_file(file, failed)));
+        Ok(rnote_files
+            .into_iter()
+            .enumerate()
+            .filter(|(i, file)| {
+                last_i.is_none_or(|last_i| *i > last_i)
+                    || self.failed.iter().any(|failed| is_same_file(file, failed))
+            })
+            .map(|(_, file)| file)
+            .collect())
+    }
+
+    async fn remove(state_file: &Path) -> anyhow::Result<()> {
+        if state_file.exists() {
+            smol::fs::remove_file(state_file).await.with_context(|| {
+                format!("Removing state file \"{}\" failed.", state_file.display())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the paths are equal or point to the same file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || a.canonicalize()
+            .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// The amount of render tasks that fit into the memory limit in megabytes, at most the amount of cpu cores.
+///
+/// A task is estimated to need a multiple of the size of the largest rnote file, because the files are compressed
+/// and the rendered pages are held as uncompressed images, but at least the memory of a large bitmap page.
+fn concurrency_for_memory(
+    rnote_files: &[PathBuf],
+    max_render_memory: u64,
+) -> anyhow::Result<usize> {
+    /// The factor of the rnote file size that a render task is estimated to need.
+    ///
+    /// A conservative guess, not a measured value: the gzip compressed Json of the strokes is usually
+    /// a few times smaller than the loaded strokes, and their rendered images take a multiple of that again.
+    const TASK_MEMORY_FILE_SIZE_FACTOR: u64 = 16;
+    /// The least memory a render task is estimated to need, about a A4 page at 300 dpi with supersampling.
+    const TASK_MEMORY_MIN: u64 = 64 * 1024 * 1024;
+
+    let mut largest_file_size = 0;
+    for rnote_file in rnote_files {
+        let metadata = std::fs::metadata(rnote_file).with_context(|| {
+            format!(
+                "Reading the size of rnote file \"{}\" failed.",
+                rnote_file.display()
+            )
+        })?;
+        largest_file_size = largest_file_size.max(metadata.len());
+    }
+    let task_memory = largest_file_size
+        .saturating_mul(TASK_MEMORY_FILE_SIZE_FACTOR)
+        .max(TASK_MEMORY_MIN);
+    let tasks = (max_render_memory.saturating_mul(1024 * 1024) / task_memory).max(1);
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let concurrency = usize::try_from(tasks).unwrap_or(usize::MAX).min(cores);
+    if cli::verbose() {
+        println!(
+            "Estimated {} MB per render task, rendering up to {concurrency} task(s) in parallel.",
+            task_memory / (1024 * 1024)
+        );
+    }
+    Ok(concurrency)
+}
+
+/// Limits the amount of pages and strokes that are rendered in parallel.
+///
+/// The rendering runs on the global thread pool, so it must be limited before the first export.
+fn limit_render_concurrency(concurrency: usize) -> anyhow::Result<()> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(concurrency)
+        .build_global()
+        .context("Limiting the render concurrency failed.")
+}
+
+#[allow(clippy::too_many_arguments)]
 fn apply_export_prefs(
     engine: &mut Engine,
     export_command: &cli::ExportCommand,
@@ -190,11 +1009,36 @@ fn apply_export_prefs(
     no_background: bool,
     no_pattern: bool,
     optimize_printing: bool,
+    strip_metadata: bool,
+    supersampling: f64,
+    min_line_width: f64,
+    svg_classes: bool,
+    svg_origin: SvgOrigin,
+    svg_comment: bool,
+    svg_units: Option<SvgUnits>,
+    smooth_strokes: StrokeSmoothing,
+    nup: Option<ExportNup>,
+    render_backend: render::RenderBackend,
+    ink_color: Option<Color>,
+    export_dpi: Option<(f64, f64)>,
+    flip: ExportFlip,
+    rotation: ExportRotation,
 ) -> anyhow::Result<()> {
     match &export_command {
         cli::ExportCommand::Doc {
             file_args,
             page_order,
+            orientation,
+            flatten_transparency_to_pdf_background,
+            crop_marks,
+            bleed,
+            paper_size,
+            fit_to_page,
+            margins,
+            page_size_from_content,
+            margin,
+            center_content,
+            ..
         } => {
             engine.export_prefs.doc_export_prefs = create_doc_export_prefs_from_args(
                 output_file,
@@ -202,7 +1046,29 @@ fn apply_export_prefs(
                 no_background,
                 no_pattern,
                 optimize_printing,
+                strip_metadata,
+                supersampling,
+                min_line_width,
+                svg_classes,
+                svg_origin,
+                svg_comment,
+                svg_units,
+                smooth_strokes,
+                ink_color,
+                export_dpi.map(|(dpi_x, _)| dpi_x),
+                flip,
+                rotation,
                 *page_order,
+                *orientation,
+                *flatten_transparency_to_pdf_background,
+                *crop_marks,
+                *bleed,
+                *paper_size,
+                *fit_to_page,
+                *margins,
+                nup,
+                page_size_from_content.then_some(*margin),
+                *center_content,
             )?;
         }
         cli::ExportCommand::DocPages {
@@ -210,6 +1076,13 @@ fn apply_export_prefs(
             page_order,
             bitmap_scalefactor,
             jpeg_quality,
+            jpeg_subsampling,
+            png_palette,
+            dither,
+            gamma,
+            orientation,
+            parallel_pages_threshold,
+            center_content,
             ..
         } => {
             engine.export_prefs.doc_pages_export_prefs = create_doc_pages_export_prefs_from_args(
@@ -217,15 +1090,40 @@ fn apply_export_prefs(
                 no_background,
                 no_pattern,
                 optimize_printing,
+                strip_metadata,
+                supersampling,
+                min_line_width,
+                svg_classes,
+                svg_origin,
+                svg_comment,
+                svg_units,
+                smooth_strokes,
+                ink_color,
+                export_dpi,
+                flip,
+                rotation,
                 *page_order,
                 *bitmap_scalefactor,
                 *jpeg_quality,
+                *jpeg_subsampling,
+                *png_palette,
+                *dither,
+                *gamma,
+                *orientation,
+                render_backend,
+                *parallel_pages_threshold,
+                nup,
+                *center_content,
             )?;
         }
         cli::ExportCommand::Selection {
             file_args,
             bitmap_scalefactor,
             jpeg_quality,
+            jpeg_subsampling,
+            png_palette,
+            dither,
+            gamma,
             margin,
             ..
         } => {
@@ -235,9 +1133,26 @@ fn apply_export_prefs(
                 no_background,
                 no_pattern,
                 optimize_printing,
+                strip_metadata,
+                supersampling,
+                min_line_width,
+                svg_classes,
+                svg_origin,
+                svg_comment,
+                svg_units,
+                smooth_strokes,
+                ink_color,
+                export_dpi,
+                flip,
+                rotation,
                 *bitmap_scalefactor,
                 *jpeg_quality,
+                *jpeg_subsampling,
+                *png_palette,
+                *dither,
+                *gamma,
                 *margin,
+                render_backend,
             )?;
         }
     }
@@ -267,20 +1182,44 @@ fn file_ext_from_export_command(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create_doc_export_prefs_from_args(
     output_file: Option<impl AsRef<Path>>,
     output_format: Option<DocExportFormat>,
     no_background: bool,
     no_pattern: bool,
     optimize_printing: bool,
+    strip_metadata: bool,
+    supersampling: f64,
+    min_line_width: f64,
+    svg_classes: bool,
+    svg_origin: SvgOrigin,
+    svg_comment: bool,
+    svg_units: Option<SvgUnits>,
+    smooth_strokes: StrokeSmoothing,
+    ink_color: Option<Color>,
+    export_dpi: Option<f64>,
+    flip: ExportFlip,
+    rotation: ExportRotation,
     page_order: SplitOrder,
+    orientation: ExportOrientation,
+    pdf_background_color: Option<Color>,
+    crop_marks: bool,
+    bleed: f64,
+    paper_size: Option<PredefinedFormat>,
+    fit_to_page: bool,
+    paper_margins: f64,
+    nup: Option<ExportNup>,
+    page_size_from_content: Option<f64>,
+    center_content: Option<ExportCenterContent>,
 ) -> anyhow::Result<DocExportPrefs> {
     let format = match (output_file, output_format) {
         (Some(file), None) => match file.as_ref().extension().and_then(|ext| ext.to_str()) {
             Some(extension) => doc_export_format_from_ext_str(extension)?,
             None => return Err(anyhow::anyhow!(
-                "The output file \"{}\" needs to have a supported extension to determine its file type.",
-                file.as_ref().display()
+                "The output file \"{}\" needs to have a supported extension to determine its file type. Supported extensions: {}.",
+                file.as_ref().display(),
+                DOC_EXPORT_EXTS.join(", ")
             )),
         },
         (None, Some(out_format)) => out_format,
@@ -302,41 +1241,113 @@ pub(crate) fn create_doc_export_prefs_from_args(
         export_format: format,
         with_background: !no_background,
         with_pattern: !no_pattern,
+        with_strokes: true,
         optimize_printing,
+        strip_metadata,
+        supersampling,
+        min_line_width,
+        svg_classes,
+        svg_origin,
+        svg_comment,
+        svg_units,
+        smooth_strokes,
+        ink_color,
+        dpi: export_dpi,
+        flip,
+        rotation,
         page_order,
+        orientation,
+        pdf_background_color,
+        crop_marks,
+        bleed,
+        paper_size,
+        fit_to_page,
+        paper_margins,
+        nup,
+        page_size_from_content,
+        center_content,
     };
 
     Ok(prefs)
 }
 
+/// The file extensions that are recognized when exporting the document.
+const DOC_EXPORT_EXTS: &[&str] = &["svg", "xopp", "pdf"];
+/// The file extensions that are recognized when exporting the selection.
+const SELECTION_EXPORT_EXTS: &[&str] = &["svg", "png", "jpg", "jpeg"];
+
 fn doc_export_format_from_ext_str(format: &str) -> anyhow::Result<DocExportFormat> {
     match format {
         "svg" => Ok(DocExportFormat::Svg),
         "xopp" => Ok(DocExportFormat::Xopp),
         "pdf" => Ok(DocExportFormat::Pdf),
-        ext => Err(anyhow::anyhow!(
-            "Exporting document to format with extension \"{ext}\" is not supported."
-        )),
+        ext => Err(validators::unsupported_ext_error(ext, DOC_EXPORT_EXTS)
+            .context("Exporting the document to this format is not supported.")),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create_doc_pages_export_prefs_from_args(
     export_format: DocPagesExportFormat,
     no_background: bool,
     no_pattern: bool,
     optimize_printing: bool,
+    strip_metadata: bool,
+    supersampling: f64,
+    min_line_width: f64,
+    svg_classes: bool,
+    svg_origin: SvgOrigin,
+    svg_comment: bool,
+    svg_units: Option<SvgUnits>,
+    smooth_strokes: StrokeSmoothing,
+    ink_color: Option<Color>,
+    export_dpi: Option<(f64, f64)>,
+    flip: ExportFlip,
+    rotation: ExportRotation,
     page_order: SplitOrder,
     bitmap_scalefactor: f64,
     jpeg_quality: u8,
+    jpeg_subsampling: render::JpegSubsampling,
+    png_palette: Option<u16>,
+    dither: render::Dither,
+    gamma: f64,
+    orientation: ExportOrientation,
+    render_backend: render::RenderBackend,
+    parallel_pages_threshold: usize,
+    nup: Option<ExportNup>,
+    center_content: Option<ExportCenterContent>,
 ) -> anyhow::Result<DocPagesExportPrefs> {
     Ok(DocPagesExportPrefs {
         export_format,
         with_background: !no_background,
         with_pattern: !no_pattern,
+        with_strokes: true,
         optimize_printing,
+        strip_metadata,
+        supersampling,
+        min_line_width,
+        svg_classes,
+        svg_origin,
+        svg_comment,
+        svg_units,
+        smooth_strokes,
+        ink_color,
+        dpi: export_dpi.map(|(dpi_x, _)| dpi_x),
+        dpi_y: export_dpi.map(|(_, dpi_y)| dpi_y),
+        flip,
+        rotation,
         page_order,
         bitmap_scalefactor,
         jpeg_quality,
+        jpeg_subsampling,
+        png_palette,
+        dither,
+        gamma,
+        orientation,
+        render_backend,
+        parallel_pages_threshold,
+        nup,
+        center_content,
     })
 }
 
@@ -347,16 +1358,35 @@ pub(crate) fn create_selection_export_prefs_from_args(
     no_background: bool,
     no_pattern: bool,
     optimize_printing: bool,
+    strip_metadata: bool,
+    supersampling: f64,
+    min_line_width: f64,
+    svg_classes: bool,
+    svg_origin: SvgOrigin,
+    svg_comment: bool,
+    svg_units: Option<SvgUnits>,
+    smooth_strokes: StrokeSmoothing,
+    ink_color: Option<Color>,
+    export_dpi: Option<(f64, f64)>,
+    flip: ExportFlip,
+    rotation: ExportRotation,
     bitmap_scalefactor: f64,
     jpeg_quality: u8,
+    jpeg_subsampling: render::JpegSubsampling,
+    png_palette: Option<u16>,
+    dither: render::Dither,
+    gamma: f64,
     margin: f64,
+    render_backend: render::RenderBackend,
 ) -> anyhow::Result<SelectionExportPrefs> {
     let format = match (output_file, output_format) {
         (Some(file), None) => match file.as_ref().extension().and_then(|ext| ext.to_str()) {
             Some(extension) => get_selection_export_format(extension)?,
             None => {
                 return Err(anyhow::anyhow!(
-                    "The output file \"{}\" needs to have a supported extension to determine its file type.", file.as_ref().display()
+                    "The output file \"{}\" needs to have a supported extension to determine its file type. Supported extensions: {}.",
+                    file.as_ref().display(),
+                    SELECTION_EXPORT_EXTS.join(", ")
                 ))
             }
         },
@@ -379,10 +1409,29 @@ pub(crate) fn create_selection_export_prefs_from_args(
         export_format: format,
         with_background: !no_background,
         with_pattern: !no_pattern,
+        with_strokes: true,
         optimize_printing,
+        strip_metadata,
+        supersampling,
+        min_line_width,
+        svg_classes,
+        svg_origin,
+        svg_comment,
+        svg_units,
+        smooth_strokes,
+        ink_color,
+        dpi: export_dpi.map(|(dpi_x, _)| dpi_x),
+        dpi_y: export_dpi.map(|(_, dpi_y)| dpi_y),
+        flip,
+        rotation,
         bitmap_scalefactor,
         jpeg_quality,
+        jpeg_subsampling,
+        png_palette,
+        dither,
+        gamma,
         margin,
+        render_backend,
     };
 
     Ok(prefs)
@@ -393,9 +1442,10 @@ fn get_selection_export_format(format: &str) -> anyhow::Result<SelectionExportFo
         "svg" => Ok(SelectionExportFormat::Svg),
         "png" => Ok(SelectionExportFormat::Png),
         "jpg" | "jpeg" => Ok(SelectionExportFormat::Jpeg),
-        ext => Err(anyhow::anyhow!(
-            "Exporting selection to format with extension \"{ext}\" is not supported."
-        )),
+        ext => Err(
+            validators::unsupported_ext_error(ext, SELECTION_EXPORT_EXTS)
+                .context("Exporting the selection to this format is not supported."),
+        ),
     }
 }
 
@@ -408,6 +1458,11 @@ pub(crate) fn get_output_file_path(
     match export_command {
         // output file will be ignored when parsing output file
         cli::ExportCommand::DocPages { .. } => Ok(initial_output_file.to_path_buf()),
+        // the conflicts are resolved for every file the document is split into
+        cli::ExportCommand::Doc {
+            split_every: Some(_),
+            ..
+        } => Ok(initial_output_file.to_path_buf()),
         _ => Ok(file_conflict_prompt_action(
             initial_output_file,
             on_conflict,
@@ -428,9 +1483,13 @@ pub(crate) fn file_conflict_prompt_action(
     if !output_file.exists() {
         return Ok(None);
     }
-    if !io::stdout().is_terminal() {
+    if matches!(on_conflict, OnConflict::Ask) && cli::assume_yes() {
+        // Asking before overwriting is answered with yes
+        on_conflict = OnConflict::Overwrite;
+    }
+    if matches!(on_conflict, OnConflict::Ask) && !io::stdout().is_terminal() {
         return Err(anyhow::anyhow!(
-            "File conflict for file \"{}\" detected and terminal is not interactive. Option \"--on-conflict\" needs to be supplied.", output_file.display()
+            "File conflict for file \"{}\" detected and terminal is not interactive. Option \"--on-conflict\" or \"--yes\" needs to be supplied.", output_file.display()
         ));
     }
     match on_conflict_overwrite {
@@ -518,78 +1577,934 @@ pub(crate) fn file_conflict_prompt_action(
     }
 }
 
-pub(crate) async fn export_to_file(
-    engine: &mut Engine,
-    rnote_file: impl AsRef<Path>,
-    output_file: impl AsRef<Path>,
-    export_command: &cli::ExportCommand,
-    on_conflict: OnConflict,
-    on_conflict_overwrite: &mut Option<OnConflict>,
-    open: bool,
-) -> anyhow::Result<()> {
+/// Reports the progress of exporting files in the selected style.
+///
+/// The counters are atomic, so that they can be updated from multiple exports at once.
+struct ExportProgress {
+    style: ProgressStyle,
+    total: usize,
+    exported: AtomicUsize,
+    failed: AtomicUsize,
+    /// The files that were skipped because they have no content, see `--skip-empty`.
+    skipped: AtomicUsize,
+    /// The bytes written by the files that finished exporting.
+    bytes_written: AtomicU64,
+    /// The single progress bar when using the aggregate style.
+    aggregate_progressbar: Option<indicatif::ProgressBar>,
+}
+
+/// The progress of a single file that is being exported.
+struct FileProgress {
+    /// The progress bar when a bar for every file is used.
+    progressbar: Option<indicatif::ProgressBar>,
+    rnote_file: PathBuf,
+    output: PathBuf,
+    /// The bytes written to the output files, passed to the export of the file.
+    bytes_written: AtomicU64,
+}
+
+impl ExportProgress {
+    fn new(style: ProgressStyle, total: usize) -> Self {
+        let aggregate_progressbar = (style == ProgressStyle::Aggregate)
+            .then(|| cli::new_progressbar(format!("Exported 0/{total}, 0 failed, 0 B written")));
+        Self {
+            style,
+            total,
+            exported: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            skipped: AtomicUsize::new(0),
+            bytes_written: AtomicU64::new(0),
+            aggregate_progressbar,
+        }
+    }
+
+    /// The output is the output file, or the output directory when exporting the pages.
+    fn start_file(&self, rnote_file: &Path, output: &Path, message: String) -> FileProgress {
+        if self.style == ProgressStyle::Events {
+            emit_event(serde_json::json!({
+                "event": "start",
+                "file": rnote_file.display().to_string(),
+                "output": output.display().to_string(),
+            }));
+        }
+        FileProgress {
+            progressbar: (self.style == ProgressStyle::Bars).then(|| cli::new_progressbar(message)),
+            rnote_file: rnote_file.to_path_buf(),
+            output: output.to_path_buf(),
+            bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds the bytes written by the finished file to the bytes written by the export.
+    fn add_file_bytes_written(&self, file: &FileProgress) {
+        self.bytes_written.fetch_add(
+            file.bytes_written.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn file_succeeded(&self, file: FileProgress, message: String) {
+        self.exported.fetch_add(1, Ordering::Relaxed);
+        self.add_file_bytes_written(&file);
+        if self.style == ProgressStyle::Events {
+            emit_event(serde_json::json!({
+                "event": "done",
+                "file": file.rnote_file.display().to_string(),
+                "output": file.output.display().to_string(),
+                "bytes": file.bytes_written.load(Ordering::Relaxed),
+            }));
+        }
+        if let Some(progressbar) = file.progressbar {
+            if progressbar.is_hidden() {
+                println!("{message}")
+            }
+            progressbar.finish_with_message(message);
+        }
+        self.update_aggregate();
+    }
+
+    fn file_skipped(&self, file: FileProgress, message: String) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        self.add_file_bytes_written(&file);
+        if self.style == ProgressStyle::Events {
+            emit_event(serde_json::json!({
+                "event": "skipped",
+                "file": file.rnote_file.display().to_string(),
+                "message": message,
+            }));
+        }
+        match file.progressbar {
+            Some(progressbar) => {
+                if progressbar.is_hidden() {
+                    println!("{message}")
+                }
+                progressbar.finish_with_message(message);
+            }
+            None => match &self.aggregate_progressbar {
+                Some(aggregate_progressbar) => aggregate_progressbar.println(message),
+                None if self.style != ProgressStyle::Events => println!("{message}"),
+                None => {}
+            },
+        }
+        self.update_aggregate();
+    }
+
+    /// The file is `None` when the export of the rnote file failed before it was started.
+    fn file_failed(
+        &self,
+        rnote_file: &Path,
+        file: Option<FileProgress>,
+        error: &anyhow::Error,
+        message: String,
+    ) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        if let Some(file) = &file {
+            self.add_file_bytes_written(file);
+        }
+        if self.style == ProgressStyle::Events {
+            emit_event(serde_json::json!({
+                "event": "error",
+                "file": rnote_file.display().to_string(),
+                "output": file.as_ref().map(|file| file.output.display().to_string()),
+                "message": format!("{error:#}"),
+            }));
+        }
+        match file.and_then(|file| file.progressbar) {
+            Some(progressbar) => {
+                if progressbar.is_hidden() {
+                    println!("{message}")
+                }
+                progressbar.abandon_with_message(message);
+            }
+            None => match &self.aggregate_progressbar {
+                Some(aggregate_progressbar) => aggregate_progressbar.println(message),
+                None if self.style != ProgressStyle::Events => println!("{message}"),
+                None => {}
+            },
+        }
+        self.update_aggregate();
+    }
+
+    fn aggregate_message(&self) -> String {
+        let skipped = self.skipped.load(Ordering::Relaxed);
+        format!(
+            "Exported {}/{}, {} failed,{} {} written",
+            self.exported.load(Ordering::Relaxed),
+            self.total,
+            self.failed.load(Ordering::Relaxed),
+            if skipped > 0 {
+                format!(" {skipped} skipped,")
+            } else {
+                String::new()
+            },
+            indicatif::HumanBytes(self.bytes_written.load(Ordering::Relaxed))
+        )
+    }
+
+    fn update_aggregate(&self) {
+        if let Some(aggregate_progressbar) = &self.aggregate_progressbar {
+            aggregate_progressbar.set_message(self.aggregate_message());
+        }
+        if self.style == ProgressStyle::Events {
+            emit_event(serde_json::json!({
+                "event": "progress",
+                "unit": "files",
+                "done": self.exported.load(Ordering::Relaxed)
+                    + self.failed.load(Ordering::Relaxed)
+                    + self.skipped.load(Ordering::Relaxed),
+                "total": self.total,
+            }));
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(aggregate_progressbar) = &self.aggregate_progressbar {
+            let message = self.aggregate_message();
+            if aggregate_progressbar.is_hidden() {
+                println!("{message}")
+            }
+            aggregate_progressbar.finish_with_message(message);
+        }
+    }
+}
+
+/// Estimates the remaining time of an export from its progress reports.
+///
+/// The rate is smoothed with an exponential moving average, so that the estimate stays stable when the time it takes
+/// to draw the strokes varies a lot, e.g. between pages with many images and pages with only a few strokes.
+pub(crate) struct EtaEstimator {
+    /// The weight of the latest rate in the average, in `0.0..=1.0`. 1.0 disables the smoothing.
+    smoothing: f64,
+    /// The time and amount of done units of the last report that made progress.
+    last: Option<(Instant, usize)>,
+    /// The smoothed rate, in units per second.
+    rate: Option<f64>,
+}
+
+impl EtaEstimator {
+    /// The default smoothing factor.
+    pub(crate) const SMOOTHING_DEFAULT: f64 = 0.2;
+
+    fn new(smoothing: f64) -> Self {
+        Self {
+            smoothing: smoothing.clamp(0.0, 1.0),
+            last: None,
+            rate: None,
+        }
+    }
+
+    /// The template of the progress bars of files that report their progress, with the remaining time as prefix.
+    const PROGRESSBAR_TEMPLATE: &'static str =
+        "{spinner} {msg} [{bar:30}] {pos}/{len} strokes, {prefix}";
+
+    /// Records the progress and returns the estimated remaining time, `None` until the rate is known.
+    fn update(&mut self, done: usize, total: usize) -> Option<Duration> {
+        self.update_at(Instant::now(), done, total)
+    }
+
+    /// Records the progress reported at the given time.
+    fn update_at(&mut self, now: Instant, done: usize, total: usize) -> Option<Duration> {
+        match self.last {
+            Some((last_time, last_done)) if done > last_done => {
+                let elapsed = now.duration_since(last_time).as_secs_f64();
+                if elapsed > 0.0 {
+                    let rate = (done - last_done) as f64 / elapsed;
+                    self.rate = Some(match self.rate {
+                        Some(smoothed) => self.smoothing * rate + (1.0 - self.smoothing) * smoothed,
+                        None => rate,
+                    });
+                    self.last = Some((now, done));
+                }
+            }
+            Some(_) => {}
+            None => self.last = Some((now, done)),
+        }
+        let rate = self.rate.filter(|rate| *rate > 0.0)?;
+        Some(Duration::from_secs_f64(
+            total.saturating_sub(done) as f64 / rate,
+        ))
+    }
+
+    /// The remaining time as shown in the progress bars.
+    fn eta_message(eta: Option<Duration>) -> String {
+        match eta {
+            Some(eta) => format!("ETA {}", indicatif::HumanDuration(eta)),
+            None => String::from("ETA unknown"),
+        }
+    }
+
+    /// Switches the progress bar from a spinner to a bar showing the drawn strokes and the remaining time.
+    fn progressbar_callback(
+        mut self,
+        progressbar: indicatif::ProgressBar,
+    ) -> ExportProgressCallback {
+        if let Ok(style) = indicatif::ProgressStyle::with_template(Self::PROGRESSBAR_TEMPLATE) {
+            progressbar.set_style(style.progress_chars("=> "));
+        }
+        progressbar.set_prefix(Self::eta_message(None));
+        Box::new(move |drawn: usize, total: usize| {
+            let eta = self.update(drawn, total);
+            progressbar.set_length(total as u64);
+            progressbar.set_position(drawn as u64);
+            progressbar.set_prefix(Self::eta_message(eta));
+        })
+    }
+
+    /// Emits a progress event with the drawn strokes and the remaining time for every report.
+    fn events_callback(mut self, rnote_file: &Path) -> ExportProgressCallback {
+        let rnote_file = rnote_file.display().to_string();
+        Box::new(move |drawn: usize, total: usize| {
+            let eta = self.update(drawn, total);
+            emit_event(serde_json::json!({
+                "event": "progress",
+                "unit": "strokes",
+                "file": rnote_file,
+                "done": drawn,
+                "total": total,
+                "eta_secs": eta.map(|eta| eta.as_secs_f64()),
+            }))
+        })
+    }
+}
+
+/// Prints the event as a single line of Json on stdout.
+fn emit_event(event: serde_json::Value) {
+    println!("{event}");
+}
+
+/// The parts of the document that are exported into a single output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportLayer {
+    /// Background and strokes together, as configured by the export preferences.
+    All,
+    /// Only the background, without any strokes.
+    Background,
+    /// Only the strokes on a transparent background.
+    Strokes,
+}
+
+impl ExportLayer {
+    /// The suffix that is appended to the file stem of the output file(s).
+    fn file_stem_suffix(&self) -> &'static str {
+        match self {
+            Self::All => "",
+            Self::Background => " - background",
+            Self::Strokes => " - strokes",
+        }
+    }
+
+    /// Adjusts the `with_background` and `with_pattern` toggles for the layer.
+    ///
+    /// Returns (with_background, with_pattern, with_strokes).
+    fn apply_toggles(&self, with_background: bool, with_pattern: bool) -> (bool, bool, bool) {
+        match self {
+            Self::All => (with_background, with_pattern, true),
+            Self::Background => (true, with_pattern, false),
+            Self::Strokes => (false, false, true),
+        }
+    }
+
+    fn output_file(
+        &self,
+        output_file: &Path,
+        on_conflict: OnConflict,
+        on_conflict_overwrite: &mut Option<OnConflict>,
+    ) -> anyhow::Result<PathBuf> {
+        if *self == Self::All {
+            // Conflicts are already resolved for the unmodified output file
+            return Ok(output_file.to_path_buf());
+        }
+        let Some(file_stem) = output_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+        else {
+            return Err(anyhow::anyhow!(
+                "Failed to get file stem from output-file \"{}\".",
+                output_file.display()
+            ));
+        };
+        let mut file_name = format!("{file_stem}{}", self.file_stem_suffix());
+        if let Some(ext) = output_file.extension() {
+            file_name.push('.');
+            file_name.push_str(&ext.to_string_lossy());
+        }
+        let out = output_file.with_file_name(file_name);
+        Ok(file_conflict_prompt_action(&out, on_conflict, on_conflict_overwrite)?.unwrap_or(out))
+    }
+}
+
+/// The outcome of exporting a single rnote file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportOutcome {
+    Exported,
+    /// The file has fewer strokes than required by `--skip-empty` and was not exported.
+    SkippedEmpty {
+        strokes: usize,
+    },
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn export_to_file(
+    engine: &mut Engine,
+    export_options: &ExportOptions,
+    rnote_file: impl AsRef<Path>,
+    output_file: impl AsRef<Path>,
+    export_command: &cli::ExportCommand,
+    export_layers: &[ExportLayer],
+    on_conflict: OnConflict,
+    on_conflict_overwrite: &mut Option<OnConflict>,
+    file_mode: Option<u32>,
+    bytes_written: &AtomicU64,
+    open: bool,
+    progress: ProgressStyle,
+    progressbar: Option<&indicatif::ProgressBar>,
+    eta_smoothing: f64,
+    retina: bool,
+    metadata_json: Option<&Path>,
+    legend_sidecar: bool,
+    min_strokes: Option<usize>,
+) -> anyhow::Result<ExportOutcome> {
+    let load_span = tracing::info_span!("load", file = %rnote_file.as_ref().display());
     let rnote_bytes = cli::read_bytes_from_file(&rnote_file).await?;
     let engine_snapshot = EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?;
     let _ = engine.load_snapshot(engine_snapshot);
+    drop(load_span);
+    ensure_export_stroke_ids_exist(engine, export_options)?;
+    if let Some(min_strokes) = min_strokes {
+        let strokes = engine.export_strokes_count(export_options);
+        if strokes < min_strokes {
+            return Ok(ExportOutcome::SkippedEmpty { strokes });
+        }
+    }
+    let mut export_options = export_options.clone();
+    if let Some(header_footer) = export_options.header_footer.as_mut() {
+        header_footer.filename = rnote_file
+            .as_ref()
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+
+    for export_layer in export_layers {
+        let _export_span = tracing::info_span!("export", layer = ?export_layer);
+        export_layer_to_file(
+            engine,
+            &export_options,
+            rnote_file.as_ref(),
+            output_file.as_ref(),
+            export_command,
+            *export_layer,
+            on_conflict,
+            on_conflict_overwrite,
+            file_mode,
+            bytes_written,
+            open,
+            progress,
+            progressbar,
+            eta_smoothing,
+            retina,
+        )
+        .await?;
+    }
+    if let Some(metadata_json) = metadata_json {
+        export_strokes_metadata_to_file(
+            engine,
+            &export_options,
+            export_command,
+            metadata_json,
+            file_mode,
+            bytes_written,
+        )
+        .await?;
+    }
+    if legend_sidecar {
+        let legend_file =
+            legend_file_path(rnote_file.as_ref(), output_file.as_ref(), export_command)?;
+        let legend_file =
+            file_conflict_prompt_action(&legend_file, on_conflict, on_conflict_overwrite)?
+                .unwrap_or(legend_file);
+        write_output_file(
+            &legend_file,
+            &engine.export_legend_as_svg_bytes(&export_options)?,
+            file_mode,
+            bytes_written,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Writing the legend to \"{}\" failed.",
+                legend_file.display()
+            )
+        })?;
+    }
+    Ok(ExportOutcome::Exported)
+}
+
+/// Writes the bytes to the output file and adds their length to the bytes written by the export of the file.
+async fn write_output_file(
+    output_file: impl AsRef<Path>,
+    bytes: &[u8],
+    file_mode: Option<u32>,
+    bytes_written: &AtomicU64,
+) -> anyhow::Result<()> {
+    cli::create_overwrite_file_w_bytes(output_file, bytes, file_mode).await?;
+    bytes_written.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    Ok(())
+}
+
+/// The path of the legend Svg written next to the export, e.g. "notes - legend.svg".
+///
+/// Exported pages get the legend in their output directory, named after their file stem.
+fn legend_file_path(
+    rnote_file: &Path,
+    output_file: &Path,
+    export_command: &cli::ExportCommand,
+) -> anyhow::Result<PathBuf> {
+    let (dir, file_stem) = match export_command {
+        cli::ExportCommand::DocPages {
+            output_dir: Some(output_dir),
+            output_file_stem,
+            ..
+        } => (
+            output_dir.as_path(),
+            output_file_stem.clone().or_else(|| {
+                rnote_file
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+            }),
+        ),
+        _ => (
+            output_file.parent().unwrap_or(Path::new("")),
+            output_file
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string()),
+        ),
+    };
+    let Some(file_stem) = file_stem else {
+        return Err(anyhow::anyhow!(
+            "Failed to get file stem for the legend of \"{}\".",
+            rnote_file.display()
+        ));
+    };
+    Ok(dir.join(format!("{file_stem} - legend.svg")))
+}
+
+/// Returns an error listing the ids of "--stroke-ids" that don't belong to a stroke of the loaded document.
+fn ensure_export_stroke_ids_exist(
+    engine: &Engine,
+    export_options: &ExportOptions,
+) -> anyhow::Result<()> {
+    let Some(stroke_ids) = &export_options.stroke_ids else {
+        return Ok(());
+    };
+    let missing_ids = engine.store.missing_stroke_ids(stroke_ids);
+    if !missing_ids.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The stroke id(s) {} do not exist in the document.",
+            missing_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Writes exactly the Png bytes of the page (starting at 1) to stdout, e.g. for piping into clipboard tools.
+async fn export_doc_page_to_stdout(
+    engine: &mut Engine,
+    export_options: &ExportOptions,
+    rnote_file: &Path,
+    page: usize,
+    max_stdout_bytes: Option<u64>,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+    ensure_export_stroke_ids_exist(engine, export_options)?;
+    let mut export_options = export_options.clone();
+    if let Some(header_footer) = export_options.header_footer.as_mut() {
+        header_footer.filename = rnote_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    let prefs = engine.export_prefs.doc_pages_export_prefs;
+    let pages_amount = engine
+        .export_pages_bounds(prefs.page_order, &export_options)
+        .len();
+    if page == 0 || page > pages_amount {
+        return Err(anyhow::anyhow!(
+            "Page {page} does not exist, \"{}\" has {pages_amount} page(s).",
+            rnote_file.display()
+        ));
+    }
 
+    export_options.page_range = Some(page - 1..page);
+    let pages_export_bytes = engine
+        .export_doc_pages(Some(prefs), &export_options)
+        .await??;
+    let Some(bytes) = pages_export_bytes.into_iter().next() else {
+        return Err(anyhow::anyhow!(
+            "Exporting page {page} failed, no page exported."
+        ));
+    };
+    if let Some(max_stdout_bytes) = max_stdout_bytes {
+        if bytes.len() as u64 > max_stdout_bytes {
+            return Err(anyhow::anyhow!(
+                "The Png of page {page} has {} bytes, more than the maximum of {max_stdout_bytes} bytes.",
+                bytes.len()
+            ));
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(&bytes)
+        .and_then(|()| stdout.flush())
+        .context("Writing the Png to stdout failed.")?;
+    Ok(())
+}
+
+/// Exports the document, titled with the file name of the output file.
+#[allow(clippy::too_many_arguments)]
+async fn export_doc_bytes(
+    engine: &Engine,
+    export_options: &ExportOptions,
+    rnote_file: &Path,
+    output_file: &Path,
+    prefs: DocExportPrefs,
+    page_labels: Option<Vec<String>>,
+    progress: ProgressStyle,
+    progressbar: Option<&indicatif::ProgressBar>,
+    eta_smoothing: f64,
+) -> anyhow::Result<Vec<u8>> {
+    let Some(export_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!(
+            "Failed to get file name from output-file \"{}\".",
+            output_file.display()
+        ));
+    };
+    match page_labels {
+        Some(page_labels) => {
+            engine
+                .export_doc_as_pdf_w_page_labels(
+                    export_file_name,
+                    Some(prefs),
+                    export_options,
+                    page_labels,
+                )
+                .await?
+        }
+        None if prefs.export_format == DocExportFormat::Pdf
+            && (progressbar.is_some() || progress == ProgressStyle::Events) =>
+        {
+            let eta_estimator = EtaEstimator::new(eta_smoothing);
+            let progress_cb = match progressbar {
+                Some(progressbar) => eta_estimator.progressbar_callback(progressbar.clone()),
+                None => eta_estimator.events_callback(rnote_file),
+            };
+            engine
+                .export_doc_as_pdf_w_progress(
+                    export_file_name,
+                    Some(prefs),
+                    export_options,
+                    progress_cb,
+                )
+                .await?
+        }
+        None => {
+            engine
+                .export_doc(export_file_name, Some(prefs), export_options)
+                .await?
+        }
+    }
+}
+
+/// The path of the file holding the pages in `page_range` when splitting the document,
+/// named with the one-based page numbers, e.g. "notes-pages-1-10.pdf".
+fn split_output_file_path(
+    output_file: &Path,
+    page_range: &std::ops::Range<usize>,
+) -> anyhow::Result<PathBuf> {
+    let Some(file_stem) = output_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!(
+            "Failed to get file stem from output-file \"{}\".",
+            output_file.display()
+        ));
+    };
+    let mut file_name = format!(
+        "{file_stem}-pages-{}-{}",
+        page_range.start + 1,
+        page_range.end
+    );
+    if let Some(ext) = output_file.extension() {
+        file_name.push('.');
+        file_name.push_str(&ext.to_string_lossy());
+    }
+    Ok(output_file.with_file_name(file_name))
+}
+
+/// Writes the metadata of the exported strokes as Json. Must be called after the export,
+/// so that the strokes of a selection export are selected.
+async fn export_strokes_metadata_to_file(
+    engine: &Engine,
+    export_options: &ExportOptions,
+    export_command: &cli::ExportCommand,
+    metadata_json: &Path,
+    file_mode: Option<u32>,
+    bytes_written: &AtomicU64,
+) -> anyhow::Result<()> {
+    let metadata = match export_command {
+        cli::ExportCommand::Doc { .. } => {
+            return Err(anyhow::anyhow!(
+                "Exporting the strokes metadata of a document export is not supported."
+            ))
+        }
+        cli::ExportCommand::DocPages { .. } => serde_json::to_vec_pretty(
+            &engine.export_doc_pages_strokes_metadata(None, export_options),
+        )?,
+        cli::ExportCommand::Selection { .. } => serde_json::to_vec_pretty(
+            &engine
+                .export_selection_strokes_metadata(None, export_options)
+                .context("Exporting the strokes metadata failed, no strokes selected.")?,
+        )?,
+    };
+    write_output_file(metadata_json, &metadata, file_mode, bytes_written)
+        .await
+        .with_context(|| {
+            format!(
+                "Writing the strokes metadata to \"{}\" failed.",
+                metadata_json.display()
+            )
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn export_layer_to_file(
+    engine: &mut Engine,
+    export_options: &ExportOptions,
+    rnote_file: &Path,
+    output_file: &Path,
+    export_command: &cli::ExportCommand,
+    export_layer: ExportLayer,
+    on_conflict: OnConflict,
+    on_conflict_overwrite: &mut Option<OnConflict>,
+    file_mode: Option<u32>,
+    bytes_written: &AtomicU64,
+    open: bool,
+    progress: ProgressStyle,
+    progressbar: Option<&indicatif::ProgressBar>,
+    eta_smoothing: f64,
+    retina: bool,
+) -> anyhow::Result<()> {
     match export_command {
         cli::ExportCommand::Selection {
             selection,
             selection_collision,
             ..
         } => {
+            let mut prefs = engine.export_prefs.selection_export_prefs;
+            (
+                prefs.with_background,
+                prefs.with_pattern,
+                prefs.with_strokes,
+            ) = export_layer.apply_toggles(prefs.with_background, prefs.with_pattern);
+            let output_file =
+                export_layer.output_file(output_file, on_conflict, on_conflict_overwrite)?;
             select_strokes_for_selection_args(engine, selection, *selection_collision);
+            if retina {
+                let export = engine
+                    .export_selection_w_retina(Some(prefs), export_options)
+                    .await??
+                    .context("Exporting selection failed, no strokes selected.")?;
+                let retina_file = file_conflict_prompt_action(
+                    &retina_file_path(&output_file),
+                    on_conflict,
+                    on_conflict_overwrite,
+                )?
+                .unwrap_or_else(|| retina_file_path(&output_file));
+                write_output_file(&output_file, &export.standard, file_mode, bytes_written).await?;
+                write_output_file(&retina_file, &export.retina, file_mode, bytes_written).await?;
+                if open {
+                    cli::open_file_default_app(output_file)?;
+                }
+                return Ok(());
+            }
             let export_bytes = engine
-                .export_selection(None)
+                .export_selection(Some(prefs), export_options)
                 .await??
                 .context("Exporting selection failed, no strokes selected.")?;
-            cli::create_overwrite_file_w_bytes(&output_file, &export_bytes).await?;
+            if prefs.export_format == SelectionExportFormat::Png
+                && progress != ProgressStyle::Events
+            {
+                report_png_palette_savings(prefs.png_palette, [export_bytes.as_slice()]);
+            }
+            write_output_file(&output_file, &export_bytes, file_mode, bytes_written).await?;
             if open {
                 cli::open_file_default_app(output_file)?;
             }
         }
-        cli::ExportCommand::Doc { .. } => {
-            let Some(export_file_name) = output_file
-                .as_ref()
-                .file_name()
-                .map(|s| s.to_string_lossy().to_string())
-            else {
-                return Err(anyhow::anyhow!(
-                    "Failed to get file name from output-file \"{}\".",
-                    output_file.as_ref().display()
-                ));
+        cli::ExportCommand::Doc {
+            page_labels,
+            split_every,
+            ..
+        } => {
+            let mut prefs = engine.export_prefs.doc_export_prefs;
+            (
+                prefs.with_background,
+                prefs.with_pattern,
+                prefs.with_strokes,
+            ) = export_layer.apply_toggles(prefs.with_background, prefs.with_pattern);
+            let output_file =
+                export_layer.output_file(output_file, on_conflict, on_conflict_overwrite)?;
+            let Some(split_every) = split_every else {
+                let export_bytes = export_doc_bytes(
+                    engine,
+                    export_options,
+                    rnote_file,
+                    &output_file,
+                    prefs,
+                    page_labels
+                        .as_ref()
+                        .map(|page_labels| page_labels.0.clone()),
+                    progress,
+                    progressbar,
+                    eta_smoothing,
+                )
+                .await?;
+                write_output_file(&output_file, &export_bytes, file_mode, bytes_written).await?;
+                if open {
+                    cli::open_file_default_app(output_file)?;
+                }
+                return Ok(());
             };
-            let export_bytes = engine.export_doc(export_file_name, None).await??;
-            cli::create_overwrite_file_w_bytes(&output_file, &export_bytes).await?;
-            if open {
-                cli::open_file_default_app(output_file)?;
+
+            let pages_amount = engine
+                .export_pages_bounds(prefs.page_order, export_options)
+                .len();
+            for start in (0..pages_amount).step_by(*split_every) {
+                let page_range = start..(start + split_every).min(pages_amount);
+                let split_file = split_output_file_path(&output_file, &page_range)?;
+                let split_file =
+                    file_conflict_prompt_action(&split_file, on_conflict, on_conflict_overwrite)?
+                        .unwrap_or(split_file);
+                // The labels are assigned to the pages in order
+                let page_labels = page_labels.as_ref().map(|page_labels| {
+                    page_labels
+                        .0
+                        .iter()
+                        .skip(page_range.start)
+                        .take(page_range.len())
+                        .cloned()
+                        .collect()
+                });
+                let split_options = ExportOptions {
+                    page_range: Some(page_range),
+                    ..export_options.clone()
+                };
+                let export_bytes = export_doc_bytes(
+                    engine,
+                    &split_options,
+                    rnote_file,
+                    &split_file,
+                    prefs,
+                    page_labels,
+                    progress,
+                    progressbar,
+                    eta_smoothing,
+                )
+                .await?;
+                write_output_file(&split_file, &export_bytes, file_mode, bytes_written).await?;
             }
         }
         cli::ExportCommand::DocPages {
             output_dir,
             output_file_stem,
             export_format: output_format,
+            zip,
+            zip_folder,
             ..
         } => {
-            validators::path_is_dir(output_dir)?;
+            if let Some(output_dir) = output_dir {
+                validators::path_is_dir(output_dir)?;
+            }
             // The output file cannot be set with this subcommand
-            drop(output_file);
+            let _ = output_file;
 
-            let pages_export_bytes = engine.export_doc_pages(None).await??;
+            let mut prefs = engine.export_prefs.doc_pages_export_prefs;
+            (
+                prefs.with_background,
+                prefs.with_pattern,
+                prefs.with_strokes,
+            ) = export_layer.apply_toggles(prefs.with_background, prefs.with_pattern);
+            let (pages_export_bytes, pages_retina_bytes) = if retina {
+                engine
+                    .export_doc_pages_w_retina(Some(prefs), export_options)
+                    .await??
+                    .into_iter()
+                    .map(|export| (export.standard, Some(export.retina)))
+                    .unzip()
+            } else {
+                let pages_export_bytes = engine
+                    .export_doc_pages(Some(prefs), export_options)
+                    .await??;
+                let pages_amount = pages_export_bytes.len();
+                (pages_export_bytes, vec![None; pages_amount])
+            };
+            if prefs.export_format == DocPagesExportFormat::Png && progress != ProgressStyle::Events
+            {
+                report_png_palette_savings(
+                    prefs.png_palette,
+                    pages_export_bytes.iter().map(|bytes| bytes.as_slice()),
+                );
+            }
             let out_ext = output_format.file_ext();
             let output_file_stem = match output_file_stem {
                 Some(o) => o.clone(),
-                None => match rnote_file.as_ref().file_stem() {
+                None => match rnote_file.file_stem() {
                     Some(stem) => stem.to_string_lossy().to_string(),
                     None => {
                         return Err(anyhow::anyhow!(
                             "Failed to get file stem from rnote file \"{}\"",
-                            rnote_file.as_ref().display()
+                            rnote_file.display()
                         ))
                     }
                 },
+            } + export_layer.file_stem_suffix();
+            if let Some(zip) = zip {
+                let zip = file_conflict_prompt_action(zip, on_conflict, on_conflict_overwrite)?
+                    .unwrap_or_else(|| zip.clone());
+                let zip_bytes = doc_pages_zip_archive(
+                    rnote_file,
+                    pages_export_bytes,
+                    &out_ext,
+                    &output_file_stem,
+                    zip_folder.as_deref(),
+                )?;
+                write_output_file(&zip, &zip_bytes, file_mode, bytes_written).await?;
+                if open {
+                    cli::open_file_default_app(zip)?;
+                }
+                return Ok(());
+            }
+            let Some(output_dir) = output_dir else {
+                return Err(anyhow::anyhow!(
+                    "Either \"--output-dir\" or \"--zip\" must be specified."
+                ));
             };
             let pages_amount = pages_export_bytes.len();
-            for (page_i, bytes) in pages_export_bytes.into_iter().enumerate() {
+            for (page_i, (bytes, retina_bytes)) in pages_export_bytes
+                .into_iter()
+                .zip(pages_retina_bytes)
+                .enumerate()
+            {
                 let output_file = doc_page_determine_output_file(
                     page_i,
                     pages_amount,
@@ -599,12 +2514,26 @@ pub(crate) async fn export_to_file(
                     on_conflict,
                     on_conflict_overwrite,
                 )?;
-                cli::create_overwrite_file_w_bytes(&output_file, &bytes)
+                write_output_file(&output_file, &bytes, file_mode, bytes_written)
                     .await
                     .context(format!(
                         "Failed to export page {page_i} of document \"{}\".",
-                        rnote_file.as_ref().display()
-                    ))?
+                        rnote_file.display()
+                    ))?;
+                if let Some(retina_bytes) = retina_bytes {
+                    let retina_file = file_conflict_prompt_action(
+                        &retina_file_path(&output_file),
+                        on_conflict,
+                        on_conflict_overwrite,
+                    )?
+                    .unwrap_or_else(|| retina_file_path(&output_file));
+                    write_output_file(&retina_file, &retina_bytes, file_mode, bytes_written)
+                        .await
+                        .context(format!(
+                            "Failed to export the retina image of page {page_i} of document \"{}\".",
+                            rnote_file.display()
+                        ))?;
+                }
             }
             if open {
                 cli::open_file_default_app(output_dir)?;
@@ -614,6 +2543,52 @@ pub(crate) async fn export_to_file(
     Ok(())
 }
 
+/// Prints how much smaller the indexed-color Png images are compared to encoding them in truecolor.
+fn report_png_palette_savings<'a>(
+    png_palette: Option<u16>,
+    png_images: impl IntoIterator<Item = &'a [u8]>,
+) {
+    let Some(colors) = png_palette else {
+        return;
+    };
+    let sizes = png_images
+        .into_iter()
+        .map(|bytes| -> anyhow::Result<(usize, usize)> {
+            let mut truecolor = io::Cursor::new(Vec::new());
+            image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?
+                .to_rgba8()
+                .write_to(&mut truecolor, image::ImageFormat::Png)?;
+            Ok((bytes.len(), truecolor.into_inner().len()))
+        })
+        .try_fold((0, 0), |(indexed, truecolor), sizes| {
+            sizes.map(|(i, t)| (indexed + i, truecolor + t))
+        });
+    match sizes {
+        Ok((indexed_size, truecolor_size)) if truecolor_size > 0 => println!(
+            "Png palette of {colors} colors: {} instead of {} in truecolor ({:.0}% smaller).",
+            indicatif::HumanBytes(indexed_size as u64),
+            indicatif::HumanBytes(truecolor_size as u64),
+            (1.0 - indexed_size as f64 / truecolor_size as f64) * 100.0
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::debug!("Comparing indexed Png size to truecolor failed, Err: {e:?}"),
+    }
+}
+
+/// The path of the companion at twice the resolution, with the "@2x" suffix appended to the file stem.
+fn retina_file_path(output_file: &Path) -> PathBuf {
+    let file_stem = output_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut file_name = format!("{file_stem}{}", RetinaExport::FILE_STEM_SUFFIX);
+    if let Some(ext) = output_file.extension() {
+        file_name.push('.');
+        file_name.push_str(&ext.to_string_lossy());
+    }
+    output_file.with_file_name(file_name)
+}
+
 fn select_strokes_for_selection_args(
     engine: &mut Engine,
     selection: &cli::SelectionCommand,
@@ -637,8 +2612,23 @@ fn select_strokes_for_selection_args(
     };
 }
 
+fn doc_page_file_name(
+    page_i: usize,
+    pages_amount: usize,
+    out_ext: &str,
+    output_file_stem: &str,
+) -> String {
+    cli::numbered_file_name(
+        output_file_stem,
+        "page",
+        page_i,
+        pages_amount,
+        &format!(".{out_ext}"),
+    )
+}
+
 fn doc_page_determine_output_file(
-    mut page_i: usize,
+    page_i: usize,
     pages_amount: usize,
     output_dir: &Path,
     out_ext: &str,
@@ -646,12 +2636,11 @@ fn doc_page_determine_output_file(
     on_conflict: OnConflict,
     on_conflict_overwrite: &mut Option<OnConflict>,
 ) -> anyhow::Result<PathBuf> {
-    // user facing number is one-indexed
-    page_i += 1;
-    let leading_zeros = pages_amount.to_string().len();
-    let mut out = output_dir.join(format!(
-        "{output_file_stem} - page {number}.{out_ext}",
-        number = format_args!("{page_i:0fill$}", fill = leading_zeros)
+    let mut out = output_dir.join(doc_page_file_name(
+        page_i,
+        pages_amount,
+        out_ext,
+        output_file_stem,
     ));
     if let Some(new_out) =
         file_conflict_prompt_action(out.as_ref(), on_conflict, on_conflict_overwrite)?
@@ -660,3 +2649,357 @@ fn doc_page_determine_output_file(
     }
     Ok(out)
 }
+
+/// Packs the exported pages into a Zip archive, together with an "index.json" manifest.
+///
+/// All entries are placed inside `folder` when it is set.
+fn doc_pages_zip_archive(
+    rnote_file: &Path,
+    pages_export_bytes: Vec<Vec<u8>>,
+    out_ext: &str,
+    output_file_stem: &str,
+    folder: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
+    let folder = folder
+        .map(|f| f.trim_matches('/'))
+        .filter(|f| !f.is_empty())
+        .map(|f| format!("{f}/"))
+        .unwrap_or_default();
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    let pages_amount = pages_export_bytes.len();
+    let mut pages = Vec::with_capacity(pages_amount);
+
+    for (page_i, bytes) in pages_export_bytes.into_iter().enumerate() {
+        let file_name = doc_page_file_name(page_i, pages_amount, out_ext, output_file_stem);
+        writer
+            .start_file(format!("{folder}{file_name}"), options)
+            .with_context(|| format!("Adding page {page_i} to the Zip archive failed."))?;
+        io::Write::write_all(&mut writer, &bytes)
+            .with_context(|| format!("Writing page {page_i} to the Zip archive failed."))?;
+        pages.push(serde_json::json!({
+            "page": page_i + 1,
+            "file": file_name,
+            "size": bytes.len(),
+        }));
+    }
+
+    let index = serde_json::json!({
+        "document": rnote_file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "format": out_ext,
+        "pages_amount": pages_amount,
+        "pages": pages,
+    });
+    writer
+        .start_file(format!("{folder}index.json"), options)
+        .context("Adding the index to the Zip archive failed.")?;
+    io::Write::write_all(
+        &mut writer,
+        serde_json::to_string_pretty(&index)?.as_bytes(),
+    )
+    .context("Writing the index to the Zip archive failed.")?;
+
+    Ok(writer
+        .finish()
+        .context("Finishing the Zip archive failed.")?
+        .into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        concurrency_for_memory, doc_export_format_from_ext_str, export_to_file, legend_file_path,
+        BatchState, EtaEstimator, ExportProgress,
+    };
+    use crate::cli::tests::temp_test_dir;
+    use crate::cli::{self, OnConflict, ProgressStyle};
+    use clap::Parser;
+    use rnote_compose::shapes::{Rectangle, Shape};
+    use rnote_compose::style::smooth::SmoothOptions;
+    use rnote_compose::{Color, Style};
+    use rnote_engine::engine::export::{DocExportFormat, ExportOptions};
+    use rnote_engine::strokes::{ShapeStroke, Stroke};
+    use rnote_engine::Engine;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn concurrency_for_memory_fits_the_tasks_into_the_limit() {
+        let dir = temp_test_dir("concurrency-for-memory");
+        let small_file = dir.join("small.rnote");
+        let large_file = dir.join("large.rnote");
+        std::fs::write(&small_file, [0; 1024]).unwrap();
+        // 8 MB, estimated to need 128 MB per task
+        std::fs::File::create(&large_file)
+            .unwrap()
+            .set_len(8 * 1024 * 1024)
+            .unwrap();
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        // Small files need at least the minimum memory of 64 MB per task
+        let small_files = [small_file.clone()];
+        assert_eq!(
+            concurrency_for_memory(&small_files, 128).unwrap(),
+            2.min(cores)
+        );
+        // The largest file determines the memory of a task
+        let files = [small_file.clone(), large_file];
+        assert_eq!(concurrency_for_memory(&files, 256).unwrap(), 2.min(cores));
+        assert_eq!(concurrency_for_memory(&files, 100_000).unwrap(), cores);
+        // At least one task is always run
+        assert_eq!(concurrency_for_memory(&files, 0).unwrap(), 1);
+        assert!(concurrency_for_memory(&[dir.join("missing.rnote")], 128).is_err());
+    }
+
+    fn export_command(args: &[&str]) -> cli::ExportCommand {
+        let cli = cli::Cli::parse_from(["rnote-cli", "export"].iter().chain(args));
+        let cli::Command::Export { export_command, .. } = cli.command else {
+            panic!("expected the export command");
+        };
+        export_command
+    }
+
+    #[test]
+    fn legend_sidecar_is_written_next_to_the_export() {
+        let dir = temp_test_dir("legend-sidecar");
+        let rnote_file = dir.join("notes.rnote");
+        let mut engine = Engine::default();
+        let stroke = Stroke::ShapeStroke(ShapeStroke::new(
+            Shape::Rectangle(Rectangle::from_corners(
+                na::vector![10.0, 10.0],
+                na::vector![60.0, 60.0],
+            )),
+            Style::Smooth(SmoothOptions {
+                stroke_color: Some(Color::new(1.0, 0.0, 0.0, 1.0)),
+                ..Default::default()
+            }),
+        ));
+        let _ = engine.import_generated_content(vec![(stroke, None)], false);
+        let rnote_bytes = smol::block_on(async {
+            engine
+                .save_as_rnote_bytes(String::from("notes.rnote"))
+                .await
+                .unwrap()
+        })
+        .unwrap();
+        std::fs::write(&rnote_file, rnote_bytes).unwrap();
+
+        // Without export layers only the legend is written
+        let doc_export_command = export_command(&["doc", "--output-format", "svg"]);
+        let bytes_written = AtomicU64::new(0);
+        smol::block_on(export_to_file(
+            &mut Engine::default(),
+            &ExportOptions::default(),
+            &rnote_file,
+            dir.join("notes.svg"),
+            &doc_export_command,
+            &[],
+            OnConflict::Overwrite,
+            &mut None,
+            None,
+            &bytes_written,
+            false,
+            ProgressStyle::None,
+            None,
+            1.0,
+            false,
+            None,
+            true,
+            None,
+        ))
+        .unwrap();
+        let legend = std::fs::read(dir.join("notes - legend.svg")).unwrap();
+        assert!(legend.starts_with(b"<?xml"));
+        assert!(!dir.join("notes.svg").exists());
+        assert_eq!(bytes_written.load(Ordering::Relaxed), legend.len() as u64);
+
+        // Exported pages get the legend in their output directory
+        let output_dir = dir.join("pages");
+        let output_dir_arg = output_dir.to_string_lossy().to_string();
+        let doc_pages_export_command = export_command(&[
+            "doc-pages",
+            "--output-dir",
+            &output_dir_arg,
+            "--export-format",
+            "png",
+        ]);
+        assert_eq!(
+            legend_file_path(&rnote_file, &rnote_file, &doc_pages_export_command).unwrap(),
+            output_dir.join("notes - legend.svg")
+        );
+        let doc_pages_export_command = export_command(&[
+            "doc-pages",
+            "--output-dir",
+            &output_dir_arg,
+            "--export-format",
+            "png",
+            "--output-file-stem",
+            "page",
+        ]);
+        assert_eq!(
+            legend_file_path(&rnote_file, &rnote_file, &doc_pages_export_command).unwrap(),
+            output_dir.join("page - legend.svg")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn batch_state_resumes_after_the_last_exported_file() {
+        let dir = temp_test_dir("batch-state-resume");
+        let files = ["a", "b", "c"]
+            .map(|name| {
+                let file = dir.join(format!("{name}.rnote"));
+                std::fs::write(&file, []).unwrap();
+                file
+            })
+            .to_vec();
+        let state_file = dir.join("state.json");
+
+        let mut state = BatchState::default();
+        state.record(&files[0], true, false);
+        smol::block_on(state.save(&state_file)).unwrap();
+        // The state is renamed into place
+        assert!(!dir.join("state.json.tmp").exists());
+        let mut loaded = smol::block_on(BatchState::load(&state_file))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.remaining_files(files.clone()).unwrap(), files[1..]);
+
+        let mut unknown = BatchState {
+            last_exported: Some(dir.join("unknown.rnote")),
+            failed: vec![],
+        };
+        assert!(unknown.remaining_files(files).is_err());
+        assert!(smol::block_on(BatchState::load(&dir.join("missing.json")))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn batch_state_retries_failed_files() {
+        let dir = temp_test_dir("batch-state-failed");
+        let files = ["a", "b", "c", "d"]
+            .map(|name| {
+                let file = dir.join(format!("{name}.rnote"));
+                std::fs::write(&file, []).unwrap();
+                file
+            })
+            .to_vec();
+        let state_file = dir.join("state.json");
+
+        // A later success doesn't drop the failed file
+        let mut state = BatchState::default();
+        state.record(&files[0], true, false);
+        state.record(&files[1], false, false);
+        state.record(&files[2], true, false);
+        smol::block_on(state.save(&state_file)).unwrap();
+        let mut loaded = smol::block_on(BatchState::load(&state_file))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            loaded.remaining_files(files.clone()).unwrap(),
+            vec![files[1].clone(), files[3].clone()]
+        );
+
+        // Retrying the failed file doesn't move the resume position back
+        loaded.record(&files[1], true, true);
+        assert!(loaded.failed.is_empty());
+        assert_eq!(
+            loaded.remaining_files(files.clone()).unwrap(),
+            vec![files[3].clone()]
+        );
+
+        // Failed files that are no longer exported are forgotten
+        state.remaining_files(files[2..].to_vec()).unwrap();
+        assert!(state.failed.is_empty());
+    }
+
+    #[test]
+    fn eta_smooths_the_drawing_rate() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let eta_secs = |estimator: &mut EtaEstimator, secs: u64, done: usize| {
+            estimator
+                .update_at(at(secs), done, 100)
+                .map(|eta| eta.as_secs_f64())
+        };
+
+        let mut smoothed = EtaEstimator::new(0.5);
+        // The rate is unknown until the first progress
+        assert_eq!(eta_secs(&mut smoothed, 0, 0), None);
+        // 10 strokes per second, 90 remaining
+        assert_eq!(eta_secs(&mut smoothed, 1, 10), Some(9.0));
+        // The latest rate of 20 strokes per second is averaged with the previous one
+        let eta = eta_secs(&mut smoothed, 2, 30).unwrap();
+        assert!((eta - 70.0 / 15.0).abs() < 1e-9, "{eta}");
+        // Reports without progress don't change the rate
+        let eta = eta_secs(&mut smoothed, 5, 30).unwrap();
+        assert!((eta - 70.0 / 15.0).abs() < 1e-9, "{eta}");
+
+        let mut unsmoothed = EtaEstimator::new(1.0);
+        assert_eq!(eta_secs(&mut unsmoothed, 0, 0), None);
+        assert_eq!(eta_secs(&mut unsmoothed, 1, 10), Some(9.0));
+        assert_eq!(eta_secs(&mut unsmoothed, 2, 30), Some(3.5));
+        assert_eq!(eta_secs(&mut unsmoothed, 3, 100), Some(0.0));
+    }
+
+    #[test]
+    fn eta_message_shows_unknown_remaining_time() {
+        assert_eq!(EtaEstimator::eta_message(None), "ETA unknown");
+        assert_eq!(
+            EtaEstimator::eta_message(Some(Duration::from_secs(90))),
+            format!("ETA {}", indicatif::HumanDuration(Duration::from_secs(90)))
+        );
+    }
+
+    #[test]
+    fn doc_export_format_from_unknown_ext_suggests_closest() {
+        assert_eq!(
+            doc_export_format_from_ext_str("pdf").unwrap(),
+            DocExportFormat::Pdf
+        );
+        let err = format!("{:#}", doc_export_format_from_ext_str("pfd").unwrap_err());
+        assert!(err.contains("did you mean \".pdf\"?"), "{err}");
+        assert!(
+            err.contains("Supported extensions: .svg, .xopp, .pdf."),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn export_progress_counts_the_bytes_of_its_own_files() {
+        let export_progress = ExportProgress::new(ProgressStyle::None, 3);
+        let file =
+            export_progress.start_file(Path::new("a.rnote"), Path::new("a.pdf"), String::new());
+        file.bytes_written.fetch_add(1000, Ordering::Relaxed);
+        export_progress.file_succeeded(file, String::new());
+        let file =
+            export_progress.start_file(Path::new("b.rnote"), Path::new("b.pdf"), String::new());
+        file.bytes_written.fetch_add(24, Ordering::Relaxed);
+        export_progress.file_failed(
+            Path::new("b.rnote"),
+            Some(file),
+            &anyhow::anyhow!("failed"),
+            String::new(),
+        );
+        assert_eq!(
+            export_progress.aggregate_message(),
+            "Exported 1/3, 1 failed, 1.00 KiB written"
+        );
+
+        // A second export starts counting from zero
+        let export_progress = ExportProgress::new(ProgressStyle::None, 1);
+        assert_eq!(
+            export_progress.aggregate_message(),
+            "Exported 0/1, 0 failed, 0 B written"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// Imports
+use crate::{cli, validators};
+use anyhow::Context;
+use rnote_compose::shapes::Shapeable;
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::strokes::Stroke;
+use rnote_engine::StrokeStore;
+use std::path::Path;
+
+/// The file name of the manifest that lists the extracted images.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Extracts the images of all bitmap image strokes of the rnote file as Png files into the output directory.
+///
+/// The images are re-encoded from the pixel data stored in the strokes, so they have the resolution they were
+/// imported with. A manifest is written next to them, with the position of every image on the document.
+pub(crate) async fn run_extract_images(rnote_file: &Path, output_dir: &Path) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_dir(output_dir)?;
+    let Some(output_file_stem) = rnote_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!(
+            "Failed to get file stem from rnote file \"{}\"",
+            rnote_file.display()
+        ));
+    };
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let snapshot = EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?;
+    let bitmapimages = snapshot
+        .stroke_components
+        .iter()
+        .filter_map(|(key, stroke)| match stroke.as_ref() {
+            Stroke::BitmapImage(bitmapimage) => Some((key, bitmapimage)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let images_amount = bitmapimages.len();
+    let mut images = Vec::with_capacity(images_amount);
+
+    for (i, (key, bitmapimage)) in bitmapimages.into_iter().enumerate() {
+        let file_name =
+            cli::numbered_file_name(&output_file_stem, "image", i, images_amount, ".png");
+        let png_bytes = bitmapimage
+            .image
+            .clone()
+            .into_encoded_bytes(image::ImageFormat::Png, None)
+            .with_context(|| format!("Encoding image {} failed.", i + 1))?;
+        cli::create_overwrite_file_w_bytes(&output_dir.join(&file_name), &png_bytes, None)
+            .await
+            .with_context(|| format!("Writing image {} failed.", i + 1))?;
+        let bounds = bitmapimage.bounds();
+        images.push(serde_json::json!({
+            "index": i + 1,
+            "file": file_name,
+            "stroke_id": StrokeStore::stroke_id(key),
+            "x": bounds.mins[0],
+            "y": bounds.mins[1],
+            "width": bounds.extents()[0],
+            "height": bounds.extents()[1],
+            "pixel_width": bitmapimage.image.pixel_width,
+            "pixel_height": bitmapimage.image.pixel_height,
+        }));
+    }
+
+    let manifest = serde_json::json!({
+        "document": rnote_file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "images_amount": images_amount,
+        "images": images,
+    });
+    cli::create_overwrite_file_w_bytes(
+        &output_dir.join(MANIFEST_FILE_NAME),
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        None,
+    )
+    .await
+    .context("Writing the manifest failed.")?;
+    println!(
+        "Extracted {images_amount} image(s) to \"{}\".",
+        output_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_extract_images, MANIFEST_FILE_NAME};
+    use crate::cli::tests::temp_test_dir;
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::render;
+    use rnote_engine::strokes::resize::ImageSizeOption;
+    use rnote_engine::strokes::{BitmapImage, Stroke};
+    use rnote_engine::{Engine, StrokeStore};
+    use std::path::Path;
+
+    /// Saves an rnote file with bitmap images of the given pixel sizes.
+    fn save_rnote_file_w_images(rnote_file: &Path, sizes: &[(u32, u32)]) {
+        let mut engine = Engine::default();
+        let strokes = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &(width, height))| {
+                let image = render::Image::from(image::DynamicImage::ImageRgba8(
+                    image::RgbaImage::new(width, height),
+                ));
+                let pos = na::vector![10.0, 10.0 + 50.0 * i as f64];
+                let bitmapimage =
+                    BitmapImage::from_image(image, pos, ImageSizeOption::RespectOriginalSize)
+                        .unwrap();
+                (Stroke::BitmapImage(bitmapimage), None)
+            })
+            .collect();
+        let _ = engine.import_generated_content(strokes, false);
+        let rnote_bytes = smol::block_on(async {
+            engine
+                .save_as_rnote_bytes(String::from("in.rnote"))
+                .await
+                .unwrap()
+        })
+        .unwrap();
+        std::fs::write(rnote_file, rnote_bytes).unwrap();
+    }
+
+    #[test]
+    fn extract_images_writes_the_images_and_the_manifest() {
+        let dir = temp_test_dir("extract-images");
+        let rnote_file = dir.join("in.rnote");
+        save_rnote_file_w_images(&rnote_file, &[(3, 2), (1, 1)]);
+        let output_dir = dir.join("images");
+        std::fs::create_dir(&output_dir).unwrap();
+
+        smol::block_on(run_extract_images(&rnote_file, &output_dir)).unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(output_dir.join(MANIFEST_FILE_NAME)).unwrap())
+                .unwrap();
+        assert_eq!(manifest["document"], "in.rnote");
+        assert_eq!(manifest["images_amount"], 2);
+        let snapshot = smol::block_on(EngineSnapshot::load_from_rnote_bytes(
+            std::fs::read(&rnote_file).unwrap(),
+        ))
+        .unwrap();
+        let images = manifest["images"].as_array().unwrap();
+        for (image, (key, _)) in images.iter().zip(snapshot.stroke_components.iter()) {
+            assert_eq!(image["stroke_id"], StrokeStore::stroke_id(key));
+            let decoded = image::open(output_dir.join(image["file"].as_str().unwrap())).unwrap();
+            assert_eq!(image["pixel_width"], decoded.width());
+            assert_eq!(image["pixel_height"], decoded.height());
+        }
+        let mut files = images
+            .iter()
+            .map(|image| image["file"].as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        files.sort();
+        assert_eq!(files, ["in - image 1.png", "in - image 2.png"]);
+    }
+
+    #[test]
+    fn extract_images_rejects_invalid_paths() {
+        let dir = temp_test_dir("extract-images-invalid");
+        let rnote_file = dir.join("in.rnote");
+        save_rnote_file_w_images(&rnote_file, &[(1, 1)]);
+
+        // The output must be an existing directory
+        assert!(smol::block_on(run_extract_images(&rnote_file, &dir.join("missing"))).is_err());
+        assert!(smol::block_on(run_extract_images(&rnote_file, &rnote_file)).is_err());
+        assert!(smol::block_on(run_extract_images(&dir.join("in.pdf"), &dir)).is_err());
+    }
+}
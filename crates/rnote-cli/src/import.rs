@@ -1,38 +1,159 @@
 // Imports
-use crate::{cli, validators};
+use crate::cli::{self, InputFormat};
+use crate::validators;
+use anyhow::Context;
+use p2d::bounding_volume::BoundingVolume;
+use rnote_compose::ext::Vector2Ext;
+use rnote_compose::shapes::Shapeable;
+use rnote_compose::transform::Transformable;
+use rnote_compose::SplitOrder;
+use rnote_engine::engine::import::{
+    PdfImportFormFields, PdfImportLayout, PdfImportPadTo, PdfImportPagesType,
+};
 use rnote_engine::engine::EngineSnapshot;
+#[cfg(feature = "epub")]
+use rnote_engine::fileformats::epubformat::EpubFile;
+#[cfg(feature = "markdown")]
+use rnote_engine::fileformats::markdownformat::MarkdownFile;
+#[cfg(feature = "metafile")]
+use rnote_engine::fileformats::metafileformat::MetafileFile;
+#[cfg(any(feature = "markdown", feature = "metafile"))]
+use rnote_engine::fileformats::FileFormatLoader;
+use rnote_engine::render::{self, BackgroundRemoval};
+use rnote_engine::strokes::textstroke::TextStyle;
+use rnote_engine::strokes::{BitmapImage, PdfBitmapPages, Stroke};
 use rnote_engine::Engine;
-use std::path::Path;
+use smol::stream::StreamExt;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn run_import(
     rnote_file: &Path,
-    input_file: &Path,
+    input_files: &[PathBuf],
     xopp_dpi: f64,
+    pdf_pad_to: PdfImportPadTo,
+    pdf_layout: PdfImportLayout,
+    pdf_form_fields: PdfImportFormFields,
+    whiten_threshold: Option<u8>,
+    deskew: bool,
+    remove_background: Option<(BackgroundRemoval, u8)>,
+    allow_partial_images: bool,
+    invert_colors: bool,
+    input_format: Option<InputFormat>,
+    csv_axes: bool,
+    text_style: TextStyle,
+    dedupe_pages: bool,
+    generate_toc: bool,
+    validate_only: bool,
+    validate_images: bool,
+    file_mode: Option<u32>,
 ) -> anyhow::Result<()> {
     validators::file_has_ext(rnote_file, "rnote")?;
-    // Xopp files don't require file extensions
-    validators::path_is_file(input_file)?;
+    // Xopp files don't require file extensions, the other formats are recognized by their content or extension
+    for input_file in input_files {
+        validators::path_is_file(input_file)?;
+    }
+    if validate_images {
+        validate_input_images(input_files, input_format).await?;
+    }
+    if dedupe_pages && pdf_layout == PdfImportLayout::Grid {
+        return Err(anyhow::anyhow!(
+            "The option \"--dedupe-pages\" is not supported with the \"grid\" Pdf layout."
+        ));
+    }
+    let merging_pdfs = input_files.len() > 1 || generate_toc;
+    if merging_pdfs && remove_background.is_some() {
+        return Err(anyhow::anyhow!(
+            "The option \"--remove-background\" is only supported when importing bitmap images."
+        ));
+    }
+    if merging_pdfs && invert_colors {
+        return Err(anyhow::anyhow!(
+            "The option \"--invert-colors\" is only supported when importing bitmap images."
+        ));
+    }
+    if merging_pdfs {
+        let only_pdf_msg =
+            "Importing multiple files or \"--generate-toc\" is only supported for Pdf files.";
+        match input_format {
+            Some(InputFormat::Pdf) => {}
+            Some(_) => return Err(anyhow::anyhow!(only_pdf_msg)),
+            None => {
+                for input_file in input_files {
+                    validators::file_has_ext(input_file, "pdf").context(only_pdf_msg)?;
+                }
+            }
+        }
+    }
+
+    if !validate_only {
+        cli::confirm_overwrite(rnote_file, "Import")?;
+    }
 
     let mut engine = Engine::default();
 
-    apply_import_prefs(&mut engine, xopp_dpi)?;
+    apply_import_prefs(
+        &mut engine,
+        xopp_dpi,
+        pdf_pad_to,
+        pdf_layout,
+        pdf_form_fields,
+        whiten_threshold,
+        deskew,
+        dedupe_pages,
+        allow_partial_images,
+        invert_colors,
+    )?;
 
     let rnote_file_disp = rnote_file.display().to_string();
-    let input_file_disp = input_file.display().to_string();
+    let input_files_disp = input_files
+        .iter()
+        .map(|f| format!("\"{}\"", f.display()))
+        .collect::<Vec<String>>()
+        .join(", ");
     let progressbar = cli::new_progressbar(format!(
-        "Importing \"{input_file_disp}\" to: \"{rnote_file_disp}\""
+        "Importing {input_files_disp} to: \"{rnote_file_disp}\""
     ));
 
-    if let Err(e) = import_file(&mut engine, input_file, rnote_file).await {
+    let res = if merging_pdfs {
+        import_pdf_files(&mut engine, input_files, generate_toc, deskew, dedupe_pages).await
+    } else {
+        import_file(
+            &mut engine,
+            &input_files[0],
+            input_format,
+            csv_axes,
+            text_style,
+            deskew,
+            remove_background,
+            dedupe_pages,
+        )
+        .await
+    };
+    let res = match res {
+        Ok(()) if validate_only => {
+            progressbar.suspend(|| println!("{}", import_report(&engine)));
+            Ok(())
+        }
+        Ok(()) => save_rnote_file(&engine, rnote_file, file_mode).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = res {
         let abandon_msg =
-            format!("Import \"{input_file_disp}\" to \"{rnote_file_disp}\" failed, Err: {e:?}");
+            format!("Import {input_files_disp} to \"{rnote_file_disp}\" failed, Err: {e:?}");
         if progressbar.is_hidden() {
             println!("{abandon_msg}");
         }
         progressbar.abandon_with_message(abandon_msg);
         return Err(e);
     } else {
-        let finish_msg = format!("Import \"{input_file_disp}\" to \"{rnote_file_disp}\" succeeded");
+        let finish_msg = if validate_only {
+            format!("Validated import {input_files_disp}, \"{rnote_file_disp}\" was not written")
+        } else {
+            format!("Import {input_files_disp} to \"{rnote_file_disp}\" succeeded")
+        };
         if progressbar.is_hidden() {
             println!("{finish_msg}");
         }
@@ -42,15 +163,323 @@ pub(crate) async fn run_import(
     Ok(())
 }
 
-pub(crate) fn apply_import_prefs(engine: &mut Engine, xopp_dpi: f64) -> anyhow::Result<()> {
+/// Checks that the input files that are bitmap images can be decoded and reports all that can't.
+///
+/// Only the image headers are read. Fails after all files are checked when any of them is corrupt.
+async fn validate_input_images(
+    input_files: &[PathBuf],
+    input_format: Option<InputFormat>,
+) -> anyhow::Result<()> {
+    let mut images_amount = 0;
+    let mut corrupt_images = vec![];
+    for input_file in input_files {
+        let input_bytes = match cli::read_bytes_from_file(input_file).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                images_amount += 1;
+                corrupt_images.push((input_file, e));
+                continue;
+            }
+        };
+        let format = input_format
+            .or_else(|| sniff_input_format(&input_bytes))
+            .or_else(|| input_format_from_ext(input_file));
+        if !matches!(format, Some(InputFormat::Png | InputFormat::Jpeg)) {
+            if cli::verbose() {
+                println!(
+                    "Skipped validating \"{}\", it is not a bitmap image.",
+                    input_file.display()
+                );
+            }
+            continue;
+        }
+        images_amount += 1;
+        match render::Image::validate_encoded_bytes(&input_bytes) {
+            Ok((width, height)) if cli::verbose() => {
+                println!("\"{}\": {width}x{height} pixels.", input_file.display())
+            }
+            Ok(_) => {}
+            Err(e) => corrupt_images.push((input_file, e)),
+        }
+    }
+
+    if corrupt_images.is_empty() {
+        println!("Validated {images_amount} image(s), none are corrupt.");
+        return Ok(());
+    }
+    println!(
+        "Validated {images_amount} image(s), {} are corrupt:",
+        corrupt_images.len()
+    );
+    for (input_file, e) in corrupt_images.iter() {
+        println!("    \"{}\": {e:#}", input_file.display());
+    }
+    Err(anyhow::anyhow!(
+        "{} of {images_amount} image(s) can't be decoded, the import was not started.",
+        corrupt_images.len()
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_import_prefs(
+    engine: &mut Engine,
+    xopp_dpi: f64,
+    pdf_pad_to: PdfImportPadTo,
+    pdf_layout: PdfImportLayout,
+    pdf_form_fields: PdfImportFormFields,
+    whiten_threshold: Option<u8>,
+    deskew: bool,
+    dedupe_pages: bool,
+    allow_partial_images: bool,
+    invert_colors: bool,
+) -> anyhow::Result<()> {
     engine.import_prefs.xopp_import_prefs.dpi = xopp_dpi;
+    engine.import_prefs.bitmap_import_prefs.allow_partial_images = allow_partial_images;
+    engine.import_prefs.bitmap_import_prefs.invert_colors = invert_colors;
+    engine.import_prefs.pdf_import_prefs.pad_to = pdf_pad_to;
+    engine.import_prefs.pdf_import_prefs.layout = pdf_layout;
+    engine.import_prefs.pdf_import_prefs.form_fields = pdf_form_fields;
+    engine.import_prefs.pdf_import_prefs.whiten_threshold = whiten_threshold;
+    if whiten_threshold.is_some() || deskew || dedupe_pages {
+        // Only bitmap pages can be cleaned up, straightened and compared
+        engine.import_prefs.pdf_import_prefs.pages_type = PdfImportPagesType::Bitmap;
+    }
+    // The imported Pdf is the entire document, so the layout should match it.
+    // The grid layout tiles the pages onto the document format, so it is kept.
+    engine.import_prefs.pdf_import_prefs.adjust_document = pdf_layout == PdfImportLayout::Vertical;
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn import_file(
     engine: &mut Engine,
     input_file: &Path,
+    input_format: Option<InputFormat>,
+    csv_axes: bool,
+    text_style: TextStyle,
+    deskew: bool,
+    remove_background: Option<(BackgroundRemoval, u8)>,
+    dedupe_pages: bool,
+) -> anyhow::Result<()> {
+    let input_bytes = cli::read_bytes_from_file(&input_file).await?;
+    let input_format = input_format
+        .or_else(|| sniff_input_format(&input_bytes))
+        .or_else(|| input_format_from_ext(input_file))
+        // Xopp files don't require file extensions
+        .unwrap_or(InputFormat::Xopp);
+    let whiten_threshold = engine.import_prefs.pdf_import_prefs.whiten_threshold;
+    if whiten_threshold.is_some()
+        && !matches!(
+            input_format,
+            InputFormat::Pdf | InputFormat::Png | InputFormat::Jpeg
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "The options \"--whiten-threshold\" and \"--despeckle\" are only supported when importing Pdf files or bitmap images."
+        ));
+    }
+    if deskew
+        && !matches!(
+            input_format,
+            InputFormat::Pdf | InputFormat::Png | InputFormat::Jpeg
+        )
+    {
+        return Err(anyhow::anyhow!(
+            "The option \"--deskew\" is only supported when importing Pdf files or bitmap images."
+        ));
+    }
+    if engine.import_prefs.bitmap_import_prefs.invert_colors
+        && !matches!(input_format, InputFormat::Png | InputFormat::Jpeg)
+    {
+        return Err(anyhow::anyhow!(
+            "The option \"--invert-colors\" is only supported when importing bitmap images."
+        ));
+    }
+    if remove_background.is_some() && !matches!(input_format, InputFormat::Png | InputFormat::Jpeg)
+    {
+        return Err(anyhow::anyhow!(
+            "The option \"--remove-background\" is only supported when importing bitmap images."
+        ));
+    }
+    if dedupe_pages && input_format != InputFormat::Pdf {
+        return Err(anyhow::anyhow!(
+            "The option \"--dedupe-pages\" is only supported when importing Pdf files."
+        ));
+    }
+    match input_format {
+        InputFormat::Xopp => {
+            let snapshot = EngineSnapshot::load_from_xopp_bytes(
+                input_bytes,
+                engine.import_prefs.xopp_import_prefs,
+            )
+            .await?;
+            let _ = engine.load_snapshot(snapshot);
+        }
+        InputFormat::Pdf => {
+            let mut seen_pages = dedupe_pages.then(HashSet::new);
+            import_pdf_pages(engine, input_bytes, 0.0, deskew, seen_pages.as_mut()).await?;
+        }
+        #[cfg(feature = "epub")]
+        InputFormat::Epub => {
+            let adjust_document = engine.import_prefs.pdf_import_prefs.adjust_document;
+            let pages = engine
+                .generate_epub_pages_from_bytes(input_bytes, na::Vector2::zeros())
+                .await??;
+            let _ = engine.import_generated_content(pages, adjust_document);
+        }
+        #[cfg(feature = "goodnotes")]
+        InputFormat::Goodnotes => {
+            let adjust_document = engine.import_prefs.pdf_import_prefs.adjust_document;
+            let (pages, skipped) = engine
+                .generate_goodnotes_pages_from_bytes(input_bytes, na::Vector2::zeros())
+                .await??;
+            for entry in skipped.iter() {
+                println!("Warning: skipped \"{}\", {}.", entry.path, entry.reason);
+            }
+            println!(
+                "Imported {} page(s) from the GoodNotes archive, skipped {} entries.",
+                pages.len(),
+                skipped.len()
+            );
+            if pages.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "The GoodNotes archive does not contain any pages that can be imported."
+                ));
+            }
+            let _ = engine.import_generated_content(pages, adjust_document);
+        }
+        InputFormat::Png | InputFormat::Jpeg => {
+            if engine.import_prefs.bitmap_import_prefs.allow_partial_images
+                && render::png_is_truncated(&input_bytes)
+            {
+                println!(
+                    "Warning: the Png image appears truncated, the rows that can't be decoded are left transparent."
+                );
+            }
+            let mut bitmapimage = engine
+                .generate_bitmapimage_from_bytes(na::Vector2::zeros(), input_bytes, false)
+                .await??;
+            if let Some(threshold) = whiten_threshold {
+                bitmapimage.image.whiten(threshold);
+            }
+            if let Some((removal, threshold)) = remove_background {
+                match bitmapimage.image.remove_background(removal, threshold) {
+                    Some(background) if cli::verbose() => println!(
+                        "Removed the background color #{:02x}{:02x}{:02x}.",
+                        background[0], background[1], background[2]
+                    ),
+                    Some(_) => {}
+                    None => println!(
+                        "Warning: the background color could not be sampled, the border of the image is transparent."
+                    ),
+                }
+            }
+            if deskew {
+                deskew_page(&mut bitmapimage, 1)?;
+            }
+            let _ = engine
+                .import_generated_content(vec![(Stroke::BitmapImage(bitmapimage), None)], false);
+        }
+        InputFormat::Svg => {
+            let vectorimage = engine
+                .generate_vectorimage_from_bytes(na::Vector2::zeros(), input_bytes, false)
+                .await??;
+            let _ = engine
+                .import_generated_content(vec![(Stroke::VectorImage(vectorimage), None)], false);
+        }
+        #[cfg(feature = "metafile")]
+        InputFormat::Metafile => import_metafile(engine, input_bytes).await?,
+        InputFormat::Csv => import_csv_chart(engine, input_bytes, csv_axes).await?,
+        InputFormat::Txt => import_plain_text(engine, input_bytes, text_style)?,
+        #[cfg(feature = "markdown")]
+        InputFormat::Markdown => import_markdown(engine, input_bytes, text_style)?,
+    }
+
+    Ok(())
+}
+
+/// Recognizes the formats that have a distinct signature at the start of the file.
+fn sniff_input_format(bytes: &[u8]) -> Option<InputFormat> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+    // Xopp files are gzip compressed
+    const GZIP_SIGNATURE: &[u8] = &[0x1f, 0x8b];
+
+    #[cfg(feature = "epub")]
+    if EpubFile::is_epub(bytes) {
+        return Some(InputFormat::Epub);
+    }
+    #[cfg(feature = "metafile")]
+    if MetafileFile::detect(bytes).is_some() {
+        return Some(InputFormat::Metafile);
+    }
+    if bytes.starts_with(b"%PDF-") {
+        Some(InputFormat::Pdf)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        Some(InputFormat::Png)
+    } else if bytes.starts_with(JPEG_SIGNATURE) {
+        Some(InputFormat::Jpeg)
+    } else if bytes.starts_with(GZIP_SIGNATURE) {
+        Some(InputFormat::Xopp)
+    } else {
+        None
+    }
+}
+
+fn input_format_from_ext(input_file: &Path) -> Option<InputFormat> {
+    match input_file
+        .extension()
+        .and_then(|ext| ext.to_str())?
+        .to_lowercase()
+        .as_str()
+    {
+        "xopp" => Some(InputFormat::Xopp),
+        "pdf" => Some(InputFormat::Pdf),
+        #[cfg(feature = "epub")]
+        "epub" => Some(InputFormat::Epub),
+        #[cfg(feature = "goodnotes")]
+        "goodnotes" | "note" => Some(InputFormat::Goodnotes),
+        "png" => Some(InputFormat::Png),
+        "jpg" | "jpeg" => Some(InputFormat::Jpeg),
+        "svg" => Some(InputFormat::Svg),
+        #[cfg(feature = "metafile")]
+        "wmf" | "emf" => Some(InputFormat::Metafile),
+        "csv" => Some(InputFormat::Csv),
+        "txt" => Some(InputFormat::Txt),
+        #[cfg(feature = "markdown")]
+        "md" | "markdown" => Some(InputFormat::Markdown),
+        _ => None,
+    }
+}
+
+/// Prints the amount of pages and strokes and the dimensions of the imported document.
+fn import_report(engine: &Engine) -> String {
+    let snapshot = engine.take_snapshot();
+    let content_bounds = snapshot
+        .stroke_components
+        .values()
+        .map(|stroke| stroke.bounds())
+        .reduce(|acc, bounds| acc.merged(&bounds));
+    let content_bounds = match content_bounds {
+        Some(bounds) => format!(
+            "[{:.1}, {:.1}] - [{:.1}, {:.1}]",
+            bounds.mins[0], bounds.mins[1], bounds.maxs[0], bounds.maxs[1]
+        ),
+        None => String::from("-"),
+    };
+    format!(
+        "    Pages: {}\n    Strokes: {}\n    Document size: {:.1} x {:.1}\n    Content bounds: {content_bounds}",
+        engine.pages_bounds_w_content(SplitOrder::default()).len(),
+        snapshot.stroke_components.len(),
+        snapshot.document.width,
+        snapshot.document.height
+    )
+}
+
+async fn save_rnote_file(
+    engine: &Engine,
     rnote_file: &Path,
+    file_mode: Option<u32>,
 ) -> anyhow::Result<()> {
     let Some(rnote_file_name) = rnote_file
         .file_name()
@@ -58,13 +487,414 @@ pub(crate) async fn import_file(
     else {
         return Err(anyhow::anyhow!("Failed to get filename from rnote_file"));
     };
-    let input_bytes = cli::read_bytes_from_file(&input_file).await?;
-    let snapshot =
-        EngineSnapshot::load_from_xopp_bytes(input_bytes, engine.import_prefs.xopp_import_prefs)
-            .await?;
-    let _ = engine.load_snapshot(snapshot);
     let rnote_bytes = engine.save_as_rnote_bytes(rnote_file_name).await??;
-    cli::create_overwrite_file_w_bytes(&rnote_file, &rnote_bytes).await?;
+    cli::create_overwrite_file_w_bytes(&rnote_file, &rnote_bytes, file_mode).await?;
+    Ok(())
+}
+
+/// Imports the Pdf files one after another into the document.
+///
+/// When `generate_toc` is set, a table of contents listing the files and the document pages they start on
+/// is inserted as the first pages. With `dedupe_pages`, pages identical to a page of any previous file are skipped as well.
+pub(crate) async fn import_pdf_files(
+    engine: &mut Engine,
+    input_files: &[PathBuf],
+    generate_toc: bool,
+    deskew: bool,
+    dedupe_pages: bool,
+) -> anyhow::Result<()> {
+    let mut seen_pages = dedupe_pages.then(HashSet::new);
+    let mut toc_entries = Vec::with_capacity(input_files.len());
+    let mut y_offset = 0.0;
+
+    for input_file in input_files {
+        if engine.import_prefs.pdf_import_prefs.layout == PdfImportLayout::Grid {
+            // Every file starts on a new page of the document format
+            let format_height = engine.document.format.height();
+            y_offset = (y_offset / format_height).ceil() * format_height;
+        }
+        let input_bytes = cli::read_bytes_from_file(input_file).await?;
+        let (_, top, bottom) =
+            import_pdf_pages(engine, input_bytes, y_offset, deskew, seen_pages.as_mut())
+                .await
+                .with_context(|| format!("Importing \"{}\" failed.", input_file.display()))?;
+        let title = input_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| input_file.display().to_string());
+        toc_entries.push((title, top));
+        y_offset = y_offset.max(bottom);
+    }
+
+    if generate_toc {
+        let _ = engine.insert_toc_pages(&toc_entries);
+    }
+    Ok(())
+}
+
+/// Imports the pages of the Pdf progressively as bitmap images,
+/// so that only a limited amount of rendered pages are held in memory at once.
+///
+/// The pages are moved down by `y_offset`, to append them below already imported content.
+/// With `deskew`, slightly rotated pages are straightened.
+/// With `seen_pages`, pages whose pixels hash to one of the seen pages are skipped and the following pages
+/// are moved up to close the gap. The hashes of the imported pages are added.
+/// Returns the amount of imported pages, the top y coordinate of the first page and the bottom y coordinate
+/// of the last page. Both are `y_offset` when no page is imported.
+pub(crate) async fn import_pdf_pages(
+    engine: &mut Engine,
+    input_bytes: Vec<u8>,
+    y_offset: f64,
+    deskew: bool,
+    mut seen_pages: Option<&mut HashSet<u64>>,
+) -> anyhow::Result<(usize, f64, f64)> {
+    let adjust_document = engine.import_prefs.pdf_import_prefs.adjust_document;
+    let mut chunks = engine.generate_pdf_pages_from_bytes_chunked(
+        input_bytes,
+        na::Vector2::zeros(),
+        None,
+        None,
+        PdfBitmapPages::CHUNK_SIZE_DEFAULT,
+    );
+    let mut pages_amount = 0;
+    let mut page = 0;
+    let mut removed = 0;
+    // How far the pages are moved up to close the gaps of the skipped pages
+    let mut removed_height = 0.0;
+    let mut skipped_top = None;
+    let mut first_top = None;
+    let mut bottom = y_offset;
+    let mut max_page_size = na::Vector2::<f64>::zeros();
+    while let Some(chunk) = chunks.next().await {
+        let mut imported = Vec::new();
+        for (mut stroke, layer) in chunk? {
+            page += 1;
+            let top = stroke.bounds().mins[1];
+            if let Some(skipped_top) = skipped_top.take() {
+                removed_height += top - skipped_top;
+            }
+            if let (Some(seen_pages), Stroke::BitmapImage(bitmapimage)) =
+                (seen_pages.as_deref_mut(), &stroke)
+            {
+                if !seen_pages.insert(bitmapimage.image.pixels_hash()) {
+                    if cli::verbose() {
+                        println!("Page {page}: skipped, duplicate of an imported page.");
+                    }
+                    skipped_top = Some(top);
+                    removed += 1;
+                    continue;
+                }
+            }
+            if let Stroke::BitmapImage(bitmapimage) = &mut stroke {
+                if deskew {
+                    deskew_page(bitmapimage, page)?;
+                }
+            }
+            stroke.translate(na::vector![0.0, y_offset - removed_height]);
+            first_top.get_or_insert(stroke.bounds().mins[1]);
+            bottom = bottom.max(stroke.bounds().maxs[1]);
+            imported.push((stroke, layer));
+        }
+        pages_amount += imported.len();
+        max_page_size = max_page_size.maxs(&Engine::generated_content_max_size(&imported));
+        let _ = engine.insert_generated_content(imported, !adjust_document);
+    }
+    // adjusting the document once after the last chunk, to the largest page of all chunks
+    if adjust_document && pages_amount > 0 {
+        let _ = engine.adjust_doc_to_page_size(max_page_size);
+    }
+    if seen_pages.is_some() {
+        println!("Removed {removed} duplicate page(s).");
+    }
+    Ok((pages_amount, first_top.unwrap_or(y_offset), bottom))
+}
+
+/// Straightens the page and prints the detected angle with "--verbose".
+fn deskew_page(bitmapimage: &mut BitmapImage, page: usize) -> anyhow::Result<()> {
+    let angle = bitmapimage
+        .deskew()
+        .with_context(|| format!("Straightening page {page} failed."))?;
+    if cli::verbose() {
+        match angle {
+            Some(angle) => println!(
+                "Page {page}: detected a rotation of {:.2} degrees.",
+                angle.to_degrees()
+            ),
+            None => println!("Page {page}: not enough content to detect a rotation."),
+        }
+    }
+    Ok(())
+}
+
+/// Imports the Csv data as a line chart onto the first page.
+pub(crate) async fn import_csv_chart(
+    engine: &mut Engine,
+    input_bytes: Vec<u8>,
+    with_axes: bool,
+) -> anyhow::Result<()> {
+    let strokes = engine
+        .generate_csv_chart_from_bytes(input_bytes, na::Vector2::zeros(), with_axes)
+        .await??;
+    let _ = engine.import_generated_content(strokes, false);
+    Ok(())
+}
 
+/// Imports the UTF-8 plain text as text strokes, wrapped to the page width.
+pub(crate) fn import_plain_text(
+    engine: &mut Engine,
+    input_bytes: Vec<u8>,
+    text_style: TextStyle,
+) -> anyhow::Result<()> {
+    let text = String::from_utf8(input_bytes).context("Input file is not valid UTF-8 text.")?;
+    let _ = engine.import_text(&text, text_style);
     Ok(())
 }
+
+/// Imports the Wmf or Emf metafile as vector image.
+///
+/// Prints a warning for every type of record that is not supported and skipped, and the total amount of skipped
+/// records.
+#[cfg(feature = "metafile")]
+pub(crate) async fn import_metafile(
+    engine: &mut Engine,
+    input_bytes: Vec<u8>,
+) -> anyhow::Result<()> {
+    let metafile = MetafileFile::load_from_bytes(&input_bytes)?;
+    for (record, amount) in metafile.skipped.iter() {
+        println!("Warning: skipped {amount} unsupported {record} record(s).");
+    }
+    println!(
+        "Imported {} shape(s) from the metafile, skipped {} records.",
+        metafile.shapes.len(),
+        metafile.skipped_amount()
+    );
+    let vectorimage = engine
+        .generate_vectorimage_from_bytes(
+            na::Vector2::zeros(),
+            metafile.to_svg().into_bytes(),
+            false,
+        )
+        .await??;
+    let _ = engine.import_generated_content(vec![(Stroke::VectorImage(vectorimage), None)], false);
+    Ok(())
+}
+
+/// Imports the Markdown text as styled text strokes, wrapped to the page width.
+///
+/// Prints a warning for every construct that is not supported and imported as plain text.
+#[cfg(feature = "markdown")]
+pub(crate) fn import_markdown(
+    engine: &mut Engine,
+    input_bytes: Vec<u8>,
+    text_style: TextStyle,
+) -> anyhow::Result<()> {
+    let markdown = MarkdownFile::load_from_bytes(&input_bytes)?;
+    for warning in markdown.warnings.iter() {
+        println!("Warning: {warning}");
+    }
+    let _ = engine.import_markdown(&markdown, text_style);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import_pdf_files, import_report, run_import};
+    use crate::cli::tests::temp_test_dir;
+    use crate::cli::InputFormat;
+    use rnote_compose::shapes::Shapeable;
+    use rnote_compose::shapes::{Rectangle, Shape};
+    use rnote_compose::Style;
+    use rnote_engine::engine::export::{DocExportFormat, DocExportPrefs, ExportOptions};
+    use rnote_engine::engine::import::PdfImportLayout;
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::strokes::textstroke::TextStyle;
+    use rnote_engine::strokes::{ShapeStroke, Stroke};
+    use rnote_engine::Engine;
+    use std::path::{Path, PathBuf};
+
+    /// A Pdf with three identical pages followed by a different one.
+    fn pdf_w_duplicate_pages() -> Vec<u8> {
+        let mut engine = Engine::default();
+        let page_height = engine.document.format.height();
+        let pages = [(0, 100.0), (1, 100.0), (2, 100.0), (3, 300.0)]
+            .into_iter()
+            .map(|(page, width)| {
+                let offset = page_height * page as f64;
+                let rectangle = Rectangle::from_corners(
+                    na::vector![10.0, offset + 10.0],
+                    na::vector![10.0 + width, offset + 70.0],
+                );
+                (
+                    Stroke::ShapeStroke(ShapeStroke::new(
+                        Shape::Rectangle(rectangle),
+                        Style::default(),
+                    )),
+                    None,
+                )
+            })
+            .collect();
+        let _ = engine.import_generated_content(pages, false);
+        let _ = engine.doc_resize_to_fit_content();
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            ..Default::default()
+        };
+        smol::block_on(engine.export_doc(
+            String::from("duplicates"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap()
+    }
+
+    fn import_pdfs(rnote_file: &Path, input_files: &[PathBuf], dedupe_pages: bool) -> Vec<Stroke> {
+        smol::block_on(run_import(
+            rnote_file,
+            input_files,
+            96.0,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            None,
+            false,
+            None,
+            false,
+            false,
+            None,
+            false,
+            TextStyle::default(),
+            dedupe_pages,
+            false,
+            false,
+            false,
+            None,
+        ))
+        .unwrap();
+        let snapshot = smol::block_on(EngineSnapshot::load_from_rnote_bytes(
+            std::fs::read(rnote_file).unwrap(),
+        ))
+        .unwrap();
+        snapshot
+            .stroke_components
+            .values()
+            .map(|stroke| (**stroke).clone())
+            .collect()
+    }
+
+    #[test]
+    fn validate_only_import_does_not_write_the_file() {
+        let dir = temp_test_dir("validate-only");
+        let input_file = dir.join("notes.txt");
+        std::fs::write(&input_file, "Some notes").unwrap();
+        let rnote_file = dir.join("notes.rnote");
+
+        smol::block_on(run_import(
+            &rnote_file,
+            &[input_file],
+            96.0,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            None,
+            false,
+            None,
+            false,
+            false,
+            Some(InputFormat::Txt),
+            false,
+            TextStyle::default(),
+            false,
+            false,
+            true,
+            false,
+            None,
+        ))
+        .unwrap();
+        assert!(!rnote_file.exists());
+    }
+
+    #[test]
+    fn import_report_counts_pages_and_strokes() {
+        let mut engine = Engine::default();
+        assert!(import_report(&engine).contains("Strokes: 0"));
+        assert!(import_report(&engine).contains("Content bounds: -"));
+
+        let _ = engine.import_text("Some notes", TextStyle::default());
+        let report = import_report(&engine);
+        assert!(report.contains("Pages: 1"), "{report}");
+        assert!(report.contains("Strokes: 1"), "{report}");
+        assert!(!report.contains("Content bounds: -"), "{report}");
+    }
+
+    #[test]
+    fn dedupe_pages_skips_identical_pdf_pages() {
+        let dir = temp_test_dir("dedupe-pages");
+        let pdf_file = dir.join("duplicates.pdf");
+        std::fs::write(&pdf_file, pdf_w_duplicate_pages()).unwrap();
+        let pdf_files = [pdf_file.clone(), pdf_file];
+
+        let pages = import_pdfs(&dir.join("all.rnote"), &pdf_files[..1], false);
+        assert_eq!(pages.len(), 4);
+
+        // The pages are imported as bitmap images, so that they can be compared
+        let pages = import_pdfs(&dir.join("deduped.rnote"), &pdf_files[..1], true);
+        assert_eq!(pages.len(), 2);
+        assert!(pages
+            .iter()
+            .all(|stroke| matches!(stroke, Stroke::BitmapImage(_))));
+
+        // Pages of previous files are duplicates as well
+        let pages = import_pdfs(&dir.join("merged.rnote"), &pdf_files, true);
+        assert_eq!(pages.len(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn grid_layout_starts_every_pdf_file_on_a_new_page() {
+        let dir = temp_test_dir("grid-pdf-files");
+        let pdf_file = dir.join("pages.pdf");
+        std::fs::write(&pdf_file, pdf_w_duplicate_pages()).unwrap();
+        let mut engine = Engine::default();
+        engine.import_prefs.pdf_import_prefs.layout = PdfImportLayout::Grid;
+
+        smol::block_on(import_pdf_files(
+            &mut engine,
+            &[pdf_file.clone(), pdf_file],
+            false,
+            false,
+            false,
+        ))
+        .unwrap();
+        let mut bounds = engine
+            .take_snapshot()
+            .stroke_components
+            .values()
+            .map(|stroke| stroke.bounds())
+            .collect::<Vec<_>>();
+        bounds.sort_by(|a, b| {
+            a.mins[1]
+                .total_cmp(&b.mins[1])
+                .then(a.mins[0].total_cmp(&b.mins[0]))
+        });
+        assert_eq!(bounds.len(), 8);
+        let (first_file, second_file) = bounds.split_at(4);
+        // Two rows with two pages each
+        for file in [first_file, second_file] {
+            assert_eq!(file[0].mins[1], file[1].mins[1]);
+            assert_eq!(file[2].mins[1], file[3].mins[1]);
+            assert!(file[0].mins[0] < file[1].mins[0]);
+            assert!(file[0].maxs[1] < file[2].mins[1]);
+        }
+        // The second file starts at the top of the first document page below the first file
+        let format_height = engine.document.format.height();
+        let first_file_bottom = first_file
+            .iter()
+            .map(|bounds| bounds.maxs[1])
+            .fold(f64::MIN, f64::max);
+        let next_page_top = (first_file_bottom / format_height).ceil() * format_height;
+        assert!((second_file[0].mins[1] - next_page_top).abs() < 1e-6);
+        assert_eq!(second_file[0].mins[0], first_file[0].mins[0]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
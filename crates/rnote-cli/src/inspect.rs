@@ -0,0 +1,81 @@
+// Imports
+use crate::{cli, validators};
+use rnote_engine::engine::{EngineSnapshot, RnoteLoadDiagnostics};
+use rnote_engine::Engine;
+use std::path::PathBuf;
+
+/// Reports whether the rnote files load and, with `strokes`, lists the ids and types of their strokes.
+pub(crate) async fn run_inspect(
+    rnote_files: &[PathBuf],
+    debug: bool,
+    strokes: bool,
+) -> anyhow::Result<()> {
+    let mut failed_files = 0;
+
+    for rnote_file in rnote_files.iter() {
+        validators::file_has_ext(rnote_file, "rnote")?;
+        let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+        let (diagnostics, rnote_bytes) = smol::unblock(move || {
+            (
+                RnoteLoadDiagnostics::from_rnote_bytes(&rnote_bytes),
+                rnote_bytes,
+            )
+        })
+        .await;
+
+        println!("File \"{}\":", rnote_file.display());
+        if debug {
+            println!("    Compressed size: {} bytes", diagnostics.compressed_size);
+            match diagnostics.decompressed_size {
+                Some(size) => println!("    Decompressed size: {size} bytes"),
+                None => println!("    Decompressed size: -"),
+            }
+        }
+        match &diagnostics.format_version {
+            Some(version) => println!("    Format version: {version}"),
+            None => println!("    Format version: -"),
+        }
+        match diagnostics.strokes_amount {
+            Some(amount) => println!("    Strokes: {amount}"),
+            None => println!("    Strokes: -"),
+        }
+        match &diagnostics.failure {
+            None => {
+                println!("    Loads successfully.");
+                if strokes {
+                    for line in stroke_lines(rnote_bytes).await? {
+                        println!("{line}");
+                    }
+                }
+            }
+            Some((location, e)) => {
+                failed_files += 1;
+                if debug {
+                    println!("    Loading failed at `{location}`, Err: {e}");
+                } else {
+                    println!("    Loading failed at `{location}`. Use \"--debug\" for details.");
+                }
+            }
+        }
+    }
+
+    if failed_files > 0 {
+        return Err(anyhow::anyhow!(
+            "{failed_files} of {} file(s) failed to load.",
+            rnote_files.len()
+        ));
+    }
+    Ok(())
+}
+
+/// A line with the id and the type of every stroke, in the order they are rendered.
+async fn stroke_lines(rnote_bytes: Vec<u8>) -> anyhow::Result<Vec<String>> {
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+    Ok(engine
+        .store
+        .stroke_ids_w_type_names()
+        .into_iter()
+        .map(|(id, type_name)| format!("        Stroke {id}: {type_name}"))
+        .collect())
+}
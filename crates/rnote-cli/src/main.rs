@@ -3,10 +3,25 @@
 //! The cli interface is not (yet) stable and could change at any time.
 
 // Modules
+pub(crate) mod animation;
+pub(crate) mod check;
 pub(crate) mod cli;
+pub(crate) mod diff;
 pub(crate) mod export;
+pub(crate) mod extract_images;
 pub(crate) mod import;
+pub(crate) mod inspect;
+pub(crate) mod migrate;
+pub(crate) mod new;
+pub(crate) mod prefs_map;
+pub(crate) mod profile;
+pub(crate) mod relink;
+pub(crate) mod reorder;
+pub(crate) mod repair;
+pub(crate) mod self_test;
+pub(crate) mod stickers;
 pub(crate) mod test;
+pub(crate) mod transform;
 pub(crate) mod validators;
 
 // Renames
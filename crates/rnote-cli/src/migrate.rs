@@ -0,0 +1,53 @@
+// Imports
+use crate::{cli, validators};
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::fileformats::rnoteformat::RnoteFile;
+use rnote_engine::Engine;
+use std::path::Path;
+
+pub(crate) async fn run_migrate(rnote_file: &Path, output_file: &Path) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_file(rnote_file)?;
+    validators::file_has_ext(output_file, "rnote")?;
+    let Some(output_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from output_file"));
+    };
+    // Migrating in place is requested explicitly
+    let in_place = output_file.canonicalize().ok() == rnote_file.canonicalize().ok();
+    if !in_place {
+        cli::confirm_overwrite(output_file, "Migration")?;
+    }
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let (snapshot, migration) = EngineSnapshot::load_from_rnote_bytes_w_migration(rnote_bytes)
+        .await
+        .map_err(|e| e.context(format!("Migrating \"{}\" failed.", rnote_file.display())))?;
+
+    println!(
+        "File \"{}\" was saved with version {}.",
+        rnote_file.display(),
+        migration.from_version
+    );
+    if migration.migrated() {
+        for step in migration.steps.iter() {
+            println!("    Migrated format {step}");
+        }
+    } else {
+        println!("    Already in the newest format, no migration needed.");
+    }
+
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(snapshot);
+    let rnote_bytes = engine.save_as_rnote_bytes(output_file_name).await??;
+    cli::create_overwrite_file_w_bytes(output_file, &rnote_bytes, None).await?;
+    println!(
+        "Saved \"{}\" with version {}.",
+        output_file.display(),
+        RnoteFile::SEMVER
+    );
+
+    Ok(())
+}
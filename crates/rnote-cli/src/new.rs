@@ -0,0 +1,140 @@
+// Imports
+use crate::{cli, validators};
+use rnote_compose::Color;
+use rnote_engine::document::background::PatternStyle;
+use rnote_engine::document::format::{Orientation, PredefinedFormat};
+use rnote_engine::document::{Background, Format};
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::Engine;
+use std::path::Path;
+
+/// Creates a rnote file with the amount of blank pages in the predefined format and with the background.
+pub(crate) async fn run_new(
+    rnote_file: &Path,
+    pages: u32,
+    format: PredefinedFormat,
+    landscape: bool,
+    background_color: Option<Color>,
+    no_pattern: bool,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    let Some(rnote_file_name) = rnote_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from rnote_file"));
+    };
+    cli::confirm_overwrite(rnote_file, "Creating")?;
+
+    let orientation = if landscape {
+        Orientation::Landscape
+    } else {
+        Orientation::Portrait
+    };
+    let Some(format) = Format::from_predefined(format, orientation) else {
+        return Err(anyhow::anyhow!("The format must have a predefined size."));
+    };
+    let mut background = Background::default();
+    if let Some(color) = background_color {
+        background.color = color;
+    }
+    if no_pattern {
+        background.pattern = PatternStyle::None;
+    }
+
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::new_w_pages(pages, format, background));
+    let rnote_bytes = engine.save_as_rnote_bytes(rnote_file_name).await??;
+    cli::create_overwrite_file_w_bytes(rnote_file, &rnote_bytes, None).await?;
+    println!("Created \"{}\" with {pages} page(s).", rnote_file.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_new;
+    use crate::cli::tests::temp_test_dir;
+    use rnote_compose::Color;
+    use rnote_engine::document::background::PatternStyle;
+    use rnote_engine::document::format::{Orientation, PredefinedFormat};
+    use rnote_engine::document::{Background, Format, Layout};
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::Engine;
+    use std::path::Path;
+
+    fn load(rnote_file: &Path) -> Engine {
+        let snapshot = smol::block_on(EngineSnapshot::load_from_rnote_bytes(
+            std::fs::read(rnote_file).unwrap(),
+        ))
+        .unwrap();
+        let mut engine = Engine::default();
+        let _ = engine.load_snapshot(snapshot);
+        engine
+    }
+
+    #[test]
+    fn new_creates_the_pages_in_the_format() {
+        let dir = temp_test_dir("new-pages");
+        let rnote_file = dir.join("new.rnote");
+        smol::block_on(run_new(
+            &rnote_file,
+            3,
+            PredefinedFormat::A5,
+            true,
+            None,
+            false,
+        ))
+        .unwrap();
+
+        let engine = load(&rnote_file);
+        let format = Format::from_predefined(PredefinedFormat::A5, Orientation::Landscape).unwrap();
+        assert_eq!(engine.document.format.orientation(), Orientation::Landscape);
+        assert_eq!(engine.document.format.size(), format.size());
+        assert_eq!(engine.document.layout, Layout::FixedSize);
+        assert_eq!(engine.document.width, format.width());
+        assert_eq!(engine.document.height, format.height() * 3.0);
+        assert_eq!(
+            engine.document.background.color,
+            Background::default().color
+        );
+        assert_eq!(
+            engine.document.background.pattern,
+            Background::default().pattern
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn new_applies_the_background() {
+        let dir = temp_test_dir("new-background");
+        let rnote_file = dir.join("new.rnote");
+        let color = Color::new(0.2, 0.4, 0.6, 1.0);
+        smol::block_on(run_new(
+            &rnote_file,
+            1,
+            PredefinedFormat::A4,
+            false,
+            Some(color),
+            true,
+        ))
+        .unwrap();
+
+        let engine = load(&rnote_file);
+        assert_eq!(engine.document.format.orientation(), Orientation::Portrait);
+        assert_eq!(engine.document.background.color, color);
+        assert_eq!(engine.document.background.pattern, PatternStyle::None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn new_rejects_other_extensions() {
+        let dir = temp_test_dir("new-extension");
+        let file = dir.join("new.pdf");
+        assert!(
+            smol::block_on(run_new(&file, 1, PredefinedFormat::A4, false, None, false,)).is_err()
+        );
+        assert!(!file.exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,310 @@
+// Imports
+use crate::{cli, validators};
+use anyhow::Context;
+use rnote_engine::engine::export::{
+    DocExportPrefs, DocPagesExportPrefs, ExportPrefs, SelectionExportPrefs,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Export preferences that override the ones given by the command line flags for the rnote files matching a pattern.
+///
+/// The file is either Json or Toml and holds a list of entries with a pattern and the overridden preferences,
+/// e.g. in Toml:
+///
+/// ```toml
+/// [[files]]
+/// pattern = "drafts/**"
+/// prefs = { with_background = false, optimize_printing = true }
+/// ```
+///
+/// The keys of the preferences are the ones of the serialized export preferences of the export command.
+/// Precedence, from highest to lowest: the matching entries (later entries over earlier ones), the command line
+/// flags, the export preferences, the built-in defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PrefsMap {
+    #[serde(default)]
+    files: Vec<PrefsMapEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrefsMapEntry {
+    /// Matched like the patterns of "--exclude".
+    pattern: String,
+    prefs: serde_json::Map<String, serde_json::Value>,
+}
+
+impl PrefsMap {
+    /// The file extensions of the supported prefs map formats.
+    const EXTS: &'static [&'static str] = &["json", "toml"];
+
+    /// Loads the prefs map and validates its entries against the export preferences of the export command.
+    pub(crate) async fn load_from_file(
+        file: &Path,
+        export_command: &cli::ExportCommand,
+    ) -> anyhow::Result<Self> {
+        let bytes = cli::read_bytes_from_file(file)
+            .await
+            .with_context(|| format!("Reading prefs map \"{}\" failed.", file.display()))?;
+        let prefs_map = match file.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_slice::<Self>(&bytes).map_err(anyhow::Error::from),
+            Some("toml") => std::str::from_utf8(&bytes)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| toml::from_str::<Self>(text).map_err(anyhow::Error::from)),
+            Some(ext) => return Err(validators::unsupported_ext_error(ext, Self::EXTS)),
+            None => {
+                return Err(anyhow::anyhow!(
+                    "The prefs map \"{}\" needs to have a supported extension to determine its format. Supported extensions: {}.",
+                    file.display(),
+                    Self::EXTS.join(", ")
+                ))
+            }
+        }
+        .with_context(|| format!("Parsing prefs map \"{}\" failed.", file.display()))?;
+        prefs_map
+            .validate(export_command)
+            .with_context(|| format!("Prefs map \"{}\" is invalid.", file.display()))?;
+        Ok(prefs_map)
+    }
+
+    /// Applies every entry once, so that invalid entries are reported before anything is exported.
+    fn validate(&self, export_command: &cli::ExportCommand) -> anyhow::Result<()> {
+        for entry in self.files.iter() {
+            let result = match export_command {
+                cli::ExportCommand::Doc { .. } => {
+                    entry.override_prefs(DocExportPrefs::default()).map(|_| ())
+                }
+                cli::ExportCommand::DocPages { .. } => entry
+                    .override_prefs(DocPagesExportPrefs::default())
+                    .map(|_| ()),
+                cli::ExportCommand::Selection { .. } => entry
+                    .override_prefs(SelectionExportPrefs::default())
+                    .map(|_| ()),
+            };
+            result
+                .with_context(|| format!("Entry with pattern \"{}\" is invalid.", entry.pattern))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the export preferences with the preferences of the export command overridden
+    /// by the entries matching the rnote file.
+    pub(crate) fn override_export_prefs(
+        &self,
+        mut export_prefs: ExportPrefs,
+        export_command: &cli::ExportCommand,
+        rnote_file: &Path,
+    ) -> anyhow::Result<ExportPrefs> {
+        for entry in self
+            .files
+            .iter()
+            .filter(|entry| cli::file_matches_pattern(rnote_file, &entry.pattern))
+        {
+            match export_command {
+                cli::ExportCommand::Doc { .. } => {
+                    export_prefs.doc_export_prefs =
+                        entry.override_prefs(export_prefs.doc_export_prefs)?;
+                }
+                cli::ExportCommand::DocPages { .. } => {
+                    export_prefs.doc_pages_export_prefs =
+                        entry.override_prefs(export_prefs.doc_pages_export_prefs)?;
+                }
+                cli::ExportCommand::Selection { .. } => {
+                    export_prefs.selection_export_prefs =
+                        entry.override_prefs(export_prefs.selection_export_prefs)?;
+                }
+            }
+        }
+        Ok(export_prefs)
+    }
+}
+
+impl PrefsMapEntry {
+    /// Keys that can't be overridden per file, because the output file names are determined before exporting.
+    const FIXED_KEYS: &'static [&'static str] = &["export_format"];
+
+    fn override_prefs<T>(&self, prefs: T) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let mut value = serde_json::to_value(prefs)?;
+        let Some(fields) = value.as_object_mut() else {
+            return Err(anyhow::anyhow!(
+                "Export preferences are not serialized as map."
+            ));
+        };
+        for (key, override_value) in self.prefs.iter() {
+            if Self::FIXED_KEYS.contains(&key.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Key \"{key}\" can't be overridden per file."
+                ));
+            }
+            let Some(field) = fields.get_mut(key) else {
+                let mut known_keys = fields
+                    .keys()
+                    .filter(|k| !Self::FIXED_KEYS.contains(&k.as_str()))
+                    .map(|k| k.as_str())
+                    .collect::<Vec<&str>>();
+                known_keys.sort_unstable();
+                return Err(anyhow::anyhow!(
+                    "Unknown key \"{key}\". Known keys: {}.",
+                    known_keys.join(", ")
+                ));
+            };
+            *field = override_value.clone();
+        }
+        serde_json::from_value(value).context("Deserializing the overridden preferences failed.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::tests::temp_test_dir;
+    use clap::Parser;
+
+    fn doc_export_command() -> cli::ExportCommand {
+        let cli = cli::Cli::parse_from([
+            "rnote-cli",
+            "export",
+            "doc",
+            "--output-format",
+            "pdf",
+            "a.rnote",
+        ]);
+        let cli::Command::Export { export_command, .. } = cli.command else {
+            panic!("expected the export command");
+        };
+        export_command
+    }
+
+    fn load(dir: &Path, file_name: &str, content: &str) -> anyhow::Result<PrefsMap> {
+        let file = dir.join(file_name);
+        std::fs::write(&file, content).unwrap();
+        smol::block_on(PrefsMap::load_from_file(&file, &doc_export_command()))
+    }
+
+    #[test]
+    fn load_json_and_toml() {
+        let dir = temp_test_dir("prefs-map-load");
+        let json = load(
+            &dir,
+            "map.json",
+            r#"{ "files": [ { "pattern": "drafts/**", "prefs": { "with_background": false } } ] }"#,
+        )
+        .unwrap();
+        let toml = load(
+            &dir,
+            "map.toml",
+            "[[files]]\npattern = \"drafts/**\"\nprefs = { with_background = false }\n",
+        )
+        .unwrap();
+        for prefs_map in [json, toml] {
+            assert_eq!(prefs_map.files.len(), 1);
+            assert_eq!(prefs_map.files[0].pattern, "drafts/**");
+            assert_eq!(
+                prefs_map.files[0].prefs.get("with_background"),
+                Some(&serde_json::Value::Bool(false))
+            );
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn reject_unsupported_formats_and_invalid_entries() {
+        let dir = temp_test_dir("prefs-map-invalid");
+        assert!(load(&dir, "map.yaml", "files: []").is_err());
+        assert!(load(&dir, "map", "{}").is_err());
+        // Unknown fields of the map and its entries
+        assert!(load(&dir, "unknown-field.json", r#"{ "entries": [] }"#).is_err());
+        assert!(load(
+            &dir,
+            "unknown-entry-field.json",
+            r#"{ "files": [ { "pattern": "*", "glob": "*", "prefs": {} } ] }"#,
+        )
+        .is_err());
+        // Unknown and fixed preference keys
+        assert!(load(
+            &dir,
+            "unknown-key.json",
+            r#"{ "files": [ { "pattern": "*", "prefs": { "with_colors": false } } ] }"#,
+        )
+        .is_err());
+        assert!(load(
+            &dir,
+            "fixed-key.json",
+            r#"{ "files": [ { "pattern": "*", "prefs": { "export_format": "svg" } } ] }"#,
+        )
+        .is_err());
+        // Values of the wrong type
+        assert!(load(
+            &dir,
+            "wrong-type.json",
+            r#"{ "files": [ { "pattern": "*", "prefs": { "with_background": "no" } } ] }"#,
+        )
+        .is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn later_matching_entries_override_earlier_ones() {
+        let dir = temp_test_dir("prefs-map-override");
+        let prefs_map = load(
+            &dir,
+            "map.toml",
+            r#"
+[[files]]
+pattern = "*.rnote"
+prefs = { with_background = false, with_pattern = false }
+
+[[files]]
+pattern = "drafts/**"
+prefs = { with_background = true, optimize_printing = true }
+
+[[files]]
+pattern = "other-*.rnote"
+prefs = { with_strokes = false }
+"#,
+        )
+        .unwrap();
+        let export_command = doc_export_command();
+        let mut export_prefs = ExportPrefs::default();
+        export_prefs.doc_export_prefs.optimize_printing = false;
+        export_prefs.doc_export_prefs.with_strokes = true;
+
+        let draft = prefs_map
+            .override_export_prefs(
+                export_prefs,
+                &export_command,
+                Path::new("drafts/note.rnote"),
+            )
+            .unwrap()
+            .doc_export_prefs;
+        assert!(draft.with_background);
+        assert!(!draft.with_pattern);
+        assert!(draft.optimize_printing);
+        assert!(draft.with_strokes);
+
+        let note = prefs_map
+            .override_export_prefs(export_prefs, &export_command, Path::new("notes/note.rnote"))
+            .unwrap()
+            .doc_export_prefs;
+        assert!(!note.with_background);
+        assert!(!note.with_pattern);
+        assert!(!note.optimize_printing);
+        assert!(note.with_strokes);
+
+        let unmatched = prefs_map
+            .override_export_prefs(export_prefs, &export_command, Path::new("notes/note.txt"))
+            .unwrap()
+            .doc_export_prefs;
+        assert_eq!(
+            serde_json::to_value(unmatched).unwrap(),
+            serde_json::to_value(export_prefs.doc_export_prefs).unwrap()
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
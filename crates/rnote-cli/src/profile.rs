@@ -0,0 +1,223 @@
+// Imports
+use crate::cli;
+use anyhow::Context as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Records the spans of the export phases (loading, rendering every page, encoding, writing) and writes them as
+/// Chrome trace events, set through "--profile".
+///
+/// The trace can be opened in chrome://tracing, Perfetto or speedscope and converted for flamegraph tools.
+/// The recording subscriber is only installed when profiling, without it the spans are disabled.
+#[derive(Debug, Clone)]
+pub(crate) struct Profiler {
+    events: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl Profiler {
+    /// Installs the recording subscriber. Can only be called once.
+    pub(crate) fn install() -> anyhow::Result<Self> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let layer = ProfileLayer {
+            start: Instant::now(),
+            events: Arc::clone(&events),
+        };
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+            .context("Installing the profiling subscriber failed.")?;
+        Ok(Self { events })
+    }
+
+    /// Writes the spans that were closed until now as Chrome trace Json file.
+    pub(crate) async fn write(&self, profile_file: &Path) -> anyhow::Result<()> {
+        let events = self
+            .events
+            .lock()
+            .map(|mut events| std::mem::take(&mut *events))
+            .unwrap_or_default();
+        let trace = serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        });
+        cli::create_overwrite_file_w_bytes(
+            profile_file,
+            serde_json::to_string(&trace)?.as_bytes(),
+            None,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Writing the profile to \"{}\" failed.",
+                profile_file.display()
+            )
+        })
+    }
+}
+
+/// The start time, thread and fields of a span that is not closed yet.
+#[derive(Debug)]
+struct SpanTiming {
+    start: Duration,
+    thread_id: u64,
+    args: serde_json::Map<String, serde_json::Value>,
+}
+
+struct ProfileLayer {
+    start: Instant,
+    events: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl<S> Layer<S> for ProfileLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = ArgsVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanTiming {
+            start: self.start.elapsed(),
+            thread_id: thread_id(),
+            args: visitor.0,
+        });
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            let mut visitor = ArgsVisitor(std::mem::take(&mut timing.args));
+            values.record(&mut visitor);
+            timing.args = visitor.0;
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else {
+            return;
+        };
+        let duration = self.start.elapsed().saturating_sub(timing.start);
+        // Complete events, their nesting is derived from the times on every thread
+        let event = serde_json::json!({
+            "name": span.name(),
+            "cat": span.metadata().target(),
+            "ph": "X",
+            "ts": timing.start.as_secs_f64() * 1e6,
+            "dur": duration.as_secs_f64() * 1e6,
+            "pid": std::process::id(),
+            "tid": timing.thread_id,
+            "args": timing.args,
+        });
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+/// Collects the fields of a span as the arguments of the trace event.
+#[derive(Debug, Default)]
+struct ArgsVisitor(serde_json::Map<String, serde_json::Value>);
+
+impl Visit for ArgsVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+/// A small sequential id of the current thread, as trace viewers expect numeric thread ids.
+fn thread_id() -> u64 {
+    static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    }
+    THREAD_ID.with(|id| *id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileLayer, Profiler};
+    use crate::cli::tests::temp_test_dir;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn profile_layer_records_complete_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(ProfileLayer {
+            start: Instant::now(),
+            events: Arc::clone(&events),
+        }));
+        tracing::dispatcher::with_default(&dispatch, || {
+            let span = tracing::info_span!("render", page = 3, format = tracing::field::Empty);
+            span.record("format", "png");
+        });
+        let thread_dispatch = dispatch.clone();
+        std::thread::spawn(move || {
+            tracing::dispatcher::with_default(&thread_dispatch, || {
+                let _span = tracing::info_span!("encode");
+            })
+        })
+        .join()
+        .unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        let (render, encode) = (&events[0], &events[1]);
+        assert_eq!(render["name"], "render");
+        assert_eq!(render["ph"], "X");
+        assert_eq!(render["pid"], std::process::id());
+        assert_eq!(render["args"]["page"], 3);
+        assert_eq!(render["args"]["format"], "png");
+        assert!(render["ts"].as_f64().unwrap() >= 0.0);
+        assert!(render["dur"].as_f64().unwrap() >= 0.0);
+        assert_eq!(encode["name"], "encode");
+        assert_eq!(encode["args"], serde_json::json!({}));
+        // Every thread gets its own numeric id
+        assert!(render["tid"].is_u64());
+        assert_ne!(render["tid"], encode["tid"]);
+    }
+
+    #[test]
+    fn profiler_writes_the_trace_events() {
+        let dir = temp_test_dir("profile-write");
+        let profile_file = dir.join("profile.json");
+        let profiler = Profiler {
+            events: Arc::new(Mutex::new(vec![serde_json::json!({ "name": "load" })])),
+        };
+        smol::block_on(profiler.write(&profile_file)).unwrap();
+        let trace: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&profile_file).unwrap()).unwrap();
+        assert_eq!(trace["traceEvents"][0]["name"], "load");
+        assert_eq!(trace["displayTimeUnit"], "ms");
+        // The written events are taken
+        assert!(profiler.events.lock().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,249 @@
+// Imports
+use crate::{cli, validators};
+use anyhow::Context;
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::store::StrokeKey;
+use rnote_engine::strokes::VectorImage;
+use rnote_engine::Engine;
+use std::path::{Path, PathBuf};
+
+/// Rewrites the references to external files of the images embedded in the rnote file,
+/// optionally inlines the referenced files and reports the images that are not self-contained.
+///
+/// With `check`, only the report is generated and the command fails when not all images are self-contained.
+pub(crate) async fn run_relink(
+    rnote_file: &Path,
+    output_file: Option<&Path>,
+    maps: &[(String, String)],
+    inline: bool,
+    check: bool,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_file(rnote_file)?;
+    if let Some(output_file) = output_file {
+        validators::file_has_ext(output_file, "rnote")?;
+    }
+    // Relative references are resolved against the directory of the rnote file
+    let base_dir = rnote_file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+
+    let refs = engine.store.external_image_refs()?;
+    println!(
+        "File \"{}\" has {} external image reference(s).",
+        rnote_file.display(),
+        refs.len()
+    );
+    if check {
+        return report_external_refs(&refs, &base_dir);
+    }
+    let Some(output_file) = output_file else {
+        return Err(anyhow::anyhow!("No output file specified."));
+    };
+
+    let remapped = engine.store.rewrite_external_image_refs(|href| {
+        Ok(maps.iter().find_map(|(old, new)| {
+            href.strip_prefix(old.as_str())
+                .map(|rest| format!("{new}{rest}"))
+        }))
+    })?;
+    println!("    Rewrote {remapped} reference(s).");
+    if inline {
+        let inlined = engine.store.rewrite_external_image_refs(|href| {
+            let Some(path) = resolve_local_ref(href, &base_dir) else {
+                // Remote references are left for the report
+                return Ok(None);
+            };
+            let bytes = std::fs::read(&path).with_context(|| {
+                format!("Reading referenced image \"{}\" failed.", path.display())
+            })?;
+            VectorImage::image_data_url(&bytes)
+                .map(Some)
+                .with_context(|| {
+                    format!("Inlining referenced image \"{}\" failed.", path.display())
+                })
+        })?;
+        println!("    Inlined {inlined} referenced image(s).");
+    }
+    let remaining_refs = engine.store.external_image_refs()?;
+    if let Err(e) = report_external_refs(&remaining_refs, &base_dir) {
+        println!("    {e}");
+    }
+
+    let Some(output_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from output_file"));
+    };
+    let in_place = output_file.canonicalize().ok() == rnote_file.canonicalize().ok();
+    if !in_place {
+        cli::confirm_overwrite(output_file, "Relinking")?;
+    }
+    let rnote_bytes = engine.save_as_rnote_bytes(output_file_name).await??;
+    cli::create_overwrite_file_w_bytes(output_file, &rnote_bytes, None).await?;
+    println!("Saved \"{}\".", output_file.display());
+
+    Ok(())
+}
+
+/// Prints the references and whether the referenced files exist.
+///
+/// Fails when there are any, because then the rnote file is not self-contained.
+fn report_external_refs(refs: &[(StrokeKey, String)], base_dir: &Path) -> anyhow::Result<()> {
+    if refs.is_empty() {
+        println!("    All images are self-contained.");
+        return Ok(());
+    }
+    for (_, href) in refs {
+        match resolve_local_ref(href, base_dir) {
+            Some(path) if path.is_file() => println!("    Image references \"{href}\""),
+            Some(_) => println!("    Image references \"{href}\", which does not exist"),
+            None => println!("    Image references remote \"{href}\""),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "{} image reference(s) are not self-contained.",
+        refs.len()
+    ))
+}
+
+/// Resolves a reference to a local file. Returns `None` for references with other Url schemes than "file".
+fn resolve_local_ref(href: &str, base_dir: &Path) -> Option<PathBuf> {
+    let path = match href.split_once("://") {
+        Some(("file", path)) => path,
+        Some(_) => return None,
+        None => href,
+    };
+    Some(base_dir.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_local_ref, run_relink};
+    use crate::cli::tests::temp_test_dir;
+    use p2d::bounding_volume::Aabb;
+    use rnote_compose::shapes::Rectangle;
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::strokes::{Stroke, VectorImage};
+    use rnote_engine::Engine;
+    use std::path::{Path, PathBuf};
+
+    /// Saves an rnote file with a vector image that references the image file.
+    fn save_rnote_file_w_image_ref(rnote_file: &Path, href: &str) {
+        let vectorimage = VectorImage {
+            svg_data: format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10" height="10"><image width="10" height="10" xlink:href="{href}"/></svg>"#
+            ),
+            intrinsic_size: na::vector![10.0, 10.0],
+            rectangle: Rectangle::from_p2d_aabb(Aabb::new(
+                na::point![0.0, 0.0],
+                na::point![10.0, 10.0],
+            )),
+            ..Default::default()
+        };
+        let mut engine = Engine::default();
+        let _ =
+            engine.import_generated_content(vec![(Stroke::VectorImage(vectorimage), None)], false);
+        let rnote_bytes = smol::block_on(async {
+            engine
+                .save_as_rnote_bytes(String::from("in.rnote"))
+                .await
+                .unwrap()
+        })
+        .unwrap();
+        std::fs::write(rnote_file, rnote_bytes).unwrap();
+    }
+
+    fn external_image_refs(rnote_file: &Path) -> Vec<String> {
+        let snapshot = smol::block_on(EngineSnapshot::load_from_rnote_bytes(
+            std::fs::read(rnote_file).unwrap(),
+        ))
+        .unwrap();
+        let mut engine = Engine::default();
+        let _ = engine.load_snapshot(snapshot);
+        engine
+            .store
+            .external_image_refs()
+            .unwrap()
+            .into_iter()
+            .map(|(_, href)| href)
+            .collect()
+    }
+
+    #[test]
+    fn relink_maps_and_inlines_the_referenced_images() {
+        let dir = temp_test_dir("relink");
+        std::fs::create_dir(dir.join("images")).unwrap();
+        image::RgbaImage::new(2, 2)
+            .save(dir.join("images").join("dot.png"))
+            .unwrap();
+        let rnote_file = dir.join("in.rnote");
+        save_rnote_file_w_image_ref(&rnote_file, "old/dot.png");
+        // The check fails while the image is referenced
+        assert!(smol::block_on(run_relink(&rnote_file, None, &[], false, true)).is_err());
+
+        let mapped_file = dir.join("mapped.rnote");
+        let maps = [(String::from("old/"), String::from("images/"))];
+        smol::block_on(run_relink(
+            &rnote_file,
+            Some(&mapped_file),
+            &maps,
+            false,
+            false,
+        ))
+        .unwrap();
+        assert_eq!(external_image_refs(&mapped_file), vec!["images/dot.png"]);
+
+        let inlined_file = dir.join("inlined.rnote");
+        smol::block_on(run_relink(
+            &rnote_file,
+            Some(&inlined_file),
+            &maps,
+            true,
+            false,
+        ))
+        .unwrap();
+        assert!(external_image_refs(&inlined_file).is_empty());
+        smol::block_on(run_relink(&inlined_file, None, &[], false, true)).unwrap();
+    }
+
+    #[test]
+    fn inlining_a_missing_image_fails() {
+        let dir = temp_test_dir("relink-missing");
+        let rnote_file = dir.join("in.rnote");
+        save_rnote_file_w_image_ref(&rnote_file, "missing.png");
+        let output_file = dir.join("out.rnote");
+        assert!(smol::block_on(run_relink(
+            &rnote_file,
+            Some(&output_file),
+            &[],
+            true,
+            false
+        ))
+        .is_err());
+        assert!(!output_file.exists());
+    }
+
+    #[test]
+    fn local_refs_resolve_against_the_base_dir() {
+        let base_dir = Path::new("/notes");
+        assert_eq!(
+            resolve_local_ref("images/a.png", base_dir),
+            Some(PathBuf::from("/notes/images/a.png"))
+        );
+        assert_eq!(
+            resolve_local_ref("file://images/a.png", base_dir),
+            Some(PathBuf::from("/notes/images/a.png"))
+        );
+        assert_eq!(
+            resolve_local_ref("https://example.org/a.png", base_dir),
+            None
+        );
+    }
+}
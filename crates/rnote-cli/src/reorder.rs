@@ -0,0 +1,48 @@
+// Imports
+use crate::{cli, validators};
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::strokes::Stroke;
+use rnote_engine::Engine;
+use std::path::Path;
+
+/// Moves the bitmap images of the rnote file behind or in front of the other strokes on their layer and saves the result.
+///
+/// The order of the images among each other and of the other strokes among each other is preserved.
+pub(crate) async fn run_reorder(
+    rnote_file: &Path,
+    output_file: &Path,
+    images_to_front: bool,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_file(rnote_file)?;
+    validators::file_has_ext(output_file, "rnote")?;
+    let Some(output_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from output_file"));
+    };
+    let in_place = output_file.canonicalize().ok() == rnote_file.canonicalize().ok();
+    if !in_place {
+        cli::confirm_overwrite(output_file, "Reordering")?;
+    }
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+
+    let moved = engine.store.reorder_strokes(
+        |stroke| matches!(stroke, Stroke::BitmapImage(_)),
+        images_to_front,
+    );
+    println!(
+        "Moved {moved} image(s) to the {}.",
+        if images_to_front { "front" } else { "back" }
+    );
+
+    let rnote_bytes = engine.save_as_rnote_bytes(output_file_name).await??;
+    cli::create_overwrite_file_w_bytes(output_file, &rnote_bytes, None).await?;
+    println!("Saved \"{}\".", output_file.display());
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// Imports
+use crate::{cli, validators};
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::Engine;
+use std::path::Path;
+
+/// Converts the bitmap images of the rnote file to the rgba8-premultiplied memory format and saves the result.
+///
+/// Images of older files can be stored in memory formats that can't be drawn directly, which fails exports.
+pub(crate) async fn run_repair(rnote_file: &Path, output_file: &Path) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_file(rnote_file)?;
+    validators::file_has_ext(output_file, "rnote")?;
+    let Some(output_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from output_file"));
+    };
+    let in_place = output_file.canonicalize().ok() == rnote_file.canonicalize().ok();
+    if !in_place {
+        cli::confirm_overwrite(output_file, "Repairing")?;
+    }
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+
+    let converted = engine.store.normalize_bitmap_image_formats();
+    println!("Converted the memory format of {converted} image(s).");
+
+    let rnote_bytes = engine.save_as_rnote_bytes(output_file_name).await??;
+    cli::create_overwrite_file_w_bytes(output_file, &rnote_bytes, None).await?;
+    println!("Saved \"{}\".", output_file.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_repair;
+    use crate::cli::tests::temp_test_dir;
+    use rnote_engine::engine::EngineSnapshot;
+    use rnote_engine::render::{self, ImageMemoryFormat};
+    use rnote_engine::strokes::resize::ImageSizeOption;
+    use rnote_engine::strokes::{BitmapImage, Stroke};
+    use rnote_engine::Engine;
+
+    #[test]
+    fn repair_converts_the_images_in_place() {
+        let dir = temp_test_dir("repair");
+        let rnote_file = dir.join("in.rnote");
+        let mut engine = Engine::default();
+        // The same bytes, but stored in the bgra8-premultiplied memory format like in older files
+        let image = render::Image {
+            memory_format: ImageMemoryFormat::B8g8r8a8Premultiplied,
+            ..render::Image::from(image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_pixel(1, 1, image::Rgba([10, 20, 30, 255])),
+            ))
+        };
+        let bitmapimage = BitmapImage::from_image(
+            image,
+            na::vector![10.0, 10.0],
+            ImageSizeOption::RespectOriginalSize,
+        )
+        .unwrap();
+        let _ =
+            engine.import_generated_content(vec![(Stroke::BitmapImage(bitmapimage), None)], false);
+        let rnote_bytes = smol::block_on(async {
+            engine
+                .save_as_rnote_bytes(String::from("in.rnote"))
+                .await
+                .unwrap()
+        })
+        .unwrap();
+        std::fs::write(&rnote_file, rnote_bytes).unwrap();
+
+        smol::block_on(run_repair(&rnote_file, &rnote_file)).unwrap();
+
+        let snapshot = smol::block_on(EngineSnapshot::load_from_rnote_bytes(
+            std::fs::read(&rnote_file).unwrap(),
+        ))
+        .unwrap();
+        let images = snapshot
+            .stroke_components
+            .values()
+            .filter_map(|stroke| match stroke.as_ref() {
+                Stroke::BitmapImage(bitmapimage) => Some(bitmapimage.image.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(images.len(), 1);
+        assert_eq!(
+            images[0].memory_format,
+            ImageMemoryFormat::R8g8b8a8Premultiplied
+        );
+        assert_eq!(&images[0].data[..], &[30, 20, 10, 255]);
+    }
+}
@@ -0,0 +1,237 @@
+// Imports
+use rnote_engine::document::format::{Orientation, PredefinedFormat};
+use rnote_engine::document::{Background, Format};
+use rnote_engine::engine::export::{
+    DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs, ExportOptions,
+};
+use rnote_engine::engine::import::XoppImportPrefs;
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::strokes::Stroke;
+use rnote_engine::Engine;
+
+/// The data of the line chart of the test document.
+const TEST_CSV: &str = "x,y\n0,1\n1,3\n2,2\n3,4\n";
+/// The vector image of the test document.
+const TEST_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40"><circle cx="20" cy="20" r="16" fill="#1c71d8"/></svg>"##;
+
+/// An output of the test document that is checked.
+#[derive(Debug, Clone, Copy)]
+enum TestFormat {
+    Rnote,
+    DocSvg,
+    DocPdf,
+    DocXopp,
+    DocPagesSvg,
+    DocPagesPng,
+    DocPagesJpeg,
+}
+
+impl TestFormat {
+    const ALL: &'static [Self] = &[
+        Self::Rnote,
+        Self::DocSvg,
+        Self::DocPdf,
+        Self::DocXopp,
+        Self::DocPagesSvg,
+        Self::DocPagesPng,
+        Self::DocPagesJpeg,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rnote => "rnote",
+            Self::DocSvg => "doc svg",
+            Self::DocPdf => "doc pdf",
+            Self::DocXopp => "doc xopp",
+            Self::DocPagesSvg => "doc-pages svg",
+            Self::DocPagesPng => "doc-pages png",
+            Self::DocPagesJpeg => "doc-pages jpeg",
+        }
+    }
+}
+
+/// Renders a small built-in document, exports it to every supported format and checks that the outputs are valid.
+///
+/// Exercises the render and export pipeline without any input files, to diagnose broken installations.
+pub(crate) async fn run_self_test() -> anyhow::Result<()> {
+    let engine = match test_engine().await {
+        Ok(engine) => engine,
+        Err(e) => {
+            println!("FAIL creating the test document: {e:#}");
+            return Err(e.context("Creating the test document failed."));
+        }
+    };
+    let mut failed_formats = 0;
+
+    for format in TestFormat::ALL.iter().copied() {
+        match test_format(&engine, format).await {
+            Ok(()) => println!("PASS {}", format.name()),
+            Err(e) => {
+                failed_formats += 1;
+                println!("FAIL {}: {e:#}", format.name());
+            }
+        }
+    }
+
+    println!(
+        "{} passed, {failed_formats} failed.",
+        TestFormat::ALL.len() - failed_formats
+    );
+    if failed_formats > 0 {
+        return Err(anyhow::anyhow!(
+            "{failed_formats} of {} format(s) failed the self-test.",
+            TestFormat::ALL.len()
+        ));
+    }
+    Ok(())
+}
+
+/// An engine with an A4 page holding a line chart, a text and a vector image.
+async fn test_engine() -> anyhow::Result<Engine> {
+    let format = Format::from_predefined(PredefinedFormat::A4, Orientation::Portrait)
+        .ok_or_else(|| anyhow::anyhow!("The A4 format has no size."))?;
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::new_w_pages(
+        1,
+        format,
+        Background::default(),
+    ));
+
+    let chart = engine
+        .generate_csv_chart_from_bytes(TEST_CSV.as_bytes().to_vec(), na::vector![40.0, 40.0], true)
+        .await??;
+    let _ = engine.import_generated_content(chart, false);
+    let _ = engine.insert_text(
+        String::from("rnote self-test"),
+        Some(na::vector![40.0, 400.0]),
+    );
+    let vectorimage = engine
+        .generate_vectorimage_from_bytes(
+            na::vector![40.0, 480.0],
+            TEST_SVG.as_bytes().to_vec(),
+            false,
+        )
+        .await??;
+    let _ = engine.import_generated_content(vec![(Stroke::VectorImage(vectorimage), None)], false);
+    if engine.export_strokes_count(&ExportOptions::default()) == 0 {
+        return Err(anyhow::anyhow!("The test document has no strokes."));
+    }
+    Ok(engine)
+}
+
+/// Exports the test document to the format and checks that the output is not empty and can be parsed.
+async fn test_format(engine: &Engine, format: TestFormat) -> anyhow::Result<()> {
+    const TITLE: &str = "self-test";
+    let export_options = ExportOptions::default();
+
+    let doc_prefs = |export_format| DocExportPrefs {
+        export_format,
+        ..Default::default()
+    };
+    let doc_pages_prefs = |export_format| DocPagesExportPrefs {
+        export_format,
+        ..Default::default()
+    };
+    match format {
+        TestFormat::Rnote => {
+            let bytes = engine
+                .save_as_rnote_bytes(format!("{TITLE}.rnote"))
+                .await??;
+            ensure_not_empty(&bytes)?;
+            let snapshot = EngineSnapshot::load_from_rnote_bytes(bytes).await?;
+            if snapshot.stroke_components.is_empty() {
+                return Err(anyhow::anyhow!("The loaded file has no strokes."));
+            }
+        }
+        TestFormat::DocSvg => {
+            let bytes = engine
+                .export_doc(
+                    TITLE.to_string(),
+                    Some(doc_prefs(DocExportFormat::Svg)),
+                    &export_options,
+                )
+                .await??;
+            check_svg(engine, bytes).await?;
+        }
+        TestFormat::DocPdf => {
+            let bytes = engine
+                .export_doc(
+                    TITLE.to_string(),
+                    Some(doc_prefs(DocExportFormat::Pdf)),
+                    &export_options,
+                )
+                .await??;
+            ensure_not_empty(&bytes)?;
+            let pages = engine
+                .generate_pdf_pages_from_bytes(bytes, na::Vector2::zeros(), None, None)
+                .await??;
+            if pages.is_empty() {
+                return Err(anyhow::anyhow!("The Pdf has no pages."));
+            }
+        }
+        TestFormat::DocXopp => {
+            let bytes = engine
+                .export_doc(
+                    TITLE.to_string(),
+                    Some(doc_prefs(DocExportFormat::Xopp)),
+                    &export_options,
+                )
+                .await??;
+            ensure_not_empty(&bytes)?;
+            let _ = EngineSnapshot::load_from_xopp_bytes(bytes, XoppImportPrefs::default()).await?;
+        }
+        TestFormat::DocPagesSvg => {
+            let pages = engine
+                .export_doc_pages(
+                    Some(doc_pages_prefs(DocPagesExportFormat::Svg)),
+                    &export_options,
+                )
+                .await??;
+            ensure_pages(&pages)?;
+            for bytes in pages {
+                check_svg(engine, bytes).await?;
+            }
+        }
+        TestFormat::DocPagesPng | TestFormat::DocPagesJpeg => {
+            let (export_format, image_format) = match format {
+                TestFormat::DocPagesJpeg => (DocPagesExportFormat::Jpeg, image::ImageFormat::Jpeg),
+                _ => (DocPagesExportFormat::Png, image::ImageFormat::Png),
+            };
+            let pages = engine
+                .export_doc_pages(Some(doc_pages_prefs(export_format)), &export_options)
+                .await??;
+            ensure_pages(&pages)?;
+            for bytes in pages {
+                ensure_not_empty(&bytes)?;
+                let image = image::load_from_memory_with_format(&bytes, image_format)?;
+                if image.width() == 0 || image.height() == 0 {
+                    return Err(anyhow::anyhow!("The image has no pixels."));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ensure_not_empty(bytes: &[u8]) -> anyhow::Result<()> {
+    if bytes.is_empty() {
+        return Err(anyhow::anyhow!("The output is empty."));
+    }
+    Ok(())
+}
+
+fn ensure_pages(pages: &[Vec<u8>]) -> anyhow::Result<()> {
+    if pages.is_empty() {
+        return Err(anyhow::anyhow!("No pages were exported."));
+    }
+    Ok(())
+}
+
+/// Parses the Svg by importing it as vector image.
+async fn check_svg(engine: &Engine, bytes: Vec<u8>) -> anyhow::Result<()> {
+    ensure_not_empty(&bytes)?;
+    let _ = engine
+        .generate_vectorimage_from_bytes(na::Vector2::zeros(), bytes, false)
+        .await??;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+// Imports
+use crate::{cli, validators};
+use anyhow::Context;
+use rnote_engine::engine::export::{ExportOptions, SelectionExportFormat, SelectionExportPrefs};
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::strokes::Stroke;
+use rnote_engine::Engine;
+use std::path::Path;
+
+pub(crate) async fn run_export_stickers(
+    rnote_file: &Path,
+    output_dir: &Path,
+    export_format: SelectionExportFormat,
+    padding: f64,
+    bitmap_scalefactor: f64,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_dir(output_dir)?;
+    if export_format == SelectionExportFormat::Jpeg {
+        return Err(anyhow::anyhow!(
+            "Exporting stickers as Jpeg is not supported, it has no transparency."
+        ));
+    }
+    if !padding.is_finite() || padding < 0.0 {
+        return Err(anyhow::anyhow!("The padding must not be negative."));
+    }
+    let Some(output_file_stem) = rnote_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!(
+            "Failed to get file stem from rnote file \"{}\"",
+            rnote_file.display()
+        ));
+    };
+
+    let mut engine = Engine::default();
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let engine_snapshot = EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?;
+    let _ = engine.load_snapshot(engine_snapshot);
+    let prefs = SelectionExportPrefs {
+        export_format,
+        with_background: false,
+        with_pattern: false,
+        bitmap_scalefactor,
+        margin: padding,
+        ..Default::default()
+    };
+    let stickers = engine
+        .export_strokes_as_stickers(Some(prefs), &ExportOptions::default())
+        .await??;
+
+    let out_ext = export_format.file_ext();
+    let stickers_amount = stickers.len();
+    for (i, (stroke, bytes)) in stickers.into_iter().enumerate() {
+        let output_file = output_dir.join(sticker_file_name(
+            i,
+            stickers_amount,
+            &stroke,
+            &out_ext,
+            &output_file_stem,
+        ));
+        cli::create_overwrite_file_w_bytes(&output_file, &bytes, None)
+            .await
+            .with_context(|| format!("Failed to export sticker {}.", i + 1))?;
+    }
+    println!(
+        "Exported {stickers_amount} sticker(s) to \"{}\".",
+        output_dir.display()
+    );
+    Ok(())
+}
+
+fn sticker_file_name(
+    sticker_i: usize,
+    stickers_amount: usize,
+    stroke: &Stroke,
+    out_ext: &str,
+    output_file_stem: &str,
+) -> String {
+    let stroke_type = match stroke {
+        Stroke::BrushStroke(_) => "brushstroke",
+        Stroke::ShapeStroke(_) => "shapestroke",
+        Stroke::TextStroke(_) => "textstroke",
+        Stroke::VectorImage(_) => "vectorimage",
+        Stroke::BitmapImage(_) => "bitmapimage",
+    };
+    cli::numbered_file_name(
+        output_file_stem,
+        "sticker",
+        sticker_i,
+        stickers_amount,
+        &format!(" {stroke_type}.{out_ext}"),
+    )
+}
@@ -0,0 +1,91 @@
+// Imports
+use crate::cli::TransformAbout;
+use crate::{cli, validators};
+use p2d::bounding_volume::Aabb;
+use rnote_engine::engine::EngineSnapshot;
+use rnote_engine::strokes::Stroke;
+use rnote_engine::Engine;
+use std::path::Path;
+
+/// Rotates and scales the images or all strokes of the rnote file and saves the result.
+///
+/// The rotation is applied before the scaling. Angles are in degrees, clockwise.
+pub(crate) async fn run_transform(
+    rnote_file: &Path,
+    output_file: &Path,
+    rotate_images: Option<f64>,
+    rotate_all: Option<f64>,
+    scale_images: Option<f64>,
+    about: TransformAbout,
+) -> anyhow::Result<()> {
+    validators::file_has_ext(rnote_file, "rnote")?;
+    validators::path_is_file(rnote_file)?;
+    validators::file_has_ext(output_file, "rnote")?;
+    if rotate_images.is_none() && rotate_all.is_none() && scale_images.is_none() {
+        return Err(anyhow::anyhow!(
+            "No transformation specified, use \"--rotate-images\", \"--rotate-all\" or \"--scale-images\"."
+        ));
+    }
+    if rotate_images
+        .into_iter()
+        .chain(rotate_all)
+        .any(|angle| !angle.is_finite())
+    {
+        return Err(anyhow::anyhow!(
+            "The rotation angle must be a finite number."
+        ));
+    }
+    let Some(output_file_name) = output_file
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+    else {
+        return Err(anyhow::anyhow!("Failed to get filename from output_file"));
+    };
+    let in_place = output_file.canonicalize().ok() == rnote_file.canonicalize().ok();
+    if !in_place {
+        cli::confirm_overwrite(output_file, "Transforming")?;
+    }
+
+    let rnote_bytes = cli::read_bytes_from_file(rnote_file).await?;
+    let mut engine = Engine::default();
+    let _ = engine.load_snapshot(EngineSnapshot::load_from_rnote_bytes(rnote_bytes).await?);
+
+    let pivot = |bounds: Aabb| match about {
+        TransformAbout::Center => bounds.center(),
+        TransformAbout::Origin => na::Point2::origin(),
+    };
+    let is_image = |stroke: &Stroke| matches!(stroke, Stroke::BitmapImage(_));
+    if let Some(angle) = rotate_images {
+        let rotated = engine.store.transform_strokes_each(
+            angle.to_radians(),
+            na::Vector2::repeat(1.0),
+            is_image,
+            pivot,
+        );
+        println!("Rotated {rotated} image(s) by {angle} degrees.");
+    }
+    if let Some(angle) = rotate_all {
+        let rotated = engine.store.transform_strokes_each(
+            angle.to_radians(),
+            na::Vector2::repeat(1.0),
+            |_| true,
+            pivot,
+        );
+        println!("Rotated {rotated} stroke(s) by {angle} degrees.");
+    }
+    if let Some(scale) = scale_images {
+        let scaled =
+            engine
+                .store
+                .transform_strokes_each(0.0, na::Vector2::repeat(scale), is_image, pivot);
+        println!("Scaled {scaled} image(s) by {scale}.");
+    }
+    // Strokes may have been moved outside of the document
+    let _ = engine.doc_resize_autoexpand();
+
+    let rnote_bytes = engine.save_as_rnote_bytes(output_file_name).await??;
+    cli::create_overwrite_file_w_bytes(output_file, &rnote_bytes, None).await?;
+    println!("Saved \"{}\".", output_file.display());
+
+    Ok(())
+}
@@ -34,3 +34,73 @@ pub(crate) fn file_has_ext(path: &Path, expected_ext: &str) -> anyhow::Result<()
         ))
     }
 }
+
+/// Creates an error for an unsupported extension.
+///
+/// The error lists the supported extensions and suggests the closest match, if there is one.
+pub(crate) fn unsupported_ext_error(ext: &str, supported_exts: &[&str]) -> anyhow::Error {
+    let supported_list = supported_exts
+        .iter()
+        .map(|e| format!(".{e}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    match closest_match(ext, supported_exts) {
+        Some(suggestion) => anyhow::anyhow!(
+            "Extension \".{ext}\" is not supported, did you mean \".{suggestion}\"? Supported extensions: {supported_list}."
+        ),
+        None => anyhow::anyhow!(
+            "Extension \".{ext}\" is not supported. Supported extensions: {supported_list}."
+        ),
+    }
+}
+
+/// The candidate with the smallest edit distance to the input, if it is close enough to be a likely typo.
+fn closest_match<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    const MAX_DISTANCE: usize = 2;
+    let input = input.to_lowercase();
+    candidates
+        .iter()
+        .map(|c| (*c, levenshtein_distance(&input, c)))
+        .filter(|(_, d)| *d <= MAX_DISTANCE)
+        .min_by_key(|(_, d)| *d)
+        .map(|(c, _)| c)
+}
+
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut prev_row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = usize::from(a_char != *b_char);
+            row[j + 1] = (prev_row[j] + substitution_cost)
+                .min(prev_row[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        prev_row = row;
+    }
+    prev_row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unsupported_ext_error;
+
+    #[test]
+    fn unsupported_ext_suggests_closest_match() {
+        let err = unsupported_ext_error("svgg", &["svg", "xopp", "pdf"]).to_string();
+        assert_eq!(
+            err,
+            "Extension \".svgg\" is not supported, did you mean \".svg\"? Supported extensions: .svg, .xopp, .pdf."
+        );
+    }
+
+    #[test]
+    fn unsupported_ext_without_close_match_lists_supported() {
+        let err = unsupported_ext_error("docx", &["svg", "xopp", "pdf"]).to_string();
+        assert_eq!(
+            err,
+            "Extension \".docx\" is not supported. Supported extensions: .svg, .xopp, .pdf."
+        );
+    }
+}
@@ -142,6 +142,46 @@ impl PenPath {
         Some(Self { start, segments })
     }
 
+    /// Fits a catmull-rom spline through the elements of a path that consists only of lines.
+    ///
+    /// The curves pass through every element and keep its pressure. The first and last element are repeated to get
+    /// the tangents at the ends. Returns None for paths with less than two segments or that already contain curves.
+    pub fn smoothed_w_catmull_rom(&self) -> Option<Self> {
+        if self.segments.len() < 2
+            || !self
+                .segments
+                .iter()
+                .all(|seg| matches!(seg, Segment::LineTo { .. }))
+        {
+            return None;
+        }
+        let elements = self.clone().into_elements();
+        let last = elements.len() - 1;
+        let segments = (0..last)
+            .map(|i| {
+                let end = elements[i + 1];
+                match CubicBezier::new_w_catmull_rom(
+                    elements[i.saturating_sub(1)].pos,
+                    elements[i].pos,
+                    end.pos,
+                    elements[(i + 2).min(last)].pos,
+                ) {
+                    Some(cubbez) => Segment::CubBezTo {
+                        cp1: cubbez.cp1,
+                        cp2: cubbez.cp2,
+                        end,
+                    },
+                    None => Segment::LineTo { end },
+                }
+            })
+            .collect::<Vec<Segment>>();
+
+        Some(Self {
+            start: self.start,
+            segments,
+        })
+    }
+
     /// Checks whether bounds collide with the path. If it does, it returns the indices of the colliding segments
     ///
     /// `loosened` loosens the segments hitboxes by the value
@@ -106,4 +106,13 @@ impl Segment {
             Segment::CubBezTo { end, .. } => *end,
         }
     }
+
+    /// A mutable reference to the end element of a segment.
+    pub fn end_mut(&mut self) -> &mut Element {
+        match self {
+            Segment::LineTo { end, .. } => end,
+            Segment::QuadBezTo { end, .. } => end,
+            Segment::CubBezTo { end, .. } => end,
+        }
+    }
 }
@@ -355,6 +355,24 @@ impl PressureCurve {
             Self::Pow3 => width * pressure.powi(3),
         }
     }
+
+    /// The smallest pressure at which applying the pressure curve to the width results in at least the min width.
+    ///
+    /// Clamped to range [0.0 - 1.0].
+    pub fn min_pressure_for_width(&self, width: f64, min_width: f64) -> f64 {
+        if width <= 0.0 {
+            return 1.0;
+        }
+        let ratio = (min_width / width).clamp(0.0, 1.0);
+        match self {
+            Self::Const => 0.0,
+            Self::Linear => ratio,
+            Self::Sqrt => ratio.powi(2),
+            Self::Cbrt => ratio.powi(3),
+            Self::Pow2 => ratio.sqrt(),
+            Self::Pow3 => ratio.cbrt(),
+        }
+    }
 }
 
 impl TryFrom<u32> for PressureCurve {
@@ -50,6 +50,48 @@ pub fn wrap_svg_root(
         )
     };
 
+    svg_root(
+        svg_data,
+        &x,
+        &y,
+        &width,
+        &height,
+        viewbox,
+        preserve_aspectratio,
+    )
+}
+
+/// Wrap a Svg root element around the Svg string, with the width and height in a physical unit.
+///
+/// The extents of the bounds are multiplied by `unit_factor` and suffixed with `unit`, e.g. "mm".
+pub fn wrap_svg_root_w_unit(
+    svg_data: &str,
+    bounds: Aabb,
+    viewbox: Option<Aabb>,
+    preserve_aspectratio: bool,
+    unit_factor: f64,
+    unit: &str,
+) -> String {
+    svg_root(
+        svg_data,
+        &format!("{:.3}", bounds.mins[0]),
+        &format!("{:.3}", bounds.mins[1]),
+        &format!("{:.3}{unit}", bounds.extents()[0] * unit_factor),
+        &format!("{:.3}{unit}", bounds.extents()[1] * unit_factor),
+        viewbox,
+        preserve_aspectratio,
+    )
+}
+
+fn svg_root(
+    svg_data: &str,
+    x: &str,
+    y: &str,
+    width: &str,
+    height: &str,
+    viewbox: Option<Aabb>,
+    preserve_aspectratio: bool,
+) -> String {
     let viewbox = if let Some(viewbox) = viewbox {
         format!(
             "{:.3} {:.3} {:.3} {:.3}",
@@ -71,10 +113,10 @@ pub fn wrap_svg_root(
         .set("xmlns", "http://www.w3.org/2000/svg")
         .set("xmlns:svg", "http://www.w3.org/2000/svg")
         .set("xmlns:xlink", "http://www.w3.org/1999/xlink")
-        .set("x", x.as_str())
-        .set("y", y.as_str())
-        .set("width", width.as_str())
-        .set("height", height.as_str())
+        .set("x", x)
+        .set("y", y)
+        .set("width", width)
+        .set("height", height)
         .set("viewBox", viewbox.as_str())
         .set("preserveAspectRatio", preserve_aspectratio.as_str())
         .add(svg::node::Blob::new(svg_data));
@@ -414,6 +414,17 @@ impl Background {
         bounds: Aabb,
         with_pattern: bool,
         optimize_printing: bool,
+    ) -> Result<render::Svg, anyhow::Error> {
+        self.gen_svg_w_color(bounds, true, with_pattern, optimize_printing)
+    }
+
+    /// Generate the background svg, optionally without the background color.
+    fn gen_svg_w_color(
+        &self,
+        bounds: Aabb,
+        with_color: bool,
+        with_pattern: bool,
+        optimize_printing: bool,
     ) -> Result<render::Svg, anyhow::Error> {
         let (color, pattern_color) = if optimize_printing {
             if self.color.luma() > 0.5 {
@@ -438,7 +449,9 @@ impl Background {
         color_rect.assign("height", format!("{}px", bounds.extents()[1]));
 
         let mut svg_group = element::Group::new();
-        svg_group = svg_group.add(color_rect);
+        if with_color {
+            svg_group = svg_group.add(color_rect);
+        }
 
         if with_pattern {
             match self.pattern {
@@ -511,4 +524,16 @@ impl Background {
         background_svg.wrap_svg_root(Some(bounds), Some(bounds), false);
         background_svg.draw_to_cairo(cx)
     }
+
+    /// Draw only the pattern, without the background color.
+    pub(crate) fn draw_pattern_to_cairo(
+        &self,
+        cx: &cairo::Context,
+        bounds: Aabb,
+        optimize_printing: bool,
+    ) -> anyhow::Result<()> {
+        let mut pattern_svg = self.gen_svg_w_color(bounds, false, true, optimize_printing)?;
+        pattern_svg.wrap_svg_root(Some(bounds), Some(bounds), false);
+        pattern_svg.draw_to_cairo(cx)
+    }
 }
@@ -197,6 +197,26 @@ impl Format {
 
     pub const BORDER_COLOR_DEFAULT: piet::Color = color::GNOME_BRIGHTS[2];
 
+    /// A format with the size of the predefined format at the default dpi.
+    ///
+    /// Returns None for [PredefinedFormat::Custom].
+    pub fn from_predefined(predefined: PredefinedFormat, orientation: Orientation) -> Option<Self> {
+        let size_mm = predefined.size_mm(orientation)?;
+        let mm_to_px = |value: f64| {
+            MeasureUnit::convert_measurement(
+                value,
+                MeasureUnit::Mm,
+                Self::DPI_DEFAULT,
+                MeasureUnit::Px,
+                Self::DPI_DEFAULT,
+            )
+        };
+        let mut format = Self::default();
+        format.set_width(mm_to_px(size_mm[0]));
+        format.set_height(mm_to_px(size_mm[1]));
+        Some(format)
+    }
+
     pub fn width(&self) -> f64 {
         self.width
     }
@@ -227,6 +247,16 @@ impl Format {
         self.orientation
     }
 
+    /// Sets the orientation by swapping width and height when the current size has the other orientation.
+    ///
+    /// Square formats are always in portrait orientation.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        if self.orientation != orientation {
+            std::mem::swap(&mut self.width, &mut self.height);
+            self.orientation = self.determine_orientation();
+        }
+    }
+
     pub fn size(&self) -> na::Vector2<f64> {
         na::vector![self.width, self.height]
     }
@@ -239,3 +269,30 @@ impl Format {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Orientation};
+
+    #[test]
+    fn set_orientation_swaps_width_and_height() {
+        let mut format = Format::default();
+        let (width, height) = (format.width(), format.height());
+        assert_eq!(format.orientation(), Orientation::Portrait);
+        format.set_orientation(Orientation::Portrait);
+        assert_eq!(format.size(), na::vector![width, height]);
+
+        format.set_orientation(Orientation::Landscape);
+        assert_eq!(format.size(), na::vector![height, width]);
+        assert_eq!(format.orientation(), Orientation::Landscape);
+        format.set_orientation(Orientation::Portrait);
+        assert_eq!(format.size(), na::vector![width, height]);
+
+        // Square formats stay in portrait orientation
+        format.set_width(500.0);
+        format.set_height(500.0);
+        format.set_orientation(Orientation::Landscape);
+        assert_eq!(format.size(), na::vector![500.0, 500.0]);
+        assert_eq!(format.orientation(), Orientation::Portrait);
+    }
+}
@@ -1,13 +1,23 @@
 // Imports
 use super::{Engine, EngineConfig, StrokeContent};
+use crate::document::format::{MeasureUnit, Orientation, PredefinedFormat};
+use crate::fileformats::pdfformat::PdfFile;
 use crate::fileformats::rnoteformat::RnoteFile;
-use crate::fileformats::{xoppformat, FileFormatSaver};
-use crate::CloneConfig;
+use crate::fileformats::{xoppformat, FileFormatLoader, FileFormatSaver};
+use crate::store::chrono_comp::StrokeLayer;
+use crate::store::StrokeKey;
+use crate::strokes::content::GeneratedContentImages;
+use crate::strokes::{BitmapImage, Content, PdfLink, Stroke, VectorImage};
+use crate::{render, CloneConfig, Drawable, StrokeStore};
 use anyhow::Context;
 use futures::channel::oneshot;
+use p2d::bounding_volume::{Aabb, BoundingVolume};
+use piet::{RenderContext, Text, TextLayout, TextLayoutBuilder};
 use rayon::prelude::*;
-use rnote_compose::transform::Transformable;
-use rnote_compose::SplitOrder;
+use rnote_compose::ext::AabbExt;
+use rnote_compose::shapes::{Rectangle, Shapeable};
+use rnote_compose::transform::{Transform, Transformable};
+use rnote_compose::{Color, SplitOrder};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::error;
@@ -67,44 +77,75 @@ impl DocExportFormat {
     }
 }
 
-/// Document export preferences.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(default, rename = "doc_export_prefs")]
-pub struct DocExportPrefs {
-    /// Whether the background should be exported.
-    #[serde(rename = "with_background")]
-    pub with_background: bool,
-    /// Whether the background pattern should be exported.
-    #[serde(rename = "with_pattern")]
-    pub with_pattern: bool,
-    /// Whether the background and stroke colors should be optimized for printing.
-    #[serde(rename = "optimize_printing")]
-    pub optimize_printing: bool,
-    /// The export format.
-    #[serde(rename = "export_format")]
-    pub export_format: DocExportFormat,
-    /// The page order when documents with layouts that expand in horizontal and vertical directions are cut into pages.
-    #[serde(rename = "page_order")]
-    pub page_order: SplitOrder,
+/// Page orientation override when exporting to page based formats.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "export_orientation")]
+pub enum ExportOrientation {
+    /// Keep the orientation of the document format.
+    #[serde(rename = "auto")]
+    Auto,
+    /// Force portrait pages.
+    #[serde(rename = "portrait")]
+    Portrait,
+    /// Force landscape pages.
+    #[serde(rename = "landscape")]
+    Landscape,
 }
 
-impl Default for DocExportPrefs {
+impl Default for ExportOrientation {
     fn default() -> Self {
-        Self {
-            with_background: true,
-            with_pattern: true,
-            optimize_printing: false,
-            export_format: DocExportFormat::default(),
-            page_order: SplitOrder::default(),
+        Self::Auto
+    }
+}
+
+impl TryFrom<u32> for ExportOrientation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "ExportOrientation try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for ExportOrientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Portrait => write!(f, "portrait"),
+            Self::Landscape => write!(f, "landscape"),
         }
     }
 }
 
-impl DocExportPrefs {
-    const MARGIN: f64 = 0.0;
+impl ExportOrientation {
+    /// Whether a page with the given size needs to be rotated by 90° to match the orientation.
+    pub fn needs_rotation(self, page_size: na::Vector2<f64>) -> bool {
+        match self {
+            Self::Auto => false,
+            Self::Portrait => page_size[0] > page_size[1],
+            Self::Landscape => page_size[0] < page_size[1],
+        }
+    }
 }
 
-/// Document pages export format.
+/// Mirroring of the exported output.
 #[derive(
     Debug,
     Clone,
@@ -119,91 +160,65 @@ impl DocExportPrefs {
     num_derive::ToPrimitive,
 )]
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
-#[serde(rename = "doc_pages_export_format")]
-pub enum DocPagesExportFormat {
-    #[serde(rename = "svg")]
-    Svg,
-    #[serde(rename = "png")]
-    Png,
-    #[serde(rename = "jpeg")]
-    Jpeg,
+#[serde(rename = "export_flip")]
+pub enum ExportFlip {
+    /// No mirroring.
+    #[serde(rename = "none")]
+    #[cfg_attr(feature = "cli", value(name = "none"))]
+    None,
+    /// Mirror horizontally, left and right are swapped.
+    #[serde(rename = "horizontal")]
+    #[cfg_attr(feature = "cli", value(name = "h"))]
+    Horizontal,
+    /// Mirror vertically, top and bottom are swapped.
+    #[serde(rename = "vertical")]
+    #[cfg_attr(feature = "cli", value(name = "v"))]
+    Vertical,
+    /// Mirror both horizontally and vertically.
+    #[serde(rename = "both")]
+    #[cfg_attr(feature = "cli", value(name = "hv"))]
+    Both,
 }
 
-impl Default for DocPagesExportFormat {
+impl Default for ExportFlip {
     fn default() -> Self {
-        Self::Svg
+        Self::None
     }
 }
 
-impl TryFrom<u32> for DocPagesExportFormat {
+impl TryFrom<u32> for ExportFlip {
     type Error = anyhow::Error;
 
     fn try_from(value: u32) -> Result<Self, Self::Error> {
         num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
-            anyhow::anyhow!(
-                "DocPagesExportFormat try_from::<u32>() for value {} failed",
-                value
-            )
+            anyhow::anyhow!("ExportFlip try_from::<u32>() for value {} failed", value)
         })
     }
 }
 
-impl DocPagesExportFormat {
-    pub fn file_ext(self) -> String {
+impl std::fmt::Display for ExportFlip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Svg => String::from("svg"),
-            Self::Png => String::from("png"),
-            Self::Jpeg => String::from("jpg"),
+            Self::None => write!(f, "none"),
+            Self::Horizontal => write!(f, "h"),
+            Self::Vertical => write!(f, "v"),
+            Self::Both => write!(f, "hv"),
         }
     }
 }
 
-/// Document pages export preferences.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(default, rename = "doc_pages_export_prefs")]
-pub struct DocPagesExportPrefs {
-    /// Whether the background should be exported.
-    #[serde(rename = "with_background")]
-    pub with_background: bool,
-    /// Whether the background pattern should be exported.
-    #[serde(rename = "with_pattern")]
-    pub with_pattern: bool,
-    /// Whether the background and stroke colors should be optimized for printing.
-    #[serde(rename = "optimize_printing")]
-    pub optimize_printing: bool,
-    /// Export format
-    #[serde(rename = "export_format")]
-    pub export_format: DocPagesExportFormat,
-    /// The page order when documents with layouts that expand in horizontal and vertical directions are cut into pages.
-    #[serde(rename = "page_order")]
-    pub page_order: SplitOrder,
-    /// The bitmap scale-factor in relation to the actual size.
-    #[serde(rename = "bitmap_scalefactor")]
-    pub bitmap_scalefactor: f64,
-    /// Quality when exporting as Jpeg.
-    #[serde(rename = "jpg_quality")]
-    pub jpeg_quality: u8,
-}
-
-impl DocPagesExportPrefs {
-    const MARGIN: f64 = 0.0;
-}
-
-impl Default for DocPagesExportPrefs {
-    fn default() -> Self {
-        Self {
-            with_background: true,
-            with_pattern: true,
-            optimize_printing: false,
-            export_format: DocPagesExportFormat::default(),
-            page_order: SplitOrder::default(),
-            bitmap_scalefactor: 1.8,
-            jpeg_quality: 85,
+impl ExportFlip {
+    fn scale(self) -> na::Vector2<f64> {
+        match self {
+            Self::None => na::vector![1.0, 1.0],
+            Self::Horizontal => na::vector![-1.0, 1.0],
+            Self::Vertical => na::vector![1.0, -1.0],
+            Self::Both => na::vector![-1.0, -1.0],
         }
     }
 }
 
-/// Selection export format.
+/// Along which axes the content is centered in the page box when exporting.
 #[derive(
     Debug,
     Clone,
@@ -218,347 +233,3041 @@ impl Default for DocPagesExportPrefs {
     num_derive::ToPrimitive,
 )]
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
-#[serde(rename = "selection_export_format")]
-pub enum SelectionExportFormat {
-    #[serde(rename = "svg")]
-    Svg,
-    #[serde(rename = "png")]
-    Png,
-    #[serde(rename = "jpeg")]
-    Jpeg,
+#[serde(rename = "export_center_content")]
+pub enum ExportCenterContent {
+    /// Center horizontally and vertically.
+    #[serde(rename = "both")]
+    #[cfg_attr(feature = "cli", value(name = "both"))]
+    Both,
+    /// Only center horizontally, the vertical position is kept.
+    #[serde(rename = "horizontal")]
+    #[cfg_attr(feature = "cli", value(name = "horizontal"))]
+    Horizontal,
+    /// Only center vertically, the horizontal position is kept.
+    #[serde(rename = "vertical")]
+    #[cfg_attr(feature = "cli", value(name = "vertical"))]
+    Vertical,
 }
 
-impl Default for SelectionExportFormat {
+impl Default for ExportCenterContent {
     fn default() -> Self {
-        Self::Svg
+        Self::Both
     }
 }
 
-impl SelectionExportFormat {
-    pub fn file_ext(self) -> String {
+impl TryFrom<u32> for ExportCenterContent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "ExportCenterContent try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for ExportCenterContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SelectionExportFormat::Svg => String::from("svg"),
-            SelectionExportFormat::Png => String::from("png"),
-            SelectionExportFormat::Jpeg => String::from("jpg"),
+            Self::Both => write!(f, "both"),
+            Self::Horizontal => write!(f, "horizontal"),
+            Self::Vertical => write!(f, "vertical"),
         }
     }
 }
 
-impl TryFrom<u32> for SelectionExportFormat {
+impl ExportCenterContent {
+    /// The offset that moves the content bounds to the center of the page bounds along the selected axes.
+    pub fn offset(self, content_bounds: Aabb, page_bounds: Aabb) -> na::Vector2<f64> {
+        let offset = page_bounds.center() - content_bounds.center();
+        match self {
+            Self::Both => offset,
+            Self::Horizontal => na::vector![offset[0], 0.0],
+            Self::Vertical => na::vector![0.0, offset[1]],
+        }
+    }
+}
+
+/// Clockwise rotation of the exported output.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "export_rotation")]
+pub enum ExportRotation {
+    #[serde(rename = "0")]
+    #[cfg_attr(feature = "cli", value(name = "0"))]
+    Deg0,
+    #[serde(rename = "90")]
+    #[cfg_attr(feature = "cli", value(name = "90"))]
+    Deg90,
+    #[serde(rename = "180")]
+    #[cfg_attr(feature = "cli", value(name = "180"))]
+    Deg180,
+    #[serde(rename = "270")]
+    #[cfg_attr(feature = "cli", value(name = "270"))]
+    Deg270,
+}
+
+impl Default for ExportRotation {
+    fn default() -> Self {
+        Self::Deg0
+    }
+}
+
+impl TryFrom<u32> for ExportRotation {
     type Error = anyhow::Error;
 
     fn try_from(value: u32) -> Result<Self, Self::Error> {
         num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
             anyhow::anyhow!(
-                "SelectionExportFormat try_from::<u32>() for value {} failed",
+                "ExportRotation try_from::<u32>() for value {} failed",
                 value
             )
         })
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(default, rename = "selection_export_prefs")]
-pub struct SelectionExportPrefs {
-    /// Whether the background should be exported.
-    #[serde(rename = "with_background")]
-    pub with_background: bool,
-    /// Whether the background pattern should be exported.
-    #[serde(rename = "with_pattern")]
-    pub with_pattern: bool,
-    /// Whether the background and stroke colors should be optimized for printing.
-    #[serde(rename = "optimize_printing")]
-    pub optimize_printing: bool,
-    /// Export format.
-    #[serde(rename = "export_format")]
-    pub export_format: SelectionExportFormat,
-    /// The bitmap scale-factor in relation to the actual size.
-    #[serde(rename = "bitmap_scalefactor")]
-    pub bitmap_scalefactor: f64,
-    /// Quality when exporting as Jpeg.
-    #[serde(rename = "jpg_quality")]
-    pub jpeg_quality: u8,
-    /// The margins of the export extending the bounds of the selection.
-    #[serde(rename = "margin")]
-    pub margin: f64,
+impl std::fmt::Display for ExportRotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Deg0 => write!(f, "0"),
+            Self::Deg90 => write!(f, "90"),
+            Self::Deg180 => write!(f, "180"),
+            Self::Deg270 => write!(f, "270"),
+        }
+    }
 }
 
-impl Default for SelectionExportPrefs {
-    fn default() -> Self {
-        Self {
-            with_background: true,
-            with_pattern: false,
-            optimize_printing: false,
-            export_format: SelectionExportFormat::Svg,
-            bitmap_scalefactor: 1.8,
-            jpeg_quality: 85,
-            margin: 12.0,
+impl ExportRotation {
+    fn angle(self) -> f64 {
+        match self {
+            Self::Deg0 => 0.0,
+            Self::Deg90 => std::f64::consts::FRAC_PI_2,
+            Self::Deg180 => std::f64::consts::PI,
+            Self::Deg270 => 3.0 * std::f64::consts::FRAC_PI_2,
         }
     }
-}
 
-/// Export preferences.
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
-#[serde(default, rename = "export_prefs")]
-pub struct ExportPrefs {
-    /// Document export preferences.
-    #[serde(rename = "doc_export_prefs")]
-    pub doc_export_prefs: DocExportPrefs,
-    //// Document pages export preferences.
-    #[serde(rename = "doc_pages_export_prefs")]
-    pub doc_pages_export_prefs: DocPagesExportPrefs,
-    /// Selection export preferences.
-    #[serde(rename = "selection_export_prefs")]
-    pub selection_export_prefs: SelectionExportPrefs,
+    /// Whether the rotation swaps the width and height of the output.
+    pub fn swaps_extents(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
 }
 
-impl CloneConfig for ExportPrefs {
-    fn clone_config(&self) -> Self {
-        *self
+/// The transform that mirrors and then rotates content that is inside the given bounds.
+///
+/// The bounds mins stay in place, the extents are swapped for rotations by 90° and 270°.
+/// Returns `None` when the content is not transformed.
+pub fn export_output_transform(
+    flip: ExportFlip,
+    rotation: ExportRotation,
+    bounds: Aabb,
+) -> Option<Transform> {
+    if flip == ExportFlip::None && rotation == ExportRotation::Deg0 {
+        return None;
     }
+    let center = bounds.center();
+    let mut transform = Transform::default();
+    transform.append_translation_mut(-center.coords);
+    transform.append_scale_mut(flip.scale());
+    transform.append_rotation_wrt_point_mut(rotation.angle(), na::Point2::origin());
+    transform
+        .append_translation_mut(bounds.mins.coords - transform.transform_aabb(bounds).mins.coords);
+    Some(transform)
 }
 
-impl Engine {
-    /// The used image scale-factor for any strokes that are converted to bitmap images on export.
-    pub const STROKE_EXPORT_IMAGE_SCALE: f64 = 1.8;
+/// Where the coordinate system of exported Svgs is anchored.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "svg_origin")]
+pub enum SvgOrigin {
+    /// The top-left corner of the exported page.
+    #[default]
+    #[serde(rename = "page")]
+    #[cfg_attr(feature = "cli", value(name = "page"))]
+    Page,
+    /// The top-left corner of the bounding box of the strokes.
+    ///
+    /// Strokes keep their position on the page, the viewBox is shifted accordingly.
+    #[serde(rename = "content")]
+    #[cfg_attr(feature = "cli", value(name = "content"))]
+    Content,
+}
 
-    /// Save the current document as a .rnote file.
-    pub fn save_as_rnote_bytes(
-        &self,
-        file_name: String,
-    ) -> oneshot::Receiver<anyhow::Result<Vec<u8>>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
-        let engine_snapshot = self.take_snapshot();
-        rayon::spawn(move || {
-            let result = || -> anyhow::Result<Vec<u8>> {
-                let rnote_file = RnoteFile {
-                    engine_snapshot: ijson::to_value(&engine_snapshot)?,
-                };
-                rnote_file.save_as_bytes(&file_name)
-            };
-            if oneshot_sender.send(result()).is_err() {
-                error!(
-                    "Sending result to receiver failed while saving document as rnote bytes. Receiver already dropped."
-                );
-            }
-        });
-        oneshot_receiver
+impl TryFrom<u32> for SvgOrigin {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!("SvgOrigin try_from::<u32>() for value {} failed", value)
+        })
     }
+}
 
-    /// Extract the current engine configuration.
-    pub fn extract_engine_config(&self) -> EngineConfig {
-        EngineConfig {
-            document: self.document.clone_config(),
-            pens_config: self.pens_config.clone_config(),
-            penholder: self.penholder.clone_config(),
-            import_prefs: self.import_prefs.clone_config(),
-            export_prefs: self.export_prefs.clone_config(),
-            pen_sounds: self.pen_sounds(),
-            optimize_epd: self.optimize_epd(),
+impl std::fmt::Display for SvgOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Page => write!(f, "page"),
+            Self::Content => write!(f, "content"),
         }
     }
+}
 
-    pub fn extract_document_content(&self) -> StrokeContent {
-        StrokeContent::default()
-            .with_strokes(
-                self.store
-                    .get_strokes_arc(&self.store.stroke_keys_as_rendered()),
-            )
-            .with_bounds(Some(
-                self.bounds_w_content_extended()
-                    .unwrap_or(self.document.bounds()),
-            ))
-            .with_background(Some(self.document.background))
+/// The unit of the width and height of exported Svgs.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "svg_units")]
+pub enum SvgUnits {
+    /// Pixels, at the reference resolution of 96 per inch.
+    #[serde(rename = "px")]
+    #[cfg_attr(feature = "cli", value(name = "px"))]
+    Px,
+    /// Points, 1/72 of an inch.
+    #[serde(rename = "pt")]
+    #[cfg_attr(feature = "cli", value(name = "pt"))]
+    Pt,
+    /// Millimeters.
+    #[serde(rename = "mm")]
+    #[cfg_attr(feature = "cli", value(name = "mm"))]
+    Mm,
+    /// Inches.
+    #[serde(rename = "in")]
+    #[cfg_attr(feature = "cli", value(name = "in"))]
+    In,
+}
+
+impl TryFrom<u32> for SvgUnits {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value)
+            .ok_or_else(|| anyhow::anyhow!("SvgUnits try_from::<u32>() for value {} failed", value))
     }
+}
 
-    pub fn extract_pages_content(&self, page_order: SplitOrder) -> Vec<StrokeContent> {
-        self.pages_bounds_w_content(page_order)
-            .into_iter()
-            .map(|bounds| {
-                StrokeContent::default()
-                    .with_strokes(
-                        self.store.get_strokes_arc(
-                            &self
-                                .store
-                                .stroke_keys_as_rendered_intersecting_bounds(bounds),
-                        ),
-                    )
-                    .with_bounds(Some(bounds))
-                    .with_background(Some(self.document.background))
-            })
-            .collect()
+impl std::fmt::Display for SvgUnits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.suffix())
     }
+}
 
-    pub fn extract_selection_content(&self) -> Option<StrokeContent> {
-        let selection_keys = self.store.selection_keys_as_rendered();
-        if selection_keys.is_empty() {
-            return None;
+impl SvgUnits {
+    /// The suffix of the values in this unit.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Px => "px",
+            Self::Pt => "pt",
+            Self::Mm => "mm",
+            Self::In => "in",
         }
-        Some(
-            StrokeContent::default()
-                .with_strokes(self.store.get_strokes_arc(&selection_keys))
-                .with_background(Some(self.document.background)),
-        )
     }
 
-    /// Export the current engine config as Json string.
-    pub fn export_engine_config_as_json(&self) -> anyhow::Result<String> {
-        Ok(serde_json::to_string(&self.extract_engine_config())?)
+    /// The factor that converts document coordinates at the given dpi into this unit.
+    pub fn factor_from_coords(&self, dpi: f64) -> f64 {
+        match self {
+            Self::Px => SVG_PX_PER_INCH / dpi,
+            Self::Pt => PDF_POINTS_PER_INCH / dpi,
+            Self::Mm => MeasureUnit::AMOUNT_MM_IN_INCH / dpi,
+            Self::In => 1.0 / dpi,
+        }
     }
+}
 
-    /// Export the entire engine state as Json string.
+/// How the paths of the strokes are smoothed when exporting.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "stroke_smoothing")]
+pub enum StrokeSmoothing {
+    /// The strokes are rendered as they are stored.
+    #[default]
+    #[serde(rename = "none")]
+    #[cfg_attr(feature = "cli", value(name = "none"))]
+    None,
+    /// Brush strokes that consist only of lines are rendered along a catmull-rom spline through their points.
     ///
-    /// Only intended to be used for debugging.
-    pub fn export_state_as_json(&self) -> anyhow::Result<String> {
-        Ok(serde_json::to_string_pretty(self)?)
+    /// The document is not modified.
+    #[serde(rename = "catmull_rom")]
+    #[cfg_attr(feature = "cli", value(name = "catmull-rom"))]
+    CatmullRom,
+}
+
+impl TryFrom<u32> for StrokeSmoothing {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "StrokeSmoothing try_from::<u32>() for value {} failed",
+                value
+            )
+        })
     }
+}
 
-    /// Export the document.
-    pub fn export_doc(
-        &self,
-        title: String,
-        doc_export_prefs_override: Option<DocExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
-        let doc_export_prefs =
-            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
+impl std::fmt::Display for StrokeSmoothing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::CatmullRom => write!(f, "catmull-rom"),
+        }
+    }
+}
 
-        match doc_export_prefs.export_format {
-            DocExportFormat::Svg => self.export_doc_as_svg_bytes(doc_export_prefs_override),
-            DocExportFormat::Pdf => self.export_doc_as_pdf_bytes(title, doc_export_prefs_override),
-            DocExportFormat::Xopp => {
-                self.export_doc_as_xopp_bytes(title, doc_export_prefs_override)
-            }
+/// What happens when a stroke fails to draw while exporting.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "on_stroke_error")]
+pub enum OnStrokeError {
+    /// The export fails.
+    #[default]
+    #[serde(rename = "fail")]
+    Fail,
+    /// The stroke is left out.
+    #[serde(rename = "skip")]
+    Skip,
+    /// A marker is drawn where the stroke would have been.
+    #[serde(rename = "placeholder")]
+    Placeholder,
+}
+
+impl TryFrom<u32> for OnStrokeError {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!("OnStrokeError try_from::<u32>() for value {} failed", value)
+        })
+    }
+}
+
+impl std::fmt::Display for OnStrokeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fail => write!(f, "fail"),
+            Self::Skip => write!(f, "skip"),
+            Self::Placeholder => write!(f, "placeholder"),
         }
     }
+}
 
-    /// Export the doc with the strokes as Svg.
-    fn export_doc_as_svg_bytes(
-        &self,
-        doc_export_prefs_override: Option<DocExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
-        let doc_export_prefs =
-            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
-        let doc_content = self.extract_document_content();
+/// The arrangement of multiple pages on one output page, e.g. 2-up or 4-up layouts to save paper.
+///
+/// The pages are placed into a grid of cells in reading order, left-to-right and top-to-bottom.
+/// Every cell has the size of the largest page, smaller pages are centered in their cell.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename = "export_nup")]
+pub struct ExportNup {
+    /// The amount of rows of the grid.
+    #[serde(rename = "rows")]
+    pub rows: u32,
+    /// The amount of columns of the grid.
+    #[serde(rename = "columns")]
+    pub columns: u32,
+    /// The gap between the cells, in millimeters.
+    #[serde(rename = "gutter")]
+    pub gutter: f64,
+    /// Whether a border is drawn around every cell.
+    #[serde(rename = "borders")]
+    pub borders: bool,
+}
 
-        rayon::spawn(move || {
-            let result = || -> anyhow::Result<Vec<u8>> {
-                let doc_svg = doc_content
-                    .gen_svg(
-                        doc_export_prefs.with_background,
-                        doc_export_prefs.with_pattern,
-                        doc_export_prefs.optimize_printing,
-                        DocExportPrefs::MARGIN,
-                    )?
-                    .ok_or(anyhow::anyhow!("Generating doc svg failed, returned None."))?;
-                Ok(rnote_compose::utils::add_xml_header(
-                    rnote_compose::utils::wrap_svg_root(
-                        doc_svg.svg_data.as_str(),
-                        Some(doc_svg.bounds),
-                        Some(doc_svg.bounds),
-                        false,
-                    )
-                    .as_str(),
-                )
-                .into_bytes())
-            };
+impl Default for ExportNup {
+    fn default() -> Self {
+        Self {
+            rows: 1,
+            columns: 1,
+            gutter: Self::GUTTER_DEFAULT,
+            borders: false,
+        }
+    }
+}
 
-            if oneshot_sender.send(result()).is_err() {
-                error!("Sending result to receiver failed while exporting document as Svg bytes. Receiver already dropped.");
-            }
-        });
+impl ExportNup {
+    /// The default gutter, in millimeters.
+    pub const GUTTER_DEFAULT: f64 = 5.0;
+    /// The line width of the cell borders, in the units of the exported pages.
+    const BORDER_WIDTH: f64 = 1.0;
 
-        oneshot_receiver
+    /// The amount of pages that are placed on one output page.
+    pub fn pages_per_sheet(&self) -> usize {
+        self.rows.max(1) as usize * self.columns.max(1) as usize
     }
 
-    /// Export the doc with the strokes as Pdf.
-    fn export_doc_as_pdf_bytes(
+    /// The size of the output page and the cells in reading order, for cells of the given size.
+    fn sheet_layout(
         &self,
-        title: String,
-        doc_export_prefs_override: Option<DocExportPrefs>,
-    ) -> oneshot::Receiver<anyhow::Result<Vec<u8>>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
-        let doc_export_prefs =
-            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
-        let pages_content = self.extract_pages_content(doc_export_prefs.page_order);
-        let format_size = self.document.format.size();
+        cell_size: na::Vector2<f64>,
+        gutter: f64,
+    ) -> (na::Vector2<f64>, Vec<Aabb>) {
+        let (rows, columns) = (self.rows.max(1), self.columns.max(1));
+        let gutter = if gutter.is_finite() {
+            gutter.max(0.0)
+        } else {
+            0.0
+        };
+        let sheet_size = na::vector![
+            f64::from(columns) * cell_size[0] + f64::from(columns - 1) * gutter,
+            f64::from(rows) * cell_size[1] + f64::from(rows - 1) * gutter
+        ];
+        let cells = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let mins = na::point![
+                    f64::from(column) * (cell_size[0] + gutter),
+                    f64::from(row) * (cell_size[1] + gutter)
+                ];
+                Aabb::new(mins, mins + cell_size)
+            })
+            .collect();
+        (sheet_size, cells)
+    }
 
-        rayon::spawn(move || {
-            let result = || -> anyhow::Result<Vec<u8>> {
-                let target_surface =
-                    cairo::PdfSurface::for_stream(format_size[0], format_size[1], Vec::<u8>::new())
-                        .context("Creating Pdf target surface failed.")?;
-
-                target_surface
-                    .set_metadata(cairo::PdfMetadata::Title, title.as_str())
-                    .context("Set pdf surface title metadata failed.")?;
-                target_surface
-                    .set_metadata(
-                        cairo::PdfMetadata::CreateDate,
-                        crate::utils::now_formatted_string().as_str(),
-                    )
-                    .context("Set pdf surface date metadata failed.")?;
+    /// Composes the page images onto output page images, with the gutter and the border width in pixels.
+    fn compose_images(
+        &self,
+        pages: Vec<render::Image>,
+        gutter: f64,
+        border_width: f64,
+    ) -> anyhow::Result<Vec<render::Image>> {
+        let cell_size = pages
+            .iter()
+            .fold(na::Vector2::<f64>::zeros(), |size, page| {
+                na::vector![
+                    size[0].max(f64::from(page.pixel_width)),
+                    size[1].max(f64::from(page.pixel_height))
+                ]
+            });
+        let (sheet_size, cells) = self.sheet_layout(cell_size, gutter.round());
+        let border_width = border_width.round().max(1.0) as u32;
+        let mut pages = pages.into_iter().peekable();
+        let mut sheets = Vec::new();
 
-                // New scope to avoid errors when flushing
-                {
-                    let cairo_cx = cairo::Context::new(&target_surface)
-                        .context("Creating new cairo context for pdf target surface failed.")?;
+        while pages.peek().is_some() {
+            let mut sheet =
+                image::RgbaImage::new(sheet_size[0].ceil() as u32, sheet_size[1].ceil() as u32);
+            for (cell, page) in cells.iter().zip(pages.by_ref()) {
+                let (page_width, page_height) = (page.pixel_width, page.pixel_height);
+                let x = cell.mins[0] + (cell_size[0] - f64::from(page_width)) * 0.5;
+                let y = cell.mins[1] + (cell_size[1] - f64::from(page_height)) * 0.5;
+                image::imageops::replace(
+                    &mut sheet,
+                    &page.into_imgbuf()?,
+                    x.round() as i64,
+                    y.round() as i64,
+                );
+                if self.borders {
+                    draw_image_border(&mut sheet, *cell, border_width);
+                }
+            }
+            sheets.push(render::Image::from(image::DynamicImage::ImageRgba8(sheet)));
+        }
+        Ok(sheets)
+    }
 
-                    for (i, page_content) in pages_content.into_iter().enumerate() {
-                        let Some(page_bounds) = page_content.bounds() else {
-                            continue;
-                        };
+    /// Places the pages of the Pdf onto output pages, with the gutter in Pdf points.
+    ///
+    /// The metadata is not carried over, the title is set again when given.
+    /// Uri links are moved along with their pages, links to destinations inside the Pdf are dropped.
+    fn impose_pdf(&self, pdf: &[u8], gutter: f64, title: Option<&str>) -> anyhow::Result<Vec<u8>> {
+        let doc = poppler::Document::from_bytes(&glib::Bytes::from(pdf), None)
+            .context("Loading the exported Pdf for the n-up layout failed.")?;
+        let pages = (0..doc.n_pages())
+            .filter_map(|i| doc.page(i))
+            .collect::<Vec<_>>();
+        let cell_size = pages
+            .iter()
+            .fold(na::Vector2::<f64>::zeros(), |size, page| {
+                let (width, height) = page.size();
+                na::vector![size[0].max(width), size[1].max(height)]
+            });
+        let (sheet_size, cells) = self.sheet_layout(cell_size, gutter);
+        let target_surface =
+            cairo::PdfSurface::for_stream(sheet_size[0], sheet_size[1], Vec::<u8>::new())
+                .context("Creating Pdf target surface failed.")?;
+        if let Some(title) = title {
+            target_surface
+                .set_metadata(cairo::PdfMetadata::Title, title)
+                .context("Set pdf surface title metadata failed.")?;
+            target_surface
+                .set_metadata(
+                    cairo::PdfMetadata::CreateDate,
+                    crate::utils::now_formatted_string().as_str(),
+                )
+                .context("Set pdf surface date metadata failed.")?;
+        }
+
+        // New scope to avoid errors when flushing
+        {
+            let cairo_cx = cairo::Context::new(&target_surface)
+                .context("Creating new cairo context for pdf target surface failed.")?;
+            for (sheet_i, sheet_pages) in pages.chunks(self.pages_per_sheet()).enumerate() {
+                for (cell, page) in cells.iter().zip(sheet_pages) {
+                    let (width, height) = page.size();
+                    let offset = na::vector![
+                        cell.mins[0] + (cell_size[0] - width) * 0.5,
+                        cell.mins[1] + (cell_size[1] - height) * 0.5
+                    ];
+                    cairo_cx.save()?;
+                    cairo_cx.translate(offset[0], offset[1]);
+                    cairo_cx.rectangle(0.0, 0.0, width, height);
+                    cairo_cx.clip();
+                    page.render_for_printing(&cairo_cx);
+                    cairo_cx.restore()?;
+                    // Re-rendering the page doesn't carry over its annotations
+                    let page_rectangle = Rectangle::from_p2d_aabb(Aabb::new(
+                        offset.into(),
+                        (offset + na::vector![width, height]).into(),
+                    ));
+                    for link in PdfLink::extract_from_pdf_page(page) {
+                        link.draw_link_tag_to_cairo(&cairo_cx, &page_rectangle);
+                    }
+                    if self.borders {
                         cairo_cx.save()?;
-                        cairo_cx.translate(-page_bounds.mins[0], -page_bounds.mins[1]);
-                        page_content.draw_to_cairo(
-                            &cairo_cx,
-                            doc_export_prefs.with_background,
-                            doc_export_prefs.with_pattern,
-                            doc_export_prefs.optimize_printing,
-                            DocExportPrefs::MARGIN,
-                            Engine::STROKE_EXPORT_IMAGE_SCALE,
-                        )?;
-                        cairo_cx.show_page().map_err(|e| {
-                            anyhow::anyhow!(
-                                "Showing page failed while exporting page {i} as pdf, Err: {e:?}"
-                            )
-                        })?;
+                        cairo_cx.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+                        cairo_cx.set_line_width(Self::BORDER_WIDTH);
+                        let extents = cell.extents();
+                        cairo_cx.rectangle(cell.mins[0], cell.mins[1], extents[0], extents[1]);
+                        cairo_cx.stroke()?;
                         cairo_cx.restore()?;
                     }
                 }
-                let data = *target_surface
-                    .finish_output_stream()
-                    .map_err(|e| anyhow::anyhow!("Finishing outputstream failed, Err: {e:?}"))?
-                    .downcast::<Vec<u8>>()
-                    .map_err(|e| {
-                        anyhow::anyhow!("Downcasting finished output stream failed, Err: {e:?}")
-                    })?;
-
-                Ok(data)
-            };
-
-            if oneshot_sender.send(result()).is_err() {
-                error!("Sending result to receiver failed while exporting document as Pdf bytes. Receiver already dropped.");
+                cairo_cx.show_page().map_err(|e| {
+                    anyhow::anyhow!(
+                        "Showing page failed while exporting n-up page {sheet_i} as pdf, Err: {e:?}"
+                    )
+                })?;
             }
-        });
+        }
+        let data = *target_surface
+            .finish_output_stream()
+            .map_err(|e| anyhow::anyhow!("Finishing outputstream failed, Err: {e:?}"))?
+            .downcast::<Vec<u8>>()
+            .map_err(|e| {
+                anyhow::anyhow!("Downcasting finished output stream failed, Err: {e:?}")
+            })?;
+        Ok(data)
+    }
+}
 
-        oneshot_receiver
+/// Draws an opaque black border of the given width on the inside of the bounds.
+fn draw_image_border(image: &mut image::RgbaImage, bounds: Aabb, width: u32) {
+    let (image_width, image_height) = image.dimensions();
+    let x0 = (bounds.mins[0].round().max(0.0) as u32).min(image_width);
+    let y0 = (bounds.mins[1].round().max(0.0) as u32).min(image_height);
+    let x1 = (bounds.maxs[0].round().max(0.0) as u32).min(image_width);
+    let y1 = (bounds.maxs[1].round().max(0.0) as u32).min(image_height);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if x < x0 + width || x + width >= x1 || y < y0 + width || y + width >= y1 {
+                image.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+            }
+        }
     }
+}
 
-    /// Export the document as a Xournal++ .xopp file.
-    fn export_doc_as_xopp_bytes(
-        &self,
-        title: String,
-        doc_export_prefs_override: Option<DocExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
-        let doc_export_prefs =
-            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
-        let pages_content = self.extract_pages_content(doc_export_prefs.page_order);
-        let document = self.document.clone();
+/// How the background image is fitted into the page box.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "background_image_fit")]
+pub enum BackgroundImageFit {
+    /// Scale the image to cover the entire page, parts of the image may be cut off.
+    #[serde(rename = "cover")]
+    Cover,
+    /// Scale the image to fit inside the page.
+    #[serde(rename = "contain")]
+    Contain,
+    /// Repeat the image in its original size, starting at the top left corner of the page.
+    #[serde(rename = "tile")]
+    Tile,
+}
 
-        rayon::spawn(move || {
+impl Default for BackgroundImageFit {
+    fn default() -> Self {
+        Self::Contain
+    }
+}
+
+impl TryFrom<u32> for BackgroundImageFit {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "BackgroundImageFit try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for BackgroundImageFit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cover => write!(f, "cover"),
+            Self::Contain => write!(f, "contain"),
+            Self::Tile => write!(f, "tile"),
+        }
+    }
+}
+
+/// An image that is drawn beneath the background pattern and the strokes of every exported page,
+/// without being part of the document.
+#[derive(Debug, Clone)]
+pub struct ExportBackgroundImage {
+    /// The image.
+    pub image: render::Image,
+    /// How the image is fitted into the page box.
+    pub fit: BackgroundImageFit,
+}
+
+impl ExportBackgroundImage {
+    /// Load the background image from encoded image bytes.
+    pub fn from_image_bytes(bytes: &[u8], fit: BackgroundImageFit) -> anyhow::Result<Self> {
+        Ok(Self {
+            image: render::Image::try_from_encoded_bytes(bytes)?,
+            fit,
+        })
+    }
+
+    /// The bounds the image is placed at, fitted into the given page bounds.
+    fn placements(&self, bounds: Aabb) -> Vec<Aabb> {
+        let image_size = na::vector![
+            f64::from(self.image.pixel_width),
+            f64::from(self.image.pixel_height)
+        ];
+        if image_size[0] <= 0.0 || image_size[1] <= 0.0 {
+            return vec![];
+        }
+        let scales = bounds.extents().component_div(&image_size);
+        let scale = match self.fit {
+            BackgroundImageFit::Cover => scales.max(),
+            BackgroundImageFit::Contain => scales.min(),
+            BackgroundImageFit::Tile => {
+                let tiles = bounds.extents().component_div(&image_size).map(f64::ceil);
+                return (0..tiles[1] as usize)
+                    .flat_map(|row| {
+                        (0..tiles[0] as usize).map(move |col| {
+                            let mins = bounds.mins
+                                + image_size.component_mul(&na::vector![col as f64, row as f64]);
+                            Aabb::new(mins, mins + image_size)
+                        })
+                    })
+                    .collect();
+            }
+        };
+        let size = image_size * scale;
+        let mins = bounds.center() - size * 0.5;
+        vec![Aabb::new(mins, mins + size)]
+    }
+
+    /// Draw the image fitted into the page bounds.
+    pub(crate) fn draw_to_cairo(
+        &self,
+        cairo_cx: &cairo::Context,
+        bounds: Aabb,
+    ) -> anyhow::Result<()> {
+        for placement in self.placements(bounds) {
+            let bitmapimage = BitmapImage {
+                image: self.image.clone(),
+                rectangle: Rectangle::from_p2d_aabb(placement),
+                links: Vec::new(),
+            };
+            bitmapimage.draw_to_cairo(cairo_cx, 1.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text that is drawn at the top and the bottom margin of every exported page.
+///
+/// The placeholders `{page}`, `{pages}`, `{filename}` and `{date}` are replaced for every page.
+#[derive(Debug, Clone)]
+pub struct ExportHeaderFooter {
+    /// The text drawn at the top of the page.
+    pub header: Option<String>,
+    /// The text drawn at the bottom of the page.
+    pub footer: Option<String>,
+    /// Replaces the `{filename}` placeholder.
+    pub filename: String,
+    /// The font size.
+    pub font_size: f64,
+    /// The distance of the header and footer to the top and bottom page edge.
+    pub margin: f64,
+}
+
+impl Default for ExportHeaderFooter {
+    fn default() -> Self {
+        Self {
+            header: None,
+            footer: None,
+            filename: String::new(),
+            font_size: Self::FONT_SIZE_DEFAULT,
+            margin: Self::MARGIN_DEFAULT,
+        }
+    }
+}
+
+impl ExportHeaderFooter {
+    pub const FONT_SIZE_DEFAULT: f64 = 12.0;
+    pub const MARGIN_DEFAULT: f64 = 24.0;
+    const TEXT_COLOR: piet::Color = piet::Color::BLACK;
+
+    /// Replace the placeholders in the text for the given page, starting at 1.
+    fn expand_placeholders(&self, text: &str, page: usize, pages: usize) -> String {
+        text.replace("{page}", &page.to_string())
+            .replace("{pages}", &pages.to_string())
+            .replace("{filename}", &self.filename)
+            .replace(
+                "{date}",
+                &chrono::Local::now().format("%Y-%m-%d").to_string(),
+            )
+    }
+
+    /// Draw the header and footer horizontally centered into the page bounds.
+    pub(crate) fn draw(
+        &self,
+        piet_cx: &mut impl RenderContext,
+        bounds: Aabb,
+        page: usize,
+        pages: usize,
+    ) -> anyhow::Result<()> {
+        let max_width = (bounds.extents()[0] - 2.0 * self.margin).max(1.0);
+        for (text, at_top) in [(&self.header, true), (&self.footer, false)] {
+            let Some(text) = text else {
+                continue;
+            };
+            let text_layout = piet_cx
+                .text()
+                .new_text_layout(self.expand_placeholders(text, page, pages))
+                .font(piet::FontFamily::SANS_SERIF, self.font_size)
+                .text_color(Self::TEXT_COLOR)
+                .alignment(piet::TextAlignment::Center)
+                .max_width(max_width)
+                .build()
+                .map_err(|e| {
+                    anyhow::anyhow!("Building header/footer text layout failed, Err: {e:?}")
+                })?;
+            let size = text_layout.size();
+            let x = bounds.center()[0] - size.width * 0.5;
+            let y = if at_top {
+                bounds.mins[1] + self.margin
+            } else {
+                bounds.maxs[1] - self.margin - size.height
+            };
+            piet_cx.draw_text(&text_layout, (x, y));
+        }
+        Ok(())
+    }
+
+    /// Draw the header and footer on top of the page Svg.
+    fn draw_on_svg(
+        &self,
+        page_svg: &mut render::Svg,
+        page: usize,
+        pages: usize,
+    ) -> anyhow::Result<()> {
+        let bounds = page_svg.bounds;
+        let overlay = render::Svg::gen_with_piet_cairo_backend(
+            |piet_cx| self.draw(piet_cx, bounds, page, pages),
+            bounds,
+        )?;
+        page_svg.merge([overlay]);
+        Ok(())
+    }
+}
+
+/// The corner of the page the page number badge is drawn in.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "export_page_badge_corner")]
+pub enum ExportPageBadgeCorner {
+    #[serde(rename = "top_left")]
+    #[cfg_attr(feature = "cli", value(name = "tl"))]
+    TopLeft,
+    #[serde(rename = "top_right")]
+    #[cfg_attr(feature = "cli", value(name = "tr"))]
+    TopRight,
+    #[serde(rename = "bottom_left")]
+    #[cfg_attr(feature = "cli", value(name = "bl"))]
+    BottomLeft,
+    #[default]
+    #[serde(rename = "bottom_right")]
+    #[cfg_attr(feature = "cli", value(name = "br"))]
+    BottomRight,
+}
+
+impl TryFrom<u32> for ExportPageBadgeCorner {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "ExportPageBadgeCorner try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for ExportPageBadgeCorner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TopLeft => write!(f, "tl"),
+            Self::TopRight => write!(f, "tr"),
+            Self::BottomLeft => write!(f, "bl"),
+            Self::BottomRight => write!(f, "br"),
+        }
+    }
+}
+
+/// A circular badge with the page number that is drawn into a corner of every exported page.
+///
+/// The size is in document coordinates, so the badge is scaled together with the page content.
+#[derive(Debug, Clone, Copy)]
+pub struct ExportPageBadge {
+    /// The corner of the page.
+    pub corner: ExportPageBadgeCorner,
+    /// The diameter of the badge.
+    pub size: f64,
+    /// The fill color. The number is drawn in black or white, whichever contrasts better.
+    pub color: Color,
+}
+
+impl Default for ExportPageBadge {
+    fn default() -> Self {
+        Self {
+            corner: ExportPageBadgeCorner::default(),
+            size: Self::SIZE_DEFAULT,
+            color: Self::COLOR_DEFAULT,
+        }
+    }
+}
+
+impl ExportPageBadge {
+    pub const SIZE_DEFAULT: f64 = 24.0;
+    pub const COLOR_DEFAULT: Color = Color {
+        r: 0.208,
+        g: 0.518,
+        b: 0.894,
+        a: 1.0,
+    };
+    /// The distance of the badge to the page edges in relation to its size.
+    const MARGIN_FACTOR: f64 = 0.5;
+
+    /// The center of the badge in the page bounds.
+    fn center(&self, bounds: Aabb) -> na::Point2<f64> {
+        let inset = self.size * (Self::MARGIN_FACTOR + 0.5);
+        let (left, top) = match self.corner {
+            ExportPageBadgeCorner::TopLeft => (true, true),
+            ExportPageBadgeCorner::TopRight => (false, true),
+            ExportPageBadgeCorner::BottomLeft => (true, false),
+            ExportPageBadgeCorner::BottomRight => (false, false),
+        };
+        na::point![
+            if left {
+                bounds.mins[0] + inset
+            } else {
+                bounds.maxs[0] - inset
+            },
+            if top {
+                bounds.mins[1] + inset
+            } else {
+                bounds.maxs[1] - inset
+            }
+        ]
+    }
+
+    /// Draw the badge with the page number, starting at 1, into the page bounds.
+    pub(crate) fn draw(
+        &self,
+        piet_cx: &mut impl RenderContext,
+        bounds: Aabb,
+        page: usize,
+    ) -> anyhow::Result<()> {
+        let center = self.center(bounds);
+        piet_cx.fill(
+            kurbo::Circle::new((center[0], center[1]), self.size * 0.5),
+            &piet::Color::from(self.color),
+        );
+        let text = page.to_string();
+        // Longer numbers get a smaller font to stay inside the badge
+        let font_size = self.size / (text.len().max(2) as f64);
+        let text_color = if self.color.luma() > 0.5 {
+            piet::Color::BLACK
+        } else {
+            piet::Color::WHITE
+        };
+        let text_layout = piet_cx
+            .text()
+            .new_text_layout(text)
+            .font(piet::FontFamily::SANS_SERIF, font_size)
+            .default_attribute(piet::FontWeight::BOLD)
+            .text_color(text_color)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Building page badge text layout failed, Err: {e:?}"))?;
+        let size = text_layout.size();
+        piet_cx.draw_text(
+            &text_layout,
+            (center[0] - size.width * 0.5, center[1] - size.height * 0.5),
+        );
+        Ok(())
+    }
+
+    /// Draw the badge on top of the page Svg.
+    fn draw_on_svg(&self, page_svg: &mut render::Svg, page: usize) -> anyhow::Result<()> {
+        let bounds = page_svg.bounds;
+        let overlay = render::Svg::gen_with_piet_cairo_backend(
+            |piet_cx| self.draw(piet_cx, bounds, page),
+            bounds,
+        )?;
+        page_svg.merge([overlay]);
+        Ok(())
+    }
+}
+
+/// The pen a stroke in the legend was drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportLegendPen {
+    Brush,
+    Marker,
+    Shape,
+    Text,
+}
+
+impl std::fmt::Display for ExportLegendPen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Brush => write!(f, "Brush"),
+            Self::Marker => write!(f, "Marker"),
+            Self::Shape => write!(f, "Shape"),
+            Self::Text => write!(f, "Text"),
+        }
+    }
+}
+
+/// An entry of the legend, a distinct combination of ink color and pen used in the document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportLegendEntry {
+    pub color: Color,
+    pub pen: ExportLegendPen,
+}
+
+impl ExportLegendEntry {
+    /// The label next to the swatch, e.g. "Brush #1c71d8", with the alpha appended for translucent colors,
+    /// e.g. "Marker #f6d32d80".
+    pub fn label(&self) -> String {
+        let rgba = u32::from(self.color);
+        if rgba & 0xff == 0xff {
+            format!("{} #{:06x}", self.pen, rgba >> 8)
+        } else {
+            format!("{} #{rgba:08x}", self.pen)
+        }
+    }
+}
+
+/// A legend mapping every distinct ink color and pen of the exported strokes to a swatch with a label.
+///
+/// Sizes are in document coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct ExportLegend {
+    /// The entries in the order their first stroke is drawn.
+    pub entries: Vec<ExportLegendEntry>,
+}
+
+impl ExportLegend {
+    const WIDTH: f64 = 320.0;
+    const MARGIN: f64 = 32.0;
+    const ROW_HEIGHT: f64 = 28.0;
+    const SWATCH_SIZE: f64 = 18.0;
+    const FONT_SIZE: f64 = 14.0;
+    const TITLE: &'static str = "Legend";
+    const TITLE_FONT_SIZE: f64 = 18.0;
+
+    /// The size of the legend, with a row for the title and every entry.
+    pub fn size(&self) -> na::Vector2<f64> {
+        na::vector![
+            Self::WIDTH,
+            2.0 * Self::MARGIN + Self::ROW_HEIGHT * (self.entries.len() + 1) as f64
+        ]
+    }
+
+    /// Draw the legend on a white background into the bounds, starting at the top-left corner.
+    pub(crate) fn draw(
+        &self,
+        piet_cx: &mut impl RenderContext,
+        bounds: Aabb,
+    ) -> anyhow::Result<()> {
+        piet_cx.fill(
+            kurbo::Rect::new(
+                bounds.mins[0],
+                bounds.mins[1],
+                bounds.maxs[0],
+                bounds.maxs[1],
+            ),
+            &piet::Color::WHITE,
+        );
+        let x = bounds.mins[0] + Self::MARGIN;
+        let mut y = bounds.mins[1] + Self::MARGIN;
+        let title_layout = piet_cx
+            .text()
+            .new_text_layout(Self::TITLE)
+            .font(piet::FontFamily::SANS_SERIF, Self::TITLE_FONT_SIZE)
+            .default_attribute(piet::FontWeight::BOLD)
+            .text_color(piet::Color::BLACK)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Building legend title text layout failed, Err: {e:?}"))?;
+        piet_cx.draw_text(
+            &title_layout,
+            (x, y + (Self::ROW_HEIGHT - title_layout.size().height) * 0.5),
+        );
+        for entry in self.entries.iter() {
+            y += Self::ROW_HEIGHT;
+            let swatch = kurbo::Rect::new(
+                x,
+                y + (Self::ROW_HEIGHT - Self::SWATCH_SIZE) * 0.5,
+                x + Self::SWATCH_SIZE,
+                y + (Self::ROW_HEIGHT + Self::SWATCH_SIZE) * 0.5,
+            );
+            piet_cx.fill(swatch, &piet::Color::from(entry.color));
+            // The outline keeps light and translucent swatches visible
+            piet_cx.stroke(swatch, &piet::Color::grey(0.5), 1.0);
+            let label_layout = piet_cx
+                .text()
+                .new_text_layout(entry.label())
+                .font(piet::FontFamily::SANS_SERIF, Self::FONT_SIZE)
+                .text_color(piet::Color::BLACK)
+                .build()
+                .map_err(|e| {
+                    anyhow::anyhow!("Building legend label text layout failed, Err: {e:?}")
+                })?;
+            piet_cx.draw_text(
+                &label_layout,
+                (
+                    x + Self::SWATCH_SIZE * 1.5,
+                    y + (Self::ROW_HEIGHT - label_layout.size().height) * 0.5,
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    /// Generate the legend as Svg, with its top-left corner at the origin.
+    pub fn gen_svg(&self) -> anyhow::Result<render::Svg> {
+        let bounds = Aabb::new(na::point![0.0, 0.0], na::Point2::from(self.size()));
+        render::Svg::gen_with_piet_cairo_backend(|piet_cx| self.draw(piet_cx, bounds), bounds)
+    }
+}
+
+/// A bitmap export together with its companion at twice the resolution.
+#[derive(Debug, Clone, Default)]
+pub struct RetinaExport {
+    /// The encoded image at the standard resolution.
+    pub standard: Vec<u8>,
+    /// The encoded image at twice the resolution.
+    pub retina: Vec<u8>,
+}
+
+impl RetinaExport {
+    /// The factor the retina image is scaled by in relation to the standard image.
+    pub const SCALE: f64 = 2.0;
+    /// The suffix that is appended to the file stem of the retina image, following the common "@2x" naming.
+    pub const FILE_STEM_SUFFIX: &'static str = "@2x";
+}
+
+/// The metadata of the strokes in an exported image, written as Json sidecar next to the image.
+///
+/// The coordinates are in the coordinate system of the exported image: pixels for bitmap images, user units for
+/// Svgs.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportImageMetadata {
+    /// The width of the exported image.
+    pub width: f64,
+    /// The height of the exported image.
+    pub height: f64,
+    /// The strokes in the order they are rendered.
+    pub strokes: Vec<ExportStrokeMetadata>,
+}
+
+/// The metadata of an exported stroke.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportStrokeMetadata {
+    /// The id of the stroke, see [StrokeStore::stroke_id].
+    pub id: u64,
+    /// The stroke type, see [Stroke::type_name].
+    #[serde(rename = "type")]
+    pub stroke_type: &'static str,
+    /// The bounds of the stroke as [x, y, width, height].
+    pub bounds: [f64; 4],
+    pub style: ExportStrokeStyle,
+}
+
+/// The style of an exported stroke. Entries that don't apply to the stroke type are left out.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportStrokeStyle {
+    /// The style of brush and shape strokes, e.g. "smooth".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+    /// Colors are formatted as "#rrggbbaa".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<String>,
+    /// Scaled into the coordinate system of the exported image, like the bounds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    /// Scaled into the coordinate system of the exported image, like the bounds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+}
+
+impl ExportStrokeStyle {
+    fn from_stroke(stroke: &Stroke, scale: f64) -> Self {
+        let color_hex = |color: Color| format!("#{:08x}", u32::from(color));
+        let from_style = |style: &rnote_compose::Style| Self {
+            name: Some(match style {
+                rnote_compose::Style::Smooth(_) => "smooth",
+                rnote_compose::Style::Rough(_) => "rough",
+                rnote_compose::Style::Textured(_) => "textured",
+            }),
+            stroke_color: style.stroke_color().map(color_hex),
+            fill_color: style.fill_color().map(color_hex),
+            stroke_width: Some(style.stroke_width() * scale),
+            ..Default::default()
+        };
+        match stroke {
+            Stroke::BrushStroke(brushstroke) => from_style(&brushstroke.style),
+            Stroke::ShapeStroke(shapestroke) => from_style(&shapestroke.style),
+            Stroke::TextStroke(textstroke) => Self {
+                stroke_color: Some(color_hex(textstroke.text_style.color)),
+                font_family: Some(textstroke.text_style.font_family.clone()),
+                font_size: Some(textstroke.text_style.font_size * scale),
+                ..Default::default()
+            },
+            Stroke::VectorImage(_) | Stroke::BitmapImage(_) => Self::default(),
+        }
+    }
+}
+
+impl ExportImageMetadata {
+    /// Collects the metadata of the content strokes in the coordinate system of the image the content is exported to.
+    ///
+    /// Mirrors the steps of the export: the content bounds loosened by the margin are moved to the origin, flipped
+    /// and rotated, rotated by 90° for the orientation when needed, and finally rendered with the scale.
+    fn from_content(
+        content: &StrokeContent,
+        margin: f64,
+        scale: f64,
+        flip: ExportFlip,
+        rotation: ExportRotation,
+        orientation: Option<ExportOrientation>,
+    ) -> Option<Self> {
+        let content_bounds = content.bounds()?.loosened(margin);
+        let mut image_bounds = Aabb::new(na::point![0.0, 0.0], content_bounds.extents().into());
+        let output_transform = export_output_transform(flip, rotation, image_bounds);
+        if let Some(output_transform) = &output_transform {
+            image_bounds = output_transform.transform_aabb(image_bounds);
+        }
+        let rotate_90deg = orientation.is_some_and(|o| o.needs_rotation(image_bounds.extents()));
+        let image_height = image_bounds.extents()[1];
+        let image_extents = if rotate_90deg {
+            na::vector![image_height, image_bounds.extents()[0]]
+        } else {
+            image_bounds.extents()
+        };
+
+        let map_bounds = |bounds: Aabb| -> Aabb {
+            let mut bounds = Aabb::new(
+                bounds.mins - content_bounds.mins.coords,
+                bounds.maxs - content_bounds.mins.coords,
+            );
+            if let Some(output_transform) = &output_transform {
+                bounds = output_transform.transform_aabb(bounds);
+            }
+            if rotate_90deg {
+                // Same as `render::Svg::rotate_90deg()`, mapping (x, y) to (height - y, x)
+                bounds = Aabb::new(
+                    na::point![image_height - bounds.maxs[1], bounds.mins[0]],
+                    na::point![image_height - bounds.mins[1], bounds.maxs[0]],
+                );
+            }
+            Aabb::new(bounds.mins * scale, bounds.maxs * scale)
+        };
+
+        Some(Self {
+            width: image_extents[0] * scale,
+            height: image_extents[1] * scale,
+            strokes: content
+                .strokes
+                .iter()
+                .zip(&content.stroke_ids)
+                .map(|(stroke, &id)| {
+                    let bounds = map_bounds(stroke.bounds());
+                    ExportStrokeMetadata {
+                        id,
+                        stroke_type: stroke.type_name(),
+                        bounds: [
+                            bounds.mins[0],
+                            bounds.mins[1],
+                            bounds.extents()[0],
+                            bounds.extents()[1],
+                        ],
+                        style: ExportStrokeStyle::from_stroke(stroke, scale),
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Called with the amount of drawn strokes and the total amount of strokes that are drawn by an export.
+pub type ExportProgressCallback = Box<dyn FnMut(usize, usize) + Send>;
+
+/// Document export preferences.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename = "doc_export_prefs")]
+pub struct DocExportPrefs {
+    /// Whether the background should be exported.
+    #[serde(rename = "with_background")]
+    pub with_background: bool,
+    /// Whether the background pattern should be exported.
+    #[serde(rename = "with_pattern")]
+    pub with_pattern: bool,
+    /// Whether the strokes should be exported.
+    #[serde(rename = "with_strokes")]
+    pub with_strokes: bool,
+    /// Whether the background and stroke colors should be optimized for printing.
+    #[serde(rename = "optimize_printing")]
+    pub optimize_printing: bool,
+    /// Whether identifying metadata (e.g. creation date, producing software) should be stripped from the output.
+    #[serde(rename = "strip_metadata")]
+    pub strip_metadata: bool,
+    /// When set, all strokes are rendered with this color instead of their own. Images are not affected.
+    #[serde(rename = "ink_color")]
+    pub ink_color: Option<Color>,
+    /// The minimum width strokes are drawn with, in pixels of the output at the export resolution.
+    ///
+    /// Keeps thin strokes visible at low resolutions. Disabled when 0.0.
+    #[serde(rename = "min_line_width")]
+    pub min_line_width: f64,
+    /// How the paths of the strokes are smoothed before rendering, e.g. to clean up jagged imported strokes.
+    #[serde(rename = "smooth_strokes")]
+    pub smooth_strokes: StrokeSmoothing,
+    /// Whether the strokes in exported Svgs are tagged with Css classes derived from their type and color.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_classes")]
+    pub svg_classes: bool,
+    /// Where the coordinate system of exported Svgs is anchored.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_origin")]
+    pub svg_origin: SvgOrigin,
+    /// Whether exported Svgs start with an Xml comment that records the rnote version and the time of the export.
+    ///
+    /// Only has an effect when exporting to Svg. Not added when the metadata is stripped.
+    #[serde(rename = "svg_comment")]
+    pub svg_comment: bool,
+    /// The unit of the width and height of exported Svgs, converted with the dpi of the document format.
+    /// Unitless when not set. The viewBox is always unitless.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_units")]
+    pub svg_units: Option<SvgUnits>,
+    /// The export format.
+    #[serde(rename = "export_format")]
+    pub export_format: DocExportFormat,
+    /// The page order when documents with layouts that expand in horizontal and vertical directions are cut into pages.
+    #[serde(rename = "page_order")]
+    pub page_order: SplitOrder,
+    /// The orientation of the exported pages. Only has an effect on page based formats.
+    #[serde(rename = "orientation")]
+    pub orientation: ExportOrientation,
+    /// When set, Pdf pages are filled with this (opaque) color and the content is composited against it,
+    /// so that semi-transparent strokes look the same in every Pdf viewer.
+    ///
+    /// Only has an effect when exporting to Pdf.
+    #[serde(rename = "pdf_background_color")]
+    pub pdf_background_color: Option<Color>,
+    /// The factor the strokes that are converted to bitmap images are additionally rendered at.
+    ///
+    /// Higher values give crisper results when the Pdf is zoomed in or printed, but need quadratically more memory.
+    /// Clamped to `1.0..=ExportPrefs::SUPERSAMPLING_MAX`. Only has an effect when exporting to Pdf.
+    #[serde(rename = "supersampling")]
+    pub supersampling: f64,
+    /// When set, the resolution in dots per inch the strokes that are converted to bitmap images are rendered at.
+    ///
+    /// Takes precedence over the Pdf entry of `ExportPrefs::format_dpis`, see `ExportPrefs::resolve_image_scale()`.
+    #[serde(rename = "dpi")]
+    pub dpi: Option<f64>,
+    /// Whether crop marks should be drawn at the corners of the trim box.
+    ///
+    /// The page box is extended by room for the marks. Only has an effect when exporting to Pdf.
+    #[serde(rename = "crop_marks")]
+    pub crop_marks: bool,
+    /// The bleed in millimeters the page box is extended by on every side.
+    ///
+    /// The background and strokes that reach over the page edges extend into the bleed.
+    /// Negative values are treated as no bleed. Only has an effect when exporting to Pdf.
+    #[serde(rename = "bleed")]
+    pub bleed: f64,
+    /// Mirroring of the exported output. Not supported when exporting to Xopp.
+    #[serde(rename = "flip")]
+    pub flip: ExportFlip,
+    /// Clockwise rotation of the exported output. Page boxes are swapped for rotations by 90° and 270°.
+    /// Not supported when exporting to Xopp.
+    #[serde(rename = "rotation")]
+    pub rotation: ExportRotation,
+    /// When set, the pages are exported at this physical paper size instead of the document format size.
+    ///
+    /// The pages are centered on the paper, `orientation` selects between portrait and landscape paper.
+    /// Only has an effect when exporting to Pdf.
+    #[serde(rename = "paper_size")]
+    pub paper_size: Option<PredefinedFormat>,
+    /// Whether the content of the pages is scaled to fit the paper inside the paper margins.
+    ///
+    /// Only has an effect when `paper_size` is set.
+    #[serde(rename = "fit_to_page")]
+    pub fit_to_page: bool,
+    /// The margins in millimeters on every side of the paper that the content is fitted into.
+    ///
+    /// Only has an effect when `paper_size` is set.
+    #[serde(rename = "paper_margins")]
+    pub paper_margins: f64,
+    /// When set, multiple pages are arranged on every exported page.
+    ///
+    /// Applied to the finished pages, after the paper size, crop marks and bleed.
+    /// Only has an effect when exporting to Pdf.
+    #[serde(rename = "nup")]
+    pub nup: Option<ExportNup>,
+    /// When set, the content is exported as a single page that is sized to the bounds of the exported strokes,
+    /// extended by this margin on every side, instead of the pages of the document format.
+    ///
+    /// Falls back to the pages of the document format when no strokes are exported.
+    /// Has no effect when exporting to Xopp.
+    #[serde(rename = "page_size_from_content")]
+    pub page_size_from_content: Option<f64>,
+    /// When set, the strokes of every page are moved so that their bounds are centered in the page box.
+    ///
+    /// Only the export is affected, the document is not modified. Has no effect when exporting to Xopp.
+    #[serde(rename = "center_content")]
+    pub center_content: Option<ExportCenterContent>,
+}
+
+impl Default for DocExportPrefs {
+    fn default() -> Self {
+        Self {
+            with_background: true,
+            with_pattern: true,
+            with_strokes: true,
+            optimize_printing: false,
+            strip_metadata: false,
+            ink_color: None,
+            min_line_width: 0.0,
+            smooth_strokes: StrokeSmoothing::default(),
+            svg_classes: false,
+            svg_origin: SvgOrigin::default(),
+            svg_comment: false,
+            svg_units: None,
+            export_format: DocExportFormat::default(),
+            page_order: SplitOrder::default(),
+            orientation: ExportOrientation::default(),
+            pdf_background_color: None,
+            supersampling: 1.0,
+            dpi: None,
+            crop_marks: false,
+            bleed: 0.0,
+            flip: ExportFlip::default(),
+            rotation: ExportRotation::default(),
+            paper_size: None,
+            fit_to_page: false,
+            paper_margins: Self::PAPER_MARGINS_DEFAULT,
+            nup: None,
+            page_size_from_content: None,
+            center_content: None,
+        }
+    }
+}
+
+impl DocExportPrefs {
+    const MARGIN: f64 = 0.0;
+    /// The default paper margins, in millimeters.
+    pub const PAPER_MARGINS_DEFAULT: f64 = 10.0;
+    /// The default margin around the content when the page size is taken from the content.
+    pub const CONTENT_MARGIN_DEFAULT: f64 = 12.0;
+    /// The distance of the crop marks from the bleed edge, in millimeters.
+    const CROP_MARKS_OFFSET_MM: f64 = 1.0;
+    /// The length of the crop marks, in millimeters.
+    const CROP_MARKS_LENGTH_MM: f64 = 5.0;
+    /// The line width of the crop marks.
+    const CROP_MARKS_LINE_WIDTH: f64 = 0.5;
+}
+
+/// Document pages export format.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "doc_pages_export_format")]
+pub enum DocPagesExportFormat {
+    #[serde(rename = "svg")]
+    Svg,
+    #[serde(rename = "png")]
+    Png,
+    #[serde(rename = "jpeg")]
+    Jpeg,
+}
+
+impl Default for DocPagesExportFormat {
+    fn default() -> Self {
+        Self::Svg
+    }
+}
+
+impl TryFrom<u32> for DocPagesExportFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "DocPagesExportFormat try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl DocPagesExportFormat {
+    pub fn file_ext(self) -> String {
+        match self {
+            Self::Svg => String::from("svg"),
+            Self::Png => String::from("png"),
+            Self::Jpeg => String::from("jpg"),
+        }
+    }
+}
+
+/// Document pages export preferences.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename = "doc_pages_export_prefs")]
+pub struct DocPagesExportPrefs {
+    /// Whether the background should be exported.
+    #[serde(rename = "with_background")]
+    pub with_background: bool,
+    /// Whether the background pattern should be exported.
+    #[serde(rename = "with_pattern")]
+    pub with_pattern: bool,
+    /// Whether the strokes should be exported.
+    #[serde(rename = "with_strokes")]
+    pub with_strokes: bool,
+    /// Whether the background and stroke colors should be optimized for printing.
+    #[serde(rename = "optimize_printing")]
+    pub optimize_printing: bool,
+    /// Whether identifying metadata (e.g. creation date, producing software) should be stripped from the output.
+    #[serde(rename = "strip_metadata")]
+    pub strip_metadata: bool,
+    /// When set, all strokes are rendered with this color instead of their own. Images are not affected.
+    #[serde(rename = "ink_color")]
+    pub ink_color: Option<Color>,
+    /// The minimum width strokes are drawn with, in pixels of the output at the export resolution.
+    ///
+    /// Keeps thin strokes visible at low resolutions. Disabled when 0.0.
+    #[serde(rename = "min_line_width")]
+    pub min_line_width: f64,
+    /// How the paths of the strokes are smoothed before rendering, e.g. to clean up jagged imported strokes.
+    #[serde(rename = "smooth_strokes")]
+    pub smooth_strokes: StrokeSmoothing,
+    /// Whether the strokes in exported Svgs are tagged with Css classes derived from their type and color.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_classes")]
+    pub svg_classes: bool,
+    /// Where the coordinate system of exported Svgs is anchored.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_origin")]
+    pub svg_origin: SvgOrigin,
+    /// Whether exported Svgs start with an Xml comment that records the rnote version and the time of the export.
+    ///
+    /// Only has an effect when exporting to Svg. Not added when the metadata is stripped.
+    #[serde(rename = "svg_comment")]
+    pub svg_comment: bool,
+    /// The unit of the width and height of exported Svgs, converted with the dpi of the document format.
+    /// Unitless when not set. The viewBox is always unitless.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_units")]
+    pub svg_units: Option<SvgUnits>,
+    /// Export format
+    #[serde(rename = "export_format")]
+    pub export_format: DocPagesExportFormat,
+    /// The page order when documents with layouts that expand in horizontal and vertical directions are cut into pages.
+    #[serde(rename = "page_order")]
+    pub page_order: SplitOrder,
+    /// The bitmap scale-factor in relation to the actual size.
+    #[serde(rename = "bitmap_scalefactor")]
+    pub bitmap_scalefactor: f64,
+    /// Quality when exporting as Jpeg.
+    #[serde(rename = "jpg_quality")]
+    pub jpeg_quality: u8,
+    /// The chroma subsampling when exporting as Jpeg.
+    #[serde(rename = "jpeg_subsampling")]
+    pub jpeg_subsampling: render::JpegSubsampling,
+    /// The orientation of the exported pages.
+    #[serde(rename = "orientation")]
+    pub orientation: ExportOrientation,
+    /// The supersampling factor for bitmap formats.
+    ///
+    /// The pages are rendered at the bitmap scale-factor multiplied by this factor and then downsampled,
+    /// which gives smoother edges at the cost of quadratically more memory while rendering.
+    /// Clamped to `1.0..=ExportPrefs::SUPERSAMPLING_MAX`.
+    #[serde(rename = "supersampling")]
+    pub supersampling: f64,
+    /// When set, the resolution in dots per inch of the exported bitmap images.
+    ///
+    /// Takes precedence over `ExportPrefs::format_dpis` and the bitmap scale-factor,
+    /// see `ExportPrefs::resolve_image_scale()`.
+    #[serde(rename = "dpi")]
+    pub dpi: Option<f64>,
+    /// When set together with `dpi`, the vertical resolution in dots per inch of the exported bitmap images,
+    /// for output devices with non-square pixels. `dpi` is then the horizontal resolution.
+    #[serde(rename = "dpi_y")]
+    pub dpi_y: Option<f64>,
+    /// Mirroring of the exported output.
+    #[serde(rename = "flip")]
+    pub flip: ExportFlip,
+    /// Clockwise rotation of the exported output. Page boxes are swapped for rotations by 90° and 270°.
+    #[serde(rename = "rotation")]
+    pub rotation: ExportRotation,
+    /// When set, Png exports are quantized to a palette with this amount of colors and encoded as indexed-color Png.
+    ///
+    /// Must be in `render::Image::PALETTE_COLORS_RANGE`.
+    #[serde(rename = "png_palette")]
+    pub png_palette: Option<u16>,
+    /// How the colors are dithered when Png exports are quantized to the palette.
+    #[serde(rename = "dither")]
+    pub dither: render::Dither,
+    /// The gamma correction applied to the rendered bitmap exports, see [render::Image::apply_gamma()].
+    ///
+    /// 1.0 keeps the colors unchanged.
+    #[serde(rename = "gamma")]
+    pub gamma: f64,
+    /// The renderer that rasterizes the bitmap exports.
+    #[serde(rename = "render_backend")]
+    pub render_backend: render::RenderBackend,
+    /// The pages are only rendered in parallel when there are more pages than this.
+    ///
+    /// Distributing the pages onto threads has an overhead that small documents don't make up for.
+    #[serde(rename = "parallel_pages_threshold")]
+    pub parallel_pages_threshold: usize,
+    /// When set, multiple pages are arranged on every exported image.
+    ///
+    /// Not applied to retina exports.
+    #[serde(rename = "nup")]
+    pub nup: Option<ExportNup>,
+    /// When set, the strokes of every page are moved so that their bounds are centered in the page box.
+    ///
+    /// Only the export is affected, the document is not modified.
+    #[serde(rename = "center_content")]
+    pub center_content: Option<ExportCenterContent>,
+}
+
+impl DocPagesExportPrefs {
+    const MARGIN: f64 = 0.0;
+    /// A conservative estimate, not a measured value: a few pages render fast enough on one thread,
+    /// so spawning work on the thread pool only pays off above it. Can be tuned per export.
+    pub const PARALLEL_PAGES_THRESHOLD_DEFAULT: usize = 4;
+
+    /// The minimum amount of pages that are rendered by one thread.
+    ///
+    /// Up to the parallel pages threshold all pages are rendered by the same thread, one after another.
+    fn pages_per_thread_min(&self, pages_amount: usize) -> usize {
+        if pages_amount > self.parallel_pages_threshold {
+            1
+        } else {
+            pages_amount.max(1)
+        }
+    }
+}
+
+impl Default for DocPagesExportPrefs {
+    fn default() -> Self {
+        Self {
+            with_background: true,
+            with_pattern: true,
+            with_strokes: true,
+            optimize_printing: false,
+            strip_metadata: false,
+            ink_color: None,
+            min_line_width: 0.0,
+            smooth_strokes: StrokeSmoothing::default(),
+            svg_classes: false,
+            svg_origin: SvgOrigin::default(),
+            svg_comment: false,
+            svg_units: None,
+            export_format: DocPagesExportFormat::default(),
+            page_order: SplitOrder::default(),
+            bitmap_scalefactor: 1.8,
+            jpeg_quality: 85,
+            jpeg_subsampling: render::JpegSubsampling::default(),
+            orientation: ExportOrientation::default(),
+            supersampling: 1.0,
+            dpi: None,
+            dpi_y: None,
+            flip: ExportFlip::default(),
+            rotation: ExportRotation::default(),
+            png_palette: None,
+            dither: render::Dither::default(),
+            gamma: 1.0,
+            render_backend: render::RenderBackend::default(),
+            parallel_pages_threshold: Self::PARALLEL_PAGES_THRESHOLD_DEFAULT,
+            nup: None,
+            center_content: None,
+        }
+    }
+}
+
+/// Selection export format.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "selection_export_format")]
+pub enum SelectionExportFormat {
+    #[serde(rename = "svg")]
+    Svg,
+    #[serde(rename = "png")]
+    Png,
+    #[serde(rename = "jpeg")]
+    Jpeg,
+}
+
+impl Default for SelectionExportFormat {
+    fn default() -> Self {
+        Self::Svg
+    }
+}
+
+impl SelectionExportFormat {
+    pub fn file_ext(self) -> String {
+        match self {
+            SelectionExportFormat::Svg => String::from("svg"),
+            SelectionExportFormat::Png => String::from("png"),
+            SelectionExportFormat::Jpeg => String::from("jpg"),
+        }
+    }
+}
+
+impl TryFrom<u32> for SelectionExportFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "SelectionExportFormat try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename = "selection_export_prefs")]
+pub struct SelectionExportPrefs {
+    /// Whether the background should be exported.
+    #[serde(rename = "with_background")]
+    pub with_background: bool,
+    /// Whether the background pattern should be exported.
+    #[serde(rename = "with_pattern")]
+    pub with_pattern: bool,
+    /// Whether the strokes should be exported.
+    #[serde(rename = "with_strokes")]
+    pub with_strokes: bool,
+    /// Whether the background and stroke colors should be optimized for printing.
+    #[serde(rename = "optimize_printing")]
+    pub optimize_printing: bool,
+    /// Whether identifying metadata (e.g. creation date, producing software) should be stripped from the output.
+    #[serde(rename = "strip_metadata")]
+    pub strip_metadata: bool,
+    /// When set, all strokes are rendered with this color instead of their own. Images are not affected.
+    #[serde(rename = "ink_color")]
+    pub ink_color: Option<Color>,
+    /// The minimum width strokes are drawn with, in pixels of the output at the export resolution.
+    ///
+    /// Keeps thin strokes visible at low resolutions. Disabled when 0.0.
+    #[serde(rename = "min_line_width")]
+    pub min_line_width: f64,
+    /// How the paths of the strokes are smoothed before rendering, e.g. to clean up jagged imported strokes.
+    #[serde(rename = "smooth_strokes")]
+    pub smooth_strokes: StrokeSmoothing,
+    /// Whether the strokes in exported Svgs are tagged with Css classes derived from their type and color.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_classes")]
+    pub svg_classes: bool,
+    /// Where the coordinate system of exported Svgs is anchored.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_origin")]
+    pub svg_origin: SvgOrigin,
+    /// Whether exported Svgs start with an Xml comment that records the rnote version and the time of the export.
+    ///
+    /// Only has an effect when exporting to Svg. Not added when the metadata is stripped.
+    #[serde(rename = "svg_comment")]
+    pub svg_comment: bool,
+    /// The unit of the width and height of exported Svgs, converted with the dpi of the document format.
+    /// Unitless when not set. The viewBox is always unitless.
+    ///
+    /// Only has an effect when exporting to Svg.
+    #[serde(rename = "svg_units")]
+    pub svg_units: Option<SvgUnits>,
+    /// Export format.
+    #[serde(rename = "export_format")]
+    pub export_format: SelectionExportFormat,
+    /// The bitmap scale-factor in relation to the actual size.
+    #[serde(rename = "bitmap_scalefactor")]
+    pub bitmap_scalefactor: f64,
+    /// Quality when exporting as Jpeg.
+    #[serde(rename = "jpg_quality")]
+    pub jpeg_quality: u8,
+    /// The chroma subsampling when exporting as Jpeg.
+    #[serde(rename = "jpeg_subsampling")]
+    pub jpeg_subsampling: render::JpegSubsampling,
+    /// The margins of the export extending the bounds of the selection.
+    #[serde(rename = "margin")]
+    pub margin: f64,
+    /// The supersampling factor for bitmap formats.
+    ///
+    /// The selection is rendered at the bitmap scale-factor multiplied by this factor and then downsampled,
+    /// which gives smoother edges at the cost of quadratically more memory while rendering.
+    /// Clamped to `1.0..=ExportPrefs::SUPERSAMPLING_MAX`.
+    #[serde(rename = "supersampling")]
+    pub supersampling: f64,
+    /// When set, the resolution in dots per inch of the exported bitmap images.
+    ///
+    /// Takes precedence over `ExportPrefs::format_dpis` and the bitmap scale-factor,
+    /// see `ExportPrefs::resolve_image_scale()`.
+    #[serde(rename = "dpi")]
+    pub dpi: Option<f64>,
+    /// When set together with `dpi`, the vertical resolution in dots per inch of the exported bitmap images,
+    /// for output devices with non-square pixels. `dpi` is then the horizontal resolution.
+    #[serde(rename = "dpi_y")]
+    pub dpi_y: Option<f64>,
+    /// Mirroring of the exported output.
+    #[serde(rename = "flip")]
+    pub flip: ExportFlip,
+    /// Clockwise rotation of the exported output. Page boxes are swapped for rotations by 90° and 270°.
+    #[serde(rename = "rotation")]
+    pub rotation: ExportRotation,
+    /// When set, Png exports are quantized to a palette with this amount of colors and encoded as indexed-color Png.
+    ///
+    /// Must be in `render::Image::PALETTE_COLORS_RANGE`.
+    #[serde(rename = "png_palette")]
+    pub png_palette: Option<u16>,
+    /// How the colors are dithered when Png exports are quantized to the palette.
+    #[serde(rename = "dither")]
+    pub dither: render::Dither,
+    /// The gamma correction applied to the rendered bitmap exports, see [render::Image::apply_gamma()].
+    ///
+    /// 1.0 keeps the colors unchanged.
+    #[serde(rename = "gamma")]
+    pub gamma: f64,
+    /// The renderer that rasterizes the bitmap exports.
+    #[serde(rename = "render_backend")]
+    pub render_backend: render::RenderBackend,
+}
+
+impl Default for SelectionExportPrefs {
+    fn default() -> Self {
+        Self {
+            with_background: true,
+            with_pattern: false,
+            with_strokes: true,
+            optimize_printing: false,
+            strip_metadata: false,
+            ink_color: None,
+            min_line_width: 0.0,
+            smooth_strokes: StrokeSmoothing::default(),
+            svg_classes: false,
+            svg_origin: SvgOrigin::default(),
+            svg_comment: false,
+            svg_units: None,
+            export_format: SelectionExportFormat::Svg,
+            bitmap_scalefactor: 1.8,
+            jpeg_quality: 85,
+            jpeg_subsampling: render::JpegSubsampling::default(),
+            margin: 12.0,
+            supersampling: 1.0,
+            dpi: None,
+            dpi_y: None,
+            flip: ExportFlip::default(),
+            rotation: ExportRotation::default(),
+            png_palette: None,
+            dither: render::Dither::default(),
+            gamma: 1.0,
+            render_backend: render::RenderBackend::default(),
+        }
+    }
+}
+
+/// The configured default resolutions per export format, in dots per inch.
+///
+/// Formats without an entry fall back to their built-in default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename = "export_format_dpis")]
+pub struct ExportFormatDpis {
+    /// The default resolution for Png exports.
+    #[serde(rename = "png")]
+    pub png: Option<f64>,
+    /// The default resolution for Jpeg exports.
+    #[serde(rename = "jpeg")]
+    pub jpeg: Option<f64>,
+    /// The default resolution of the strokes that are converted to bitmap images in Pdf exports.
+    #[serde(rename = "pdf")]
+    pub pdf: Option<f64>,
+}
+
+impl ExportFormatDpis {
+    /// The configured resolution for the given bitmap image format.
+    pub fn for_image_format(&self, image_format: image::ImageFormat) -> Option<f64> {
+        match image_format {
+            image::ImageFormat::Png => self.png,
+            image::ImageFormat::Jpeg => self.jpeg,
+            _ => None,
+        }
+    }
+}
+
+/// Options of a single export that are not part of the export preferences.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Drawn beneath the background pattern and the strokes of every exported page.
+    pub background_image: Option<ExportBackgroundImage>,
+    /// Only strokes created at or after this unix timestamp in seconds are exported.
+    pub strokes_since: Option<i64>,
+    /// When set, only the strokes with these ids are exported, see [StrokeStore::stroke_id].
+    pub stroke_ids: Option<Vec<u64>>,
+    /// Only the pages in this range of the exported pages are exported, see [Engine::export_pages_bounds].
+    pub page_range: Option<std::ops::Range<usize>>,
+    /// When set, trailing pages whose area is covered by strokes less than this fraction (0.0 - 1.0) are not exported.
+    pub trim_blank_pages: Option<f64>,
+    /// Drawn at the top and bottom margin of every exported page.
+    pub header_footer: Option<ExportHeaderFooter>,
+    /// Drawn into a corner of every exported page.
+    pub page_badge: Option<ExportPageBadge>,
+    /// When set, the legend of the ink colors and pens is appended as last page of Pdf exports.
+    pub legend_page: bool,
+    /// What happens when a stroke fails to draw while exporting.
+    pub on_stroke_error: OnStrokeError,
+    /// A Css stylesheet that is inlined into exported Svgs.
+    pub svg_style: Option<String>,
+    /// When set, the pages of Pdf exports get an invisible text layer with the recognized text.
+    #[cfg(feature = "ocr")]
+    pub ocr: Option<super::ExportOcr>,
+}
+
+/// Export preferences.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default, rename = "export_prefs")]
+pub struct ExportPrefs {
+    /// Document export preferences.
+    #[serde(rename = "doc_export_prefs")]
+    pub doc_export_prefs: DocExportPrefs,
+    //// Document pages export preferences.
+    #[serde(rename = "doc_pages_export_prefs")]
+    pub doc_pages_export_prefs: DocPagesExportPrefs,
+    /// Selection export preferences.
+    #[serde(rename = "selection_export_prefs")]
+    pub selection_export_prefs: SelectionExportPrefs,
+    /// The default resolutions per export format.
+    #[serde(rename = "format_dpis")]
+    pub format_dpis: ExportFormatDpis,
+}
+
+impl CloneConfig for ExportPrefs {
+    fn clone_config(&self) -> Self {
+        *self
+    }
+}
+
+impl ExportPrefs {
+    /// The maximum supersampling factor.
+    ///
+    /// The memory needed while rendering grows quadratically with the factor, so it needs to be bounded.
+    pub const SUPERSAMPLING_MAX: f64 = 4.0;
+
+    /// Clamps the supersampling factor to the supported range.
+    pub fn clamp_supersampling(supersampling: f64) -> f64 {
+        if supersampling.is_finite() {
+            supersampling.clamp(1.0, Self::SUPERSAMPLING_MAX)
+        } else {
+            1.0
+        }
+    }
+
+    /// The supported range of export resolutions, in dots per inch.
+    pub const DPI_RANGE: std::ops::RangeInclusive<f64> = 1.0..=2400.0;
+
+    /// The ratio of the vertical to the horizontal image scale for the explicit resolutions of a bitmap export.
+    ///
+    /// Is `1.0` for square pixels, unless both resolutions are set.
+    pub fn dpi_aspect_ratio(dpi: Option<f64>, dpi_y: Option<f64>) -> f64 {
+        let clamp = |dpi: f64| dpi.clamp(*Self::DPI_RANGE.start(), *Self::DPI_RANGE.end());
+        match (
+            dpi.filter(|dpi| dpi.is_finite()),
+            dpi_y.filter(|dpi| dpi.is_finite()),
+        ) {
+            (Some(dpi), Some(dpi_y)) => clamp(dpi_y) / clamp(dpi),
+            _ => 1.0,
+        }
+    }
+
+    /// Resolves the image scale-factor an export is rendered at.
+    ///
+    /// The precedence is:
+    /// 1. the explicitly requested resolution (e.g. `--export-dpi` of the cli),
+    /// 2. the configured resolution for the format in `ExportPrefs::format_dpis`,
+    /// 3. the built-in default scale-factor.
+    ///
+    /// Resolutions are clamped to `ExportPrefs::DPI_RANGE` and converted to a scale-factor
+    /// relative to the document resolution. Non-finite resolutions are ignored.
+    pub fn resolve_image_scale(
+        explicit_dpi: Option<f64>,
+        format_dpi: Option<f64>,
+        default_scale: f64,
+        document_dpi: f64,
+    ) -> f64 {
+        match explicit_dpi
+            .filter(|dpi| dpi.is_finite())
+            .or(format_dpi.filter(|dpi| dpi.is_finite()))
+        {
+            Some(dpi) => dpi.clamp(*Self::DPI_RANGE.start(), *Self::DPI_RANGE.end()) / document_dpi,
+            None => default_scale,
+        }
+    }
+}
+
+impl Engine {
+    /// The used image scale-factor for any strokes that are converted to bitmap images on export.
+    pub const STROKE_EXPORT_IMAGE_SCALE: f64 = 1.8;
+
+    /// Save the current document as a .rnote file.
+    pub fn save_as_rnote_bytes(
+        &self,
+        file_name: String,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<u8>>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
+        let engine_snapshot = self.take_snapshot();
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<u8>> {
+                let rnote_file = RnoteFile {
+                    engine_snapshot: ijson::to_value(&engine_snapshot)?,
+                };
+                rnote_file.save_as_bytes(&file_name)
+            };
+            if oneshot_sender.send(result()).is_err() {
+                error!(
+                    "Sending result to receiver failed while saving document as rnote bytes. Receiver already dropped."
+                );
+            }
+        });
+        oneshot_receiver
+    }
+
+    /// Extract the current engine configuration.
+    pub fn extract_engine_config(&self) -> EngineConfig {
+        EngineConfig {
+            document: self.document.clone_config(),
+            pens_config: self.pens_config.clone_config(),
+            penholder: self.penholder.clone_config(),
+            import_prefs: self.import_prefs.clone_config(),
+            export_prefs: self.export_prefs.clone_config(),
+            pen_sounds: self.pen_sounds(),
+            optimize_epd: self.optimize_epd(),
+            render_overscan: self.render_overscan(),
+        }
+    }
+
+    /// Filters the keys for the strokes that should be exported, see [ExportOptions::strokes_since]
+    /// and [ExportOptions::stroke_ids].
+    fn filter_export_keys(
+        &self,
+        keys: Vec<StrokeKey>,
+        export_options: &ExportOptions,
+    ) -> Vec<StrokeKey> {
+        let keys = match export_options.strokes_since {
+            Some(since) => self.store.filter_keys_created_since(keys, since),
+            None => keys,
+        };
+        match &export_options.stroke_ids {
+            Some(ids) => self.store.filter_keys_w_ids(keys, ids),
+            None => keys,
+        }
+    }
+
+    /// The legend of the distinct ink colors and pens of the exported strokes. Images are not part of it.
+    pub fn export_legend(&self, export_options: &ExportOptions) -> ExportLegend {
+        let mut entries: Vec<ExportLegendEntry> = vec![];
+        for key in self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options) {
+            let Some(stroke) = self.store.get_stroke_ref(key) else {
+                continue;
+            };
+            let Some(color) = stroke.ink_color() else {
+                continue;
+            };
+            let pen = match stroke {
+                Stroke::BrushStroke(_)
+                    if self.store.stroke_layer(key) == Some(StrokeLayer::Highlighter) =>
+                {
+                    ExportLegendPen::Marker
+                }
+                Stroke::BrushStroke(_) => ExportLegendPen::Brush,
+                Stroke::ShapeStroke(_) => ExportLegendPen::Shape,
+                _ => ExportLegendPen::Text,
+            };
+            let entry = ExportLegendEntry { color, pen };
+            // Colors are compared as they are labeled
+            if !entries
+                .iter()
+                .any(|e| e.pen == pen && u32::from(e.color) == u32::from(color))
+            {
+                entries.push(entry);
+            }
+        }
+        ExportLegend { entries }
+    }
+
+    /// Export the legend of the exported strokes as Svg, see [Engine::export_legend].
+    pub fn export_legend_as_svg_bytes(
+        &self,
+        export_options: &ExportOptions,
+    ) -> anyhow::Result<Vec<u8>> {
+        let legend_svg = self.export_legend(export_options).gen_svg()?;
+        Ok(rnote_compose::utils::add_xml_header(
+            wrap_export_svg_root(&legend_svg, None, self.document.format.dpi()).as_str(),
+        )
+        .into_bytes())
+    }
+
+    /// The amount of strokes that are exported, see [ExportOptions::strokes_since].
+    ///
+    /// Used to skip exporting documents without content.
+    pub fn export_strokes_count(&self, export_options: &ExportOptions) -> usize {
+        self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options)
+            .len()
+    }
+
+    /// Iterates over the exported strokes in the order they are rendered, see [ExportOptions::strokes_since].
+    ///
+    /// The strokes are looked up when the iterator advances, so that consumers can generate their Svg or images
+    /// one at a time with the [Content] methods instead of exporting the entire document at once.
+    pub fn export_strokes_iter(
+        &self,
+        export_options: &ExportOptions,
+    ) -> impl Iterator<Item = (StrokeKey, Arc<Stroke>)> + '_ {
+        self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options)
+            .into_iter()
+            .filter_map(|key| Some((key, self.store.get_stroke_arc(key)?)))
+    }
+
+    /// Iterates over the Svgs of the exported strokes, generated when the iterator advances.
+    pub fn export_strokes_svg_iter(
+        &self,
+        export_options: &ExportOptions,
+    ) -> impl Iterator<Item = (StrokeKey, anyhow::Result<render::Svg>)> + '_ {
+        self.export_strokes_iter(export_options)
+            .map(|(key, stroke)| (key, stroke.gen_svg()))
+    }
+
+    /// Iterates over the bitmap images of the exported strokes, generated when the iterator advances.
+    ///
+    /// A larger `image_scale` renders them in a higher resolution, their bounds are not scaled by it.
+    pub fn export_strokes_images_iter(
+        &self,
+        image_scale: f64,
+        export_options: &ExportOptions,
+    ) -> impl Iterator<Item = (StrokeKey, anyhow::Result<Vec<render::Image>>)> + '_ {
+        self.export_strokes_iter(export_options)
+            .map(move |(key, stroke)| {
+                // With the stroke bounds as viewport the entire stroke is rendered
+                let images = stroke
+                    .gen_images(stroke.bounds(), image_scale)
+                    .map(|images| match images {
+                        GeneratedContentImages::Full(images)
+                        | GeneratedContentImages::Partial { images, .. } => images,
+                    });
+                (key, images)
+            })
+    }
+
+    /// Renders a stroke-by-stroke playback of the exported strokes, drawn in the order they were created.
+    ///
+    /// The first frame shows only the background, every following frame draws one more stroke on top.
+    /// The frames are passed to `on_frame` as they are rendered, so that they don't need to be held in memory.
+    /// Returns the amount of rendered frames, which is never zero.
+    pub fn export_playback_frames(
+        &self,
+        with_background: bool,
+        with_pattern: bool,
+        image_scale: f64,
+        export_options: &ExportOptions,
+        mut on_frame: impl FnMut(render::Image) -> anyhow::Result<()>,
+    ) -> anyhow::Result<usize> {
+        let mut keys =
+            self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options);
+        // Strokes created at the same time, or without a creation time, keep their rendering order
+        keys.sort_by_key(|key| self.store.stroke_created(*key));
+        let bounds = self
+            .bounds_w_content_extended()
+            .unwrap_or(self.document.bounds());
+        bounds.assert_valid()?;
+        let width = (bounds.extents()[0] * image_scale).round() as i32;
+        let height = (bounds.extents()[1] * image_scale).round() as i32;
+        // The strokes are drawn incrementally onto the canvas, which is copied for every frame
+        let canvas =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).map_err(|e| {
+                anyhow::anyhow!(
+                    "Creating image surface with dimensions ({width}, {height}) failed, Err: {e:?}"
+                )
+            })?;
+        let draw_to_canvas =
+            |content: StrokeContent, draw_background: bool| -> anyhow::Result<()> {
+                let cairo_cx = cairo::Context::new(&canvas)?;
+                cairo_cx.scale(image_scale, image_scale);
+                cairo_cx.translate(-bounds.mins[0], -bounds.mins[1]);
+                content.with_bounds(Some(bounds)).draw_to_cairo(
+                    &cairo_cx,
+                    draw_background,
+                    with_pattern,
+                    false,
+                    0.0,
+                    image_scale,
+                )
+            };
+        let mut emit_frame = || -> anyhow::Result<()> {
+            let frame = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+            {
+                let cairo_cx = cairo::Context::new(&frame)?;
+                cairo_cx.set_source_surface(&canvas, 0.0, 0.0)?;
+                cairo_cx.paint()?;
+            }
+            on_frame(render::Image::try_from_cairo_surface(frame, bounds)?)
+        };
+
+        draw_to_canvas(
+            StrokeContent::default()
+                .with_background(Some(self.document.background))
+                .with_background_image(export_options.background_image.clone()),
+            with_background,
+        )?;
+        emit_frame()?;
+        for key in keys.iter() {
+            draw_to_canvas(self.export_strokes_content(&[*key], export_options), false)?;
+            emit_frame()?;
+        }
+        Ok(keys.len() + 1)
+    }
+
+    pub fn extract_document_content(&self, export_options: &ExportOptions) -> StrokeContent {
+        self.export_strokes_content(
+            &self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options),
+            export_options,
+        )
+        .with_bounds(Some(
+            self.bounds_w_content_extended()
+                .unwrap_or(self.document.bounds()),
+        ))
+        .with_background(Some(self.document.background))
+        .with_background_image(export_options.background_image.clone())
+    }
+
+    /// The bounds of the exported strokes, extended by the margin on every side.
+    ///
+    /// Used as page bounds when the page size is taken from the content, None when no strokes are exported.
+    pub fn export_content_bounds(
+        &self,
+        margin: f64,
+        export_options: &ExportOptions,
+    ) -> Option<Aabb> {
+        let margin = if margin.is_finite() {
+            margin.max(0.0)
+        } else {
+            0.0
+        };
+        self.store
+            .bounds_for_strokes(
+                &self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options),
+            )
+            .map(|bounds| bounds.loosened(margin))
+    }
+
+    pub fn extract_pages_content(
+        &self,
+        page_order: SplitOrder,
+        export_options: &ExportOptions,
+    ) -> Vec<StrokeContent> {
+        self.extract_pages_content_w_bleed(page_order, 0.0, export_options)
+    }
+
+    /// The bounds of the pages that are exported, the pages with content without the trailing pages
+    /// that are trimmed by [ExportOptions::trim_blank_pages].
+    ///
+    /// At least one page is returned.
+    pub fn export_pages_bounds(
+        &self,
+        page_order: SplitOrder,
+        export_options: &ExportOptions,
+    ) -> Vec<Aabb> {
+        let mut pages_bounds = self.pages_bounds_w_content(page_order);
+        if let Some(threshold) = export_options.trim_blank_pages {
+            while pages_bounds.len() > 1
+                && pages_bounds.last().is_some_and(|bounds| {
+                    self.export_page_coverage(*bounds, export_options) < threshold
+                })
+            {
+                pages_bounds.pop();
+            }
+        }
+        pages_bounds
+    }
+
+    /// The fraction (0.0 - 1.0) of the page area that is covered by the bounds of the exported strokes.
+    ///
+    /// Overlapping strokes are counted multiple times, so this is an upper bound of the covered area.
+    pub fn export_page_coverage(&self, page_bounds: Aabb, export_options: &ExportOptions) -> f64 {
+        let page_area = page_bounds.volume();
+        if page_area <= 0.0 {
+            return 0.0;
+        }
+        let covered_area = self
+            .store
+            .strokes_bounds(
+                &self.filter_export_keys(
+                    self.store
+                        .stroke_keys_as_rendered_intersecting_bounds(page_bounds),
+                    export_options,
+                ),
+            )
+            .into_iter()
+            .filter_map(|bounds| bounds.intersection(&page_bounds))
+            .map(|bounds| bounds.volume())
+            .sum::<f64>();
+        (covered_area / page_area).min(1.0)
+    }
+
+    /// The bounds of the exported pages in [ExportOptions::page_range], together with the index of each page
+    /// in all pages of the document.
+    ///
+    /// The index doesn't change when preceding pages are skipped because they are empty or outside the page range.
+    pub fn export_pages_bounds_w_indices(
+        &self,
+        page_order: SplitOrder,
+        export_options: &ExportOptions,
+    ) -> Vec<(usize, Aabb)> {
+        let doc_pages_bounds = self.document.pages_bounds(page_order);
+        let mut pages_bounds = self.export_pages_bounds(page_order, export_options);
+        if let Some(page_range) = &export_options.page_range {
+            let end = page_range.end.min(pages_bounds.len());
+            let start = page_range.start.min(end);
+            pages_bounds = pages_bounds.drain(start..end).collect();
+        }
+        pages_bounds
+            .into_iter()
+            .map(|bounds| {
+                let index = doc_pages_bounds
+                    .iter()
+                    .position(|doc_page_bounds| *doc_page_bounds == bounds)
+                    .unwrap_or_default();
+                (index, bounds)
+            })
+            .collect()
+    }
+
+    /// Extract the pages content with the page bounds extended by the bleed on every side.
+    ///
+    /// Only the pages in [ExportOptions::page_range] are extracted.
+    pub fn extract_pages_content_w_bleed(
+        &self,
+        page_order: SplitOrder,
+        bleed: f64,
+        export_options: &ExportOptions,
+    ) -> Vec<StrokeContent> {
+        self.export_pages_bounds_w_indices(page_order, export_options)
+            .into_iter()
+            .map(|(_, bounds)| {
+                let bounds = bounds.loosened(bleed);
+                self.export_strokes_content(
+                    &self.export_page_keys(bounds, export_options),
+                    export_options,
+                )
+                .with_bounds(Some(bounds))
+                .with_background(Some(self.document.background))
+                .with_background_image(export_options.background_image.clone())
+            })
+            .collect()
+    }
+
+    /// The keys of the exported strokes on the page, in the order they are rendered.
+    fn export_page_keys(
+        &self,
+        page_bounds: Aabb,
+        export_options: &ExportOptions,
+    ) -> Vec<StrokeKey> {
+        self.filter_export_keys(
+            self.store
+                .stroke_keys_as_rendered_intersecting_bounds(page_bounds),
+            export_options,
+        )
+    }
+
+    pub fn extract_selection_content(
+        &self,
+        export_options: &ExportOptions,
+    ) -> Option<StrokeContent> {
+        let selection_keys =
+            self.filter_export_keys(self.store.selection_keys_as_rendered(), export_options);
+        if selection_keys.is_empty() {
+            return None;
+        }
+        Some(
+            self.export_strokes_content(&selection_keys, export_options)
+                .with_background(Some(self.document.background)),
+        )
+    }
+
+    /// Content with the exported strokes of the keys and their ids, handling the errors of strokes that fail to draw
+    /// according to [ExportOptions::on_stroke_error].
+    fn export_strokes_content(
+        &self,
+        keys: &[StrokeKey],
+        export_options: &ExportOptions,
+    ) -> StrokeContent {
+        StrokeContent::default()
+            .with_strokes(self.store.get_strokes_arc(keys))
+            .with_stroke_ids(keys.iter().copied().map(StrokeStore::stroke_id).collect())
+            .with_on_stroke_error(export_options.on_stroke_error)
+    }
+
+    /// Export the current engine config as Json string.
+    pub fn export_engine_config_as_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(&self.extract_engine_config())?)
+    }
+
+    /// Export the entire engine state as Json string.
+    ///
+    /// Only intended to be used for debugging.
+    pub fn export_state_as_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Export the document.
+    pub fn export_doc(
+        &self,
+        title: String,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
+        let doc_export_prefs =
+            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
+
+        match doc_export_prefs.export_format {
+            DocExportFormat::Svg => {
+                self.export_doc_as_svg_bytes(doc_export_prefs_override, export_options)
+            }
+            DocExportFormat::Pdf => self.export_doc_as_pdf_bytes(
+                title,
+                doc_export_prefs_override,
+                export_options,
+                Vec::new(),
+                None,
+            ),
+            DocExportFormat::Xopp => {
+                self.export_doc_as_xopp_bytes(title, doc_export_prefs_override, export_options)
+            }
+        }
+    }
+
+    /// Export the document as Pdf and set the page labels that are displayed by Pdf viewers.
+    ///
+    /// The labels are assigned by the index of the page in the document, so skipped empty pages or pages outside
+    /// the page range don't shift the labels of the following pages. Pages without a label keep the default numbering.
+    pub fn export_doc_as_pdf_w_page_labels(
+        &self,
+        title: String,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+        page_labels: Vec<String>,
+    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
+        self.export_doc_as_pdf_bytes(
+            title,
+            doc_export_prefs_override,
+            export_options,
+            page_labels,
+            None,
+        )
+    }
+
+    /// Export the document as Pdf and report the progress while drawing the strokes.
+    ///
+    /// The strokes are drawn in chunks and the callback is called after every chunk.
+    pub fn export_doc_as_pdf_w_progress(
+        &self,
+        title: String,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+        progress: ExportProgressCallback,
+    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
+        self.export_doc_as_pdf_bytes(
+            title,
+            doc_export_prefs_override,
+            export_options,
+            Vec::new(),
+            Some(progress),
+        )
+    }
+
+    /// Export the doc with the strokes as Svg.
+    fn export_doc_as_svg_bytes(
+        &self,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
+        let doc_export_prefs =
+            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
+        let mut doc_content = self.extract_document_content(export_options);
+        if let Some(content_bounds) = doc_export_prefs
+            .page_size_from_content
+            .and_then(|margin| self.export_content_bounds(margin, export_options))
+        {
+            doc_content = doc_content.with_bounds(Some(content_bounds));
+        }
+        if !doc_export_prefs.with_strokes {
+            doc_content = doc_content.without_strokes();
+        }
+        if let Some(center_content) = doc_export_prefs.center_content {
+            doc_content = doc_content.with_centered_content(center_content);
+        }
+        if let Some(ink_color) = doc_export_prefs.ink_color {
+            doc_content = doc_content.with_ink_color(ink_color);
+        }
+        doc_content = doc_content.with_stroke_smoothing(doc_export_prefs.smooth_strokes);
+        doc_content = doc_content.with_min_line_width(doc_export_prefs.min_line_width);
+        let header_footer = export_options.header_footer.clone();
+        let page_badge = export_options.page_badge;
+        let svg_style = export_options.svg_style.clone();
+        let document_dpi = self.document.format.dpi();
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<u8>> {
+                let mut content_bounds = svg_content_bounds(&doc_content, DocExportPrefs::MARGIN);
+                let mut doc_svg = gen_export_svg(
+                    &doc_content,
+                    doc_export_prefs.svg_classes,
+                    doc_export_prefs.with_background,
+                    doc_export_prefs.with_pattern,
+                    doc_export_prefs.optimize_printing,
+                    DocExportPrefs::MARGIN,
+                )?
+                .ok_or(anyhow::anyhow!("Generating doc svg failed, returned None."))?;
+                if let Some(transform) = export_output_transform(
+                    doc_export_prefs.flip,
+                    doc_export_prefs.rotation,
+                    doc_svg.bounds,
+                ) {
+                    doc_svg.transform(&transform)?;
+                    content_bounds = content_bounds.map(|b| transform.transform_aabb(b));
+                }
+                if let Some(header_footer) = &header_footer {
+                    // The document is exported as a single page
+                    header_footer.draw_on_svg(&mut doc_svg, 1, 1)?;
+                }
+                if let Some(page_badge) = &page_badge {
+                    page_badge.draw_on_svg(&mut doc_svg, 1)?;
+                }
+                apply_svg_origin(&mut doc_svg, doc_export_prefs.svg_origin, content_bounds)?;
+                if let Some(svg_style) = &svg_style {
+                    inline_svg_style(&mut doc_svg, svg_style);
+                }
+                Ok(rnote_compose::utils::add_xml_header(
+                    with_export_comment(
+                        wrap_export_svg_root(&doc_svg, doc_export_prefs.svg_units, document_dpi),
+                        doc_export_prefs.svg_comment && !doc_export_prefs.strip_metadata,
+                    )
+                    .as_str(),
+                )
+                .into_bytes())
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting document as Svg bytes. Receiver already dropped.");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Export the doc with the strokes as Pdf.
+    fn export_doc_as_pdf_bytes(
+        &self,
+        title: String,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+        page_labels: Vec<String>,
+        mut progress: Option<ExportProgressCallback>,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<u8>>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
+        let doc_export_prefs =
+            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
+        let document_dpi = self.document.format.dpi();
+        let mm_to_px = |mm: f64| {
+            MeasureUnit::convert_measurement(
+                mm,
+                MeasureUnit::Mm,
+                document_dpi,
+                MeasureUnit::Px,
+                document_dpi,
+            )
+        };
+        let bleed = if doc_export_prefs.bleed.is_finite() {
+            mm_to_px(doc_export_prefs.bleed.max(0.0))
+        } else {
+            0.0
+        };
+        // the area around the bleed that holds the crop marks
+        let crop_marks_area = if doc_export_prefs.crop_marks {
+            mm_to_px(DocExportPrefs::CROP_MARKS_OFFSET_MM + DocExportPrefs::CROP_MARKS_LENGTH_MM)
+        } else {
+            0.0
+        };
+        let content_page_bounds = doc_export_prefs
+            .page_size_from_content
+            .and_then(|margin| self.export_content_bounds(margin, export_options));
+        let mut pages_content = match content_page_bounds {
+            Some(bounds) => vec![self
+                .extract_document_content(export_options)
+                .with_bounds(Some(bounds.loosened(bleed)))],
+            None => self.extract_pages_content_w_bleed(
+                doc_export_prefs.page_order,
+                bleed,
+                export_options,
+            ),
+        };
+        // The labels of the exported pages, looked up by the index of the pages in the document
+        let page_labels = match content_page_bounds {
+            Some(_) => page_labels.into_iter().take(1).map(Some).collect(),
+            None => self
+                .export_pages_bounds_w_indices(doc_export_prefs.page_order, export_options)
+                .into_iter()
+                .map(|(index, _)| page_labels.get(index).cloned())
+                .collect::<Vec<Option<String>>>(),
+        };
+        if !doc_export_prefs.with_strokes {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.without_strokes())
+                .collect();
+        }
+        if let Some(center_content) = doc_export_prefs.center_content {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.with_centered_content(center_content))
+                .collect();
+        }
+        if let Some(ink_color) = doc_export_prefs.ink_color {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.with_ink_color(ink_color))
+                .collect();
+        }
+        pages_content = pages_content
+            .into_iter()
+            .map(|c| c.with_stroke_smoothing(doc_export_prefs.smooth_strokes))
+            .collect();
+        let supersampling = ExportPrefs::clamp_supersampling(doc_export_prefs.supersampling);
+        let image_scale = ExportPrefs::resolve_image_scale(
+            doc_export_prefs.dpi,
+            self.export_prefs.format_dpis.pdf,
+            Engine::STROKE_EXPORT_IMAGE_SCALE,
+            document_dpi,
+        );
+        pages_content = pages_content
+            .into_iter()
+            .map(|c| c.with_min_line_width(doc_export_prefs.min_line_width / image_scale))
+            .collect();
+        // The page box is the content when the page size is taken from the content
+        let page_size = content_page_bounds
+            .map(|bounds| bounds.extents())
+            .unwrap_or_else(|| self.document.format.size());
+        let format_size = if doc_export_prefs.rotation.swaps_extents() {
+            na::vector![page_size[1], page_size[0]]
+        } else {
+            page_size
+        };
+        // The paper size replaces the format size and the orientation selects the paper orientation
+        let paper_size = doc_export_prefs
+            .paper_size
+            .and_then(|paper_size| paper_size.size_mm(Orientation::Portrait))
+            .map(|size| size.map(mm_to_px));
+        let paper_margins = if doc_export_prefs.paper_margins.is_finite() {
+            mm_to_px(doc_export_prefs.paper_margins.max(0.0))
+        } else {
+            0.0
+        };
+        let rotate_pages =
+            paper_size.is_none() && doc_export_prefs.orientation.needs_rotation(format_size);
+        let surface_size = if rotate_pages {
+            na::vector![format_size[1], format_size[0]]
+        } else {
+            format_size
+        }
+        .add_scalar(2.0 * (bleed + crop_marks_area));
+        let crop_marks_line_width = DocExportPrefs::CROP_MARKS_LINE_WIDTH;
+        let crop_marks_offset = bleed + mm_to_px(DocExportPrefs::CROP_MARKS_OFFSET_MM);
+        let crop_marks_length = mm_to_px(DocExportPrefs::CROP_MARKS_LENGTH_MM);
+        // In the units of the finished Pdf pages, which are Pdf points when exporting at a paper size
+        let nup_gutter = doc_export_prefs.nup.map(|nup| {
+            let gutter = mm_to_px(nup.gutter);
+            if paper_size.is_some() {
+                gutter * PDF_POINTS_PER_INCH / document_dpi
+            } else {
+                gutter
+            }
+        });
+        let header_footer = export_options.header_footer.clone();
+        let page_badge = export_options.page_badge;
+        let legend = export_options
+            .legend_page
+            .then(|| self.export_legend(export_options))
+            .filter(|legend| !legend.entries.is_empty());
+        #[cfg(feature = "ocr")]
+        let ocr = export_options.ocr.clone();
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<u8>> {
+                #[cfg(feature = "ocr")]
+                let mut ocr_recognizer = ocr.as_ref().map(|ocr| ocr.recognizer()).transpose()?;
+                let target_surface = cairo::PdfSurface::for_stream(
+                    surface_size[0],
+                    surface_size[1],
+                    Vec::<u8>::new(),
+                )
+                .context("Creating Pdf target surface failed.")?;
+
+                if !doc_export_prefs.strip_metadata {
+                    target_surface
+                        .set_metadata(cairo::PdfMetadata::Title, title.as_str())
+                        .context("Set pdf surface title metadata failed.")?;
+                    target_surface
+                        .set_metadata(
+                            cairo::PdfMetadata::CreateDate,
+                            crate::utils::now_formatted_string().as_str(),
+                        )
+                        .context("Set pdf surface date metadata failed.")?;
+                }
+
+                // New scope to avoid errors when flushing
+                {
+                    let cairo_cx = cairo::Context::new(&target_surface)
+                        .context("Creating new cairo context for pdf target surface failed.")?;
+                    let strokes_total =
+                        pages_content.iter().map(|c| c.strokes.len()).sum::<usize>();
+                    let mut strokes_drawn = 0;
+                    let pages_amount = pages_content.len();
+
+                    for (i, page_content) in pages_content.into_iter().enumerate() {
+                        let _render_span = tracing::trace_span!("render_page", page = i);
+                        let Some(page_bounds) = page_content.bounds() else {
+                            continue;
+                        };
+                        let output_transform = export_output_transform(
+                            doc_export_prefs.flip,
+                            doc_export_prefs.rotation,
+                            page_bounds,
+                        );
+                        let output_bounds = output_transform
+                            .map(|t| t.transform_aabb(page_bounds))
+                            .unwrap_or(page_bounds);
+                        let paper_fit = paper_size.map(|paper_size| {
+                            let content_bounds = page_content
+                                .strokes
+                                .iter()
+                                .map(|stroke| stroke.bounds())
+                                .reduce(|acc, bounds| acc.merged(&bounds))
+                                .map(|bounds| {
+                                    output_transform
+                                        .map(|t| t.transform_aabb(bounds))
+                                        .unwrap_or(bounds)
+                                });
+                            let fit_bounds = match content_bounds {
+                                Some(content_bounds) if doc_export_prefs.fit_to_page => {
+                                    content_bounds
+                                }
+                                _ => output_bounds.tightened(bleed),
+                            };
+                            PaperFit::new(
+                                fit_bounds,
+                                paper_size,
+                                paper_margins,
+                                doc_export_prefs.orientation,
+                                doc_export_prefs.fit_to_page,
+                            )
+                        });
+                        // Paper sizes are physical, so the page is drawn in Pdf points
+                        let surface_scale = if paper_fit.is_some() {
+                            PDF_POINTS_PER_INCH / document_dpi
+                        } else {
+                            1.0
+                        };
+                        let surface_size = match &paper_fit {
+                            Some(paper_fit) => {
+                                let surface_size = paper_fit
+                                    .paper_size
+                                    .add_scalar(2.0 * (bleed + crop_marks_area));
+                                target_surface
+                                    .set_size(
+                                        surface_size[0] * surface_scale,
+                                        surface_size[1] * surface_scale,
+                                    )
+                                    .context("Setting the Pdf page size failed.")?;
+                                surface_size
+                            }
+                            None => surface_size,
+                        };
+                        if let Some(Some(label)) = page_labels.get(i) {
+                            target_surface
+                                .set_page_label(label)
+                                .context("Setting the Pdf page label failed.")?;
+                        }
+                        cairo_cx.save()?;
+                        cairo_cx.scale(surface_scale, surface_scale);
+                        if let Some(color) = doc_export_prefs.pdf_background_color {
+                            cairo_cx.set_source_rgba(color.r, color.g, color.b, 1.0);
+                            cairo_cx.paint()?;
+                        }
+                        if doc_export_prefs.crop_marks {
+                            let trim_bounds =
+                                Aabb::new(na::point![0.0, 0.0], na::Point2::from(surface_size))
+                                    .tightened(crop_marks_area + bleed);
+                            draw_crop_marks(
+                                &cairo_cx,
+                                trim_bounds,
+                                crop_marks_offset,
+                                crop_marks_length,
+                                crop_marks_line_width,
+                            )?;
+                        }
+                        cairo_cx.translate(crop_marks_area, crop_marks_area);
+                        if let Some(paper_fit) = &paper_fit {
+                            cairo_cx.translate(
+                                bleed + paper_fit.offset[0],
+                                bleed + paper_fit.offset[1],
+                            );
+                            cairo_cx.scale(paper_fit.scale, paper_fit.scale);
+                            cairo_cx
+                                .translate(-paper_fit.bounds.mins[0], -paper_fit.bounds.mins[1]);
+                        } else {
+                            if rotate_pages {
+                                // Rotate the page content clockwise by 90° into the swapped page box
+                                cairo_cx.translate(output_bounds.extents()[1], 0.0);
+                                cairo_cx.rotate(std::f64::consts::FRAC_PI_2);
+                            }
+                            cairo_cx.translate(-output_bounds.mins[0], -output_bounds.mins[1]);
+                        }
+                        if let Some(transform) = output_transform {
+                            let c = transform.to_kurbo().as_coeffs();
+                            cairo_cx
+                                .transform(cairo::Matrix::new(c[0], c[1], c[2], c[3], c[4], c[5]));
+                        }
+                        #[cfg(feature = "ocr")]
+                        if let Some(recognizer) = ocr_recognizer.as_mut() {
+                            let words = super::ExportOcr::recognize_page(
+                                recognizer,
+                                &page_content,
+                                doc_export_prefs.with_background,
+                                doc_export_prefs.with_pattern,
+                                document_dpi,
+                            )?;
+                            // Placed behind the content, pages without text are skipped
+                            super::ExportOcr::draw_words_to_cairo(&cairo_cx, &words)?;
+                        }
+                        if doc_export_prefs.pdf_background_color.is_some() {
+                            // Composite the content as a group against the page background
+                            cairo_cx.push_group();
+                        }
+                        page_content.draw_to_cairo_chunked(
+                            &cairo_cx,
+                            doc_export_prefs.with_background,
+                            doc_export_prefs.with_pattern,
+                            doc_export_prefs.optimize_printing,
+                            DocExportPrefs::MARGIN,
+                            image_scale * supersampling,
+                            StrokeContent::DRAW_CHUNK_SIZE,
+                            |drawn| {
+                                if let Some(progress) = progress.as_mut() {
+                                    progress(strokes_drawn + drawn, strokes_total);
+                                }
+                            },
+                        )?;
+                        strokes_drawn += page_content.strokes.len();
+                        if doc_export_prefs.pdf_background_color.is_some() {
+                            cairo_cx.pop_group_to_source()?;
+                            cairo_cx.paint()?;
+                        }
+                        // Carry over the hyperlinks of imported Pdf pages as link annotations
+                        for stroke in page_content.strokes.iter() {
+                            match stroke.as_ref() {
+                                Stroke::BitmapImage(BitmapImage {
+                                    rectangle, links, ..
+                                })
+                                | Stroke::VectorImage(VectorImage {
+                                    rectangle, links, ..
+                                }) => {
+                                    for link in links.iter() {
+                                        link.draw_link_tag_to_cairo(&cairo_cx, rectangle);
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                        cairo_cx.restore()?;
+                        if header_footer.is_some() || page_badge.is_some() {
+                            let trim_bounds =
+                                Aabb::new(na::point![0.0, 0.0], na::Point2::from(surface_size))
+                                    .tightened(crop_marks_area + bleed);
+                            cairo_cx.save()?;
+                            cairo_cx.scale(surface_scale, surface_scale);
+                            let mut piet_cx = piet_cairo::CairoRenderContext::new(&cairo_cx);
+                            if let Some(header_footer) = &header_footer {
+                                header_footer.draw(
+                                    &mut piet_cx,
+                                    trim_bounds,
+                                    i + 1,
+                                    pages_amount,
+                                )?;
+                            }
+                            if let Some(page_badge) = &page_badge {
+                                page_badge.draw(&mut piet_cx, trim_bounds, i + 1)?;
+                            }
+                            piet_cx.finish().map_err(|e| {
+                                anyhow::anyhow!("Finishing piet context failed, Err: {e:?}")
+                            })?;
+                            cairo_cx.restore()?;
+                        }
+                        cairo_cx.show_page().map_err(|e| {
+                            anyhow::anyhow!(
+                                "Showing page failed while exporting page {i} as pdf, Err: {e:?}"
+                            )
+                        })?;
+                    }
+                    if let Some(legend) = &legend {
+                        let legend_size = legend.size();
+                        target_surface
+                            .set_size(legend_size[0], legend_size[1])
+                            .context("Setting the size of the legend page failed.")?;
+                        let mut piet_cx = piet_cairo::CairoRenderContext::new(&cairo_cx);
+                        legend.draw(
+                            &mut piet_cx,
+                            Aabb::new(na::point![0.0, 0.0], na::Point2::from(legend_size)),
+                        )?;
+                        piet_cx.finish().map_err(|e| {
+                            anyhow::anyhow!("Finishing piet context failed, Err: {e:?}")
+                        })?;
+                        drop(piet_cx);
+                        cairo_cx.show_page().map_err(|e| {
+                            anyhow::anyhow!(
+                                "Showing page failed while exporting the legend as pdf, Err: {e:?}"
+                            )
+                        })?;
+                    }
+                }
+                let mut data = *target_surface
+                    .finish_output_stream()
+                    .map_err(|e| anyhow::anyhow!("Finishing outputstream failed, Err: {e:?}"))?
+                    .downcast::<Vec<u8>>()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Downcasting finished output stream failed, Err: {e:?}")
+                    })?;
+                if let (Some(nup), Some(nup_gutter)) = (doc_export_prefs.nup, nup_gutter) {
+                    data = nup.impose_pdf(
+                        &data,
+                        nup_gutter,
+                        (!doc_export_prefs.strip_metadata).then_some(title.as_str()),
+                    )?;
+                }
+                if doc_export_prefs.strip_metadata {
+                    // cairo always adds some entries itself, e.g. the producer
+                    data = strip_pdf_info_dict(&data)?;
+                }
+
+                Ok(data)
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting document as Pdf bytes. Receiver already dropped.");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Export the document as a Xournal++ .xopp file.
+    fn export_doc_as_xopp_bytes(
+        &self,
+        title: String,
+        doc_export_prefs_override: Option<DocExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<u8>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<u8>>>();
+        let doc_export_prefs =
+            doc_export_prefs_override.unwrap_or(self.export_prefs.doc_export_prefs);
+        let mut pages_content =
+            self.extract_pages_content(doc_export_prefs.page_order, export_options);
+        if !doc_export_prefs.with_strokes {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.without_strokes())
+                .collect();
+        }
+        if let Some(ink_color) = doc_export_prefs.ink_color {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.with_ink_color(ink_color))
+                .collect();
+        }
+        pages_content = pages_content
+            .into_iter()
+            .map(|c| c.with_stroke_smoothing(doc_export_prefs.smooth_strokes))
+            .collect();
+        let document = self.document.clone();
+        let with_background_image = export_options.background_image.is_some();
+        let with_header_footer = export_options.header_footer.is_some();
+        let with_page_badge = export_options.page_badge.is_some();
+
+        rayon::spawn(move || {
             let result = || -> anyhow::Result<Vec<u8>> {
+                if with_background_image {
+                    return Err(anyhow::anyhow!(
+                        "A background image is not supported when exporting to Xopp."
+                    ));
+                }
+                if with_header_footer {
+                    return Err(anyhow::anyhow!(
+                        "Headers and footers are not supported when exporting to Xopp."
+                    ));
+                }
+                if with_page_badge {
+                    return Err(anyhow::anyhow!(
+                        "Page badges are not supported when exporting to Xopp."
+                    ));
+                }
+                if doc_export_prefs.flip != ExportFlip::None
+                    || doc_export_prefs.rotation != ExportRotation::Deg0
+                {
+                    return Err(anyhow::anyhow!(
+                        "Flipping or rotating the output is not supported when exporting to Xopp."
+                    ));
+                }
                 // Only one background for all pages
                 let xopp_background = xoppformat::XoppBackground {
                     name: None,
@@ -568,343 +3277,3178 @@ impl Engine {
                     },
                 };
 
-                // xopp spec needs at least one page in vec,
-                // but it is fine because pages_bounds_w_content() always produces at least one.
-                let pages = pages_content
-                    .into_iter()
-                    .filter_map(|page_content| {
-                        let page_bounds = page_content.bounds()?;
-                        // Translate strokes to to page mins and convert to XoppStrokStyle
-                        let xopp_strokestyles = page_content
-                            .strokes
-                            .into_iter()
-                            .filter_map(|mut stroke| {
-                                let mut stroke = Arc::make_mut(&mut stroke).clone();
-                                stroke.translate(-page_bounds.mins.coords);
-                                stroke.into_xopp(document.format.dpi())
-                            })
-                            .collect::<Vec<xoppformat::XoppStrokeType>>();
+                // xopp spec needs at least one page in vec,
+                // but it is fine because pages_bounds_w_content() always produces at least one.
+                let pages = pages_content
+                    .into_iter()
+                    .filter_map(|page_content| {
+                        let page_bounds = page_content.bounds()?;
+                        // Translate strokes to to page mins and convert to XoppStrokStyle
+                        let xopp_strokestyles = page_content
+                            .strokes
+                            .into_iter()
+                            .filter_map(|mut stroke| {
+                                let mut stroke = Arc::make_mut(&mut stroke).clone();
+                                stroke.translate(-page_bounds.mins.coords);
+                                stroke.into_xopp(document.format.dpi())
+                            })
+                            .collect::<Vec<xoppformat::XoppStrokeType>>();
+
+                        // Extract the strokes
+                        let xopp_strokes = xopp_strokestyles
+                            .iter()
+                            .filter_map(|stroke| {
+                                if let xoppformat::XoppStrokeType::XoppStroke(xoppstroke) = stroke {
+                                    Some(xoppstroke.clone())
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<xoppformat::XoppStroke>>();
+
+                        // Extract the texts
+                        let xopp_texts = xopp_strokestyles
+                            .iter()
+                            .filter_map(|stroke| {
+                                if let xoppformat::XoppStrokeType::XoppText(xopptext) = stroke {
+                                    Some(xopptext.clone())
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<xoppformat::XoppText>>();
+
+                        // Extract the images
+                        let xopp_images = xopp_strokestyles
+                            .iter()
+                            .filter_map(|stroke| {
+                                if let xoppformat::XoppStrokeType::XoppImage(xoppstroke) = stroke {
+                                    Some(xoppstroke.clone())
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<xoppformat::XoppImage>>();
+
+                        // In Rnote images are always rendered below strokes and text.
+                        // To match this behaviour accurately, images are separated into another layer.
+                        let image_layer = xoppformat::XoppLayer {
+                            name: None,
+                            strokes: vec![],
+                            texts: vec![],
+                            images: xopp_images,
+                        };
+
+                        let strokes_layer = xoppformat::XoppLayer {
+                            name: None,
+                            strokes: xopp_strokes,
+                            texts: xopp_texts,
+                            images: vec![],
+                        };
+
+                        let page_dimensions = crate::utils::convert_coord_dpi(
+                            page_bounds.extents(),
+                            document.format.dpi(),
+                            xoppformat::XoppFile::DPI,
+                        );
+
+                        Some(xoppformat::XoppPage {
+                            width: page_dimensions[0],
+                            height: page_dimensions[1],
+                            background: xopp_background.clone(),
+                            layers: vec![image_layer, strokes_layer],
+                        })
+                    })
+                    .collect::<Vec<xoppformat::XoppPage>>();
+
+                let xopp_title = String::from(
+                    "Xournal++ document - see https://github.com/xournalpp/xournalpp (exported from Rnote - see https://github.com/flxzt/rnote)"
+                );
+
+                let xopp_root = xoppformat::XoppRoot {
+                    title: xopp_title,
+                    fileversion: String::from("4"),
+                    preview: String::from(""),
+                    pages,
+                };
+                let xopp_file = xoppformat::XoppFile { xopp_root };
+
+                xopp_file.save_as_bytes(&title)
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!(
+                    "Sending result to receiver failed while exporting document as xopp bytes. Receiver already dropped."
+                );
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Export the document pages.
+    pub fn export_doc_pages(
+        &self,
+        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<Vec<u8>>, anyhow::Error>> {
+        let doc_pages_export_prefs =
+            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
+
+        match doc_pages_export_prefs.export_format {
+            DocPagesExportFormat::Svg => {
+                self.export_doc_pages_as_svgs_bytes(doc_pages_export_prefs_override, export_options)
+            }
+            DocPagesExportFormat::Png | DocPagesExportFormat::Jpeg => self
+                .export_doc_pages_as_bitmap_bytes(
+                    doc_pages_export_prefs_override,
+                    export_options,
+                    false,
+                    |bytes, _| bytes,
+                ),
+        }
+    }
+
+    /// Export the document as Svg.
+    fn export_doc_pages_as_svgs_bytes(
+        &self,
+        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<Vec<u8>>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<Vec<u8>>>>();
+        let doc_pages_export_prefs =
+            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
+        let mut pages_content =
+            self.extract_pages_content(doc_pages_export_prefs.page_order, export_options);
+        if !doc_pages_export_prefs.with_strokes {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.without_strokes())
+                .collect();
+        }
+        if let Some(center_content) = doc_pages_export_prefs.center_content {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.with_centered_content(center_content))
+                .collect();
+        }
+        if let Some(ink_color) = doc_pages_export_prefs.ink_color {
+            pages_content = pages_content
+                .into_iter()
+                .map(|c| c.with_ink_color(ink_color))
+                .collect();
+        }
+        pages_content = pages_content
+            .into_iter()
+            .map(|c| c.with_stroke_smoothing(doc_pages_export_prefs.smooth_strokes))
+            .collect();
+        pages_content = pages_content
+            .into_iter()
+            .map(|c| c.with_min_line_width(doc_pages_export_prefs.min_line_width))
+            .collect();
+        let header_footer = export_options.header_footer.clone();
+        let page_badge = export_options.page_badge;
+        let svg_style = export_options.svg_style.clone();
+        let document_dpi = self.document.format.dpi();
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<Vec<u8>>> {
+                let pages_amount = pages_content.len();
+                pages_content
+                    .into_par_iter()
+                    .enumerate()
+                    .with_min_len(doc_pages_export_prefs.pages_per_thread_min(pages_amount))
+                    .map(|(i, page_content)| {
+                        let _render_span = tracing::trace_span!("render_page", page = i);
+                        let mut content_bounds =
+                            svg_content_bounds(&page_content, DocPagesExportPrefs::MARGIN);
+                        let mut page_svg = gen_export_svg(
+                            &page_content,
+                            doc_pages_export_prefs.svg_classes,
+                            doc_pages_export_prefs.with_background,
+                            doc_pages_export_prefs.with_pattern,
+                            doc_pages_export_prefs.optimize_printing,
+                            DocPagesExportPrefs::MARGIN,
+                        )?
+                        .ok_or(anyhow::anyhow!(
+                            "Generating Svg for page {i} failed, returned None."
+                        ))?;
+                        if let Some(transform) = export_output_transform(
+                            doc_pages_export_prefs.flip,
+                            doc_pages_export_prefs.rotation,
+                            page_svg.bounds,
+                        ) {
+                            page_svg.transform(&transform)?;
+                            content_bounds = content_bounds.map(|b| transform.transform_aabb(b));
+                        }
+                        if doc_pages_export_prefs
+                            .orientation
+                            .needs_rotation(page_svg.bounds.extents())
+                        {
+                            // Rotates the same way as the page
+                            if let Some(transform) = export_output_transform(
+                                ExportFlip::None,
+                                ExportRotation::Deg90,
+                                page_svg.bounds,
+                            ) {
+                                content_bounds =
+                                    content_bounds.map(|b| transform.transform_aabb(b));
+                            }
+                            page_svg.rotate_90deg()?;
+                        }
+                        if let Some(header_footer) = &header_footer {
+                            header_footer.draw_on_svg(&mut page_svg, i + 1, pages_amount)?;
+                        }
+                        if let Some(page_badge) = &page_badge {
+                            page_badge.draw_on_svg(&mut page_svg, i + 1)?;
+                        }
+                        apply_svg_origin(
+                            &mut page_svg,
+                            doc_pages_export_prefs.svg_origin,
+                            content_bounds,
+                        )?;
+                        if let Some(svg_style) = &svg_style {
+                            inline_svg_style(&mut page_svg, svg_style);
+                        }
+                        Ok(rnote_compose::utils::add_xml_header(
+                            with_export_comment(
+                                wrap_export_svg_root(
+                                    &page_svg,
+                                    doc_pages_export_prefs.svg_units,
+                                    document_dpi,
+                                ),
+                                doc_pages_export_prefs.svg_comment
+                                    && !doc_pages_export_prefs.strip_metadata,
+                            )
+                            .as_str(),
+                        )
+                        .into_bytes())
+                    })
+                    .collect()
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!(
+                    "Sending result to receiver failed while exporting document pages as Svg bytes. Receiver already dropped."
+                );
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Export the document pages as bitmaps together with companions at twice the resolution,
+    /// e.g. for "@2x" images on high density displays.
+    ///
+    /// The pages are rendered once at twice the resolution and downsampled for the standard resolution,
+    /// so that both images are consistent.
+    ///
+    /// Returns an error if the format pref is not set to a bitmap variant.
+    pub fn export_doc_pages_w_retina(
+        &self,
+        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<RetinaExport>, anyhow::Error>> {
+        self.export_doc_pages_as_bitmap_bytes(
+            doc_pages_export_prefs_override,
+            export_options,
+            true,
+            |standard, retina| RetinaExport {
+                standard,
+                retina: retina.unwrap_or_default(),
+            },
+        )
+    }
+
+    /// Export the document pages as bitmap.
+    ///
+    /// When `retina` is set, the pages are rendered at twice the resolution and downsampled for the standard
+    /// resolution. `output` receives the encoded standard resolution image of every page, and the encoded image at
+    /// twice the resolution when `retina` is set.
+    ///
+    /// Returns an error if the format pref is not set to a bitmap variant.
+    fn export_doc_pages_as_bitmap_bytes<T: Send + 'static>(
+        &self,
+        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
+        export_options: &ExportOptions,
+        retina: bool,
+        output: fn(Vec<u8>, Option<Vec<u8>>) -> T,
+    ) -> oneshot::Receiver<Result<Vec<T>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<T>>>();
+        let doc_pages_export_prefs =
+            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
+        let mut pages_contents =
+            self.extract_pages_content(doc_pages_export_prefs.page_order, export_options);
+        if !doc_pages_export_prefs.with_strokes {
+            pages_contents = pages_contents
+                .into_iter()
+                .map(|c| c.without_strokes())
+                .collect();
+        }
+        if let Some(center_content) = doc_pages_export_prefs.center_content {
+            pages_contents = pages_contents
+                .into_iter()
+                .map(|c| c.with_centered_content(center_content))
+                .collect();
+        }
+        if let Some(ink_color) = doc_pages_export_prefs.ink_color {
+            pages_contents = pages_contents
+                .into_iter()
+                .map(|c| c.with_ink_color(ink_color))
+                .collect();
+        }
+        pages_contents = pages_contents
+            .into_iter()
+            .map(|c| c.with_stroke_smoothing(doc_pages_export_prefs.smooth_strokes))
+            .collect();
+        let format_dpis = self.export_prefs.format_dpis;
+        let document_dpi = self.document.format.dpi();
+        let nup_gutter = doc_pages_export_prefs.nup.map(|nup| {
+            MeasureUnit::convert_measurement(
+                nup.gutter,
+                MeasureUnit::Mm,
+                document_dpi,
+                MeasureUnit::Px,
+                document_dpi,
+            )
+        });
+        let header_footer = export_options.header_footer.clone();
+        let page_badge = export_options.page_badge;
+
+        rayon::spawn(move || {
+            let result = || -> Result<Vec<T>, anyhow::Error> {
+                let image_format = match doc_pages_export_prefs.export_format {
+                    DocPagesExportFormat::Svg => return Err(anyhow::anyhow!("Extracting bitmap image format from doc pages export prefs failed, not set to a bitmap format.")),
+                    DocPagesExportFormat::Png => image::ImageFormat::Png,
+                    DocPagesExportFormat::Jpeg => image::ImageFormat::Jpeg,
+                };
+                let supersampling =
+                    ExportPrefs::clamp_supersampling(doc_pages_export_prefs.supersampling);
+                let image_scale = ExportPrefs::resolve_image_scale(
+                    doc_pages_export_prefs.dpi,
+                    format_dpis.for_image_format(image_format),
+                    doc_pages_export_prefs.bitmap_scalefactor,
+                    document_dpi,
+                ) * supersampling;
+                // Differs per axis for output devices with non-square pixels
+                let image_scale_xy = na::vector![
+                    image_scale,
+                    image_scale
+                        * ExportPrefs::dpi_aspect_ratio(
+                            doc_pages_export_prefs.dpi,
+                            doc_pages_export_prefs.dpi_y
+                        )
+                ];
+                // The min line width is in output pixels, after downsampling
+                let min_line_width =
+                    doc_pages_export_prefs.min_line_width * supersampling / image_scale_xy.min();
+                let pages_amount = pages_contents.len();
+                let gen_page_svg = |i: usize, page_content: StrokeContent| {
+                    let mut page_svg = page_content
+                        .with_min_line_width(min_line_width)
+                        .gen_svg_w_image_scale(
+                            doc_pages_export_prefs.with_background,
+                            doc_pages_export_prefs.with_pattern,
+                            doc_pages_export_prefs.optimize_printing,
+                            DocPagesExportPrefs::MARGIN,
+                            image_scale_xy.max(),
+                        )?
+                        .ok_or(anyhow::anyhow!(
+                            "Generating Svg for page {i} failed, returned None."
+                        ))?;
+                    if let Some(transform) = export_output_transform(
+                        doc_pages_export_prefs.flip,
+                        doc_pages_export_prefs.rotation,
+                        page_svg.bounds,
+                    ) {
+                        page_svg.transform(&transform)?;
+                    }
+                    if doc_pages_export_prefs
+                        .orientation
+                        .needs_rotation(page_svg.bounds.extents())
+                    {
+                        page_svg.rotate_90deg()?;
+                    }
+                    if let Some(header_footer) = &header_footer {
+                        header_footer.draw_on_svg(&mut page_svg, i + 1, pages_amount)?;
+                    }
+                    if let Some(page_badge) = &page_badge {
+                        page_badge.draw_on_svg(&mut page_svg, i + 1)?;
+                    }
+                    anyhow::Ok(page_svg)
+                };
+                // The resolution of the downsampled images
+                let output_dpi = image_scale_xy / supersampling * document_dpi;
+                let encode = |image: render::Image, dpi: na::Vector2<f64>| {
+                    encode_export_image(
+                        image,
+                        image_format,
+                        doc_pages_export_prefs.jpeg_quality,
+                        doc_pages_export_prefs.jpeg_subsampling,
+                        doc_pages_export_prefs.png_palette,
+                        doc_pages_export_prefs.dither,
+                        doc_pages_export_prefs.gamma,
+                        doc_pages_export_prefs.strip_metadata,
+                        dpi,
+                    )
+                };
+                if let (Some(nup), Some(nup_gutter), false) =
+                    (doc_pages_export_prefs.nup, nup_gutter, retina)
+                {
+                    let pages_images = pages_contents
+                        .into_par_iter()
+                        .enumerate()
+                        .with_min_len(doc_pages_export_prefs.pages_per_thread_min(pages_amount))
+                        .map(|(i, page_content)| {
+                            let _render_span = tracing::trace_span!("render_page", page = i);
+                            gen_page_svg(i, page_content)?
+                                .gen_image_w_backend_xy(
+                                    image_scale_xy,
+                                    doc_pages_export_prefs.render_backend,
+                                )?
+                                .downsample(supersampling)
+                        })
+                        .collect::<anyhow::Result<Vec<render::Image>>>()?;
+                    // The gutter and borders are in document pixels, scaled to the downsampled images
+                    let output_scale = image_scale / supersampling;
+                    return nup
+                        .compose_images(
+                            pages_images,
+                            nup_gutter * output_scale,
+                            ExportNup::BORDER_WIDTH * output_scale,
+                        )?
+                        .into_iter()
+                        .map(|sheet| Ok(output(encode(sheet, output_dpi)?, None)))
+                        .collect();
+                }
+                pages_contents
+                    .into_par_iter()
+                    .enumerate()
+                    .with_min_len(doc_pages_export_prefs.pages_per_thread_min(pages_amount))
+                    .map(|(i, page_content)| {
+                        let _render_span = tracing::trace_span!("render_page", page = i);
+                        let page_svg = gen_page_svg(i, page_content)?;
+                        if retina {
+                            let image = page_svg
+                                .gen_image_w_backend_xy(
+                                    image_scale_xy * RetinaExport::SCALE,
+                                    doc_pages_export_prefs.render_backend,
+                                )?
+                                .downsample(supersampling)?;
+                            let retina_bytes =
+                                encode(image.clone(), output_dpi * RetinaExport::SCALE)?;
+                            let standard_bytes =
+                                encode(image.downsample(RetinaExport::SCALE)?, output_dpi)?;
+                            Ok(output(standard_bytes, Some(retina_bytes)))
+                        } else {
+                            let image = page_svg
+                                .gen_image_w_backend_xy(
+                                    image_scale_xy,
+                                    doc_pages_export_prefs.render_backend,
+                                )?
+                                .downsample(supersampling)?;
+                            Ok(output(encode(image, output_dpi)?, None))
+                        }
+                    })
+                    .collect()
+            };
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting document pages as bitmap bytes. Receiver already dropped.");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Exports the current selection.
+    pub fn export_selection(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Option<Vec<u8>>, anyhow::Error>> {
+        let selection_export_prefs =
+            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+
+        match selection_export_prefs.export_format {
+            SelectionExportFormat::Svg => {
+                self.export_selection_as_svg_bytes(selection_export_prefs_override, export_options)
+            }
+            SelectionExportFormat::Png | SelectionExportFormat::Jpeg => self
+                .export_selection_as_bitmap_bytes(
+                    selection_export_prefs_override,
+                    export_options,
+                    false,
+                    |bytes, _| bytes,
+                ),
+        }
+    }
+
+    /// Exports the selection as Svg.
+    fn export_selection_as_svg_bytes(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Option<Vec<u8>>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) =
+            oneshot::channel::<anyhow::Result<Option<Vec<u8>>>>();
+        let selection_export_prefs =
+            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+        let mut content = self.extract_selection_content(export_options);
+        if !selection_export_prefs.with_strokes {
+            content = content.map(|c| c.without_strokes());
+        }
+        if let Some(ink_color) = selection_export_prefs.ink_color {
+            content = content.map(|c| c.with_ink_color(ink_color));
+        }
+        content = content.map(|c| c.with_stroke_smoothing(selection_export_prefs.smooth_strokes));
+        content = content.map(|c| c.with_min_line_width(selection_export_prefs.min_line_width));
+        let svg_style = export_options.svg_style.clone();
+        let document_dpi = self.document.format.dpi();
+
+        rayon::spawn(move || {
+            let result = || -> Result<Option<Vec<u8>>, anyhow::Error> {
+                let Some(content) = content else {
+                    return Ok(None);
+                };
+                let mut content_bounds =
+                    svg_content_bounds(&content, selection_export_prefs.margin);
+                let Some(mut svg) = gen_export_svg(
+                    &content,
+                    selection_export_prefs.svg_classes,
+                    selection_export_prefs.with_background,
+                    selection_export_prefs.with_pattern,
+                    selection_export_prefs.optimize_printing,
+                    selection_export_prefs.margin,
+                )?
+                else {
+                    return Ok(None);
+                };
+                if let Some(transform) = export_output_transform(
+                    selection_export_prefs.flip,
+                    selection_export_prefs.rotation,
+                    svg.bounds,
+                ) {
+                    svg.transform(&transform)?;
+                    content_bounds = content_bounds.map(|b| transform.transform_aabb(b));
+                }
+                apply_svg_origin(&mut svg, selection_export_prefs.svg_origin, content_bounds)?;
+                if let Some(svg_style) = &svg_style {
+                    inline_svg_style(&mut svg, svg_style);
+                }
+
+                Ok(Some(
+                    rnote_compose::utils::add_xml_header(
+                        with_export_comment(
+                            wrap_export_svg_root(
+                                &svg,
+                                selection_export_prefs.svg_units,
+                                document_dpi,
+                            ),
+                            selection_export_prefs.svg_comment
+                                && !selection_export_prefs.strip_metadata,
+                        )
+                        .as_str(),
+                    )
+                    .into_bytes(),
+                ))
+            };
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting selection as Svg bytes. Receiver already dropped.");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Export the selection as bitmap together with a companion at twice the resolution,
+    /// e.g. for "@2x" images on high density displays.
+    ///
+    /// The selection is rendered once at twice the resolution and downsampled for the standard resolution,
+    /// so that both images are consistent.
+    ///
+    /// Returns an error if the format pref is not set to a bitmap format
+    pub fn export_selection_w_retina(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Option<RetinaExport>, anyhow::Error>> {
+        self.export_selection_as_bitmap_bytes(
+            selection_export_prefs_override,
+            export_options,
+            true,
+            |standard, retina| RetinaExport {
+                standard,
+                retina: retina.unwrap_or_default(),
+            },
+        )
+    }
+
+    /// Export the selection a bitmap bytes.
+    ///
+    /// When `retina` is set, the selection is rendered at twice the resolution and downsampled for the standard
+    /// resolution. `output` receives the encoded standard resolution image, and the encoded image at
+    /// twice the resolution when `retina` is set.
+    ///
+    /// Returns an error if the format pref is not set to a bitmap format
+    fn export_selection_as_bitmap_bytes<T: Send + 'static>(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+        retina: bool,
+        output: fn(Vec<u8>, Option<Vec<u8>>) -> T,
+    ) -> oneshot::Receiver<Result<Option<T>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Option<T>>>();
+        let format_dpis = self.export_prefs.format_dpis;
+        let document_dpi = self.document.format.dpi();
+        let selection_export_prefs =
+            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+        let mut content = self.extract_selection_content(export_options);
+        if !selection_export_prefs.with_strokes {
+            content = content.map(|c| c.without_strokes());
+        }
+        if let Some(ink_color) = selection_export_prefs.ink_color {
+            content = content.map(|c| c.with_ink_color(ink_color));
+        }
+        content = content.map(|c| c.with_stroke_smoothing(selection_export_prefs.smooth_strokes));
+
+        rayon::spawn(move || {
+            let result = || -> Result<Option<T>, anyhow::Error> {
+                let Some(content) = content else {
+                    return Ok(None);
+                };
+                let image_format = match selection_export_prefs.export_format {
+                    SelectionExportFormat::Svg => return Err(anyhow::anyhow!("Extracting bitmap image format from doc pages export prefs failed, not set to a bitmap format.")),
+                    SelectionExportFormat::Png => image::ImageFormat::Png,
+                    SelectionExportFormat::Jpeg => image::ImageFormat::Jpeg
+                };
+                let supersampling =
+                    ExportPrefs::clamp_supersampling(selection_export_prefs.supersampling);
+                let image_scale = ExportPrefs::resolve_image_scale(
+                    selection_export_prefs.dpi,
+                    format_dpis.for_image_format(image_format),
+                    selection_export_prefs.bitmap_scalefactor,
+                    document_dpi,
+                ) * supersampling;
+                // Differs per axis for output devices with non-square pixels
+                let image_scale_xy = na::vector![
+                    image_scale,
+                    image_scale
+                        * ExportPrefs::dpi_aspect_ratio(
+                            selection_export_prefs.dpi,
+                            selection_export_prefs.dpi_y
+                        )
+                ];
+                // The min line width is in output pixels, after downsampling
+                let content = content.with_min_line_width(
+                    selection_export_prefs.min_line_width * supersampling / image_scale_xy.min(),
+                );
+                let Some(mut svg) = content.gen_svg_w_image_scale(
+                    selection_export_prefs.with_background,
+                    selection_export_prefs.with_pattern,
+                    selection_export_prefs.optimize_printing,
+                    selection_export_prefs.margin,
+                    image_scale_xy.max(),
+                )?
+                else {
+                    return Ok(None);
+                };
+                if let Some(transform) = export_output_transform(
+                    selection_export_prefs.flip,
+                    selection_export_prefs.rotation,
+                    svg.bounds,
+                ) {
+                    svg.transform(&transform)?;
+                }
+                // The resolution of the downsampled images
+                let output_dpi = image_scale_xy / supersampling * document_dpi;
+                let encode = |image: render::Image, dpi: na::Vector2<f64>| {
+                    encode_export_image(
+                        image,
+                        image_format,
+                        selection_export_prefs.jpeg_quality,
+                        selection_export_prefs.jpeg_subsampling,
+                        selection_export_prefs.png_palette,
+                        selection_export_prefs.dither,
+                        selection_export_prefs.gamma,
+                        selection_export_prefs.strip_metadata,
+                        dpi,
+                    )
+                };
+                if retina {
+                    let image = svg
+                        .gen_image_w_backend_xy(
+                            image_scale_xy * RetinaExport::SCALE,
+                            selection_export_prefs.render_backend,
+                        )?
+                        .downsample(supersampling)?;
+                    let retina_bytes = encode(image.clone(), output_dpi * RetinaExport::SCALE)?;
+                    let standard_bytes =
+                        encode(image.downsample(RetinaExport::SCALE)?, output_dpi)?;
+                    Ok(Some(output(standard_bytes, Some(retina_bytes))))
+                } else {
+                    let image = svg
+                        .gen_image_w_backend_xy(
+                            image_scale_xy,
+                            selection_export_prefs.render_backend,
+                        )?
+                        .downsample(supersampling)?;
+                    Ok(Some(output(encode(image, output_dpi)?, None)))
+                }
+            };
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting selection as bitmap image bytes. Receiver already dropped");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// The metadata of the strokes on every page, in the coordinate system of the pages exported with the prefs.
+    pub fn export_doc_pages_strokes_metadata(
+        &self,
+        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> Vec<ExportImageMetadata> {
+        let doc_pages_export_prefs =
+            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
+        let scale = match doc_pages_export_prefs.export_format {
+            DocPagesExportFormat::Svg => 1.0,
+            DocPagesExportFormat::Png => ExportPrefs::resolve_image_scale(
+                doc_pages_export_prefs.dpi,
+                self.export_prefs
+                    .format_dpis
+                    .for_image_format(image::ImageFormat::Png),
+                doc_pages_export_prefs.bitmap_scalefactor,
+                self.document.format.dpi(),
+            ),
+            DocPagesExportFormat::Jpeg => ExportPrefs::resolve_image_scale(
+                doc_pages_export_prefs.dpi,
+                self.export_prefs
+                    .format_dpis
+                    .for_image_format(image::ImageFormat::Jpeg),
+                doc_pages_export_prefs.bitmap_scalefactor,
+                self.document.format.dpi(),
+            ),
+        };
+        self.extract_pages_content(doc_pages_export_prefs.page_order, export_options)
+            .into_iter()
+            .filter_map(|page_content| {
+                let page_content = if doc_pages_export_prefs.with_strokes {
+                    page_content
+                } else {
+                    page_content.without_strokes()
+                };
+                ExportImageMetadata::from_content(
+                    &page_content,
+                    DocPagesExportPrefs::MARGIN,
+                    scale,
+                    doc_pages_export_prefs.flip,
+                    doc_pages_export_prefs.rotation,
+                    Some(doc_pages_export_prefs.orientation),
+                )
+            })
+            .collect()
+    }
+
+    /// The metadata of the selected strokes, in the coordinate system of the selection exported with the prefs.
+    ///
+    /// Returns `None` when nothing is selected.
+    pub fn export_selection_strokes_metadata(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> Option<ExportImageMetadata> {
+        let selection_export_prefs =
+            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+        let image_format = match selection_export_prefs.export_format {
+            SelectionExportFormat::Svg => None,
+            SelectionExportFormat::Png => Some(image::ImageFormat::Png),
+            SelectionExportFormat::Jpeg => Some(image::ImageFormat::Jpeg),
+        };
+        let scale = image_format.map_or(1.0, |image_format| {
+            ExportPrefs::resolve_image_scale(
+                selection_export_prefs.dpi,
+                self.export_prefs.format_dpis.for_image_format(image_format),
+                selection_export_prefs.bitmap_scalefactor,
+                self.document.format.dpi(),
+            )
+        });
+        let mut content = self.extract_selection_content(export_options)?;
+        if !selection_export_prefs.with_strokes {
+            content = content.without_strokes();
+        }
+        ExportImageMetadata::from_content(
+            &content,
+            selection_export_prefs.margin,
+            scale,
+            selection_export_prefs.flip,
+            selection_export_prefs.rotation,
+            None,
+        )
+    }
+
+    /// Export every stroke as its own image, cropped to the bounds of the stroke with a transparent background.
+    ///
+    /// The selection export prefs are used, with the margin as padding around the stroke.
+    /// Returns the strokes in the order they are rendered, together with their exported bytes.
+    #[allow(clippy::type_complexity)]
+    pub fn export_strokes_as_stickers(
+        &self,
+        selection_export_prefs_override: Option<SelectionExportPrefs>,
+        export_options: &ExportOptions,
+    ) -> oneshot::Receiver<Result<Vec<(Arc<Stroke>, Vec<u8>)>, anyhow::Error>> {
+        let (oneshot_sender, oneshot_receiver) =
+            oneshot::channel::<anyhow::Result<Vec<(Arc<Stroke>, Vec<u8>)>>>();
+        let on_stroke_error = export_options.on_stroke_error;
+        let format_dpis = self.export_prefs.format_dpis;
+        let document_dpi = self.document.format.dpi();
+        let selection_export_prefs =
+            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+        let strokes = self.store.get_strokes_arc(
+            &self.filter_export_keys(self.store.stroke_keys_as_rendered(), export_options),
+        );
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<(Arc<Stroke>, Vec<u8>)>> {
+                let image_format = match selection_export_prefs.export_format {
+                    SelectionExportFormat::Svg => None,
+                    SelectionExportFormat::Png => Some(image::ImageFormat::Png),
+                    SelectionExportFormat::Jpeg => Some(image::ImageFormat::Jpeg),
+                };
+                let supersampling =
+                    ExportPrefs::clamp_supersampling(selection_export_prefs.supersampling);
+                let image_scale = match image_format {
+                    Some(image_format) => {
+                        ExportPrefs::resolve_image_scale(
+                            selection_export_prefs.dpi,
+                            format_dpis.for_image_format(image_format),
+                            selection_export_prefs.bitmap_scalefactor,
+                            document_dpi,
+                        ) * supersampling
+                    }
+                    None => 1.0,
+                };
+                let min_line_width = match image_format {
+                    // The min line width is in output pixels, after downsampling
+                    Some(_) => selection_export_prefs.min_line_width * supersampling / image_scale,
+                    None => selection_export_prefs.min_line_width,
+                };
+                strokes
+                    .into_par_iter()
+                    .enumerate()
+                    .map(|(i, stroke)| {
+                        let mut content = StrokeContent::default()
+                            .with_strokes(vec![Arc::clone(&stroke)])
+                            .with_on_stroke_error(on_stroke_error);
+                        if let Some(ink_color) = selection_export_prefs.ink_color {
+                            content = content.with_ink_color(ink_color);
+                        }
+                        content =
+                            content.with_stroke_smoothing(selection_export_prefs.smooth_strokes);
+                        content = content.with_min_line_width(min_line_width);
+                        let mut svg = content
+                            .gen_svg_w_image_scale(
+                                false,
+                                false,
+                                selection_export_prefs.optimize_printing,
+                                selection_export_prefs.margin,
+                                image_scale,
+                            )?
+                            .ok_or(anyhow::anyhow!(
+                                "Generating Svg for stroke {i} failed, returned None."
+                            ))?;
+                        if let Some(transform) = export_output_transform(
+                            selection_export_prefs.flip,
+                            selection_export_prefs.rotation,
+                            svg.bounds,
+                        ) {
+                            svg.transform(&transform)?;
+                        }
+                        let Some(image_format) = image_format else {
+                            let bytes = rnote_compose::utils::add_xml_header(
+                                with_export_comment(
+                                    wrap_export_svg_root(
+                                        &svg,
+                                        selection_export_prefs.svg_units,
+                                        document_dpi,
+                                    ),
+                                    selection_export_prefs.svg_comment
+                                        && !selection_export_prefs.strip_metadata,
+                                )
+                                .as_str(),
+                            )
+                            .into_bytes();
+                            return Ok((stroke, bytes));
+                        };
+                        let bytes = encode_export_image(
+                            svg.gen_image_w_backend(
+                                image_scale,
+                                selection_export_prefs.render_backend,
+                            )?
+                            .downsample(supersampling)?,
+                            image_format,
+                            selection_export_prefs.jpeg_quality,
+                            selection_export_prefs.jpeg_subsampling,
+                            selection_export_prefs.png_palette,
+                            selection_export_prefs.dither,
+                            selection_export_prefs.gamma,
+                            selection_export_prefs.strip_metadata,
+                            na::Vector2::repeat(image_scale / supersampling * document_dpi),
+                        )?;
+                        Ok((stroke, bytes))
+                    })
+                    .collect()
+            };
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver failed while exporting strokes as stickers. Receiver already dropped.");
+            }
+        });
+
+        oneshot_receiver
+    }
+}
+
+/// Generates the Svg of the content, with the strokes tagged with Css classes when `svg_classes` is set.
+fn gen_export_svg(
+    content: &StrokeContent,
+    svg_classes: bool,
+    draw_background: bool,
+    draw_pattern: bool,
+    optimize_printing: bool,
+    margin: f64,
+) -> anyhow::Result<Option<render::Svg>> {
+    if svg_classes {
+        content.gen_svg_w_stroke_classes(draw_background, draw_pattern, optimize_printing, margin)
+    } else {
+        content.gen_svg(draw_background, draw_pattern, optimize_printing, margin)
+    }
+}
+
+/// The bounds of the strokes of the content in the coordinates of the Svg generated from it, which has its bounds
+/// moved to mins: [0.0, 0.0]. Strokes are clipped to the content bounds.
+///
+/// Returns `None` when the content has no visible strokes.
+fn svg_content_bounds(content: &StrokeContent, margin: f64) -> Option<Aabb> {
+    let bounds = content.bounds()?;
+    let strokes_bounds = content
+        .strokes
+        .iter()
+        .map(|s| s.bounds())
+        .reduce(|acc, b| acc.merged(&b))?
+        .intersection(&bounds)?;
+    let offset = bounds.loosened(margin).mins.coords;
+    Some(Aabb::new(
+        strokes_bounds.mins - offset,
+        strokes_bounds.maxs - offset,
+    ))
+}
+
+/// Moves the Svg so that its coordinate system is anchored at the origin, the viewBox follows its bounds.
+///
+/// Without content bounds the Svg stays anchored at the page.
+fn apply_svg_origin(
+    svg: &mut render::Svg,
+    origin: SvgOrigin,
+    content_bounds: Option<Aabb>,
+) -> anyhow::Result<()> {
+    let (SvgOrigin::Content, Some(content_bounds)) = (origin, content_bounds) else {
+        return Ok(());
+    };
+    svg.transform(&Transform::new_w_isometry(na::Isometry2::new(
+        -content_bounds.mins.coords,
+        0.0,
+    )))
+}
 
-                        // Extract the strokes
-                        let xopp_strokes = xopp_strokestyles
-                            .iter()
-                            .filter_map(|stroke| {
-                                if let xoppformat::XoppStrokeType::XoppStroke(xoppstroke) = stroke {
-                                    Some(xoppstroke.clone())
-                                } else {
-                                    None
+/// Prepends an Xml comment that records the rnote version and the time of the export to the Svg when enabled,
+/// e.g. `<!-- exported by rnote 0.11.0 at 2024-09-01T12:00:00Z -->`.
+fn with_export_comment(svg: String, enabled: bool) -> String {
+    if !enabled {
+        return svg;
+    }
+    format!(
+        "<!-- exported by rnote {} at {} -->\n{svg}",
+        crate::utils::crate_version(),
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    )
+}
+
+/// Inlines the Css stylesheet as `<style>` element at the start of the Svg.
+fn inline_svg_style(svg: &mut render::Svg, stylesheet: &str) {
+    // The stylesheet is put into a CDATA section, so that it can contain characters like '<' and '&'.
+    // A CDATA section can't contain its end marker, so it is split up.
+    svg.svg_data = format!(
+        "<style><![CDATA[\n{}\n]]></style>\n{}",
+        stylesheet.replace("]]>", "]]]]><![CDATA[>"),
+        svg.svg_data
+    );
+}
+
+/// Removes the document information dictionary of the Pdf (title, producer, creation date, ..).
+fn strip_pdf_info_dict(pdf: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut pdf_file = PdfFile::load_from_bytes(pdf)
+        .context("Loading the exported Pdf for stripping the metadata failed.")?;
+    pdf_file.remove_info();
+    pdf_file.save_as_bytes("")
+}
+
+/// Wraps the Svg root around the exported Svg, with the width and height in the units when they are set.
+fn wrap_export_svg_root(svg: &render::Svg, svg_units: Option<SvgUnits>, dpi: f64) -> String {
+    match svg_units {
+        Some(svg_units) => rnote_compose::utils::wrap_svg_root_w_unit(
+            svg.svg_data.as_str(),
+            svg.bounds,
+            Some(svg.bounds),
+            false,
+            svg_units.factor_from_coords(dpi),
+            svg_units.suffix(),
+        ),
+        None => rnote_compose::utils::wrap_svg_root(
+            svg.svg_data.as_str(),
+            Some(svg.bounds),
+            Some(svg.bounds),
+            false,
+        ),
+    }
+}
+
+/// The resolution of the Pdf user space.
+const PDF_POINTS_PER_INCH: f64 = 72.0;
+/// The resolution of the Svg (and Css) px unit.
+const SVG_PX_PER_INCH: f64 = 96.0;
+
+/// The placement of page content on a physical paper.
+#[derive(Debug, Clone, Copy)]
+struct PaperFit {
+    /// The size of the paper in the chosen orientation.
+    paper_size: na::Vector2<f64>,
+    /// The bounds of the content that is placed on the paper.
+    bounds: Aabb,
+    /// The scale the content is drawn with.
+    scale: f64,
+    /// The offset of the scaled content from the paper origin.
+    offset: na::Vector2<f64>,
+}
+
+impl PaperFit {
+    /// Centers the bounds on the paper, scaling them to fit inside the margins when `fit` is set.
+    ///
+    /// `paper_size` is expected in portrait orientation. With the auto orientation, the paper is turned
+    /// to landscape for bounds that are wider than high.
+    fn new(
+        bounds: Aabb,
+        paper_size: na::Vector2<f64>,
+        margins: f64,
+        orientation: ExportOrientation,
+        fit: bool,
+    ) -> Self {
+        let extents = bounds.extents();
+        let landscape = match orientation {
+            ExportOrientation::Auto => extents[0] > extents[1],
+            ExportOrientation::Portrait => false,
+            ExportOrientation::Landscape => true,
+        };
+        let paper_size = if landscape {
+            na::vector![paper_size[1], paper_size[0]]
+        } else {
+            paper_size
+        };
+        let available = paper_size.add_scalar(-2.0 * margins).map(|v| v.max(1.0));
+        let scale = if fit && extents[0] > 0.0 && extents[1] > 0.0 {
+            (available[0] / extents[0]).min(available[1] / extents[1])
+        } else {
+            1.0
+        };
+        let offset = (paper_size - extents * scale) * 0.5;
+
+        Self {
+            paper_size,
+            bounds,
+            scale,
+            offset,
+        }
+    }
+}
+
+/// Draws crop marks at the corners of the trim bounds.
+///
+/// Each mark starts at the offset outside of the trim bounds and continues outwards for the given length.
+fn draw_crop_marks(
+    cairo_cx: &cairo::Context,
+    trim_bounds: Aabb,
+    offset: f64,
+    length: f64,
+    line_width: f64,
+) -> anyhow::Result<()> {
+    let mut piet_cx = piet_cairo::CairoRenderContext::new(cairo_cx);
+    for (corner, direction) in [
+        (trim_bounds.mins, na::vector![-1.0, -1.0]),
+        (
+            na::point![trim_bounds.maxs[0], trim_bounds.mins[1]],
+            na::vector![1.0, -1.0],
+        ),
+        (trim_bounds.maxs, na::vector![1.0, 1.0]),
+        (
+            na::point![trim_bounds.mins[0], trim_bounds.maxs[1]],
+            na::vector![-1.0, 1.0],
+        ),
+    ] {
+        // horizontal mark in line with the horizontal trim edge
+        piet_cx.stroke(
+            kurbo::Line::new(
+                (corner[0] + direction[0] * offset, corner[1]),
+                (corner[0] + direction[0] * (offset + length), corner[1]),
+            ),
+            &piet::Color::BLACK,
+            line_width,
+        );
+        // vertical mark in line with the vertical trim edge
+        piet_cx.stroke(
+            kurbo::Line::new(
+                (corner[0], corner[1] + direction[1] * offset),
+                (corner[0], corner[1] + direction[1] * (offset + length)),
+            ),
+            &piet::Color::BLACK,
+            line_width,
+        );
+    }
+    piet_cx.finish().map_err(|e| anyhow::anyhow!("{e:?}"))
+}
+
+/// Encodes a rendered export image.
+///
+/// The gamma correction is applied first.
+/// Jpeg images are encoded with the quality and chroma subsampling.
+/// Png images are quantized with the dithering and encoded as indexed-color Png when a palette size is given,
+/// and their metadata chunks are removed when `strip_metadata` is set. The resolution is embedded into Png images.
+#[allow(clippy::too_many_arguments)]
+fn encode_export_image(
+    mut image: render::Image,
+    image_format: image::ImageFormat,
+    jpeg_quality: u8,
+    jpeg_subsampling: render::JpegSubsampling,
+    png_palette: Option<u16>,
+    dither: render::Dither,
+    gamma: f64,
+    strip_metadata: bool,
+    dpi: na::Vector2<f64>,
+) -> anyhow::Result<Vec<u8>> {
+    let _encode_span = tracing::trace_span!("encode", format = ?image_format);
+    image.apply_gamma(gamma);
+    let mut bytes = match (image_format, png_palette) {
+        (image::ImageFormat::Jpeg, _) => image.into_jpeg_bytes(jpeg_quality, jpeg_subsampling)?,
+        (image::ImageFormat::Png, Some(colors)) => {
+            image.into_indexed_png_bytes(colors, dither, png_pixel_dims(dpi))?
+        }
+        (image::ImageFormat::Png, None) => image.into_png_bytes(png_pixel_dims(dpi))?,
+        _ => image.into_encoded_bytes(image_format, Some(jpeg_quality))?,
+    };
+    // The resolution is not identifying, so it is kept when stripping the metadata
+    if image_format == image::ImageFormat::Png && strip_metadata {
+        bytes = strip_png_metadata_chunks(&bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Splits Png encoded bytes into their chunks, as pairs of the chunk type and the entire chunk.
+fn split_png_chunks(png: &[u8]) -> anyhow::Result<Vec<(&[u8], &[u8])>> {
+    if !png.starts_with(render::PNG_SIGNATURE) {
+        return Err(anyhow::anyhow!("Bytes are not Png encoded."));
+    }
+    let mut chunks = vec![];
+    let mut pos = render::PNG_SIGNATURE.len();
+    while pos < png.len() {
+        // chunk layout: length (4 bytes), type (4 bytes), data (length bytes), crc (4 bytes)
+        let header = png
+            .get(pos..pos + 8)
+            .ok_or_else(|| anyhow::anyhow!("Png chunk header at offset {pos} is truncated."))?;
+        let data_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_end = pos + 8 + data_len + 4;
+        let chunk = png
+            .get(pos..chunk_end)
+            .ok_or_else(|| anyhow::anyhow!("Png chunk at offset {pos} is truncated."))?;
+        chunks.push((&header[4..8], chunk));
+        pos = chunk_end;
+    }
+    Ok(chunks)
+}
+
+/// Removes the textual and time metadata chunks from Png encoded bytes.
+fn strip_png_metadata_chunks(png: &[u8]) -> anyhow::Result<Vec<u8>> {
+    const METADATA_CHUNK_TYPES: [&[u8]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf"];
+
+    let mut stripped = Vec::with_capacity(png.len());
+    stripped.extend_from_slice(render::PNG_SIGNATURE);
+    for (chunk_type, chunk) in split_png_chunks(png)? {
+        if !METADATA_CHUNK_TYPES.contains(&chunk_type) {
+            stripped.extend_from_slice(chunk);
+        }
+    }
+    Ok(stripped)
+}
+
+/// The horizontal and vertical resolution in dots per inch as Png pixel dimensions, which are embedded as pHYs chunk.
+///
+/// Layout tools read it to place the image at its physical size, instead of assuming 72 dpi.
+fn png_pixel_dims(dpi: na::Vector2<f64>) -> Option<png::PixelDimensions> {
+    const INCHES_PER_METER: f64 = 1.0 / 0.0254;
+    if dpi.iter().any(|dpi| !dpi.is_finite() || *dpi <= 0.0) {
+        return None;
+    }
+    let pixels_per_meter = dpi.map(|dpi| (dpi * INCHES_PER_METER).round() as u32);
+    Some(png::PixelDimensions {
+        xppu: pixels_per_meter[0],
+        yppu: pixels_per_meter[1],
+        unit: png::Unit::Meter,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        strip_png_metadata_chunks, BackgroundImageFit, DocExportFormat, DocExportPrefs,
+        DocPagesExportFormat, DocPagesExportPrefs, ExportBackgroundImage, ExportCenterContent,
+        ExportFlip, ExportFormatDpis, ExportHeaderFooter, ExportLegendEntry, ExportLegendPen,
+        ExportNup, ExportOptions, ExportOrientation, ExportPageBadge, ExportPageBadgeCorner,
+        ExportRotation, OnStrokeError, PaperFit, SelectionExportFormat, SelectionExportPrefs,
+        StrokeSmoothing, SvgOrigin, SvgUnits,
+    };
+    use crate::document::background::PatternStyle;
+    use crate::document::format::{MeasureUnit, PredefinedFormat};
+    use crate::engine::StrokeContent;
+    use crate::render;
+    use crate::strokes::{BrushStroke, PdfLink, ShapeStroke, Stroke, VectorImage};
+    use crate::{Engine, StrokeStore};
+    use approx::assert_relative_eq;
+    use p2d::bounding_volume::{Aabb, BoundingVolume};
+    use rnote_compose::penpath::{Element, Segment};
+    use rnote_compose::shapes::{Rectangle, Shape, Shapeable};
+    use rnote_compose::style::smooth::SmoothOptions;
+    use rnote_compose::style::PressureCurve;
+    use rnote_compose::{Color, PenPath, SplitOrder, Style};
+
+    #[test]
+    fn pdf_background_color_composites_transparent_strokes() {
+        let mut engine = Engine::default();
+        let rect = Rectangle::from_corners(na::vector![100.0, 100.0], na::vector![300.0, 300.0]);
+        let style = Style::Smooth(SmoothOptions {
+            stroke_color: None,
+            fill_color: Some(Color::new(0.0, 0.0, 1.0, 0.5)),
+            ..Default::default()
+        });
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(Shape::Rectangle(rect), style)),
+            None,
+        );
+
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            with_background: false,
+            pdf_background_color: Some(Color::new(1.0, 0.0, 0.0, 1.0)),
+            ..Default::default()
+        };
+        let pdf_bytes = futures::executor::block_on(engine.export_doc(
+            String::from("test"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+
+        let doc = poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+        let page = doc.page(0).unwrap();
+        let (width, height) = page.size();
+        let mut surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            width.ceil() as i32,
+            height.ceil() as i32,
+        )
+        .unwrap();
+        {
+            let cx = cairo::Context::new(&surface).unwrap();
+            page.render_for_printing(&cx);
+        }
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+
+        // cairo ARgb32 pixels are premultiplied native endian u32's
+        let pixel = |x: usize, y: usize| {
+            let i = y * stride + x * 4;
+            let argb = u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+            let channel = |shift: u32| f64::from((argb >> shift) & 0xff) / 255.0;
+            (channel(16), channel(8), channel(0), channel(24))
+        };
+        // Outside of the stroke only the page background is visible
+        let (r, g, b, a) = pixel(20, 20);
+        assert_relative_eq!(r, 1.0, epsilon = 0.02);
+        assert_relative_eq!(g, 0.0, epsilon = 0.02);
+        assert_relative_eq!(b, 0.0, epsilon = 0.02);
+        assert_relative_eq!(a, 1.0, epsilon = 0.02);
+        // The semi-transparent blue stroke is blended with the red page background
+        let (r, g, b, a) = pixel(200, 200);
+        assert_relative_eq!(r, 0.5, epsilon = 0.02);
+        assert_relative_eq!(g, 0.0, epsilon = 0.02);
+        assert_relative_eq!(b, 0.5, epsilon = 0.02);
+        assert_relative_eq!(a, 1.0, epsilon = 0.02);
+    }
+
+    #[test]
+    fn ink_color_overrides_stroke_colors() {
+        let mut engine = Engine::default();
+        for (mins, maxs, stroke_color, fill_color) in [
+            (
+                na::vector![100.0, 100.0],
+                na::vector![300.0, 300.0],
+                Color::new(1.0, 0.0, 0.0, 1.0),
+                Color::new(0.0, 0.0, 1.0, 1.0),
+            ),
+            (
+                na::vector![400.0, 400.0],
+                na::vector![500.0, 600.0],
+                Color::new(0.2, 0.8, 0.1, 1.0),
+                Color::new(0.9, 0.9, 0.0, 1.0),
+            ),
+        ] {
+            let style = Style::Smooth(SmoothOptions {
+                stroke_color: Some(stroke_color),
+                fill_color: Some(fill_color),
+                stroke_width: 8.0,
+                ..Default::default()
+            });
+            engine.store.insert_stroke(
+                Stroke::ShapeStroke(ShapeStroke::new(
+                    Shape::Rectangle(Rectangle::from_corners(mins, maxs)),
+                    style,
+                )),
+                None,
+            );
+        }
+
+        let ink_color = Color::new(0.0, 0.5, 1.0, 1.0);
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            with_background: false,
+            ink_color: Some(ink_color),
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap().into_rgba8();
+
+        let mut opaque_pixels = 0;
+        for pixel in page.pixels().filter(|p| p[3] == 255) {
+            opaque_pixels += 1;
+            assert_relative_eq!(f64::from(pixel[0]) / 255.0, ink_color.r, epsilon = 0.01);
+            assert_relative_eq!(f64::from(pixel[1]) / 255.0, ink_color.g, epsilon = 0.01);
+            assert_relative_eq!(f64::from(pixel[2]) / 255.0, ink_color.b, epsilon = 0.01);
+        }
+        // Both strokes are rendered
+        assert!(opaque_pixels > 200 * 200 + 100 * 200);
+        // The document itself is not modified
+        let colors = engine
+            .store
+            .get_strokes_ref(&engine.store.stroke_keys_unordered())
+            .into_iter()
+            .filter_map(|s| match s {
+                Stroke::ShapeStroke(s) => s.style.stroke_color(),
+                _ => None,
+            })
+            .collect::<Vec<Color>>();
+        assert!(!colors.contains(&ink_color));
+    }
+
+    #[test]
+    fn stroke_smoothing_reduces_angular_error_of_zigzag() {
+        // Alternating between two lines, every vertex turns by 90 degrees
+        let elements = (0..12).map(|i| {
+            Element::new(
+                na::vector![100.0 + f64::from(i) * 10.0, 100.0 + f64::from(i % 2) * 10.0],
+                0.5,
+            )
+        });
+        let path = PenPath::try_from_elements(elements).unwrap();
+        let stroke = Stroke::BrushStroke(BrushStroke::from_penpath(
+            path,
+            Style::Smooth(SmoothOptions::default()),
+        ));
+        let content = StrokeContent::default().with_strokes(vec![std::sync::Arc::new(stroke)]);
+
+        // The largest change of direction between two consecutive segments of the rendered path
+        fn max_turning_angle(content: &StrokeContent) -> f64 {
+            let Stroke::BrushStroke(brush_stroke) = content.strokes[0].as_ref() else {
+                panic!("stroke is not a brush stroke");
+            };
+            let points = brush_stroke
+                .path
+                .to_kurbo_flattened(0.01)
+                .elements()
+                .iter()
+                .filter_map(|el| match el {
+                    kurbo::PathEl::MoveTo(p) | kurbo::PathEl::LineTo(p) => Some(p.to_vec2()),
+                    _ => None,
+                })
+                .collect::<Vec<kurbo::Vec2>>();
+            points
+                .windows(3)
+                .map(|w| {
+                    let (d0, d1) = (w[1] - w[0], w[2] - w[1]);
+                    d0.cross(d1).atan2(d0.dot(d1)).abs()
+                })
+                .fold(0.0, f64::max)
+        }
+
+        let jagged = max_turning_angle(&content);
+        let smoothed = max_turning_angle(
+            &content
+                .clone()
+                .with_stroke_smoothing(StrokeSmoothing::CatmullRom),
+        );
+        assert_relative_eq!(jagged, std::f64::consts::FRAC_PI_2, epsilon = 1e-6);
+        assert!(smoothed < jagged / 4.0, "{smoothed} >= {jagged} / 4");
+        // Disabled smoothing keeps the strokes as they are
+        assert_relative_eq!(
+            max_turning_angle(&content.with_stroke_smoothing(StrokeSmoothing::None)),
+            jagged
+        );
+    }
+
+    #[test]
+    fn svg_export_keeps_variable_stroke_width() {
+        let mut engine = Engine::default();
+        // A stroke with length 200 that tapers from width 10 to 0
+        let path = PenPath::new_w_segments(
+            Element::new(na::vector![100.0, 100.0], 1.0),
+            [Segment::LineTo {
+                end: Element::new(na::vector![300.0, 100.0], 0.0),
+            }],
+        );
+        let style = Style::Smooth(SmoothOptions {
+            stroke_width: 10.0,
+            stroke_color: Some(Color::BLACK),
+            pressure_curve: PressureCurve::Linear,
+            ..Default::default()
+        });
+        engine.store.insert_stroke(
+            Stroke::BrushStroke(BrushStroke::from_penpath(path, style)),
+            None,
+        );
+
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Svg,
+            with_background: false,
+            ..Default::default()
+        };
+        let svg_bytes = futures::executor::block_on(engine.export_doc(
+            String::from("test"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+        let tree = usvg::Tree::from_data(&svg_bytes, &usvg::Options::default()).unwrap();
+
+        fn filled_area(group: &usvg::Group) -> f64 {
+            use usvg::tiny_skia_path::{PathSegment, Point};
+            let pt = |p: Point| kurbo::Point::new(f64::from(p.x), f64::from(p.y));
+
+            group
+                .children()
+                .iter()
+                .map(|node| match node {
+                    usvg::Node::Group(group) => filled_area(group),
+                    usvg::Node::Path(path) if path.fill().is_some() => {
+                        let mut bez_path = kurbo::BezPath::new();
+                        for seg in path.data().segments() {
+                            match seg {
+                                PathSegment::MoveTo(p) => bez_path.move_to(pt(p)),
+                                PathSegment::LineTo(p) => bez_path.line_to(pt(p)),
+                                PathSegment::QuadTo(p1, p) => bez_path.quad_to(pt(p1), pt(p)),
+                                PathSegment::CubicTo(p1, p2, p) => {
+                                    bez_path.curve_to(pt(p1), pt(p2), pt(p))
                                 }
-                            })
-                            .collect::<Vec<xoppformat::XoppStroke>>();
+                                PathSegment::Close => bez_path.close_path(),
+                            }
+                        }
+                        let t = path.abs_transform();
+                        kurbo::Shape::area(&bez_path).abs()
+                            * f64::from((t.sx * t.sy - t.kx * t.ky).abs())
+                    }
+                    _ => 0.0,
+                })
+                .sum()
+        }
+
+        // The stroke must be exported as its filled outline, not as a constant width centerline.
+        // Expected is the tapering body (200 * 10 / 2)
+        // plus the rounded start cap, a cubic bezier approximation of a half circle with area 1.6 * r^2.
+        assert_relative_eq!(
+            filled_area(tree.root()),
+            1000.0 + 1.6 * 5.0_f64.powi(2),
+            epsilon = 1.0
+        );
+    }
+
+    #[test]
+    fn paper_size_pdf_has_physical_page_size() {
+        let engine = engine_w_rectangles(2);
+        let export_page_sizes = |orientation: ExportOrientation| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Pdf,
+                paper_size: Some(PredefinedFormat::A4),
+                fit_to_page: true,
+                orientation,
+                ..Default::default()
+            };
+            let pdf_bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                &ExportOptions::default(),
+            ))
+            .unwrap()
+            .unwrap();
+            let document =
+                poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+            (0..document.n_pages())
+                .map(|i| document.page(i).unwrap().size())
+                .collect::<Vec<(f64, f64)>>()
+        };
+
+        // A4 is 210 x 297 mm
+        for (width, height) in export_page_sizes(ExportOrientation::Portrait) {
+            assert_relative_eq!(width, 595.28, epsilon = 0.1);
+            assert_relative_eq!(height, 841.89, epsilon = 0.1);
+        }
+        for (width, height) in export_page_sizes(ExportOrientation::Landscape) {
+            assert_relative_eq!(width, 841.89, epsilon = 0.1);
+            assert_relative_eq!(height, 595.28, epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn paper_fit_scales_and_centers_content() {
+        let bounds = Aabb::new(na::point![100.0, 100.0], na::point![300.0, 200.0]);
+        let paper_size = na::vector![210.0, 297.0];
+
+        let fit = PaperFit::new(bounds, paper_size, 5.0, ExportOrientation::Auto, true);
+        // The wide content turns the paper to landscape
+        assert_eq!(fit.paper_size, na::vector![297.0, 210.0]);
+        assert_relative_eq!(fit.scale, (297.0 - 10.0) / 200.0);
+        assert_relative_eq!(fit.offset[0], 5.0);
+        assert_relative_eq!(fit.offset[1], (210.0 - 100.0 * fit.scale) * 0.5);
+
+        let fit = PaperFit::new(bounds, paper_size, 5.0, ExportOrientation::Portrait, false);
+        assert_eq!(fit.paper_size, paper_size);
+        assert_relative_eq!(fit.scale, 1.0);
+        assert_relative_eq!(fit.offset[0], 5.0);
+        assert_relative_eq!(fit.offset[1], 98.5);
+    }
+
+    #[test]
+    fn strip_metadata_pdf() {
+        let engine = Engine::default();
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            strip_metadata: true,
+            ..Default::default()
+        };
+        let pdf_bytes = futures::executor::block_on(engine.export_doc(
+            String::from("secret title"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+
+        let contains = |needle: &[u8]| pdf_bytes.windows(needle.len()).any(|w| w == needle);
+        for key in [
+            b"/Title".as_slice(),
+            b"/Producer",
+            b"/Creator",
+            b"/CreationDate",
+            b"/ModDate",
+        ] {
+            assert!(!contains(key), "{} found", String::from_utf8_lossy(key));
+        }
+        assert!(!contains(b"secret title"));
+        // The document still needs to be readable
+        assert!(poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).is_ok());
+    }
+
+    #[test]
+    fn strip_metadata_png() {
+        let png_chunk = |chunk_type: &[u8], data: &[u8]| {
+            let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+            chunk.extend_from_slice(chunk_type);
+            chunk.extend_from_slice(data);
+            // the crc is not validated while stripping
+            chunk.extend_from_slice(&[0; 4]);
+            chunk
+        };
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        png.extend(png_chunk(b"tEXt", b"Software\0rnote"));
+        png.extend(png_chunk(b"tIME", &[0; 7]));
+        png.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        png.extend(png_chunk(b"IEND", &[]));
+
+        let stripped = strip_png_metadata_chunks(&png).unwrap();
+
+        let contains = |needle: &[u8]| stripped.windows(needle.len()).any(|w| w == needle);
+        assert!(!contains(b"tEXt"));
+        assert!(!contains(b"tIME"));
+        assert!(contains(b"IHDR"));
+        assert!(contains(b"IDAT"));
+        assert!(contains(b"IEND"));
+        assert_eq!(stripped.len(), png.len() - (12 + 14) - (12 + 7));
+    }
+
+    #[test]
+    fn png_export_embeds_dpi() {
+        let engine = engine_w_rectangles(1);
+        let pixel_dims = |dpi: f64, strip_metadata: bool, png_palette: Option<u16>| {
+            let prefs = DocPagesExportPrefs {
+                export_format: DocPagesExportFormat::Png,
+                dpi: Some(dpi),
+                strip_metadata,
+                png_palette,
+                ..Default::default()
+            };
+            let pages = futures::executor::block_on(
+                engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+            )
+            .unwrap()
+            .unwrap();
+            let reader = png::Decoder::new(std::io::Cursor::new(&pages[0]))
+                .read_info()
+                .unwrap();
+            reader.info().pixel_dims.unwrap()
+        };
+
+        for (dpi, strip_metadata, png_palette) in [
+            (150.0, false, None),
+            (300.0, true, None),
+            (200.0, false, Some(16)),
+        ] {
+            let dims = pixel_dims(dpi, strip_metadata, png_palette);
+            assert_eq!(dims.unit, png::Unit::Meter);
+            assert_eq!(dims.xppu, dims.yppu);
+            assert_relative_eq!(f64::from(dims.xppu) * 0.0254, dpi, epsilon = 0.05);
+        }
+    }
+
+    #[test]
+    fn png_export_per_axis_dpi() {
+        let engine = engine_w_rectangles(1);
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            dpi: Some(192.0),
+            dpi_y: Some(96.0),
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let reader = png::Decoder::new(std::io::Cursor::new(&pages[0]))
+            .read_info()
+            .unwrap();
+        let info = reader.info();
+        let dims = info.pixel_dims.unwrap();
+        assert_relative_eq!(f64::from(dims.xppu) * 0.0254, 192.0, epsilon = 0.05);
+        assert_relative_eq!(f64::from(dims.yppu) * 0.0254, 96.0, epsilon = 0.05);
+
+        let format_size = engine.document.format.size();
+        assert_eq!(info.width, (format_size[0] * 2.0).round() as u32);
+        assert_eq!(info.height, format_size[1].round() as u32);
+    }
+
+    fn png_page_width(format_dpis: ExportFormatDpis, explicit_dpi: Option<f64>) -> (u32, f64) {
+        let mut engine = Engine::default();
+        engine.export_prefs.format_dpis = format_dpis;
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![100.0, 100.0],
+                    na::vector![200.0, 200.0],
+                )),
+                Style::default(),
+            )),
+            None,
+        );
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.5,
+            dpi: explicit_dpi,
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap();
+        (page.width(), engine.document.format.width())
+    }
+
+    #[test]
+    fn export_dpi_explicit_takes_precedence() {
+        let (width, format_width) = png_page_width(
+            ExportFormatDpis {
+                png: Some(300.0),
+                ..Default::default()
+            },
+            Some(192.0),
+        );
+        assert_eq!(width, (format_width * 2.0).round() as u32);
+    }
+
+    #[test]
+    fn export_dpi_from_format_dpis() {
+        let (width, format_width) = png_page_width(
+            ExportFormatDpis {
+                png: Some(48.0),
+                jpeg: Some(300.0),
+                ..Default::default()
+            },
+            None,
+        );
+        assert_eq!(width, (format_width * 0.5).round() as u32);
+    }
+
+    #[test]
+    fn export_dpi_falls_back_to_builtin_default() {
+        let (width, format_width) = png_page_width(ExportFormatDpis::default(), None);
+        assert_eq!(width, (format_width * 1.5).round() as u32);
+    }
+
+    #[test]
+    fn pdf_bleed_and_crop_marks() {
+        let mut engine = Engine::default();
+        // reaches over the top left page edges into the bleed
+        let rect = Rectangle::from_corners(na::vector![-20.0, -20.0], na::vector![100.0, 100.0]);
+        let style = Style::Smooth(SmoothOptions {
+            stroke_color: None,
+            fill_color: Some(Color::new(1.0, 0.0, 0.0, 1.0)),
+            ..Default::default()
+        });
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(Shape::Rectangle(rect), style)),
+            None,
+        );
+
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            with_background: false,
+            crop_marks: true,
+            bleed: 10.0,
+            ..Default::default()
+        };
+        let pdf_bytes = futures::executor::block_on(engine.export_doc(
+            String::from("test"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+
+        let mm = |mm: f64| mm / 25.4 * engine.document.format.dpi();
+        // bleed and the area for the crop marks (offset 1mm, length 5mm)
+        let trim_offset = mm(10.0) + mm(6.0);
+        let doc = poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+        let page = doc.page(0).unwrap();
+        let (width, height) = page.size();
+        assert_relative_eq!(
+            width,
+            engine.document.format.width() + 2.0 * trim_offset,
+            epsilon = 0.01
+        );
+        assert_relative_eq!(
+            height,
+            engine.document.format.height() + 2.0 * trim_offset,
+            epsilon = 0.01
+        );
+
+        let mut surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            width.ceil() as i32,
+            height.ceil() as i32,
+        )
+        .unwrap();
+        {
+            let cx = cairo::Context::new(&surface).unwrap();
+            page.render_for_printing(&cx);
+        }
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        // cairo ARgb32 pixels are premultiplied native endian u32's
+        let pixel = |x: f64, y: f64| {
+            let i = y.floor() as usize * stride + x.floor() as usize * 4;
+            let argb = u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+            (
+                (argb >> 16) & 0xff,
+                (argb >> 8) & 0xff,
+                argb & 0xff,
+                argb >> 24,
+            )
+        };
+
+        // the stroke extends into the bleed
+        assert_eq!(
+            pixel(trim_offset - 10.0, trim_offset - 10.0),
+            (255, 0, 0, 255)
+        );
+        // the horizontal crop mark of the top left corner
+        let (_, _, _, a) = pixel(trim_offset - mm(1.0) - mm(2.5), trim_offset);
+        assert!(a > 0);
+        // outside of the bleed and the marks nothing is drawn
+        assert_eq!(pixel(5.0, 5.0).3, 0);
+    }
+
+    /// Exports the first page with an asymmetric red mark in its top left corner.
+    fn png_page_w_marked_corner(
+        flip: ExportFlip,
+        rotation: ExportRotation,
+    ) -> (image::RgbaImage, na::Vector2<f64>) {
+        let mut engine = Engine::default();
+        let style = Style::Smooth(SmoothOptions {
+            stroke_color: None,
+            fill_color: Some(Color::new(1.0, 0.0, 0.0, 1.0)),
+            ..Default::default()
+        });
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![0.0, 0.0],
+                    na::vector![100.0, 50.0],
+                )),
+                style,
+            )),
+            None,
+        );
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            with_background: false,
+            bitmap_scalefactor: 1.0,
+            flip,
+            rotation,
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        (
+            image::load_from_memory(&pages[0]).unwrap().into_rgba8(),
+            engine.document.format.size(),
+        )
+    }
+
+    #[test]
+    fn export_flip_mirrors_output() {
+        let is_red = |p: &image::Rgba<u8>| p.0 == [255, 0, 0, 255];
+        let (page, size) = png_page_w_marked_corner(ExportFlip::Horizontal, ExportRotation::Deg0);
+        let width = size[0].round() as u32;
+        assert_eq!(page.dimensions(), (width, size[1].round() as u32));
+        assert!(is_red(page.get_pixel(width - 90, 40)));
+        assert!(!is_red(page.get_pixel(90, 40)));
+
+        let (page, size) = png_page_w_marked_corner(ExportFlip::Vertical, ExportRotation::Deg0);
+        let height = size[1].round() as u32;
+        assert!(is_red(page.get_pixel(90, height - 40)));
+        assert!(!is_red(page.get_pixel(90, 40)));
+    }
+
+    #[test]
+    fn export_rotation_rotates_output_and_swaps_extents() {
+        let is_red = |p: &image::Rgba<u8>| p.0 == [255, 0, 0, 255];
+        let (page, size) = png_page_w_marked_corner(ExportFlip::None, ExportRotation::Deg90);
+        let (width, height) = (size[1].round() as u32, size[0].round() as u32);
+        assert_eq!(page.dimensions(), (width, height));
+        // the top left corner ends up in the top right corner, the long edge of the mark vertical
+        assert!(is_red(page.get_pixel(width - 40, 90)));
+        assert!(!is_red(page.get_pixel(width - 90, 40)));
+        assert!(!is_red(page.get_pixel(40, 90)));
+
+        let (page, size) = png_page_w_marked_corner(ExportFlip::None, ExportRotation::Deg180);
+        let (width, height) = (size[0].round() as u32, size[1].round() as u32);
+        assert_eq!(page.dimensions(), (width, height));
+        assert!(is_red(page.get_pixel(width - 90, height - 40)));
+        assert!(!is_red(page.get_pixel(90, 40)));
+    }
+
+    fn engine_w_rectangles(amount: usize) -> Engine {
+        let mut engine = Engine::default();
+        for i in 0..amount {
+            let offset = 20.0 * i as f64;
+            let style = Style::Smooth(SmoothOptions {
+                stroke_color: Some(Color::new(0.0, 0.0, 1.0, 0.5)),
+                fill_color: Some(Color::new(1.0, 0.0, 0.0, 0.5)),
+                stroke_width: 4.0,
+                ..Default::default()
+            });
+            engine.store.insert_stroke(
+                Stroke::ShapeStroke(ShapeStroke::new(
+                    Shape::Rectangle(Rectangle::from_corners(
+                        na::vector![offset, offset],
+                        na::vector![offset + 100.0, offset + 60.0],
+                    )),
+                    style,
+                )),
+                None,
+            );
+        }
+        engine
+    }
+
+    /// An engine with a rectangle near the top of each of the given pages, resized to fit the content.
+    fn engine_w_content_on_pages(pages: &[usize]) -> Engine {
+        let mut engine = Engine::default();
+        let page_height = engine.document.format.height();
+        for &page in pages {
+            let offset = page_height * page as f64 + 10.0;
+            engine.store.insert_stroke(
+                Stroke::ShapeStroke(ShapeStroke::new(
+                    Shape::Rectangle(Rectangle::from_corners(
+                        na::vector![10.0, offset],
+                        na::vector![110.0, offset + 60.0],
+                    )),
+                    Style::default(),
+                )),
+                None,
+            );
+        }
+        let _ = engine.doc_resize_to_fit_content();
+        engine
+    }
+
+    #[test]
+    fn chunked_drawing_output_equals_unchunked() {
+        let engine = engine_w_rectangles(25);
+        let content = engine.extract_document_content(&ExportOptions::default());
+        let bounds = content.bounds().unwrap();
+        let render = |chunk_size: Option<usize>, chunks_drawn: &mut Vec<usize>| {
+            let mut surface = cairo::ImageSurface::create(
+                cairo::Format::ARgb32,
+                bounds.extents()[0].ceil() as i32,
+                bounds.extents()[1].ceil() as i32,
+            )
+            .unwrap();
+            {
+                let cx = cairo::Context::new(&surface).unwrap();
+                cx.translate(-bounds.mins[0], -bounds.mins[1]);
+                match chunk_size {
+                    Some(chunk_size) => content
+                        .draw_to_cairo_chunked(&cx, true, true, false, 0.0, 1.0, chunk_size, |n| {
+                            chunks_drawn.push(n)
+                        })
+                        .unwrap(),
+                    None => content
+                        .draw_to_cairo(&cx, true, true, false, 0.0, 1.0)
+                        .unwrap(),
+                }
+            }
+            surface.flush();
+            let data = surface.data().unwrap().to_vec();
+            data
+        };
+
+        let mut chunks_drawn = Vec::new();
+        let unchunked = render(None, &mut Vec::new());
+        let chunked = render(Some(4), &mut chunks_drawn);
+        assert!(unchunked == chunked);
+        assert_eq!(chunks_drawn, vec![4, 8, 12, 16, 20, 24, 25]);
+    }
+
+    #[test]
+    fn pdf_export_reports_progress() {
+        let engine = engine_w_rectangles(5);
+        let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reported_c = reported.clone();
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            ..Default::default()
+        };
+        futures::executor::block_on(engine.export_doc_as_pdf_w_progress(
+            String::from("test"),
+            Some(prefs),
+            &ExportOptions::default(),
+            Box::new(move |drawn, total| reported_c.lock().unwrap().push((drawn, total))),
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(reported.lock().unwrap().last(), Some(&(5, 5)));
+    }
+
+    fn background_image(width: u32, height: u32, fit: BackgroundImageFit) -> ExportBackgroundImage {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 255, 255]))
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        ExportBackgroundImage::from_image_bytes(bytes.get_ref(), fit).unwrap()
+    }
+
+    #[test]
+    fn background_image_placements() {
+        let bounds = Aabb::new(na::point![0.0, 0.0], na::point![100.0, 50.0]);
+
+        let contain = background_image(10, 10, BackgroundImageFit::Contain).placements(bounds);
+        assert_eq!(contain.len(), 1);
+        assert_relative_eq!(contain[0].mins, na::point![25.0, 0.0]);
+        assert_relative_eq!(contain[0].maxs, na::point![75.0, 50.0]);
+
+        let cover = background_image(10, 10, BackgroundImageFit::Cover).placements(bounds);
+        assert_eq!(cover.len(), 1);
+        assert_relative_eq!(cover[0].mins, na::point![0.0, -25.0]);
+        assert_relative_eq!(cover[0].maxs, na::point![100.0, 75.0]);
+
+        let tile = background_image(30, 30, BackgroundImageFit::Tile).placements(bounds);
+        assert_eq!(tile.len(), 4 * 2);
+        assert_relative_eq!(tile[7].mins, na::point![90.0, 30.0]);
+    }
+
+    #[test]
+    fn background_image_drawn_beneath_page_content() {
+        let mut engine = Engine::default();
+        let mut export_options = ExportOptions::default();
+        engine.document.background.pattern = PatternStyle::None;
+        export_options.background_image = Some(background_image(1, 1, BackgroundImageFit::Cover));
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let pages =
+            futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                .unwrap()
+                .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap().into_rgba8();
+        // The image is cut off horizontally, so the left border is covered
+        assert_eq!(page.get_pixel(0, page.height() / 2).0, [0, 0, 255, 255]);
+        assert_eq!(
+            page.get_pixel(page.width() / 2, page.height() / 2).0,
+            [0, 0, 255, 255]
+        );
+
+        export_options.background_image = None;
+        let pages =
+            futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                .unwrap()
+                .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap().into_rgba8();
+        assert_ne!(page.get_pixel(0, page.height() / 2).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn header_footer_placeholders() {
+        let header_footer = ExportHeaderFooter {
+            filename: String::from("notes"),
+            ..Default::default()
+        };
+        assert_eq!(
+            header_footer.expand_placeholders("{filename}: Page {page} of {pages}", 2, 5),
+            "notes: Page 2 of 5"
+        );
+    }
+
+    #[test]
+    fn footer_drawn_at_bottom_of_pages() {
+        fn dark_rows(page: &image::RgbaImage) -> Vec<u32> {
+            (0..page.height())
+                .filter(|&y| (0..page.width()).any(|x| page.get_pixel(x, y).0[0] < 128))
+                .collect()
+        }
+        let mut engine = Engine::default();
+        let mut export_options = ExportOptions::default();
+        engine.document.background.pattern = PatternStyle::None;
+        export_options.header_footer = Some(ExportHeaderFooter {
+            footer: Some(String::from("Page {page} of {pages}")),
+            font_size: 24.0,
+            ..Default::default()
+        });
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let pages =
+            futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                .unwrap()
+                .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap().into_rgba8();
+        let rows = dark_rows(&page);
+        assert!(!rows.is_empty());
+        assert!(rows
+            .iter()
+            .all(|&y| y > page.height() - ExportHeaderFooter::MARGIN_DEFAULT as u32 - 48));
+
+        export_options.header_footer = None;
+        let pages =
+            futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                .unwrap()
+                .unwrap();
+        let page = image::load_from_memory(&pages[0]).unwrap().into_rgba8();
+        assert!(dark_rows(&page).is_empty());
+    }
+
+    #[test]
+    fn page_badge_drawn_in_corner_scaled_with_page() {
+        /// The bounds of the pixels with the badge color as (min x, min y, max x, max y).
+        fn badge_bounds(page: &image::RgbaImage) -> (u32, u32, u32, u32) {
+            page.enumerate_pixels()
+                .filter(|(_, _, p)| p.0[0] > 200 && p.0[1] < 100 && p.0[2] < 100)
+                .fold((u32::MAX, u32::MAX, 0, 0), |b, (x, y, _)| {
+                    (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y))
+                })
+        }
+        let mut engine = Engine::default();
+        let mut export_options = ExportOptions::default();
+        engine.document.background.pattern = PatternStyle::None;
+        export_options.page_badge = Some(ExportPageBadge {
+            corner: ExportPageBadgeCorner::TopRight,
+            color: Color::RED,
+            ..Default::default()
+        });
+        let export_page = |bitmap_scalefactor: f64| {
+            let prefs = DocPagesExportPrefs {
+                export_format: DocPagesExportFormat::Png,
+                bitmap_scalefactor,
+                ..Default::default()
+            };
+            let pages =
+                futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                    .unwrap()
+                    .unwrap();
+            image::load_from_memory(&pages[0]).unwrap().into_rgba8()
+        };
+
+        for scale in [1.0, 2.0] {
+            let page = export_page(scale);
+            let (min_x, min_y, max_x, max_y) = badge_bounds(&page);
+            let size = ExportPageBadge::SIZE_DEFAULT * scale;
+            assert_relative_eq!(f64::from(max_x - min_x + 1), size, epsilon = 2.0);
+            assert_relative_eq!(f64::from(max_y - min_y + 1), size, epsilon = 2.0);
+            // Inset from the top right page corner by half its size
+            assert_relative_eq!(f64::from(min_y), size * 0.5, epsilon = 2.0);
+            assert_relative_eq!(
+                f64::from(page.width() - 1 - max_x),
+                size * 0.5,
+                epsilon = 2.0
+            );
+        }
+    }
+
+    #[test]
+    fn on_stroke_error_skips_or_replaces_failing_strokes() {
+        fn placeholder_pixels(page: &image::RgbaImage) -> usize {
+            page.pixels()
+                .filter(|p| p.0[0] > 200 && p.0[1] < 100 && p.0[2] < 100)
+                .count()
+        }
+        let mut engine = engine_w_rectangles(1);
+        let mut export_options = ExportOptions::default();
+        engine.document.background.pattern = PatternStyle::None;
+        // Malformed Svg data fails to render
+        engine.store.insert_stroke(
+            Stroke::VectorImage(VectorImage {
+                svg_data: String::from("<g"),
+                intrinsic_size: na::vector![40.0, 40.0],
+                rectangle: Rectangle::from_corners(
+                    na::vector![300.0, 300.0],
+                    na::vector![340.0, 340.0],
+                ),
+                ..Default::default()
+            }),
+            None,
+        );
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let mut export_page = |on_stroke_error: OnStrokeError| {
+            export_options.on_stroke_error = on_stroke_error;
+            futures::executor::block_on(engine.export_doc_pages(Some(prefs), &export_options))
+                .unwrap()
+                .map(|pages| image::load_from_memory(&pages[0]).unwrap().into_rgba8())
+        };
+
+        assert!(export_page(OnStrokeError::Fail).is_err());
+        let skipped = export_page(OnStrokeError::Skip).unwrap();
+        assert_eq!(placeholder_pixels(&skipped), 0);
+        // The other strokes are still drawn
+        assert!(skipped.pixels().any(|p| p.0[0] < 200));
+        let placeholder = export_page(OnStrokeError::Placeholder).unwrap();
+        assert!(placeholder_pixels(&placeholder) > 0);
+    }
+
+    #[test]
+    fn stickers_cropped_to_stroke_bounds() {
+        let engine = engine_w_rectangles(3);
+        let prefs = SelectionExportPrefs {
+            export_format: SelectionExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            margin: 8.0,
+            ..Default::default()
+        };
+        let stickers = futures::executor::block_on(
+            engine.export_strokes_as_stickers(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(stickers.len(), 3);
+        for (stroke, bytes) in stickers {
+            let sticker = image::load_from_memory(&bytes).unwrap().into_rgba8();
+            let extents = stroke.bounds().extents().add_scalar(2.0 * 8.0);
+            assert_relative_eq!(f64::from(sticker.width()), extents[0], epsilon = 2.0);
+            assert_relative_eq!(f64::from(sticker.height()), extents[1], epsilon = 2.0);
+            // The padding is transparent
+            assert_eq!(sticker.get_pixel(0, 0).0[3], 0);
+        }
+    }
+
+    #[test]
+    fn min_line_width_keeps_hairlines_visible() {
+        let mut engine = Engine::default();
+        engine.document.background.pattern = PatternStyle::None;
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![100.0, 100.0],
+                    na::vector![300.0, 200.0],
+                )),
+                Style::Smooth(SmoothOptions {
+                    stroke_color: Some(Color::BLACK),
+                    fill_color: None,
+                    stroke_width: 0.05,
+                    ..Default::default()
+                }),
+            )),
+            None,
+        );
+        let dark_pixels = |min_line_width: f64| {
+            let prefs = DocPagesExportPrefs {
+                export_format: DocPagesExportFormat::Png,
+                bitmap_scalefactor: 1.0,
+                min_line_width,
+                ..Default::default()
+            };
+            let pages = futures::executor::block_on(
+                engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+            )
+            .unwrap()
+            .unwrap();
+            image::load_from_memory(&pages[0])
+                .unwrap()
+                .into_rgba8()
+                .pixels()
+                .filter(|p| p.0[0] < 128)
+                .count()
+        };
+        assert_eq!(dark_pixels(0.0), 0);
+        assert!(dark_pixels(2.0) > 0);
+    }
+
+    #[test]
+    fn retina_export_is_twice_the_standard_resolution() {
+        let engine = engine_w_rectangles(2);
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let standard_pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let retina_pages = futures::executor::block_on(
+            engine.export_doc_pages_w_retina(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(standard_pages.len(), retina_pages.len());
+
+        let dimensions = |bytes: &[u8]| {
+            let image = image::load_from_memory(bytes).unwrap();
+            (image.width(), image.height())
+        };
+        // The pixel sizes are rounded, so they may differ by a pixel
+        let assert_size_eq = |a: (u32, u32), b: (u32, u32)| {
+            assert!(
+                a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1,
+                "{a:?} != {b:?}"
+            );
+        };
+        for (standard, retina) in standard_pages.iter().zip(retina_pages.iter()) {
+            let (width, height) = dimensions(&retina.standard);
+            assert_size_eq((width, height), dimensions(standard));
+            assert_size_eq((width * 2, height * 2), dimensions(&retina.retina));
+        }
+    }
+
+    #[test]
+    fn render_backends_produce_matching_images() {
+        let engine = engine_w_rectangles(2);
+        let export_pages = |render_backend: render::RenderBackend| {
+            let prefs = DocPagesExportPrefs {
+                export_format: DocPagesExportFormat::Png,
+                bitmap_scalefactor: 1.0,
+                render_backend,
+                ..Default::default()
+            };
+            futures::executor::block_on(
+                engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+            )
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|bytes| image::load_from_memory(bytes).unwrap().into_rgba8())
+            .collect::<Vec<image::RgbaImage>>()
+        };
+        let cairo_pages = export_pages(render::RenderBackend::Cairo);
+        let tiny_skia_pages = export_pages(render::RenderBackend::TinySkia);
+        assert_eq!(cairo_pages.len(), tiny_skia_pages.len());
+
+        for (cairo_page, tiny_skia_page) in cairo_pages.iter().zip(tiny_skia_pages.iter()) {
+            assert_eq!(cairo_page.dimensions(), tiny_skia_page.dimensions());
+            // The anti-aliasing of the renderers differs slightly
+            let diff_sum = cairo_page
+                .as_raw()
+                .iter()
+                .zip(tiny_skia_page.as_raw().iter())
+                .map(|(a, b)| u64::from(a.abs_diff(*b)))
+                .sum::<u64>();
+            let mean_diff = diff_sum as f64 / cairo_page.as_raw().len() as f64;
+            assert!(mean_diff < 4.0, "mean channel difference {mean_diff}");
+        }
+    }
+
+    #[test]
+    fn svg_classes_tag_strokes() {
+        let engine = engine_w_rectangles(2);
+        let export_svg = |export_options: &ExportOptions, svg_classes: bool| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Svg,
+                svg_classes,
+                ..Default::default()
+            };
+            let bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                export_options,
+            ))
+            .unwrap()
+            .unwrap();
+            String::from_utf8(bytes).unwrap()
+        };
+
+        assert!(!export_svg(&ExportOptions::default(), false).contains("rnote-stroke"));
+        let svg = export_svg(&ExportOptions::default(), true);
+        assert_eq!(
+            svg.matches(r#"class="rnote-stroke rnote-shapestroke rnote-color-0000ff""#)
+                .count(),
+            2
+        );
+        assert_eq!(svg.matches(r#"class="rnote-background""#).count(), 1);
+
+        let export_options = ExportOptions {
+            svg_style: Some(String::from(".rnote-shapestroke > * { opacity: 0.5; }")),
+            ..Default::default()
+        };
+        assert!(export_svg(&export_options, false)
+            .contains("<style><![CDATA[\n.rnote-shapestroke > * { opacity: 0.5; }\n]]></style>"));
+    }
+
+    #[test]
+    fn svg_origin_shifts_viewbox_to_content() {
+        let mut engine = Engine::default();
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![200.0, 300.0],
+                    na::vector![300.0, 360.0],
+                )),
+                Style::Smooth(SmoothOptions {
+                    stroke_width: 4.0,
+                    ..Default::default()
+                }),
+            )),
+            None,
+        );
+        let export_viewbox = |svg_origin: SvgOrigin| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Svg,
+                svg_origin,
+                ..Default::default()
+            };
+            let bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                &ExportOptions::default(),
+            ))
+            .unwrap()
+            .unwrap();
+            let svg = String::from_utf8(bytes).unwrap();
+            let viewbox = svg.split("viewBox=\"").nth(1).unwrap();
+            viewbox[..viewbox.find('"').unwrap()]
+                .split(' ')
+                .map(|v| v.parse::<f64>().unwrap())
+                .collect::<Vec<f64>>()
+        };
+
+        let page_viewbox = export_viewbox(SvgOrigin::Page);
+        let content_viewbox = export_viewbox(SvgOrigin::Content);
+        assert_eq!(&page_viewbox[..2], &[0.0, 0.0]);
+        // The stroke width extends the bounds of the rectangle
+        assert_relative_eq!(content_viewbox[0], -198.0);
+        assert_relative_eq!(content_viewbox[1], -298.0);
+        assert_eq!(&page_viewbox[2..], &content_viewbox[2..]);
+    }
+
+    #[test]
+    fn svg_units_convert_width_and_height() {
+        for dpi in [96.0, 300.0] {
+            svg_units_convert_width_and_height_at_dpi(dpi);
+        }
+    }
+
+    fn svg_units_convert_width_and_height_at_dpi(dpi: f64) {
+        let mut engine = engine_w_rectangles(1);
+        engine.document.format.set_dpi(dpi);
+        // Returns the width, height and viewBox attributes of the Svg root
+        let export_root_attrs = |svg_units: Option<SvgUnits>| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Svg,
+                svg_units,
+                ..Default::default()
+            };
+            let bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                &ExportOptions::default(),
+            ))
+            .unwrap()
+            .unwrap();
+            let svg = String::from_utf8(bytes).unwrap();
+            let root = &svg[svg.find("<svg").unwrap()..];
+            let root = &root[..root.find('>').unwrap()];
+            ["width", "height", "viewBox"].map(|attr| {
+                let value = root.split(&format!(" {attr}=\"")).nth(1).unwrap();
+                value[..value.find('"').unwrap()].to_string()
+            })
+        };
+
+        let [width, height, viewbox] = export_root_attrs(None);
+        let (width, height) = (
+            width.parse::<f64>().unwrap(),
+            height.parse::<f64>().unwrap(),
+        );
+        for (svg_units, suffix, factor) in [
+            (SvgUnits::Px, "px", 96.0 / dpi),
+            (SvgUnits::Pt, "pt", 72.0 / dpi),
+            (SvgUnits::Mm, "mm", 25.4 / dpi),
+            (SvgUnits::In, "in", 1.0 / dpi),
+        ] {
+            let [unit_width, unit_height, unit_viewbox] = export_root_attrs(Some(svg_units));
+            assert_relative_eq!(
+                unit_width
+                    .strip_suffix(suffix)
+                    .unwrap()
+                    .parse::<f64>()
+                    .unwrap(),
+                width * factor,
+                epsilon = 0.001
+            );
+            assert_relative_eq!(
+                unit_height
+                    .strip_suffix(suffix)
+                    .unwrap()
+                    .parse::<f64>()
+                    .unwrap(),
+                height * factor,
+                epsilon = 0.001
+            );
+            assert_eq!(unit_viewbox, viewbox);
+        }
+    }
+
+    #[test]
+    fn svg_comment_records_version_unless_metadata_is_stripped() {
+        let engine = engine_w_rectangles(1);
+        let export_svg = |svg_comment: bool, strip_metadata: bool| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Svg,
+                svg_comment,
+                strip_metadata,
+                ..Default::default()
+            };
+            let bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                &ExportOptions::default(),
+            ))
+            .unwrap()
+            .unwrap();
+            String::from_utf8(bytes).unwrap()
+        };
+        let comment_start = format!(
+            "<!-- exported by rnote {} at ",
+            crate::utils::crate_version()
+        );
+
+        let svg = export_svg(true, false);
+        // The Xml declaration must stay at the very start
+        assert!(svg.starts_with("<?xml"));
+        let comment_pos = svg.find(&comment_start).unwrap();
+        assert!(comment_pos < svg.find("<svg").unwrap());
+        assert!(!export_svg(false, false).contains("<!--"));
+        assert!(!export_svg(true, true).contains("<!--"));
+    }
+
+    #[test]
+    fn export_strokes_since_filters_by_creation_time() {
+        let engine = engine_w_rectangles(3);
+        let mut export_options = ExportOptions::default();
+        let created = engine
+            .store
+            .stroke_keys_unordered()
+            .into_iter()
+            .map(|key| engine.store.stroke_created(key).unwrap())
+            .collect::<Vec<i64>>();
+        let (first, last) = (
+            *created.iter().min().unwrap(),
+            *created.iter().max().unwrap(),
+        );
+
+        export_options.strokes_since = Some(first);
+        assert_eq!(
+            engine
+                .extract_document_content(&export_options)
+                .strokes
+                .len(),
+            3
+        );
+        assert_eq!(engine.export_strokes_count(&export_options), 3);
+        export_options.strokes_since = Some(last + 1);
+        assert!(engine
+            .extract_document_content(&export_options)
+            .strokes
+            .is_empty());
+        assert_eq!(engine.export_strokes_count(&export_options), 0);
+        assert!(engine
+            .extract_pages_content(SplitOrder::default(), &export_options)
+            .iter()
+            .all(|content| content.strokes.is_empty()));
+    }
+
+    #[test]
+    fn export_stroke_ids_filter_strokes() {
+        let mut engine = engine_w_rectangles(3);
+        let mut export_options = ExportOptions::default();
+        let keys = engine.store.stroke_keys_as_rendered();
+        let ids = keys
+            .iter()
+            .map(|key| StrokeStore::stroke_id(*key))
+            .collect::<Vec<u64>>();
+        assert_eq!(engine.store.missing_stroke_ids(&ids), Vec::<u64>::new());
+        let missing_id = ids.iter().max().unwrap() + 1;
+        assert_eq!(
+            engine.store.missing_stroke_ids(&[ids[0], missing_id]),
+            vec![missing_id]
+        );
+
+        export_options.stroke_ids = Some(vec![ids[0], ids[2]]);
+        assert_eq!(engine.export_strokes_count(&export_options), 2);
+        let _ = engine.select_all_strokes();
+        // The selection content is cropped to the bounds of the strokes with the ids
+        let content = engine.extract_selection_content(&export_options).unwrap();
+        assert_eq!(
+            content.bounds(),
+            engine.store.bounds_for_strokes(&[keys[0], keys[2]])
+        );
+        // The ids are carried along to refer to strokes that fail to draw
+        assert_eq!(content.stroke_ids, vec![ids[0], ids[2]]);
+        assert_eq!(
+            engine.extract_document_content(&export_options).stroke_ids,
+            vec![ids[0], ids[2]]
+        );
+        assert!(content.without_strokes().stroke_ids.is_empty());
+    }
+
+    #[test]
+    fn export_strokes_iter_generates_strokes_in_rendering_order() {
+        let engine = engine_w_rectangles(3);
+        let mut export_options = ExportOptions::default();
+        let rendered_keys = engine.store.stroke_keys_as_rendered();
+        assert_eq!(
+            engine
+                .export_strokes_iter(&export_options)
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>(),
+            rendered_keys
+        );
+        for (key, svg) in engine.export_strokes_svg_iter(&export_options) {
+            let svg = svg.unwrap();
+            assert!(!svg.svg_data.is_empty());
+            assert_eq!(
+                svg.bounds,
+                engine.store.get_stroke_ref(key).unwrap().bounds()
+            );
+        }
+        for (_, images) in engine.export_strokes_images_iter(2.0, &export_options) {
+            let images = images.unwrap();
+            assert_eq!(images.len(), 1);
+            images[0].assert_valid().unwrap();
+        }
 
-                        // Extract the texts
-                        let xopp_texts = xopp_strokestyles
-                            .iter()
-                            .filter_map(|stroke| {
-                                if let xoppformat::XoppStrokeType::XoppText(xopptext) = stroke {
-                                    Some(xopptext.clone())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<xoppformat::XoppText>>();
+        // Only the exported strokes are iterated
+        export_options.strokes_since = Some(i64::MAX);
+        assert_eq!(engine.export_strokes_iter(&export_options).count(), 0);
+    }
 
-                        // Extract the images
-                        let xopp_images = xopp_strokestyles
-                            .iter()
-                            .filter_map(|stroke| {
-                                if let xoppformat::XoppStrokeType::XoppImage(xoppstroke) = stroke {
-                                    Some(xoppstroke.clone())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<xoppformat::XoppImage>>();
+    #[test]
+    fn playback_frames_add_one_stroke_each() {
+        let engine = engine_w_rectangles(3);
+        let mut frames = Vec::new();
+        let amount = engine
+            .export_playback_frames(true, false, 1.0, &ExportOptions::default(), |frame| {
+                frames.push(frame);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(amount, 4);
+        assert_eq!(frames.len(), 4);
+        for pair in frames.windows(2) {
+            assert_eq!(
+                (pair[0].pixel_width, pair[0].pixel_height),
+                (pair[1].pixel_width, pair[1].pixel_height)
+            );
+            // Every frame draws the next stroke
+            assert_ne!(pair[0].data, pair[1].data);
+        }
+    }
 
-                        // In Rnote images are always rendered below strokes and text.
-                        // To match this behaviour accurately, images are separated into another layer.
-                        let image_layer = xoppformat::XoppLayer {
-                            name: None,
-                            strokes: vec![],
-                            texts: vec![],
-                            images: xopp_images,
-                        };
+    #[test]
+    fn export_page_range_selects_page_window() {
+        let engine = engine_w_content_on_pages(&[0, 1, 2]);
+        let mut export_options = ExportOptions::default();
+        let pages_bounds = engine.pages_bounds_w_content(SplitOrder::default());
+        assert_eq!(pages_bounds.len(), 3);
 
-                        let strokes_layer = xoppformat::XoppLayer {
-                            name: None,
-                            strokes: xopp_strokes,
-                            texts: xopp_texts,
-                            images: vec![],
-                        };
+        export_options.page_range = Some(1..3);
+        let window = engine.extract_pages_content(SplitOrder::default(), &export_options);
+        assert_eq!(window.len(), 2);
+        assert_eq!(window[0].bounds(), Some(pages_bounds[1]));
+        // Windows reaching over the last page are cut off
+        export_options.page_range = Some(2..5);
+        assert_eq!(
+            engine
+                .extract_pages_content(SplitOrder::default(), &export_options)
+                .len(),
+            1
+        );
+        export_options.page_range = Some(4..6);
+        assert!(engine
+            .extract_pages_content(SplitOrder::default(), &export_options)
+            .is_empty());
+    }
 
-                        let page_dimensions = crate::utils::convert_coord_dpi(
-                            page_bounds.extents(),
-                            document.format.dpi(),
-                            xoppformat::XoppFile::DPI,
-                        );
+    #[test]
+    fn page_labels_follow_document_page_index() {
+        // the second page stays empty and is skipped
+        let engine = engine_w_content_on_pages(&[0, 2]);
+        let mut export_options = ExportOptions::default();
+        let indices = engine
+            .export_pages_bounds_w_indices(SplitOrder::default(), &export_options)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+        assert_eq!(indices, vec![0, 2]);
 
-                        Some(xoppformat::XoppPage {
-                            width: page_dimensions[0],
-                            height: page_dimensions[1],
-                            background: xopp_background.clone(),
-                            layers: vec![image_layer, strokes_layer],
-                        })
-                    })
-                    .collect::<Vec<xoppformat::XoppPage>>();
+        let labels = ["i", "ii", "iii"].map(String::from).to_vec();
+        let pdf_bytes = futures::executor::block_on(engine.export_doc_as_pdf_w_page_labels(
+            String::from("labels"),
+            None,
+            &export_options,
+            labels.clone(),
+        ))
+        .unwrap()
+        .unwrap();
+        let doc = poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+        assert_eq!(doc.n_pages(), 2);
+        assert_eq!(doc.page(0).unwrap().label().unwrap().as_str(), "i");
+        assert_eq!(doc.page(1).unwrap().label().unwrap().as_str(), "iii");
 
-                let xopp_title = String::from(
-                    "Xournal++ document - see https://github.com/xournalpp/xournalpp (exported from Rnote - see https://github.com/flxzt/rnote)"
-                );
+        // The page range doesn't shift the labels either
+        export_options.page_range = Some(1..2);
+        let pdf_bytes = futures::executor::block_on(engine.export_doc_as_pdf_w_page_labels(
+            String::from("labels"),
+            None,
+            &export_options,
+            labels,
+        ))
+        .unwrap()
+        .unwrap();
+        let doc = poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+        assert_eq!(doc.n_pages(), 1);
+        assert_eq!(doc.page(0).unwrap().label().unwrap().as_str(), "iii");
+    }
 
-                let xopp_root = xoppformat::XoppRoot {
-                    title: xopp_title,
-                    fileversion: String::from("4"),
-                    preview: String::from(""),
-                    pages,
-                };
-                let xopp_file = xoppformat::XoppFile { xopp_root };
+    #[test]
+    fn blank_trailing_pages_are_trimmed() {
+        let mut engine = Engine::default();
+        let mut export_options = ExportOptions::default();
+        let page_height = engine.document.format.height();
+        // Overflows slightly onto the second page
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![10.0, page_height - 400.0],
+                    na::vector![610.0, page_height + 20.0],
+                )),
+                Style::default(),
+            )),
+            None,
+        );
+        let _ = engine.doc_resize_to_fit_content();
+        assert_eq!(
+            engine
+                .extract_pages_content(SplitOrder::default(), &export_options)
+                .len(),
+            2
+        );
 
-                xopp_file.save_as_bytes(&title)
-            };
+        export_options.trim_blank_pages = Some(0.01);
+        let pages_bounds = engine.export_pages_bounds(SplitOrder::default(), &export_options);
+        assert_eq!(pages_bounds.len(), 1);
+        assert!(engine.export_page_coverage(pages_bounds[0], &export_options) > 0.01);
+        // The only page is kept even when it is below the threshold
+        export_options.trim_blank_pages = Some(1.0);
+        assert_eq!(
+            engine
+                .extract_pages_content(SplitOrder::default(), &export_options)
+                .len(),
+            1
+        );
+    }
 
-            if oneshot_sender.send(result()).is_err() {
-                error!(
-                    "Sending result to receiver failed while exporting document as xopp bytes. Receiver already dropped."
+    #[test]
+    fn nup_arranges_pages_in_grid() {
+        let engine = engine_w_content_on_pages(&[0, 1, 2]);
+        let nup = ExportNup {
+            rows: 1,
+            columns: 2,
+            gutter: 10.0,
+            borders: true,
+        };
+        let gutter = MeasureUnit::convert_measurement(
+            nup.gutter,
+            MeasureUnit::Mm,
+            engine.document.format.dpi(),
+            MeasureUnit::Px,
+            engine.document.format.dpi(),
+        );
+
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let sheets = futures::executor::block_on(engine.export_doc_pages(
+            Some(DocPagesExportPrefs {
+                nup: Some(nup),
+                ..prefs
+            }),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(pages.len(), 3);
+        // The last sheet holds the remaining page in its first cell
+        assert_eq!(sheets.len(), 2);
+        let page = image::load_from_memory(&pages[0]).unwrap();
+        for sheet in sheets.iter() {
+            let sheet = image::load_from_memory(sheet).unwrap();
+            assert!(
+                sheet
+                    .width()
+                    .abs_diff(2 * page.width() + gutter.round() as u32)
+                    <= 1,
+                "{} != 2 * {} + {gutter}",
+                sheet.width(),
+                page.width()
+            );
+            assert_eq!(sheet.height(), page.height());
+        }
+
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            nup: Some(nup),
+            ..Default::default()
+        };
+        let pdf_bytes = futures::executor::block_on(engine.export_doc(
+            String::from("test"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+        let document = poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+        assert_eq!(document.n_pages(), 2);
+        let (width, height) = document.page(0).unwrap().size();
+        let format_size = engine.document.format.size();
+        assert_relative_eq!(width, 2.0 * format_size[0] + gutter, epsilon = 0.1);
+        assert_relative_eq!(height, format_size[1], epsilon = 0.1);
+    }
+
+    #[test]
+    fn nup_pdf_keeps_uri_links() {
+        // Three pages with a link each
+        let surface = cairo::PdfSurface::for_stream(200.0, 100.0, Vec::<u8>::new()).unwrap();
+        {
+            let cx = cairo::Context::new(&surface).unwrap();
+            for page in 0..3 {
+                cx.rectangle(20.0, 20.0, 60.0, 30.0);
+                cx.fill().unwrap();
+                cx.tag_begin(
+                    "Link",
+                    &format!("rect=[20 20 60 30] uri='https://example.org/{page}'"),
                 );
+                cx.tag_end("Link");
+                cx.show_page().unwrap();
             }
-        });
+        }
+        let pdf = *surface
+            .finish_output_stream()
+            .unwrap()
+            .downcast::<Vec<u8>>()
+            .unwrap();
 
-        oneshot_receiver
+        let nup = ExportNup {
+            rows: 1,
+            columns: 2,
+            gutter: 0.0,
+            borders: false,
+        };
+        let imposed = nup.impose_pdf(&pdf, 0.0, None).unwrap();
+        let document = poppler::Document::from_bytes(&glib::Bytes::from(&imposed), None).unwrap();
+        assert_eq!(document.n_pages(), 2);
+        let mut links = PdfLink::extract_from_pdf_page(&document.page(0).unwrap());
+        links.sort_by(|a, b| a.uri.cmp(&b.uri));
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].uri, "https://example.org/0");
+        assert_eq!(links[1].uri, "https://example.org/1");
+        // The areas are moved into the cells of their pages on the twice as wide sheet
+        assert_relative_eq!(links[0].area.mins, na::point![0.05, 0.2], epsilon = 1e-3);
+        assert_relative_eq!(links[0].area.maxs, na::point![0.2, 0.5], epsilon = 1e-3);
+        assert_relative_eq!(links[1].area.mins, na::point![0.55, 0.2], epsilon = 1e-3);
+        assert_relative_eq!(links[1].area.maxs, na::point![0.7, 0.5], epsilon = 1e-3);
+
+        let links = PdfLink::extract_from_pdf_page(&document.page(1).unwrap());
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].uri, "https://example.org/2");
     }
 
-    /// Export the document pages.
-    pub fn export_doc_pages(
-        &self,
-        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<Vec<u8>>, anyhow::Error>> {
-        let doc_pages_export_prefs =
-            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
+    #[test]
+    fn page_size_from_content() {
+        let pdf_pages_sizes = |engine: &Engine| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Pdf,
+                page_size_from_content: Some(10.0),
+                ..Default::default()
+            };
+            let pdf_bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                &ExportOptions::default(),
+            ))
+            .unwrap()
+            .unwrap();
+            let document =
+                poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None).unwrap();
+            (0..document.n_pages())
+                .map(|i| document.page(i).unwrap().size())
+                .collect::<Vec<(f64, f64)>>()
+        };
 
-        match doc_pages_export_prefs.export_format {
-            DocPagesExportFormat::Svg => {
-                self.export_doc_pages_as_svgs_bytes(doc_pages_export_prefs_override)
-            }
-            DocPagesExportFormat::Png | DocPagesExportFormat::Jpeg => {
-                self.export_doc_pages_as_bitmap_bytes(doc_pages_export_prefs_override)
-            }
-        }
+        let engine = engine_w_rectangles(2);
+        let content_bounds = engine
+            .export_content_bounds(10.0, &ExportOptions::default())
+            .unwrap();
+        let sizes = pdf_pages_sizes(&engine);
+        assert_eq!(sizes.len(), 1);
+        assert_relative_eq!(sizes[0].0, content_bounds.extents()[0], epsilon = 0.1);
+        assert_relative_eq!(sizes[0].1, content_bounds.extents()[1], epsilon = 0.1);
+        assert!(content_bounds.extents()[0] < engine.document.format.width());
+
+        // Without content the pages of the document format are exported
+        let engine = Engine::default();
+        let sizes = pdf_pages_sizes(&engine);
+        let format_size = engine.document.format.size();
+        assert_relative_eq!(sizes[0].0, format_size[0], epsilon = 0.1);
+        assert_relative_eq!(sizes[0].1, format_size[1], epsilon = 0.1);
     }
 
-    /// Export the document as Svg.
-    fn export_doc_pages_as_svgs_bytes(
-        &self,
-        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<Vec<u8>>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<Vec<u8>>>>();
-        let doc_pages_export_prefs =
-            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
-        let pages_content = self.extract_pages_content(doc_pages_export_prefs.page_order);
+    #[test]
+    fn content_centered_in_page_box() {
+        let engine = engine_w_rectangles(2);
+        let page_content = engine
+            .extract_pages_content(SplitOrder::default(), &ExportOptions::default())
+            .into_iter()
+            .next()
+            .unwrap();
+        let page_bounds = page_content.bounds().unwrap();
+        let strokes_bounds = |content: &StrokeContent| {
+            content
+                .strokes
+                .iter()
+                .map(|stroke| stroke.bounds())
+                .reduce(|acc, bounds| acc.merged(&bounds))
+                .unwrap()
+        };
+        let original = strokes_bounds(&page_content);
 
-        rayon::spawn(move || {
-            let result = || -> anyhow::Result<Vec<Vec<u8>>> {
-                pages_content
-                    .into_par_iter()
-                    .enumerate()
-                    .map(|(i, page_content)| {
-                        let page_svg = page_content
-                            .gen_svg(
-                                doc_pages_export_prefs.with_background,
-                                doc_pages_export_prefs.with_pattern,
-                                doc_pages_export_prefs.optimize_printing,
-                                DocPagesExportPrefs::MARGIN,
-                            )?
-                            .ok_or(anyhow::anyhow!(
-                                "Generating Svg for page {i} failed, returned None."
-                            ))?;
-                        Ok(rnote_compose::utils::add_xml_header(
-                            rnote_compose::utils::wrap_svg_root(
-                                page_svg.svg_data.as_str(),
-                                Some(page_svg.bounds),
-                                Some(page_svg.bounds),
-                                false,
-                            )
-                            .as_str(),
-                        )
-                        .into_bytes())
-                    })
-                    .collect()
-            };
+        let centered = strokes_bounds(
+            &page_content
+                .clone()
+                .with_centered_content(ExportCenterContent::Both),
+        );
+        assert_relative_eq!(centered.center(), page_bounds.center(), epsilon = 1e-9);
+        assert_relative_eq!(centered.extents(), original.extents(), epsilon = 1e-9);
 
-            if oneshot_sender.send(result()).is_err() {
-                error!(
-                    "Sending result to receiver failed while exporting document pages as Svg bytes. Receiver already dropped."
-                );
-            }
-        });
+        let horizontal = strokes_bounds(
+            &page_content
+                .clone()
+                .with_centered_content(ExportCenterContent::Horizontal),
+        );
+        assert_relative_eq!(
+            horizontal.center()[0],
+            page_bounds.center()[0],
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(horizontal.mins[1], original.mins[1], epsilon = 1e-9);
 
-        oneshot_receiver
+        // The document is not modified
+        assert_eq!(strokes_bounds(&page_content), original);
     }
 
-    /// Export the document pages as bitmap.
-    ///
-    /// Returns an error if the format pref is not set to a bitmap variant.
-    fn export_doc_pages_as_bitmap_bytes(
-        &self,
-        doc_pages_export_prefs_override: Option<DocPagesExportPrefs>,
-    ) -> oneshot::Receiver<Result<Vec<Vec<u8>>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel::<anyhow::Result<Vec<Vec<u8>>>>();
-        let doc_pages_export_prefs =
-            doc_pages_export_prefs_override.unwrap_or(self.export_prefs.doc_pages_export_prefs);
-        let pages_contents = self.extract_pages_content(doc_pages_export_prefs.page_order);
+    #[test]
+    fn pages_render_sequentially_up_to_parallel_threshold() {
+        let prefs = DocPagesExportPrefs {
+            parallel_pages_threshold: 4,
+            ..Default::default()
+        };
+        assert_eq!(prefs.pages_per_thread_min(0), 1);
+        assert_eq!(prefs.pages_per_thread_min(3), 3);
+        assert_eq!(prefs.pages_per_thread_min(4), 4);
+        assert_eq!(prefs.pages_per_thread_min(5), 1);
 
-        rayon::spawn(move || {
-            let result = || -> Result<Vec<Vec<u8>>, anyhow::Error> {
-                let image_format = match doc_pages_export_prefs.export_format {
-                    DocPagesExportFormat::Svg => return Err(anyhow::anyhow!("Extracting bitmap image format from doc pages export prefs failed, not set to a bitmap format.")),
-                    DocPagesExportFormat::Png => image::ImageFormat::Png,
-                    DocPagesExportFormat::Jpeg => image::ImageFormat::Jpeg,
-                };
-                pages_contents
-                    .into_par_iter()
-                    .enumerate()
-                    .map(|(i, page_content)| {
-                        page_content
-                            .gen_svg(
-                                doc_pages_export_prefs.with_background,
-                                doc_pages_export_prefs.with_pattern,
-                                doc_pages_export_prefs.optimize_printing,
-                                DocPagesExportPrefs::MARGIN,
-                            )?
-                            .ok_or(anyhow::anyhow!(
-                                "Generating Svg for page {i} failed, returned None."
-                            ))?
-                            .gen_image(doc_pages_export_prefs.bitmap_scalefactor)?
-                            .into_encoded_bytes(
-                                image_format,
-                                Some(doc_pages_export_prefs.jpeg_quality),
-                            )
-                    })
-                    .collect()
+        let engine = engine_w_content_on_pages(&[0, 1, 2]);
+        let export_pages = |parallel_pages_threshold: usize| {
+            let prefs = DocPagesExportPrefs {
+                export_format: DocPagesExportFormat::Png,
+                bitmap_scalefactor: 0.5,
+                parallel_pages_threshold,
+                ..Default::default()
             };
-            if oneshot_sender.send(result()).is_err() {
-                error!("Sending result to receiver failed while exporting document pages as bitmap bytes. Receiver already dropped.");
-            }
-        });
-
-        oneshot_receiver
+            futures::executor::block_on(
+                engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+            )
+            .unwrap()
+            .unwrap()
+        };
+        let parallel = export_pages(0);
+        assert_eq!(parallel.len(), 3);
+        assert_eq!(export_pages(usize::MAX), parallel);
     }
 
-    /// Exports the current selection.
-    pub fn export_selection(
-        &self,
-        selection_export_prefs_override: Option<SelectionExportPrefs>,
-    ) -> oneshot::Receiver<Result<Option<Vec<u8>>, anyhow::Error>> {
-        let selection_export_prefs =
-            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
+    #[test]
+    fn png_palette_exports_indexed_png() {
+        let engine = engine_w_rectangles(3);
+        let prefs = DocPagesExportPrefs {
+            export_format: DocPagesExportFormat::Png,
+            bitmap_scalefactor: 1.0,
+            png_palette: Some(16),
+            ..Default::default()
+        };
+        let pages = futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(&pages[0]));
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        assert_eq!(info.bit_depth, png::BitDepth::Four);
+        assert!(info.palette.as_ref().unwrap().len() <= 16 * 3);
 
-        match selection_export_prefs.export_format {
-            SelectionExportFormat::Svg => {
-                self.export_selection_as_svg_bytes(selection_export_prefs_override)
-            }
-            SelectionExportFormat::Png | SelectionExportFormat::Jpeg => {
-                self.export_selection_as_bitmap_bytes(selection_export_prefs_override)
-            }
-        }
+        let prefs = DocPagesExportPrefs {
+            png_palette: Some(1),
+            ..prefs
+        };
+        assert!(futures::executor::block_on(
+            engine.export_doc_pages(Some(prefs), &ExportOptions::default())
+        )
+        .unwrap()
+        .is_err());
     }
 
-    /// Exports the selection as Svg.
-    fn export_selection_as_svg_bytes(
-        &self,
-        selection_export_prefs_override: Option<SelectionExportPrefs>,
-    ) -> oneshot::Receiver<Result<Option<Vec<u8>>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) =
-            oneshot::channel::<anyhow::Result<Option<Vec<u8>>>>();
-        let selection_export_prefs =
-            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
-        let content = self.extract_selection_content();
+    #[test]
+    fn selection_strokes_metadata_in_exported_image_coordinates() {
+        let mut engine = engine_w_rectangles(2);
+        let keys = engine.store.stroke_keys_as_rendered();
+        engine.store.set_selected_keys(&keys, true);
+        let strokes_bounds = keys
+            .iter()
+            .map(|key| engine.store.get_stroke_ref(*key).unwrap().bounds())
+            .collect::<Vec<Aabb>>();
+        let selection_bounds = strokes_bounds[0].merged(&strokes_bounds[1]).loosened(10.0);
+        let prefs = SelectionExportPrefs {
+            export_format: SelectionExportFormat::Png,
+            bitmap_scalefactor: 2.0,
+            margin: 10.0,
+            ..Default::default()
+        };
 
-        rayon::spawn(move || {
-            let result = || -> Result<Option<Vec<u8>>, anyhow::Error> {
-                let Some(content) = content else {
-                    return Ok(None);
-                };
-                let Some(svg) = content.gen_svg(
-                    selection_export_prefs.with_background,
-                    selection_export_prefs.with_pattern,
-                    selection_export_prefs.optimize_printing,
-                    selection_export_prefs.margin,
-                )?
-                else {
-                    return Ok(None);
-                };
+        let metadata = engine
+            .export_selection_strokes_metadata(Some(prefs), &ExportOptions::default())
+            .unwrap();
+        assert_relative_eq!(metadata.width, selection_bounds.extents()[0] * 2.0);
+        assert_relative_eq!(metadata.height, selection_bounds.extents()[1] * 2.0);
+        assert_eq!(metadata.strokes.len(), 2);
+        for ((stroke, bounds), key) in metadata.strokes.iter().zip(&strokes_bounds).zip(&keys) {
+            assert_eq!(stroke.id, StrokeStore::stroke_id(*key));
+            assert_eq!(stroke.stroke_type, "shapestroke");
+            assert_relative_eq!(
+                stroke.bounds[0],
+                (bounds.mins[0] - selection_bounds.mins[0]) * 2.0
+            );
+            assert_relative_eq!(
+                stroke.bounds[1],
+                (bounds.mins[1] - selection_bounds.mins[1]) * 2.0
+            );
+            assert_relative_eq!(stroke.bounds[2], bounds.extents()[0] * 2.0);
+            assert_relative_eq!(stroke.bounds[3], bounds.extents()[1] * 2.0);
+            assert_eq!(stroke.style.stroke_color.as_deref(), Some("#0000ff80"));
+            assert_eq!(stroke.style.stroke_width, Some(8.0));
+        }
 
-                Ok(Some(
-                    rnote_compose::utils::add_xml_header(
-                        rnote_compose::utils::wrap_svg_root(
-                            svg.svg_data.as_str(),
-                            Some(svg.bounds),
-                            Some(svg.bounds),
-                            false,
-                        )
-                        .as_str(),
-                    )
-                    .into_bytes(),
-                ))
-            };
-            if oneshot_sender.send(result()).is_err() {
-                error!("Sending result to receiver failed while exporting selection as Svg bytes. Receiver already dropped.");
-            }
-        });
+        // Mirrored horizontally, the right edge of the strokes maps to the left edge
+        let metadata = engine
+            .export_selection_strokes_metadata(
+                Some(SelectionExportPrefs {
+                    flip: ExportFlip::Horizontal,
+                    ..prefs
+                }),
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        for (stroke, bounds) in metadata.strokes.iter().zip(&strokes_bounds) {
+            assert_relative_eq!(
+                stroke.bounds[0],
+                (selection_bounds.maxs[0] - bounds.maxs[0]) * 2.0,
+                epsilon = 1e-9
+            );
+        }
 
-        oneshot_receiver
+        let metadata = engine
+            .export_selection_strokes_metadata(
+                Some(SelectionExportPrefs {
+                    rotation: ExportRotation::Deg90,
+                    ..prefs
+                }),
+                &ExportOptions::default(),
+            )
+            .unwrap();
+        assert_relative_eq!(metadata.width, selection_bounds.extents()[1] * 2.0);
+        assert_relative_eq!(metadata.height, selection_bounds.extents()[0] * 2.0);
+        assert_relative_eq!(
+            metadata.strokes[0].bounds[2],
+            strokes_bounds[0].extents()[1] * 2.0,
+            epsilon = 1e-9
+        );
     }
 
-    /// Export the selection a bitmap bytes.
-    ///
-    /// Returns an error if the format pref is not set to a bitmap format
-    fn export_selection_as_bitmap_bytes(
-        &self,
-        selection_export_prefs_override: Option<SelectionExportPrefs>,
-    ) -> oneshot::Receiver<Result<Option<Vec<u8>>, anyhow::Error>> {
-        let (oneshot_sender, oneshot_receiver) =
-            oneshot::channel::<anyhow::Result<Option<Vec<u8>>>>();
-        let selection_export_prefs =
-            selection_export_prefs_override.unwrap_or(self.export_prefs.selection_export_prefs);
-        let content = self.extract_selection_content();
+    #[test]
+    fn legend_lists_distinct_colors_and_is_appended_to_pdf() {
+        let mut engine = Engine::default();
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color::new(0.0, 0.0, 1.0, 1.0);
+        let translucent_red = Color::new(1.0, 0.0, 0.0, 0.5);
+        for (i, color) in [red, blue, red, translucent_red].into_iter().enumerate() {
+            let offset = 10.0 + 100.0 * i as f64;
+            engine.store.insert_stroke(
+                Stroke::ShapeStroke(ShapeStroke::new(
+                    Shape::Rectangle(Rectangle::from_corners(
+                        na::vector![offset, 10.0],
+                        na::vector![offset + 60.0, 60.0],
+                    )),
+                    Style::Smooth(SmoothOptions {
+                        stroke_color: Some(color),
+                        ..Default::default()
+                    }),
+                )),
+                None,
+            );
+        }
 
-        rayon::spawn(move || {
-            let result = || -> Result<Option<Vec<u8>>, anyhow::Error> {
-                let Some(content) = content else {
-                    return Ok(None);
-                };
-                let Some(svg) = content.gen_svg(
-                    selection_export_prefs.with_background,
-                    selection_export_prefs.with_pattern,
-                    selection_export_prefs.optimize_printing,
-                    selection_export_prefs.margin,
-                )?
-                else {
-                    return Ok(None);
-                };
-                let image_format = match selection_export_prefs.export_format {
-                    SelectionExportFormat::Svg => return Err(anyhow::anyhow!("Extracting bitmap image format from doc pages export prefs failed, not set to a bitmap format.")),
-                    SelectionExportFormat::Png => image::ImageFormat::Png,
-                    SelectionExportFormat::Jpeg => image::ImageFormat::Jpeg
-                };
+        let legend = engine.export_legend(&ExportOptions::default());
+        assert_eq!(
+            legend.entries,
+            vec![
+                ExportLegendEntry {
+                    color: red,
+                    pen: ExportLegendPen::Shape
+                },
+                ExportLegendEntry {
+                    color: blue,
+                    pen: ExportLegendPen::Shape
+                },
+                ExportLegendEntry {
+                    color: translucent_red,
+                    pen: ExportLegendPen::Shape
+                },
+            ]
+        );
+        assert_eq!(legend.entries[0].label(), "Shape #ff0000");
+        // Colors that only differ in alpha get distinct labels
+        assert_eq!(legend.entries[2].label(), "Shape #ff000080");
 
-                Ok(Some(
-                    svg.gen_image(selection_export_prefs.bitmap_scalefactor)?
-                        .into_encoded_bytes(
-                            image_format,
-                            Some(selection_export_prefs.jpeg_quality),
-                        )?,
-                ))
+        let export_pdf_pages = |export_options: &ExportOptions| {
+            let prefs = DocExportPrefs {
+                export_format: DocExportFormat::Pdf,
+                ..Default::default()
             };
-            if oneshot_sender.send(result()).is_err() {
-                error!("Sending result to receiver failed while exporting selection as bitmap image bytes. Receiver already dropped");
-            }
-        });
-
-        oneshot_receiver
+            let pdf_bytes = futures::executor::block_on(engine.export_doc(
+                String::from("test"),
+                Some(prefs),
+                export_options,
+            ))
+            .unwrap()
+            .unwrap();
+            poppler::Document::from_bytes(&glib::Bytes::from(&pdf_bytes), None)
+                .unwrap()
+                .n_pages()
+        };
+        let pages = export_pdf_pages(&ExportOptions::default());
+        let export_options = ExportOptions {
+            legend_page: true,
+            ..Default::default()
+        };
+        assert_eq!(export_pdf_pages(&export_options), pages + 1);
     }
 }
@@ -1,22 +1,41 @@
 // Imports
 use super::{EngineConfig, StrokeContent};
-use crate::document::Layout;
+use crate::document::{Format, Layout};
 use crate::engine_view_mut;
+use crate::fileformats::csvformat::CsvFile;
+#[cfg(feature = "goodnotes")]
+use crate::fileformats::goodnotesformat::{
+    GoodnotesFile, GoodnotesPageContent, GoodnotesSkippedEntry,
+};
+#[cfg(feature = "markdown")]
+use crate::fileformats::markdownformat::{
+    MarkdownBlock, MarkdownBlockKind, MarkdownFile, MarkdownStyle,
+};
+use crate::fileformats::FileFormatLoader;
 use crate::pens::Pen;
 use crate::pens::PenStyle;
 use crate::store::chrono_comp::StrokeLayer;
 use crate::store::StrokeKey;
+#[cfg(feature = "markdown")]
+use crate::strokes::textstroke::{FontStyle, RangedTextAttribute, TextAttribute};
+use crate::strokes::textstroke::{TextAlignment, TextStyle};
 use crate::strokes::{resize::calculate_resize_ratio, resize::ImageSizeOption, Resize};
-use crate::strokes::{BitmapImage, Stroke, VectorImage};
-use crate::{CloneConfig, Engine, WidgetFlags};
-use futures::channel::oneshot;
+use crate::strokes::{BitmapImage, PdfBitmapPages, ShapeStroke, Stroke, TextStroke, VectorImage};
+use crate::{render, CloneConfig, Engine, WidgetFlags};
+use futures::channel::{mpsc, oneshot};
+use futures::SinkExt;
+use glib::translate::{from_glib_none, ToGlibPtr};
+use p2d::bounding_volume::{Aabb, BoundingVolume};
 use rnote_compose::ext::Vector2Ext;
-use rnote_compose::shapes::Shapeable;
+use rnote_compose::shapes::{Line, Polyline, Shapeable};
+use rnote_compose::style::smooth::SmoothOptions;
+use rnote_compose::transform::Transformable;
+use rnote_compose::{Shape, Style};
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use std::path::PathBuf;
 use std::time::Instant;
-use tracing::error;
+use tracing::{error, warn};
 
 #[derive(
     Debug, Clone, Copy, Serialize, Deserialize, num_derive::FromPrimitive, num_derive::ToPrimitive,
@@ -78,6 +97,316 @@ impl TryFrom<u32> for PdfImportPageSpacing {
     }
 }
 
+/// To which size imported Pdf pages are padded.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "pdf_import_pad_to")]
+pub enum PdfImportPadTo {
+    /// Keep the exact page sizes.
+    #[serde(rename = "none")]
+    None = 0,
+    /// Pad all pages to the width of the widest and the height of the tallest page.
+    #[serde(rename = "widest")]
+    Widest,
+    /// Pad all pages to the aspect ratio of the document format, large enough to contain every page.
+    #[serde(rename = "format")]
+    Format,
+}
+
+impl Default for PdfImportPadTo {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl TryFrom<u32> for PdfImportPadTo {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "PdfImportPadTo try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for PdfImportPadTo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Widest => write!(f, "widest"),
+            Self::Format => write!(f, "format"),
+        }
+    }
+}
+
+impl PdfImportPadTo {
+    /// The size in Pdf units that all pages are padded to, given the sizes of the imported pages.
+    ///
+    /// The size contains every page, so all pages are padded to exactly this size.
+    /// Returns `None` when the pages should not be padded.
+    pub fn padded_page_size(
+        self,
+        page_sizes: impl Iterator<Item = na::Vector2<f64>>,
+        format_aspect_ratio: f64,
+    ) -> Option<na::Vector2<f64>> {
+        match self {
+            Self::None => None,
+            Self::Widest => page_sizes.reduce(|acc, size| acc.sup(&size)),
+            Self::Format => {
+                let max_size = page_sizes.reduce(|acc, size| acc.sup(&size))?;
+                // The smallest size with the aspect ratio of the format that the largest extents fit in
+                let width = max_size[0].max(max_size[1] * format_aspect_ratio);
+                Some(na::vector![width, width / format_aspect_ratio])
+            }
+        }
+    }
+}
+
+/// How imported Pdf pages are laid out.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "pdf_import_layout")]
+pub enum PdfImportLayout {
+    /// One page below the other, spaced by the page spacing.
+    #[serde(rename = "vertical")]
+    Vertical = 0,
+    /// Pages are tiled next to each other in rows onto the pages of the document format.
+    ///
+    /// The pages are shrunk so that at least two fit next to each other.
+    #[serde(rename = "grid")]
+    Grid,
+}
+
+impl Default for PdfImportLayout {
+    fn default() -> Self {
+        Self::Vertical
+    }
+}
+
+impl TryFrom<u32> for PdfImportLayout {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "PdfImportLayout try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for PdfImportLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vertical => write!(f, "vertical"),
+            Self::Grid => write!(f, "grid"),
+        }
+    }
+}
+
+/// Places imported Pdf pages one after another according to the layout of the Pdf import preferences.
+#[derive(Debug, Clone, Copy)]
+pub struct PdfPagePlacer {
+    pdf_import_prefs: PdfImportPrefs,
+    format: Format,
+    /// The position of the next page.
+    pos: na::Vector2<f64>,
+    /// The left edge of the rows, used by the grid layout.
+    row_start: f64,
+    /// The top of the document page the pages are currently placed on, used by the grid layout.
+    doc_page_top: f64,
+    /// The height of the tallest page in the current row, used by the grid layout.
+    row_height: f64,
+    row_pages: usize,
+    doc_page_pages: usize,
+}
+
+impl PdfPagePlacer {
+    /// The space between the tiled pages of the grid layout, half of the default import offset.
+    pub const GRID_GAP: f64 = 16.0;
+
+    pub fn new(
+        pdf_import_prefs: PdfImportPrefs,
+        insert_pos: na::Vector2<f64>,
+        format: &Format,
+    ) -> Self {
+        let doc_page_top = (insert_pos[1] / format.height()).floor() * format.height();
+        Self {
+            pdf_import_prefs,
+            format: *format,
+            pos: insert_pos,
+            row_start: insert_pos[0],
+            doc_page_top,
+            row_height: 0.0,
+            row_pages: 0,
+            doc_page_pages: 0,
+        }
+    }
+
+    fn grid(&self) -> bool {
+        self.pdf_import_prefs.layout == PdfImportLayout::Grid
+            && !self.pdf_import_prefs.adjust_document
+    }
+
+    /// Returns the position of the next page with the given size and advances past it.
+    pub fn place(&mut self, page_size: na::Vector2<f64>) -> na::Vector2<f64> {
+        if !self.grid() {
+            let pos = self.pos;
+            self.pos[1] += self
+                .pdf_import_prefs
+                .page_advance(page_size[1], &self.format);
+            return pos;
+        }
+        // The pages keep the import offset as margin to the right and bottom edges of the document page
+        let doc_page_left = (self.row_start / self.format.width()).floor() * self.format.width();
+        let right = doc_page_left + self.format.width() - Stroke::IMPORT_OFFSET_DEFAULT[0];
+        let bottom = self.doc_page_top + self.format.height() - Stroke::IMPORT_OFFSET_DEFAULT[1];
+        let exceeds = |end: f64, limit: f64| end > limit && approx::relative_ne!(end, limit);
+
+        if self.row_pages > 0 && exceeds(self.pos[0] + page_size[0], right) {
+            self.pos = na::vector![
+                self.row_start,
+                self.pos[1] + self.row_height + Self::GRID_GAP
+            ];
+            self.row_height = 0.0;
+            self.row_pages = 0;
+        }
+        if self.row_pages == 0
+            && self.doc_page_pages > 0
+            && exceeds(self.pos[1] + page_size[1], bottom)
+        {
+            // Continue on the next document page that is not covered by the already placed pages
+            self.doc_page_top = (self.pos[1] / self.format.height()).ceil() * self.format.height();
+            self.pos[1] = self.doc_page_top + Stroke::IMPORT_OFFSET_DEFAULT[1];
+            self.doc_page_pages = 0;
+        }
+
+        let pos = self.pos;
+        self.pos[0] += page_size[0] + Self::GRID_GAP;
+        self.row_height = self.row_height.max(page_size[1]);
+        self.row_pages += 1;
+        self.doc_page_pages += 1;
+        pos
+    }
+}
+
+/// Whether the form fields of imported Pdf pages are rendered with their values.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "pdf_import_form_fields")]
+pub enum PdfImportFormFields {
+    /// Render the form fields with the values they are filled in with.
+    #[serde(rename = "filled")]
+    Filled = 0,
+    /// Clear the values of the form fields, rendering the empty form.
+    #[serde(rename = "empty")]
+    Empty,
+}
+
+impl Default for PdfImportFormFields {
+    fn default() -> Self {
+        Self::Filled
+    }
+}
+
+impl TryFrom<u32> for PdfImportFormFields {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "PdfImportFormFields try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for PdfImportFormFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Filled => write!(f, "filled"),
+            Self::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+impl PdfImportFormFields {
+    /// Renders the Pdf page with its annotations and form fields.
+    ///
+    /// The filled form is rendered for printing, like all imported pages. The empty form is rendered for display,
+    /// because poppler skips the form fields that don't have the print flag set when printing,
+    /// which many form editors don't set, and the fields would disappear instead of being shown empty.
+    pub(crate) fn render_page(self, page: &poppler::Page, cx: &cairo::Context) {
+        match self {
+            Self::Filled => page.render_for_printing(cx),
+            Self::Empty => {
+                Self::clear_page_form_fields(page);
+                page.render(cx);
+            }
+        }
+    }
+
+    /// Clears the values of the form fields on the page. Poppler regenerates their appearance.
+    fn clear_page_form_fields(page: &poppler::Page) {
+        for field in page.form_field_mapping().into_iter().filter_map(|mapping| {
+            let mapping_ptr: *const poppler::ffi::PopplerFormFieldMapping =
+                mapping.to_glib_none().0;
+            // SAFETY: the pointer is valid while `mapping` is alive and the field is either null or owned by the mapping.
+            // `from_glib_none` takes a new reference, so the field outlives the mapping.
+            unsafe {
+                let field = (*mapping_ptr).field;
+                (!field.is_null()).then(|| from_glib_none::<_, poppler::FormField>(field))
+            }
+        }) {
+            match field.field_type() {
+                poppler::FormFieldType::Text => field.text_set_text(""),
+                poppler::FormFieldType::Button
+                    if field.button_get_button_type() != poppler::FormButtonType::Push =>
+                {
+                    field.button_set_state(false)
+                }
+                poppler::FormFieldType::Choice => field.choice_unselect_all(),
+                _ => {}
+            }
+        }
+    }
+}
+
 /// Pdf import preferences.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(default, rename = "pdf_import_prefs")]
@@ -100,6 +429,22 @@ pub struct PdfImportPrefs {
     /// Whether the document layout should be adjusted to the Pdf
     #[serde(rename = "adjust_document")]
     pub adjust_document: bool,
+    /// Whether the hyperlinks of the Pdf pages should be preserved, to be emitted again when exporting as Pdf
+    #[serde(rename = "preserve_links")]
+    pub preserve_links: bool,
+    /// To which size the pages are padded, centering their content
+    #[serde(rename = "pad_to")]
+    pub pad_to: PdfImportPadTo,
+    /// When set, near-white pixels of pages imported as bitmap are lifted to pure white,
+    /// see [crate::render::Image::whiten()]. Removes faint bleed-through from the back of scanned pages.
+    #[serde(rename = "whiten_threshold")]
+    pub whiten_threshold: Option<u8>,
+    /// Whether the form fields are rendered with their values or as the empty form.
+    #[serde(rename = "form_fields")]
+    pub form_fields: PdfImportFormFields,
+    /// How the pages are laid out. Has no effect when the document layout is adjusted to the Pdf.
+    #[serde(rename = "layout")]
+    pub layout: PdfImportLayout,
 }
 
 impl Default for PdfImportPrefs {
@@ -111,6 +456,53 @@ impl Default for PdfImportPrefs {
             bitmap_scalefactor: 1.8,
             page_borders: true,
             adjust_document: false,
+            preserve_links: true,
+            pad_to: PdfImportPadTo::default(),
+            whiten_threshold: None,
+            form_fields: PdfImportFormFields::default(),
+            layout: PdfImportLayout::default(),
+        }
+    }
+}
+
+impl PdfImportPrefs {
+    /// A whiten threshold that removes typical bleed-through while keeping light strokes and pencil marks.
+    pub const WHITEN_THRESHOLD_DEFAULT: u8 = 225;
+
+    /// The width of the imported pages.
+    pub fn page_width(&self, format: &Format) -> f64 {
+        if self.adjust_document {
+            return format.width();
+        }
+        let page_width = format.width() * (self.page_width_perc / 100.0);
+        match self.layout {
+            PdfImportLayout::Vertical => page_width,
+            PdfImportLayout::Grid => {
+                // Two pages with the import offset as margins to the edges of the document page
+                let two_up_width = (format.width()
+                    - Stroke::IMPORT_OFFSET_DEFAULT[0] * 2.0
+                    - PdfPagePlacer::GRID_GAP)
+                    * 0.5;
+                page_width.min(two_up_width)
+            }
+        }
+    }
+
+    /// Creates a placer for pages imported at the insert position.
+    pub fn page_placer(&self, insert_pos: na::Vector2<f64>, format: &Format) -> PdfPagePlacer {
+        PdfPagePlacer::new(*self, insert_pos, format)
+    }
+
+    /// The vertical distance from the top of an imported page with the given height to the top of the next page.
+    pub fn page_advance(&self, page_height: f64, format: &Format) -> f64 {
+        if self.adjust_document {
+            return page_height;
+        }
+        match self.page_spacing {
+            PdfImportPageSpacing::Continuous => {
+                page_height + Stroke::IMPORT_OFFSET_DEFAULT[1] * 0.5
+            }
+            PdfImportPageSpacing::OnePerDocumentPage => format.height(),
         }
     }
 }
@@ -130,6 +522,22 @@ impl Default for XoppImportPrefs {
     }
 }
 
+/// Bitmap image import preferences.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default, rename = "bitmap_import_prefs")]
+pub struct BitmapImportPrefs {
+    /// Whether the decodable part of truncated Png images is imported, with the missing rows left transparent.
+    ///
+    /// When not set, truncated images fail to import.
+    #[serde(rename = "allow_partial_images")]
+    pub allow_partial_images: bool,
+    /// Whether the colors of the images are inverted, keeping their transparency, see [render::Image::invert_colors()].
+    ///
+    /// Makes photographed chalkboards and other light writing on dark backgrounds readable on light backgrounds.
+    #[serde(rename = "invert_colors")]
+    pub invert_colors: bool,
+}
+
 /// Import preferences.
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 #[serde(default, rename = "import_prefs")]
@@ -140,6 +548,9 @@ pub struct ImportPrefs {
     /// Xournal++ `.xopp` file import preferences
     #[serde(rename = "xopp_import_prefs")]
     pub xopp_import_prefs: XoppImportPrefs,
+    /// Bitmap image import preferences
+    #[serde(rename = "bitmap_import_prefs")]
+    pub bitmap_import_prefs: BitmapImportPrefs,
 }
 
 impl CloneConfig for ImportPrefs {
@@ -167,6 +578,8 @@ impl Engine {
         self.set_pen_sounds(engine_config.pen_sounds, data_dir);
 
         self.set_optimize_epd(engine_config.optimize_epd);
+        self.store
+            .set_render_overscan(engine_config.render_overscan);
 
         widget_flags |= self
             .penholder
@@ -194,6 +607,8 @@ impl Engine {
         self.set_pen_sounds(engine_config.pen_sounds, data_dir);
 
         self.set_optimize_epd(engine_config.optimize_epd);
+        self.store
+            .set_render_overscan(engine_config.render_overscan);
 
         widget_flags |= self
             .penholder
@@ -275,13 +690,26 @@ impl Engine {
             restrain_to_viewport: true,
             respect_borders,
         };
+        let bitmap_import_prefs = self.import_prefs.bitmap_import_prefs;
         rayon::spawn(move || {
             let result = || -> anyhow::Result<BitmapImage> {
-                BitmapImage::from_image_bytes(
-                    &bytes,
-                    pos,
-                    ImageSizeOption::ResizeImage(resize_struct),
-                )
+                let mut image = if bitmap_import_prefs.allow_partial_images {
+                    let (image, recovered_rows) =
+                        render::Image::try_from_encoded_bytes_allow_partial(&bytes)?;
+                    if let Some(recovered_rows) = recovered_rows {
+                        warn!(
+                            "Imported truncated image, recovered {recovered_rows} of {} rows.",
+                            image.pixel_height
+                        );
+                    }
+                    image
+                } else {
+                    render::Image::try_from_encoded_bytes(&bytes)?
+                };
+                if bitmap_import_prefs.invert_colors {
+                    image.invert_colors();
+                }
+                BitmapImage::from_image(image, pos, ImageSizeOption::ResizeImage(resize_struct))
             };
 
             if oneshot_sender.send(result()).is_err() {
@@ -359,6 +787,284 @@ impl Engine {
         oneshot_receiver
     }
 
+    /// Generate bitmap image strokes for each page for the bytes, sent in chunks of `chunk_size` pages.
+    ///
+    /// The bytes are expected to be from a valid Pdf.
+    ///
+    /// In contrast to [Engine::generate_pdf_pages_from_bytes], only a limited amount of pages is held in memory at once,
+    /// the rendering waits until the receiver has consumed the previous chunk. The chunks are sent in page order.
+    /// Ignores the `pages_type` import pref, the pages are always imported as bitmap images.
+    ///
+    /// Note: `insert_pos` does not have an effect when the `adjust_document` import pref is set true.
+    #[allow(clippy::type_complexity)]
+    pub fn generate_pdf_pages_from_bytes_chunked(
+        &self,
+        bytes: Vec<u8>,
+        insert_pos: na::Vector2<f64>,
+        page_range: Option<Range<u32>>,
+        password: Option<String>,
+        chunk_size: usize,
+    ) -> mpsc::Receiver<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>> {
+        let (mut sender, receiver) =
+            mpsc::channel::<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>>(0);
+        let pdf_import_prefs = self.import_prefs.pdf_import_prefs;
+        let format = self.document.format;
+        let insert_pos = if self.import_prefs.pdf_import_prefs.adjust_document {
+            na::Vector2::<f64>::zeros()
+        } else {
+            insert_pos
+        };
+
+        rayon::spawn(move || {
+            let mut pages = match PdfBitmapPages::new(
+                &bytes,
+                pdf_import_prefs,
+                insert_pos,
+                page_range,
+                &format,
+                password,
+                chunk_size,
+            ) {
+                Ok(pages) => pages,
+                Err(e) => {
+                    if futures::executor::block_on(sender.send(Err(e))).is_err() {
+                        error!("Sending result to receiver while importing Pdf bytes chunked failed. Receiver already dropped");
+                    }
+                    return;
+                }
+            };
+            loop {
+                let chunk = pages
+                    .by_ref()
+                    .take(chunk_size.max(1))
+                    .map(|res| res.map(|s| (Stroke::BitmapImage(s), Some(StrokeLayer::Document))))
+                    .collect::<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>>();
+                if matches!(&chunk, Ok(chunk) if chunk.is_empty()) {
+                    break;
+                }
+                let failed = chunk.is_err();
+                if futures::executor::block_on(sender.send(chunk)).is_err() {
+                    error!("Sending result to receiver while importing Pdf bytes chunked failed. Receiver already dropped");
+                    break;
+                }
+                if failed {
+                    break;
+                }
+            }
+        });
+
+        receiver
+    }
+
+    /// Generate bitmap image strokes for the pages of the text of an Epub, laid out like imported Pdf pages.
+    ///
+    /// The bytes are expected to be from a valid Epub.
+    ///
+    /// Note: `insert_pos` does not have an effect when the `adjust_document` import pref is set true.
+    #[cfg(feature = "epub")]
+    #[allow(clippy::type_complexity)]
+    pub fn generate_epub_pages_from_bytes(
+        &self,
+        bytes: Vec<u8>,
+        insert_pos: na::Vector2<f64>,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>> {
+        let (oneshot_sender, oneshot_receiver) =
+            oneshot::channel::<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>>();
+        let pdf_import_prefs = self.import_prefs.pdf_import_prefs;
+        let format = self.document.format;
+        let insert_pos = if pdf_import_prefs.adjust_document {
+            na::Vector2::<f64>::zeros()
+        } else {
+            insert_pos
+        };
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>> {
+                Ok(
+                    BitmapImage::from_epub_bytes(&bytes, pdf_import_prefs, insert_pos, &format)?
+                        .into_iter()
+                        .map(|s| (Stroke::BitmapImage(s), Some(StrokeLayer::Document)))
+                        .collect(),
+                )
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver while importing Epub bytes failed. Receiver already dropped");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Generate the pages from the bytes of a GoodNotes archive.
+    ///
+    /// Page images are imported as bitmap images, ink stored as Svg as vector images. The pages are sized to the
+    /// width and spaced like imported Pdf pages, keeping their aspect ratio.
+    /// Returns the strokes together with the entries of the archive that were skipped.
+    ///
+    /// Note: `insert_pos` does not have an effect when the `adjust_document` import pref is set true.
+    #[cfg(feature = "goodnotes")]
+    #[allow(clippy::type_complexity)]
+    pub fn generate_goodnotes_pages_from_bytes(
+        &self,
+        bytes: Vec<u8>,
+        insert_pos: na::Vector2<f64>,
+    ) -> oneshot::Receiver<
+        anyhow::Result<(
+            Vec<(Stroke, Option<StrokeLayer>)>,
+            Vec<GoodnotesSkippedEntry>,
+        )>,
+    > {
+        let (oneshot_sender, oneshot_receiver) = oneshot::channel();
+        let pdf_import_prefs = self.import_prefs.pdf_import_prefs;
+        let format = self.document.format;
+        let insert_pos = if pdf_import_prefs.adjust_document {
+            na::Vector2::<f64>::zeros()
+        } else {
+            insert_pos
+        };
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<(
+                Vec<(Stroke, Option<StrokeLayer>)>,
+                Vec<GoodnotesSkippedEntry>,
+            )> {
+                let goodnotes_file = GoodnotesFile::load_from_bytes(&bytes)?;
+                let page_width = pdf_import_prefs.page_width(&format);
+                let mut skipped = goodnotes_file.skipped;
+                let mut strokes = Vec::with_capacity(goodnotes_file.pages.len());
+                let mut placer = pdf_import_prefs.page_placer(insert_pos, &format);
+                // The pages are created at the origin and moved to their place once their size is known
+                let pos = na::Vector2::<f64>::zeros();
+
+                for page in goodnotes_file.pages {
+                    let stroke = match page.content {
+                        GoodnotesPageContent::Image(image_bytes) => BitmapImage::from_image_bytes(
+                            &image_bytes,
+                            pos,
+                            ImageSizeOption::RespectOriginalSize,
+                        )
+                        .map(|mut bitmapimage| {
+                            let size = na::vector![
+                                f64::from(bitmapimage.image.pixel_width),
+                                f64::from(bitmapimage.image.pixel_height)
+                            ];
+                            bitmapimage.rectangle = fit_page_rectangle(pos, size, page_width);
+                            Stroke::BitmapImage(bitmapimage)
+                        }),
+                        GoodnotesPageContent::Ink(svg_data) => VectorImage::from_svg_str(
+                            &svg_data,
+                            pos,
+                            ImageSizeOption::RespectOriginalSize,
+                        )
+                        .map(|mut vectorimage| {
+                            vectorimage.rectangle =
+                                fit_page_rectangle(pos, vectorimage.intrinsic_size, page_width);
+                            Stroke::VectorImage(vectorimage)
+                        }),
+                    };
+                    match stroke {
+                        Ok(mut stroke) => {
+                            let bounds = stroke.bounds();
+                            stroke.translate(placer.place(bounds.extents()) - bounds.mins.coords);
+                            strokes.push((stroke, Some(StrokeLayer::Document)));
+                        }
+                        Err(e) => skipped.push(GoodnotesSkippedEntry {
+                            path: page.path,
+                            reason: format!("loading the page failed, Err: {e}"),
+                        }),
+                    }
+                }
+
+                Ok((strokes, skipped))
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver while importing GoodNotes bytes failed. Receiver already dropped");
+            }
+        });
+
+        oneshot_receiver
+    }
+
+    /// Generate a line chart from the bytes of a Csv file with numeric x,y pairs.
+    ///
+    /// The data is drawn as a polyline, scaled to fit into the page at `insert_pos`.
+    /// When `with_axes` is true, the x and y axes are drawn as well.
+    #[allow(clippy::type_complexity)]
+    pub fn generate_csv_chart_from_bytes(
+        &self,
+        bytes: Vec<u8>,
+        insert_pos: na::Vector2<f64>,
+        with_axes: bool,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>> {
+        let (oneshot_sender, oneshot_receiver) =
+            oneshot::channel::<anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>>>();
+        let format_size = self.document.format.size();
+        let style = Style::Smooth(SmoothOptions {
+            fill_color: None,
+            ..self.pens_config.shaper_config.smooth_options.clone()
+        });
+
+        rayon::spawn(move || {
+            let result = || -> anyhow::Result<Vec<(Stroke, Option<StrokeLayer>)>> {
+                let csv_file = CsvFile::load_from_bytes(&bytes)?;
+                let data_bounds = csv_file.data_bounds();
+                const MARGIN_RATIO: f64 = 0.1;
+                let margin = format_size.min() * MARGIN_RATIO;
+                let chart_bounds = Aabb::new(insert_pos.into(), (insert_pos + format_size).into())
+                    .loosened(-margin);
+                let mut points = csv_file
+                    .points
+                    .iter()
+                    .map(|p| CsvFile::map_point_to_bounds(*p, data_bounds, chart_bounds));
+                let start = points
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Csv file does not contain any points."))?;
+                let polyline = Polyline {
+                    start,
+                    path: points.collect(),
+                };
+                let mut strokes = vec![(
+                    Stroke::ShapeStroke(ShapeStroke::new(Shape::Polyline(polyline), style.clone())),
+                    None,
+                )];
+
+                if with_axes {
+                    // The axes cross at the data origin when it is inside the data bounds,
+                    // else they are placed at the lower left edges
+                    let origin = na::vector![
+                        0.0_f64.clamp(data_bounds.mins[0], data_bounds.maxs[0]),
+                        0.0_f64.clamp(data_bounds.mins[1], data_bounds.maxs[1])
+                    ];
+                    let origin = CsvFile::map_point_to_bounds(origin, data_bounds, chart_bounds);
+                    let x_axis = Line {
+                        start: na::vector![chart_bounds.mins[0], origin[1]],
+                        end: na::vector![chart_bounds.maxs[0], origin[1]],
+                    };
+                    let y_axis = Line {
+                        start: na::vector![origin[0], chart_bounds.maxs[1]],
+                        end: na::vector![origin[0], chart_bounds.mins[1]],
+                    };
+                    for axis in [x_axis, y_axis] {
+                        strokes.push((
+                            Stroke::ShapeStroke(ShapeStroke::new(Shape::Line(axis), style.clone())),
+                            None,
+                        ));
+                    }
+                }
+
+                Ok(strokes)
+            };
+
+            if oneshot_sender.send(result()).is_err() {
+                error!("Sending result to receiver while importing Csv bytes failed. Receiver already dropped");
+            }
+        });
+
+        oneshot_receiver
+    }
+
     /// Import the generated strokes into the store.
     pub fn import_generated_content(
         &mut self,
@@ -369,7 +1075,44 @@ impl Engine {
         if strokes.is_empty() {
             return widget_flags;
         }
-        let select = !adjust_document;
+        if adjust_document {
+            widget_flags |=
+                self.adjust_doc_to_page_size(Self::generated_content_max_size(&strokes));
+        }
+        widget_flags |= self.insert_generated_content(strokes, !adjust_document);
+        widget_flags
+    }
+
+    /// The size of the largest of the generated strokes, which is the page size when the document is adjusted
+    /// to the imported content.
+    pub fn generated_content_max_size(
+        strokes: &[(Stroke, Option<StrokeLayer>)],
+    ) -> na::Vector2<f64> {
+        strokes
+            .iter()
+            .map(|(stroke, _)| stroke.bounds().extents())
+            .fold(na::Vector2::<f64>::zeros(), |acc, x| acc.maxs(&x))
+    }
+
+    /// Sets the document format to the page size and switches to the fixed-size layout.
+    ///
+    /// Imports that insert their content in multiple chunks adjust the document once after the last chunk.
+    pub fn adjust_doc_to_page_size(&mut self, page_size: na::Vector2<f64>) -> WidgetFlags {
+        self.document.format.set_width(page_size[0]);
+        self.document.format.set_height(page_size[1]);
+        self.set_doc_layout(Layout::FixedSize) | self.doc_resize_autoexpand()
+    }
+
+    /// Inserts the generated strokes into the store without adjusting the document, and selects them with `select`.
+    pub fn insert_generated_content(
+        &mut self,
+        strokes: Vec<(Stroke, Option<StrokeLayer>)>,
+        select: bool,
+    ) -> WidgetFlags {
+        let mut widget_flags = WidgetFlags::default();
+        if strokes.is_empty() {
+            return widget_flags;
+        }
 
         // we need to always deselect all strokes. Even tough changing the pen style deselects too, it does only when
         // the pen is actually different.
@@ -380,16 +1123,6 @@ impl Engine {
             widget_flags |= self.change_pen_style(PenStyle::Selector);
         }
 
-        if adjust_document {
-            let max_size = strokes
-                .iter()
-                .map(|(stroke, _)| stroke.bounds().extents())
-                .fold(na::Vector2::<f64>::zeros(), |acc, x| acc.maxs(&x));
-            self.document.format.set_width(max_size[0]);
-            self.document.format.set_height(max_size[1]);
-            widget_flags |= self.set_doc_layout(Layout::FixedSize) | self.doc_resize_autoexpand()
-        }
-
         let inserted = strokes
             .into_iter()
             .map(|(stroke, layer)| self.store.insert_stroke(stroke, layer))
@@ -409,6 +1142,173 @@ impl Engine {
         widget_flags
     }
 
+    /// Inserts a table of contents as the new first pages and moves all existing content down below it.
+    ///
+    /// The entries are the titles together with the y coordinates where the content they list starts.
+    /// Entries that don't fit on a page continue on the next one, and the content is moved down by whole pages.
+    /// The listed page numbers are the numbers of the document pages the content starts on after it is moved.
+    pub fn insert_toc_pages(&mut self, entries: &[(String, f64)]) -> WidgetFlags {
+        let mut widget_flags = WidgetFlags::default();
+        let format_size = self.document.format.size();
+
+        const MARGIN_RATIO: f64 = 0.1;
+        // The share of the content width that is reserved for the titles, the rest is for the page numbers
+        const TITLES_WIDTH_RATIO: f64 = 0.85;
+        let margin = format_size.min() * MARGIN_RATIO;
+        let content_width = format_size[0] - 2.0 * margin;
+        let titles_width = content_width * TITLES_WIDTH_RATIO;
+        let entry_spacing = TextStyle::FONT_SIZE_DEFAULT * 0.5;
+
+        let mut heading_style = TextStyle::default();
+        heading_style.font_size = TextStyle::FONT_SIZE_DEFAULT * 1.5;
+        heading_style.font_weight = 700;
+        let heading = TextStroke::new(
+            String::from("Contents"),
+            na::vector![margin, margin],
+            heading_style,
+        );
+        let mut y = heading.bounds().maxs[1] + TextStyle::FONT_SIZE_DEFAULT;
+        let mut toc_strokes = vec![heading];
+        let mut titles_y = Vec::with_capacity(entries.len());
+        for (title, _) in entries {
+            let mut title_style = TextStyle::default();
+            title_style.set_max_width(Some(titles_width));
+            let mut title = TextStroke::new(title.clone(), na::vector![margin, y], title_style);
+            // Entries that would reach into the bottom margin continue at the top of the next page
+            let page_bottom = ((y / format_size[1]).floor() + 1.0) * format_size[1] - margin;
+            if title.bounds().maxs[1] > page_bottom {
+                let next_page_top = page_bottom + 2.0 * margin;
+                title.translate(na::vector![0.0, next_page_top - y]);
+                y = next_page_top;
+            }
+            titles_y.push(y);
+            y = title.bounds().maxs[1] + entry_spacing;
+            toc_strokes.push(title);
+        }
+        let toc_pages = (y / format_size[1]).ceil().max(1.0);
+        let offset = na::vector![0.0, toc_pages * format_size[1]];
+        for ((_, start_y), title_y) in entries.iter().zip(titles_y) {
+            // The tolerance keeps content that starts exactly on a page boundary on that page
+            let page = ((start_y + offset[1]) / format_size[1] + 1e-6)
+                .floor()
+                .max(0.0) as usize
+                + 1;
+            let mut page_style = TextStyle::default();
+            page_style.alignment = TextAlignment::End;
+            page_style.set_max_width(Some(content_width - titles_width));
+            toc_strokes.push(TextStroke::new(
+                page.to_string(),
+                na::vector![margin + titles_width, title_y],
+                page_style,
+            ));
+        }
+
+        let all_strokes = self.store.stroke_keys_unordered();
+        self.store.translate_strokes(&all_strokes, offset);
+        self.store.translate_strokes_images(&all_strokes, offset);
+        for stroke in toc_strokes {
+            self.store.insert_stroke(Stroke::TextStroke(stroke), None);
+        }
+
+        widget_flags |= self.doc_resize_to_fit_content();
+        widget_flags |= self.store.record(Instant::now());
+        widget_flags.resize = true;
+        widget_flags.store_modified = true;
+
+        widget_flags
+    }
+
+    /// Import plain text as text strokes, wrapped to the width of the pages.
+    ///
+    /// The text starts at the top of the page below the existing content.
+    /// Text that doesn't fit on a page continues on the next one, the document is extended as needed.
+    pub fn import_text(&mut self, text: &str, mut text_style: TextStyle) -> WidgetFlags {
+        let mut widget_flags = WidgetFlags::default();
+        let mut pagination = TextPagination::below_content(self);
+        text_style.set_max_width(Some(pagination.content_size()[0]));
+        let lines = match text_style.lines(&mut piet_cairo::CairoText::new(), text.to_string()) {
+            Ok(lines) => lines,
+            Err(e) => {
+                error!("Laying out text failed while importing text, Err: {e:?}");
+                return widget_flags;
+            }
+        };
+
+        let text_strokes = pagination
+            .place_block(&lines, text.len(), 0.0)
+            .into_iter()
+            .map(|(range, pos)| {
+                TextStroke::new(
+                    text[range].trim_end_matches(['\n', '\r']).to_string(),
+                    pos,
+                    text_style.clone(),
+                )
+            })
+            .collect::<Vec<TextStroke>>();
+        for stroke in text_strokes {
+            self.store.insert_stroke(Stroke::TextStroke(stroke), None);
+        }
+
+        widget_flags |= self.doc_resize_to_fit_content();
+        widget_flags |= self.store.record(Instant::now());
+        widget_flags.resize = true;
+        widget_flags.store_modified = true;
+
+        widget_flags
+    }
+
+    /// Import the blocks of a Markdown file as styled text strokes, laid out from top to bottom on the pages.
+    ///
+    /// Headings are enlarged and bold, list items and block quotes are indented by their nesting depth.
+    /// Blocks that don't fit on a page continue on the next one, the document is extended as needed.
+    #[cfg(feature = "markdown")]
+    pub fn import_markdown(
+        &mut self,
+        markdown: &MarkdownFile,
+        text_style: TextStyle,
+    ) -> WidgetFlags {
+        let mut widget_flags = WidgetFlags::default();
+        let mut pagination = TextPagination::below_content(self);
+        let content_width = pagination.content_size()[0];
+        let indent_width = text_style.font_size * 2.0;
+        let block_spacing = text_style.font_size * 0.5;
+
+        let mut text_strokes = vec![];
+        for block in markdown.blocks.iter() {
+            let mut block_style = markdown_block_text_style(block, &text_style);
+            let indent = block.indent as f64 * indent_width;
+            block_style.set_max_width(Some((content_width - indent).max(indent_width)));
+            pagination.add_spacing(match block.kind {
+                MarkdownBlockKind::Heading(_) => block_style.font_size,
+                _ => block_spacing,
+            });
+            let lines =
+                match block_style.lines(&mut piet_cairo::CairoText::new(), block.text.clone()) {
+                    Ok(lines) => lines,
+                    Err(e) => {
+                        error!("Laying out text failed while importing Markdown, Err: {e:?}");
+                        return widget_flags;
+                    }
+                };
+            text_strokes.extend(
+                pagination
+                    .place_block(&lines, block.text.len(), indent)
+                    .into_iter()
+                    .map(|(range, pos)| markdown_text_stroke(block, range, pos, &block_style)),
+            );
+        }
+        for stroke in text_strokes {
+            self.store.insert_stroke(Stroke::TextStroke(stroke), None);
+        }
+
+        widget_flags |= self.doc_resize_to_fit_content();
+        widget_flags |= self.store.record(Instant::now());
+        widget_flags.resize = true;
+        widget_flags.store_modified = true;
+
+        widget_flags
+    }
+
     /// Insert text.
     pub fn insert_text(&mut self, text: String, pos: Option<na::Vector2<f64>>) -> WidgetFlags {
         let mut widget_flags = WidgetFlags::default();
@@ -473,3 +1373,578 @@ impl Engine {
         widget_flags
     }
 }
+
+/// Lays out blocks of text from top to bottom on the pages, continuing on the next page when a block doesn't fit.
+struct TextPagination {
+    format_size: na::Vector2<f64>,
+    margin: f64,
+    page: usize,
+    /// The vertical offset into the content area of the current page.
+    y: f64,
+}
+
+impl TextPagination {
+    const MARGIN_RATIO: f64 = 0.1;
+
+    /// Starts at the top of the page below the existing content of the engine.
+    fn below_content(engine: &Engine) -> Self {
+        let format_size = engine.document.format.size();
+        let page = engine
+            .store
+            .bounds_for_strokes(&engine.store.stroke_keys_unordered())
+            .map(|bounds| (bounds.maxs[1] / format_size[1]).ceil().max(0.0) as usize)
+            .unwrap_or(0);
+        Self {
+            format_size,
+            margin: format_size.min() * Self::MARGIN_RATIO,
+            page,
+            y: 0.0,
+        }
+    }
+
+    /// The size of the area inside the margins of a page.
+    fn content_size(&self) -> na::Vector2<f64> {
+        self.format_size - na::Vector2::repeat(2.0 * self.margin)
+    }
+
+    /// Adds spacing between blocks, none at the top of a page.
+    #[cfg(feature = "markdown")]
+    fn add_spacing(&mut self, spacing: f64) {
+        if self.y > 0.0 {
+            self.y += spacing;
+        }
+    }
+
+    /// Splits the laid out lines of a block into chunks that fit on the pages, at the line boundaries.
+    ///
+    /// Returns the byte ranges of the chunks in the text of the block, together with their positions.
+    /// The x offset is relative to the left margin.
+    fn place_block(
+        &mut self,
+        lines: &[piet::LineMetric],
+        text_len: usize,
+        x: f64,
+    ) -> Vec<(Range<usize>, na::Vector2<f64>)> {
+        let content_height = self.content_size()[1];
+        let mut chunks = vec![];
+        let mut chunk_start = 0;
+        let mut chunk_height = 0.0;
+        for line in lines.iter() {
+            if self.y + chunk_height + line.height > content_height
+                && (line.start_offset > chunk_start || self.y > 0.0)
+            {
+                if line.start_offset > chunk_start {
+                    chunks.push((chunk_start..line.start_offset, self.pos(x)));
+                }
+                self.page += 1;
+                self.y = 0.0;
+                chunk_start = line.start_offset;
+                chunk_height = 0.0;
+            }
+            chunk_height += line.height;
+        }
+        if chunk_start < text_len {
+            chunks.push((chunk_start..text_len, self.pos(x)));
+        }
+        self.y += chunk_height;
+        chunks
+    }
+
+    fn pos(&self, x: f64) -> na::Vector2<f64> {
+        na::vector![
+            self.margin + x,
+            self.page as f64 * self.format_size[1] + self.margin + self.y
+        ]
+    }
+}
+
+/// The rectangle of an imported page with the given size, scaled to the page width keeping the aspect ratio.
+#[cfg(feature = "goodnotes")]
+fn fit_page_rectangle(
+    pos: na::Vector2<f64>,
+    size: na::Vector2<f64>,
+    page_width: f64,
+) -> rnote_compose::shapes::Rectangle {
+    let size = if size[0] > 0.0 {
+        size * (page_width / size[0])
+    } else {
+        size
+    };
+    rnote_compose::shapes::Rectangle::from_corners(pos, pos + size)
+}
+
+/// The text style of a Markdown block, with the size and weight of headings and the inline styles as ranged attributes.
+#[cfg(feature = "markdown")]
+fn markdown_block_text_style(block: &MarkdownBlock, text_style: &TextStyle) -> TextStyle {
+    const HEADING_SCALES: [f64; 6] = [2.0, 1.6, 1.35, 1.2, 1.1, 1.0];
+    const MONOSPACE_FONT_FAMILY: &str = "monospace";
+    let bold_weight = piet::FontWeight::BOLD.to_raw();
+
+    let mut block_style = text_style.clone();
+    match block.kind {
+        MarkdownBlockKind::Heading(level) => {
+            block_style.font_size *= HEADING_SCALES[(level.clamp(1, 6) - 1) as usize];
+            block_style.font_weight = bold_weight;
+        }
+        MarkdownBlockKind::CodeBlock => {
+            block_style.font_family = String::from(MONOSPACE_FONT_FAMILY);
+        }
+        MarkdownBlockKind::Paragraph | MarkdownBlockKind::ListItem => {}
+    }
+    block_style.ranged_text_attributes = block
+        .spans
+        .iter()
+        .map(|span| RangedTextAttribute {
+            range: span.range.clone(),
+            attribute: match span.style {
+                MarkdownStyle::Emphasis => TextAttribute::Style(FontStyle::Italic),
+                MarkdownStyle::Strong => TextAttribute::FontWeight(bold_weight),
+                MarkdownStyle::Strikethrough => TextAttribute::Strikethrough(true),
+                MarkdownStyle::Code => {
+                    TextAttribute::FontFamily(String::from(MONOSPACE_FONT_FAMILY))
+                }
+            },
+        })
+        .collect();
+    block_style
+}
+
+/// A text stroke for the range of the text of a Markdown block, with the ranged attributes clipped to it.
+#[cfg(feature = "markdown")]
+fn markdown_text_stroke(
+    block: &MarkdownBlock,
+    range: Range<usize>,
+    pos: na::Vector2<f64>,
+    block_style: &TextStyle,
+) -> TextStroke {
+    let text = block.text[range.clone()].trim_end_matches(['\n', '\r']);
+    let mut chunk_style = block_style.clone();
+    chunk_style.ranged_text_attributes = block_style
+        .ranged_text_attributes
+        .iter()
+        .filter_map(|attr| {
+            let start = attr.range.start.max(range.start);
+            let end = attr.range.end.min(range.start + text.len());
+            (end > start).then(|| RangedTextAttribute {
+                range: start - range.start..end - range.start,
+                attribute: attr.attribute.clone(),
+            })
+        })
+        .collect();
+    TextStroke::new(text.to_string(), pos, chunk_style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PdfImportLayout, PdfImportPadTo, PdfImportPagesType, PdfImportPrefs, PdfPagePlacer,
+    };
+    use crate::document::{Format, Layout};
+    use crate::engine::export::{DocExportFormat, DocExportPrefs, ExportOptions};
+    use crate::store::chrono_comp::StrokeLayer;
+    use crate::strokes::textstroke::TextStyle;
+    use crate::strokes::{ShapeStroke, Stroke};
+    use crate::Engine;
+    use approx::assert_relative_eq;
+    use p2d::bounding_volume::BoundingVolume;
+    use rnote_compose::ext::Vector2Ext;
+    use rnote_compose::shapes::{Rectangle, Shape, Shapeable};
+    use rnote_compose::Style;
+
+    fn rect_page(pos: na::Vector2<f64>, size: na::Vector2<f64>) -> (Stroke, Option<StrokeLayer>) {
+        let rect = Rectangle::from_corners(pos, pos + size);
+        (
+            Stroke::ShapeStroke(ShapeStroke::new(Shape::Rectangle(rect), Style::default())),
+            None,
+        )
+    }
+
+    #[test]
+    fn chunked_import_adjusts_document_once_to_largest_page() {
+        let mut engine = Engine::default();
+        let first_chunk = vec![rect_page(na::vector![0.0, 0.0], na::vector![200.0, 300.0])];
+        let second_chunk = vec![rect_page(
+            na::vector![0.0, 300.0],
+            na::vector![300.0, 100.0],
+        )];
+        let max_size = Engine::generated_content_max_size(&first_chunk)
+            .maxs(&Engine::generated_content_max_size(&second_chunk));
+
+        let _ = engine.insert_generated_content(first_chunk, false);
+        let _ = engine.insert_generated_content(second_chunk, false);
+        let _ = engine.adjust_doc_to_page_size(max_size);
+
+        assert_eq!(engine.document.layout, Layout::FixedSize);
+        // the width comes from the second chunk, the height from the first
+        assert!(max_size[0] > 300.0 && max_size[1] > 300.0);
+        assert_relative_eq!(engine.document.format.width(), max_size[0]);
+        assert_relative_eq!(engine.document.format.height(), max_size[1]);
+    }
+
+    /// The texts of the text strokes and their y positions, ordered from top to bottom and left to right.
+    fn text_strokes_texts(engine: &Engine) -> Vec<(f64, String)> {
+        let mut texts = engine
+            .store
+            .get_strokes_ref(&engine.store.stroke_keys_unordered())
+            .into_iter()
+            .filter_map(|stroke| match stroke {
+                Stroke::TextStroke(textstroke) => Some((
+                    textstroke.transform.translation_part()[1],
+                    textstroke.transform.translation_part()[0],
+                    textstroke.text.clone(),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<(f64, f64, String)>>();
+        texts.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        texts.into_iter().map(|(y, _, text)| (y, text)).collect()
+    }
+
+    #[test]
+    fn pad_to_format_contains_every_page_at_format_ratio() {
+        let page_sizes = [
+            na::vector![100.0, 100.0],
+            na::vector![200.0, 50.0],
+            na::vector![80.0, 300.0],
+        ];
+        let ratio = 0.5;
+        let padded = PdfImportPadTo::Format
+            .padded_page_size(page_sizes.into_iter(), ratio)
+            .unwrap();
+        assert_relative_eq!(padded[0] / padded[1], ratio);
+        // Every page fits, so all pages are padded to the same size
+        for size in page_sizes {
+            assert_eq!(padded.sup(&size), padded);
+        }
+        assert_relative_eq!(padded, na::vector![200.0, 400.0]);
+        assert!(PdfImportPadTo::None
+            .padded_page_size(page_sizes.into_iter(), ratio)
+            .is_none());
+    }
+
+    fn grid_prefs() -> PdfImportPrefs {
+        PdfImportPrefs {
+            layout: PdfImportLayout::Grid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn page_placer_wraps_rows_and_continues_on_the_next_page() {
+        let format = Format::default();
+        let prefs = grid_prefs();
+        let page_width = prefs.page_width(&format);
+        let page_size = na::vector![page_width, page_width * format.height() / format.width()];
+        let offset = Stroke::IMPORT_OFFSET_DEFAULT;
+        let advance = page_size.add_scalar(PdfPagePlacer::GRID_GAP);
+        // Two rows of two pages fit on a document page, but not three
+        assert!(offset[1] + advance[1] * 2.0 < format.height());
+        assert!(offset[1] + advance[1] * 2.0 + page_size[1] > format.height() - offset[1]);
+
+        let mut placer = prefs.page_placer(offset, &format);
+        let positions = (0..5)
+            .map(|_| placer.place(page_size))
+            .collect::<Vec<na::Vector2<f64>>>();
+        assert_relative_eq!(positions[0], offset);
+        assert_relative_eq!(positions[1], offset + na::vector![advance[0], 0.0]);
+        assert_relative_eq!(positions[2], offset + na::vector![0.0, advance[1]]);
+        assert_relative_eq!(positions[3], offset + advance);
+        assert_relative_eq!(
+            positions[4],
+            na::vector![offset[0], format.height() + offset[1]]
+        );
+    }
+
+    #[test]
+    fn page_placer_places_oversized_pages_alone() {
+        let format = Format::default();
+        let offset = Stroke::IMPORT_OFFSET_DEFAULT;
+        let mut placer = grid_prefs().page_placer(offset, &format);
+        let oversized = format.size() * 1.5;
+
+        // A page that is larger than the document page is still placed first
+        assert_relative_eq!(placer.place(oversized), offset);
+        // The next page neither fits next to nor below it, so it continues on the first document page
+        // that is not covered
+        assert_relative_eq!(
+            placer.place(na::vector![100.0, 100.0]),
+            na::vector![offset[0], format.height() * 2.0 + offset[1]]
+        );
+    }
+
+    #[test]
+    fn page_placer_stacks_pages_vertically() {
+        let format = Format::default();
+        let page_size = na::vector![300.0, 400.0];
+        let insert_pos = na::vector![50.0, 60.0];
+        let adjusted_grid = PdfImportPrefs {
+            adjust_document: true,
+            ..grid_prefs()
+        };
+        // The grid layout has no effect when the document is adjusted to the Pdf
+        for prefs in [PdfImportPrefs::default(), adjusted_grid] {
+            let mut placer = prefs.page_placer(insert_pos, &format);
+            let advance = prefs.page_advance(page_size[1], &format);
+            assert_relative_eq!(placer.place(page_size), insert_pos);
+            assert_relative_eq!(
+                placer.place(page_size),
+                insert_pos + na::vector![0.0, advance]
+            );
+            assert_relative_eq!(
+                placer.place(page_size),
+                insert_pos + na::vector![0.0, advance * 2.0]
+            );
+        }
+    }
+
+    #[test]
+    fn grid_layout_imports_two_pages_per_row() {
+        let mut pdf_engine = Engine::default();
+        let page_height = pdf_engine.document.format.height();
+        for page in 0..3 {
+            let offset = page_height * page as f64;
+            pdf_engine.store.insert_stroke(
+                rect_page(na::vector![10.0, offset + 10.0], na::vector![100.0, 60.0]).0,
+                None,
+            );
+        }
+        let _ = pdf_engine.doc_resize_to_fit_content();
+        let prefs = DocExportPrefs {
+            export_format: DocExportFormat::Pdf,
+            ..Default::default()
+        };
+        let pdf_bytes = futures::executor::block_on(pdf_engine.export_doc(
+            String::from("pages"),
+            Some(prefs),
+            &ExportOptions::default(),
+        ))
+        .unwrap()
+        .unwrap();
+
+        let mut engine = Engine::default();
+        engine.import_prefs.pdf_import_prefs = PdfImportPrefs {
+            pages_type: PdfImportPagesType::Vector,
+            ..grid_prefs()
+        };
+        let format = engine.document.format;
+        let page_width = engine.import_prefs.pdf_import_prefs.page_width(&format);
+        let page_height = page_width * format.height() / format.width();
+        let offset = Stroke::IMPORT_OFFSET_DEFAULT;
+        let strokes = futures::executor::block_on(
+            engine.generate_pdf_pages_from_bytes(pdf_bytes, offset, None, None),
+        )
+        .unwrap()
+        .unwrap();
+        let bounds = strokes
+            .iter()
+            .map(|(stroke, _)| stroke.bounds())
+            .collect::<Vec<_>>();
+        assert_eq!(bounds.len(), 3);
+        for page_bounds in &bounds {
+            assert_relative_eq!(
+                page_bounds.extents(),
+                na::vector![page_width, page_height],
+                epsilon = 1e-6
+            );
+        }
+        assert_relative_eq!(bounds[0].mins.coords, offset, epsilon = 1e-6);
+        assert_relative_eq!(
+            bounds[1].mins.coords,
+            offset + na::vector![page_width + PdfPagePlacer::GRID_GAP, 0.0],
+            epsilon = 1e-6
+        );
+        assert_relative_eq!(
+            bounds[2].mins.coords,
+            offset + na::vector![0.0, page_height + PdfPagePlacer::GRID_GAP],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn import_text_continues_on_new_pages() {
+        let mut engine = Engine::default();
+        let page_size = engine.document.format.size();
+        // Existing content on the first page, the text starts on the second
+        let (stroke, layer) = rect_page(na::vector![0.0, 0.0], page_size * 0.5);
+        engine.store.insert_stroke(stroke, layer);
+        let text = (0..200)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let _ = engine.import_text(&text, TextStyle::default());
+
+        let texts = text_strokes_texts(&engine);
+        assert!(texts.len() > 1);
+        for (page, (y, _)) in texts.iter().enumerate() {
+            let margin = page_size.min() * 0.1;
+            assert_relative_eq!(*y, (page + 1) as f64 * page_size[1] + margin);
+        }
+        // The text is split at line boundaries
+        let imported = texts
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert_eq!(imported, text);
+        // The text strokes fit on their pages
+        for stroke in engine
+            .store
+            .get_strokes_ref(&engine.store.stroke_keys_unordered())
+            .into_iter()
+            .filter(|stroke| matches!(stroke, Stroke::TextStroke(_)))
+        {
+            let bounds = stroke.bounds();
+            let page = (bounds.mins[1] / page_size[1]).floor();
+            assert!(bounds.maxs[1] <= (page + 1.0) * page_size[1]);
+            assert!(bounds.maxs[0] <= page_size[0]);
+        }
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn import_markdown_lays_out_the_blocks() {
+        use crate::fileformats::markdownformat::MarkdownFile;
+
+        let mut engine = Engine::default();
+        let page_size = engine.document.format.size();
+        let margin = page_size.min() * 0.1;
+        let markdown = MarkdownFile::parse("# Title\n\nSome *text*.\n\n- item\n  - nested\n");
+        let _ = engine.import_markdown(&markdown, TextStyle::default());
+
+        let strokes = engine
+            .store
+            .get_strokes_ref(&engine.store.stroke_keys_unordered())
+            .into_iter()
+            .filter_map(|stroke| match stroke {
+                Stroke::TextStroke(textstroke) => Some(textstroke.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let texts = text_strokes_texts(&engine)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<String>>();
+        assert_eq!(texts, ["Title", "Some text.", "• item", "• nested"]);
+        let stroke = |text: &str| strokes.iter().find(|s| s.text == text).unwrap();
+
+        // The heading starts at the top margin of the first page and is enlarged and bold
+        let title = stroke("Title");
+        assert_relative_eq!(title.transform.translation_part()[1], margin);
+        assert!(title.text_style.font_size > TextStyle::FONT_SIZE_DEFAULT);
+        assert_eq!(
+            title.text_style.font_weight,
+            piet::FontWeight::BOLD.to_raw()
+        );
+        // The emphasis is carried over as a ranged attribute
+        assert_eq!(
+            stroke("Some text.").text_style.ranged_text_attributes.len(),
+            1
+        );
+        // Nested list items are indented
+        assert!(
+            stroke("• nested").transform.translation_part()[0]
+                > stroke("• item").transform.translation_part()[0]
+        );
+        // The blocks are laid out from top to bottom without overlapping
+        let mut bounds = strokes.iter().map(|s| s.bounds()).collect::<Vec<_>>();
+        bounds.sort_by(|a, b| a.mins[1].total_cmp(&b.mins[1]));
+        for pair in bounds.windows(2) {
+            assert!(pair[0].maxs[1] <= pair[1].mins[1]);
+        }
+    }
+
+    #[test]
+    fn toc_numbers_document_pages_below_the_toc() {
+        let mut engine = Engine::default();
+        let page_size = engine.document.format.size();
+        // The first file starts on the first page, the second on the third
+        for start in [0.0, 2.0 * page_size[1]] {
+            let (stroke, layer) = rect_page(na::vector![0.0, start], page_size * 0.5);
+            engine.store.insert_stroke(stroke, layer);
+        }
+        let _ = engine.insert_toc_pages(&[
+            (String::from("first"), 0.0),
+            (String::from("second"), 2.0 * page_size[1]),
+        ]);
+
+        let texts = text_strokes_texts(&engine)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<String>>();
+        assert_eq!(texts, ["Contents", "first", "2", "second", "4"]);
+        // The content is moved below the table of contents
+        let content_top = engine
+            .store
+            .get_strokes_ref(&engine.store.stroke_keys_unordered())
+            .into_iter()
+            .filter(|stroke| matches!(stroke, Stroke::ShapeStroke(_)))
+            .map(|stroke| stroke.bounds().mins[1])
+            .fold(f64::INFINITY, f64::min);
+        assert!(content_top >= page_size[1] - 1.0);
+    }
+
+    #[test]
+    fn long_toc_continues_on_next_pages_without_overlapping_content() {
+        let mut engine = Engine::default();
+        let page_size = engine.document.format.size();
+        let (stroke, layer) = rect_page(na::vector![0.0, 0.0], page_size * 0.5);
+        let content_key = engine.store.insert_stroke(stroke, layer);
+        let entries = (0..100)
+            .map(|i| (format!("document {i}"), 0.0))
+            .collect::<Vec<(String, f64)>>();
+        let _ = engine.insert_toc_pages(&entries);
+
+        let content_bounds = engine.store.get_stroke_ref(content_key).unwrap().bounds();
+        let toc_pages = (content_bounds.mins[1] / page_size[1]).round();
+        assert!(toc_pages >= 2.0);
+        let toc_keys = engine
+            .store
+            .stroke_keys_unordered()
+            .into_iter()
+            .filter(|key| *key != content_key)
+            .collect::<Vec<_>>();
+        for bounds in engine.store.strokes_bounds(&toc_keys) {
+            assert!(!bounds.intersects(&content_bounds));
+        }
+        let expected_page = (toc_pages as usize + 1).to_string();
+        let texts = text_strokes_texts(&engine);
+        assert_eq!(texts.last().unwrap().1, expected_page);
+    }
+
+    #[cfg(feature = "goodnotes")]
+    #[test]
+    fn goodnotes_pages_are_sized_and_placed_like_pdf_pages() {
+        use crate::fileformats::goodnotesformat::tests::{archive_bytes, png_bytes, INK_SVG};
+
+        let png = png_bytes(100, 200);
+        let bytes = archive_bytes(&[
+            ("pages/1.png", &png),
+            ("pages/2.svg", INK_SVG.as_bytes()),
+            ("pages/3.svg", b"<svg broken"),
+        ]);
+        let engine = Engine::default();
+        let page_width = engine
+            .import_prefs
+            .pdf_import_prefs
+            .page_width(&engine.document.format);
+
+        let (strokes, skipped) = futures::executor::block_on(
+            engine.generate_goodnotes_pages_from_bytes(bytes, na::vector![0.0, 0.0]),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(matches!(strokes[0].0, Stroke::BitmapImage(_)));
+        assert!(matches!(strokes[1].0, Stroke::VectorImage(_)));
+        assert!(strokes
+            .iter()
+            .all(|(_, layer)| *layer == Some(StrokeLayer::Document)));
+        let first = strokes[0].0.bounds();
+        let second = strokes[1].0.bounds();
+        // Sized to the page width, keeping the aspect ratio
+        assert_relative_eq!(first.extents(), na::vector![page_width, page_width * 2.0]);
+        assert_relative_eq!(second.extents(), na::vector![page_width, page_width * 0.5]);
+        assert!(second.mins[1] >= first.maxs[1]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].path, "pages/3.svg");
+    }
+}
@@ -1,21 +1,29 @@
 // Modules
 pub mod export;
 pub mod import;
+#[cfg(feature = "ocr")]
+pub mod ocr;
 pub mod rendering;
 pub mod snapshot;
 pub mod strokecontent;
 pub mod visual_debug;
 
 // Re-exports
-pub use export::ExportPrefs;
+pub use export::{
+    ExportBackgroundImage, ExportHeaderFooter, ExportLegend, ExportOptions, ExportPageBadge,
+    ExportPrefs, OnStrokeError,
+};
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::StreamExt;
 pub use import::ImportPrefs;
-pub use snapshot::EngineSnapshot;
+#[cfg(feature = "ocr")]
+pub use ocr::ExportOcr;
+pub use snapshot::{EngineSnapshot, RnoteLoadDiagnostics};
 pub use strokecontent::StrokeContent;
 
 // Imports
-use crate::document::Layout;
+use crate::document::format::Orientation;
+use crate::document::{Format, Layout};
 use crate::pens::{Pen, PenStyle};
 use crate::pens::{PenMode, PensConfig};
 use crate::store::render_comp::{self, RenderCompState};
@@ -141,7 +149,7 @@ pub enum EngineTask {
 }
 
 /// The engine configuration. Used when loading/saving the current configuration from/into persistent application settings.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(default, rename = "engine_config")]
 pub struct EngineConfig {
     #[serde(rename = "document")]
@@ -158,6 +166,23 @@ pub struct EngineConfig {
     pen_sounds: bool,
     #[serde(rename = "optimize_epd")]
     optimize_epd: bool,
+    #[serde(rename = "render_overscan")]
+    render_overscan: f64,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            document: Document::default(),
+            pens_config: PensConfig::default(),
+            penholder: PenHolder::default(),
+            import_prefs: ImportPrefs::default(),
+            export_prefs: ExportPrefs::default(),
+            pen_sounds: false,
+            optimize_epd: false,
+            render_overscan: render::OVERSCAN_DEFAULT,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -343,6 +368,21 @@ impl Engine {
         self.optimize_epd = optimize_epd
     }
 
+    /// The margin in pixels by which partially rendered strokes extend beyond the viewport.
+    pub fn render_overscan(&self) -> f64 {
+        self.store.render_overscan()
+    }
+
+    /// Set the render overscan.
+    ///
+    /// A larger margin means strokes are rerendered less often while moving the view, at the cost of memory.
+    pub fn set_render_overscan(&mut self, overscan: f64) -> WidgetFlags {
+        let mut widget_flags = WidgetFlags::default();
+        self.store.set_render_overscan(overscan);
+        widget_flags |= self.update_rendering_current_viewport();
+        widget_flags
+    }
+
     pub fn visual_debug(&self) -> bool {
         self.visual_debug
     }
@@ -625,6 +665,13 @@ impl Engine {
         )
     }
 
+    /// The union of the bounds of all strokes in the document, skipping strokes with bounds that are not finite.
+    ///
+    /// Returns `None` when there is no such stroke.
+    pub fn strokes_bounds_union(&self) -> Option<Aabb> {
+        self.store.strokes_bounds_union()
+    }
+
     /// First zoom temporarily and then permanently after a timeout.
     ///
     /// Repeated calls to this function reset the timeout.
@@ -774,6 +821,57 @@ impl Engine {
             .cut_clipboard_content(&mut engine_view_mut!(self))
     }
 
+    /// The format of the document pages.
+    ///
+    /// Width and height are in pixels at the dpi of the format.
+    pub fn doc_format(&self) -> Format {
+        self.document.format
+    }
+
+    /// Replaces the format of the document pages and resizes the document to the new page boundaries.
+    ///
+    /// Background rendering then needs to be updated.
+    pub fn set_doc_format(&mut self, format: Format) -> WidgetFlags {
+        self.document.format = format;
+        self.doc_resize_to_fit_content()
+    }
+
+    /// Sets the size of the document pages and resizes the document to the new page boundaries.
+    ///
+    /// Width and height are in pixels at the dpi of the format, use [MeasureUnit::convert_measurement] for other units.
+    /// They are clamped to the format limits and the orientation follows from them.
+    /// Fails when they are not finite or not positive.
+    ///
+    /// Background rendering then needs to be updated.
+    ///
+    /// [MeasureUnit::convert_measurement]: crate::document::format::MeasureUnit::convert_measurement
+    pub fn set_doc_format_size(&mut self, width: f64, height: f64) -> anyhow::Result<WidgetFlags> {
+        if !(width.is_finite() && width > 0.0 && height.is_finite() && height > 0.0) {
+            return Err(anyhow::anyhow!(
+                "Setting the document format size failed, invalid size {width}x{height}."
+            ));
+        }
+        let mut format = self.document.format;
+        format.set_width(width);
+        format.set_height(height);
+        Ok(self.set_doc_format(format))
+    }
+
+    /// Sets the orientation of the document pages, swapping width and height when it changes,
+    /// and resizes the document to the new page boundaries.
+    ///
+    /// Background rendering then needs to be updated.
+    pub fn set_doc_format_orientation(&mut self, orientation: Orientation) -> WidgetFlags {
+        let mut format = self.document.format;
+        format.set_orientation(orientation);
+        self.set_doc_format(format)
+    }
+
+    /// The layout of the document.
+    pub fn doc_layout(&self) -> Layout {
+        self.document.layout
+    }
+
     pub fn set_doc_layout(&mut self, layout: Layout) -> WidgetFlags {
         if self.document.layout != layout {
             self.document.layout = layout;
@@ -935,3 +1033,100 @@ impl Engine {
             .handle_animation_frame(&mut engine_view_mut!(self), optimize_epd);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Engine;
+    use crate::document::format::Orientation;
+    use crate::document::{Format, Layout};
+    use crate::strokes::{ShapeStroke, Stroke};
+    use rnote_compose::shapes::{Rectangle, Shape};
+    use rnote_compose::Style;
+
+    /// An engine with the fixed size layout and a rectangle that reaches down to about y = 1500.
+    fn engine_w_content() -> Engine {
+        let mut engine = Engine::default();
+        engine.document.layout = Layout::FixedSize;
+        engine.store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(
+                    na::vector![10.0, 1400.0],
+                    na::vector![100.0, 1500.0],
+                )),
+                Style::default(),
+            )),
+            None,
+        );
+        engine
+    }
+
+    #[test]
+    fn set_doc_format_size_resizes_the_document() {
+        let mut engine = engine_w_content();
+        let _ = engine.set_doc_format_size(600.0, 400.0).unwrap();
+        assert_eq!(engine.document.format.size(), na::vector![600.0, 400.0]);
+        assert_eq!(engine.document.format.orientation(), Orientation::Landscape);
+        // The document holds the pages that the content reaches into
+        assert_eq!(engine.document.width, 600.0);
+        assert_eq!(engine.document.height, 1600.0);
+
+        // Sizes are clamped to the format limits
+        let _ = engine.set_doc_format_size(50000.0, 400.0).unwrap();
+        assert_eq!(engine.document.format.width(), Format::WIDTH_MAX);
+    }
+
+    #[test]
+    fn set_doc_format_size_rejects_invalid_sizes() {
+        let mut engine = engine_w_content();
+        let format_size = engine.document.format.size();
+        for (width, height) in [
+            (0.0, 400.0),
+            (600.0, -1.0),
+            (f64::NAN, 400.0),
+            (600.0, f64::INFINITY),
+        ] {
+            assert!(
+                engine.set_doc_format_size(width, height).is_err(),
+                "{width}x{height}"
+            );
+        }
+        assert_eq!(engine.document.format.size(), format_size);
+    }
+
+    #[test]
+    fn set_doc_format_orientation_swaps_the_page_size() {
+        let mut engine = engine_w_content();
+        let format = engine.document.format;
+        assert_eq!(format.orientation(), Orientation::Portrait);
+
+        let _ = engine.set_doc_format_orientation(Orientation::Landscape);
+        assert_eq!(
+            engine.document.format.size(),
+            na::vector![format.height(), format.width()]
+        );
+        assert_eq!(engine.document.format.orientation(), Orientation::Landscape);
+        assert_eq!(engine.document.width, format.height());
+        assert_eq!(engine.document.height, format.width() * 2.0);
+
+        // Setting the current orientation keeps the size
+        let _ = engine.set_doc_format_orientation(Orientation::Landscape);
+        assert_eq!(
+            engine.document.format.size(),
+            na::vector![format.height(), format.width()]
+        );
+    }
+
+    #[test]
+    fn set_doc_format_replaces_the_format() {
+        let mut engine = engine_w_content();
+        let mut format = Format::default();
+        format.set_width(500.0);
+        format.set_height(1000.0);
+        format.set_dpi(300.0);
+        let _ = engine.set_doc_format(format);
+        assert_eq!(engine.document.format.size(), format.size());
+        assert_eq!(engine.document.format.dpi(), 300.0);
+        assert_eq!(engine.document.width, 500.0);
+        assert_eq!(engine.document.height, 2000.0);
+    }
+}
@@ -0,0 +1,191 @@
+//! Optical character recognition of the exported pages, to make Pdf exports of scanned documents searchable.
+//!
+//! The recognized words are placed behind the page content as invisible text,
+//! so that they can be searched and selected in Pdf viewers.
+
+// Imports
+use super::StrokeContent;
+use crate::render;
+use anyhow::Context;
+use p2d::bounding_volume::Aabb;
+use rnote_compose::shapes::Shapeable;
+
+/// Ocr settings for Pdf exports.
+#[derive(Debug, Clone)]
+pub struct ExportOcr {
+    /// The Tesseract language(s) of the text, e.g. "eng" or "deu+eng".
+    pub lang: String,
+}
+
+impl Default for ExportOcr {
+    fn default() -> Self {
+        Self {
+            lang: String::from(Self::LANG_DEFAULT),
+        }
+    }
+}
+
+/// A recognized word.
+#[derive(Debug, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    /// The bounds of the word in the coordinate space of the document.
+    pub bounds: Aabb,
+}
+
+impl ExportOcr {
+    pub const LANG_DEFAULT: &'static str = "eng";
+    /// The resolution the pages are rendered at for the recognition.
+    pub const DPI: f64 = 300.0;
+    /// Words the recognition is less confident about are dropped, in percent.
+    const CONFIDENCE_MIN: f64 = 30.0;
+    /// The alpha the text is drawn with. Fully transparent text would be skipped by cairo.
+    const TEXT_ALPHA: f64 = 0.01;
+
+    /// Creates the recognizer. Fails when the data for the language is not installed.
+    pub(crate) fn recognizer(&self) -> anyhow::Result<leptess::LepTess> {
+        leptess::LepTess::new(None, &self.lang).with_context(|| {
+            format!(
+                "Initializing the Ocr for language \"{}\" failed, is the language data installed?",
+                self.lang
+            )
+        })
+    }
+
+    /// Renders the page content and recognizes the words on it.
+    ///
+    /// Returns no words for pages without text.
+    pub(crate) fn recognize_page(
+        recognizer: &mut leptess::LepTess,
+        page_content: &StrokeContent,
+        draw_background: bool,
+        draw_pattern: bool,
+        document_dpi: f64,
+    ) -> anyhow::Result<Vec<OcrWord>> {
+        let Some(page_bounds) = page_content.bounds() else {
+            return Ok(vec![]);
+        };
+        let image_scale = Self::DPI / document_dpi;
+        let image = render::Image::gen_with_cairo(
+            |cairo_cx| {
+                // The recognition expects dark text on a light, opaque background
+                cairo_cx.set_source_rgb(1.0, 1.0, 1.0);
+                cairo_cx.paint()?;
+                page_content.draw_to_cairo(
+                    cairo_cx,
+                    draw_background,
+                    draw_pattern,
+                    false,
+                    0.0,
+                    image_scale,
+                )
+            },
+            page_bounds,
+            image_scale,
+        )?;
+        let image_bounds = image.rect.bounds();
+        let png = image.into_encoded_bytes(image::ImageFormat::Png, None)?;
+        recognizer
+            .set_image_from_mem(&png)
+            .context("Loading the rendered page for the Ocr failed.")?;
+        recognizer.set_source_resolution(Self::DPI.round() as i32);
+        let tsv = recognizer
+            .get_tsv_text(0)
+            .context("Recognizing the text of the page failed.")?;
+
+        Ok(parse_tsv_words(&tsv)
+            .map(|(text, [left, top, width, height])| OcrWord {
+                text,
+                bounds: Aabb::new(
+                    image_bounds.mins + na::vector![left, top] / image_scale,
+                    image_bounds.mins + na::vector![left + width, top + height] / image_scale,
+                ),
+            })
+            .collect())
+    }
+
+    /// Draws the words as invisible text, each stretched to its bounds.
+    ///
+    /// Must be drawn before the page content so that it is placed behind it.
+    pub(crate) fn draw_words_to_cairo(
+        cairo_cx: &cairo::Context,
+        words: &[OcrWord],
+    ) -> anyhow::Result<()> {
+        cairo_cx.save()?;
+        cairo_cx.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+        cairo_cx.set_source_rgba(0.0, 0.0, 0.0, Self::TEXT_ALPHA);
+        for word in words {
+            let extents = word.bounds.extents();
+            cairo_cx.set_font_size(extents[1]);
+            let text_extents = cairo_cx.text_extents(&word.text)?;
+            if text_extents.x_advance() <= 0.0 {
+                continue;
+            }
+            cairo_cx.save()?;
+            // The baseline of the text is placed near the bottom of the bounds, leaving room for descenders
+            cairo_cx.translate(word.bounds.mins[0], word.bounds.maxs[1] - extents[1] * 0.2);
+            cairo_cx.scale(extents[0] / text_extents.x_advance(), 1.0);
+            cairo_cx.move_to(0.0, 0.0);
+            cairo_cx.show_text(&word.text)?;
+            cairo_cx.restore()?;
+        }
+        cairo_cx.restore()?;
+        Ok(())
+    }
+}
+
+/// Parses the words with their pixel bounds [left, top, width, height] from Tesseract's Tsv output.
+fn parse_tsv_words(tsv: &str) -> impl Iterator<Item = (String, [f64; 4])> + '_ {
+    // Tsv columns: level, page_num, block_num, par_num, line_num, word_num, left, top, width, height, conf, text
+    const WORD_LEVEL: &str = "5";
+
+    tsv.lines().filter_map(|line| {
+        let columns = line.split('\t').collect::<Vec<&str>>();
+        if columns.len() < 12 || columns[0] != WORD_LEVEL {
+            return None;
+        }
+        let text = columns[11].trim();
+        let confidence = columns[10].parse::<f64>().ok()?;
+        if text.is_empty() || confidence < ExportOcr::CONFIDENCE_MIN {
+            return None;
+        }
+        let mut bounds = [0.0; 4];
+        for (bound, column) in bounds.iter_mut().zip(&columns[6..10]) {
+            *bound = column.parse::<f64>().ok()?;
+        }
+        Some((text.to_string(), bounds))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_tsv_words;
+
+    #[test]
+    fn parse_tsv_words_keeps_confident_words() {
+        let tsv = [
+            "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext",
+            "1\t1\t0\t0\t0\t0\t0\t0\t2480\t3508\t-1\t",
+            "4\t1\t1\t1\t1\t0\t100\t200\t400\t50\t-1\t",
+            "5\t1\t1\t1\t1\t1\t100\t200\t180\t50\t96.5\tHello",
+            "5\t1\t1\t1\t1\t2\t300\t202\t200\t48\t91\tworld! ",
+            // Not confident enough
+            "5\t1\t1\t1\t1\t3\t520\t200\t40\t50\t12.0\t~",
+            // Empty text and malformed bounds
+            "5\t1\t1\t1\t1\t4\t600\t200\t40\t50\t95\t ",
+            "5\t1\t1\t1\t1\t5\tleft\t200\t40\t50\t95\tword",
+            // Too few columns
+            "5\t1\t1\t1\t1\t6\t700\t200",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            parse_tsv_words(&tsv).collect::<Vec<(String, [f64; 4])>>(),
+            vec![
+                (String::from("Hello"), [100.0, 200.0, 180.0, 50.0]),
+                (String::from("world!"), [300.0, 202.0, 200.0, 48.0]),
+            ]
+        );
+        assert_eq!(parse_tsv_words("").count(), 0);
+    }
+}
@@ -1,12 +1,15 @@
 // Imports
 use crate::document::background;
+use crate::document::{Background, Format, Layout};
 use crate::engine::import::XoppImportPrefs;
-use crate::fileformats::{rnoteformat, xoppformat, FileFormatLoader};
+use crate::fileformats::rnoteformat::{self, RnoteFileMigration, RnotefileWrapper};
+use crate::fileformats::{xoppformat, FileFormatLoader};
 use crate::store::{ChronoComponent, StrokeKey};
 use crate::strokes::Stroke;
 use crate::{Camera, Document, Engine};
 use anyhow::Context;
 use futures::channel::oneshot;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use slotmap::{HopSlotMap, SecondaryMap};
 use std::sync::Arc;
@@ -41,17 +44,56 @@ impl Default for EngineSnapshot {
 }
 
 impl EngineSnapshot {
+    /// A snapshot of an empty document with the amount of pages in the format and with the background.
+    ///
+    /// The document has a fixed-size layout, so that the pages are kept when it is loaded.
+    pub fn new_w_pages(pages: u32, format: Format, background: Background) -> Self {
+        Self {
+            document: Document {
+                width: format.width(),
+                height: format.height() * f64::from(pages.max(1)),
+                format,
+                background,
+                layout: Layout::FixedSize,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
     /// Loads a snapshot from the bytes of a .rnote file.
     ///
     /// To import this snapshot into the current engine, use [`Engine::load_snapshot()`].
     pub async fn load_from_rnote_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
-        let (snapshot_sender, snapshot_receiver) = oneshot::channel::<anyhow::Result<Self>>();
+        Self::load_from_rnote_bytes_w_migration(bytes)
+            .await
+            .map(|(snapshot, _)| snapshot)
+    }
+
+    /// Loads a snapshot from the bytes of a .rnote file,
+    /// migrating files of older format versions step by step to the newest one.
+    ///
+    /// Returns the snapshot together with the applied migration steps.
+    pub async fn load_from_rnote_bytes_w_migration(
+        bytes: Vec<u8>,
+    ) -> anyhow::Result<(Self, RnoteFileMigration)> {
+        let (snapshot_sender, snapshot_receiver) =
+            oneshot::channel::<anyhow::Result<(Self, RnoteFileMigration)>>();
 
         rayon::spawn(move || {
-            let result = || -> anyhow::Result<Self> {
-                let rnote_file = rnoteformat::RnoteFile::load_from_bytes(&bytes)
-                    .context("loading RnoteFile from bytes failed.")?;
-                Ok(ijson::from_value(&rnote_file.engine_snapshot)?)
+            let result = || -> anyhow::Result<(Self, RnoteFileMigration)> {
+                let (rnote_file, migration) =
+                    rnoteformat::RnoteFile::load_from_bytes_w_migration(&bytes)
+                        .context("loading RnoteFile from bytes failed.")?;
+                let snapshot = ijson::from_value(&rnote_file.engine_snapshot).map_err(|e| {
+                    let location = locate_snapshot_deser_failure(&rnote_file.engine_snapshot)
+                        .map(|(location, _)| location)
+                        .unwrap_or_else(|| String::from("engine_snapshot"));
+                    anyhow::Error::from(e).context(format!(
+                        "deserializing engine snapshot failed at `{location}`."
+                    ))
+                })?;
+                Ok((snapshot, migration))
             };
 
             if let Err(_data) = snapshot_sender.send(result()) {
@@ -63,6 +105,7 @@ impl EngineSnapshot {
 
         snapshot_receiver.await?
     }
+
     /// Loads from the bytes of a Xournal++ .xopp file.
     ///
     /// To import this snapshot into the current engine, use [`Engine::load_snapshot()`].
@@ -197,3 +240,143 @@ impl EngineSnapshot {
         snapshot_receiver.await?
     }
 }
+
+/// Diagnostics about loading a .rnote file, collected step by step to pinpoint where loading fails.
+///
+/// Intended to help with triaging reports about corrupted files.
+#[derive(Debug, Clone, Default)]
+pub struct RnoteLoadDiagnostics {
+    /// The size of the (compressed) file in bytes.
+    pub compressed_size: usize,
+    /// The size of the decompressed data in bytes.
+    pub decompressed_size: Option<usize>,
+    /// The detected file format version.
+    pub format_version: Option<semver::Version>,
+    /// The amount of strokes, when the stroke components could be read.
+    pub strokes_amount: Option<usize>,
+    /// Where loading failed and the error message. None if the file loads successfully.
+    ///
+    /// The location is either a loading stage or a path into the engine snapshot, e.g. `stroke_components[12]`.
+    pub failure: Option<(String, String)>,
+}
+
+impl RnoteLoadDiagnostics {
+    /// Loads the bytes of a .rnote file step by step and collects diagnostics along the way.
+    pub fn from_rnote_bytes(bytes: &[u8]) -> Self {
+        let mut diagnostics = Self {
+            compressed_size: bytes.len(),
+            ..Default::default()
+        };
+        let decompressed = match rnoteformat::decompress_from_gzip(bytes) {
+            Ok(decompressed) => decompressed,
+            Err(e) => {
+                diagnostics.failure = Some((String::from("decompressing"), format!("{e:?}")));
+                return diagnostics;
+            }
+        };
+        diagnostics.decompressed_size = Some(decompressed.len());
+        let wrapper = match serde_json::from_slice::<RnotefileWrapper>(&decompressed) {
+            Ok(wrapper) => wrapper,
+            Err(e) => {
+                diagnostics.failure = Some((String::from("file wrapper"), format!("{e:?}")));
+                return diagnostics;
+            }
+        };
+        diagnostics.format_version = Some(wrapper.version.clone());
+        let rnote_file = match rnoteformat::RnoteFile::load_from_wrapper(wrapper) {
+            Ok(rnote_file) => rnote_file,
+            Err(e) => {
+                diagnostics.failure = Some((
+                    String::from("upgrading to the newest format version"),
+                    format!("{e:?}"),
+                ));
+                return diagnostics;
+            }
+        };
+        diagnostics.strokes_amount =
+            snapshot_field(&rnote_file.engine_snapshot, "stroke_components")
+                .and_then(|components| components.as_array())
+                .map(|slots| {
+                    slots
+                        .iter()
+                        .filter(|slot| slot_value(slot).is_some())
+                        .count()
+                });
+        if let Err(e) = ijson::from_value::<EngineSnapshot>(&rnote_file.engine_snapshot) {
+            diagnostics.failure = Some(
+                locate_snapshot_deser_failure(&rnote_file.engine_snapshot)
+                    .unwrap_or_else(|| (String::from("engine_snapshot"), format!("{e:?}"))),
+            );
+        }
+        diagnostics
+    }
+}
+
+fn snapshot_field<'a>(snapshot: &'a ijson::IValue, field: &str) -> Option<&'a ijson::IValue> {
+    snapshot.as_object().and_then(|object| object.get(field))
+}
+
+/// The value of a serialized slotmap slot, None if the slot is vacant.
+fn slot_value(slot: &ijson::IValue) -> Option<&ijson::IValue> {
+    slot.as_object()
+        .and_then(|slot| slot.get("value"))
+        .filter(|value| !value.is_null())
+}
+
+/// Deserializes the fields of the engine snapshot one by one to find the one that fails.
+///
+/// For the slotmaps the index of the failing slot is reported.
+/// Returns the location and the error message.
+fn locate_snapshot_deser_failure(snapshot: &ijson::IValue) -> Option<(String, String)> {
+    fn check<T: DeserializeOwned>(
+        location: &str,
+        value: &ijson::IValue,
+    ) -> Option<(String, String)> {
+        ijson::from_value::<T>(value)
+            .err()
+            .map(|e| (location.to_string(), format!("{e:?}")))
+    }
+    fn check_slots<T: DeserializeOwned>(
+        field: &str,
+        value: &ijson::IValue,
+    ) -> Option<(String, String)> {
+        let Some(slots) = value.as_array() else {
+            return Some((
+                field.to_string(),
+                String::from("expected the slots to be a JSON array."),
+            ));
+        };
+        slots.iter().enumerate().find_map(|(i, slot)| {
+            slot_value(slot).and_then(|value| check::<T>(&format!("{field}[{i}]"), value))
+        })
+    }
+
+    if snapshot.as_object().is_none() {
+        return Some((
+            String::from("engine_snapshot"),
+            String::from("engine snapshot is not a JSON object."),
+        ));
+    }
+    if let Some(failure) =
+        snapshot_field(snapshot, "document").and_then(|value| check::<Document>("document", value))
+    {
+        return Some(failure);
+    }
+    if let Some(failure) =
+        snapshot_field(snapshot, "camera").and_then(|value| check::<Camera>("camera", value))
+    {
+        return Some(failure);
+    }
+    if let Some(failure) = snapshot_field(snapshot, "stroke_components")
+        .and_then(|value| check_slots::<Stroke>("stroke_components", value))
+    {
+        return Some(failure);
+    }
+    if let Some(failure) = snapshot_field(snapshot, "chrono_components")
+        .and_then(|value| check_slots::<ChronoComponent>("chrono_components", value))
+    {
+        return Some(failure);
+    }
+    snapshot_field(snapshot, "chrono_counter")
+        .and_then(|value| check::<u32>("chrono_counter", value))
+}
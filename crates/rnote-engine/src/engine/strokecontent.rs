@@ -1,10 +1,15 @@
 // Imports
 use crate::document::Background;
+use crate::engine::export::{ExportCenterContent, OnStrokeError, StrokeSmoothing};
+use crate::engine::ExportBackgroundImage;
 use crate::render::Svg;
 use crate::strokes::Stroke;
 use crate::Drawable;
+use anyhow::Context;
 use p2d::bounding_volume::{Aabb, BoundingVolume};
 use rnote_compose::shapes::Shapeable;
+use rnote_compose::transform::Transformable;
+use rnote_compose::Color;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::warn;
@@ -21,19 +26,42 @@ pub struct StrokeContent {
     pub bounds: Option<Aabb>,
     #[serde(rename = "background")]
     pub background: Option<Background>,
+    /// Drawn between the background color and the pattern. Only used when exporting.
+    #[serde(skip)]
+    pub background_image: Option<ExportBackgroundImage>,
+    /// What happens when a stroke fails to draw. Only used when exporting.
+    #[serde(skip)]
+    pub on_stroke_error: OnStrokeError,
+    /// The ids of the strokes in the same order, see [crate::store::StrokeStore::stroke_id].
+    /// Used to refer to the strokes that fail to draw. Only used when exporting.
+    #[serde(skip)]
+    pub stroke_ids: Vec<u64>,
 }
 
 impl StrokeContent {
     pub const MIME_TYPE: &'static str = "application/rnote-stroke-content";
     pub const CLIPBOARD_EXPORT_MARGIN: f64 = 6.0;
+    /// The amount of strokes that are drawn in one chunk by [StrokeContent::draw_to_cairo_chunked].
+    pub const DRAW_CHUNK_SIZE: usize = 1000;
+    /// The size of the marker that is drawn for strokes that failed to draw, see [OnStrokeError::Placeholder].
+    const PLACEHOLDER_SIZE: f64 = 12.0;
+    const PLACEHOLDER_COLOR: Color = Color::RED;
 
     pub fn with_bounds(mut self, bounds: Option<Aabb>) -> Self {
         self.bounds = bounds;
         self
     }
 
+    /// Sets the strokes, clearing the stroke ids.
     pub fn with_strokes(mut self, strokes: Vec<Arc<Stroke>>) -> Self {
         self.strokes = strokes;
+        self.stroke_ids.clear();
+        self
+    }
+
+    /// Sets the ids of the strokes, which must be in the same order as the strokes.
+    pub fn with_stroke_ids(mut self, stroke_ids: Vec<u64>) -> Self {
+        self.stroke_ids = stroke_ids;
         self
     }
 
@@ -42,6 +70,116 @@ impl StrokeContent {
         self
     }
 
+    pub fn with_background_image(
+        mut self,
+        background_image: Option<ExportBackgroundImage>,
+    ) -> Self {
+        self.background_image = background_image;
+        self
+    }
+
+    pub fn with_on_stroke_error(mut self, on_stroke_error: OnStrokeError) -> Self {
+        self.on_stroke_error = on_stroke_error;
+        self
+    }
+
+    /// Removes the strokes, but keeps the bounds they were covering.
+    ///
+    /// Used to export the background separately.
+    pub fn without_strokes(mut self) -> Self {
+        self.bounds = self.bounds();
+        self.strokes.clear();
+        self.stroke_ids.clear();
+        self
+    }
+
+    /// Sets the colors of all strokes to the given color, see [Stroke::set_to_color].
+    ///
+    /// Used to export with a uniform ink color without modifying the document.
+    pub fn with_ink_color(mut self, color: Color) -> Self {
+        self.strokes = self
+            .strokes
+            .into_iter()
+            .map(|stroke| {
+                let mut stroke = Arc::unwrap_or_clone(stroke);
+                stroke.set_to_color(color);
+                Arc::new(stroke)
+            })
+            .collect();
+        self
+    }
+
+    /// Widen the strokes so that they are drawn with at least the min width.
+    pub fn with_min_line_width(mut self, min_width: f64) -> Self {
+        if min_width <= 0.0 {
+            return self;
+        }
+        self.strokes = self
+            .strokes
+            .into_iter()
+            .map(|stroke| {
+                let mut stroke = Arc::unwrap_or_clone(stroke);
+                stroke.set_min_width(min_width);
+                Arc::new(stroke)
+            })
+            .collect();
+        self
+    }
+
+    /// Smooth the paths of the strokes, see [Stroke::smooth_path].
+    ///
+    /// Used to export jagged strokes with cleaner curves without modifying the document.
+    pub fn with_stroke_smoothing(mut self, smoothing: StrokeSmoothing) -> Self {
+        if smoothing == StrokeSmoothing::None {
+            return self;
+        }
+        self.strokes = self
+            .strokes
+            .into_iter()
+            .map(|stroke| {
+                let mut smoothed = Stroke::clone(&stroke);
+                if smoothed.smooth_path() {
+                    Arc::new(smoothed)
+                } else {
+                    stroke
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Moves the strokes so that their bounds are centered in the bounds of the content along the given axes.
+    ///
+    /// Used to export with the content centered on the page without modifying the document.
+    /// Has no effect when the content has no explicit bounds or no strokes.
+    pub fn with_centered_content(mut self, center: ExportCenterContent) -> Self {
+        let Some(page_bounds) = self.bounds else {
+            return self;
+        };
+        let Some(strokes_bounds) = self
+            .strokes
+            .iter()
+            .map(|stroke| stroke.bounds())
+            .reduce(|acc, bounds| acc.merged(&bounds))
+        else {
+            return self;
+        };
+        let offset = center.offset(strokes_bounds, page_bounds);
+        if offset == na::Vector2::zeros() {
+            return self;
+        }
+        self.strokes = self
+            .strokes
+            .into_iter()
+            .map(|stroke| {
+                let mut stroke = Arc::unwrap_or_clone(stroke);
+                stroke.translate(offset);
+                Arc::new(stroke)
+            })
+            .collect();
+        self
+    }
+
     pub fn bounds(&self) -> Option<Aabb> {
         if self.bounds.is_some() {
             return self.bounds;
@@ -72,6 +210,29 @@ impl StrokeContent {
         draw_pattern: bool,
         optimize_printing: bool,
         margin: f64,
+    ) -> anyhow::Result<Option<Svg>> {
+        self.gen_svg_w_image_scale(
+            draw_background,
+            draw_pattern,
+            optimize_printing,
+            margin,
+            1.0,
+        )
+    }
+
+    /// Generate a Svg from the content, rendering strokes that are converted to bitmap images with the given image
+    /// scale.
+    ///
+    /// Moves the bounds to mins: [0.0, 0.0], maxs: extents.
+    ///
+    /// Returns Ok(None) if there is no content stored.
+    pub fn gen_svg_w_image_scale(
+        &self,
+        draw_background: bool,
+        draw_pattern: bool,
+        optimize_printing: bool,
+        margin: f64,
+        image_scale: f64,
     ) -> anyhow::Result<Option<Svg>> {
         let Some(bounds_loosened) = self.bounds().map(|b| b.loosened(margin)) else {
             return Ok(None);
@@ -84,7 +245,7 @@ impl StrokeContent {
                     draw_pattern,
                     optimize_printing,
                     margin,
-                    1.0,
+                    image_scale,
                 )
             },
             bounds_loosened,
@@ -104,6 +265,35 @@ impl StrokeContent {
         optimize_printing: bool,
         margin: f64,
         image_scale: f64,
+    ) -> anyhow::Result<()> {
+        self.draw_to_cairo_chunked(
+            cairo_cx,
+            draw_background,
+            draw_pattern,
+            optimize_printing,
+            margin,
+            image_scale,
+            usize::MAX,
+            |_| {},
+        )
+    }
+
+    /// Draw the content, with the strokes drawn in chunks of the given size.
+    ///
+    /// After every chunk `on_chunk_drawn` is called with the amount of strokes drawn so far.
+    /// Between the chunks the current thread yields to other pending work of the thread pool,
+    /// so that huge amounts of strokes don't stall it. The output is the same as when drawing in one go.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_to_cairo_chunked(
+        &self,
+        cairo_cx: &cairo::Context,
+        draw_background: bool,
+        draw_pattern: bool,
+        optimize_printing: bool,
+        margin: f64,
+        image_scale: f64,
+        chunk_size: usize,
+        mut on_chunk_drawn: impl FnMut(usize),
     ) -> anyhow::Result<()> {
         let Some(bounds) = self.bounds() else {
             return Ok(());
@@ -124,10 +314,21 @@ impl StrokeContent {
                 background.draw_to_cairo(
                     cairo_cx,
                     bounds_loosened,
-                    draw_pattern,
+                    draw_pattern && self.background_image.is_none(),
                     optimize_printing,
                 )?;
             }
+            if let Some(background_image) = &self.background_image {
+                background_image.draw_to_cairo(cairo_cx, bounds)?;
+
+                if let (true, Some(background)) = (draw_pattern, &self.background) {
+                    background.draw_pattern_to_cairo(
+                        cairo_cx,
+                        bounds_loosened,
+                        optimize_printing,
+                    )?;
+                }
+            }
         }
 
         cairo_cx.restore()?;
@@ -140,38 +341,249 @@ impl StrokeContent {
         );
         cairo_cx.clip();
 
-        let image_bounds = self
-            .strokes
+        let image_bounds = self.image_bounds();
+
+        let mut strokes_drawn = 0;
+        for (i, chunk) in self.strokes.chunks(chunk_size.max(1)).enumerate() {
+            if i > 0 {
+                // Lets other pending work run, only has an effect on threads of the rayon thread pool
+                let _ = rayon::yield_now();
+            }
+            for (j, stroke) in chunk.iter().enumerate() {
+                self.draw_stroke_handling_error(
+                    strokes_drawn + j,
+                    stroke,
+                    cairo_cx,
+                    optimize_printing,
+                    &image_bounds,
+                    image_scale,
+                )?;
+            }
+            strokes_drawn += chunk.len();
+            on_chunk_drawn(strokes_drawn);
+        }
+
+        cairo_cx.restore()?;
+
+        Ok(())
+    }
+
+    /// Generate a Svg from the content, with every stroke in a group that has the stroke's
+    /// [Css classes](Stroke::css_classes). The background is in a group with the class "rnote-background".
+    /// Strokes are generated one by one, so this is slower than [Self::gen_svg].
+    ///
+    /// Moves the bounds to mins: [0.0, 0.0], maxs: extents.
+    ///
+    /// Returns Ok(None) if there is no content stored.
+    pub fn gen_svg_w_stroke_classes(
+        &self,
+        draw_background: bool,
+        draw_pattern: bool,
+        optimize_printing: bool,
+        margin: f64,
+    ) -> anyhow::Result<Option<Svg>> {
+        let Some(bounds) = self.bounds() else {
+            return Ok(None);
+        };
+        let bounds_loosened = bounds.loosened(margin);
+        let image_bounds = self.image_bounds();
+        // Every stroke is rendered into its own Svg, the classes would not survive the simplification.
+        let gen_classed_svg = |draw_func: &dyn Fn(&cairo::Context) -> anyhow::Result<()>,
+                               classes: String|
+         -> anyhow::Result<String> {
+            let mut svg = Svg::gen_with_cairo(draw_func, bounds_loosened)?;
+            svg.simplify()?;
+            let group = svg::node::element::Group::new()
+                .set("class", classes)
+                .add(svg::node::Blob::new(svg.svg_data));
+            rnote_compose::utils::svg_node_to_string(&group)
+        };
+
+        let mut svg_data = Vec::with_capacity(self.strokes.len() + 1);
+        if draw_background {
+            let background = self.clone().with_strokes(vec![]).with_bounds(Some(bounds));
+            svg_data.push(gen_classed_svg(
+                &|cairo_cx| {
+                    background.draw_to_cairo(
+                        cairo_cx,
+                        true,
+                        draw_pattern,
+                        optimize_printing,
+                        margin,
+                        1.0,
+                    )
+                },
+                String::from("rnote-background"),
+            )?);
+        }
+        for (i, stroke) in self.strokes.iter().enumerate() {
+            svg_data.push(gen_classed_svg(
+                &|cairo_cx| {
+                    cairo_cx.rectangle(
+                        bounds_loosened.mins[0],
+                        bounds_loosened.mins[1],
+                        bounds_loosened.extents()[0],
+                        bounds_loosened.extents()[1],
+                    );
+                    cairo_cx.clip();
+                    self.draw_stroke_handling_error(
+                        i,
+                        stroke,
+                        cairo_cx,
+                        optimize_printing,
+                        &image_bounds,
+                        1.0,
+                    )
+                },
+                stroke.css_classes(),
+            )?);
+        }
+
+        Ok(Some(Svg {
+            svg_data: svg_data.join("\n"),
+            bounds: Aabb::new(na::point![0.0, 0.0], bounds_loosened.extents().into()),
+        }))
+    }
+
+    /// The bounds of the images. Strokes on top of them are not darkened when optimizing for printing.
+    fn image_bounds(&self) -> Vec<Aabb> {
+        self.strokes
             .iter()
             .filter_map(|stroke| match stroke.as_ref() {
                 Stroke::BitmapImage(image) => Some(image.rectangle.bounds()),
                 Stroke::VectorImage(image) => Some(image.rectangle.bounds()),
                 _ => None,
             })
-            .collect::<Vec<Aabb>>();
-
-        for stroke in self.strokes.iter() {
-            let stroke_bounds = stroke.bounds();
-
-            if optimize_printing
-                && image_bounds
-                    .iter()
-                    .all(|bounds| !bounds.contains(&stroke_bounds))
-            {
-                // Using the stroke's bounds instead of hitboxes works for inclusion.
-                // If this is changed to intersection, all hitboxes must be checked individually.
+            .collect::<Vec<Aabb>>()
+    }
 
-                let mut darkest_color_stroke = stroke.as_ref().clone();
-                darkest_color_stroke.set_to_darkest_color();
+    /// Describes the stroke at the given index for the log, with its id when it is known.
+    fn stroke_description(&self, index: usize) -> String {
+        match self.stroke_ids.get(index) {
+            Some(id) => format!("stroke with id {id}"),
+            None => format!("stroke at index {index}"),
+        }
+    }
 
-                darkest_color_stroke.draw_to_cairo(cairo_cx, image_scale)?;
-            } else {
-                stroke.draw_to_cairo(cairo_cx, image_scale)?;
+    /// Draws the stroke at the given index, handling the error when it fails according to
+    /// [StrokeContent::on_stroke_error].
+    ///
+    /// Unless the export fails anyway, the stroke is drawn directly onto the target through a separate context,
+    /// so that a stroke failing halfway doesn't leave an unbalanced state of the context behind.
+    /// Strokes mostly fail before drawing anything (e.g. when decoding images), what was drawn before the failure is kept.
+    fn draw_stroke_handling_error(
+        &self,
+        index: usize,
+        stroke: &Stroke,
+        cairo_cx: &cairo::Context,
+        optimize_printing: bool,
+        image_bounds: &[Aabb],
+        image_scale: f64,
+    ) -> anyhow::Result<()> {
+        if self.on_stroke_error == OnStrokeError::Fail {
+            return Self::draw_stroke_to_cairo(
+                stroke,
+                cairo_cx,
+                optimize_printing,
+                image_bounds,
+                image_scale,
+            )
+            .with_context(|| format!("Drawing {} failed.", self.stroke_description(index)));
+        }
+        let stroke_cx = cairo::Context::new(cairo_cx.group_target())?;
+        stroke_cx.set_matrix(cairo_cx.matrix());
+        match cairo_cx.copy_clip_rectangle_list() {
+            Ok(clip_rectangles) => {
+                for rectangle in clip_rectangles.iter() {
+                    stroke_cx.rectangle(
+                        rectangle.x(),
+                        rectangle.y(),
+                        rectangle.width(),
+                        rectangle.height(),
+                    );
+                }
+                stroke_cx.clip();
+            }
+            Err(_) => {
+                // The clip can't be represented by rectangles, falling back to its extents
+                let (x1, y1, x2, y2) = cairo_cx.clip_extents()?;
+                stroke_cx.rectangle(x1, y1, x2 - x1, y2 - y1);
+                stroke_cx.clip();
             }
         }
+        match Self::draw_stroke_to_cairo(
+            stroke,
+            &stroke_cx,
+            optimize_printing,
+            image_bounds,
+            image_scale,
+        ) {
+            Ok(()) => {}
+            Err(e) if self.on_stroke_error == OnStrokeError::Skip => {
+                warn!(
+                    "Skipping {} that failed to draw, Err: {e:?}",
+                    self.stroke_description(index)
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "Drawing placeholder for {} that failed to draw, Err: {e:?}",
+                    self.stroke_description(index)
+                );
+                Self::draw_stroke_placeholder_to_cairo(stroke, cairo_cx)?;
+            }
+        }
+        Ok(())
+    }
 
+    /// Draws a crossed out square at the center of the stroke bounds.
+    fn draw_stroke_placeholder_to_cairo(
+        stroke: &Stroke,
+        cairo_cx: &cairo::Context,
+    ) -> anyhow::Result<()> {
+        let center = stroke.bounds().center();
+        if !center.coords.iter().all(|c| c.is_finite()) {
+            return Ok(());
+        }
+        let half_size = Self::PLACEHOLDER_SIZE * 0.5;
+        let (x, y) = (center[0] - half_size, center[1] - half_size);
+        let color = Self::PLACEHOLDER_COLOR;
+        cairo_cx.save()?;
+        cairo_cx.set_source_rgba(color.r, color.g, color.b, color.a);
+        cairo_cx.set_line_width(Self::PLACEHOLDER_SIZE * 0.15);
+        cairo_cx.rectangle(x, y, Self::PLACEHOLDER_SIZE, Self::PLACEHOLDER_SIZE);
+        cairo_cx.move_to(x, y);
+        cairo_cx.line_to(x + Self::PLACEHOLDER_SIZE, y + Self::PLACEHOLDER_SIZE);
+        cairo_cx.move_to(x + Self::PLACEHOLDER_SIZE, y);
+        cairo_cx.line_to(x, y + Self::PLACEHOLDER_SIZE);
+        cairo_cx.stroke()?;
         cairo_cx.restore()?;
-
         Ok(())
     }
+
+    fn draw_stroke_to_cairo(
+        stroke: &Stroke,
+        cairo_cx: &cairo::Context,
+        optimize_printing: bool,
+        image_bounds: &[Aabb],
+        image_scale: f64,
+    ) -> anyhow::Result<()> {
+        let stroke_bounds = stroke.bounds();
+
+        if optimize_printing
+            && image_bounds
+                .iter()
+                .all(|bounds| !bounds.contains(&stroke_bounds))
+        {
+            // Using the stroke's bounds instead of hitboxes works for inclusion.
+            // If this is changed to intersection, all hitboxes must be checked individually.
+
+            let mut darkest_color_stroke = stroke.clone();
+            darkest_color_stroke.set_to_darkest_color();
+
+            darkest_color_stroke.draw_to_cairo(cairo_cx, image_scale)
+        } else {
+            stroke.draw_to_cairo(cairo_cx, image_scale)
+        }
+    }
 }
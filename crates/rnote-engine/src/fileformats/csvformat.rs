@@ -0,0 +1,104 @@
+//! Loading numeric data from `.csv` files.
+//!
+//! Only the first two columns are read, interpreted as x,y pairs.
+
+// Imports
+use super::FileFormatLoader;
+use anyhow::Context;
+use p2d::bounding_volume::Aabb;
+
+/// A Csv file with numeric x,y data points.
+#[derive(Debug, Clone, Default)]
+pub struct CsvFile {
+    /// The data points, in the order they appear in the file.
+    pub points: Vec<na::Vector2<f64>>,
+}
+
+impl FileFormatLoader for CsvFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let text = std::str::from_utf8(bytes).context("Csv file is not valid UTF-8.")?;
+        let mut points = Vec::new();
+        // The first row with content is allowed to be a header
+        let mut header_allowed = true;
+
+        for (line_i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line
+                .split([',', ';', '\t'])
+                .map(|c| c.trim().trim_matches('"'));
+            let (Some(x), Some(y)) = (columns.next(), columns.next()) else {
+                if header_allowed {
+                    header_allowed = false;
+                    continue;
+                }
+                return Err(anyhow::anyhow!(
+                    "Line {} of the Csv file has less than two columns.",
+                    line_i + 1
+                ));
+            };
+            match (x.parse::<f64>(), y.parse::<f64>()) {
+                (Ok(x), Ok(y)) if x.is_finite() && y.is_finite() => {
+                    points.push(na::vector![x, y]);
+                }
+                _ if header_allowed => {}
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Line {} of the Csv file does not contain a numeric x,y pair.",
+                        line_i + 1
+                    ))
+                }
+            }
+            header_allowed = false;
+        }
+
+        if points.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "The Csv file needs to contain at least two data points."
+            ));
+        }
+        Ok(Self { points })
+    }
+}
+
+impl CsvFile {
+    /// The bounds of the data points.
+    pub fn data_bounds(&self) -> Aabb {
+        let mut bounds = Aabb::new_invalid();
+        for p in self.points.iter() {
+            bounds.take_point((*p).into());
+        }
+        bounds
+    }
+
+    /// Maps a data point into the target bounds, stretching the data bounds to fill them.
+    ///
+    /// The y axis is flipped, so that larger y values end up higher up in the target.
+    pub fn map_point_to_bounds(
+        p: na::Vector2<f64>,
+        data_bounds: Aabb,
+        target_bounds: Aabb,
+    ) -> na::Vector2<f64> {
+        let data_extents = data_bounds.extents();
+        let target_extents = target_bounds.extents();
+        // Avoid dividing by zero for data with constant x or y values
+        let relative = na::vector![
+            if data_extents[0] > 0.0 {
+                (p[0] - data_bounds.mins[0]) / data_extents[0]
+            } else {
+                0.5
+            },
+            if data_extents[1] > 0.0 {
+                (p[1] - data_bounds.mins[1]) / data_extents[1]
+            } else {
+                0.5
+            }
+        ];
+        na::vector![
+            target_bounds.mins[0] + relative[0] * target_extents[0],
+            target_bounds.maxs[1] - relative[1] * target_extents[1]
+        ]
+    }
+}
@@ -0,0 +1,407 @@
+//! Loading the text content of `.epub` files.
+//!
+//! Only the text of the chapters is read, in the reading order of the spine.
+//! Styles, images and other media are ignored.
+
+// Imports
+use super::FileFormatLoader;
+use anyhow::Context;
+use roxmltree::Node;
+use std::io::{Cursor, Read};
+
+/// An Epub file.
+#[derive(Debug, Clone, Default)]
+pub struct EpubFile {
+    /// The title of the publication.
+    pub title: Option<String>,
+    /// The chapters in reading order.
+    pub chapters: Vec<EpubChapter>,
+}
+
+/// A chapter or section of an Epub file, a single document of the spine.
+#[derive(Debug, Clone, Default)]
+pub struct EpubChapter {
+    /// The text blocks, like paragraphs and headings.
+    pub blocks: Vec<EpubBlock>,
+}
+
+/// A block of text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpubBlock {
+    /// The text with collapsed whitespace. Line breaks are preserved as '\n'.
+    pub text: String,
+    /// If the block is a heading.
+    pub heading: bool,
+}
+
+impl FileFormatLoader for EpubFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut archive =
+            zip::ZipArchive::new(Cursor::new(bytes)).context("Epub file is not a Zip archive.")?;
+
+        let container = read_archive_file(&mut archive, "META-INF/container.xml")?;
+        let container = parse_xml(&container).context("Parsing the Epub container failed.")?;
+        let package_path = container
+            .descendants()
+            .find(|n| n.has_tag_name("rootfile"))
+            .and_then(|n| n.attribute("full-path"))
+            .ok_or_else(|| anyhow::anyhow!("Epub container does not reference a package."))?
+            .to_string();
+        // hrefs in the package are relative to its directory
+        let package_dir = match package_path.rsplit_once('/') {
+            Some((dir, _)) => format!("{dir}/"),
+            None => String::new(),
+        };
+
+        let package = read_archive_file(&mut archive, &package_path)?;
+        let package = parse_xml(&package).context("Parsing the Epub package failed.")?;
+        let title = package
+            .descendants()
+            .find(|n| n.has_tag_name("title"))
+            .and_then(|n| n.text())
+            .map(|t| collapse_whitespace(t).trim().to_string())
+            .filter(|t| !t.is_empty());
+        let manifest_href = |id: &str| {
+            package
+                .descendants()
+                .find(|n| n.has_tag_name("item") && n.attribute("id") == Some(id))
+                .and_then(|n| n.attribute("href"))
+        };
+        let chapter_paths = package
+            .descendants()
+            .filter(|n| n.has_tag_name("itemref"))
+            // Non-linear items are supplementary content that is not part of the reading order
+            .filter(|n| n.attribute("linear") != Some("no"))
+            .filter_map(|n| manifest_href(n.attribute("idref")?))
+            .map(|href| {
+                let href = href.split('#').next().unwrap_or(href);
+                resolve_path(&package_dir, &percent_decode(href))
+            })
+            .collect::<Vec<String>>();
+
+        let mut chapters = Vec::with_capacity(chapter_paths.len());
+        for chapter_path in chapter_paths {
+            // The Html entity is common, but not defined in Xml without loading the external Dtd
+            let xhtml = read_archive_file(&mut archive, &chapter_path)?.replace("&nbsp;", "&#160;");
+            let xhtml = parse_xml(&xhtml)
+                .with_context(|| format!("Parsing Epub chapter \"{chapter_path}\" failed."))?;
+            let mut blocks = Vec::new();
+            let mut current = EpubBlock::default();
+            if let Some(body) = xhtml.descendants().find(|n| n.has_tag_name("body")) {
+                collect_blocks(body, &mut blocks, &mut current);
+            }
+            push_block(&mut blocks, &mut current);
+            if !blocks.is_empty() {
+                chapters.push(EpubChapter { blocks });
+            }
+        }
+
+        Ok(Self { title, chapters })
+    }
+}
+
+impl EpubFile {
+    /// If the file is an Epub, recognized by the "mimetype" file that must be stored first in the archive.
+    pub fn is_epub(bytes: &[u8]) -> bool {
+        const MIMETYPE_ENTRY: &[u8] = b"PK\x03\x04";
+        bytes.starts_with(MIMETYPE_ENTRY)
+            && bytes.get(30..38) == Some(b"mimetype")
+            && bytes.get(38..58) == Some(b"application/epub+zip")
+    }
+}
+
+fn read_archive_file(
+    archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    path: &str,
+) -> anyhow::Result<String> {
+    let mut file = archive
+        .by_name(path)
+        .with_context(|| format!("Epub file does not contain \"{path}\"."))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .with_context(|| format!("Reading \"{path}\" from the Epub file failed."))?;
+    Ok(content)
+}
+
+fn parse_xml(text: &str) -> anyhow::Result<roxmltree::Document<'_>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    Ok(roxmltree::Document::parse_with_options(text, options)?)
+}
+
+/// Element names that start a new block of text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "li",
+    "p",
+    "pre",
+    "section",
+    "td",
+    "th",
+];
+/// Element names whose content is not displayed.
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "title"];
+
+fn collect_blocks(node: Node, blocks: &mut Vec<EpubBlock>, current: &mut EpubBlock) {
+    for child in node.children() {
+        if child.is_text() {
+            current
+                .text
+                .push_str(&collapse_whitespace(child.text().unwrap_or_default()));
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+        let name = child.tag_name().name();
+        if HIDDEN_ELEMENTS.contains(&name) {
+            continue;
+        }
+        if name == "br" {
+            current.text.push('\n');
+            continue;
+        }
+        if BLOCK_ELEMENTS.contains(&name) {
+            push_block(blocks, current);
+            current.heading = matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6");
+            collect_blocks(child, blocks, current);
+            push_block(blocks, current);
+        } else {
+            collect_blocks(child, blocks, current);
+        }
+    }
+}
+
+/// Pushes the current block when it has text and resets it.
+fn push_block(blocks: &mut Vec<EpubBlock>, current: &mut EpubBlock) {
+    let block = std::mem::take(current);
+    let text = block
+        .text
+        .lines()
+        .map(|line| line.trim())
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_string();
+    if !text.is_empty() {
+        blocks.push(EpubBlock {
+            text,
+            heading: block.heading,
+        });
+    }
+}
+
+/// Replaces every sequence of whitespace with a single space.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut prev_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !prev_whitespace {
+                collapsed.push(' ');
+            }
+            prev_whitespace = true;
+        } else {
+            collapsed.push(c);
+            prev_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Resolves the relative path in the archive against the directory, which is empty or ends with '/'.
+fn resolve_path(dir: &str, path: &str) -> String {
+    let mut components = dir
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<&str>>();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+    components.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// An Epub with the package in "OEBPS/" and the chapters as Xhtml bodies in "OEBPS/text/", in spine order.
+    pub(crate) fn epub_bytes(title: &str, chapter_bodies: &[&str]) -> Vec<u8> {
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("mimetype", stored).unwrap();
+        writer.write_all(b"application/epub+zip").unwrap();
+        writer.start_file("META-INF/container.xml", stored).unwrap();
+        writer
+            .write_all(
+                br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#,
+            )
+            .unwrap();
+
+        let items = (0..chapter_bodies.len())
+            .map(|i| {
+                format!(r#"<item id="c{i}" href="text/chapter%20{i}.xhtml" media-type="application/xhtml+xml"/>"#)
+            })
+            .collect::<String>();
+        let itemrefs = (0..chapter_bodies.len())
+            .map(|i| format!(r#"<itemref idref="c{i}"/>"#))
+            .collect::<String>();
+        writer.start_file("OEBPS/content.opf", stored).unwrap();
+        writer
+            .write_all(
+                format!(
+                    r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>{title}</dc:title></metadata>
+  <manifest>{items}</manifest>
+  <spine>{itemrefs}</spine>
+</package>"#
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        for (i, body) in chapter_bodies.iter().enumerate() {
+            writer
+                .start_file(format!("OEBPS/text/chapter {i}.xhtml"), stored)
+                .unwrap();
+            writer
+                .write_all(
+                    format!(
+                        r#"<?xml version="1.0"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Ignored</title></head><body>{body}</body></html>"#
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn load_chapters_in_spine_order() {
+        let bytes = epub_bytes(
+            " A   Title ",
+            &[
+                "<h1>Chapter  One</h1><p>First\n  paragraph&nbsp;here.</p><p>Line<br/>break</p>",
+                "<div><script>ignored()</script><h2>Two</h2><p>Nested <em>inline</em> text.</p></div>",
+                "<p>  </p>",
+            ],
+        );
+        assert!(EpubFile::is_epub(&bytes));
+
+        let epub = EpubFile::load_from_bytes(&bytes).unwrap();
+        assert_eq!(epub.title.as_deref(), Some("A Title"));
+        // The empty chapter is skipped
+        assert_eq!(epub.chapters.len(), 2);
+        assert_eq!(
+            epub.chapters[0].blocks,
+            vec![
+                EpubBlock {
+                    text: String::from("Chapter One"),
+                    heading: true,
+                },
+                EpubBlock {
+                    text: String::from("First paragraph\u{a0}here."),
+                    heading: false,
+                },
+                EpubBlock {
+                    text: String::from("Line\nbreak"),
+                    heading: false,
+                },
+            ]
+        );
+        assert_eq!(
+            epub.chapters[1].blocks,
+            vec![
+                EpubBlock {
+                    text: String::from("Two"),
+                    heading: true,
+                },
+                EpubBlock {
+                    text: String::from("Nested inline text."),
+                    heading: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn non_epub_archives_are_rejected() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("notes.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"no epub").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert!(!EpubFile::is_epub(&bytes));
+        assert!(EpubFile::load_from_bytes(&bytes).is_err());
+        assert!(EpubFile::load_from_bytes(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn resolve_relative_archive_paths() {
+        assert_eq!(
+            resolve_path("OEBPS/", "text/ch1.xhtml"),
+            "OEBPS/text/ch1.xhtml"
+        );
+        assert_eq!(
+            resolve_path("OEBPS/text/", "../ch1.xhtml"),
+            "OEBPS/ch1.xhtml"
+        );
+        assert_eq!(resolve_path("", "./ch1.xhtml"), "ch1.xhtml");
+        assert_eq!(percent_decode("chapter%201.xhtml"), "chapter 1.xhtml");
+        assert_eq!(percent_decode("100%.xhtml"), "100%.xhtml");
+    }
+}
@@ -0,0 +1,209 @@
+//! Loading the pages of GoodNotes-style `.goodnotes` / `.note` archives.
+//!
+//! The archives are Zip files. Page images and ink stored as Svg are loaded in the order they are stored in the
+//! archive. The binary ink data of GoodNotes, Pdf attachments and other unknown entries can't be imported
+//! and are reported as skipped. Thumbnails and document metadata are ignored.
+
+// Imports
+use super::FileFormatLoader;
+use anyhow::Context;
+use std::io::{Cursor, Read};
+
+/// A GoodNotes archive.
+#[derive(Debug, Clone, Default)]
+pub struct GoodnotesFile {
+    /// The pages in the order they are stored in the archive.
+    pub pages: Vec<GoodnotesPage>,
+    /// The entries that could not be imported.
+    pub skipped: Vec<GoodnotesSkippedEntry>,
+}
+
+/// A page of a GoodNotes archive.
+#[derive(Debug, Clone)]
+pub struct GoodnotesPage {
+    /// The path of the entry in the archive.
+    pub path: String,
+    pub content: GoodnotesPageContent,
+}
+
+/// The content of a page.
+#[derive(Debug, Clone)]
+pub enum GoodnotesPageContent {
+    /// An encoded bitmap image, e.g. a scanned page.
+    Image(Vec<u8>),
+    /// Ink stored as Svg.
+    Ink(String),
+}
+
+/// An entry of the archive that could not be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoodnotesSkippedEntry {
+    /// The path of the entry in the archive.
+    pub path: String,
+    /// Why the entry was skipped.
+    pub reason: String,
+}
+
+impl FileFormatLoader for GoodnotesFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+            .context("GoodNotes file is not a Zip archive.")?;
+        let mut goodnotes_file = Self::default();
+
+        for i in 0..archive.len() {
+            let mut entry = archive
+                .by_index(i)
+                .with_context(|| format!("Reading entry {i} of the GoodNotes archive failed."))?;
+            let path = entry.name().to_string();
+            if entry.is_dir() || is_ignored_path(&path) {
+                continue;
+            }
+            let mut entry_bytes = Vec::with_capacity(entry.size() as usize);
+            if let Err(e) = entry.read_to_end(&mut entry_bytes) {
+                goodnotes_file.skip(path, format!("reading the entry failed, Err: {e}"));
+                continue;
+            }
+
+            match classify_entry(entry_bytes) {
+                Ok(content) => goodnotes_file.pages.push(GoodnotesPage { path, content }),
+                Err(reason) => goodnotes_file.skip(path, reason),
+            }
+        }
+
+        Ok(goodnotes_file)
+    }
+}
+
+impl GoodnotesFile {
+    fn skip(&mut self, path: String, reason: impl Into<String>) {
+        self.skipped.push(GoodnotesSkippedEntry {
+            path,
+            reason: reason.into(),
+        });
+    }
+}
+
+/// Thumbnails duplicate the pages, metadata doesn't hold any page content.
+fn is_ignored_path(path: &str) -> bool {
+    const IGNORED_DIRS: &[&str] = &["thumbnails/", "__MACOSX/"];
+    const METADATA_EXTS: &[&str] = &[".plist", ".json", ".pb"];
+
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    IGNORED_DIRS.iter().any(|dir| path.starts_with(dir))
+        || file_name.starts_with('.')
+        // Metadata is stored in the root of the archive, e.g. "index.notes.pb"
+        || (!path.contains('/') && METADATA_EXTS.iter().any(|ext| file_name.ends_with(ext)))
+}
+
+/// Recognizes the page content by its signature, or returns the reason why the entry is skipped.
+fn classify_entry(bytes: Vec<u8>) -> Result<GoodnotesPageContent, String> {
+    if bytes.starts_with(b"%PDF-") {
+        return Err(String::from("Pdf attachments are not supported"));
+    }
+    if let Ok(format) = image::guess_format(&bytes) {
+        return match format {
+            image::ImageFormat::Png
+            | image::ImageFormat::Jpeg
+            | image::ImageFormat::Gif
+            | image::ImageFormat::WebP
+            | image::ImageFormat::Tiff
+            | image::ImageFormat::Bmp => Ok(GoodnotesPageContent::Image(bytes)),
+            format => Err(format!("images in format {format:?} are not supported")),
+        };
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) if is_svg(&text) => Ok(GoodnotesPageContent::Ink(text)),
+        _ => Err(String::from(
+            "unsupported content, like the binary ink data of GoodNotes",
+        )),
+    }
+}
+
+fn is_svg(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    pub(crate) const INK_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100"><path d="M10 10 L190 90" stroke="black"/></svg>"#;
+
+    pub(crate) fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(image::RgbaImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    /// A Zip archive with the entries in the given order.
+    pub(crate) fn archive_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, bytes) in entries {
+            if path.ends_with('/') {
+                writer
+                    .add_directory(*path, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                continue;
+            }
+            writer
+                .start_file(*path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn pages_are_loaded_in_archive_order_and_unsupported_entries_skipped() {
+        let png = png_bytes(4, 2);
+        let bytes = archive_bytes(&[
+            ("index.notes.pb", b"metadata"),
+            ("thumbnails/", b""),
+            ("thumbnails/page1.png", &png),
+            ("pages/.DS_Store", b"hidden"),
+            ("pages/1.png", &png),
+            ("pages/2.svg", INK_SVG.as_bytes()),
+            ("attachments/doc.pdf", b"%PDF-1.7"),
+            ("notes/3", b"\x08\x01\x12\x00binary ink"),
+        ]);
+
+        let goodnotes_file = GoodnotesFile::load_from_bytes(&bytes).unwrap();
+        assert_eq!(
+            goodnotes_file
+                .pages
+                .iter()
+                .map(|page| page.path.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["pages/1.png", "pages/2.svg"]
+        );
+        assert!(
+            matches!(&goodnotes_file.pages[0].content, GoodnotesPageContent::Image(image) if *image == png)
+        );
+        assert!(
+            matches!(&goodnotes_file.pages[1].content, GoodnotesPageContent::Ink(svg) if svg == INK_SVG)
+        );
+        assert_eq!(
+            goodnotes_file
+                .skipped
+                .iter()
+                .map(|entry| entry.path.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["attachments/doc.pdf", "notes/3"]
+        );
+
+        assert!(GoodnotesFile::load_from_bytes(b"no archive").is_err());
+    }
+
+    #[test]
+    fn svg_is_recognized_with_xml_declaration_and_bom() {
+        assert!(is_svg(INK_SVG));
+        assert!(is_svg("\u{feff}<?xml version=\"1.0\"?>\n<svg/>"));
+        assert!(!is_svg("<?xml version=\"1.0\"?><html/>"));
+        assert!(!is_ignored_path("pages/page.json"));
+        assert!(is_ignored_path("document.json"));
+    }
+}
@@ -0,0 +1,384 @@
+//! Loading the text content of Markdown files.
+//!
+//! Headings, paragraphs, lists, block quotes and code blocks are read as blocks of text, with the emphasis,
+//! strong emphasis, strikethrough and inline code as styled ranges.
+//! Tables and images are not supported, their text is kept as plain text and a warning is recorded.
+//! Html is skipped, with a warning as well.
+
+// Imports
+use super::FileFormatLoader;
+use anyhow::Context;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+
+/// A Markdown file.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownFile {
+    /// The text blocks in document order.
+    pub blocks: Vec<MarkdownBlock>,
+    /// Warnings about constructs that are not supported and were degraded to plain text.
+    pub warnings: Vec<String>,
+}
+
+/// A block of text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkdownBlock {
+    /// The kind of the block.
+    pub kind: MarkdownBlockKind,
+    /// The nesting depth of the lists and block quotes the block is part of.
+    pub indent: usize,
+    /// The text. List items start with their marker, line breaks are preserved as '\n'.
+    pub text: String,
+    /// The styled ranges of the text, as byte ranges.
+    pub spans: Vec<MarkdownSpan>,
+}
+
+/// The kind of a Markdown block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownBlockKind {
+    /// A paragraph.
+    #[default]
+    Paragraph,
+    /// A heading with its level from 1 to 6.
+    Heading(u8),
+    /// A list item.
+    ListItem,
+    /// A fenced or indented code block.
+    CodeBlock,
+}
+
+/// A styled range of the text of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownSpan {
+    /// The byte range of the text.
+    pub range: Range<usize>,
+    /// The style.
+    pub style: MarkdownStyle,
+}
+
+/// An inline style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkdownStyle {
+    /// Emphasis, rendered in italic.
+    Emphasis,
+    /// Strong emphasis, rendered in bold.
+    Strong,
+    /// Strikethrough.
+    Strikethrough,
+    /// Inline code, rendered in a monospace font.
+    Code,
+}
+
+impl FileFormatLoader for MarkdownFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let markdown =
+            std::str::from_utf8(bytes).context("Markdown file is not valid UTF-8 text.")?;
+        Ok(Self::parse(markdown))
+    }
+}
+
+impl MarkdownFile {
+    /// Parses the Markdown text.
+    pub fn parse(markdown: &str) -> Self {
+        let options =
+            Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        let mut parser = MarkdownParser::default();
+        for event in Parser::new_ext(markdown, options) {
+            parser.handle_event(event);
+        }
+        parser.flush();
+        Self {
+            blocks: parser.blocks,
+            warnings: parser.warnings,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MarkdownParser {
+    blocks: Vec<MarkdownBlock>,
+    warnings: Vec<String>,
+    current: MarkdownBlock,
+    /// The next number of the ordered lists, None for unordered lists.
+    lists: Vec<Option<u64>>,
+    block_quotes: usize,
+    /// If the current list item has no text yet besides its marker.
+    item_empty: bool,
+    styles: Vec<MarkdownStyle>,
+    tables: usize,
+    images: usize,
+    /// If Html was skipped, it is only warned about once.
+    html_skipped: bool,
+}
+
+impl MarkdownParser {
+    fn indent(&self) -> usize {
+        self.lists.len().saturating_sub(1) + self.block_quotes
+    }
+
+    /// Finishes the current block and starts a new one of the kind.
+    fn start_block(&mut self, kind: MarkdownBlockKind) {
+        self.flush();
+        self.current.kind = kind;
+        self.current.indent = self.indent();
+    }
+
+    fn flush(&mut self) {
+        let block = std::mem::take(&mut self.current);
+        let text = block.text.trim_end();
+        // list items that only hold a nested list are skipped
+        let only_marker = block.kind == MarkdownBlockKind::ListItem && self.item_empty;
+        self.item_empty = false;
+        if !text.is_empty() && !only_marker {
+            let len = text.len();
+            self.blocks.push(MarkdownBlock {
+                text: text.to_string(),
+                spans: block
+                    .spans
+                    .into_iter()
+                    .filter(|span| span.range.start < len)
+                    .map(|span| MarkdownSpan {
+                        range: span.range.start..span.range.end.min(len),
+                        style: span.style,
+                    })
+                    .collect(),
+                ..block
+            });
+        }
+        self.current.indent = self.indent();
+    }
+
+    fn push_text(&mut self, text: &str, extra_style: Option<MarkdownStyle>) {
+        self.item_empty = false;
+        let start = self.current.text.len();
+        self.current.text.push_str(text);
+        let range = start..self.current.text.len();
+        for style in self.styles.iter().copied().chain(extra_style) {
+            self.current.spans.push(MarkdownSpan {
+                range: range.clone(),
+                style,
+            });
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start_tag(tag),
+            Event::End(tag) => self.end_tag(tag),
+            Event::Text(text) => self.push_text(&text, None),
+            Event::Code(text) => self.push_text(&text, Some(MarkdownStyle::Code)),
+            Event::Html(_) | Event::InlineHtml(_) if !self.html_skipped => {
+                self.html_skipped = true;
+                self.warnings
+                    .push(String::from("Html is not supported, it is skipped."));
+            }
+            Event::SoftBreak => self.push_text(" ", None),
+            Event::HardBreak => self.push_text("\n", None),
+            Event::TaskListMarker(checked) => {
+                self.push_text(if checked { "☑ " } else { "☐ " }, None)
+            }
+            Event::Rule => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn start_tag(&mut self, tag: Tag) {
+        match tag {
+            // the first paragraph of a loose list item continues the item
+            Tag::Paragraph
+                if !(self.current.kind == MarkdownBlockKind::ListItem && self.item_empty) =>
+            {
+                self.start_block(MarkdownBlockKind::Paragraph);
+            }
+            Tag::Heading { level, .. } => {
+                self.start_block(MarkdownBlockKind::Heading(heading_level_number(level)))
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.block_quotes += 1;
+                self.current.indent = self.indent();
+            }
+            Tag::CodeBlock(_) => self.start_block(MarkdownBlockKind::CodeBlock),
+            Tag::List(first_number) => {
+                self.flush();
+                self.lists.push(first_number);
+            }
+            Tag::Item => {
+                self.start_block(MarkdownBlockKind::ListItem);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        let marker = format!("{number}. ");
+                        *number += 1;
+                        marker
+                    }
+                    _ => String::from("• "),
+                };
+                self.current.text.push_str(&marker);
+                self.item_empty = true;
+            }
+            Tag::Table(_) => {
+                self.tables += 1;
+                self.warnings.push(format!(
+                    "Tables are not supported, table {} is imported as plain text.",
+                    self.tables
+                ));
+                self.flush();
+            }
+            Tag::TableHead | Tag::TableRow => self.start_block(MarkdownBlockKind::Paragraph),
+            Tag::TableCell if !self.current.text.is_empty() => {
+                self.push_text(" | ", None);
+            }
+            Tag::Image { dest_url, .. } => {
+                self.images += 1;
+                self.warnings.push(format!(
+                    "Images are not supported, image {} (\"{dest_url}\") is imported as its description.",
+                    self.images
+                ));
+            }
+            Tag::Emphasis => self.styles.push(MarkdownStyle::Emphasis),
+            Tag::Strong => self.styles.push(MarkdownStyle::Strong),
+            Tag::Strikethrough => self.styles.push(MarkdownStyle::Strikethrough),
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::CodeBlock
+            | TagEnd::Item
+            | TagEnd::TableHead
+            | TagEnd::TableRow => self.flush(),
+            TagEnd::BlockQuote(_) => {
+                self.block_quotes = self.block_quotes.saturating_sub(1);
+                self.flush();
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.flush();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn heading_level_number(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MarkdownBlock, MarkdownBlockKind, MarkdownFile, MarkdownSpan, MarkdownStyle};
+
+    fn span(text: &str, part: &str, style: MarkdownStyle) -> MarkdownSpan {
+        let start = text.find(part).unwrap();
+        MarkdownSpan {
+            range: start..start + part.len(),
+            style,
+        }
+    }
+
+    #[test]
+    fn parse_headings_and_paragraphs() {
+        let markdown = MarkdownFile::parse("# Title\n\nFirst\nline\n\n### Section\n");
+        assert_eq!(
+            markdown.blocks,
+            vec![
+                MarkdownBlock {
+                    kind: MarkdownBlockKind::Heading(1),
+                    text: String::from("Title"),
+                    ..Default::default()
+                },
+                MarkdownBlock {
+                    kind: MarkdownBlockKind::Paragraph,
+                    // soft breaks are joined with a space
+                    text: String::from("First line"),
+                    ..Default::default()
+                },
+                MarkdownBlock {
+                    kind: MarkdownBlockKind::Heading(3),
+                    text: String::from("Section"),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(markdown.warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_lists() {
+        let markdown = MarkdownFile::parse("- one\n- two\n  1. nested\n  2. second\n- [x] done\n");
+        let blocks = markdown
+            .blocks
+            .iter()
+            .map(|block| (block.kind, block.indent, block.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            vec![
+                (MarkdownBlockKind::ListItem, 0, "• one"),
+                (MarkdownBlockKind::ListItem, 0, "• two"),
+                (MarkdownBlockKind::ListItem, 1, "1. nested"),
+                (MarkdownBlockKind::ListItem, 1, "2. second"),
+                (MarkdownBlockKind::ListItem, 0, "• ☑ done"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_code() {
+        let markdown = MarkdownFile::parse("Call `run()` now.\n\n```rust\nfn main() {}\n```\n");
+        let text = "Call run() now.";
+        assert_eq!(
+            markdown.blocks,
+            vec![
+                MarkdownBlock {
+                    kind: MarkdownBlockKind::Paragraph,
+                    text: String::from(text),
+                    spans: vec![span(text, "run()", MarkdownStyle::Code)],
+                    ..Default::default()
+                },
+                MarkdownBlock {
+                    kind: MarkdownBlockKind::CodeBlock,
+                    text: String::from("fn main() {}"),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_emphasis() {
+        let markdown = MarkdownFile::parse("Some *italic*, **bold** and ~~gone~~ words.");
+        let text = "Some italic, bold and gone words.";
+        assert_eq!(markdown.blocks.len(), 1);
+        assert_eq!(markdown.blocks[0].text, text);
+        assert_eq!(
+            markdown.blocks[0].spans,
+            vec![
+                span(text, "italic", MarkdownStyle::Emphasis),
+                span(text, "bold", MarkdownStyle::Strong),
+                span(text, "gone", MarkdownStyle::Strikethrough),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_skips_html_with_a_warning() {
+        let markdown = MarkdownFile::parse("<div>\nblock\n</div>\n\nText with <b>inline</b> html.");
+        assert_eq!(markdown.blocks.len(), 1);
+        assert_eq!(markdown.blocks[0].text, "Text with inline html.");
+        assert_eq!(markdown.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,1094 @@
+//! Loading Windows metafiles, the `.wmf` and `.emf` vector graphics formats.
+//!
+//! The drawing records are converted to Svg shapes: lines, polylines, polygons, rectangles and ellipses, drawn
+//! with the selected pen and brush. Text, bitmaps, paths, clipping and the other records are not supported,
+//! they are skipped and counted.
+
+// Imports
+use super::FileFormatLoader;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The size of the placeable header some Wmf files start with.
+const WMF_PLACEABLE_HEADER_SIZE: usize = 22;
+const WMF_PLACEABLE_MAGIC: u32 = 0x9AC6_CDD7;
+/// The " EMF" signature of the Emf header record.
+const EMF_SIGNATURE: u32 = 0x464D_4520;
+const EMF_SIGNATURE_OFFSET: usize = 40;
+/// The offset of the amount of handles in the object table of the Emf header record.
+const EMF_HANDLES_OFFSET: usize = 56;
+/// Svg user units per inch.
+const SVG_DPI: f64 = 96.0;
+
+/// The variant of a metafile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetafileKind {
+    /// The 16-bit Windows metafile.
+    Wmf,
+    /// The 32-bit enhanced metafile.
+    Emf,
+}
+
+/// A Windows metafile converted to Svg shapes.
+#[derive(Debug, Clone)]
+pub struct MetafileFile {
+    pub kind: MetafileKind,
+    /// The logical coordinates of the drawing, as (x, y, width, height).
+    pub view_box: (f64, f64, f64, f64),
+    /// The size of the drawing in Svg user units, if the header has a physical size.
+    pub size: Option<(f64, f64)>,
+    /// The converted shapes in drawing order.
+    pub shapes: Vec<MetafileShape>,
+    /// The amount of skipped records, by the name of the record type.
+    pub skipped: BTreeMap<String, usize>,
+}
+
+/// A shape drawn by a record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetafileShape {
+    pub geometry: MetafileGeometry,
+    /// The stroke color and width, None if drawn with the null pen.
+    pub stroke: Option<(MetafileColor, f64)>,
+    /// The fill color, None if drawn with the null brush or not fillable.
+    pub fill: Option<MetafileColor>,
+    /// If the alternate fill mode is used, filling with the even-odd rule.
+    pub even_odd: bool,
+}
+
+/// The geometry of a shape, in logical coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetafileGeometry {
+    /// Open polylines.
+    Polyline(Vec<(f64, f64)>),
+    /// Closed polygons, drawn as one shape.
+    Polygons(Vec<Vec<(f64, f64)>>),
+    /// A rectangle as (left, top, right, bottom).
+    Rectangle(f64, f64, f64, f64),
+    /// The ellipse inscribed into the rectangle as (left, top, right, bottom).
+    Ellipse(f64, f64, f64, f64),
+}
+
+/// A color as stored in a COLORREF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetafileColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl MetafileColor {
+    const BLACK: Self = Self { r: 0, g: 0, b: 0 };
+    const WHITE: Self = Self {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    fn from_colorref(colorref: u32) -> Self {
+        Self {
+            r: (colorref & 0xff) as u8,
+            g: ((colorref >> 8) & 0xff) as u8,
+            b: ((colorref >> 16) & 0xff) as u8,
+        }
+    }
+
+    fn gray(value: u8) -> Self {
+        Self {
+            r: value,
+            g: value,
+            b: value,
+        }
+    }
+
+    fn to_svg(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl FileFormatLoader for MetafileFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        match Self::detect(bytes) {
+            Some(MetafileKind::Emf) => Self::load_emf(bytes),
+            Some(MetafileKind::Wmf) => Self::load_wmf(bytes),
+            None => Err(anyhow::anyhow!("File is not a Wmf or Emf metafile.")),
+        }
+    }
+}
+
+impl MetafileFile {
+    /// Detects the variant of the metafile from its header.
+    pub fn detect(bytes: &[u8]) -> Option<MetafileKind> {
+        if read_u32(bytes, 0) == Some(1)
+            && read_u32(bytes, EMF_SIGNATURE_OFFSET) == Some(EMF_SIGNATURE)
+        {
+            return Some(MetafileKind::Emf);
+        }
+        if read_u32(bytes, 0) == Some(WMF_PLACEABLE_MAGIC) {
+            return Some(MetafileKind::Wmf);
+        }
+        // Wmf files without the placeable header start with the type (memory or disk) and the header size of
+        // nine words.
+        match (read_u16(bytes, 0), read_u16(bytes, 2)) {
+            (Some(1 | 2), Some(9)) => Some(MetafileKind::Wmf),
+            _ => None,
+        }
+    }
+
+    /// The total amount of skipped records.
+    pub fn skipped_amount(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    /// Generates a Svg document of the shapes.
+    pub fn to_svg(&self) -> String {
+        let (x, y, width, height) = self.view_box;
+        let (svg_width, svg_height) = self.size.unwrap_or((width.abs(), height.abs()));
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{svg_width}" height="{svg_height}" viewBox="{x} {y} {width} {height}" preserveAspectRatio="none">"#
+        );
+        // a pen width of zero is drawn one device pixel wide
+        let min_stroke_width = if svg_width > 0.0 {
+            width.abs() / svg_width
+        } else {
+            1.0
+        };
+        for shape in self.shapes.iter() {
+            shape.write_svg(&mut svg, min_stroke_width);
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn skip_record(&mut self, name: String) {
+        *self.skipped.entry(name).or_default() += 1;
+    }
+
+    fn load_emf(bytes: &[u8]) -> anyhow::Result<Self> {
+        let header_bounds =
+            read_rect_i32(bytes, 8).ok_or_else(|| anyhow::anyhow!("Emf header is truncated."))?;
+        // the frame is in units of 0.01 mm
+        let frame =
+            read_rect_i32(bytes, 24).ok_or_else(|| anyhow::anyhow!("Emf header is truncated."))?;
+        let frame_size = (
+            (frame.2 - frame.0) / 2540.0 * SVG_DPI,
+            (frame.3 - frame.1) / 2540.0 * SVG_DPI,
+        );
+        let handles = read_u16(bytes, EMF_HANDLES_OFFSET)
+            .ok_or_else(|| anyhow::anyhow!("Emf header is truncated."))?
+            as usize;
+        let mut file = Self::new(MetafileKind::Emf, header_bounds);
+        if frame_size.0 > 0.0 && frame_size.1 > 0.0 {
+            file.size = Some(frame_size);
+        }
+        let mut dc = DeviceContext::default();
+        let mut offset = 0;
+
+        while offset + 8 <= bytes.len() {
+            let record_type = read_u32(bytes, offset).unwrap_or_default();
+            let size = read_u32(bytes, offset + 4).unwrap_or_default() as usize;
+            if size < 8 || size % 4 != 0 || offset + size > bytes.len() {
+                return Err(anyhow::anyhow!(
+                    "Emf record at offset {offset} has an invalid size of {size} bytes."
+                ));
+            }
+            let data = &bytes[offset + 8..offset + size];
+            offset += size;
+
+            match record_type {
+                // EMR_HEADER
+                1 => {}
+                // EMR_EOF
+                14 => break,
+                // EMR_POLYGON and EMR_POLYLINE
+                3 | 4 => {
+                    let Some(points) = read_emf_points(data, 16, false) else {
+                        file.skip_record(emf_record_name(record_type));
+                        continue;
+                    };
+                    let geometry = if record_type == 3 {
+                        MetafileGeometry::Polygons(vec![points])
+                    } else {
+                        MetafileGeometry::Polyline(points)
+                    };
+                    file.push_shape(&dc, geometry);
+                }
+                // EMR_POLYGON16 and EMR_POLYLINE16
+                86 | 87 => {
+                    let Some(points) = read_emf_points(data, 16, true) else {
+                        file.skip_record(emf_record_name(record_type));
+                        continue;
+                    };
+                    let geometry = if record_type == 86 {
+                        MetafileGeometry::Polygons(vec![points])
+                    } else {
+                        MetafileGeometry::Polyline(points)
+                    };
+                    file.push_shape(&dc, geometry);
+                }
+                // EMR_POLYPOLYGON and EMR_POLYPOLYGON16
+                8 | 91 => {
+                    let Some(polygons) = read_emf_polypolygon(data, record_type == 91) else {
+                        file.skip_record(emf_record_name(record_type));
+                        continue;
+                    };
+                    file.push_shape(&dc, MetafileGeometry::Polygons(polygons));
+                }
+                // EMR_SETWINDOWEXTEX
+                9 => {
+                    if let (Some(cx), Some(cy)) = (read_i32(data, 0), read_i32(data, 4)) {
+                        dc.window_ext = Some((cx as f64, cy as f64));
+                    }
+                }
+                // EMR_SETWINDOWORGEX
+                10 => {
+                    if let (Some(x), Some(y)) = (read_i32(data, 0), read_i32(data, 4)) {
+                        dc.window_org = Some((x as f64, y as f64));
+                    }
+                }
+                // EMR_SETPOLYFILLMODE
+                19 => dc.even_odd = read_u32(data, 0) == Some(1),
+                // EMR_SETMAPMODE, EMR_SETBKMODE, EMR_SETROP2, EMR_SETSTRETCHBLTMODE, EMR_SETTEXTALIGN,
+                // EMR_SETTEXTCOLOR, EMR_SETBKCOLOR and EMR_GDICOMMENT don't change the converted shapes
+                17 | 18 | 20 | 21 | 22 | 24 | 25 | 70 => {}
+                // EMR_MOVETOEX
+                27 => {
+                    if let (Some(x), Some(y)) = (read_i32(data, 0), read_i32(data, 4)) {
+                        dc.position = (x as f64, y as f64);
+                    }
+                }
+                // EMR_LINETO
+                54 => {
+                    if let (Some(x), Some(y)) = (read_i32(data, 0), read_i32(data, 4)) {
+                        let to = (x as f64, y as f64);
+                        file.push_line(&dc, dc.position, to);
+                        dc.position = to;
+                    }
+                }
+                // EMR_SELECTOBJECT
+                37 => {
+                    if let Some(index) = read_u32(data, 0) {
+                        dc.select_emf_object(index);
+                    }
+                }
+                // EMR_CREATEPEN
+                38 => {
+                    if let (Some(index), Some(style), Some(width), Some(color)) = (
+                        read_u32(data, 0),
+                        read_u32(data, 4),
+                        read_i32(data, 8),
+                        read_u32(data, 16),
+                    ) {
+                        dc.insert_emf_object(
+                            index as usize,
+                            pen_object(style, width as f64, color),
+                            handles,
+                        )?;
+                    }
+                }
+                // EMR_EXTCREATEPEN
+                95 => {
+                    if let (Some(index), Some(style), Some(width), Some(color)) = (
+                        read_u32(data, 0),
+                        read_u32(data, 20),
+                        read_u32(data, 24),
+                        read_u32(data, 32),
+                    ) {
+                        dc.insert_emf_object(
+                            index as usize,
+                            pen_object(style, width as f64, color),
+                            handles,
+                        )?;
+                    }
+                }
+                // EMR_CREATEBRUSHINDIRECT
+                39 => {
+                    if let (Some(index), Some(style), Some(color)) =
+                        (read_u32(data, 0), read_u32(data, 4), read_u32(data, 8))
+                    {
+                        dc.insert_emf_object(index as usize, brush_object(style, color), handles)?;
+                    }
+                }
+                // EMR_DELETEOBJECT
+                40 => {
+                    if let Some(index) = read_u32(data, 0) {
+                        dc.delete_object(index as usize);
+                    }
+                }
+                // EMR_ELLIPSE and EMR_RECTANGLE
+                42 | 43 => {
+                    let Some((left, top, right, bottom)) = read_rect_i32(data, 0) else {
+                        file.skip_record(emf_record_name(record_type));
+                        continue;
+                    };
+                    let geometry = if record_type == 42 {
+                        MetafileGeometry::Ellipse(left, top, right, bottom)
+                    } else {
+                        MetafileGeometry::Rectangle(left, top, right, bottom)
+                    };
+                    file.push_shape(&dc, geometry);
+                }
+                _ => file.skip_record(emf_record_name(record_type)),
+            }
+        }
+
+        file.apply_window(&dc);
+        Ok(file)
+    }
+
+    fn load_wmf(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut header_bounds = None;
+        let mut size = None;
+        let mut offset = 0;
+        if read_u32(bytes, 0) == Some(WMF_PLACEABLE_MAGIC) {
+            let (Some(left), Some(top), Some(right), Some(bottom), Some(units_per_inch)) = (
+                read_i16(bytes, 6),
+                read_i16(bytes, 8),
+                read_i16(bytes, 10),
+                read_i16(bytes, 12),
+                read_u16(bytes, 14),
+            ) else {
+                return Err(anyhow::anyhow!("Wmf placeable header is truncated."));
+            };
+            let bounds = (left as f64, top as f64, right as f64, bottom as f64);
+            if units_per_inch > 0 {
+                size = Some((
+                    (bounds.2 - bounds.0).abs() / units_per_inch as f64 * SVG_DPI,
+                    (bounds.3 - bounds.1).abs() / units_per_inch as f64 * SVG_DPI,
+                ));
+            }
+            header_bounds = Some(bounds);
+            offset = WMF_PLACEABLE_HEADER_SIZE;
+        }
+        // the header size is in words
+        let header_size = read_u16(bytes, offset + 2)
+            .ok_or_else(|| anyhow::anyhow!("Wmf header is truncated."))?
+            as usize
+            * 2;
+        offset += header_size;
+
+        let mut file = Self::new(MetafileKind::Wmf, header_bounds.unwrap_or_default());
+        file.size = size;
+        let mut dc = DeviceContext::default();
+
+        while offset + 6 <= bytes.len() {
+            // the record size is in words and includes the size and function fields
+            let size = read_u32(bytes, offset).unwrap_or_default() as usize * 2;
+            let function = read_u16(bytes, offset + 4).unwrap_or_default();
+            if size < 6 || offset + size > bytes.len() {
+                return Err(anyhow::anyhow!(
+                    "Wmf record at offset {offset} has an invalid size of {size} bytes."
+                ));
+            }
+            let params = &bytes[offset + 6..offset + size];
+            offset += size;
+
+            match function {
+                // META_EOF
+                0x0000 => break,
+                // META_SETWINDOWORG
+                0x020B => {
+                    if let (Some(y), Some(x)) = (read_i16(params, 0), read_i16(params, 2)) {
+                        dc.window_org = Some((x as f64, y as f64));
+                    }
+                }
+                // META_SETWINDOWEXT
+                0x020C => {
+                    if let (Some(y), Some(x)) = (read_i16(params, 0), read_i16(params, 2)) {
+                        dc.window_ext = Some((x as f64, y as f64));
+                    }
+                }
+                // META_SETPOLYFILLMODE
+                0x0106 => dc.even_odd = read_u16(params, 0) == Some(1),
+                // META_SETBKMODE, META_SETMAPMODE, META_SETROP2, META_SETSTRETCHBLTMODE, META_SETTEXTALIGN,
+                // META_SETBKCOLOR and META_SETTEXTCOLOR don't change the converted shapes
+                0x0102 | 0x0103 | 0x0104 | 0x0107 | 0x012E | 0x0201 | 0x0209 => {}
+                // META_MOVETO
+                0x0214 => {
+                    if let (Some(y), Some(x)) = (read_i16(params, 0), read_i16(params, 2)) {
+                        dc.position = (x as f64, y as f64);
+                    }
+                }
+                // META_LINETO
+                0x0213 => {
+                    if let (Some(y), Some(x)) = (read_i16(params, 0), read_i16(params, 2)) {
+                        let to = (x as f64, y as f64);
+                        file.push_line(&dc, dc.position, to);
+                        dc.position = to;
+                    }
+                }
+                // META_POLYGON and META_POLYLINE
+                0x0324 | 0x0325 => {
+                    let points = read_u16(params, 0)
+                        .and_then(|count| read_points_i16(params, 2, count as usize));
+                    let Some(points) = points else {
+                        file.skip_record(wmf_record_name(function));
+                        continue;
+                    };
+                    let geometry = if function == 0x0324 {
+                        MetafileGeometry::Polygons(vec![points])
+                    } else {
+                        MetafileGeometry::Polyline(points)
+                    };
+                    file.push_shape(&dc, geometry);
+                }
+                // META_POLYPOLYGON
+                0x0538 => {
+                    let Some(polygons) = read_wmf_polypolygon(params) else {
+                        file.skip_record(wmf_record_name(function));
+                        continue;
+                    };
+                    file.push_shape(&dc, MetafileGeometry::Polygons(polygons));
+                }
+                // META_RECTANGLE and META_ELLIPSE, with the coordinates in reverse order
+                0x041B | 0x0418 => {
+                    let (Some(bottom), Some(right), Some(top), Some(left)) = (
+                        read_i16(params, 0),
+                        read_i16(params, 2),
+                        read_i16(params, 4),
+                        read_i16(params, 6),
+                    ) else {
+                        file.skip_record(wmf_record_name(function));
+                        continue;
+                    };
+                    let rect = (left as f64, top as f64, right as f64, bottom as f64);
+                    let geometry = if function == 0x0418 {
+                        MetafileGeometry::Ellipse(rect.0, rect.1, rect.2, rect.3)
+                    } else {
+                        MetafileGeometry::Rectangle(rect.0, rect.1, rect.2, rect.3)
+                    };
+                    file.push_shape(&dc, geometry);
+                }
+                // META_CREATEPENINDIRECT
+                0x02FA => {
+                    if let (Some(style), Some(width), Some(color)) = (
+                        read_u16(params, 0),
+                        read_i16(params, 2),
+                        read_u32(params, 6),
+                    ) {
+                        dc.insert_wmf_object(pen_object(style as u32, width as f64, color));
+                    }
+                }
+                // META_CREATEBRUSHINDIRECT
+                0x02FC => {
+                    if let (Some(style), Some(color)) = (read_u16(params, 0), read_u32(params, 2)) {
+                        dc.insert_wmf_object(brush_object(style as u32, color));
+                    }
+                }
+                // META_SELECTOBJECT
+                0x012D => {
+                    if let Some(index) = read_u16(params, 0) {
+                        dc.select_object(index as usize);
+                    }
+                }
+                // META_DELETEOBJECT
+                0x01F0 => {
+                    if let Some(index) = read_u16(params, 0) {
+                        dc.delete_object(index as usize);
+                    }
+                }
+                _ => {
+                    // records that create objects must still take a slot in the object table, so that the
+                    // indices of the following objects stay correct
+                    if wmf_record_creates_object(function) {
+                        dc.insert_wmf_object(GdiObject::Unsupported);
+                    }
+                    file.skip_record(wmf_record_name(function));
+                }
+            }
+        }
+
+        file.apply_window(&dc);
+        Ok(file)
+    }
+
+    fn new(kind: MetafileKind, bounds: (f64, f64, f64, f64)) -> Self {
+        Self {
+            kind,
+            view_box: (bounds.0, bounds.1, bounds.2 - bounds.0, bounds.3 - bounds.1),
+            size: None,
+            shapes: vec![],
+            skipped: BTreeMap::new(),
+        }
+    }
+
+    /// The window set by the records takes precedence over the bounds of the header.
+    fn apply_window(&mut self, dc: &DeviceContext) {
+        if let Some((width, height)) = dc.window_ext {
+            let (x, y) = dc.window_org.unwrap_or((self.view_box.0, self.view_box.1));
+            self.view_box = (x, y, width, height);
+        }
+        if self.view_box.2 == 0.0 || self.view_box.3 == 0.0 {
+            self.view_box = self.shapes_bounds().unwrap_or((0.0, 0.0, 1.0, 1.0));
+        }
+    }
+
+    fn shapes_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let mut points = self.shapes.iter().flat_map(|shape| match &shape.geometry {
+            MetafileGeometry::Polyline(points) => points.clone(),
+            MetafileGeometry::Polygons(polygons) => polygons.concat(),
+            MetafileGeometry::Rectangle(l, t, r, b) | MetafileGeometry::Ellipse(l, t, r, b) => {
+                vec![(*l, *t), (*r, *b)]
+            }
+        });
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (
+                (min.0.min(p.0), min.1.min(p.1)),
+                (max.0.max(p.0), max.1.max(p.1)),
+            )
+        });
+        Some((
+            min.0,
+            min.1,
+            (max.0 - min.0).max(1.0),
+            (max.1 - min.1).max(1.0),
+        ))
+    }
+
+    fn push_line(&mut self, dc: &DeviceContext, from: (f64, f64), to: (f64, f64)) {
+        // lines are never filled
+        let stroke = dc.pen;
+        if stroke.is_some() {
+            self.shapes.push(MetafileShape {
+                geometry: MetafileGeometry::Polyline(vec![from, to]),
+                stroke,
+                fill: None,
+                even_odd: false,
+            });
+        }
+    }
+
+    fn push_shape(&mut self, dc: &DeviceContext, geometry: MetafileGeometry) {
+        let fill = match geometry {
+            MetafileGeometry::Polyline(_) => None,
+            _ => dc.brush,
+        };
+        let stroke = dc.pen;
+        if stroke.is_none() && fill.is_none() {
+            return;
+        }
+        self.shapes.push(MetafileShape {
+            geometry,
+            stroke,
+            fill,
+            even_odd: dc.even_odd,
+        });
+    }
+}
+
+impl MetafileShape {
+    fn write_svg(&self, svg: &mut String, min_stroke_width: f64) {
+        let style = {
+            let stroke = match self.stroke {
+                Some((color, width)) => format!(
+                    r#"stroke="{}" stroke-width="{}" stroke-linejoin="round" stroke-linecap="round""#,
+                    color.to_svg(),
+                    width.max(min_stroke_width)
+                ),
+                None => String::from(r#"stroke="none""#),
+            };
+            let fill = match self.fill {
+                Some(color) => format!(
+                    r#"fill="{}" fill-rule="{}""#,
+                    color.to_svg(),
+                    if self.even_odd { "evenodd" } else { "nonzero" }
+                ),
+                None => String::from(r#"fill="none""#),
+            };
+            format!("{stroke} {fill}")
+        };
+        let _ = match &self.geometry {
+            MetafileGeometry::Polyline(points) => {
+                write!(
+                    svg,
+                    r#"<polyline points="{}" {style}/>"#,
+                    svg_points(points)
+                )
+            }
+            MetafileGeometry::Polygons(polygons) => {
+                let d = polygons
+                    .iter()
+                    .filter(|polygon| !polygon.is_empty())
+                    .map(|polygon| format!("M{}Z", svg_points(polygon)))
+                    .collect::<String>();
+                write!(svg, r#"<path d="{d}" {style}/>"#)
+            }
+            MetafileGeometry::Rectangle(left, top, right, bottom) => write!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" {style}/>"#,
+                left.min(*right),
+                top.min(*bottom),
+                (right - left).abs(),
+                (bottom - top).abs()
+            ),
+            MetafileGeometry::Ellipse(left, top, right, bottom) => write!(
+                svg,
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" {style}/>"#,
+                (left + right) * 0.5,
+                (top + bottom) * 0.5,
+                (right - left).abs() * 0.5,
+                (bottom - top).abs() * 0.5
+            ),
+        };
+    }
+}
+
+/// A Gdi object created by the records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GdiObject {
+    /// A pen, None for the null pen.
+    Pen(Option<(MetafileColor, f64)>),
+    /// A brush, None for the null brush.
+    Brush(Option<MetafileColor>),
+    /// An object like a font or a palette that does not change the converted shapes.
+    Unsupported,
+}
+
+/// The drawing state.
+#[derive(Debug, Clone)]
+struct DeviceContext {
+    objects: Vec<Option<GdiObject>>,
+    pen: Option<(MetafileColor, f64)>,
+    brush: Option<MetafileColor>,
+    position: (f64, f64),
+    window_org: Option<(f64, f64)>,
+    window_ext: Option<(f64, f64)>,
+    even_odd: bool,
+}
+
+impl Default for DeviceContext {
+    fn default() -> Self {
+        // the default pen is black and one pixel wide, the default brush is white
+        Self {
+            objects: vec![],
+            pen: Some((MetafileColor::BLACK, 0.0)),
+            brush: Some(MetafileColor::WHITE),
+            position: (0.0, 0.0),
+            window_org: None,
+            window_ext: None,
+            even_odd: true,
+        }
+    }
+}
+
+impl DeviceContext {
+    /// Emf objects are placed at the index given by the record, which is bounded by the amount of handles in the
+    /// header.
+    fn insert_emf_object(
+        &mut self,
+        index: usize,
+        object: GdiObject,
+        handles: usize,
+    ) -> anyhow::Result<()> {
+        if index >= handles {
+            return Err(anyhow::anyhow!(
+                "Emf object index {index} is outside of the {handles} handles of the header."
+            ));
+        }
+        if self.objects.len() <= index {
+            self.objects.resize(index + 1, None);
+        }
+        self.objects[index] = Some(object);
+        Ok(())
+    }
+
+    /// Wmf objects take the lowest free index of the object table.
+    fn insert_wmf_object(&mut self, object: GdiObject) {
+        match self.objects.iter().position(Option::is_none) {
+            Some(index) => self.objects[index] = Some(object),
+            None => self.objects.push(Some(object)),
+        }
+    }
+
+    fn delete_object(&mut self, index: usize) {
+        if let Some(object) = self.objects.get_mut(index) {
+            *object = None;
+        }
+    }
+
+    fn select_object(&mut self, index: usize) {
+        match self.objects.get(index).copied().flatten() {
+            Some(GdiObject::Pen(pen)) => self.pen = pen,
+            Some(GdiObject::Brush(brush)) => self.brush = brush,
+            _ => {}
+        }
+    }
+
+    /// Emf objects with the high bit set are stock objects.
+    fn select_emf_object(&mut self, index: u32) {
+        if index & 0x8000_0000 == 0 {
+            self.select_object(index as usize);
+            return;
+        }
+        match index & 0x7fff_ffff {
+            // WHITE_BRUSH, LTGRAY_BRUSH, GRAY_BRUSH, DKGRAY_BRUSH and BLACK_BRUSH
+            0 => self.brush = Some(MetafileColor::WHITE),
+            1 => self.brush = Some(MetafileColor::gray(0xc0)),
+            2 => self.brush = Some(MetafileColor::gray(0x80)),
+            3 => self.brush = Some(MetafileColor::gray(0x40)),
+            4 => self.brush = Some(MetafileColor::BLACK),
+            // NULL_BRUSH
+            5 => self.brush = None,
+            // WHITE_PEN, BLACK_PEN and NULL_PEN
+            6 => self.pen = Some((MetafileColor::WHITE, 0.0)),
+            7 => self.pen = Some((MetafileColor::BLACK, 0.0)),
+            8 => self.pen = None,
+            _ => {}
+        }
+    }
+}
+
+fn pen_object(style: u32, width: f64, color: u32) -> GdiObject {
+    // PS_NULL
+    if style & 0x0f == 5 {
+        GdiObject::Pen(None)
+    } else {
+        GdiObject::Pen(Some((MetafileColor::from_colorref(color), width.abs())))
+    }
+}
+
+fn brush_object(style: u32, color: u32) -> GdiObject {
+    match style {
+        // BS_SOLID, and BS_HATCHED drawn as solid
+        0 | 2 => GdiObject::Brush(Some(MetafileColor::from_colorref(color))),
+        // BS_NULL and the pattern brushes
+        _ => GdiObject::Brush(None),
+    }
+}
+
+fn svg_points(points: &[(f64, f64)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{x},{y}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn wmf_record_creates_object(function: u16) -> bool {
+    // META_CREATEPALETTE, META_CREATEPATTERNBRUSH, META_CREATEFONTINDIRECT, META_CREATEREGION and
+    // META_DIBCREATEPATTERNBRUSH
+    matches!(function, 0x00F7 | 0x01F9 | 0x02FB | 0x06FF | 0x0142)
+}
+
+fn wmf_record_name(function: u16) -> String {
+    let name = match function {
+        0x0521 => "META_TEXTOUT",
+        0x0A32 => "META_EXTTEXTOUT",
+        0x02FB => "META_CREATEFONTINDIRECT",
+        0x0940 => "META_DIBBITBLT",
+        0x0B41 => "META_DIBSTRETCHBLT",
+        0x0F43 => "META_STRETCHDIB",
+        0x0817 => "META_ARC",
+        0x081A => "META_PIE",
+        0x0830 => "META_CHORD",
+        0x061C => "META_ROUNDRECT",
+        0x001E => "META_SAVEDC",
+        0x0127 => "META_RESTOREDC",
+        0x0416 => "META_INTERSECTCLIPRECT",
+        _ => return format!("Wmf record 0x{function:04X}"),
+    };
+    name.to_string()
+}
+
+fn emf_record_name(record_type: u32) -> String {
+    let name = match record_type {
+        2 => "EMR_POLYBEZIER",
+        5 => "EMR_POLYBEZIERTO",
+        6 => "EMR_POLYLINETO",
+        33 => "EMR_SAVEDC",
+        34 => "EMR_RESTOREDC",
+        35 => "EMR_SETWORLDTRANSFORM",
+        36 => "EMR_MODIFYWORLDTRANSFORM",
+        44 => "EMR_ROUNDRECT",
+        45 => "EMR_ARC",
+        46 => "EMR_CHORD",
+        47 => "EMR_PIE",
+        59 => "EMR_BEGINPATH",
+        60 => "EMR_ENDPATH",
+        62 => "EMR_FILLPATH",
+        63 => "EMR_STROKEANDFILLPATH",
+        64 => "EMR_STROKEPATH",
+        76 => "EMR_BITBLT",
+        77 => "EMR_STRETCHBLT",
+        81 => "EMR_STRETCHDIBITS",
+        82 => "EMR_EXTCREATEFONTINDIRECTW",
+        84 => "EMR_EXTTEXTOUTW",
+        _ => return format!("Emf record {record_type}"),
+    };
+    name.to_string()
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_i16(bytes: &[u8], offset: usize) -> Option<i16> {
+    Some(i16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a RECTL as (left, top, right, bottom).
+fn read_rect_i32(bytes: &[u8], offset: usize) -> Option<(f64, f64, f64, f64)> {
+    Some((
+        read_i32(bytes, offset)? as f64,
+        read_i32(bytes, offset + 4)? as f64,
+        read_i32(bytes, offset + 8)? as f64,
+        read_i32(bytes, offset + 12)? as f64,
+    ))
+}
+
+fn read_points_i16(bytes: &[u8], offset: usize, count: usize) -> Option<Vec<(f64, f64)>> {
+    (0..count)
+        .map(|i| {
+            let offset = offset + i * 4;
+            Some((
+                read_i16(bytes, offset)? as f64,
+                read_i16(bytes, offset + 2)? as f64,
+            ))
+        })
+        .collect()
+}
+
+fn read_points_i32(bytes: &[u8], offset: usize, count: usize) -> Option<Vec<(f64, f64)>> {
+    (0..count)
+        .map(|i| {
+            let offset = offset + i * 8;
+            Some((
+                read_i32(bytes, offset)? as f64,
+                read_i32(bytes, offset + 4)? as f64,
+            ))
+        })
+        .collect()
+}
+
+/// Reads the point count and the points that follow the bounds of a Emf poly record.
+fn read_emf_points(data: &[u8], offset: usize, short: bool) -> Option<Vec<(f64, f64)>> {
+    let count = read_u32(data, offset)? as usize;
+    if short {
+        read_points_i16(data, offset + 4, count)
+    } else {
+        read_points_i32(data, offset + 4, count)
+    }
+}
+
+fn read_emf_polypolygon(data: &[u8], short: bool) -> Option<Vec<Vec<(f64, f64)>>> {
+    // the bounds are followed by the amount of polygons and the total amount of points
+    let polygons_amount = read_u32(data, 16)? as usize;
+    let counts = (0..polygons_amount)
+        .map(|i| read_u32(data, 24 + i * 4).map(|count| count as usize))
+        .collect::<Option<Vec<_>>>()?;
+    let point_size = if short { 4 } else { 8 };
+    let mut offset = 24 + polygons_amount * 4;
+    counts
+        .into_iter()
+        .map(|count| {
+            let points = if short {
+                read_points_i16(data, offset, count)
+            } else {
+                read_points_i32(data, offset, count)
+            };
+            offset += count * point_size;
+            points
+        })
+        .collect()
+}
+
+fn read_wmf_polypolygon(params: &[u8]) -> Option<Vec<Vec<(f64, f64)>>> {
+    let polygons_amount = read_u16(params, 0)? as usize;
+    let counts = (0..polygons_amount)
+        .map(|i| read_u16(params, 2 + i * 2).map(|count| count as usize))
+        .collect::<Option<Vec<_>>>()?;
+    let mut offset = 2 + polygons_amount * 2;
+    counts
+        .into_iter()
+        .map(|count| {
+            let points = read_points_i16(params, offset, count);
+            offset += count * 4;
+            points
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MetafileColor, MetafileFile, MetafileGeometry, MetafileKind, MetafileShape, EMF_SIGNATURE,
+    };
+    use crate::fileformats::FileFormatLoader;
+
+    const RED: u32 = 0x0000_00ff;
+    const GREEN: u32 = 0x0000_ff00;
+    const BLUE: u32 = 0x00ff_0000;
+
+    fn emf_record(record_type: u32, data: &[i32]) -> Vec<u8> {
+        let mut record = record_type.to_le_bytes().to_vec();
+        record.extend_from_slice(&(8 + data.len() as u32 * 4).to_le_bytes());
+        record.extend(data.iter().flat_map(|value| value.to_le_bytes()));
+        record
+    }
+
+    /// An Emf drawing a rectangle with a red pen and a blue brush, followed by an unsupported record.
+    fn emf_fixture(handles: i32, pen_index: i32) -> Vec<u8> {
+        let mut bytes = emf_record(
+            1,
+            &[
+                // bounds
+                0,
+                0,
+                200,
+                100,
+                // the frame of 2 by 1 inches, in 0.01 mm
+                0,
+                0,
+                5080,
+                2540,
+                EMF_SIGNATURE as i32,
+                0x0001_0000,
+                0,
+                0,
+                // the handles and the reserved field
+                handles,
+                0,
+                0,
+                0,
+                // the device size in pixels and millimeters
+                200,
+                100,
+                51,
+                25,
+            ],
+        );
+        // EMR_CREATEPEN and EMR_SELECTOBJECT
+        bytes.extend(emf_record(38, &[pen_index, 0, 2, 0, RED as i32]));
+        bytes.extend(emf_record(37, &[pen_index]));
+        // EMR_CREATEBRUSHINDIRECT and EMR_SELECTOBJECT
+        bytes.extend(emf_record(39, &[2, 0, BLUE as i32, 0]));
+        bytes.extend(emf_record(37, &[2]));
+        // EMR_RECTANGLE
+        bytes.extend(emf_record(43, &[10, 20, 110, 70]));
+        // EMR_SAVEDC
+        bytes.extend(emf_record(33, &[]));
+        // EMR_EOF
+        bytes.extend(emf_record(14, &[0, 16, 20]));
+        bytes
+    }
+
+    fn wmf_record(function: u16, params: &[u16]) -> Vec<u8> {
+        let mut record = (3 + params.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&function.to_le_bytes());
+        record.extend(params.iter().flat_map(|value| value.to_le_bytes()));
+        record
+    }
+
+    /// A Wmf without the placeable header drawing an ellipse with a green pen and a blue brush, followed by an
+    /// unsupported record.
+    fn wmf_fixture() -> Vec<u8> {
+        // the type, the header size, the version, the file size, the amount of objects, the largest record and the
+        // unused member count
+        let mut bytes = [1, 9, 0x0300, 0, 0, 2, 0, 0, 0]
+            .iter()
+            .flat_map(|value: &u16| value.to_le_bytes())
+            .collect::<Vec<u8>>();
+        // META_SETWINDOWORG and META_SETWINDOWEXT, with y before x
+        bytes.extend(wmf_record(0x020B, &[0, 0]));
+        bytes.extend(wmf_record(0x020C, &[100, 200]));
+        // META_CREATEPENINDIRECT and META_SELECTOBJECT
+        bytes.extend(wmf_record(0x02FA, &[0, 3, 0, GREEN as u16, 0]));
+        bytes.extend(wmf_record(0x012D, &[0]));
+        // META_CREATEBRUSHINDIRECT and META_SELECTOBJECT
+        bytes.extend(wmf_record(
+            0x02FC,
+            &[0, BLUE as u16, (BLUE >> 16) as u16, 0],
+        ));
+        bytes.extend(wmf_record(0x012D, &[1]));
+        // META_ELLIPSE, with the coordinates in reverse order
+        bytes.extend(wmf_record(0x0418, &[70, 110, 20, 10]));
+        // META_TEXTOUT
+        bytes.extend(wmf_record(0x0521, &[]));
+        // META_EOF
+        bytes.extend(wmf_record(0x0000, &[]));
+        bytes
+    }
+
+    #[test]
+    fn load_emf() {
+        let bytes = emf_fixture(3, 1);
+        assert_eq!(MetafileFile::detect(&bytes), Some(MetafileKind::Emf));
+        let file = MetafileFile::load_from_bytes(&bytes).unwrap();
+        assert_eq!(file.view_box, (0.0, 0.0, 200.0, 100.0));
+        assert_eq!(file.size, Some((192.0, 96.0)));
+        assert_eq!(
+            file.shapes,
+            vec![MetafileShape {
+                geometry: MetafileGeometry::Rectangle(10.0, 20.0, 110.0, 70.0),
+                stroke: Some((MetafileColor::from_colorref(RED), 2.0)),
+                fill: Some(MetafileColor::from_colorref(BLUE)),
+                even_odd: true,
+            }]
+        );
+        assert_eq!(file.skipped.get("EMR_SAVEDC"), Some(&1));
+        assert_eq!(file.skipped_amount(), 1);
+        let svg = file.to_svg();
+        assert!(
+            svg.contains(r#"<rect x="10" y="20" width="100" height="50""#),
+            "{svg}"
+        );
+        assert!(svg.contains(r##"stroke="#ff0000""##), "{svg}");
+        assert!(svg.contains(r##"fill="#0000ff""##), "{svg}");
+    }
+
+    #[test]
+    fn load_wmf() {
+        let bytes = wmf_fixture();
+        assert_eq!(MetafileFile::detect(&bytes), Some(MetafileKind::Wmf));
+        let file = MetafileFile::load_from_bytes(&bytes).unwrap();
+        assert_eq!(file.view_box, (0.0, 0.0, 200.0, 100.0));
+        assert_eq!(file.size, None);
+        assert_eq!(
+            file.shapes,
+            vec![MetafileShape {
+                geometry: MetafileGeometry::Ellipse(10.0, 20.0, 110.0, 70.0),
+                stroke: Some((MetafileColor::from_colorref(GREEN), 3.0)),
+                fill: Some(MetafileColor::from_colorref(BLUE)),
+                even_odd: true,
+            }]
+        );
+        assert_eq!(file.skipped.get("META_TEXTOUT"), Some(&1));
+    }
+
+    #[test]
+    fn reject_malformed_records() {
+        // An object index outside of the handles of the header
+        let err = MetafileFile::load_from_bytes(&emf_fixture(3, 0x7fff_0000)).unwrap_err();
+        assert!(
+            err.to_string().contains("outside of the 3 handles"),
+            "{err}"
+        );
+        // A record size smaller than the record type and size fields
+        let mut bytes = emf_fixture(3, 1);
+        let header_size = 88;
+        bytes[header_size + 4..header_size + 8].copy_from_slice(&4_u32.to_le_bytes());
+        assert!(MetafileFile::load_from_bytes(&bytes).is_err());
+        // A Wmf record reaching past the end of the file
+        let mut bytes = wmf_fixture();
+        let header_size = 18;
+        bytes[header_size..header_size + 4].copy_from_slice(&1000_u32.to_le_bytes());
+        assert!(MetafileFile::load_from_bytes(&bytes).is_err());
+        assert!(MetafileFile::load_from_bytes(b"not a metafile").is_err());
+    }
+}
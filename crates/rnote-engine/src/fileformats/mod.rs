@@ -1,4 +1,14 @@
 // Modules
+pub mod csvformat;
+#[cfg(feature = "epub")]
+pub mod epubformat;
+#[cfg(feature = "goodnotes")]
+pub mod goodnotesformat;
+#[cfg(feature = "markdown")]
+pub mod markdownformat;
+#[cfg(feature = "metafile")]
+pub mod metafileformat;
+pub mod pdfformat;
 pub mod rnoteformat;
 pub mod xoppformat;
 
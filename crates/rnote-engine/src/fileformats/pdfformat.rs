@@ -0,0 +1,1253 @@
+//! Loading and saving the object structure of Pdf files.
+//!
+//! Used to rewrite exported Pdfs, e.g. to remove the document information dictionary. Cross-reference tables,
+//! cross-reference streams and compressed object streams are read. Saving writes all objects uncompressed with a
+//! cross-reference table. Encrypted Pdfs are not supported.
+
+// Imports
+use super::{FileFormatLoader, FileFormatSaver};
+use anyhow::Context;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+
+const PDF_HEADER: &[u8] = b"%PDF-";
+/// How far from the end of the file the `startxref` keyword is searched.
+const STARTXREF_SEARCH_LEN: usize = 1024;
+/// The trailer entries that describe the cross-reference sections, which are written anew when saving.
+const XREF_SECTION_KEYS: [&[u8]; 9] = [
+    b"Size",
+    b"Prev",
+    b"XRefStm",
+    b"Type",
+    b"W",
+    b"Index",
+    b"Length",
+    b"Filter",
+    b"DecodeParms",
+];
+
+/// A dictionary, keeping the order of its entries.
+pub type PdfDict = Vec<(Vec<u8>, PdfObject)>;
+
+/// A Pdf object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdfObject {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    /// A literal or hexadecimal string.
+    String(Vec<u8>),
+    /// A name, without the leading slash.
+    Name(Vec<u8>),
+    Array(Vec<PdfObject>),
+    Dict(PdfDict),
+    /// A stream with its dictionary and the encoded data.
+    Stream(PdfDict, Vec<u8>),
+    /// A reference to an indirect object, as object number and generation.
+    Reference(u32, u16),
+}
+
+impl PdfObject {
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            Self::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The dictionary of a dictionary or stream.
+    pub fn as_dict(&self) -> Option<&PdfDict> {
+        match self {
+            Self::Dict(dict) | Self::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+/// Looks up the value of the key in the dictionary.
+pub fn dict_get<'a>(dict: &'a PdfDict, key: &[u8]) -> Option<&'a PdfObject> {
+    dict.iter().find(|(k, _)| k == key).map(|(_, value)| value)
+}
+
+fn dict_remove(dict: &mut PdfDict, key: &[u8]) -> Option<PdfObject> {
+    let i = dict.iter().position(|(k, _)| k == key)?;
+    Some(dict.remove(i).1)
+}
+
+fn dict_set(dict: &mut PdfDict, key: &[u8], value: PdfObject) {
+    match dict.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) => *v = value,
+        None => dict.push((key.to_vec(), value)),
+    }
+}
+
+/// A Pdf file with its indirect objects.
+#[derive(Debug, Clone)]
+pub struct PdfFile {
+    /// The version of the header, e.g. "1.7".
+    pub version: String,
+    /// The trailer dictionary, without the entries that describe the cross-reference sections.
+    pub trailer: PdfDict,
+    /// The indirect objects with their generation, by their object number.
+    pub objects: BTreeMap<u32, (u16, PdfObject)>,
+}
+
+impl FileFormatLoader for PdfFile {
+    fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let version = bytes
+            .strip_prefix(PDF_HEADER)
+            .map(|rest| {
+                String::from_utf8_lossy(
+                    &rest[..rest
+                        .iter()
+                        .position(|b| is_whitespace(*b))
+                        .unwrap_or(rest.len())],
+                )
+                .to_string()
+            })
+            .context("The file does not start with a Pdf header.")?;
+        let mut reader = PdfReader {
+            data: bytes,
+            xref: HashMap::new(),
+        };
+        let mut trailer = reader.read_xref()?;
+        if dict_get(&trailer, b"Encrypt").is_some() {
+            return Err(anyhow::anyhow!("Encrypted Pdfs are not supported."));
+        }
+        trailer.retain(|(key, _)| !XREF_SECTION_KEYS.contains(&key.as_slice()));
+
+        let mut objects = BTreeMap::new();
+        let mut object_streams = HashMap::<u32, Vec<(u32, PdfObject)>>::new();
+        for (&num, &entry) in reader.xref.iter() {
+            let (obj_num, gen, object) =
+                match entry {
+                    None => continue,
+                    Some(XrefEntry::Offset(offset)) => reader
+                        .object_at(offset)
+                        .with_context(|| format!("Reading Pdf object {num} failed."))?,
+                    Some(XrefEntry::Compressed(stream_num, index)) => {
+                        let stream_objects = match object_streams.entry(stream_num) {
+                            Entry::Occupied(e) => e.into_mut(),
+                            Entry::Vacant(e) => {
+                                e.insert(reader.object_stream(stream_num).with_context(|| {
+                                    format!("Reading Pdf object stream {stream_num} failed.")
+                                })?)
+                            }
+                        };
+                        let (obj_num, object) = stream_objects.get(index).cloned().with_context(|| {
+                        format!("Pdf object stream {stream_num} has no object at index {index}.")
+                    })?;
+                        (obj_num, 0, object)
+                    }
+                };
+            if obj_num != num {
+                return Err(anyhow::anyhow!(
+                    "The cross-reference entry of Pdf object {num} points to object {obj_num}."
+                ));
+            }
+            objects.insert(num, (gen, object));
+        }
+        // The compressed objects and cross-references are written uncompressed when saving
+        objects.retain(|_, (_, object)| {
+            !matches!(
+                object,
+                PdfObject::Stream(dict, _)
+                    if matches!(
+                        dict_get(dict, b"Type").and_then(PdfObject::as_name),
+                        Some(b"ObjStm" | b"XRef")
+                    )
+            )
+        });
+
+        Ok(Self {
+            version,
+            trailer,
+            objects,
+        })
+    }
+}
+
+impl FileFormatSaver for PdfFile {
+    fn save_as_bytes(&self, _file_name: &str) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = format!("%PDF-{}\n", self.version).into_bytes();
+        // Marks the file as binary
+        bytes.extend_from_slice(b"%\xe2\xe3\xcf\xd3\n");
+
+        let size = self
+            .objects
+            .last_key_value()
+            .map(|(num, _)| num + 1)
+            .unwrap_or(1);
+        let mut offsets = vec![None; size as usize];
+        for (&num, (gen, object)) in self.objects.iter() {
+            offsets[num as usize] = Some((bytes.len(), *gen));
+            bytes.extend_from_slice(format!("{num} {gen} obj\n").as_bytes());
+            write_object(&mut bytes, object);
+            bytes.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = bytes.len();
+        bytes.extend_from_slice(format!("xref\n0 {size}\n").as_bytes());
+        for entry in offsets {
+            match entry {
+                // Entries are exactly 20 bytes long
+                Some((offset, gen)) => {
+                    bytes.extend_from_slice(format!("{offset:010} {gen:05} n\r\n").as_bytes())
+                }
+                None => bytes.extend_from_slice(b"0000000000 65535 f\r\n"),
+            }
+        }
+        let mut trailer = vec![(b"Size".to_vec(), PdfObject::Integer(i64::from(size)))];
+        trailer.extend(self.trailer.iter().cloned());
+        bytes.extend_from_slice(b"trailer\n");
+        write_object(&mut bytes, &PdfObject::Dict(trailer));
+        bytes.extend_from_slice(format!("\nstartxref\n{xref_offset}\n%%EOF\n").as_bytes());
+
+        Ok(bytes)
+    }
+}
+
+impl PdfFile {
+    /// Removes the document information dictionary with the title, producer, creation date, ..
+    ///
+    /// Returns whether the Pdf had one.
+    pub fn remove_info(&mut self) -> bool {
+        match dict_remove(&mut self.trailer, b"Info") {
+            Some(PdfObject::Reference(num, _)) => {
+                self.objects.remove(&num);
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+/// Where an indirect object is stored.
+#[derive(Debug, Clone, Copy)]
+enum XrefEntry {
+    /// The byte offset of an uncompressed object.
+    Offset(usize),
+    /// The object number of the object stream and the index in it of a compressed object.
+    Compressed(u32, usize),
+}
+
+/// The entries of a cross-reference section by object number, None for free objects.
+type XrefEntries = Vec<(u32, Option<XrefEntry>)>;
+
+/// Reads the cross-references and the objects of the Pdf.
+struct PdfReader<'a> {
+    data: &'a [u8],
+    /// The entries by object number, None for free objects.
+    xref: HashMap<u32, Option<XrefEntry>>,
+}
+
+impl PdfReader<'_> {
+    /// Reads all cross-reference sections, starting from the last one, and returns the trailer of the last.
+    fn read_xref(&mut self) -> anyhow::Result<PdfDict> {
+        let search_start = self.data.len().saturating_sub(STARTXREF_SEARCH_LEN);
+        let startxref = rfind(&self.data[search_start..], b"startxref")
+            .map(|i| i + search_start)
+            .context("The Pdf has no `startxref`.")?;
+        let mut lexer = Lexer::new(self.data, startxref + b"startxref".len());
+        let mut next = Some(lexer.offset()?);
+        let mut trailer = None;
+        let mut visited = HashSet::new();
+
+        while let Some(offset) = next.take() {
+            if !visited.insert(offset) {
+                return Err(anyhow::anyhow!(
+                    "The Pdf cross-reference sections loop back to offset {offset}."
+                ));
+            }
+            let (section_trailer, mut entries) = self.read_xref_section(offset)?;
+            // Hybrid files have an additional cross-reference stream that takes precedence over the table
+            if let Some(xref_stm) = dict_get(&section_trailer, b"XRefStm") {
+                let xref_stm = xref_stm
+                    .as_integer()
+                    .and_then(|offset| usize::try_from(offset).ok())
+                    .context("Invalid `XRefStm` offset in the Pdf trailer.")?;
+                let (_, stream_entries) = self.read_xref_section(xref_stm)?;
+                entries.splice(0..0, stream_entries);
+            }
+            // Newer sections come first and override the entries of older ones
+            for (num, entry) in entries {
+                self.xref.entry(num).or_insert(entry);
+            }
+            next = match dict_get(&section_trailer, b"Prev") {
+                Some(prev) => Some(
+                    prev.as_integer()
+                        .and_then(|offset| usize::try_from(offset).ok())
+                        .context("Invalid `Prev` offset in the Pdf trailer.")?,
+                ),
+                None => None,
+            };
+            trailer.get_or_insert(section_trailer);
+        }
+
+        trailer.context("The Pdf has no trailer.")
+    }
+
+    /// Reads the cross-reference table or stream at the offset.
+    fn read_xref_section(&self, offset: usize) -> anyhow::Result<(PdfDict, XrefEntries)> {
+        let mut lexer = Lexer::new(self.data, offset);
+        let mut entries = Vec::new();
+
+        if lexer.try_keyword(b"xref") {
+            while !lexer.try_keyword(b"trailer") {
+                let first = lexer.unsigned()?;
+                let count = lexer.unsigned()?;
+                let end = first
+                    .checked_add(count)
+                    .context("Invalid Pdf cross-reference subsection.")?;
+                for num in first..end {
+                    let offset = lexer.offset()?;
+                    let _gen = lexer.unsigned()?;
+                    lexer.skip_whitespace();
+                    let entry = match lexer.regular_token() {
+                        b"n" => Some(XrefEntry::Offset(offset)),
+                        b"f" => None,
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "Invalid Pdf cross-reference entry for object {num}."
+                            ))
+                        }
+                    };
+                    entries.push((u32::try_from(num)?, entry));
+                }
+            }
+            let PdfObject::Dict(trailer) = lexer.object()? else {
+                return Err(anyhow::anyhow!("The Pdf trailer is not a dictionary."));
+            };
+            return Ok((trailer, entries));
+        }
+
+        let (_, _, object) = self.object_at(offset)?;
+        let PdfObject::Stream(dict, data) = object else {
+            return Err(anyhow::anyhow!(
+                "No cross-reference table or stream at offset {offset} of the Pdf."
+            ));
+        };
+        if dict_get(&dict, b"Type").and_then(PdfObject::as_name) != Some(b"XRef") {
+            return Err(anyhow::anyhow!(
+                "The stream at offset {offset} of the Pdf is not a cross-reference stream."
+            ));
+        }
+        let data = decode_stream(&dict, &data)?;
+        let widths = match dict_get(&dict, b"W") {
+            Some(PdfObject::Array(widths)) if widths.len() == 3 => widths
+                .iter()
+                .map(|w| {
+                    w.as_integer()
+                        .and_then(|w| usize::try_from(w).ok())
+                        .filter(|w| *w <= 8)
+                })
+                .collect::<Option<Vec<usize>>>(),
+            _ => None,
+        }
+        .context("Invalid widths of the Pdf cross-reference stream.")?;
+        let index = match dict_get(&dict, b"Index") {
+            Some(PdfObject::Array(index)) => index
+                .chunks_exact(2)
+                .map(|pair| {
+                    Some((
+                        u64::try_from(pair[0].as_integer()?).ok()?,
+                        u64::try_from(pair[1].as_integer()?).ok()?,
+                    ))
+                })
+                .collect::<Option<Vec<(u64, u64)>>>(),
+            _ => dict_get(&dict, b"Size")
+                .and_then(PdfObject::as_integer)
+                .and_then(|size| u64::try_from(size).ok())
+                .map(|size| vec![(0, size)]),
+        }
+        .context("Invalid index of the Pdf cross-reference stream.")?;
+        let entry_len = widths.iter().sum::<usize>();
+        if entry_len == 0 {
+            return Err(anyhow::anyhow!(
+                "The Pdf cross-reference stream has empty entries."
+            ));
+        }
+
+        let field = |bytes: &[u8]| bytes.iter().fold(0u64, |v, b| (v << 8) | u64::from(*b));
+        let mut rows = data.chunks_exact(entry_len);
+        for (first, count) in index {
+            let end = first
+                .checked_add(count)
+                .context("Invalid Pdf cross-reference subsection.")?;
+            for num in first..end {
+                let row = rows
+                    .next()
+                    .context("The Pdf cross-reference stream is too short.")?;
+                let (kind, row) = row.split_at(widths[0]);
+                let (field2, field3) = row.split_at(widths[1]);
+                // The type defaults to uncompressed objects when omitted
+                let kind = if widths[0] == 0 { 1 } else { field(kind) };
+                let entry = match kind {
+                    1 => Some(XrefEntry::Offset(usize::try_from(field(field2))?)),
+                    2 => Some(XrefEntry::Compressed(
+                        u32::try_from(field(field2))?,
+                        usize::try_from(field(field3))?,
+                    )),
+                    // Free and unknown entries are treated as the null object
+                    _ => None,
+                };
+                entries.push((u32::try_from(num)?, entry));
+            }
+        }
+        Ok((dict, entries))
+    }
+
+    /// Reads the indirect object at the offset, returning its number, generation and the object.
+    fn object_at(&self, offset: usize) -> anyhow::Result<(u32, u16, PdfObject)> {
+        let mut lexer = Lexer::new(self.data, offset);
+        let num = u32::try_from(lexer.unsigned()?)?;
+        let gen = u16::try_from(lexer.unsigned()?)?;
+        lexer.expect_keyword(b"obj")?;
+        let object = match lexer.object()? {
+            PdfObject::Dict(mut dict) if lexer.try_keyword(b"stream") => {
+                let data = self.stream_data(&mut lexer, &dict)?;
+                // The length might be an indirect object
+                dict_set(&mut dict, b"Length", PdfObject::Integer(data.len() as i64));
+                PdfObject::Stream(dict, data)
+            }
+            object => object,
+        };
+        Ok((num, gen, object))
+    }
+
+    /// Reads the data of the stream that starts after the `stream` keyword.
+    fn stream_data(&self, lexer: &mut Lexer, dict: &PdfDict) -> anyhow::Result<Vec<u8>> {
+        // The keyword is followed by CRLF or LF
+        if lexer.data[lexer.pos..].starts_with(b"\r\n") {
+            lexer.pos += 2;
+        } else if matches!(lexer.peek(), Some(b'\n' | b'\r')) {
+            lexer.pos += 1;
+        }
+        let start = lexer.pos;
+        let len = match dict_get(dict, b"Length") {
+            Some(PdfObject::Integer(len)) => usize::try_from(*len).ok(),
+            Some(PdfObject::Reference(num, _)) => match self.xref.get(num) {
+                Some(Some(XrefEntry::Offset(offset))) => self
+                    .object_at(*offset)
+                    .ok()
+                    .and_then(|(_, _, len)| len.as_integer())
+                    .and_then(|len| usize::try_from(len).ok()),
+                _ => None,
+            },
+            _ => None,
+        };
+        // Only trust the length if the stream ends there, else search for the end
+        let end = len.and_then(|len| start.checked_add(len)).filter(|&end| {
+            end <= lexer.data.len() && Lexer::new(lexer.data, end).try_keyword(b"endstream")
+        });
+        let end = match end {
+            Some(end) => end,
+            None => {
+                let end = find(lexer.data, b"endstream", start)
+                    .context("The Pdf stream has no `endstream`.")?;
+                // The data is followed by an end of line before the keyword
+                let data = &lexer.data[start..end];
+                if data.ends_with(b"\r\n") {
+                    end - 2
+                } else if data.ends_with(b"\n") || data.ends_with(b"\r") {
+                    end - 1
+                } else {
+                    end
+                }
+            }
+        };
+        lexer.pos = end;
+        lexer.expect_keyword(b"endstream")?;
+        Ok(lexer.data[start..end].to_vec())
+    }
+
+    /// Reads the compressed objects of the object stream, with their object numbers.
+    fn object_stream(&self, num: u32) -> anyhow::Result<Vec<(u32, PdfObject)>> {
+        let Some(Some(XrefEntry::Offset(offset))) = self.xref.get(&num) else {
+            return Err(anyhow::anyhow!(
+                "The object stream is not an uncompressed object."
+            ));
+        };
+        let (_, _, PdfObject::Stream(dict, data)) = self.object_at(*offset)? else {
+            return Err(anyhow::anyhow!("The object stream is not a stream."));
+        };
+        let data = decode_stream(&dict, &data)?;
+        let count = dict_get(&dict, b"N")
+            .and_then(PdfObject::as_integer)
+            .and_then(|n| usize::try_from(n).ok())
+            .context("The object stream has no valid number of objects.")?;
+        let first = dict_get(&dict, b"First")
+            .and_then(PdfObject::as_integer)
+            .and_then(|first| usize::try_from(first).ok())
+            .context("The object stream has no valid offset of the first object.")?;
+
+        // The header holds pairs of the object number and the offset relative to the first object
+        let mut lexer = Lexer::new(&data, 0);
+        let headers = (0..count)
+            .map(|_| Ok((u32::try_from(lexer.unsigned()?)?, lexer.offset()?)))
+            .collect::<anyhow::Result<Vec<(u32, usize)>>>()?;
+        headers
+            .into_iter()
+            .map(|(num, offset)| {
+                let offset = first
+                    .checked_add(offset)
+                    .context("Invalid object offset in the object stream.")?;
+                Ok((num, Lexer::new(&data, offset).object()?))
+            })
+            .collect()
+    }
+}
+
+/// Decodes the data of the stream with its filters.
+fn decode_stream(dict: &PdfDict, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let filters = match dict_get(dict, b"Filter") {
+        None => vec![],
+        Some(PdfObject::Name(filter)) => vec![filter.as_slice()],
+        Some(PdfObject::Array(filters)) => filters
+            .iter()
+            .map(PdfObject::as_name)
+            .collect::<Option<Vec<&[u8]>>>()
+            .context("Invalid Pdf stream filters.")?,
+        Some(_) => return Err(anyhow::anyhow!("Invalid Pdf stream filter.")),
+    };
+    let params = |i: usize| match dict_get(dict, b"DecodeParms") {
+        Some(PdfObject::Dict(params)) if i == 0 => Some(params),
+        Some(PdfObject::Array(params)) => match params.get(i) {
+            Some(PdfObject::Dict(params)) => Some(params),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let mut data = data.to_vec();
+    for (i, filter) in filters.into_iter().enumerate() {
+        match filter {
+            b"FlateDecode" => {
+                let mut decoded = Vec::new();
+                flate2::read::ZlibDecoder::new(data.as_slice())
+                    .read_to_end(&mut decoded)
+                    .context("Inflating the Pdf stream failed.")?;
+                data = apply_png_predictor(decoded, params(i))?;
+            }
+            filter => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported Pdf stream filter `{}`.",
+                    String::from_utf8_lossy(filter)
+                ))
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// Reverses the Png predictors of the decoded data, if the parameters of the filter have one.
+fn apply_png_predictor(data: Vec<u8>, params: Option<&PdfDict>) -> anyhow::Result<Vec<u8>> {
+    let param = |key: &[u8], default: usize| -> anyhow::Result<usize> {
+        match params.and_then(|params| dict_get(params, key)) {
+            Some(value) => value
+                .as_integer()
+                .and_then(|value| usize::try_from(value).ok())
+                .filter(|value| *value > 0)
+                .with_context(|| {
+                    format!(
+                        "Invalid Pdf stream parameter `{}`.",
+                        String::from_utf8_lossy(key)
+                    )
+                }),
+            None => Ok(default),
+        }
+    };
+    let predictor = param(b"Predictor", 1)?;
+    if predictor == 1 {
+        return Ok(data);
+    }
+    if predictor < 10 {
+        return Err(anyhow::anyhow!(
+            "Unsupported Pdf stream predictor {predictor}."
+        ));
+    }
+    let bits_per_pixel = param(b"Colors", 1)?
+        .checked_mul(param(b"BitsPerComponent", 8)?)
+        .context("Invalid Pdf stream predictor parameters.")?;
+    let bytes_per_pixel = bits_per_pixel.div_ceil(8);
+    let row_len = bits_per_pixel
+        .checked_mul(param(b"Columns", 1)?)
+        .context("Invalid Pdf stream predictor parameters.")?
+        .div_ceil(8);
+
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut prev = vec![0u8; row_len];
+    // Every row starts with the byte of its Png filter type
+    for row in data.chunks(row_len + 1) {
+        let (&filter, row) = row.split_first().context("Empty Pdf stream row.")?;
+        let mut row = row.to_vec();
+        for i in 0..row.len() {
+            let left = if i >= bytes_per_pixel {
+                row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = prev[i];
+            let up_left = if i >= bytes_per_pixel {
+                prev[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Invalid Png filter type {filter} in Pdf stream."
+                    ))
+                }
+            };
+            row[i] = row[i].wrapping_add(predicted);
+        }
+        prev[..row.len()].copy_from_slice(&row);
+        decoded.extend_from_slice(&row);
+    }
+    Ok(decoded)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let (pa, pb, pc) = (
+        (p - i16::from(left)).abs(),
+        (p - i16::from(up)).abs(),
+        (p - i16::from(up_left)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// Reads tokens and direct objects.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Reads a keyword or number, which ends at whitespace or a delimiter.
+    fn regular_token(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| !is_whitespace(b) && !is_delimiter(b))
+        {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn expect_keyword(&mut self, keyword: &[u8]) -> anyhow::Result<()> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        let token = self.regular_token();
+        if token != keyword {
+            return Err(anyhow::anyhow!(
+                "Expected `{}` at offset {pos} of the Pdf, found `{}`.",
+                String::from_utf8_lossy(keyword),
+                String::from_utf8_lossy(token)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads the keyword if it follows, else stays at the position.
+    fn try_keyword(&mut self, keyword: &[u8]) -> bool {
+        let start = self.pos;
+        let found = self.expect_keyword(keyword).is_ok();
+        if !found {
+            self.pos = start;
+        }
+        found
+    }
+
+    fn unsigned(&mut self) -> anyhow::Result<u64> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        let token = self.regular_token();
+        std::str::from_utf8(token)
+            .ok()
+            .filter(|token| !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|token| token.parse().ok())
+            .with_context(|| format!("Expected an unsigned integer at offset {pos} of the Pdf."))
+    }
+
+    fn offset(&mut self) -> anyhow::Result<usize> {
+        Ok(usize::try_from(self.unsigned()?)?)
+    }
+
+    fn object(&mut self) -> anyhow::Result<PdfObject> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        match self.peek() {
+            None => Err(anyhow::anyhow!("Unexpected end of the Pdf.")),
+            Some(b'<') if self.data.get(pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                let mut dict = PdfDict::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.data[self.pos..].starts_with(b">>") {
+                        self.pos += 2;
+                        return Ok(PdfObject::Dict(dict));
+                    }
+                    let PdfObject::Name(key) = self.object()? else {
+                        return Err(anyhow::anyhow!(
+                            "Expected a dictionary key at offset {} of the Pdf.",
+                            self.pos
+                        ));
+                    };
+                    let value = self.object()?;
+                    dict.push((key, value));
+                }
+            }
+            Some(b'<') => {
+                self.pos += 1;
+                self.hex_string()
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                self.literal_string()
+            }
+            Some(b'/') => {
+                self.pos += 1;
+                Ok(PdfObject::Name(self.name()))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        return Ok(PdfObject::Array(items));
+                    }
+                    items.push(self.object()?);
+                }
+            }
+            Some(_) => match self.regular_token() {
+                b"true" => Ok(PdfObject::Bool(true)),
+                b"false" => Ok(PdfObject::Bool(false)),
+                b"null" => Ok(PdfObject::Null),
+                token => self.number(token, pos),
+            },
+        }
+    }
+
+    /// Parses the number token, or the reference it starts.
+    fn number(&mut self, token: &[u8], pos: usize) -> anyhow::Result<PdfObject> {
+        let text = std::str::from_utf8(token)
+            .ok()
+            .filter(|text| {
+                !text.is_empty()
+                    && text
+                        .bytes()
+                        .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.'))
+            })
+            .with_context(|| {
+                format!(
+                    "Unexpected `{}` at offset {pos} of the Pdf.",
+                    String::from_utf8_lossy(token)
+                )
+            })?;
+        if let Ok(integer) = text.parse::<i64>() {
+            // A reference is written as "<number> <generation> R"
+            if let Ok(num) = u32::try_from(integer) {
+                let start = self.pos;
+                if let Some(gen) = self.unsigned().ok().and_then(|gen| u16::try_from(gen).ok()) {
+                    if self.try_keyword(b"R") {
+                        return Ok(PdfObject::Reference(num, gen));
+                    }
+                }
+                self.pos = start;
+            }
+            return Ok(PdfObject::Integer(integer));
+        }
+        text.parse::<f64>()
+            .map(PdfObject::Real)
+            .with_context(|| format!("Invalid number `{text}` at offset {pos} of the Pdf."))
+    }
+
+    fn name(&mut self) -> Vec<u8> {
+        let token = self.regular_token();
+        let mut name = Vec::with_capacity(token.len());
+        let mut i = 0;
+        while i < token.len() {
+            // Other characters can be written as '#' followed by two hex digits
+            let escaped = (token[i] == b'#')
+                .then(|| token.get(i + 1..i + 3))
+                .flatten()
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(b) => {
+                    name.push(b);
+                    i += 3;
+                }
+                None => {
+                    name.push(token[i]);
+                    i += 1;
+                }
+            }
+        }
+        name
+    }
+
+    fn hex_string(&mut self) -> anyhow::Result<PdfObject> {
+        let mut digits = Vec::new();
+        loop {
+            let b = self.peek().context("Unterminated hex string in the Pdf.")?;
+            self.pos += 1;
+            match b {
+                b'>' => break,
+                b if is_whitespace(b) => {}
+                b => digits.push(
+                    (b as char)
+                        .to_digit(16)
+                        .context("Invalid hex digit in a Pdf string.")? as u8,
+                ),
+            }
+        }
+        // A missing last digit is zero
+        Ok(PdfObject::String(
+            digits
+                .chunks(2)
+                .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+                .collect(),
+        ))
+    }
+
+    fn literal_string(&mut self) -> anyhow::Result<PdfObject> {
+        let mut string = Vec::new();
+        // Balanced parentheses are part of the string
+        let mut depth = 1;
+        loop {
+            let b = self
+                .peek()
+                .context("Unterminated literal string in the Pdf.")?;
+            self.pos += 1;
+            match b {
+                b'\\' => {
+                    let escaped = self
+                        .peek()
+                        .context("Unterminated literal string in the Pdf.")?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(b'\x08'),
+                        b'f' => string.push(b'\x0c'),
+                        b'0'..=b'7' => {
+                            let mut value = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'7') => {
+                                        value = value * 8 + u32::from(digit - b'0');
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues the string on the next line
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => string.push(other),
+                    }
+                }
+                // Line endings are read as LF
+                b'\r' => {
+                    if self.peek() == Some(b'\n') {
+                        self.pos += 1;
+                    }
+                    string.push(b'\n');
+                }
+                b'(' => {
+                    depth += 1;
+                    string.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(PdfObject::String(string));
+                    }
+                    string.push(b);
+                }
+                b => string.push(b),
+            }
+        }
+    }
+}
+
+fn write_object(out: &mut Vec<u8>, object: &PdfObject) {
+    match object {
+        PdfObject::Null => out.extend_from_slice(b"null"),
+        PdfObject::Bool(true) => out.extend_from_slice(b"true"),
+        PdfObject::Bool(false) => out.extend_from_slice(b"false"),
+        PdfObject::Integer(integer) => out.extend_from_slice(integer.to_string().as_bytes()),
+        // Display of f64 never uses an exponent, which Pdf doesn't support
+        PdfObject::Real(real) => out.extend_from_slice(real.to_string().as_bytes()),
+        PdfObject::String(string) => {
+            out.push(b'<');
+            for b in string {
+                out.extend_from_slice(format!("{b:02x}").as_bytes());
+            }
+            out.push(b'>');
+        }
+        PdfObject::Name(name) => write_name(out, name),
+        PdfObject::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item);
+            }
+            out.push(b']');
+        }
+        PdfObject::Dict(dict) => write_dict(out, dict),
+        PdfObject::Stream(dict, data) => {
+            let mut dict = dict.clone();
+            dict_set(&mut dict, b"Length", PdfObject::Integer(data.len() as i64));
+            write_dict(out, &dict);
+            out.extend_from_slice(b"\nstream\n");
+            out.extend_from_slice(data);
+            out.extend_from_slice(b"\nendstream");
+        }
+        PdfObject::Reference(num, gen) => {
+            out.extend_from_slice(format!("{num} {gen} R").as_bytes())
+        }
+    }
+}
+
+fn write_dict(out: &mut Vec<u8>, dict: &PdfDict) {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict {
+        write_name(out, key);
+        out.push(b' ');
+        write_object(out, value);
+        out.push(b' ');
+    }
+    out.extend_from_slice(b">>");
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    out.push(b'/');
+    for &b in name {
+        if (0x21..=0x7e).contains(&b) && !is_delimiter(b) && b != b'#' {
+            out.push(b);
+        } else {
+            out.extend_from_slice(format!("#{b:02X}").as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dict_get, PdfFile, PdfObject};
+    use crate::fileformats::{FileFormatLoader, FileFormatSaver};
+    use std::io::Write;
+
+    const CATALOG: &[u8] = b"<< /Type /Catalog /Pages 2 0 R >>";
+    const PAGES: &[u8] = b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>";
+    const PAGE: &[u8] =
+        b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595.5 842] /Contents 4 0 R >>";
+    const CONTENT: &[u8] = b"0 0 m 10 10 l S";
+    const INFO: &[u8] =
+        b"<< /Title (secret \\(title\\)) /Producer <63616972> /CreationDate (D:20240101) >>";
+
+    /// Writes the objects of a Pdf, recording their offsets.
+    struct PdfWriter {
+        bytes: Vec<u8>,
+        offsets: Vec<(u32, usize)>,
+    }
+
+    impl PdfWriter {
+        fn new(version: &str) -> Self {
+            Self {
+                bytes: format!("%PDF-{version}\n").into_bytes(),
+                offsets: Vec::new(),
+            }
+        }
+
+        fn object(&mut self, num: u32, body: &[u8]) {
+            self.offsets.push((num, self.bytes.len()));
+            self.bytes
+                .extend_from_slice(format!("{num} 0 obj\n").as_bytes());
+            self.bytes.extend_from_slice(body);
+            self.bytes.extend_from_slice(b"\nendobj\n");
+        }
+
+        fn stream(&mut self, num: u32, dict: &str, data: &[u8]) {
+            let mut body = format!("<<{dict} /Length {}>>\nstream\n", data.len()).into_bytes();
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\nendstream");
+            self.object(num, &body);
+        }
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A Pdf with a cross-reference table, like older versions of cairo write.
+    fn pdf_w_xref_table(extra_trailer: &str) -> Vec<u8> {
+        let mut w = PdfWriter::new("1.5");
+        w.object(1, CATALOG);
+        w.object(2, PAGES);
+        w.object(3, PAGE);
+        // The length is an indirect object that follows the stream
+        let mut content = b"<< /Length 5 0 R >>\nstream\n".to_vec();
+        content.extend_from_slice(CONTENT);
+        content.extend_from_slice(b"\nendstream");
+        w.object(4, &content);
+        w.object(5, CONTENT.len().to_string().as_bytes());
+        w.object(6, INFO);
+
+        let xref_offset = w.bytes.len();
+        let mut xref = String::from("xref\n0 7\n0000000000 65535 f\r\n");
+        for (_, offset) in w.offsets.iter() {
+            xref += &format!("{offset:010} 00000 n\r\n");
+        }
+        xref += &format!(
+            "trailer\n<< /Size 7 /Root 1 0 R /Info 6 0 R{extra_trailer} >>\nstartxref\n{xref_offset}\n%%EOF\n"
+        );
+        w.bytes.extend_from_slice(xref.as_bytes());
+        w.bytes
+    }
+
+    /// A Pdf with a cross-reference stream and the catalog and information dictionary in an object stream.
+    fn pdf_w_xref_stream() -> Vec<u8> {
+        let mut w = PdfWriter::new("1.7");
+        w.object(2, PAGES);
+        w.object(3, PAGE);
+        w.stream(4, " /Filter /FlateDecode", &zlib(CONTENT));
+        let header = format!("1 0 6 {} ", CATALOG.len() + 1);
+        let mut objects = header.clone().into_bytes();
+        objects.extend_from_slice(CATALOG);
+        objects.push(b'\n');
+        objects.extend_from_slice(INFO);
+        w.stream(
+            7,
+            &format!(
+                " /Type /ObjStm /N 2 /First {} /Filter /FlateDecode",
+                header.len()
+            ),
+            &zlib(&objects),
+        );
+
+        // The rows are the type, the offset or object stream in two bytes and the generation or index
+        let xref_offset = w.bytes.len();
+        let offset = |num: u32| w.offsets.iter().find(|(n, _)| *n == num).unwrap().1;
+        let uncompressed = |offset: usize| [1, (offset >> 8) as u8, offset as u8, 0];
+        let rows = [
+            [0, 0, 0, 255],
+            [2, 0, 7, 0],
+            uncompressed(offset(2)),
+            uncompressed(offset(3)),
+            uncompressed(offset(4)),
+            [0, 0, 0, 0],
+            [2, 0, 7, 1],
+            uncompressed(offset(7)),
+            uncompressed(xref_offset),
+        ];
+        // Encoded with the Png up predictor
+        let mut data = Vec::new();
+        let mut prev = [0u8; 4];
+        for row in rows {
+            data.push(2);
+            data.extend(row.iter().zip(prev).map(|(b, up)| b.wrapping_sub(up)));
+            prev = row;
+        }
+        w.stream(
+            8,
+            " /Type /XRef /Size 9 /W [1 2 1] /Root 1 0 R /Info 6 0 R /Filter /FlateDecode \
+            /DecodeParms << /Predictor 12 /Columns 4 >>",
+            &zlib(&data),
+        );
+        w.bytes
+            .extend_from_slice(format!("startxref\n{xref_offset}\n%%EOF\n").as_bytes());
+        w.bytes
+    }
+
+    fn dict_entry<'a>(pdf_file: &'a PdfFile, num: u32, key: &[u8]) -> Option<&'a PdfObject> {
+        dict_get(pdf_file.objects.get(&num)?.1.as_dict()?, key)
+    }
+
+    #[test]
+    fn load_xref_table() {
+        let pdf_file = PdfFile::load_from_bytes(&pdf_w_xref_table("")).unwrap();
+
+        assert_eq!(pdf_file.version, "1.5");
+        assert_eq!(
+            pdf_file.objects.keys().copied().collect::<Vec<u32>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            dict_get(&pdf_file.trailer, b"Root"),
+            Some(&PdfObject::Reference(1, 0))
+        );
+        assert!(dict_get(&pdf_file.trailer, b"Size").is_none());
+        assert_eq!(
+            dict_entry(&pdf_file, 3, b"MediaBox"),
+            Some(&PdfObject::Array(vec![
+                PdfObject::Integer(0),
+                PdfObject::Integer(0),
+                PdfObject::Real(595.5),
+                PdfObject::Integer(842)
+            ]))
+        );
+        assert!(
+            matches!(&pdf_file.objects[&4].1, PdfObject::Stream(_, data) if data.as_slice() == CONTENT)
+        );
+        assert_eq!(
+            dict_entry(&pdf_file, 6, b"Title"),
+            Some(&PdfObject::String(b"secret (title)".to_vec()))
+        );
+        assert_eq!(
+            dict_entry(&pdf_file, 6, b"Producer"),
+            Some(&PdfObject::String(b"cair".to_vec()))
+        );
+    }
+
+    #[test]
+    fn load_xref_and_object_streams() {
+        let pdf_file = PdfFile::load_from_bytes(&pdf_w_xref_stream()).unwrap();
+
+        // The object and cross-reference streams are not kept
+        assert_eq!(
+            pdf_file.objects.keys().copied().collect::<Vec<u32>>(),
+            vec![1, 2, 3, 4, 6]
+        );
+        assert_eq!(
+            dict_get(&pdf_file.trailer, b"Info"),
+            Some(&PdfObject::Reference(6, 0))
+        );
+        assert!(dict_get(&pdf_file.trailer, b"W").is_none());
+        assert_eq!(
+            dict_entry(&pdf_file, 1, b"Type"),
+            Some(&PdfObject::Name(b"Catalog".to_vec()))
+        );
+        assert_eq!(
+            dict_entry(&pdf_file, 6, b"Title"),
+            Some(&PdfObject::String(b"secret (title)".to_vec()))
+        );
+        assert!(
+            matches!(&pdf_file.objects[&4].1, PdfObject::Stream(_, data) if *data == zlib(CONTENT))
+        );
+    }
+
+    #[test]
+    fn remove_info_and_save() {
+        for pdf in [pdf_w_xref_table(""), pdf_w_xref_stream()] {
+            let mut pdf_file = PdfFile::load_from_bytes(&pdf).unwrap();
+            assert!(pdf_file.remove_info());
+            assert!(!pdf_file.remove_info());
+
+            let saved = pdf_file.save_as_bytes("").unwrap();
+            let contains = |needle: &[u8]| saved.windows(needle.len()).any(|w| w == needle);
+            assert!(!contains(b"/Info"));
+            assert!(!contains(b"/Title"));
+            assert!(!contains(b"/Producer"));
+
+            let reloaded = PdfFile::load_from_bytes(&saved).unwrap();
+            assert_eq!(reloaded.trailer, pdf_file.trailer);
+            assert_eq!(reloaded.objects, pdf_file.objects);
+        }
+    }
+
+    #[test]
+    fn reject_malformed_pdfs() {
+        assert!(PdfFile::load_from_bytes(b"not a pdf").is_err());
+
+        let pdf = pdf_w_xref_table("");
+        let startxref = pdf.windows(9).rposition(|w| w == b"startxref").unwrap();
+        assert!(PdfFile::load_from_bytes(&pdf[..startxref]).is_err());
+
+        let mut pdf = pdf[..startxref].to_vec();
+        pdf.extend_from_slice(b"startxref\n9\n%%EOF\n");
+        assert!(PdfFile::load_from_bytes(&pdf).is_err());
+
+        // The previous section is the same one
+        let pdf = pdf_w_xref_table("");
+        let xref_offset = pdf.windows(6).rposition(|w| w == b"\nxref\n").unwrap() + 1;
+        assert!(
+            PdfFile::load_from_bytes(&pdf_w_xref_table(&format!(" /Prev {xref_offset}"))).is_err()
+        );
+    }
+}
@@ -3,7 +3,8 @@
 //! Older formats can be added, with the naming scheme `RnoteFileMaj<X>Min<Y>`,
 //! where X: semver major, Y: semver minor version.
 //!
-//! Then [TryFrom] can be implemented to allow conversions and chaining from older to newer versions.
+//! Then [TryFrom] can be implemented to allow conversions from older to newer versions.
+//! Each version bump gets its own migration function, which are applied step by step when loading a file.
 
 // Modules
 pub(crate) mod maj0min5patch8;
@@ -29,7 +30,7 @@ fn compress_to_gzip(to_compress: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
 }
 
 /// Decompress from gzip.
-fn decompress_from_gzip(compressed: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+pub(crate) fn decompress_from_gzip(compressed: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
     // Optimization for the gzip format, defined by RFC 1952
     // capacity of the vector defined by the size of the uncompressed data
     // given in little endian format, by the last 4 bytes of "compressed"
@@ -62,11 +63,116 @@ fn decompress_from_gzip(compressed: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
 /// Used to extract and match the version up front, before deserializing the data.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "rnotefile_wrapper")]
-struct RnotefileWrapper {
+pub(crate) struct RnotefileWrapper {
     #[serde(rename = "version")]
-    version: semver::Version,
+    pub(crate) version: semver::Version,
     #[serde(rename = "data")]
-    data: ijson::IValue,
+    pub(crate) data: ijson::IValue,
+}
+
+/// The migrations that were applied while loading a rnote file.
+#[derive(Debug, Clone)]
+pub struct RnoteFileMigration {
+    /// The version the file was saved with.
+    pub from_version: semver::Version,
+    /// The applied migration steps in order, e.g. `"0.5.8 -> 0.5.9"`.
+    ///
+    /// Empty if the file already was in the newest format.
+    pub steps: Vec<String>,
+}
+
+impl RnoteFileMigration {
+    /// Whether the file needed to be migrated.
+    pub fn migrated(&self) -> bool {
+        !self.steps.is_empty()
+    }
+}
+
+/// A rnote file in one of the supported format versions.
+#[derive(Debug)]
+enum VersionedRnoteFile {
+    Maj0Min5Patch8(RnoteFileMaj0Min5Patch8),
+    Maj0Min5Patch9(RnoteFileMaj0Min5Patch9),
+    Maj0Min6(RnoteFileMaj0Min6),
+    Maj0Min9(RnoteFileMaj0Min9),
+}
+
+impl VersionedRnoteFile {
+    /// Detects the format version from the stored version and deserializes the data in it.
+    fn from_wrapper(wrapper: &RnotefileWrapper) -> anyhow::Result<Self> {
+        let version = &wrapper.version;
+        let matches = |req: &str| semver::VersionReq::parse(req).unwrap().matches(version);
+
+        if matches(">=0.9.0") {
+            ijson::from_value(&wrapper.data)
+                .map(Self::Maj0Min9)
+                .with_context(|| {
+                    format!("deserializing RnoteFileMaj0Min9 (version {version}) failed.")
+                })
+        } else if matches(">=0.5.10") {
+            ijson::from_value(&wrapper.data)
+                .map(Self::Maj0Min6)
+                .with_context(|| {
+                    format!("deserializing RnoteFileMaj0Min6 (version {version}) failed.")
+                })
+        } else if matches(">=0.5.9") {
+            ijson::from_value(&wrapper.data)
+                .map(Self::Maj0Min5Patch9)
+                .with_context(|| {
+                    format!("deserializing RnoteFileMaj0Min5Patch9 (version {version}) failed.")
+                })
+        } else if matches(">=0.5.0") {
+            ijson::from_value(&wrapper.data)
+                .map(Self::Maj0Min5Patch8)
+                .with_context(|| {
+                    format!("deserializing RnoteFileMaj0Min5Patch8 (version {version}) failed.")
+                })
+        } else {
+            Err(anyhow::anyhow!(
+                "failed to load rnote file from bytes, unsupported version: {version}."
+            ))
+        }
+    }
+
+    /// The format version, as it appears in the name of the format struct.
+    fn format_version(&self) -> &'static str {
+        match self {
+            Self::Maj0Min5Patch8(_) => "0.5.8",
+            Self::Maj0Min5Patch9(_) => "0.5.9",
+            Self::Maj0Min6(_) => "0.6",
+            Self::Maj0Min9(_) => "0.9",
+        }
+    }
+
+    /// Migrates to the next format version. The newest format is returned unchanged.
+    fn migrate_step(self) -> anyhow::Result<Self> {
+        match self {
+            Self::Maj0Min5Patch8(file) => migrate_maj0min5patch8(file).map(Self::Maj0Min5Patch9),
+            Self::Maj0Min5Patch9(file) => migrate_maj0min5patch9(file).map(Self::Maj0Min6),
+            Self::Maj0Min6(file) => migrate_maj0min6(file).map(Self::Maj0Min9),
+            Self::Maj0Min9(file) => Ok(Self::Maj0Min9(file)),
+        }
+    }
+}
+
+/// Migrates from format version 0.5.8 to 0.5.9.
+fn migrate_maj0min5patch8(
+    file: RnoteFileMaj0Min5Patch8,
+) -> anyhow::Result<RnoteFileMaj0Min5Patch9> {
+    RnoteFileMaj0Min5Patch9::try_from(file)
+        .context("migrating RnoteFileMaj0Min5Patch8 to RnoteFileMaj0Min5Patch9 failed.")
+}
+
+/// Migrates from format version 0.5.9 to 0.6.
+fn migrate_maj0min5patch9(file: RnoteFileMaj0Min5Patch9) -> anyhow::Result<RnoteFileMaj0Min6> {
+    RnoteFileMaj0Min6::try_from(file)
+        .context("migrating RnoteFileMaj0Min5Patch9 to RnoteFileMaj0Min6 failed.")
+}
+
+/// Migrates from format version 0.6 to 0.9.
+fn migrate_maj0min6(file: RnoteFileMaj0Min6) -> anyhow::Result<RnoteFileMaj0Min9> {
+    RnoteFileMaj0Min9::try_from(file)
+        .context("migrating RnoteFileMaj0Min6 to RnoteFileMaj0Min9 failed.")
 }
 
 /// The Rnote file in the newest format version.
@@ -76,55 +182,64 @@ pub type RnoteFile = RnoteFileMaj0Min9;
 
 impl RnoteFile {
     pub const SEMVER: &'static str = crate::utils::crate_version();
+
+    /// Deserializes the data of the wrapper, converting older file format versions to the newest one.
+    pub(crate) fn load_from_wrapper(wrapper: RnotefileWrapper) -> anyhow::Result<Self> {
+        Self::load_from_wrapper_w_migration(wrapper).map(|(file, _)| file)
+    }
+
+    /// Deserializes the data of the wrapper and migrates older file format versions step by step to the newest one.
+    ///
+    /// Returns the file together with the applied migration steps.
+    pub(crate) fn load_from_wrapper_w_migration(
+        wrapper: RnotefileWrapper,
+    ) -> anyhow::Result<(Self, RnoteFileMigration)> {
+        let mut versioned = VersionedRnoteFile::from_wrapper(&wrapper)?;
+        let mut migration = RnoteFileMigration {
+            from_version: wrapper.version,
+            steps: vec![],
+        };
+
+        let rnote_file = loop {
+            match versioned {
+                VersionedRnoteFile::Maj0Min9(file) => break file,
+                older => {
+                    let from = older.format_version();
+                    versioned = older.migrate_step().with_context(|| {
+                        format!(
+                            "migrating file with version {} to the newest format failed.",
+                            migration.from_version
+                        )
+                    })?;
+                    migration
+                        .steps
+                        .push(format!("{from} -> {}", versioned.format_version()));
+                }
+            }
+        };
+
+        Ok((rnote_file, migration))
+    }
+
+    /// Loads the file from bytes and migrates older file format versions to the newest one.
+    ///
+    /// Returns the file together with the applied migration steps.
+    pub fn load_from_bytes_w_migration(bytes: &[u8]) -> anyhow::Result<(Self, RnoteFileMigration)> {
+        let decompressed = decompress_from_gzip(bytes).context("decompressing bytes failed.")?;
+        let wrapper =
+            serde_json::from_slice::<RnotefileWrapper>(&decompressed).with_context(|| {
+                format!(
+                    "deserializing RnotefileWrapper from {} decompressed bytes failed.",
+                    decompressed.len()
+                )
+            })?;
+        Self::load_from_wrapper_w_migration(wrapper)
+    }
 }
 
 impl FileFormatLoader for RnoteFile {
     fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
-        let wrapper = serde_json::from_slice::<RnotefileWrapper>(
-            &decompress_from_gzip(bytes).context("decompressing bytes failed.")?,
-        )
-        .context("deserializing RnotefileWrapper from bytes failed.")?;
-
-        // Conversions for older file format versions happen here
-        if semver::VersionReq::parse(">=0.9.0")
-            .unwrap()
-            .matches(&wrapper.version)
-        {
-            ijson::from_value::<RnoteFileMaj0Min9>(&wrapper.data)
-                .context("deserializing RnoteFileMaj0Min9 failed.")
-        } else if semver::VersionReq::parse(">=0.5.10")
-            .unwrap()
-            .matches(&wrapper.version)
-        {
-            ijson::from_value::<RnoteFileMaj0Min6>(&wrapper.data)
-                .context("deserializing RnoteFileMaj0Min6 failed.")
-                .and_then(RnoteFileMaj0Min9::try_from)
-                .context("converting RnoteFileMaj0Min6 to newest file version failed.")
-        } else if semver::VersionReq::parse(">=0.5.9")
-            .unwrap()
-            .matches(&wrapper.version)
-        {
-            ijson::from_value::<RnoteFileMaj0Min5Patch9>(&wrapper.data)
-                .context("deserializing RnoteFileMaj0Min5Patch9 failed.")
-                .and_then(RnoteFileMaj0Min6::try_from)
-                .and_then(RnoteFileMaj0Min9::try_from)
-                .context("converting RnoteFileMaj0Min5Patch9 to newest file version failed.")
-        } else if semver::VersionReq::parse(">=0.5.0")
-            .unwrap()
-            .matches(&wrapper.version)
-        {
-            ijson::from_value::<RnoteFileMaj0Min5Patch8>(&wrapper.data)
-                .context("deserializing RnoteFileMaj0Min5Patch8 failed")
-                .and_then(RnoteFileMaj0Min5Patch9::try_from)
-                .and_then(RnoteFileMaj0Min6::try_from)
-                .and_then(RnoteFileMaj0Min9::try_from)
-                .context("converting RnoteFileMaj0Min5Patch8 to newest file version failed.")
-        } else {
-            Err(anyhow::anyhow!(
-                "failed to load rnote file from bytes, unsupported version: {}.",
-                wrapper.version
-            ))
-        }
+        Self::load_from_bytes_w_migration(bytes).map(|(file, _)| file)
     }
 }
 
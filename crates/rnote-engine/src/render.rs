@@ -8,8 +8,10 @@ use p2d::bounding_volume::{Aabb, BoundingVolume};
 use piet::RenderContext;
 use rnote_compose::ext::AabbExt;
 use rnote_compose::shapes::{Rectangle, Shapeable};
-use rnote_compose::transform::Transformable;
+use rnote_compose::transform::{Transform, Transformable};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Cursor};
 use std::sync::Arc;
 use svg::Node;
@@ -31,11 +33,310 @@ pub const POINT_TO_PX_CONV_FACTOR: f64 = 72.0 / 96.0;
 /// Used when checking rendering for new zooms or a moved viewport.
 /// There is a trade off: a larger value will consume more memory, a smaller value will mean more stuttering on zooms and when moving the view.
 pub const VIEWPORT_EXTENTS_MARGIN_FACTOR: f64 = 0.4;
+/// The default overscan, the margin in pixels by which partially rendered stroke images extend beyond the viewport.
+///
+/// Added on top of the [VIEWPORT_EXTENTS_MARGIN_FACTOR], so the images keep a margin of a few pixels
+/// even when the viewport is small.
+pub const OVERSCAN_DEFAULT: f64 = 64.0;
+/// The signature every Png file starts with.
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The renderer that rasterizes Svgs into bitmap images.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "render_backend")]
+pub enum RenderBackend {
+    /// Cairo with librsvg.
+    #[default]
+    #[serde(rename = "cairo")]
+    #[cfg_attr(feature = "cli", value(name = "cairo"))]
+    Cairo,
+    /// Rasterizes with resvg and tiny-skia instead of librsvg.
+    ///
+    /// Only the final rasterization is swapped: the Svg of the content is still generated through cairo,
+    /// so exports with this backend depend on the same system libraries.
+    #[serde(rename = "tiny_skia")]
+    #[cfg_attr(feature = "cli", value(name = "tiny-skia"))]
+    TinySkia,
+}
+
+impl TryFrom<u32> for RenderBackend {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!("RenderBackend try_from::<u32>() for value {} failed", value)
+        })
+    }
+}
+
+impl std::fmt::Display for RenderBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cairo => write!(f, "cairo"),
+            Self::TinySkia => write!(f, "tiny-skia"),
+        }
+    }
+}
+
+/// How the colors are dithered when an image is quantized to a palette.
+///
+/// Dithering trades the bands of smooth gradients for a pattern of the palette colors.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "dither")]
+pub enum Dither {
+    /// Every pixel gets the nearest palette color.
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// Error diffusion, the quantization error of every pixel is distributed to its neighbours.
+    #[serde(rename = "floyd_steinberg")]
+    #[cfg_attr(feature = "cli", value(name = "floyd-steinberg"))]
+    FloydSteinberg,
+    /// A regular Bayer threshold pattern, which compresses better than error diffusion.
+    #[serde(rename = "ordered")]
+    Ordered,
+}
+
+impl TryFrom<u32> for Dither {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value)
+            .ok_or_else(|| anyhow::anyhow!("Dither try_from::<u32>() for value {} failed", value))
+    }
+}
+
+impl std::fmt::Display for Dither {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::FloydSteinberg => write!(f, "floyd-steinberg"),
+            Self::Ordered => write!(f, "ordered"),
+        }
+    }
+}
+
+impl Dither {
+    /// The 4x4 Bayer matrix, the thresholds are `(value + 0.5) / 16`.
+    const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+    /// The palette indices of the pixels, row by row.
+    fn palette_indices(self, imgbuf: &image::RgbaImage, quant: &color_quant::NeuQuant) -> Vec<u8> {
+        let (width, height) = (imgbuf.width() as usize, imgbuf.height() as usize);
+        let index_of =
+            |rgba: [f32; 4]| quant.index_of(&rgba.map(|c| c.round().clamp(0.0, 255.0) as u8)) as u8;
+        let to_f32 = |pixel: &image::Rgba<u8>| pixel.0.map(f32::from);
+
+        match self {
+            Self::None => imgbuf.pixels().map(|p| index_of(to_f32(p))).collect(),
+            Self::Ordered => {
+                // The pattern spreads over the distance between neighbouring palette colors,
+                // assuming they are evenly distributed in the color cube
+                let palette_len = quant.color_map_rgba().len() / 4;
+                let spread = 255.0 / ((palette_len as f32).cbrt() - 1.0).max(1.0);
+                imgbuf
+                    .enumerate_pixels()
+                    .map(|(x, y, p)| {
+                        let threshold =
+                            (f32::from(Self::BAYER_4X4[y as usize % 4][x as usize % 4]) + 0.5)
+                                / 16.0
+                                - 0.5;
+                        let mut rgba = to_f32(p);
+                        for c in rgba.iter_mut().take(3) {
+                            *c += threshold * spread;
+                        }
+                        index_of(rgba)
+                    })
+                    .collect()
+            }
+            Self::FloydSteinberg => {
+                let palette = quant.color_map_rgba();
+                let mut pixels = imgbuf.pixels().map(to_f32).collect::<Vec<[f32; 4]>>();
+                let mut indices = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let rgba = pixels[y * width + x];
+                        let index = index_of(rgba);
+                        indices.push(index);
+                        // The alpha channel is not diffused, to keep the edges of transparent areas sharp
+                        let mut error = [0.0; 3];
+                        for (c, e) in error.iter_mut().enumerate() {
+                            *e = rgba[c] - f32::from(palette[index as usize * 4 + c]);
+                        }
+                        let mut diffuse = |x: usize, y: usize, weight: f32| {
+                            if x < width && y < height {
+                                for (c, e) in error.iter().enumerate() {
+                                    pixels[y * width + x][c] += e * weight;
+                                }
+                            }
+                        };
+                        diffuse(x + 1, y, 7.0 / 16.0);
+                        if x > 0 {
+                            diffuse(x - 1, y + 1, 3.0 / 16.0);
+                        }
+                        diffuse(x, y + 1, 5.0 / 16.0);
+                        diffuse(x + 1, y + 1, 1.0 / 16.0);
+                    }
+                }
+                indices
+            }
+        }
+    }
+}
+
+/// The chroma subsampling of Jpeg images.
+///
+/// Subsampling stores the colors at a lower resolution than the brightness, which blurs the edges of colored ink.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "jpeg_subsampling")]
+pub enum JpegSubsampling {
+    /// The colors are stored at full resolution, preserving sharp colored edges at the cost of size.
+    #[serde(rename = "444")]
+    #[cfg_attr(feature = "cli", value(name = "444"))]
+    R444,
+    /// The colors are stored at half the horizontal resolution.
+    #[serde(rename = "422")]
+    #[cfg_attr(feature = "cli", value(name = "422"))]
+    R422,
+    /// The colors are stored at half the horizontal and vertical resolution, supported by every decoder.
+    #[default]
+    #[serde(rename = "420")]
+    #[cfg_attr(feature = "cli", value(name = "420"))]
+    R420,
+}
+
+impl TryFrom<u32> for JpegSubsampling {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "JpegSubsampling try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for JpegSubsampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::R444 => write!(f, "444"),
+            Self::R422 => write!(f, "422"),
+            Self::R420 => write!(f, "420"),
+        }
+    }
+}
+
+impl JpegSubsampling {
+    fn sampling_factor(self) -> jpeg_encoder::SamplingFactor {
+        match self {
+            Self::R444 => jpeg_encoder::SamplingFactor::R_4_4_4,
+            Self::R422 => jpeg_encoder::SamplingFactor::R_4_2_2,
+            Self::R420 => jpeg_encoder::SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+/// The color that is made transparent when removing the background of an image, see [Image::remove_background].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename = "background_removal")]
+pub enum BackgroundRemoval {
+    /// White, e.g. the paper of photographed handwriting.
+    #[default]
+    #[serde(rename = "white")]
+    White,
+    /// The color sampled from the border of the image, for paper that is not white.
+    #[serde(rename = "sampled")]
+    Sampled,
+}
+
+impl TryFrom<u32> for BackgroundRemoval {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        num_traits::FromPrimitive::from_u32(value).ok_or_else(|| {
+            anyhow::anyhow!(
+                "BackgroundRemoval try_from::<u32>() for value {} failed",
+                value
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for BackgroundRemoval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::White => write!(f, "white"),
+            Self::Sampled => write!(f, "sampled"),
+        }
+    }
+}
+
+impl BackgroundRemoval {
+    /// A threshold that removes the slightly uneven paper of photos while keeping light strokes.
+    pub const THRESHOLD_DEFAULT: u8 = 40;
+}
 
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ImageMemoryFormat {
     R8g8b8a8Premultiplied,
+    B8g8r8a8Premultiplied,
+    /// Rgba with straight, not premultiplied alpha.
+    R8g8b8a8,
+    /// Rgb without alpha, the pixels are opaque.
+    R8g8b8,
+    /// Rgba with straight alpha and 16 bit channels in little-endian byte order.
+    R16g16b16a16,
 }
 
 impl Default for ImageMemoryFormat {
@@ -44,12 +345,66 @@ impl Default for ImageMemoryFormat {
     }
 }
 
+impl ImageMemoryFormat {
+    /// The size of a single pixel in bytes.
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::R8g8b8a8Premultiplied | Self::B8g8r8a8Premultiplied | Self::R8g8b8a8 => 4,
+            Self::R8g8b8 => 3,
+            Self::R16g16b16a16 => 8,
+        }
+    }
+
+    /// Converts pixel data in this format to rgba8-premultiplied.
+    pub fn convert_to_r8g8b8a8_premultiplied(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::R8g8b8a8Premultiplied => data.to_vec(),
+            Self::B8g8r8a8Premultiplied => convert_image_bgra_to_rgba(0, 0, data.to_vec()),
+            Self::R8g8b8a8 => data
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let premultiply = |c: u8| ((u32::from(c) * u32::from(p[3]) + 127) / 255) as u8;
+                    [
+                        premultiply(p[0]),
+                        premultiply(p[1]),
+                        premultiply(p[2]),
+                        p[3],
+                    ]
+                })
+                .collect(),
+            Self::R8g8b8 => data
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], u8::MAX])
+                .collect(),
+            Self::R16g16b16a16 => data
+                .chunks_exact(8)
+                .flat_map(|p| {
+                    let channel =
+                        |i: usize| u32::from(u16::from_le_bytes([p[2 * i], p[2 * i + 1]]));
+                    let alpha = channel(3);
+                    // Premultiplies in 16 bit before reducing the precision
+                    let premultiply = |c: u32| (((c * alpha + 32767) / 65535 + 128) / 257) as u8;
+                    [
+                        premultiply(channel(0)),
+                        premultiply(channel(1)),
+                        premultiply(channel(2)),
+                        ((alpha + 128) / 257) as u8,
+                    ]
+                })
+                .collect(),
+        }
+    }
+}
+
 #[cfg(feature = "ui")]
 impl TryFrom<gtk4::gdk::MemoryFormat> for ImageMemoryFormat {
     type Error = anyhow::Error;
     fn try_from(value: gtk4::gdk::MemoryFormat) -> Result<Self, Self::Error> {
         match value {
             gtk4::gdk::MemoryFormat::R8g8b8a8Premultiplied => Ok(Self::R8g8b8a8Premultiplied),
+            gtk4::gdk::MemoryFormat::B8g8r8a8Premultiplied => Ok(Self::B8g8r8a8Premultiplied),
+            gtk4::gdk::MemoryFormat::R8g8b8a8 => Ok(Self::R8g8b8a8),
+            gtk4::gdk::MemoryFormat::R8g8b8 => Ok(Self::R8g8b8),
             _ => Err(anyhow::anyhow!(
                 "ImageMemoryFormat try_from() gdk::MemoryFormat failed, unsupported MemoryFormat `{:?}`",
                 value
@@ -59,20 +414,37 @@ impl TryFrom<gtk4::gdk::MemoryFormat> for ImageMemoryFormat {
 }
 
 #[cfg(feature = "ui")]
-impl From<ImageMemoryFormat> for gtk4::gdk::MemoryFormat {
-    fn from(value: ImageMemoryFormat) -> Self {
+impl TryFrom<ImageMemoryFormat> for gtk4::gdk::MemoryFormat {
+    type Error = anyhow::Error;
+    fn try_from(value: ImageMemoryFormat) -> Result<Self, Self::Error> {
         match value {
             ImageMemoryFormat::R8g8b8a8Premultiplied => {
-                gtk4::gdk::MemoryFormat::R8g8b8a8Premultiplied
+                Ok(gtk4::gdk::MemoryFormat::R8g8b8a8Premultiplied)
             }
+            ImageMemoryFormat::B8g8r8a8Premultiplied => {
+                Ok(gtk4::gdk::MemoryFormat::B8g8r8a8Premultiplied)
+            }
+            ImageMemoryFormat::R8g8b8a8 => Ok(gtk4::gdk::MemoryFormat::R8g8b8a8),
+            ImageMemoryFormat::R8g8b8 => Ok(gtk4::gdk::MemoryFormat::R8g8b8),
+            _ => Err(anyhow::anyhow!(
+                "gdk::MemoryFormat try_from() ImageMemoryFormat failed, unsupported ImageMemoryFormat `{:?}`",
+                value
+            )),
         }
     }
 }
 
-impl From<ImageMemoryFormat> for piet::ImageFormat {
-    fn from(value: ImageMemoryFormat) -> Self {
+impl TryFrom<ImageMemoryFormat> for piet::ImageFormat {
+    type Error = anyhow::Error;
+    fn try_from(value: ImageMemoryFormat) -> Result<Self, Self::Error> {
         match value {
-            ImageMemoryFormat::R8g8b8a8Premultiplied => piet::ImageFormat::RgbaPremul,
+            ImageMemoryFormat::R8g8b8a8Premultiplied => Ok(piet::ImageFormat::RgbaPremul),
+            ImageMemoryFormat::R8g8b8a8 => Ok(piet::ImageFormat::RgbaSeparate),
+            ImageMemoryFormat::R8g8b8 => Ok(piet::ImageFormat::Rgb),
+            _ => Err(anyhow::anyhow!(
+                "piet::ImageFormat try_from() ImageMemoryFormat failed, unsupported ImageMemoryFormat `{:?}`",
+                value
+            )),
         }
     }
 }
@@ -148,18 +520,18 @@ impl From<image::DynamicImage> for Image {
 impl Drawable for Image {
     /// Draw itself on a [piet::RenderContext].
     ///
-    /// Expects image to be in rgba8-premultiplied format, else drawing will fail.
+    /// Memory formats that piet does not support are converted on the fly.
     ///
     /// `image_scale` has no meaning here, because the bitamp is already provided.
     fn draw(&self, cx: &mut impl piet::RenderContext, _image_scale: f64) -> anyhow::Result<()> {
-        let piet_image_format = piet::ImageFormat::from(self.memory_format);
+        let (data, piet_image_format) = self.piet_image_data();
 
         cx.save().map_err(|e| anyhow::anyhow!("{e:?}"))?;
         let piet_image = cx
             .make_image(
                 self.pixel_width as usize,
                 self.pixel_height as usize,
-                &self.data,
+                &data,
                 piet_image_format,
             )
             .map_err(|e| anyhow::anyhow!("{e:?}"))?;
@@ -194,7 +566,10 @@ impl Image {
 
         if self.pixel_width == 0
             || self.pixel_height == 0
-            || self.data.len() as u32 != 4 * self.pixel_width * self.pixel_height
+            || self.data.len()
+                != self.memory_format.bytes_per_pixel()
+                    * self.pixel_width as usize
+                    * self.pixel_height as usize
         {
             Err(anyhow::anyhow!(
                 "Asserting image validity failed, invalid size or data."
@@ -204,9 +579,60 @@ impl Image {
         }
     }
 
+    /// Checks that the bytes are an encoded image that can be decoded, without decoding the pixel data.
+    ///
+    /// Recognizes the format like [Image::try_from_encoded_bytes] and reads only the header.
+    /// Returns the pixel width and height.
+    pub fn validate_encoded_bytes(bytes: &[u8]) -> anyhow::Result<(u32, u32)> {
+        let reader = ImageReader::new(io::Cursor::new(bytes)).with_guessed_format()?;
+        if reader.format().is_none() {
+            return Err(anyhow::anyhow!("The image format is not recognized."));
+        }
+        let (width, height) = reader
+            .into_dimensions()
+            .context("Reading the image header failed.")?;
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!("The image has no pixels."));
+        }
+        Ok((width, height))
+    }
+
     pub fn try_from_encoded_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
         let reader = ImageReader::new(io::Cursor::new(bytes)).with_guessed_format()?;
-        Ok(Image::from(reader.decode()?))
+        // The image crate rejects CMYK images or decodes them without converting the colors
+        if let Some(format) = reader.format() {
+            if let Some(rgba) = decode_cmyk_image(bytes, format)? {
+                return Ok(Image::from(image::DynamicImage::ImageRgba8(rgba)));
+            }
+        }
+        match reader.decode() {
+            Ok(dynamic_image) => Ok(Image::from(dynamic_image)),
+            Err(e) if png_is_truncated(bytes) => Err(anyhow::anyhow!(
+                "The Png image appears truncated, the data ends before the end of the image. Err: {e}"
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Like [Image::try_from_encoded_bytes], but recovers the decodable part of truncated Png images,
+    /// e.g. partially downloaded files.
+    ///
+    /// The rows that could not be decoded are filled with transparency.
+    /// Returns the amount of recovered rows as second element when the image was truncated.
+    pub fn try_from_encoded_bytes_allow_partial(
+        bytes: &[u8],
+    ) -> anyhow::Result<(Self, Option<u32>)> {
+        match Self::try_from_encoded_bytes(bytes) {
+            Ok(image) => Ok((image, None)),
+            Err(e) if png_is_truncated(bytes) => {
+                let (rgba, recovered_rows) = decode_partial_png(bytes).context(e)?;
+                Ok((
+                    Image::from(image::DynamicImage::ImageRgba8(rgba)),
+                    Some(recovered_rows),
+                ))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     pub fn try_from_cairo_surface(
@@ -227,27 +653,298 @@ impl Image {
         })
     }
 
+    /// The image data in a memory format that piet can draw.
+    ///
+    /// Data in formats that piet does not support is converted to rgba8-premultiplied.
+    pub fn piet_image_data(&self) -> (std::borrow::Cow<'_, [u8]>, piet::ImageFormat) {
+        match piet::ImageFormat::try_from(self.memory_format) {
+            Ok(piet_image_format) => (
+                std::borrow::Cow::Borrowed(&self.data[..]),
+                piet_image_format,
+            ),
+            Err(_) => (
+                std::borrow::Cow::Owned(
+                    self.memory_format
+                        .convert_to_r8g8b8a8_premultiplied(&self.data),
+                ),
+                piet::ImageFormat::RgbaPremul,
+            ),
+        }
+    }
+
+    /// Converts the image data to the rgba8-premultiplied memory format.
+    pub fn convert_to_r8g8b8a8_premultiplied(&mut self) {
+        if self.memory_format != ImageMemoryFormat::R8g8b8a8Premultiplied {
+            self.data = glib::Bytes::from_owned(
+                self.memory_format
+                    .convert_to_r8g8b8a8_premultiplied(&self.data),
+            );
+            self.memory_format = ImageMemoryFormat::R8g8b8a8Premultiplied;
+        }
+    }
+
+    /// Converts the image into an image buffer with rgba8-premultiplied data.
     pub fn into_imgbuf(
-        self,
+        mut self,
     ) -> Result<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>, anyhow::Error> {
         self.assert_valid()?;
+        let memory_format = self.memory_format;
+        self.convert_to_r8g8b8a8_premultiplied();
 
-        match self.memory_format {
-            ImageMemoryFormat::R8g8b8a8Premultiplied => {
-                image::RgbaImage::from_vec(self.pixel_width, self.pixel_height, self.data.to_vec())
-                    .ok_or_else(|| {
-                        anyhow::anyhow!(
+        image::RgbaImage::from_vec(self.pixel_width, self.pixel_height, self.data.to_vec())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
                     "Creating RgbaImage from data failed for image with memory-format {:?}.",
-                    self.memory_format
+                    memory_format
                 )
-                    })
+            })
+    }
+
+    /// A hash of the pixels, equal for images with identical dimensions, memory format and pixel data.
+    ///
+    /// Used to detect duplicate images, e.g. repeated blank pages when importing Pdfs.
+    pub fn pixels_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.pixel_width.hash(&mut hasher);
+        self.pixel_height.hash(&mut hasher);
+        std::mem::discriminant(&self.memory_format).hash(&mut hasher);
+        self.data.as_ref().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Lifts near-white pixels to pure white, removing faint bleed-through from the back of scanned pages
+    /// and scanner noise.
+    ///
+    /// Pixels with all color channels at or above the threshold become white, their transparency is kept.
+    /// The image is converted to the rgba8-premultiplied memory format.
+    pub fn whiten(&mut self, threshold: u8) {
+        self.convert_to_r8g8b8a8_premultiplied();
+        let mut data = self.data.to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = u32::from(pixel[3]);
+            // Compares the unpremultiplied channels without dividing
+            if alpha > 0
+                && pixel[..3]
+                    .iter()
+                    .all(|c| u32::from(*c) * 255 >= u32::from(threshold) * alpha)
+            {
+                pixel[..3].fill(alpha as u8);
+            }
+        }
+        self.data = glib::Bytes::from_owned(data);
+    }
+
+    /// Inverts the color channels, e.g. to turn photographed chalkboards into dark writing on a light background.
+    ///
+    /// The transparency is kept. The image is converted to the rgba8-premultiplied memory format.
+    pub fn invert_colors(&mut self) {
+        self.convert_to_r8g8b8a8_premultiplied();
+        let mut data = self.data.to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3];
+            // The premultiplied inverse of `c * alpha` is `(1 - c) * alpha`
+            for c in pixel[..3].iter_mut() {
+                *c = alpha.saturating_sub(*c);
+            }
+        }
+        self.data = glib::Bytes::from_owned(data);
+    }
+
+    /// Applies a gamma correction to the color channels, mapping every channel `c` in `0.0..=1.0` to `c^(1/gamma)`.
+    ///
+    /// A gamma above 1.0 brightens the midtones, below 1.0 darkens them. Black, white and the transparency are kept.
+    /// This is an advanced knob for matching the colors to a specific display or print pipeline.
+    /// The image is converted to the rgba8-premultiplied memory format.
+    pub fn apply_gamma(&mut self, gamma: f64) {
+        if gamma == 1.0 || !gamma.is_finite() || gamma <= 0.0 {
+            return;
+        }
+        self.convert_to_r8g8b8a8_premultiplied();
+        let lut: [u8; 256] =
+            std::array::from_fn(|c| ((c as f64 / 255.0).powf(gamma.recip()) * 255.0).round() as u8);
+        let mut data = self.data.to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = u32::from(pixel[3]);
+            if alpha == 0 {
+                continue;
+            }
+            // The gamma is applied to the unpremultiplied channels
+            for c in pixel[..3].iter_mut() {
+                let unpremultiplied = ((u32::from(*c) * 255 + alpha / 2) / alpha).min(255);
+                *c = ((u32::from(lut[unpremultiplied as usize]) * alpha + 127) / 255) as u8;
+            }
+        }
+        self.data = glib::Bytes::from_owned(data);
+    }
+
+    /// Makes the pixels near the background color fully transparent, so that the image composites over other content.
+    ///
+    /// Pixels where every unpremultiplied color channel differs by at most the threshold from the background color
+    /// are removed. Returns the background color, or `None` when it could not be sampled from the image border.
+    /// The image is converted to the rgba8-premultiplied memory format.
+    pub fn remove_background(
+        &mut self,
+        removal: BackgroundRemoval,
+        threshold: u8,
+    ) -> Option<[u8; 3]> {
+        self.convert_to_r8g8b8a8_premultiplied();
+        let background = match removal {
+            BackgroundRemoval::White => [255, 255, 255],
+            BackgroundRemoval::Sampled => self.sample_border_color()?,
+        };
+        let mut data = self.data.to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = u32::from(pixel[3]);
+            if alpha > 0
+                && pixel[..3].iter().zip(background).all(|(c, b)| {
+                    (u32::from(*c) * 255 / alpha).abs_diff(u32::from(b)) <= u32::from(threshold)
+                })
+            {
+                // Premultiplied colors must be zero when the pixel is fully transparent
+                pixel.fill(0);
+            }
+        }
+        self.data = glib::Bytes::from_owned(data);
+        Some(background)
+    }
+
+    /// The median of the unpremultiplied colors of the opaque pixels on the border of the image.
+    ///
+    /// Expects the image in the rgba8-premultiplied memory format.
+    fn sample_border_color(&self) -> Option<[u8; 3]> {
+        let (width, height) = (self.pixel_width as usize, self.pixel_height as usize);
+        let pixel_at = |x: usize, y: usize| {
+            let i = (y * width + x) * 4;
+            &self.data[i..i + 4]
+        };
+        let border = (0..width)
+            .flat_map(|x| [(x, 0), (x, height.saturating_sub(1))])
+            .chain((0..height).flat_map(|y| [(0, y), (width.saturating_sub(1), y)]));
+        let mut channels: [Vec<u8>; 3] = Default::default();
+        for (x, y) in border {
+            let pixel = pixel_at(x, y);
+            if pixel[3] < 255 {
+                continue;
+            }
+            for (channel, c) in channels.iter_mut().zip(&pixel[..3]) {
+                channel.push(*c);
             }
         }
+        if channels[0].is_empty() {
+            return None;
+        }
+        Some(channels.map(|mut channel| {
+            let mid = channel.len() / 2;
+            *channel.select_nth_unstable(mid).1
+        }))
+    }
+
+    /// Detects the angle in radians that the text lines or the ruling of a scanned page are rotated by,
+    /// searched within `-max_angle..=max_angle`. Positive angles are clockwise.
+    ///
+    /// The angle is found where the dark pixels projected onto the vertical axis form the sharpest peaks.
+    /// Returns `None` for images without enough dark content to determine the angle.
+    pub fn detect_skew_angle(&self, max_angle: f64) -> anyhow::Result<Option<f64>> {
+        /// Pixels with a lightness below this value are considered content.
+        const DARK_THRESHOLD: u32 = 128;
+        /// Large images are sampled down to about this size, which is sufficient for the detection.
+        const ANALYSIS_SIZE: u32 = 1000;
+        const DARK_PIXELS_MIN: usize = 100;
+        const COARSE_STEP: f64 = 0.5 * std::f64::consts::PI / 180.0;
+        const FINE_STEP: f64 = 0.05 * std::f64::consts::PI / 180.0;
+
+        let max_angle = max_angle.abs();
+        let imgbuf = self.clone().into_imgbuf()?;
+        let stride = (imgbuf.width().max(imgbuf.height()) / ANALYSIS_SIZE).max(1);
+        let center = na::vector![
+            f64::from(imgbuf.width()) * 0.5,
+            f64::from(imgbuf.height()) * 0.5
+        ];
+        let dark_pixels = (0..imgbuf.height())
+            .step_by(stride as usize)
+            .flat_map(|y| {
+                (0..imgbuf.width())
+                    .step_by(stride as usize)
+                    .map(move |x| (x, y))
+            })
+            .filter(|(x, y)| {
+                let pixel = imgbuf.get_pixel(*x, *y).0;
+                // Composited against white, the channels are premultiplied
+                let lightness = pixel[..3].iter().map(|c| u32::from(*c)).sum::<u32>() / 3 + 255
+                    - u32::from(pixel[3]);
+                lightness < DARK_THRESHOLD
+            })
+            .map(|(x, y)| (na::vector![f64::from(x), f64::from(y)] - center) / f64::from(stride))
+            .collect::<Vec<na::Vector2<f64>>>();
+        if dark_pixels.len() < DARK_PIXELS_MIN {
+            return Ok(None);
+        }
+
+        let half_diagonal = (center / f64::from(stride)).norm().ceil();
+        let rows = 2 * half_diagonal as usize + 2;
+        let mut profile = vec![0_u64; rows];
+        let mut sharpness = |angle: f64| {
+            let (sin, cos) = angle.sin_cos();
+            profile.fill(0);
+            for pixel in dark_pixels.iter() {
+                let row = (pixel[1] * cos - pixel[0] * sin + half_diagonal).round() as usize;
+                profile[row.min(rows - 1)] += 1;
+            }
+            profile.iter().map(|count| count * count).sum::<u64>()
+        };
+        let mut search = |start: f64, end: f64, step: f64| {
+            let steps = ((end - start) / step).round() as i64;
+            (0..=steps)
+                .map(|i| (start + i as f64 * step).clamp(-max_angle, max_angle))
+                .map(|angle| (angle, sharpness(angle)))
+                // Equally sharp angles are resolved to the smaller correction
+                .max_by(|(a_angle, a), (b_angle, b)| {
+                    a.cmp(b).then(b_angle.abs().total_cmp(&a_angle.abs()))
+                })
+                .map(|(angle, _)| angle)
+                .unwrap_or(0.0)
+        };
+        let coarse_angle = search(-max_angle, max_angle, COARSE_STEP);
+        let angle = search(
+            coarse_angle - COARSE_STEP,
+            coarse_angle + COARSE_STEP,
+            FINE_STEP,
+        );
+
+        Ok(Some(angle))
+    }
+
+    /// Downsamples the image by the given factor. Used to supersample renderings.
+    ///
+    /// The rectangle of the image is kept, only the pixel size shrinks. Factors <= 1.0 return the image unchanged.
+    pub fn downsample(self, factor: f64) -> anyhow::Result<Self> {
+        if factor <= 1.0 {
+            return Ok(self);
+        }
+        let rect = self.rect;
+        let pixel_width = ((f64::from(self.pixel_width) / factor).round() as u32).max(1);
+        let pixel_height = ((f64::from(self.pixel_height) / factor).round() as u32).max(1);
+        // Averaging premultiplied pixels does not bleed the color of fully transparent pixels
+        let downsampled = image::imageops::resize(
+            &self.into_imgbuf()?,
+            pixel_width,
+            pixel_height,
+            image::imageops::FilterType::Triangle,
+        );
+
+        Ok(Self {
+            data: glib::Bytes::from_owned(downsampled.into_raw()),
+            rect,
+            pixel_width,
+            pixel_height,
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        })
     }
 
     /// Encodes the image into the provided format.
     ///
     /// When the format is `Jpeg`, the quality should be provided, but falls back to 93 if it is None.
+    /// Jpeg images are encoded with the default chroma subsampling, see [Image::into_jpeg_bytes].
     pub fn into_encoded_bytes(
         self,
         format: image::ImageFormat,
@@ -255,41 +952,183 @@ impl Image {
     ) -> Result<Vec<u8>, anyhow::Error> {
         const QUALITY_FALLBACK: u8 = 93;
 
+        if format == image::ImageFormat::Jpeg {
+            return self.into_jpeg_bytes(
+                quality.unwrap_or(QUALITY_FALLBACK),
+                JpegSubsampling::default(),
+            );
+        }
         self.assert_valid()?;
         let mut bytes_buf: Cursor<Vec<u8>> = Cursor::new(Vec::new());
         let dynamic_image = image::DynamicImage::ImageRgba8(
             self.into_imgbuf()
                 .context("Converting image to image::ImageBuffer failed.")?,
         );
-        match format {
-            image::ImageFormat::Jpeg => {
-                image::codecs::jpeg::JpegEncoder::new_with_quality(
-                    &mut bytes_buf,
-                    quality.map(|q| q.clamp(0, 100)).unwrap_or(QUALITY_FALLBACK),
-                )
-                .encode_image(&dynamic_image)
-                .context("Encode dynamic image to jpeg failed.")?;
-            }
-            format => {
-                dynamic_image
-                    .write_to(&mut bytes_buf, format)
-                    .context("Encode dynamic image to format '{format}' failed.")?;
+        dynamic_image
+            .write_to(&mut bytes_buf, format)
+            .context("Encode dynamic image to format '{format}' failed.")?;
+
+        Ok(bytes_buf.into_inner())
+    }
+
+    /// Encodes the image as Jpeg with the given quality (clamped to 1 - 100) and chroma subsampling.
+    ///
+    /// The alpha channel is discarded.
+    pub fn into_jpeg_bytes(
+        self,
+        quality: u8,
+        subsampling: JpegSubsampling,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        self.assert_valid()?;
+        let (Ok(width), Ok(height)) = (
+            u16::try_from(self.pixel_width),
+            u16::try_from(self.pixel_height),
+        ) else {
+            return Err(anyhow::anyhow!(
+                "Image with size {}x{} exceeds the maximum Jpeg size of {}x{}.",
+                self.pixel_width,
+                self.pixel_height,
+                u16::MAX,
+                u16::MAX
+            ));
+        };
+        let imgbuf = self
+            .into_imgbuf()
+            .context("Converting image to image::ImageBuffer failed.")?;
+        let mut bytes = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, quality.clamp(1, 100));
+        encoder.set_sampling_factor(subsampling.sampling_factor());
+        encoder
+            .encode(
+                imgbuf.as_raw(),
+                width,
+                height,
+                jpeg_encoder::ColorType::Rgba,
+            )
+            .context("Encode image to jpeg failed.")?;
+        Ok(bytes)
+    }
+
+    /// Encodes the image as Png, embedding the pixel dimensions as pHYs chunk when given.
+    pub fn into_png_bytes(
+        self,
+        pixel_dims: Option<png::PixelDimensions>,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        self.assert_valid()?;
+        let (width, height) = (self.pixel_width, self.pixel_height);
+        let imgbuf = self
+            .into_imgbuf()
+            .context("Converting image to image::ImageBuffer failed.")?;
+        let mut bytes_buf: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes_buf, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(pixel_dims);
+        let mut writer = encoder
+            .write_header()
+            .context("Writing Png header failed.")?;
+        writer
+            .write_image_data(imgbuf.as_raw())
+            .context("Writing Png image data failed.")?;
+        writer.finish().context("Finishing Png failed.")?;
+
+        Ok(bytes_buf)
+    }
+
+    /// The supported range of palette sizes for [Image::into_indexed_png_bytes].
+    pub const PALETTE_COLORS_RANGE: std::ops::RangeInclusive<u16> = 2..=256;
+
+    /// Quantizes the image to a palette with the given amount of colors and encodes it as indexed-color Png.
+    ///
+    /// The colors are dithered with the given method. The smallest bit depth that can hold the palette indices is
+    /// used. The pixel dimensions are embedded as pHYs chunk when given.
+    pub fn into_indexed_png_bytes(
+        self,
+        colors: u16,
+        dither: Dither,
+        pixel_dims: Option<png::PixelDimensions>,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        if !Self::PALETTE_COLORS_RANGE.contains(&colors) {
+            return Err(anyhow::anyhow!(
+                "Palette must have between {} and {} colors, got {colors}.",
+                Self::PALETTE_COLORS_RANGE.start(),
+                Self::PALETTE_COLORS_RANGE.end()
+            ));
+        }
+        let (width, height) = (self.pixel_width, self.pixel_height);
+        let imgbuf = self
+            .into_imgbuf()
+            .context("Converting image to image::ImageBuffer failed.")?;
+        let quant = color_quant::NeuQuant::new(10, colors as usize, imgbuf.as_raw());
+        let palette_rgba = quant.color_map_rgba();
+        let bit_depth = match palette_rgba.len() / 4 {
+            0..=2 => png::BitDepth::One,
+            3..=4 => png::BitDepth::Two,
+            5..=16 => png::BitDepth::Four,
+            _ => png::BitDepth::Eight,
+        };
+        let bits = bit_depth as usize;
+
+        // Pack the palette indices of every row into bytes, with the leftmost pixel in the high-order bits
+        let row_len = (width as usize * bits).div_ceil(8);
+        let mut data = vec![0u8; row_len * height as usize];
+        let indices = dither.palette_indices(&imgbuf, &quant);
+        for (y, row) in indices.chunks_exact(width as usize).enumerate() {
+            for (x, index) in row.iter().enumerate() {
+                let bit_offset = x * bits;
+                data[y * row_len + bit_offset / 8] |= index << (8 - bits - bit_offset % 8);
             }
         }
 
-        Ok(bytes_buf.into_inner())
+        let mut bytes_buf: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes_buf, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(bit_depth);
+        encoder.set_pixel_dims(pixel_dims);
+        encoder.set_palette(
+            palette_rgba
+                .chunks_exact(4)
+                .flat_map(|c| [c[0], c[1], c[2]])
+                .collect::<Vec<u8>>(),
+        );
+        encoder.set_trns(
+            palette_rgba
+                .chunks_exact(4)
+                .map(|c| c[3])
+                .collect::<Vec<u8>>(),
+        );
+        let mut writer = encoder
+            .write_header()
+            .context("Writing indexed Png header failed.")?;
+        writer
+            .write_image_data(&data)
+            .context("Writing indexed Png image data failed.")?;
+        writer.finish().context("Finishing indexed Png failed.")?;
+
+        Ok(bytes_buf)
     }
 
     #[cfg(feature = "ui")]
     pub fn to_memtexture(&self) -> Result<gtk4::gdk::MemoryTexture, anyhow::Error> {
         self.assert_valid()?;
 
+        let (data, memory_format) = match gtk4::gdk::MemoryFormat::try_from(self.memory_format) {
+            Ok(_) => (self.data.clone(), self.memory_format),
+            // Formats gdk does not support are converted on the fly
+            Err(_) => (
+                glib::Bytes::from_owned(
+                    self.memory_format
+                        .convert_to_r8g8b8a8_premultiplied(&self.data),
+                ),
+                ImageMemoryFormat::R8g8b8a8Premultiplied,
+            ),
+        };
         Ok(gtk4::gdk::MemoryTexture::new(
             self.pixel_width as i32,
             self.pixel_height as i32,
-            self.memory_format.into(),
-            &self.data,
-            (self.pixel_width * 4) as usize,
+            memory_format.try_into()?,
+            &data,
+            self.pixel_width as usize * memory_format.bytes_per_pixel(),
         ))
     }
 
@@ -444,6 +1283,37 @@ impl Svg {
         self.svg_data = rnote_compose::utils::remove_xml_header(&self.svg_data);
     }
 
+    /// Rotate the Svg clockwise by 90° around its bounds, swapping its extents.
+    ///
+    /// The bounds mins stay in place.
+    pub fn rotate_90deg(&mut self) -> anyhow::Result<()> {
+        let mins = self.bounds.mins;
+        let extents = self.bounds.extents();
+        let mut group =
+            svg::node::element::Group::new().add(svg::node::Blob::new(self.svg_data.clone()));
+        group.assign(
+            "transform",
+            format!(
+                "translate({} {}) rotate(90)",
+                mins[0] + mins[1] + extents[1],
+                mins[1] - mins[0]
+            ),
+        );
+        self.svg_data = rnote_compose::utils::svg_node_to_string(&group)?;
+        self.bounds = Aabb::new(mins, mins + na::vector![extents[1], extents[0]]);
+        Ok(())
+    }
+
+    /// Apply the transform to the Svg, the bounds become the transformed bounds.
+    pub fn transform(&mut self, transform: &Transform) -> anyhow::Result<()> {
+        let mut group =
+            svg::node::element::Group::new().add(svg::node::Blob::new(self.svg_data.clone()));
+        group.assign("transform", transform.to_svg_transform_attr_str());
+        self.svg_data = rnote_compose::utils::svg_node_to_string(&group)?;
+        self.bounds = transform.transform_aabb(self.bounds);
+        Ok(())
+    }
+
     /// Simplify the Svg by passing it through [usvg].
     ///
     /// Also moves the bounds to mins: [0., 0.], maxs: extents
@@ -584,6 +1454,34 @@ impl Svg {
     ///
     /// Using rsvg for rendering.
     pub fn gen_image(&self, image_scale: f64) -> Result<Image, anyhow::Error> {
+        self.gen_image_w_backend(image_scale, RenderBackend::Cairo)
+    }
+
+    /// Generates a bitmap image of the Svg with the given render backend.
+    ///
+    /// The backend only rasterizes the already generated Svg.
+    pub fn gen_image_w_backend(
+        &self,
+        image_scale: f64,
+        render_backend: RenderBackend,
+    ) -> Result<Image, anyhow::Error> {
+        self.gen_image_w_backend_xy(na::Vector2::repeat(image_scale), render_backend)
+    }
+
+    /// Generates a bitmap image of the Svg with the given render backend and separate horizontal and vertical
+    /// image scales, e.g. for output devices with non-square pixels.
+    pub fn gen_image_w_backend_xy(
+        &self,
+        image_scale: na::Vector2<f64>,
+        render_backend: RenderBackend,
+    ) -> Result<Image, anyhow::Error> {
+        match render_backend {
+            RenderBackend::Cairo => self.gen_image_cairo(image_scale),
+            RenderBackend::TinySkia => self.gen_image_tiny_skia(image_scale),
+        }
+    }
+
+    fn gen_image_tiny_skia(&self, image_scale: na::Vector2<f64>) -> Result<Image, anyhow::Error> {
         let mut bounds = self.bounds;
         bounds.ensure_positive();
         bounds.assert_valid()?;
@@ -594,8 +1492,56 @@ impl Svg {
             Some(bounds),
             false,
         );
-        let width_scaled = ((bounds.extents()[0]) * image_scale).round() as u32;
-        let height_scaled = ((bounds.extents()[1]) * image_scale).round() as u32;
+        let width_scaled = ((bounds.extents()[0]) * image_scale[0]).round() as u32;
+        let height_scaled = ((bounds.extents()[1]) * image_scale[1]).round() as u32;
+
+        let tree = usvg::Tree::from_str(
+            &svg_data,
+            &usvg::Options {
+                fontdb: Arc::clone(&USVG_FONTDB),
+                ..Default::default()
+            },
+        )
+        .context("Parsing Svg for the tiny-skia renderer failed.")?;
+        let mut pixmap =
+            resvg::tiny_skia::Pixmap::new(width_scaled, height_scaled).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "creating Pixmap with dimensions ({width_scaled}, {height_scaled}) failed."
+                )
+            })?;
+        // The Svg root spans the bounds, so it only needs to be scaled to the pixel size
+        resvg::render(
+            &tree,
+            resvg::tiny_skia::Transform::from_scale(
+                width_scaled as f32 / tree.size().width(),
+                height_scaled as f32 / tree.size().height(),
+            ),
+            &mut pixmap.as_mut(),
+        );
+
+        Ok(Image {
+            data: glib::Bytes::from_owned(pixmap.take()),
+            rect: Rectangle::from_p2d_aabb(bounds),
+            pixel_width: width_scaled,
+            pixel_height: height_scaled,
+            // tiny-skia renders to rgba8-premultiplied
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        })
+    }
+
+    fn gen_image_cairo(&self, image_scale: na::Vector2<f64>) -> Result<Image, anyhow::Error> {
+        let mut bounds = self.bounds;
+        bounds.ensure_positive();
+        bounds.assert_valid()?;
+
+        let svg_data = rnote_compose::utils::wrap_svg_root(
+            self.svg_data.as_str(),
+            Some(bounds),
+            Some(bounds),
+            false,
+        );
+        let width_scaled = ((bounds.extents()[0]) * image_scale[0]).round() as u32;
+        let height_scaled = ((bounds.extents()[1]) * image_scale[1]).round() as u32;
 
         let mut surface = cairo::ImageSurface::create(
                 cairo::Format::ARgb32,
@@ -612,7 +1558,7 @@ impl Svg {
         {
             let cx =
                 cairo::Context::new(&surface).context("creating new cairo::Context failed.")?;
-            cx.scale(image_scale, image_scale);
+            cx.scale(image_scale[0], image_scale[1]);
             cx.translate(-bounds.mins[0], -bounds.mins[1]);
 
             let stream =
@@ -669,3 +1615,755 @@ fn convert_image_bgra_to_rgba(_width: u32, _height: u32, mut bytes: Vec<u8>) ->
     }
     bytes
 }
+
+/// Whether the bytes start like a Png image, but end before its last chunk.
+pub fn png_is_truncated(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return false;
+    }
+    let mut offset = PNG_SIGNATURE.len();
+    // every chunk has a length, a type and a crc around its data
+    while offset + 8 <= bytes.len() {
+        let len = u32::from_be_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]) as usize;
+        if &bytes[offset + 4..offset + 8] == b"IEND" {
+            return false;
+        }
+        offset = offset.saturating_add(12).saturating_add(len);
+    }
+    true
+}
+
+/// Decodes the rows of a truncated Png image up to where the data ends.
+///
+/// Returns the image with the missing rows left transparent and the amount of decoded rows.
+fn decode_partial_png(bytes: &[u8]) -> anyhow::Result<(image::RgbaImage, u32)> {
+    let mut decoder = png::Decoder::new(io::Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .context("Reading the header of the truncated Png image failed.")?;
+    let (width, height) = (reader.info().width, reader.info().height);
+    if reader.info().interlaced {
+        return Err(anyhow::anyhow!(
+            "Recovering truncated interlaced Png images is not supported."
+        ));
+    }
+    let (color_type, _) = reader.output_color_type();
+    let samples = match color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => {
+            return Err(anyhow::anyhow!(
+                "Indexed colors of the truncated Png image were not expanded."
+            ))
+        }
+    };
+    let mut rgba = image::RgbaImage::new(width, height);
+    let mut recovered_rows = 0;
+    // the row the data ends in is not complete and reported as error
+    while recovered_rows < height {
+        let Ok(Some(row)) = reader.next_row() else {
+            break;
+        };
+        for (x, pixel) in row
+            .data()
+            .chunks_exact(samples)
+            .take(width as usize)
+            .enumerate()
+        {
+            let rgba_pixel = match *pixel {
+                [v] => [v, v, v, 255],
+                [v, a] => [v, v, v, a],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a] => [r, g, b, a],
+                _ => continue,
+            };
+            rgba.put_pixel(x as u32, recovered_rows, image::Rgba(rgba_pixel));
+        }
+        recovered_rows += 1;
+    }
+    if recovered_rows == 0 {
+        return Err(anyhow::anyhow!(
+            "No rows of the truncated Png image could be decoded."
+        ));
+    }
+    Ok((rgba, recovered_rows))
+}
+
+/// Decodes Jpeg and Tiff images that hold CMYK data and converts them to rgba8.
+///
+/// Returns `None` for images in other color spaces and for images whose headers can't be read here,
+/// which are then left to the image crate.
+fn decode_cmyk_image(
+    bytes: &[u8],
+    format: image::ImageFormat,
+) -> anyhow::Result<Option<image::RgbaImage>> {
+    let (width, height, cmyk, icc_profile) = match format {
+        image::ImageFormat::Jpeg => {
+            use zune_core::colorspace::ColorSpace;
+
+            let options = zune_core::options::DecoderOptions::default()
+                .jpeg_set_out_colorspace(ColorSpace::CMYK);
+            let mut decoder = zune_jpeg::JpegDecoder::new_with_options(bytes, options);
+            if let Err(e) = decoder.decode_headers() {
+                tracing::debug!(
+                    "Decoding Jpeg headers failed, treating image as not CMYK. Err: {e:?}"
+                );
+                return Ok(None);
+            }
+            if !matches!(
+                decoder.get_input_colorspace(),
+                Some(ColorSpace::CMYK | ColorSpace::YCCK)
+            ) {
+                return Ok(None);
+            }
+            let (width, height) = decoder
+                .dimensions()
+                .context("Jpeg image has no dimensions.")?;
+            let icc_profile = decoder.icc_profile();
+            let mut cmyk = decoder
+                .decode()
+                .map_err(|e| anyhow::anyhow!("Decoding CMYK Jpeg image failed, Err: {e:?}"))?;
+            // Adobe applications, which write most of the CMYK Jpegs, store the values inverted
+            if has_jpeg_adobe_marker(bytes) {
+                cmyk.iter_mut().for_each(|v| *v = 255 - *v);
+            }
+            (width as u32, height as u32, cmyk, icc_profile)
+        }
+        image::ImageFormat::Tiff => {
+            /// The tag of embedded ICC profiles.
+            const TIFF_TAG_ICC_PROFILE: u16 = 34675;
+
+            let Ok(mut decoder) = tiff::decoder::Decoder::new(Cursor::new(bytes)) else {
+                return Ok(None);
+            };
+            let Ok(tiff::ColorType::CMYK(bit_depth)) = decoder.colortype() else {
+                return Ok(None);
+            };
+            let (width, height) = decoder.dimensions()?;
+            let icc_profile = decoder
+                .get_tag_u8_vec(tiff::tags::Tag::Unknown(TIFF_TAG_ICC_PROFILE))
+                .ok();
+            let cmyk = match decoder.read_image()? {
+                tiff::decoder::DecodingResult::U8(cmyk) => cmyk,
+                tiff::decoder::DecodingResult::U16(cmyk) => {
+                    cmyk.into_iter().map(|v| (v >> 8) as u8).collect()
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "CMYK Tiff images with bit depth {bit_depth} are not supported."
+                    ))
+                }
+            };
+            (width, height, cmyk, icc_profile)
+        }
+        _ => return Ok(None),
+    };
+    let rgba = convert_cmyk_to_rgba(&cmyk, icc_profile.as_deref());
+
+    image::RgbaImage::from_raw(width, height, rgba)
+        .map(Some)
+        .context("CMYK image data does not match its dimensions.")
+}
+
+/// Whether the Jpeg has an Adobe "APP14" segment.
+fn has_jpeg_adobe_marker(bytes: &[u8]) -> bool {
+    // The segment marker is followed by the two byte segment length and the "Adobe" identifier
+    bytes
+        .windows(9)
+        .any(|w| w[0] == 0xFF && w[1] == 0xEE && &w[4..9] == b"Adobe")
+}
+
+/// Converts CMYK to rgba8 with the embedded color profile when the "color-profile" feature is enabled,
+/// else with an uncalibrated conversion that is good enough for most images.
+fn convert_cmyk_to_rgba(cmyk: &[u8], icc_profile: Option<&[u8]>) -> Vec<u8> {
+    #[cfg(feature = "color-profile")]
+    if let Some(icc_profile) = icc_profile {
+        match convert_cmyk_to_rgba_w_profile(cmyk, icc_profile) {
+            Ok(rgba) => return rgba,
+            Err(e) => tracing::warn!(
+                "Converting CMYK image with its color profile failed, falling back to the uncalibrated conversion, Err: {e:?}"
+            ),
+        }
+    }
+    #[cfg(not(feature = "color-profile"))]
+    let _ = icc_profile;
+
+    cmyk.chunks_exact(4)
+        .flat_map(|pixel| {
+            let white = 255 - u16::from(pixel[3]);
+            let channel = |ink: u8| ((255 - u16::from(ink)) * white / 255) as u8;
+            [channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), 255]
+        })
+        .collect()
+}
+
+#[cfg(feature = "color-profile")]
+fn convert_cmyk_to_rgba_w_profile(cmyk: &[u8], icc_profile: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let input_profile = lcms2::Profile::new_icc(icc_profile)?;
+    let transform = lcms2::Transform::new(
+        &input_profile,
+        lcms2::PixelFormat::CMYK_8,
+        &lcms2::Profile::new_srgb(),
+        lcms2::PixelFormat::RGBA_8,
+        lcms2::Intent::Perceptual,
+    )?;
+    let cmyk = cmyk
+        .chunks_exact(4)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+        .collect::<Vec<[u8; 4]>>();
+    let mut rgba = vec![[0_u8; 4]; cmyk.len()];
+    transform.transform_pixels(&cmyk, &mut rgba);
+
+    // The alpha channel is not filled by the transform
+    Ok(rgba
+        .into_iter()
+        .flat_map(|[r, g, b, _]| [r, g, b, 255])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::import::PdfImportPrefs;
+
+    /// Two pixels, an opaque orange and a half-transparent blue, in rgba8-premultiplied.
+    const EXPECTED: [u8; 8] = [255, 128, 0, 255, 0, 0, 128, 128];
+
+    fn image(memory_format: ImageMemoryFormat, data: Vec<u8>) -> Image {
+        Image {
+            data: glib::Bytes::from_owned(data),
+            rect: Rectangle::from_p2d_aabb(Aabb::new(na::point![0.0, 0.0], na::point![2.0, 1.0])),
+            pixel_width: 2,
+            pixel_height: 1,
+            memory_format,
+        }
+    }
+
+    fn assert_converts(memory_format: ImageMemoryFormat, data: Vec<u8>, expected: &[u8]) {
+        let image = image(memory_format, data);
+        image.assert_valid().unwrap();
+        let (piet_data, piet_image_format) = image.piet_image_data();
+        if piet_image_format == piet::ImageFormat::RgbaPremul {
+            assert_eq!(&*piet_data, expected);
+        }
+        assert_eq!(image.into_imgbuf().unwrap().into_raw(), expected);
+    }
+
+    #[test]
+    fn memory_formats_convert_to_premultiplied() {
+        assert_converts(
+            ImageMemoryFormat::R8g8b8a8Premultiplied,
+            EXPECTED.to_vec(),
+            &EXPECTED,
+        );
+        assert_converts(
+            ImageMemoryFormat::B8g8r8a8Premultiplied,
+            vec![0, 128, 255, 255, 128, 0, 0, 128],
+            &EXPECTED,
+        );
+        assert_converts(
+            ImageMemoryFormat::R8g8b8a8,
+            vec![255, 128, 0, 255, 0, 0, 255, 128],
+            &EXPECTED,
+        );
+        assert_converts(
+            ImageMemoryFormat::R8g8b8,
+            vec![255, 128, 0, 0, 0, 255],
+            &[255, 128, 0, 255, 0, 0, 255, 255],
+        );
+        let data_16bit = [u16::MAX, 0x8080, 0, u16::MAX, 0, 0, u16::MAX, 0x8080]
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_converts(ImageMemoryFormat::R16g16b16a16, data_16bit, &EXPECTED);
+    }
+
+    #[test]
+    fn piet_supported_formats_are_not_converted() {
+        let data = vec![255, 128, 0, 0, 0, 255];
+        let image = image(ImageMemoryFormat::R8g8b8, data.clone());
+        let (piet_data, piet_image_format) = image.piet_image_data();
+        assert_eq!(piet_image_format, piet::ImageFormat::Rgb);
+        assert!(matches!(piet_data, std::borrow::Cow::Borrowed(_)));
+        assert_eq!(&*piet_data, data.as_slice());
+    }
+
+    #[test]
+    fn invalid_data_length_is_rejected() {
+        assert!(image(ImageMemoryFormat::R16g16b16a16, vec![0; 8])
+            .assert_valid()
+            .is_err());
+        assert!(image(ImageMemoryFormat::R8g8b8, vec![0; 8])
+            .assert_valid()
+            .is_err());
+    }
+
+    #[test]
+    fn gamma_maps_mid_gray() {
+        let mid_gray = image(
+            ImageMemoryFormat::R8g8b8a8Premultiplied,
+            vec![128, 128, 128, 255, 64, 64, 64, 128],
+        );
+
+        let mut unchanged = mid_gray.clone();
+        unchanged.apply_gamma(1.0);
+        assert_eq!(unchanged.data, mid_gray.data);
+
+        let mut corrected = mid_gray;
+        corrected.apply_gamma(2.2);
+        // 255 * (128 / 255)^(1 / 2.2) = 186.4, the half-transparent pixel keeps its alpha
+        assert_eq!(
+            corrected.data.as_ref(),
+            &[186, 186, 186, 255, 93, 93, 93, 128]
+        );
+    }
+
+    #[test]
+    fn inverting_keeps_transparency() {
+        let mut inverted = image(
+            ImageMemoryFormat::R8g8b8a8,
+            vec![255, 128, 0, 255, 255, 255, 255, 128],
+        );
+        inverted.invert_colors();
+        assert_eq!(
+            inverted.memory_format,
+            ImageMemoryFormat::R8g8b8a8Premultiplied
+        );
+        // White at half transparency becomes black at half transparency
+        assert_eq!(inverted.data.as_ref(), &[0, 127, 255, 255, 0, 0, 0, 128]);
+
+        let mut restored = inverted.clone();
+        restored.invert_colors();
+        assert_eq!(
+            restored.data.as_ref(),
+            &[255, 128, 0, 255, 128, 128, 128, 128]
+        );
+    }
+
+    #[test]
+    fn dithering_breaks_up_gradient_bands() {
+        const WIDTH: u32 = 256;
+        const HEIGHT: u32 = 16;
+        let data = (0..HEIGHT)
+            .flat_map(|_| (0..WIDTH).flat_map(|x| [x as u8, x as u8, x as u8, 255]))
+            .collect::<Vec<u8>>();
+        let gradient = Image {
+            data: glib::Bytes::from_owned(data),
+            rect: Rectangle::from_p2d_aabb(Aabb::new(
+                na::point![0.0, 0.0],
+                na::point![f64::from(WIDTH), f64::from(HEIGHT)],
+            )),
+            pixel_width: WIDTH,
+            pixel_height: HEIGHT,
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        };
+        // Quantizes the gradient to two colors and returns the middle row
+        let quantized_row = |dither: Dither| {
+            let png = gradient
+                .clone()
+                .into_indexed_png_bytes(2, dither, None)
+                .unwrap();
+            let image = image::load_from_memory(&png).unwrap().into_luma8();
+            (0..WIDTH)
+                .map(|x| image.get_pixel(x, HEIGHT / 2).0[0])
+                .collect::<Vec<u8>>()
+        };
+        let transitions = |row: &[u8]| row.windows(2).filter(|w| w[0] != w[1]).count();
+        let light_pixels = |row: &[u8]| row.iter().filter(|v| **v >= 128).count();
+
+        // Without dithering the gradient is cut into two hard bands
+        assert!(transitions(&quantized_row(Dither::None)) <= 2);
+        for dither in [Dither::FloydSteinberg, Dither::Ordered] {
+            let row = quantized_row(dither);
+            assert!(transitions(&row) > 20, "{dither} did not dither");
+            // The density of the light pixels follows the gradient
+            let quarter = WIDTH as usize / 4;
+            assert!(light_pixels(&row[..quarter]) < light_pixels(&row[quarter..2 * quarter]));
+            assert!(
+                light_pixels(&row[2 * quarter..3 * quarter]) < light_pixels(&row[3 * quarter..])
+            );
+        }
+    }
+
+    #[test]
+    fn jpeg_subsampling_is_written_to_frame_header() {
+        /// Returns the horizontal and vertical sampling factors of the components in the start-of-frame segment.
+        fn sampling_factors(jpeg: &[u8]) -> Vec<(u8, u8)> {
+            assert_eq!(&jpeg[..2], &[0xff, 0xd8]);
+            let mut pos = 2;
+            loop {
+                let (marker, len) = (
+                    jpeg[pos + 1],
+                    usize::from(u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]])),
+                );
+                if (0xc0..=0xc2).contains(&marker) {
+                    // segment layout: precision, height, width, components amount, 3 bytes per component
+                    let segment = &jpeg[pos + 4..pos + 2 + len];
+                    return segment[6..]
+                        .chunks_exact(3)
+                        .take(usize::from(segment[5]))
+                        .map(|c| (c[1] >> 4, c[1] & 0x0f))
+                        .collect();
+                }
+                pos += 2 + len;
+            }
+        }
+
+        let data = (0..32 * 32)
+            .flat_map(|i| [(i % 256) as u8, 64, 200, 255])
+            .collect::<Vec<u8>>();
+        let image = Image {
+            data: glib::Bytes::from_owned(data),
+            rect: Rectangle::from_p2d_aabb(Aabb::new(na::point![0.0, 0.0], na::point![32.0, 32.0])),
+            pixel_width: 32,
+            pixel_height: 32,
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        };
+        for (subsampling, luma_factors) in [
+            (JpegSubsampling::R444, (1, 1)),
+            (JpegSubsampling::R422, (2, 1)),
+            (JpegSubsampling::R420, (2, 2)),
+        ] {
+            let jpeg = image.clone().into_jpeg_bytes(85, subsampling).unwrap();
+            // The chroma components are always sampled once, the luma factors determine the subsampling
+            assert_eq!(
+                sampling_factors(&jpeg),
+                vec![luma_factors, (1, 1), (1, 1)],
+                "{subsampling}"
+            );
+            let decoded =
+                image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (32, 32));
+        }
+        assert_eq!(
+            sampling_factors(
+                &image
+                    .into_encoded_bytes(image::ImageFormat::Jpeg, None)
+                    .unwrap()
+            )[0],
+            (2, 2)
+        );
+    }
+
+    #[test]
+    fn skew_angle_of_rotated_lines_is_detected() {
+        const SIZE: u32 = 400;
+        // Dark horizontal lines every 20 pixels, rotated clockwise by the angle
+        let lines = |angle: f64| {
+            let (sin, cos) = angle.sin_cos();
+            let data = (0..SIZE)
+                .flat_map(|y| (0..SIZE).map(move |x| (f64::from(x), f64::from(y))))
+                .flat_map(|(x, y)| {
+                    let row = y * cos - x * sin;
+                    let value = if row.rem_euclid(20.0) < 2.0 { 0 } else { 255 };
+                    [value, value, value, 255]
+                })
+                .collect::<Vec<u8>>();
+            Image {
+                data: glib::Bytes::from_owned(data),
+                rect: Rectangle::from_p2d_aabb(Aabb::new(
+                    na::point![0.0, 0.0],
+                    na::point![f64::from(SIZE), f64::from(SIZE)],
+                )),
+                pixel_width: SIZE,
+                pixel_height: SIZE,
+                memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+            }
+        };
+        let max_angle = 5.0_f64.to_radians();
+
+        for degrees in [-2.0_f64, 0.0, 1.3] {
+            let detected = lines(degrees.to_radians())
+                .detect_skew_angle(max_angle)
+                .unwrap()
+                .unwrap();
+            assert!(
+                (detected.to_degrees() - degrees).abs() < 0.2,
+                "detected {} instead of {degrees} degrees",
+                detected.to_degrees()
+            );
+        }
+        // The angle is bounded
+        let detected = lines(8.0_f64.to_radians())
+            .detect_skew_angle(max_angle)
+            .unwrap()
+            .unwrap();
+        assert!(detected.abs() <= max_angle);
+        // Blank pages have no angle
+        let mut blank = lines(0.0);
+        blank.whiten(0);
+        assert!(blank.detect_skew_angle(max_angle).unwrap().is_none());
+    }
+
+    #[test]
+    fn pixels_hash_detects_identical_images() {
+        let page = image(ImageMemoryFormat::R8g8b8a8Premultiplied, EXPECTED.to_vec());
+        let duplicate = image(ImageMemoryFormat::R8g8b8a8Premultiplied, EXPECTED.to_vec());
+        let mut changed_data = EXPECTED.to_vec();
+        changed_data[0] = 254;
+        let changed = image(ImageMemoryFormat::R8g8b8a8Premultiplied, changed_data);
+
+        assert_eq!(page.pixels_hash(), duplicate.pixels_hash());
+        assert_ne!(page.pixels_hash(), changed.pixels_hash());
+        let mut resized = duplicate;
+        resized.pixel_width = 1;
+        resized.pixel_height = 2;
+        assert_ne!(page.pixels_hash(), resized.pixels_hash());
+    }
+
+    #[test]
+    fn whiten_lifts_near_white_pixels() {
+        // Bleed-through, ink, a light pencil mark, half-transparent bleed-through and a transparent pixel
+        let bleed_through = [235, 232, 238, 255];
+        let ink = [20, 20, 20, 255];
+        let pencil = [200, 200, 200, 255];
+        let translucent_bleed_through = [118, 117, 119, 128];
+        let data = [
+            bleed_through,
+            ink,
+            pencil,
+            translucent_bleed_through,
+            [0, 0, 0, 0],
+        ]
+        .concat();
+        let mut scan = Image {
+            data: glib::Bytes::from_owned(data),
+            rect: Rectangle::from_p2d_aabb(Aabb::new(na::point![0.0, 0.0], na::point![5.0, 1.0])),
+            pixel_width: 5,
+            pixel_height: 1,
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        };
+
+        scan.whiten(PdfImportPrefs::WHITEN_THRESHOLD_DEFAULT);
+        assert_eq!(
+            &scan.data[..],
+            &[
+                [255, 255, 255, 255],
+                ink,
+                pencil,
+                [128, 128, 128, 128],
+                [0, 0, 0, 0]
+            ]
+            .concat()[..]
+        );
+
+        // Other memory formats are converted
+        let mut image = image(
+            ImageMemoryFormat::B8g8r8a8Premultiplied,
+            vec![230, 240, 250, 255, 0, 0, 128, 128],
+        );
+        image.whiten(PdfImportPrefs::WHITEN_THRESHOLD_DEFAULT);
+        assert_eq!(
+            image.memory_format,
+            ImageMemoryFormat::R8g8b8a8Premultiplied
+        );
+        assert_eq!(&image.data[..], &[255, 255, 255, 255, 128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn remove_background_clears_pixels_near_the_background() {
+        // Off-white paper, black ink and half-transparent off-white in rgba8-premultiplied
+        let paper = [235, 230, 225, 255];
+        let ink = [20, 20, 20, 255];
+        let translucent_paper = [118, 115, 113, 128];
+        let data = [
+            paper,
+            paper,
+            paper,
+            paper,
+            ink,
+            paper,
+            paper,
+            paper,
+            translucent_paper,
+        ]
+        .concat();
+        let new_image = || Image {
+            data: glib::Bytes::from_owned(data.clone()),
+            rect: Rectangle::from_p2d_aabb(Aabb::new(na::point![0.0, 0.0], na::point![3.0, 3.0])),
+            pixel_width: 3,
+            pixel_height: 3,
+            memory_format: ImageMemoryFormat::R8g8b8a8Premultiplied,
+        };
+
+        let mut image = new_image();
+        assert_eq!(
+            image.remove_background(BackgroundRemoval::White, 40),
+            Some([255, 255, 255])
+        );
+        let pixels = image.into_imgbuf().unwrap().into_raw();
+        assert!(pixels
+            .chunks_exact(4)
+            .enumerate()
+            .all(|(i, pixel)| match i {
+                4 => pixel == ink,
+                _ => pixel == [0, 0, 0, 0],
+            }));
+        // The paper is not near enough to white for the threshold, but to the sampled color
+        let mut image = new_image();
+        image.remove_background(BackgroundRemoval::White, 10);
+        assert_eq!(&image.data[..4], &paper);
+        let mut image = new_image();
+        assert_eq!(
+            image.remove_background(BackgroundRemoval::Sampled, 10),
+            Some([235, 230, 225])
+        );
+        assert_eq!(&image.data[..4], &[0, 0, 0, 0]);
+        assert_eq!(&image.data[16..20], &ink);
+    }
+
+    #[test]
+    fn cmyk_tiff_imports_with_converted_colors() {
+        // Cyan, no ink, half black and magenta with yellow
+        let cmyk: [u8; 16] = [255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 0, 255, 255, 0];
+        let mut tiff_bytes = Cursor::new(Vec::new());
+        tiff::encoder::TiffEncoder::new(&mut tiff_bytes)
+            .unwrap()
+            .write_image::<tiff::encoder::colortype::CMYK8>(4, 1, &cmyk)
+            .unwrap();
+
+        let image = Image::try_from_encoded_bytes(tiff_bytes.get_ref()).unwrap();
+        assert_eq!((image.pixel_width, image.pixel_height), (4, 1));
+        let rgba = image.into_imgbuf().unwrap();
+        let expected: [[u8; 4]; 4] = [
+            [0, 255, 255, 255],
+            [255, 255, 255, 255],
+            [127, 127, 127, 255],
+            [255, 0, 0, 255],
+        ];
+        for (pixel, expected) in rgba.pixels().zip(expected) {
+            for (channel, expected_channel) in pixel.0.into_iter().zip(expected) {
+                assert!(
+                    channel.abs_diff(expected_channel) <= 4,
+                    "pixel {pixel:?} differs from {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cmyk_jpeg_imports_with_converted_colors() {
+        // Blocks of cyan, no ink, half black and magenta with yellow, wide enough to survive the compression
+        let inks: [[u8; 4]; 4] = [
+            [255, 0, 0, 0],
+            [0, 0, 0, 0],
+            [0, 0, 0, 128],
+            [0, 255, 255, 0],
+        ];
+        let (width, height) = (32_u16, 8_u16);
+        let cmyk = (0..height)
+            .flat_map(|_| (0..width).flat_map(|x| inks[usize::from(x / 8)]))
+            .collect::<Vec<u8>>();
+        let mut jpeg_bytes = Vec::new();
+        // Writes an Adobe segment and stores the values inverted, like most CMYK Jpegs
+        jpeg_encoder::Encoder::new(&mut jpeg_bytes, 100)
+            .encode(&cmyk, width, height, jpeg_encoder::ColorType::Cmyk)
+            .unwrap();
+
+        let image = Image::try_from_encoded_bytes(&jpeg_bytes).unwrap();
+        assert_eq!((image.pixel_width, image.pixel_height), (32, 8));
+        let rgba = image.into_imgbuf().unwrap();
+        let expected: [[u8; 4]; 4] = [
+            [0, 255, 255, 255],
+            [255, 255, 255, 255],
+            [127, 127, 127, 255],
+            [255, 0, 0, 255],
+        ];
+        for (block, expected) in expected.into_iter().enumerate() {
+            let pixel = rgba.get_pixel(block as u32 * 8 + 4, 4);
+            for (channel, expected_channel) in pixel.0.into_iter().zip(expected) {
+                assert!(
+                    channel.abs_diff(expected_channel) <= 4,
+                    "pixel {pixel:?} differs from {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unreadable_cmyk_headers_are_left_to_the_image_crate() {
+        let truncated_jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x02];
+        assert!(decode_cmyk_image(&truncated_jpeg, image::ImageFormat::Jpeg)
+            .unwrap()
+            .is_none());
+        assert!(decode_cmyk_image(b"II*\0", image::ImageFormat::Tiff)
+            .unwrap()
+            .is_none());
+
+        // Rgb Jpegs are not handled as CMYK
+        let mut jpeg_bytes = Vec::new();
+        jpeg_encoder::Encoder::new(&mut jpeg_bytes, 90)
+            .encode(&[255, 0, 0].repeat(64), 8, 8, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        assert!(decode_cmyk_image(&jpeg_bytes, image::ImageFormat::Jpeg)
+            .unwrap()
+            .is_none());
+        let image = Image::try_from_encoded_bytes(&jpeg_bytes).unwrap();
+        assert_eq!((image.pixel_width, image.pixel_height), (8, 8));
+    }
+
+    #[test]
+    fn encoded_bytes_are_validated_from_the_header() {
+        let png = image(ImageMemoryFormat::R8g8b8a8Premultiplied, EXPECTED.to_vec())
+            .into_encoded_bytes(image::ImageFormat::Png, None)
+            .unwrap();
+        assert_eq!(Image::validate_encoded_bytes(&png).unwrap(), (2, 1));
+
+        // The header is cut off within the image dimensions
+        assert!(Image::validate_encoded_bytes(&png[..18]).is_err());
+        assert!(Image::validate_encoded_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn truncated_png_is_reported_and_recovered() {
+        const WIDTH: u32 = 32;
+        const HEIGHT: u32 = 64;
+        // Noisy pixels, so that the image data makes up most of the file
+        let original = image::RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            image::Rgba([
+                ((x * 7 + y * 13) ^ (x * y)) as u8,
+                (y * 4) as u8,
+                (x * 8) as u8,
+                255,
+            ])
+        });
+        let mut png = Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgba8(original.clone())
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+        let truncated = &png[..png.len() * 2 / 3];
+        assert!(!png_is_truncated(&png));
+        assert!(png_is_truncated(truncated));
+
+        // Truncated images are distinguished from unrecognized data
+        let err = Image::try_from_encoded_bytes(truncated).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{err}");
+        let err = Image::try_from_encoded_bytes(b"not an image").unwrap_err();
+        assert!(!err.to_string().contains("truncated"), "{err}");
+
+        let (image, recovered_rows) =
+            Image::try_from_encoded_bytes_allow_partial(truncated).unwrap();
+        let recovered_rows = recovered_rows.unwrap();
+        assert!(recovered_rows > 0 && recovered_rows < HEIGHT);
+        assert_eq!((image.pixel_width, image.pixel_height), (WIDTH, HEIGHT));
+        let row_len = WIDTH as usize * 4;
+        assert_eq!(&image.data[..row_len], &original.as_raw()[..row_len]);
+        // The missing rows are transparent
+        assert!(image.data[image.data.len() - row_len..]
+            .iter()
+            .all(|v| *v == 0));
+
+        // Complete images are decoded as usual
+        let (_, recovered_rows) = Image::try_from_encoded_bytes_allow_partial(&png).unwrap();
+        assert!(recovered_rows.is_none());
+    }
+}
@@ -1,5 +1,6 @@
 // Imports
 use super::{StrokeKey, StrokeStore};
+use crate::strokes::Stroke;
 use p2d::bounding_volume::Aabb;
 use rayon::slice::ParallelSliceMut;
 use serde::{Deserialize, Serialize};
@@ -68,6 +69,11 @@ pub struct ChronoComponent {
     t: u32,
     #[serde(rename = "layer")]
     pub layer: StrokeLayer,
+    /// The time the stroke was created as unix timestamp in seconds.
+    ///
+    /// Is `None` for strokes of documents that were saved before it was recorded.
+    #[serde(rename = "created")]
+    created: Option<i64>,
 }
 
 impl Default for ChronoComponent {
@@ -75,13 +81,22 @@ impl Default for ChronoComponent {
         Self {
             t: 0,
             layer: StrokeLayer::default(),
+            created: None,
         }
     }
 }
 
 impl ChronoComponent {
     pub(crate) fn new(t: u32, layer: StrokeLayer) -> Self {
-        Self { t, layer }
+        Self {
+            t,
+            layer,
+            created: Some(chrono::Utc::now().timestamp()),
+        }
+    }
+
+    pub fn created(&self) -> Option<i64> {
+        self.created
     }
 }
 
@@ -94,6 +109,32 @@ impl StrokeStore {
         }
     }
 
+    /// The layer the stroke is drawn on.
+    pub(crate) fn stroke_layer(&self, key: StrokeKey) -> Option<StrokeLayer> {
+        Some(self.chrono_components.get(key)?.layer)
+    }
+
+    /// The time the stroke was created as unix timestamp in seconds, if it was recorded.
+    pub fn stroke_created(&self, key: StrokeKey) -> Option<i64> {
+        self.chrono_components.get(key)?.created()
+    }
+
+    /// Filters the keys for strokes that were created at or after the given unix timestamp in seconds.
+    ///
+    /// Strokes without a recorded creation time are filtered out.
+    pub(crate) fn filter_keys_created_since(
+        &self,
+        keys: Vec<StrokeKey>,
+        since: i64,
+    ) -> Vec<StrokeKey> {
+        keys.into_iter()
+            .filter(|&key| {
+                self.stroke_created(key)
+                    .is_some_and(|created| created >= since)
+            })
+            .collect()
+    }
+
     /// Returns the keys in chronological order, as in first: gets drawn first, last: gets drawn last.
     pub(crate) fn keys_sorted_chrono(&self) -> Vec<StrokeKey> {
         let chrono_components = &self.chrono_components;
@@ -166,4 +207,135 @@ impl StrokeStore {
 
         keys
     }
+
+    /// Moves the strokes for which `filter` returns true behind or in front of the other strokes on their layer,
+    /// preserving the order within both groups. Returns the number of moved strokes.
+    ///
+    /// The layers of the strokes are kept, so that e.g. images don't end up behind Pdf pages or in front of the ink.
+    /// Only the chrono order of all strokes is renumbered.
+    /// Strokes that are the only ones on their layer are not counted as moved.
+    pub fn reorder_strokes(
+        &mut self,
+        mut filter: impl FnMut(&Stroke) -> bool,
+        to_front: bool,
+    ) -> usize {
+        let keys = self.keys_sorted_chrono();
+        let mut order = Vec::with_capacity(keys.len());
+        let mut moved_len = 0;
+        // The keys are sorted by their layer first, so the strokes of every layer are consecutive
+        for layer_keys in keys.chunk_by(|&a, &b| self.stroke_layer(a) == self.stroke_layer(b)) {
+            let (moved, others): (Vec<StrokeKey>, Vec<StrokeKey>) = layer_keys
+                .iter()
+                .partition(|&&key| self.get_stroke_ref(key).is_some_and(&mut filter));
+            if !others.is_empty() {
+                moved_len += moved.len();
+            }
+            if to_front {
+                order.extend(others.into_iter().chain(moved));
+            } else {
+                order.extend(moved.into_iter().chain(others));
+            }
+        }
+        if moved_len == 0 {
+            return 0;
+        }
+
+        let chrono_components = Arc::make_mut(&mut self.chrono_components);
+        self.chrono_counter = 0;
+        for key in order {
+            if let Some(chrono_comp) = chrono_components.get_mut(key) {
+                self.chrono_counter += 1;
+                Arc::make_mut(chrono_comp).t = self.chrono_counter;
+            }
+        }
+        moved_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StrokeLayer;
+    use crate::store::{StrokeKey, StrokeStore};
+    use crate::strokes::textstroke::TextStyle;
+    use crate::strokes::{ShapeStroke, Stroke, TextStroke};
+    use rnote_compose::shapes::{Line, Shape};
+    use rnote_compose::Style;
+
+    fn insert_line(store: &mut StrokeStore, layer: StrokeLayer) -> StrokeKey {
+        let line = Line {
+            start: na::vector![0.0, 0.0],
+            end: na::vector![10.0, 10.0],
+        };
+        store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(Shape::Line(line), Style::default())),
+            Some(layer),
+        )
+    }
+
+    fn insert_text(store: &mut StrokeStore, layer: StrokeLayer) -> StrokeKey {
+        store.insert_stroke(
+            Stroke::TextStroke(TextStroke::new(
+                String::from("text"),
+                na::vector![0.0, 0.0],
+                TextStyle::default(),
+            )),
+            Some(layer),
+        )
+    }
+
+    /// Inserts lines and texts on multiple layers, returning the store together with the keys of the lines
+    /// and the text on the user layer and the keys of the texts that are alone on their layer.
+    fn store_w_layers() -> (StrokeStore, [StrokeKey; 3], [StrokeKey; 2]) {
+        let mut store = StrokeStore::default();
+        let user_layer = StrokeLayer::UserLayer(0);
+        let first_line = insert_line(&mut store, user_layer);
+        let text = insert_text(&mut store, user_layer);
+        let second_line = insert_line(&mut store, user_layer);
+        let image_text = insert_text(&mut store, StrokeLayer::Image);
+        let document_text = insert_text(&mut store, StrokeLayer::Document);
+        (
+            store,
+            [first_line, text, second_line],
+            [document_text, image_text],
+        )
+    }
+
+    fn layers(store: &StrokeStore, keys: &[StrokeKey]) -> Vec<Option<StrokeLayer>> {
+        keys.iter().map(|&key| store.stroke_layer(key)).collect()
+    }
+
+    #[test]
+    fn reorder_strokes_to_back_within_their_layer() {
+        let (mut store, [first_line, text, second_line], [document_text, image_text]) =
+            store_w_layers();
+        let all_keys = [first_line, text, second_line, image_text, document_text];
+        let layers_before = layers(&store, &all_keys);
+
+        let moved = store.reorder_strokes(|stroke| matches!(stroke, Stroke::TextStroke(_)), false);
+        // The texts alone on their layer are not moved
+        assert_eq!(moved, 1);
+        assert_eq!(layers(&store, &all_keys), layers_before);
+        assert_eq!(
+            store.keys_sorted_chrono(),
+            vec![document_text, image_text, text, first_line, second_line]
+        );
+    }
+
+    #[test]
+    fn reorder_strokes_to_front_within_their_layer() {
+        let (mut store, [first_line, text, second_line], [document_text, image_text]) =
+            store_w_layers();
+        let all_keys = [first_line, text, second_line, image_text, document_text];
+        let layers_before = layers(&store, &all_keys);
+
+        let moved = store.reorder_strokes(|stroke| matches!(stroke, Stroke::TextStroke(_)), true);
+        assert_eq!(moved, 1);
+        assert_eq!(layers(&store, &all_keys), layers_before);
+        assert_eq!(
+            store.keys_sorted_chrono(),
+            vec![document_text, image_text, first_line, second_line, text]
+        );
+        // Nothing is moved when no stroke matches
+        assert_eq!(store.reorder_strokes(|_| false, true), 0);
+    }
 }
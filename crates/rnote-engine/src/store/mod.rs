@@ -16,6 +16,7 @@ pub use trash_comp::TrashComponent;
 // Imports
 use self::chrono_comp::StrokeLayer;
 use crate::engine::EngineSnapshot;
+use crate::render;
 use crate::strokes::Stroke;
 use crate::WidgetFlags;
 use rnote_compose::shapes::Shapeable;
@@ -86,6 +87,9 @@ pub struct StrokeStore {
     chrono_counter: u32,
     #[serde(skip)]
     render_components: SecondaryMap<StrokeKey, RenderComponent>,
+    /// The margin in pixels by which partially rendered stroke images extend beyond the viewport.
+    #[serde(skip)]
+    render_overscan: f64,
     #[serde(skip)]
     history: VecDeque<HistoryEntry>,
     /// The index of the current live document in the history stack.
@@ -106,6 +110,7 @@ impl Default for StrokeStore {
             selection_components: Arc::new(SecondaryMap::new()),
             chrono_components: Arc::new(SecondaryMap::new()),
             render_components: SecondaryMap::new(),
+            render_overscan: render::OVERSCAN_DEFAULT,
 
             // Start off with state in the history
             history: VecDeque::from(vec![HistoryEntry::default()]),
@@ -46,6 +46,27 @@ impl Default for RenderComponent {
 }
 
 impl StrokeStore {
+    /// The margin in pixels by which partially rendered stroke images extend beyond the viewport.
+    pub fn render_overscan(&self) -> f64 {
+        self.render_overscan
+    }
+
+    /// Set the render overscan. Takes effect when the rendering is regenerated.
+    pub fn set_render_overscan(&mut self, overscan: f64) {
+        self.render_overscan = overscan.max(0.0);
+    }
+
+    /// The viewport for which the stroke images are generated.
+    ///
+    /// Extended by the [render::VIEWPORT_EXTENTS_MARGIN_FACTOR] of its extents,
+    /// plus the render overscan in pixels of the images on every side.
+    pub(crate) fn viewport_extended(&self, viewport: Aabb, image_scale: f64) -> Aabb {
+        viewport.extend_by(
+            viewport.extents() * render::VIEWPORT_EXTENTS_MARGIN_FACTOR
+                + na::Vector2::repeat(self.render_overscan / image_scale),
+        )
+    }
+
     /// Rebuild the slotmap with empty render components with the keys returned from the stroke components.
     pub(crate) fn rebuild_render_components_slotmap(&mut self) {
         self.render_components = slotmap::SecondaryMap::new();
@@ -102,6 +123,7 @@ impl StrokeStore {
         viewport: Aabb,
         image_scale: f64,
     ) {
+        let viewport_extended = self.viewport_extended(viewport, image_scale);
         if let (Some(stroke), Some(render_comp)) = (
             self.stroke_components.get(key),
             self.render_components.get_mut(key),
@@ -110,9 +132,6 @@ impl StrokeStore {
                 return;
             }
 
-            let viewport_extended =
-                viewport.extend_by(viewport.extents() * render::VIEWPORT_EXTENTS_MARGIN_FACTOR);
-
             match stroke.gen_images(viewport_extended, image_scale) {
                 Ok(GeneratedContentImages::Partial { images, viewport }) => {
                     #[cfg(feature = "ui")]
@@ -182,6 +201,7 @@ impl StrokeStore {
         viewport: Aabb,
         image_scale: f64,
     ) {
+        let viewport_extended = self.viewport_extended(viewport, image_scale);
         if let (Some(render_comp), Some(stroke)) = (
             self.render_components.get_mut(key),
             self.stroke_components.get(key),
@@ -191,8 +211,6 @@ impl StrokeStore {
             }
 
             let stroke = stroke.clone();
-            let viewport_extended =
-                viewport.extend_by(viewport.extents() * render::VIEWPORT_EXTENTS_MARGIN_FACTOR);
 
             // indicates that a task is now started rendering the stroke
             render_comp.state = RenderCompState::BusyRenderingInTask;
@@ -243,6 +261,7 @@ impl StrokeStore {
         image_scale: f64,
     ) {
         let keys = self.render_components.keys().collect::<Vec<StrokeKey>>();
+        let viewport_extended = self.viewport_extended(viewport, image_scale);
 
         for key in keys {
             if let (Some(stroke), Some(render_comp)) = (
@@ -251,8 +270,6 @@ impl StrokeStore {
             ) {
                 let tasks_tx = tasks_tx.clone();
                 let stroke_bounds = stroke.bounds();
-                let viewport_extended =
-                    viewport.extend_by(viewport.extents() * render::VIEWPORT_EXTENTS_MARGIN_FACTOR);
 
                 // skip and clear image buffer if stroke is not in viewport
                 if !viewport_extended.intersects(&stroke_bounds) {
@@ -651,3 +668,61 @@ impl StrokeStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::StrokeStore;
+    use crate::strokes::content::GeneratedContentImages;
+    use crate::strokes::{Content, ShapeStroke};
+    use p2d::bounding_volume::Aabb;
+    use rnote_compose::shapes::{Rectangle, Shape, Shapeable};
+    use rnote_compose::Style;
+
+    #[test]
+    fn viewport_extended_adds_the_overscan_to_the_margin() {
+        let mut store = StrokeStore::default();
+        let viewport = Aabb::new(na::point![0.0, 0.0], na::point![100.0, 50.0]);
+        store.set_render_overscan(0.0);
+        assert_eq!(
+            store.viewport_extended(viewport, 1.0),
+            Aabb::new(na::point![-40.0, -20.0], na::point![140.0, 70.0])
+        );
+        // The overscan is in pixels of the images, at a zoom of 2 it covers half the distance on the document
+        store.set_render_overscan(64.0);
+        assert_eq!(
+            store.viewport_extended(viewport, 2.0),
+            Aabb::new(na::point![-72.0, -52.0], na::point![172.0, 102.0])
+        );
+        store.set_render_overscan(-10.0);
+        assert_eq!(store.render_overscan(), 0.0);
+    }
+
+    #[test]
+    fn partial_images_cover_the_extended_viewport() {
+        let mut store = StrokeStore::default();
+        store.set_render_overscan(64.0);
+        let stroke = ShapeStroke::new(
+            Shape::Rectangle(Rectangle::from_corners(
+                na::vector![0.0, 0.0],
+                na::vector![1000.0, 1000.0],
+            )),
+            Style::default(),
+        );
+        let viewport = Aabb::new(na::point![400.0, 400.0], na::point![500.0, 500.0]);
+        let viewport_extended = store.viewport_extended(viewport, 1.0);
+        let GeneratedContentImages::Partial {
+            images,
+            viewport: rendered_viewport,
+        } = stroke.gen_images(viewport_extended, 1.0).unwrap()
+        else {
+            panic!("The stroke is only partially in the viewport");
+        };
+        assert_eq!(rendered_viewport, viewport_extended);
+        assert_eq!(images.len(), 1);
+        // Extended by the margin of 40% and the overscan of 64 pixels on every side
+        assert_eq!(
+            images[0].rect.bounds(),
+            Aabb::new(na::point![296.0, 296.0], na::point![604.0, 604.0])
+        );
+    }
+}
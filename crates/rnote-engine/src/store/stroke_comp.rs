@@ -2,6 +2,7 @@
 use super::render_comp::RenderCompState;
 use super::StrokeKey;
 use crate::engine::StrokeContent;
+use crate::render::ImageMemoryFormat;
 use crate::strokes::{Content, Stroke};
 use crate::{StrokeStore, WidgetFlags};
 use geo::intersects::Intersects;
@@ -11,6 +12,7 @@ use rnote_compose::penpath::Element;
 use rnote_compose::shapes::Shapeable;
 use rnote_compose::transform::Transformable;
 use rnote_compose::Color;
+use slotmap::Key;
 use std::sync::Arc;
 #[cfg(feature = "ui")]
 use tracing::error;
@@ -30,7 +32,6 @@ impl StrokeStore {
     }
 
     /// Gets the stroke by cloning the Arc that is wrapped around it.
-    #[allow(unused)]
     pub(crate) fn get_stroke_arc(&self, key: StrokeKey) -> Option<Arc<Stroke>> {
         self.stroke_components.get(key).cloned()
     }
@@ -67,6 +68,46 @@ impl StrokeStore {
             .collect()
     }
 
+    /// The id that users refer to the stroke with.
+    ///
+    /// It holds the index and the version of the stroke's slot in the store, which are serialized with the store.
+    /// So the id is kept when the document is saved and loaded, and is not reused by a new stroke after the stroke
+    /// was removed.
+    pub fn stroke_id(key: StrokeKey) -> u64 {
+        key.data().as_ffi()
+    }
+
+    /// The ids and type names of the strokes that are not trashed, in the order they are rendered.
+    pub fn stroke_ids_w_type_names(&self) -> Vec<(u64, &'static str)> {
+        self.stroke_keys_as_rendered()
+            .into_iter()
+            .filter_map(|key| {
+                self.get_stroke_ref(key)
+                    .map(|stroke| (Self::stroke_id(key), stroke.type_name()))
+            })
+            .collect()
+    }
+
+    /// Filters the keys for strokes with one of the ids, see [StrokeStore::stroke_id].
+    pub(crate) fn filter_keys_w_ids(&self, keys: Vec<StrokeKey>, ids: &[u64]) -> Vec<StrokeKey> {
+        keys.into_iter()
+            .filter(|&key| ids.contains(&Self::stroke_id(key)))
+            .collect()
+    }
+
+    /// The ids that don't belong to a stroke that is not trashed, in the order they are given.
+    pub fn missing_stroke_ids(&self, ids: &[u64]) -> Vec<u64> {
+        let existing_ids = self
+            .stroke_keys_unordered()
+            .into_iter()
+            .map(Self::stroke_id)
+            .collect::<Vec<u64>>();
+        ids.iter()
+            .copied()
+            .filter(|id| !existing_ids.contains(id))
+            .collect()
+    }
+
     /// Storke keys in the order that they should be rendered.
     pub(crate) fn stroke_keys_as_rendered(&self) -> Vec<StrokeKey> {
         self.keys_sorted_chrono()
@@ -172,6 +213,21 @@ impl StrokeStore {
         Some(bounds)
     }
 
+    /// The union of the bounds of all strokes that are not trashed.
+    ///
+    /// Bounds that are not finite are skipped. Returns `None` when there are no strokes with finite bounds.
+    pub fn strokes_bounds_union(&self) -> Option<Aabb> {
+        self.stroke_keys_unordered()
+            .into_iter()
+            .filter_map(|key| self.stroke_components.get(key))
+            .map(|stroke| stroke.bounds())
+            .filter(|bounds| {
+                bounds.mins.iter().all(|v| v.is_finite())
+                    && bounds.maxs.iter().all(|v| v.is_finite())
+            })
+            .reduce(|acc, bounds| acc.merged(&bounds))
+    }
+
     /// Collect all stroke bounds for the given keys.
     pub(crate) fn strokes_bounds(&self, keys: &[StrokeKey]) -> Vec<Aabb> {
         keys.iter()
@@ -327,6 +383,106 @@ impl StrokeStore {
         widget_flags
     }
 
+    /// The references to external files of the images embedded in the strokes, with the keys of their strokes.
+    ///
+    /// Strokes without any references are self-contained.
+    pub fn external_image_refs(&self) -> anyhow::Result<Vec<(StrokeKey, String)>> {
+        let mut refs = Vec::new();
+        for key in self.stroke_keys_as_rendered() {
+            if let Some(Stroke::VectorImage(vectorimage)) = self.get_stroke_ref(key) {
+                refs.extend(
+                    vectorimage
+                        .external_image_refs()?
+                        .into_iter()
+                        .map(|href| (key, href)),
+                );
+            }
+        }
+        Ok(refs)
+    }
+
+    /// Rewrites the references to external files of the images embedded in the strokes.
+    ///
+    /// `rewrite` returns the new reference, or `None` to keep the current one.
+    /// Returns the number of rewritten references. The rewritten strokes then need to update their rendering.
+    pub fn rewrite_external_image_refs(
+        &mut self,
+        mut rewrite: impl FnMut(&str) -> anyhow::Result<Option<String>>,
+    ) -> anyhow::Result<usize> {
+        let mut rewritten = 0;
+        for key in self.stroke_keys_as_rendered() {
+            let Some(Stroke::VectorImage(vectorimage)) = Arc::make_mut(&mut self.stroke_components)
+                .get_mut(key)
+                .map(Arc::make_mut)
+            else {
+                continue;
+            };
+            let stroke_rewritten = vectorimage.rewrite_external_image_refs(&mut rewrite)?;
+            if stroke_rewritten > 0 {
+                self.set_rendering_dirty(key);
+                rewritten += stroke_rewritten;
+            }
+        }
+        Ok(rewritten)
+    }
+
+    /// Converts the images of the bitmap image strokes to the rgba8-premultiplied memory format,
+    /// e.g. images of older files in memory formats that can't be drawn directly.
+    ///
+    /// Returns the number of converted images. The converted strokes then need to update their rendering.
+    pub fn normalize_bitmap_image_formats(&mut self) -> usize {
+        let mut converted = 0;
+        for key in self.stroke_keys_as_rendered() {
+            let needs_conversion = matches!(
+                self.get_stroke_ref(key),
+                Some(Stroke::BitmapImage(bitmapimage))
+                    if bitmapimage.image.memory_format != ImageMemoryFormat::R8g8b8a8Premultiplied
+            );
+            if !needs_conversion {
+                continue;
+            }
+            if let Some(Stroke::BitmapImage(bitmapimage)) = self.get_stroke_mut(key) {
+                bitmapimage.image.convert_to_r8g8b8a8_premultiplied();
+                self.set_rendering_dirty(key);
+                converted += 1;
+            }
+        }
+        converted
+    }
+
+    /// Rotates the strokes by the angle (in radians) and then scales them by the factor, every stroke about the point
+    /// that `pivot` returns for its bounds, e.g. their center.
+    ///
+    /// Only the strokes for which `filter` returns true are transformed. Returns the number of transformed strokes.
+    /// The transformed strokes then need to update their rendering.
+    pub fn transform_strokes_each(
+        &mut self,
+        angle: f64,
+        scale: na::Vector2<f64>,
+        mut filter: impl FnMut(&Stroke) -> bool,
+        pivot: impl Fn(Aabb) -> na::Point2<f64>,
+    ) -> usize {
+        let keys = self
+            .stroke_keys_as_rendered()
+            .into_iter()
+            .filter(|&key| self.get_stroke_ref(key).is_some_and(&mut filter))
+            .collect::<Vec<StrokeKey>>();
+        for &key in keys.iter() {
+            let Some(bounds) = self.get_stroke_ref(key).map(|stroke| stroke.bounds()) else {
+                continue;
+            };
+            let about = pivot(bounds);
+            if angle != 0.0 {
+                self.rotate_strokes(&[key], angle, about);
+            }
+            if scale != na::Vector2::repeat(1.0) {
+                self.scale_strokes_with_pivot(&[key], scale, about.coords);
+            }
+            self.set_rendering_dirty(key);
+        }
+        keys.len()
+    }
+
     /// Change the fill color of the given keys.
     ///
     /// The strokes then need to update their rendering.
@@ -750,3 +906,237 @@ impl StrokeStore {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{self, ImageMemoryFormat};
+    use crate::store::render_comp::RenderCompState;
+    use crate::strokes::resize::ImageSizeOption;
+    use crate::strokes::{BitmapImage, ShapeStroke, Stroke};
+    use crate::{Drawable, StrokeStore};
+    use approx::assert_relative_eq;
+    use p2d::bounding_volume::Aabb;
+    use rnote_compose::shapes::{Rectangle, Shape, Shapeable};
+    use rnote_compose::style::smooth::SmoothOptions;
+    use rnote_compose::{Color, Style};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn insert_rectangle(
+        store: &mut StrokeStore,
+        mins: na::Vector2<f64>,
+        maxs: na::Vector2<f64>,
+    ) -> super::StrokeKey {
+        // Without a stroke width the bounds are exactly the rectangle
+        let style = Style::Smooth(SmoothOptions {
+            stroke_color: None,
+            fill_color: Some(Color::BLACK),
+            stroke_width: 0.0,
+            ..Default::default()
+        });
+        store.insert_stroke(
+            Stroke::ShapeStroke(ShapeStroke::new(
+                Shape::Rectangle(Rectangle::from_corners(mins, maxs)),
+                style,
+            )),
+            None,
+        )
+    }
+
+    fn bounds(store: &StrokeStore, key: super::StrokeKey) -> Aabb {
+        store.get_stroke_ref(key).unwrap().bounds()
+    }
+
+    fn insert_bitmapimage(
+        store: &mut StrokeStore,
+        data: Vec<u8>,
+        memory_format: ImageMemoryFormat,
+    ) -> super::StrokeKey {
+        let image = render::Image {
+            data: glib::Bytes::from_owned(data),
+            rect: Rectangle::from_corners(na::vector![0.0, 0.0], na::vector![2.0, 1.0]),
+            pixel_width: 2,
+            pixel_height: 1,
+            memory_format,
+        };
+        let bitmapimage = BitmapImage::from_image(
+            image,
+            na::vector![0.0, 0.0],
+            ImageSizeOption::RespectOriginalSize,
+        )
+        .unwrap();
+        let key = store.insert_stroke(Stroke::BitmapImage(bitmapimage), None);
+        store.render_components.get_mut(key).unwrap().state = RenderCompState::Complete;
+        key
+    }
+
+    fn bitmapimage_ref(store: &StrokeStore, key: super::StrokeKey) -> &render::Image {
+        match store.get_stroke_ref(key) {
+            Some(Stroke::BitmapImage(bitmapimage)) => &bitmapimage.image,
+            _ => panic!("stroke is not a bitmap image"),
+        }
+    }
+
+    #[test]
+    fn transform_strokes_each_about_their_own_pivot() {
+        let mut store = StrokeStore::default();
+        let wide = insert_rectangle(&mut store, na::vector![0.0, 0.0], na::vector![100.0, 60.0]);
+        let small = insert_rectangle(
+            &mut store,
+            na::vector![200.0, 0.0],
+            na::vector![240.0, 20.0],
+        );
+
+        // Every stroke keeps its own center, unlike when rotating them as a group
+        let transformed = store.transform_strokes_each(
+            FRAC_PI_2,
+            na::Vector2::repeat(1.0),
+            |_| true,
+            |bounds| bounds.center(),
+        );
+        assert_eq!(transformed, 2);
+        let wide_bounds = bounds(&store, wide);
+        assert_relative_eq!(wide_bounds.mins, na::point![20.0, -20.0], epsilon = 1e-6);
+        assert_relative_eq!(wide_bounds.maxs, na::point![80.0, 80.0], epsilon = 1e-6);
+        let small_bounds = bounds(&store, small);
+        assert_relative_eq!(small_bounds.mins, na::point![210.0, -10.0], epsilon = 1e-6);
+        assert_relative_eq!(small_bounds.maxs, na::point![230.0, 30.0], epsilon = 1e-6);
+
+        // Only the filtered strokes are scaled, here about their top left corner
+        let transformed = store.transform_strokes_each(
+            0.0,
+            na::Vector2::repeat(2.0),
+            |stroke| stroke.bounds().extents()[1] > 50.0,
+            |bounds| bounds.mins,
+        );
+        assert_eq!(transformed, 1);
+        let scaled_bounds = bounds(&store, wide);
+        assert_relative_eq!(scaled_bounds.mins, wide_bounds.mins, epsilon = 1e-6);
+        assert_relative_eq!(
+            scaled_bounds.extents(),
+            wide_bounds.extents() * 2.0,
+            epsilon = 1e-6
+        );
+        assert_eq!(bounds(&store, small), small_bounds);
+    }
+
+    #[test]
+    fn transform_strokes_each_rotates_then_scales() {
+        let mut store = StrokeStore::default();
+        let key = insert_rectangle(&mut store, na::vector![0.0, 0.0], na::vector![100.0, 60.0]);
+
+        let transformed = store.transform_strokes_each(
+            FRAC_PI_2,
+            na::vector![2.0, 1.0],
+            |_| true,
+            |bounds| bounds.center(),
+        );
+        assert_eq!(transformed, 1);
+        // The rotated rectangle is 60 wide before its width is doubled
+        let bounds = bounds(&store, key);
+        assert_relative_eq!(bounds.center(), na::point![50.0, 30.0], epsilon = 1e-6);
+        assert_relative_eq!(bounds.extents(), na::vector![120.0, 100.0], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn stroke_ids_are_kept_when_saving_and_not_reused() {
+        let mut engine = crate::Engine::default();
+        let first = insert_rectangle(
+            &mut engine.store,
+            na::vector![0.0, 0.0],
+            na::vector![100.0, 60.0],
+        );
+        let second = insert_rectangle(
+            &mut engine.store,
+            na::vector![200.0, 0.0],
+            na::vector![240.0, 20.0],
+        );
+        let first_id = StrokeStore::stroke_id(first);
+        engine.store.remove_stroke(first);
+        // The new stroke takes the slot of the removed one, but with a new version
+        let third = insert_rectangle(
+            &mut engine.store,
+            na::vector![0.0, 100.0],
+            na::vector![50.0, 150.0],
+        );
+        assert_ne!(StrokeStore::stroke_id(third), first_id);
+        assert_eq!(engine.store.missing_stroke_ids(&[first_id]), vec![first_id]);
+        let ids = engine.store.stroke_ids_w_type_names();
+        assert_eq!(
+            ids,
+            vec![
+                (StrokeStore::stroke_id(second), "shapestroke"),
+                (StrokeStore::stroke_id(third), "shapestroke")
+            ]
+        );
+
+        let rnote_bytes = futures::executor::block_on(async {
+            engine
+                .save_as_rnote_bytes(String::from("ids.rnote"))
+                .await
+                .unwrap()
+        })
+        .unwrap();
+        let snapshot = futures::executor::block_on(
+            crate::engine::EngineSnapshot::load_from_rnote_bytes(rnote_bytes),
+        )
+        .unwrap();
+        let mut loaded = crate::Engine::default();
+        let _ = loaded.load_snapshot(snapshot);
+        assert_eq!(loaded.store.stroke_ids_w_type_names(), ids);
+    }
+
+    #[test]
+    fn normalize_bitmap_image_formats() {
+        let mut store = StrokeStore::default();
+        // An opaque red and a half transparent green pixel, 16 bits per channel in little endian
+        let rgba16 = [[0xffff_u16, 0, 0, 0xffff], [0, 0xffff, 0, 0x8000]]
+            .iter()
+            .flatten()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let rgba16 = insert_bitmapimage(&mut store, rgba16, ImageMemoryFormat::R16g16b16a16);
+        let bgra8 = insert_bitmapimage(
+            &mut store,
+            vec![10, 20, 30, 255, 0, 0, 64, 128],
+            ImageMemoryFormat::B8g8r8a8Premultiplied,
+        );
+        let rgba8 = insert_bitmapimage(
+            &mut store,
+            vec![1, 2, 3, 255, 4, 5, 6, 255],
+            ImageMemoryFormat::R8g8b8a8Premultiplied,
+        );
+
+        assert_eq!(store.normalize_bitmap_image_formats(), 2);
+        for (key, data) in [
+            (rgba16, [255, 0, 0, 255, 0, 128, 0, 128]),
+            (bgra8, [30, 20, 10, 255, 64, 0, 0, 128]),
+            (rgba8, [1, 2, 3, 255, 4, 5, 6, 255]),
+        ] {
+            let image = bitmapimage_ref(&store, key);
+            assert_eq!(
+                image.memory_format,
+                ImageMemoryFormat::R8g8b8a8Premultiplied
+            );
+            assert_eq!(&image.data[..], &data);
+        }
+        // Only the converted images need to update their rendering
+        assert_eq!(
+            store.render_comp_state(rgba16),
+            Some(RenderCompState::Dirty)
+        );
+        assert_eq!(store.render_comp_state(bgra8), Some(RenderCompState::Dirty));
+        assert_eq!(
+            store.render_comp_state(rgba8),
+            Some(RenderCompState::Complete)
+        );
+
+        for key in [rgba16, bgra8] {
+            let stroke = store.get_stroke_ref(key).unwrap();
+            let rendered =
+                render::Image::gen_with_piet(|cx| stroke.draw(cx, 1.0), stroke.bounds(), 1.0)
+                    .unwrap();
+            assert_eq!((rendered.pixel_width, rendered.pixel_height), (2, 1));
+        }
+        assert_eq!(store.normalize_bitmap_image_formats(), 0);
+    }
+}
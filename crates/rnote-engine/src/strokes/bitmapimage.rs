@@ -1,8 +1,8 @@
 // Imports
 use super::resize::{calculate_resize_ratio, ImageSizeOption};
-use super::{Content, Stroke};
+use super::{Content, PdfLink};
 use crate::document::Format;
-use crate::engine::import::{PdfImportPageSpacing, PdfImportPrefs};
+use crate::engine::import::{PdfImportPrefs, PdfPagePlacer};
 use crate::render;
 use crate::Drawable;
 use anyhow::Context;
@@ -17,6 +17,7 @@ use rnote_compose::transform::Transform;
 use rnote_compose::transform::Transformable;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename = "bitmapimage")]
@@ -29,6 +30,9 @@ pub struct BitmapImage {
     pub image: render::Image,
     #[serde(rename = "rectangle")]
     pub rectangle: Rectangle,
+    /// Hyperlinks when the image was imported from a Pdf page.
+    #[serde(rename = "links")]
+    pub links: Vec<PdfLink>,
 }
 
 impl Default for BitmapImage {
@@ -36,6 +40,7 @@ impl Default for BitmapImage {
         Self {
             image: render::Image::default(),
             rectangle: Rectangle::default(),
+            links: Vec::default(),
         }
     }
 }
@@ -46,7 +51,7 @@ impl Content for BitmapImage {
 
 impl Drawable for BitmapImage {
     fn draw(&self, cx: &mut impl piet::RenderContext, _image_scale: f64) -> anyhow::Result<()> {
-        let piet_image_format = piet::ImageFormat::from(self.image.memory_format);
+        let (data, piet_image_format) = self.image.piet_image_data();
 
         cx.save().map_err(|e| anyhow::anyhow!("{e:?}"))?;
         cx.transform(self.rectangle.transform.affine.to_kurbo());
@@ -55,7 +60,7 @@ impl Drawable for BitmapImage {
             .make_image(
                 self.image.pixel_width as usize,
                 self.image.pixel_height as usize,
-                &self.image.data,
+                &data,
                 piet_image_format,
             )
             .map_err(|e| {
@@ -83,28 +88,80 @@ impl Shapeable for BitmapImage {
     }
 }
 
+// Non-finite or zero values would corrupt the rectangle transform so that the bounds can't be computed anymore,
+// so they are rejected.
 impl Transformable for BitmapImage {
     fn translate(&mut self, offset: na::Vector2<f64>) {
+        if !offset.iter().all(|v| v.is_finite()) {
+            warn!("Translating BitmapImage failed, invalid offset {offset:?}");
+            return;
+        }
         self.rectangle.translate(offset);
     }
 
     fn rotate(&mut self, angle: f64, center: na::Point2<f64>) {
+        if !angle.is_finite() || !center.iter().all(|v| v.is_finite()) {
+            warn!("Rotating BitmapImage failed, invalid angle {angle} or center {center:?}");
+            return;
+        }
         self.rectangle.rotate(angle, center);
     }
 
     fn scale(&mut self, scale: na::Vector2<f64>) {
+        if !scale.iter().all(|v| v.is_finite() && *v != 0.0) {
+            warn!("Scaling BitmapImage failed, invalid scale {scale:?}");
+            return;
+        }
         self.rectangle.scale(scale);
     }
 }
 
 impl BitmapImage {
+    /// The largest angle images are straightened by. Larger rotations are most likely intended.
+    pub const DESKEW_ANGLE_MAX: f64 = 5.0 * std::f64::consts::PI / 180.0;
+
+    /// Straightens the image when its content is slightly rotated, e.g. on scanned pages,
+    /// by rotating the rectangle around its center.
+    ///
+    /// Returns the angle in radians the content was detected to be rotated by,
+    /// or `None` when the image has not enough content to detect it.
+    pub fn deskew(&mut self) -> anyhow::Result<Option<f64>> {
+        let Some(pixel_angle) = self.image.detect_skew_angle(Self::DESKEW_ANGLE_MAX)? else {
+            return Ok(None);
+        };
+        // The angle changes when the image is stretched to its rectangle
+        let size = self.rectangle.cuboid.half_extents * 2.0;
+        let stretch = (size[1] / f64::from(self.image.pixel_height))
+            / (size[0] / f64::from(self.image.pixel_width));
+        let angle = (pixel_angle.tan() * stretch).atan();
+        let center = self.bounds().center();
+        self.rotate(-angle, center);
+        Ok(Some(angle))
+    }
+
     pub fn from_image_bytes(
         bytes: &[u8],
         pos: na::Vector2<f64>,
         size_option: ImageSizeOption,
     ) -> Result<Self, anyhow::Error> {
-        let image = render::Image::try_from_encoded_bytes(bytes)?;
+        Self::from_image(
+            render::Image::try_from_encoded_bytes(bytes)?,
+            pos,
+            size_option,
+        )
+    }
 
+    /// Creates a bitmap image from an already decoded image.
+    pub fn from_image(
+        image: render::Image,
+        pos: na::Vector2<f64>,
+        size_option: ImageSizeOption,
+    ) -> Result<Self, anyhow::Error> {
+        if !pos.iter().all(|v| v.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Creating BitmapImage failed, invalid position {pos:?}."
+            ));
+        }
         let initial_size = na::vector![f64::from(image.pixel_width), f64::from(image.pixel_height)];
 
         let (size, resize_ratio) = match size_option {
@@ -115,6 +172,18 @@ impl BitmapImage {
                 calculate_resize_ratio(resize_struct, initial_size, pos),
             ),
         };
+        if !size.iter().all(|v| v.is_finite() && *v > 0.0) {
+            return Err(anyhow::anyhow!(
+                "Creating BitmapImage failed, invalid size {size:?}."
+            ));
+        }
+        // An invalid ratio would make the image disappear, so it is imported at its original size instead
+        let resize_ratio = if resize_ratio.is_finite() && resize_ratio > 0.0 {
+            resize_ratio
+        } else {
+            warn!("Invalid resize ratio {resize_ratio} while creating BitmapImage, using the original size");
+            1.0
+        };
 
         let mut transform = Transform::default();
         transform.append_scale_mut(na::Vector2::new(resize_ratio, resize_ratio));
@@ -123,7 +192,11 @@ impl BitmapImage {
             cuboid: p2d::shape::Cuboid::new(size * 0.5),
             transform,
         };
-        Ok(Self { image, rectangle })
+        Ok(Self {
+            image,
+            rectangle,
+            links: Vec::default(),
+        })
     }
 
     pub fn from_pdf_bytes(
@@ -134,101 +207,732 @@ impl BitmapImage {
         format: &Format,
         password: Option<String>,
     ) -> Result<Vec<Self>, anyhow::Error> {
-        let doc =
-            poppler::Document::from_bytes(&glib::Bytes::from(to_be_read), password.as_deref())?;
-        let page_range = page_range.unwrap_or(0..doc.n_pages() as u32);
-        let page_width = if pdf_import_prefs.adjust_document {
-            format.width()
+        PdfBitmapPages::new(
+            to_be_read,
+            pdf_import_prefs,
+            insert_pos,
+            page_range,
+            format,
+            password,
+            PdfBitmapPages::CHUNK_SIZE_DEFAULT,
+        )?
+        .collect()
+    }
+}
+
+#[cfg(feature = "epub")]
+impl BitmapImage {
+    /// The size of the pages the text of Epub files is laid out on, in Pdf units (A5).
+    pub const EPUB_PAGE_SIZE: na::Vector2<f64> = na::vector![419.53, 595.28];
+    const EPUB_PAGE_MARGIN: f64 = 48.0;
+    const EPUB_FONT_SIZE: f64 = 11.0;
+    const EPUB_HEADING_FONT_SIZE: f64 = 16.0;
+    /// The vertical space between text blocks.
+    const EPUB_BLOCK_SPACING: f64 = 8.0;
+
+    /// Lays out the text of the Epub on pages and renders them into bitmap images.
+    ///
+    /// Every chapter starts on a new page. The pages are sized and spaced like imported Pdf pages.
+    pub fn from_epub_bytes(
+        to_be_read: &[u8],
+        pdf_import_prefs: PdfImportPrefs,
+        insert_pos: na::Vector2<f64>,
+        format: &Format,
+    ) -> Result<Vec<Self>, anyhow::Error> {
+        use crate::fileformats::epubformat::EpubFile;
+        use crate::fileformats::FileFormatLoader;
+        use rayon::iter::IndexedParallelIterator;
+
+        let epub = EpubFile::load_from_bytes(to_be_read)?;
+        let page_zoom = pdf_import_prefs.page_width(format) / Self::EPUB_PAGE_SIZE[0];
+        let page_size = Self::EPUB_PAGE_SIZE * page_zoom;
+        let pngs = Self::paginate_epub(&epub)?
+            .iter()
+            .map(|page| Self::render_epub_page_png(page, page_zoom, pdf_import_prefs))
+            .collect::<anyhow::Result<Vec<Vec<u8>>>>()?;
+
+        let mut placer = pdf_import_prefs.page_placer(insert_pos, format);
+        let positions = (0..pngs.len())
+            .map(|_| placer.place(page_size))
+            .collect::<Vec<na::Vector2<f64>>>();
+        pngs.into_par_iter()
+            .zip(positions)
+            .map(|(png_data, pos)| {
+                BitmapImage::from_image_bytes(
+                    &png_data,
+                    pos,
+                    ImageSizeOption::ImposeSize(page_size),
+                )
+            })
+            .collect()
+    }
+
+    fn epub_text_layout(
+        piet_cx: &mut piet_cairo::CairoRenderContext,
+        text: String,
+        heading: bool,
+    ) -> anyhow::Result<piet_cairo::CairoTextLayout> {
+        use piet::{RenderContext, Text, TextLayoutBuilder};
+
+        let (font_size, font_weight) = if heading {
+            (Self::EPUB_HEADING_FONT_SIZE, piet::FontWeight::BOLD)
         } else {
-            format.width() * (pdf_import_prefs.page_width_perc / 100.0)
+            (Self::EPUB_FONT_SIZE, piet::FontWeight::NORMAL)
         };
+        piet_cx
+            .text()
+            .new_text_layout(text)
+            .font(piet::FontFamily::SERIF, font_size)
+            .default_attribute(font_weight)
+            .text_color(piet::Color::BLACK)
+            .max_width(Self::EPUB_PAGE_SIZE[0] - 2.0 * Self::EPUB_PAGE_MARGIN)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Building Epub text layout failed, Err: {e:?}"))
+    }
+
+    /// Distributes the text blocks of the chapters onto pages, splitting blocks between lines at page ends.
+    fn paginate_epub(
+        epub: &crate::fileformats::epubformat::EpubFile,
+    ) -> anyhow::Result<Vec<Vec<EpubPageBlock>>> {
+        use piet::TextLayout;
+
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)?;
+        let cairo_cx = cairo::Context::new(&surface)?;
+        let mut piet_cx = piet_cairo::CairoRenderContext::new(&cairo_cx);
+        let bottom = Self::EPUB_PAGE_SIZE[1] - Self::EPUB_PAGE_MARGIN;
+        let mut pages = Vec::new();
+
+        for chapter in epub.chapters.iter() {
+            let mut page = Vec::new();
+            let mut y = Self::EPUB_PAGE_MARGIN;
+            for block in chapter.blocks.iter() {
+                let text_layout =
+                    Self::epub_text_layout(&mut piet_cx, block.text.clone(), block.heading)?;
+                let line_count = text_layout.line_count();
+                let mut line_i = 0;
+                while line_i < line_count {
+                    let Some(start) = text_layout.line_metric(line_i) else {
+                        break;
+                    };
+                    let lines_end = (line_i..line_count)
+                        .take_while(|&i| {
+                            text_layout.line_metric(i).is_some_and(|line| {
+                                y + line.y_offset + line.height - start.y_offset <= bottom
+                            })
+                        })
+                        .last()
+                        .map(|i| i + 1);
+                    let lines_end = match lines_end {
+                        Some(lines_end) => lines_end,
+                        // Lines that are taller than an empty page are placed anyway
+                        None if page.is_empty() => line_i + 1,
+                        None => {
+                            pages.push(std::mem::take(&mut page));
+                            y = Self::EPUB_PAGE_MARGIN;
+                            continue;
+                        }
+                    };
+                    let Some(end) = text_layout.line_metric(lines_end - 1) else {
+                        break;
+                    };
+                    page.push(EpubPageBlock {
+                        text: block.text[start.start_offset..end.end_offset]
+                            .trim_end()
+                            .to_string(),
+                        heading: block.heading,
+                        y,
+                    });
+                    y += end.y_offset + end.height - start.y_offset;
+                    line_i = lines_end;
+                    if line_i < line_count {
+                        pages.push(std::mem::take(&mut page));
+                        y = Self::EPUB_PAGE_MARGIN;
+                    }
+                }
+                y += Self::EPUB_BLOCK_SPACING;
+            }
+            if !page.is_empty() {
+                pages.push(page);
+            }
+        }
+        Ok(pages)
+    }
+
+    fn render_epub_page_png(
+        page: &[EpubPageBlock],
+        page_zoom: f64,
+        pdf_import_prefs: PdfImportPrefs,
+    ) -> anyhow::Result<Vec<u8>> {
+        use piet::RenderContext;
+
+        let scale = page_zoom * pdf_import_prefs.bitmap_scalefactor;
+        let surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            (Self::EPUB_PAGE_SIZE[0] * scale).round() as i32,
+            (Self::EPUB_PAGE_SIZE[1] * scale).round() as i32,
+        )
+        .map_err(|e| {
+            anyhow::anyhow!("Creating image surface while importing Epub page failed, Err: {e:?}")
+        })?;
+
+        {
+            let cairo_cx =
+                cairo::Context::new(&surface).context("Creating new cairo Context failed")?;
+            cairo_cx.scale(scale, scale);
+            cairo_cx.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+            cairo_cx.paint()?;
+
+            let mut piet_cx = piet_cairo::CairoRenderContext::new(&cairo_cx);
+            for block in page {
+                let text_layout =
+                    Self::epub_text_layout(&mut piet_cx, block.text.clone(), block.heading)?;
+                piet_cx.draw_text(&text_layout, (Self::EPUB_PAGE_MARGIN, block.y));
+            }
+            piet_cx.finish().map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            drop(piet_cx);
+
+            if pdf_import_prefs.page_borders {
+                let (red, green, blue, _) = color::GNOME_REDS[4].as_rgba();
+                cairo_cx.set_source_rgba(red, green, blue, 1.0);
+
+                let line_width = 1.0;
+                cairo_cx.set_line_width(line_width);
+                cairo_cx.rectangle(
+                    line_width * 0.5,
+                    line_width * 0.5,
+                    Self::EPUB_PAGE_SIZE[0] - line_width,
+                    Self::EPUB_PAGE_SIZE[1] - line_width,
+                );
+                cairo_cx.stroke()?;
+            }
+        }
+
+        let mut png_data: Vec<u8> = Vec::new();
+        surface.write_to_png(&mut png_data)?;
+        Ok(png_data)
+    }
+}
+
+/// The lines of a text block of an Epub that are placed on a page.
+#[cfg(feature = "epub")]
+#[derive(Debug, Clone)]
+struct EpubPageBlock {
+    text: String,
+    heading: bool,
+    /// The top of the text on the page, in Pdf units.
+    y: f64,
+}
+
+/// Lazily renders the pages of a Pdf into bitmap images.
+///
+/// Pages are rendered in chunks of `chunk_size` pages and decoded in parallel,
+/// so only a single chunk is held in memory at a time. The images are yielded in page order.
+#[derive(Debug)]
+pub struct PdfBitmapPages {
+    doc: poppler::Document,
+    pdf_import_prefs: PdfImportPrefs,
+    page_range: Range<u32>,
+    page_zoom: f64,
+    /// The size in Pdf units the pages are padded to.
+    padded_size: Option<na::Vector2<f64>>,
+    placer: PdfPagePlacer,
+    chunk_size: usize,
+    chunk: std::vec::IntoIter<anyhow::Result<BitmapImage>>,
+}
+
+impl PdfBitmapPages {
+    /// The default amount of pages that are rendered at once.
+    pub const CHUNK_SIZE_DEFAULT: usize = 8;
+
+    pub fn new(
+        to_be_read: &[u8],
+        pdf_import_prefs: PdfImportPrefs,
+        insert_pos: na::Vector2<f64>,
+        page_range: Option<Range<u32>>,
+        format: &Format,
+        password: Option<String>,
+        chunk_size: usize,
+    ) -> anyhow::Result<Self> {
+        let doc =
+            poppler::Document::from_bytes(&glib::Bytes::from(to_be_read), password.as_deref())?;
+        let mut page_range = page_range.unwrap_or(0..doc.n_pages() as u32);
+        let page_width = pdf_import_prefs.page_width(format);
         // calculate the page zoom based on the width of the first page.
         let page_zoom = if let Some(first_page) = doc.page(0) {
             page_width / first_page.size().0
         } else {
-            return Ok(vec![]);
+            // No pages, nothing to yield
+            page_range = 0..0;
+            1.0
         };
-        let x = insert_pos[0];
-        let mut y = insert_pos[1];
-
-        let pngs = page_range
-            .map(|page_i| {
-                let page = doc
-                    .page(page_i as i32)
-                    .ok_or_else(|| anyhow::anyhow!("no page at index '{page_i}"))?;
-                let (intrinsic_width, intrinsic_height) = page.size();
-                let width = intrinsic_width * page_zoom;
-                let height = intrinsic_height * page_zoom;
-                let surface_width = (width * pdf_import_prefs.bitmap_scalefactor).round() as i32;
-                let surface_height = (height * pdf_import_prefs.bitmap_scalefactor).round() as i32;
-                let surface = cairo::ImageSurface::create(
-                    cairo::Format::ARgb32,
-                    surface_width,
-                    surface_height,
-                )
+        let padded_size = pdf_import_prefs.pad_to.padded_page_size(
+            page_range
+                .clone()
+                .filter_map(|page_i| doc.page(page_i as i32))
+                .map(|page| {
+                    let (width, height) = page.size();
+                    na::vector![width, height]
+                }),
+            format.width() / format.height(),
+        );
+
+        Ok(Self {
+            doc,
+            pdf_import_prefs,
+            page_range,
+            page_zoom,
+            padded_size,
+            placer: pdf_import_prefs.page_placer(insert_pos, format),
+            chunk_size: chunk_size.max(1),
+            chunk: Vec::new().into_iter(),
+        })
+    }
+
+    /// The amount of pages that are not yet yielded, including the ones in the current chunk.
+    pub fn remaining_pages(&self) -> usize {
+        self.page_range.len() + self.chunk.len()
+    }
+
+    /// Render the next chunk of pages to Png data.
+    fn render_next_chunk(&mut self) {
+        let chunk_end = self
+            .page_range
+            .end
+            .min(self.page_range.start.saturating_add(self.chunk_size as u32));
+        let chunk_range = self.page_range.start..chunk_end;
+        self.page_range.start = chunk_end;
+
+        let mut pngs = Vec::with_capacity(chunk_range.len());
+        for page_i in chunk_range {
+            let png = self.render_page_png(page_i);
+            let stop = png.is_err();
+            pngs.push(png);
+            if stop {
+                // Stop rendering after the first error
+                self.page_range = self.page_range.end..self.page_range.end;
+                break;
+            }
+        }
+
+        let whiten_threshold = self.pdf_import_prefs.whiten_threshold;
+        self.chunk = pngs
+            .into_par_iter()
+            .map(|png| {
+                let (png_data, pos, size, links) = png?;
+                let mut bitmapimage = BitmapImage::from_image_bytes(
+                    &png_data,
+                    pos,
+                    ImageSizeOption::ImposeSize(size),
+                )?;
+                if let Some(threshold) = whiten_threshold {
+                    bitmapimage.image.whiten(threshold);
+                }
+                bitmapimage.links = links;
+                Ok(bitmapimage)
+            })
+            .collect::<Vec<anyhow::Result<BitmapImage>>>()
+            .into_iter();
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn render_page_png(
+        &mut self,
+        page_i: u32,
+    ) -> anyhow::Result<(Vec<u8>, na::Vector2<f64>, na::Vector2<f64>, Vec<PdfLink>)> {
+        let pdf_import_prefs = self.pdf_import_prefs;
+        let page_zoom = self.page_zoom;
+        let page = self
+            .doc
+            .page(page_i as i32)
+            .ok_or_else(|| anyhow::anyhow!("no page at index '{page_i}"))?;
+        let (intrinsic_width, intrinsic_height) = page.size();
+        let intrinsic_size = na::vector![intrinsic_width, intrinsic_height];
+        let padded_size = self
+            .padded_size
+            .map_or(intrinsic_size, |size| size.sup(&intrinsic_size));
+        let padding_offset = (padded_size - intrinsic_size) * 0.5;
+        let links = if pdf_import_prefs.preserve_links {
+            let mut links = PdfLink::extract_from_pdf_page(&page);
+            links
+                .iter_mut()
+                .for_each(|link| link.remap_to_padded_page(intrinsic_size, padded_size));
+            links
+        } else {
+            vec![]
+        };
+        let width = padded_size[0] * page_zoom;
+        let height = padded_size[1] * page_zoom;
+        let surface_width = (width * pdf_import_prefs.bitmap_scalefactor).round() as i32;
+        let surface_height = (height * pdf_import_prefs.bitmap_scalefactor).round() as i32;
+        let surface =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, surface_width, surface_height)
                 .map_err(|e| {
                     anyhow::anyhow!(
                         "Creating image surface while importing bitmapimage failed, Err: {e:?}"
                     )
                 })?;
 
-                {
-                    let cx = cairo::Context::new(&surface)
-                        .context("Creating new cairo Context failed")?;
-
-                    // Scale with the bitmap scalefactor pref
-                    cx.scale(
-                        page_zoom * pdf_import_prefs.bitmap_scalefactor,
-                        page_zoom * pdf_import_prefs.bitmap_scalefactor,
-                    );
-
-                    // Set margin to white
-                    cx.set_source_rgba(1.0, 1.0, 1.0, 1.0);
-                    cx.paint()?;
-
-                    page.render_for_printing(&cx);
-
-                    if pdf_import_prefs.page_borders {
-                        // Draw outline around page
-                        let (red, green, blue, _) = color::GNOME_REDS[4].as_rgba();
-                        cx.set_source_rgba(red, green, blue, 1.0);
-
-                        let line_width = 1.0;
-                        cx.set_line_width(line_width);
-                        cx.rectangle(
-                            line_width * 0.5,
-                            line_width * 0.5,
-                            intrinsic_width - line_width,
-                            intrinsic_height - line_width,
-                        );
-                        cx.stroke()?;
-                    }
-                }
+        {
+            let cx = cairo::Context::new(&surface).context("Creating new cairo Context failed")?;
 
-                let mut png_data: Vec<u8> = Vec::new();
-                surface.write_to_png(&mut png_data)?;
-                let image_pos = na::vector![x, y];
-                let image_size = na::vector![width, height];
-
-                if pdf_import_prefs.adjust_document {
-                    y += height
-                } else {
-                    y += match pdf_import_prefs.page_spacing {
-                        PdfImportPageSpacing::Continuous => {
-                            height + Stroke::IMPORT_OFFSET_DEFAULT[1] * 0.5
-                        }
-                        PdfImportPageSpacing::OnePerDocumentPage => format.height(),
-                    };
+            // Scale with the bitmap scalefactor pref
+            cx.scale(
+                page_zoom * pdf_import_prefs.bitmap_scalefactor,
+                page_zoom * pdf_import_prefs.bitmap_scalefactor,
+            );
+
+            // Set margin to white
+            cx.set_source_rgba(1.0, 1.0, 1.0, 1.0);
+            cx.paint()?;
+
+            // Center the page content on the padded page
+            cx.translate(padding_offset[0], padding_offset[1]);
+
+            pdf_import_prefs.form_fields.render_page(&page, &cx);
+
+            if pdf_import_prefs.page_borders {
+                // Draw outline around page
+                let (red, green, blue, _) = color::GNOME_REDS[4].as_rgba();
+                cx.set_source_rgba(red, green, blue, 1.0);
+
+                let line_width = 1.0;
+                cx.set_line_width(line_width);
+                cx.rectangle(
+                    line_width * 0.5,
+                    line_width * 0.5,
+                    intrinsic_width - line_width,
+                    intrinsic_height - line_width,
+                );
+                cx.stroke()?;
+            }
+        }
+
+        let mut png_data: Vec<u8> = Vec::new();
+        surface.write_to_png(&mut png_data)?;
+        let image_size = na::vector![width, height];
+        let image_pos = self.placer.place(image_size);
+
+        Ok((png_data, image_pos, image_size, links))
+    }
+}
+
+impl Iterator for PdfBitmapPages {
+    type Item = anyhow::Result<BitmapImage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.as_slice().is_empty() {
+            if self.page_range.is_empty() {
+                return None;
+            }
+            self.render_next_chunk();
+        }
+        self.chunk.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining_pages()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::import::{PdfImportFormFields, PdfImportPadTo};
+    use approx::assert_relative_eq;
+
+    fn bitmapimage() -> BitmapImage {
+        let png = render::Image::from(image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255])),
+        ))
+        .into_encoded_bytes(image::ImageFormat::Png, None)
+        .unwrap();
+        BitmapImage::from_image_bytes(
+            &png,
+            na::vector![10.0, 20.0],
+            ImageSizeOption::RespectOriginalSize,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn invalid_transforms_keep_image_renderable() {
+        let mut bitmapimage = bitmapimage();
+        let bounds = bitmapimage.bounds();
+
+        bitmapimage.translate(na::vector![f64::NAN, 0.0]);
+        bitmapimage.translate(na::vector![0.0, f64::INFINITY]);
+        bitmapimage.rotate(f64::NAN, na::point![0.0, 0.0]);
+        bitmapimage.rotate(1.0, na::point![f64::NEG_INFINITY, 0.0]);
+        bitmapimage.scale(na::vector![0.0, 1.0]);
+        bitmapimage.scale(na::vector![1.0, f64::NAN]);
+        assert_eq!(bitmapimage.bounds(), bounds);
+        bitmapimage.bounds().assert_valid().unwrap();
+        render::Image::gen_with_piet(
+            |piet_cx| bitmapimage.draw(piet_cx, 1.0),
+            bitmapimage.bounds(),
+            1.0,
+        )
+        .unwrap();
+
+        // Valid transforms are still applied
+        bitmapimage.scale(na::vector![2.0, -1.0]);
+        assert_ne!(bitmapimage.bounds(), bounds);
+        bitmapimage.bounds().assert_valid().unwrap();
+    }
+
+    #[test]
+    fn invalid_import_sizes_are_rejected() {
+        let png = render::Image::from(image::DynamicImage::ImageRgba8(image::RgbaImage::new(4, 2)))
+            .into_encoded_bytes(image::ImageFormat::Png, None)
+            .unwrap();
+        for size in [
+            na::vector![0.0, 10.0],
+            na::vector![10.0, f64::NAN],
+            na::vector![f64::INFINITY, 10.0],
+        ] {
+            assert!(BitmapImage::from_image_bytes(
+                &png,
+                na::vector![0.0, 0.0],
+                ImageSizeOption::ImposeSize(size)
+            )
+            .is_err());
+        }
+        assert!(BitmapImage::from_image_bytes(
+            &png,
+            na::vector![f64::NAN, 0.0],
+            ImageSizeOption::RespectOriginalSize
+        )
+        .is_err());
+    }
+
+    /// A Pdf page with a filled text field, without the print flag and with an appearance stream showing its value.
+    fn filled_form_pdf() -> Vec<u8> {
+        let appearance = "/Tx BMC BT /Helv 24 Tf 0 g 4 12 Td (Filled) Tj ET EMC";
+        let objects = [
+            String::from(
+                "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] \
+                /DR << /Font << /Helv 5 0 R >> >> /DA (/Helv 0 Tf 0 g) >> >>",
+            ),
+            String::from("<< /Type /Pages /Kids [3 0 R] /Count 1 >>"),
+            String::from(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 100] /Annots [4 0 R] >>",
+            ),
+            String::from(
+                "<< /Type /Annot /Subtype /Widget /FT /Tx /T (name) /V (Filled) /F 0 \
+                /Rect [20 30 180 70] /DA (/Helv 24 Tf 0 g) /P 3 0 R /AP << /N 6 0 R >> >>",
+            ),
+            String::from(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+            ),
+            format!(
+                "<< /Type /XObject /Subtype /Form /BBox [0 0 160 40] \
+                /Resources << /Font << /Helv 5 0 R >> >> /Length {} >>\nstream\n{appearance}\nendstream",
+                appearance.len()
+            ),
+        ];
+        let mut pdf = String::from("%PDF-1.7\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{object}\nendobj\n", i + 1));
+        }
+        let xref_offset = pdf.len();
+        pdf.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            pdf.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        pdf.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        ));
+        pdf.into_bytes()
+    }
+
+    #[test]
+    fn pdf_form_fields_are_rendered_filled_or_empty() {
+        let dark_pixels = |form_fields| {
+            let pdf_import_prefs = PdfImportPrefs {
+                page_borders: false,
+                form_fields,
+                ..Default::default()
+            };
+            let bitmapimage = PdfBitmapPages::new(
+                &filled_form_pdf(),
+                pdf_import_prefs,
+                na::vector![0.0, 0.0],
+                None,
+                &Format::default(),
+                None,
+                PdfBitmapPages::CHUNK_SIZE_DEFAULT,
+            )
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+            bitmapimage
+                .image
+                .data
+                .chunks_exact(4)
+                .filter(|pixel| pixel[..3].iter().all(|&channel| channel < 128))
+                .count()
+        };
+
+        assert!(dark_pixels(PdfImportFormFields::Filled) > 0);
+        assert_eq!(dark_pixels(PdfImportFormFields::Empty), 0);
+    }
+
+    /// A Pdf with pages of the given sizes, filled with the gray level.
+    fn filled_pages_pdf(page_sizes: &[(f64, f64)], gray: f64) -> Vec<u8> {
+        let surface =
+            cairo::PdfSurface::for_stream(page_sizes[0].0, page_sizes[0].1, Vec::<u8>::new())
+                .unwrap();
+        {
+            let cx = cairo::Context::new(&surface).unwrap();
+            for &(width, height) in page_sizes {
+                surface.set_size(width, height).unwrap();
+                cx.set_source_rgb(gray, gray, gray);
+                cx.paint().unwrap();
+                cx.show_page().unwrap();
+            }
+        }
+        *surface
+            .finish_output_stream()
+            .unwrap()
+            .downcast::<Vec<u8>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn pdf_pages_padded_to_format_are_uniform_and_centered() {
+        let format = Format::default();
+        let pdf_import_prefs = PdfImportPrefs {
+            pad_to: PdfImportPadTo::Format,
+            page_borders: false,
+            ..Default::default()
+        };
+        let images = PdfBitmapPages::new(
+            &filled_pages_pdf(&[(100.0, 100.0), (200.0, 50.0)], 0.0),
+            pdf_import_prefs,
+            na::vector![0.0, 0.0],
+            None,
+            &format,
+            None,
+            PdfBitmapPages::CHUNK_SIZE_DEFAULT,
+        )
+        .unwrap()
+        .collect::<anyhow::Result<Vec<BitmapImage>>>()
+        .unwrap();
+        assert_eq!(images.len(), 2);
+
+        let (pixel_width, pixel_height) =
+            (images[0].image.pixel_width, images[0].image.pixel_height);
+        for bitmapimage in &images {
+            let image = &bitmapimage.image;
+            assert_eq!(
+                (image.pixel_width, image.pixel_height),
+                (pixel_width, pixel_height)
+            );
+            assert_relative_eq!(
+                f64::from(image.pixel_width) / f64::from(image.pixel_height),
+                format.width() / format.height(),
+                epsilon = 0.02
+            );
+
+            // The margins around the black page content are equal on opposite sides
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+            for (i, pixel) in image.data.chunks_exact(4).enumerate() {
+                if pixel[..3].iter().all(|&channel| channel < 128) {
+                    let (x, y) = (i as u32 % image.pixel_width, i as u32 / image.pixel_width);
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
                 }
+            }
+            assert!(min_x <= max_x && min_y <= max_y);
+            assert!(min_x.abs_diff(image.pixel_width - 1 - max_x) <= 2);
+            assert!(min_y.abs_diff(image.pixel_height - 1 - max_y) <= 2);
+        }
+    }
 
-                Ok((png_data, image_pos, image_size))
-            })
-            .collect::<anyhow::Result<Vec<(Vec<u8>, na::Vector2<f64>, na::Vector2<f64>)>>>()?;
+    #[test]
+    fn pdf_pages_whiten_bleed_through() {
+        // A page with faint bleed-through
+        let pdf = filled_pages_pdf(&[(100.0, 100.0)], 0.93);
+        let page_pixels = |whiten_threshold| {
+            let pdf_import_prefs = PdfImportPrefs {
+                page_borders: false,
+                whiten_threshold,
+                ..Default::default()
+            };
+            PdfBitmapPages::new(
+                &pdf,
+                pdf_import_prefs,
+                na::vector![0.0, 0.0],
+                None,
+                &Format::default(),
+                None,
+                PdfBitmapPages::CHUNK_SIZE_DEFAULT,
+            )
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .image
+            .into_imgbuf()
+            .unwrap()
+        };
 
-        pngs.into_par_iter()
-            .map(|(png_data, pos, size)| {
-                Self::from_image_bytes(&png_data, pos, ImageSizeOption::ImposeSize(size))
-            })
-            .collect()
+        let unchanged = page_pixels(None);
+        assert_ne!(
+            unchanged
+                .get_pixel(unchanged.width() / 2, unchanged.height() / 2)
+                .0,
+            [255, 255, 255, 255]
+        );
+        assert!(page_pixels(Some(PdfImportPrefs::WHITEN_THRESHOLD_DEFAULT))
+            .pixels()
+            .all(|pixel| pixel.0 == [255, 255, 255, 255]));
+    }
+
+    #[cfg(feature = "epub")]
+    #[test]
+    fn epub_chapters_are_paginated_and_placed_like_pdf_pages() {
+        use crate::fileformats::epubformat::{self, EpubFile};
+        use crate::fileformats::FileFormatLoader;
+
+        let long_chapter = (0..80)
+            .map(|i| format!("<p>Paragraph {i} with enough words to wrap onto a second line of the page.</p>"))
+            .collect::<String>();
+        let bytes = epubformat::tests::epub_bytes("Pages", &[&long_chapter, "<p>Short.</p>"]);
+
+        let pages =
+            BitmapImage::paginate_epub(&EpubFile::load_from_bytes(&bytes).unwrap()).unwrap();
+        assert!(pages.len() >= 3);
+        // The second chapter starts on a new page
+        let last_page = pages.last().unwrap();
+        assert_eq!(last_page.len(), 1);
+        assert_eq!(last_page[0].text, "Short.");
+
+        let pdf_import_prefs = PdfImportPrefs {
+            page_borders: false,
+            ..Default::default()
+        };
+        let images = BitmapImage::from_epub_bytes(
+            &bytes,
+            pdf_import_prefs,
+            na::vector![0.0, 0.0],
+            &Format::default(),
+        )
+        .unwrap();
+        assert_eq!(images.len(), pages.len());
+        let page_size = images[0].rectangle.cuboid.half_extents * 2.0;
+        for pair in images.windows(2) {
+            assert_eq!(pair[1].rectangle.cuboid.half_extents * 2.0, page_size);
+            assert!(pair[1].bounds().mins[1] >= pair[0].bounds().maxs[1]);
+        }
     }
 }
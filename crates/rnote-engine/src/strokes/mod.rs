@@ -2,6 +2,7 @@
 pub mod bitmapimage;
 pub mod brushstroke;
 pub mod content;
+pub mod pdflink;
 pub mod resize;
 pub mod shapestroke;
 pub mod stroke;
@@ -9,9 +10,10 @@ pub mod textstroke;
 pub mod vectorimage;
 
 // Re-exports
-pub use bitmapimage::BitmapImage;
+pub use bitmapimage::{BitmapImage, PdfBitmapPages};
 pub use brushstroke::BrushStroke;
 pub use content::Content;
+pub use pdflink::PdfLink;
 pub use resize::Resize;
 pub use shapestroke::ShapeStroke;
 pub use stroke::Stroke;
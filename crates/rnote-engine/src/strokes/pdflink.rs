@@ -0,0 +1,112 @@
+// Imports
+use glib::translate::ToGlibPtr;
+use p2d::bounding_volume::Aabb;
+use rnote_compose::shapes::Rectangle;
+use serde::{Deserialize, Serialize};
+
+/// A hyperlink area of an imported Pdf page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename = "pdf_link")]
+pub struct PdfLink {
+    /// The target Uri of the link.
+    #[serde(rename = "uri")]
+    pub uri: String,
+    /// The area of the link relative to the page, with coordinates in the range `0.0..=1.0`.
+    #[serde(rename = "area")]
+    pub area: Aabb,
+}
+
+impl Default for PdfLink {
+    fn default() -> Self {
+        Self {
+            uri: String::default(),
+            area: Aabb::new(na::point![0.0, 0.0], na::point![0.0, 0.0]),
+        }
+    }
+}
+
+impl PdfLink {
+    /// Extract the Uri links of a Pdf page.
+    ///
+    /// Links with other actions (e.g. jumps to destinations inside the document) are skipped.
+    pub(crate) fn extract_from_pdf_page(page: &poppler::Page) -> Vec<Self> {
+        let (page_width, page_height) = page.size();
+        if page_width <= 0.0 || page_height <= 0.0 {
+            return vec![];
+        }
+
+        page.link_mapping()
+            .into_iter()
+            .filter_map(|mapping| {
+                // The bindings don't expose the area and action of link mappings, so they are read from the struct
+                let mapping_ptr: *const poppler::ffi::PopplerLinkMapping = mapping.to_glib_none().0;
+                // SAFETY: the pointer is valid while `mapping` is alive. The action is either null or owned by the mapping,
+                // and its type tells which member of the union is initialized.
+                let (area, uri) = unsafe {
+                    let mapping = &*mapping_ptr;
+                    let action = mapping.action.as_ref()?;
+                    if action.type_ != poppler::ffi::POPPLER_ACTION_URI || action.uri.uri.is_null()
+                    {
+                        return None;
+                    }
+                    let uri = std::ffi::CStr::from_ptr(action.uri.uri)
+                        .to_string_lossy()
+                        .into_owned();
+                    (mapping.area, uri)
+                };
+                // Pdf coordinates have their origin at the bottom-left corner of the page
+                let mins = na::point![area.x1 / page_width, 1.0 - area.y2 / page_height];
+                let maxs = na::point![area.x2 / page_width, 1.0 - area.y1 / page_height];
+                Some(Self {
+                    uri,
+                    area: Aabb::new(mins, maxs),
+                })
+            })
+            .collect()
+    }
+
+    /// Maps the area of the link to a page that was padded to the given size, with the page content centered.
+    pub(crate) fn remap_to_padded_page(
+        &mut self,
+        page_size: na::Vector2<f64>,
+        padded_size: na::Vector2<f64>,
+    ) {
+        let offset = (padded_size - page_size) * 0.5;
+        let remap = |p: na::Point2<f64>| {
+            na::Point2::from(
+                (p.coords.component_mul(&page_size) + offset).component_div(&padded_size),
+            )
+        };
+        self.area = Aabb::new(remap(self.area.mins), remap(self.area.maxs));
+    }
+
+    /// The bounds of the link in document coordinates, for the rectangle of the image the link belongs to.
+    pub fn bounds_for_rectangle(&self, rectangle: &Rectangle) -> Aabb {
+        let size = rectangle.cuboid.half_extents * 2.0;
+        let local_mins = -rectangle.cuboid.half_extents;
+        let local_area = Aabb::new(
+            na::Point2::from(local_mins + self.area.mins.coords.component_mul(&size)),
+            na::Point2::from(local_mins + self.area.maxs.coords.component_mul(&size)),
+        );
+        rectangle.transform.transform_aabb(local_area)
+    }
+
+    /// Emit the link as a link tag on the cairo context.
+    ///
+    /// Only has an effect when drawing on a Pdf surface, where it results in a link annotation.
+    pub fn draw_link_tag_to_cairo(&self, cx: &cairo::Context, rectangle: &Rectangle) {
+        let bounds = self.bounds_for_rectangle(rectangle);
+        let uri = self.uri.replace('\\', "\\\\").replace('\'', "\\'");
+        cx.tag_begin(
+            "Link",
+            &format!(
+                "rect=[{} {} {} {}] uri='{uri}'",
+                bounds.mins[0],
+                bounds.mins[1],
+                bounds.extents()[0],
+                bounds.extents()[1]
+            ),
+        );
+        cx.tag_end("Link");
+    }
+}
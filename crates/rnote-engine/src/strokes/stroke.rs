@@ -301,6 +301,160 @@ impl Stroke {
         }
     }
 
+    /// Set all colors of the stroke to the given color.
+    ///
+    /// The alpha of the original colors is kept (multiplied with the alpha of the given color),
+    /// so that e.g. highlighter strokes stay translucent. Images are not modified.
+    ///
+    /// Returns true if the stroke was modified and needs to update its rendering.
+    pub fn set_to_color(&mut self, color: Color) -> bool {
+        let recolor = |c: Color| Color {
+            a: c.a * color.a,
+            ..color
+        };
+
+        match self {
+            Stroke::BrushStroke(brush_stroke) => {
+                if let Some(c) = brush_stroke.style.stroke_color() {
+                    brush_stroke.style.set_stroke_color(recolor(c));
+                }
+
+                if let Some(c) = brush_stroke.style.fill_color() {
+                    brush_stroke.style.set_fill_color(recolor(c));
+                }
+
+                true
+            }
+            Stroke::ShapeStroke(shape_stroke) => {
+                if let Some(c) = shape_stroke.style.stroke_color() {
+                    shape_stroke.style.set_stroke_color(recolor(c));
+                }
+
+                if let Some(c) = shape_stroke.style.fill_color() {
+                    shape_stroke.style.set_fill_color(recolor(c));
+                }
+
+                true
+            }
+            Stroke::TextStroke(text_stroke) => {
+                text_stroke.text_style.color = recolor(text_stroke.text_style.color);
+
+                true
+            }
+            Stroke::VectorImage(_) => false,
+            Stroke::BitmapImage(_) => false,
+        }
+    }
+
+    /// Widen the stroke so that it is drawn with at least the min width,
+    /// also where its width is reduced by the pressure. Text strokes and images are not modified.
+    ///
+    /// Returns true if the stroke was modified and needs to update its rendering.
+    pub fn set_min_width(&mut self, min_width: f64) -> bool {
+        match self {
+            Stroke::BrushStroke(brush_stroke) => {
+                let mut modified = false;
+                if brush_stroke.style.stroke_width() < min_width {
+                    brush_stroke.style.set_stroke_width(min_width);
+                    modified = true;
+                }
+                let pressure_curve = match &brush_stroke.style {
+                    Style::Smooth(options) => Some(options.pressure_curve),
+                    Style::Textured(options) => Some(options.pressure_curve),
+                    Style::Rough(_) => None,
+                };
+                if let Some(pressure_curve) = pressure_curve {
+                    let min_pressure = pressure_curve
+                        .min_pressure_for_width(brush_stroke.style.stroke_width(), min_width);
+                    let path = &mut brush_stroke.path;
+                    for element in std::iter::once(&mut path.start)
+                        .chain(path.segments.iter_mut().map(|seg| seg.end_mut()))
+                    {
+                        if element.pressure < min_pressure {
+                            element.pressure = min_pressure;
+                            modified = true;
+                        }
+                    }
+                }
+                if modified {
+                    brush_stroke.update_geometry();
+                }
+                modified
+            }
+            Stroke::ShapeStroke(shape_stroke) => {
+                if shape_stroke.style.stroke_width() >= min_width {
+                    return false;
+                }
+                shape_stroke.style.set_stroke_width(min_width);
+                shape_stroke.update_geometry();
+                true
+            }
+            Stroke::TextStroke(_) => false,
+            Stroke::VectorImage(_) => false,
+            Stroke::BitmapImage(_) => false,
+        }
+    }
+
+    /// Smooth the path of brush strokes that consist only of lines with a catmull-rom spline,
+    /// see [PenPath::smoothed_w_catmull_rom]. Other strokes are not modified.
+    ///
+    /// Returns true if the stroke was modified and needs to update its rendering.
+    pub fn smooth_path(&mut self) -> bool {
+        match self {
+            Stroke::BrushStroke(brush_stroke) => {
+                let Some(smoothed) = brush_stroke.path.smoothed_w_catmull_rom() else {
+                    return false;
+                };
+                brush_stroke.path = smoothed;
+                brush_stroke.update_geometry();
+                true
+            }
+            Stroke::ShapeStroke(_) => false,
+            Stroke::TextStroke(_) => false,
+            Stroke::VectorImage(_) => false,
+            Stroke::BitmapImage(_) => false,
+        }
+    }
+
+    /// The name of the stroke type, e.g. "brushstroke".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Stroke::BrushStroke(_) => "brushstroke",
+            Stroke::ShapeStroke(_) => "shapestroke",
+            Stroke::TextStroke(_) => "textstroke",
+            Stroke::VectorImage(_) => "vectorimage",
+            Stroke::BitmapImage(_) => "bitmapimage",
+        }
+    }
+
+    /// The color the stroke is drawn with, the fill color for shapes without outline. `None` for images.
+    pub fn ink_color(&self) -> Option<Color> {
+        match self {
+            Stroke::BrushStroke(brushstroke) => brushstroke.style.stroke_color(),
+            Stroke::ShapeStroke(shapestroke) => shapestroke
+                .style
+                .stroke_color()
+                .or(shapestroke.style.fill_color()),
+            Stroke::TextStroke(textstroke) => Some(textstroke.text_style.color),
+            Stroke::VectorImage(_) | Stroke::BitmapImage(_) => None,
+        }
+    }
+
+    /// Css classes describing the type and the color of the stroke, e.g. "rnote-stroke rnote-brushstroke rnote-color-1c71d8".
+    ///
+    /// Used to tag the strokes in exported Svgs.
+    pub fn css_classes(&self) -> String {
+        let stroke_type = self.type_name();
+        match self.ink_color() {
+            // the alpha channel is not part of the class
+            Some(color) => format!(
+                "rnote-stroke rnote-{stroke_type} rnote-color-{:06x}",
+                u32::from(color) >> 8
+            ),
+            None => format!("rnote-stroke rnote-{stroke_type}"),
+        }
+    }
+
     pub fn from_xoppstroke(
         stroke: xoppformat::XoppStroke,
         offset: na::Vector2<f64>,
@@ -424,7 +578,11 @@ impl Stroke {
         };
         let image = render::Image::try_from_encoded_bytes(&bytes)?;
 
-        Ok(Stroke::BitmapImage(BitmapImage { image, rectangle }))
+        Ok(Stroke::BitmapImage(BitmapImage {
+            image,
+            rectangle,
+            links: Vec::default(),
+        }))
     }
 
     pub fn into_xopp(self, current_dpi: f64) -> Option<xoppformat::XoppStrokeType> {
@@ -1,10 +1,11 @@
 // Imports
 use super::content::GeneratedContentImages;
 use super::resize::{calculate_resize_ratio, ImageSizeOption};
-use super::{Content, Stroke};
+use super::{Content, PdfLink};
 use crate::document::Format;
-use crate::engine::import::{PdfImportPageSpacing, PdfImportPrefs};
+use crate::engine::import::PdfImportPrefs;
 use crate::{render, Drawable};
+use anyhow::Context;
 use kurbo::Shape;
 use p2d::bounding_volume::Aabb;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -31,6 +32,9 @@ pub struct VectorImage {
     pub intrinsic_size: na::Vector2<f64>,
     #[serde(rename = "rectangle")]
     pub rectangle: Rectangle,
+    /// Hyperlinks when the image was imported from a Pdf page.
+    #[serde(rename = "links")]
+    pub links: Vec<PdfLink>,
 }
 
 impl Default for VectorImage {
@@ -39,6 +43,7 @@ impl Default for VectorImage {
             svg_data: String::default(),
             intrinsic_size: na::Vector2::zeros(),
             rectangle: Rectangle::default(),
+            links: Vec::default(),
         }
     }
 }
@@ -201,6 +206,7 @@ impl VectorImage {
             svg_data,
             intrinsic_size,
             rectangle,
+            links: Vec::default(),
         })
     }
 
@@ -215,42 +221,57 @@ impl VectorImage {
         let doc = poppler::Document::from_bytes(&glib::Bytes::from(bytes), password.as_deref())?;
         let page_range = page_range.unwrap_or(0..doc.n_pages() as u32);
 
-        let page_width = if pdf_import_prefs.adjust_document {
-            format.width()
-        } else {
-            format.width() * (pdf_import_prefs.page_width_perc / 100.0)
-        };
+        let page_width = pdf_import_prefs.page_width(format);
         // calculate the page zoom based on the width of the first page.
         let page_zoom = if let Some(first_page) = doc.page(0) {
             page_width / first_page.size().0
         } else {
             return Ok(vec![]);
         };
-        let x = insert_pos[0];
-        let mut y = insert_pos[1];
+        let padded_size = pdf_import_prefs.pad_to.padded_page_size(
+            page_range
+                .clone()
+                .filter_map(|page_i| doc.page(page_i as i32))
+                .map(|page| {
+                    let (width, height) = page.size();
+                    na::vector![width, height]
+                }),
+            format.width() / format.height(),
+        );
+        let mut placer = pdf_import_prefs.page_placer(insert_pos, format);
 
         let svgs = page_range
             .filter_map(|page_i| {
                 let page = doc.page(page_i as i32)?;
                 let (intrinsic_width, intrinsic_height) = page.size();
-                let width = intrinsic_width * page_zoom;
-                let height = intrinsic_height * page_zoom;
+                let intrinsic_size = na::vector![intrinsic_width, intrinsic_height];
+                let padded_size =
+                    padded_size.map_or(intrinsic_size, |size| size.sup(&intrinsic_size));
+                let padding_offset = (padded_size - intrinsic_size) * 0.5;
+                let width = padded_size[0] * page_zoom;
+                let height = padded_size[1] * page_zoom;
+                let links = if pdf_import_prefs.preserve_links {
+                    let mut links = PdfLink::extract_from_pdf_page(&page);
+                    links
+                        .iter_mut()
+                        .for_each(|link| link.remap_to_padded_page(intrinsic_size, padded_size));
+                    links
+                } else {
+                    vec![]
+                };
 
                 let res = move || -> anyhow::Result<String> {
                     let svg_stream: Vec<u8> = vec![];
 
-                    let mut svg_surface = cairo::SvgSurface::for_stream(
-                        intrinsic_width,
-                        intrinsic_height,
-                        svg_stream,
-                    )
-                    .map_err(|e| {
-                        anyhow::anyhow!(
-                            "Creating SvgSurface with dimensions ({}, {}) failed, Err: {e:?}",
-                            intrinsic_width,
-                            intrinsic_height
-                        )
-                    })?;
+                    let mut svg_surface =
+                        cairo::SvgSurface::for_stream(padded_size[0], padded_size[1], svg_stream)
+                            .map_err(|e| {
+                            anyhow::anyhow!(
+                                "Creating SvgSurface with dimensions ({}, {}) failed, Err: {e:?}",
+                                padded_size[0],
+                                padded_size[1]
+                            )
+                        })?;
 
                     // Popplers page units are in points ( equals 1/72 inch )
                     svg_surface.set_document_unit(cairo::SvgUnit::Pt);
@@ -264,8 +285,11 @@ impl VectorImage {
                         cx.set_source_rgba(1.0, 1.0, 1.0, 1.0);
                         cx.paint()?;
 
+                        // Center the page content on the padded page
+                        cx.translate(padding_offset[0], padding_offset[1]);
+
                         // Render the poppler page
-                        page.render_for_printing(&cx);
+                        pdf_import_prefs.form_fields.render_page(&page, &cx);
 
                         if pdf_import_prefs.page_borders {
                             // Draw outline around page
@@ -303,37 +327,191 @@ impl VectorImage {
                     Ok(svg_content)
                 };
 
-                let bounds = Aabb::new(na::point![x, y], na::point![x + width, y + height]);
-
-                if pdf_import_prefs.adjust_document {
-                    y += height
-                } else {
-                    y += match pdf_import_prefs.page_spacing {
-                        PdfImportPageSpacing::Continuous => {
-                            height + Stroke::IMPORT_OFFSET_DEFAULT[1] * 0.5
-                        }
-                        PdfImportPageSpacing::OnePerDocumentPage => format.height(),
-                    };
-                }
+                let pos = placer.place(na::vector![width, height]);
+                let bounds = Aabb::new(pos.into(), na::point![pos[0] + width, pos[1] + height]);
 
                 match res() {
-                    Ok(svg_data) => Some(render::Svg { svg_data, bounds }),
+                    Ok(svg_data) => Some((render::Svg { svg_data, bounds }, links)),
                     Err(e) => {
                         error!("Importing page {page_i} from pdf failed, Err: {e:?}");
                         None
                     }
                 }
             })
-            .collect::<Vec<render::Svg>>();
+            .collect::<Vec<(render::Svg, Vec<PdfLink>)>>();
 
         svgs.into_par_iter()
-            .map(|svg| {
-                Self::from_svg_str(
+            .map(|(svg, links)| {
+                let mut vectorimage = Self::from_svg_str(
                     svg.svg_data.as_str(),
                     svg.bounds.mins.coords,
                     ImageSizeOption::ImposeSize(svg.bounds.extents()),
-                )
+                )?;
+                vectorimage.links = links;
+                Ok(vectorimage)
             })
             .collect()
     }
+
+    /// The references to external files of the embedded `<image>` elements.
+    ///
+    /// Inline `data:` Urls are not included, so images without any references are self-contained.
+    pub fn external_image_refs(&self) -> anyhow::Result<Vec<String>> {
+        Ok(external_image_ref_ranges(&self.svg_data)?
+            .into_iter()
+            .map(|(_, href)| href)
+            .collect())
+    }
+
+    /// Rewrites the references to external files of the embedded `<image>` elements.
+    ///
+    /// `rewrite` returns the new reference, or `None` to keep the current one.
+    /// Returns the number of rewritten references.
+    pub fn rewrite_external_image_refs(
+        &mut self,
+        rewrite: &mut impl FnMut(&str) -> anyhow::Result<Option<String>>,
+    ) -> anyhow::Result<usize> {
+        let mut svg_data = String::with_capacity(self.svg_data.len());
+        let mut last = 0;
+        let mut rewritten = 0;
+        for (range, href) in external_image_ref_ranges(&self.svg_data)? {
+            let Some(new_href) = rewrite(&href)? else {
+                continue;
+            };
+            svg_data.push_str(&self.svg_data[last..range.start]);
+            svg_data.push_str(&escape_xml_attr_value(&new_href));
+            last = range.end;
+            rewritten += 1;
+        }
+        if rewritten > 0 {
+            svg_data.push_str(&self.svg_data[last..]);
+            self.svg_data = svg_data;
+        }
+        Ok(rewritten)
+    }
+
+    /// Encodes the image file as `data:` Url, which can replace an external reference.
+    ///
+    /// Supports Svg and the bitmap formats that can be imported.
+    pub fn image_data_url(bytes: &[u8]) -> anyhow::Result<String> {
+        let mime_type = match image::guess_format(bytes) {
+            Ok(format) => format.to_mime_type(),
+            Err(_) if usvg::Tree::from_data(bytes, &usvg::Options::default()).is_ok() => {
+                "image/svg+xml"
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Detecting the image format failed, Err: {e:?}"
+                ))
+            }
+        };
+        Ok(format!(
+            "data:{mime_type};base64,{}",
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
+        ))
+    }
+}
+
+/// The ranges of the `href` attribute values of the `<image>` elements that reference external files,
+/// together with the unescaped references.
+fn external_image_ref_ranges(svg_data: &str) -> anyhow::Result<Vec<(Range<usize>, String)>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = roxmltree::Document::parse_with_options(svg_data, options)
+        .context("Parsing the Svg data of the vector image failed.")?;
+    Ok(document
+        .descendants()
+        .filter(|n| n.has_tag_name("image"))
+        // Matches both "href" and "xlink:href"
+        .filter_map(|n| n.attributes().find(|a| a.name() == "href"))
+        .filter(|a| !a.value().trim_start().starts_with("data:"))
+        .map(|a| (a.range_value(), a.value().to_string()))
+        .collect())
+}
+
+fn escape_xml_attr_value(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectorimage_w_image_hrefs(hrefs: &[&str]) -> VectorImage {
+        let images = hrefs
+            .iter()
+            .map(|href| format!(r#"<image width="10" height="10" xlink:href="{href}"/>"#))
+            .collect::<String>();
+        VectorImage {
+            svg_data: format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10" height="10">{images}</svg>"#
+            ),
+            intrinsic_size: na::vector![10.0, 10.0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn external_image_refs_exclude_inline_data() {
+        let vectorimage = vectorimage_w_image_hrefs(&[
+            "images/a.png",
+            "data:image/png;base64,iVBORw0KGgo=",
+            "https://example.org/b.jpg",
+        ]);
+        assert_eq!(
+            vectorimage.external_image_refs().unwrap(),
+            vec![
+                String::from("images/a.png"),
+                String::from("https://example.org/b.jpg")
+            ]
+        );
+        assert!(vectorimage_w_image_hrefs(&[])
+            .external_image_refs()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rewrite_external_image_refs_keeps_other_refs() {
+        let mut vectorimage =
+            vectorimage_w_image_hrefs(&["old/a.png", "https://example.org/b.jpg"]);
+        let rewritten = vectorimage
+            .rewrite_external_image_refs(&mut |href| {
+                Ok(href
+                    .strip_prefix("old/")
+                    .map(|rest| format!("new & moved/{rest}")))
+            })
+            .unwrap();
+        assert_eq!(rewritten, 1);
+        assert!(vectorimage.svg_data.contains("new &amp; moved/a.png"));
+        assert_eq!(
+            vectorimage.external_image_refs().unwrap(),
+            vec![
+                String::from("new & moved/a.png"),
+                String::from("https://example.org/b.jpg")
+            ]
+        );
+    }
+
+    #[test]
+    fn image_data_url_detects_the_format() {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        assert!(VectorImage::image_data_url(&png)
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        assert!(VectorImage::image_data_url(svg)
+            .unwrap()
+            .starts_with("data:image/svg+xml;base64,"));
+        assert!(VectorImage::image_data_url(b"no image").is_err());
+    }
 }
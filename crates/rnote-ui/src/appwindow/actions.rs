@@ -10,7 +10,7 @@ use gtk4::{
 use p2d::bounding_volume::BoundingVolume;
 use rnote_compose::penevent::ShortcutKey;
 use rnote_compose::SplitOrder;
-use rnote_engine::engine::StrokeContent;
+use rnote_engine::engine::{ExportOptions, StrokeContent};
 use rnote_engine::ext::GraphenePointExt;
 use rnote_engine::pens::PenStyle;
 use rnote_engine::strokes::resize::{ImageSizeOption, Resize};
@@ -887,7 +887,9 @@ impl RnAppWindow {
                 let Some(canvas) = appwindow.active_tab_canvas() else {
                     return;
                 };
-                let pages_content = canvas.engine_ref().extract_pages_content(page_order);
+                let pages_content = canvas
+                    .engine_ref()
+                    .extract_pages_content(page_order, &ExportOptions::default());
                 let n_pages = pages_content.len();
 
                 appwindow.overlays().progressbar_start_pulsing();
@@ -5,7 +5,9 @@ use futures::channel::oneshot;
 use futures::AsyncWriteExt;
 use gtk4::{gio, prelude::*};
 use rnote_compose::ext::Vector2Ext;
-use rnote_engine::engine::export::{DocExportPrefs, DocPagesExportPrefs, SelectionExportPrefs};
+use rnote_engine::engine::export::{
+    DocExportPrefs, DocPagesExportPrefs, ExportOptions, SelectionExportPrefs,
+};
 use rnote_engine::engine::{EngineSnapshot, StrokeContent};
 use rnote_engine::strokes::resize::ImageSizeOption;
 use rnote_engine::strokes::Stroke;
@@ -278,7 +280,9 @@ impl RnCanvas {
         title: String,
         export_prefs_override: Option<DocExportPrefs>,
     ) -> anyhow::Result<()> {
-        let export_bytes = self.engine_ref().export_doc(title, export_prefs_override);
+        let export_bytes =
+            self.engine_ref()
+                .export_doc(title, export_prefs_override, &ExportOptions::default());
 
         crate::utils::create_replace_file_future(export_bytes.await??, file).await?;
 
@@ -307,7 +311,9 @@ impl RnCanvas {
             export_prefs_override.unwrap_or(self.engine_ref().export_prefs.doc_pages_export_prefs);
         let file_ext = export_prefs.export_format.file_ext();
 
-        let export_bytes_recv = self.engine_ref().export_doc_pages(export_prefs_override);
+        let export_bytes_recv = self
+            .engine_ref()
+            .export_doc_pages(export_prefs_override, &ExportOptions::default());
         let export_bytes = export_bytes_recv.await??;
 
         for (i, page_bytes) in export_bytes.into_iter().enumerate() {
@@ -332,7 +338,9 @@ impl RnCanvas {
         file: &gio::File,
         export_prefs_override: Option<SelectionExportPrefs>,
     ) -> anyhow::Result<()> {
-        let export_bytes = self.engine_ref().export_selection(export_prefs_override);
+        let export_bytes = self
+            .engine_ref()
+            .export_selection(export_prefs_override, &ExportOptions::default());
 
         if let Some(export_bytes) = export_bytes.await?? {
             crate::utils::create_replace_file_future(export_bytes, file).await?;
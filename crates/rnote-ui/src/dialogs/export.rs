@@ -11,7 +11,7 @@ use num_traits::ToPrimitive;
 use rnote_compose::SplitOrder;
 use rnote_engine::document::Layout;
 use rnote_engine::engine::export::{
-    DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs,
+    DocExportFormat, DocExportPrefs, DocPagesExportFormat, DocPagesExportPrefs, ExportOptions,
     SelectionExportFormat, SelectionExportPrefs,
 };
 use std::cell::RefCell;
@@ -122,11 +122,10 @@ pub(crate) async fn dialog_export_doc_w_prefs(appwindow: &RnAppWindow, canvas: &
     preview.set_draw_background(initial_doc_export_prefs.with_background);
     preview.set_draw_pattern(initial_doc_export_prefs.with_pattern);
     preview.set_optimize_printing(initial_doc_export_prefs.optimize_printing);
-    preview.set_contents(
-        canvas
-            .engine_ref()
-            .extract_pages_content(initial_doc_export_prefs.page_order),
-    );
+    preview.set_contents(canvas.engine_ref().extract_pages_content(
+        initial_doc_export_prefs.page_order,
+        &ExportOptions::default(),
+    ));
     export_format_row.set_selected(initial_doc_export_prefs.export_format.to_u32().unwrap());
     page_order_row.set_selected(initial_doc_export_prefs.page_order.to_u32().unwrap());
     export_file_label.set_label(&gettext("- no file selected -"));
@@ -254,7 +253,11 @@ pub(crate) async fn dialog_export_doc_w_prefs(appwindow: &RnAppWindow, canvas: &
         move |row| {
             let page_order = SplitOrder::try_from(row.selected()).unwrap();
             canvas.engine_mut().export_prefs.doc_export_prefs.page_order = page_order;
-            preview.set_contents(canvas.engine_ref().extract_pages_content(page_order));
+            preview.set_contents(
+                canvas
+                    .engine_ref()
+                    .extract_pages_content(page_order, &ExportOptions::default()),
+            );
         }
     ));
 
@@ -439,11 +442,10 @@ pub(crate) async fn dialog_export_doc_pages_w_prefs(appwindow: &RnAppWindow, can
     preview.set_draw_background(initial_doc_pages_export_prefs.with_background);
     preview.set_draw_pattern(initial_doc_pages_export_prefs.with_pattern);
     preview.set_optimize_printing(initial_doc_pages_export_prefs.optimize_printing);
-    preview.set_contents(
-        canvas
-            .engine_ref()
-            .extract_pages_content(initial_doc_pages_export_prefs.page_order),
-    );
+    preview.set_contents(canvas.engine_ref().extract_pages_content(
+        initial_doc_pages_export_prefs.page_order,
+        &ExportOptions::default(),
+    ));
     export_format_row.set_selected(
         initial_doc_pages_export_prefs
             .export_format
@@ -622,7 +624,11 @@ pub(crate) async fn dialog_export_doc_pages_w_prefs(appwindow: &RnAppWindow, can
                 .export_prefs
                 .doc_pages_export_prefs
                 .page_order = page_order;
-            preview.set_contents(canvas.engine_ref().extract_pages_content(page_order));
+            preview.set_contents(
+                canvas
+                    .engine_ref()
+                    .extract_pages_content(page_order, &ExportOptions::default()),
+            );
         }
     ));
 
@@ -837,7 +843,7 @@ pub(crate) async fn dialog_export_selection_w_prefs(appwindow: &RnAppWindow, can
     preview.set_contents(
         canvas
             .engine_ref()
-            .extract_selection_content()
+            .extract_selection_content(&ExportOptions::default())
             .into_iter()
             .collect(),
     );